@@ -0,0 +1,132 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::ops::Range;
+
+///# Absorbing Sides
+/// Chain ends that get an absorbing layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbsorbingSides {
+    #[default]
+    Both,
+    // Only the first cells, e.g. opposite to an antenna at the end
+    Start,
+    // Only the last cells, e.g. with an antenna at the start
+    End,
+}
+
+///# Absorbing Boundaries
+/// Layers of `width` cells at the chain ends in which the damping rises
+/// quadratically from the material damping at the inner edge to
+/// `max_damping` in the outermost cell. A gradual rise absorbs the spin
+/// waves that run into the layer instead of reflecting them from a step
+/// in the damping, so propagation studies only see the outgoing waves.
+/// The layer should span a few wavelengths.
+///
+/// ```toml
+/// [absorbing_boundaries]
+/// width = 200
+/// max_damping = 1.0
+/// sides = "end"
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AbsorbingBoundaries {
+    pub width: usize,
+    pub max_damping: f64,
+    #[serde(default)]
+    pub sides: AbsorbingSides,
+}
+
+impl AbsorbingBoundaries {
+    ///# Damping in the Layer
+    /// Damping of a cell `depth` cells from the end of the chain, 0 being
+    /// the outermost cell, for the material damping `damping`.
+    pub fn damping(&self, depth: usize, damping: f64) -> f64 {
+        if depth >= self.width {
+            return damping;
+        }
+        let ramp = (self.width - depth) as f64 / self.width as f64;
+        damping + (self.max_damping - damping).max(0.0) * ramp * ramp
+    }
+
+    ///# Apply Absorbing Boundaries
+    /// Raise the damping of the magnetic cells at the ends of the range.
+    pub fn apply(
+        &self,
+        system: &mut MicromagneticSystem,
+        cells: Range<usize>,
+    ) -> Result<(), Box<dyn Error>> {
+        if cells.end > system.size() {
+            return Err(format!(
+                "Absorbing boundaries over {}..{} are outside the {} cells",
+                cells.start,
+                cells.end,
+                system.size()
+            )
+            .into());
+        }
+        if self.max_damping < 0.0 {
+            return Err("The damping of the absorbing boundaries must not be negative".into());
+        }
+        let (start, end) = match self.sides {
+            AbsorbingSides::Both => (true, true),
+            AbsorbingSides::Start => (true, false),
+            AbsorbingSides::End => (false, true),
+        };
+        let materials = system.get_materials();
+        for cell in cells.clone() {
+            let mut material = materials[cell];
+            if material.is_vacuum() {
+                continue;
+            }
+            let from_start = cell - cells.start;
+            let from_end = cells.end - 1 - cell;
+            let depth = match (start, end) {
+                (true, true) => from_start.min(from_end),
+                (true, false) => from_start,
+                _ => from_end,
+            };
+            material.damping = self.damping(depth, material.damping);
+            system.set_material(cell, material);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+
+    #[test]
+    /// Test the damping ramp at the chain ends
+    fn test_absorbing_boundaries() {
+        let mut system = MicromagneticSystem::new(12);
+        let damping = Material::default().damping;
+        let boundaries = AbsorbingBoundaries {
+            width: 4,
+            max_damping: 1.0,
+            sides: AbsorbingSides::Both,
+        };
+        boundaries.apply(&mut system, 0..12).unwrap();
+        let dampings: Vec<f64> = system.get_materials().iter().map(|m| m.damping).collect();
+        assert_eq!(dampings[0], 1.0);
+        assert_eq!(dampings[11], 1.0);
+        assert!((dampings[3] - (damping + (1.0 - damping) / 16.0)).abs() < 1e-12);
+        assert!(dampings[..4].windows(2).all(|pair| pair[0] > pair[1]));
+        assert!(dampings[4..8].iter().all(|&d| d == damping));
+        assert_eq!(dampings[10], dampings[1]);
+
+        let mut system = MicromagneticSystem::new(12);
+        let end = AbsorbingBoundaries {
+            sides: AbsorbingSides::End,
+            ..boundaries
+        };
+        end.apply(&mut system, 0..12).unwrap();
+        assert_eq!(system.get_materials()[0].damping, damping);
+        assert_eq!(system.get_materials()[11].damping, 1.0);
+        assert!(end.apply(&mut system, 0..13).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+///# Anisotropy Profile
+/// Anisotropy constant K in J/m^3 varying along the chain, as used for
+/// graded exchange coupled composite media. Positions are measured from
+/// the first cell of the graded range.
+///
+/// ```toml
+/// [anisotropy_profile]
+/// type = "exponential"
+/// start = 2.0e6
+/// decay_length = 5.0e-9
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum AnisotropyProfile {
+    // K changes linearly from start at the first cell to end at the last cell
+    Linear { start: f64, end: f64 },
+    // K = start exp(-x / decay_length) with x in m
+    Exponential { start: f64, decay_length: f64 },
+    // Text file with one K per cell, or "position K" rows in m and J/m^3
+    // that are interpolated linearly
+    File { path: PathBuf },
+}
+
+impl AnisotropyProfile {
+    ///# Profile Values
+    /// Anisotropy constant of each of `cells` consecutive cells with the
+    /// edge length `cell_size`.
+    pub fn values(&self, cells: usize, cell_size: f64) -> Result<Vec<f64>, Box<dyn Error>> {
+        match self {
+            AnisotropyProfile::Linear { start, end } => Ok((0..cells)
+                .map(|i| {
+                    let fraction = if cells > 1 {
+                        i as f64 / (cells - 1) as f64
+                    } else {
+                        0.0
+                    };
+                    start + (end - start) * fraction
+                })
+                .collect()),
+            AnisotropyProfile::Exponential {
+                start,
+                decay_length,
+            } => {
+                if *decay_length <= 0.0 {
+                    return Err(
+                        "The decay length of the anisotropy profile must be positive".into(),
+                    );
+                }
+                Ok((0..cells)
+                    .map(|i| start * (-(i as f64 * cell_size) / decay_length).exp())
+                    .collect())
+            }
+            AnisotropyProfile::File { path } => {
+                let text = fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                parse_profile(&text, cells, cell_size)
+            }
+        }
+    }
+
+    ///# Apply Profile
+    /// Set the anisotropy constant of the cells in the range, vacuum cells are skipped.
+    pub fn apply(
+        &self,
+        system: &mut MicromagneticSystem,
+        cells: Range<usize>,
+    ) -> Result<(), Box<dyn Error>> {
+        if cells.end > system.size() {
+            return Err(format!(
+                "Anisotropy profile over {}..{} is outside the {} cells",
+                cells.start,
+                cells.end,
+                system.size()
+            )
+            .into());
+        }
+        let values = self.values(cells.len(), system.get_cell_size())?;
+        let materials = system.get_materials();
+        for (cell, anisotropy_constant) in cells.zip(values) {
+            if system.is_vacuum(cell) {
+                continue;
+            }
+            let mut material = materials[cell];
+            material.anisotropy_constant = anisotropy_constant;
+            system.set_material(cell, material);
+        }
+        Ok(())
+    }
+
+    ///# Resolve Path
+    /// Make a relative profile file relative to the given directory.
+    pub fn resolve_path(&mut self, directory: &Path) {
+        if let AnisotropyProfile::File { path } = self {
+            if path.is_relative() {
+                *path = directory.join(&*path);
+            }
+        }
+    }
+}
+
+///# Parse Profile File
+/// Blank lines and lines starting with `#` are ignored, columns are
+/// separated by whitespace or commas, positions are interpolated at the
+/// cells with the edge length `cell_size`. Shared by the profiles of every
+/// material parameter.
+pub(crate) fn parse_profile(
+    text: &str,
+    cells: usize,
+    cell_size: f64,
+) -> Result<Vec<f64>, Box<dyn Error>> {
+    let mut rows: Vec<Vec<f64>> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let row = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|column| !column.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| format!("Invalid profile line {}: {}", number + 1, e))?;
+        rows.push(row);
+    }
+
+    if rows.iter().all(|row| row.len() == 1) {
+        // One value per cell
+        if rows.len() != cells {
+            return Err(format!("Profile has {} values for {} cells", rows.len(), cells).into());
+        }
+        return Ok(rows.into_iter().map(|row| row[0]).collect());
+    }
+    if rows.iter().any(|row| row.len() != 2) {
+        return Err("Profile rows must have one or two columns".into());
+    }
+    let mut points: Vec<(f64, f64)> = rows.into_iter().map(|row| (row[0], row[1])).collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // Linear interpolation, constant beyond the first and last point
+    Ok((0..cells)
+        .map(|i| {
+            let x = i as f64 * cell_size;
+            let upper = points.partition_point(|point| point.0 <= x);
+            if upper == 0 {
+                return points[0].1;
+            }
+            if upper == points.len() {
+                return points[points.len() - 1].1;
+            }
+            let (x0, k0) = points[upper - 1];
+            let (x1, k1) = points[upper];
+            k0 + (k1 - k0) * (x - x0) / (x1 - x0)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the linear and exponential profiles
+    fn test_analytic_profiles() {
+        let linear = AnisotropyProfile::Linear {
+            start: 1.0e6,
+            end: 0.0,
+        };
+        assert_eq!(
+            linear.values(5, 1e-9).unwrap(),
+            vec![1.0e6, 7.5e5, 5.0e5, 2.5e5, 0.0]
+        );
+
+        let exponential = AnisotropyProfile::Exponential {
+            start: 1.0e6,
+            decay_length: 2.0e-9,
+        };
+        let values = exponential.values(3, 1e-9).unwrap();
+        assert!((values[2] - 1.0e6 * (-1.0f64).exp()).abs() < 1e-6);
+    }
+
+    #[test]
+    /// Test per-cell and interpolated profile files
+    fn test_profile_file() {
+        assert_eq!(
+            parse_profile("# K\n1\n2\n\n3\n", 3, 1e-9).unwrap(),
+            vec![1.0, 2.0, 3.0]
+        );
+        assert!(parse_profile("1\n2\n", 3, 1e-9).is_err());
+
+        let values = parse_profile("0, 100\n2e-9, 300\n", 4, 1e-9).unwrap();
+        assert_eq!(values, vec![100.0, 200.0, 300.0, 300.0]);
+        let values = parse_profile("0, 100\n2e-9, 300\n", 3, 0.5e-9).unwrap();
+        assert_eq!(values, vec![100.0, 150.0, 200.0]);
+        assert!(parse_profile("0 1 2\n", 3, 1e-9).is_err());
+    }
+
+    #[test]
+    /// Test applying a profile to part of a system
+    fn test_apply() {
+        let mut system = MicromagneticSystem::new(6);
+        system.set_saturation_magnetization(3, 0.0);
+        let profile = AnisotropyProfile::Linear {
+            start: 3.0e5,
+            end: 0.0,
+        };
+        profile.apply(&mut system, 2..6).unwrap();
+        let materials = system.get_materials();
+        assert_eq!(
+            materials[0].anisotropy_constant,
+            crate::UNIAXIAL_ANISOTROPY_CONSTANT
+        );
+        assert_eq!(materials[2].anisotropy_constant, 3.0e5);
+        assert_eq!(
+            materials[3].anisotropy_constant,
+            crate::UNIAXIAL_ANISOTROPY_CONSTANT
+        );
+        assert_eq!(materials[4].anisotropy_constant, 1.0e5);
+        assert!(profile.apply(&mut system, 2..7).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+use crate::EASY_AXIS;
+use ndarray::Array1;
+
+// Weak fields near the switching field relax slowly, so a relaxation is
+// continued for up to this many iteration limits before giving up
+const MAX_RELAXATION_ROUNDS: usize = 50;
+
+///# Astroid Sweep
+/// Switching field measurement over field angles in the plane spanned by
+/// the easy axis and an in-plane direction perpendicular to it. The angle
+/// is measured from the reversed easy axis, in degrees.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AstroidSweep {
+    pub easy_axis: [f64; 3],
+    pub in_plane_axis: [f64; 3],
+    pub angles: Vec<f64>,
+    // Largest field magnitude tried, in T
+    pub max_field: f64,
+    // Bisection stops when the field interval is smaller than this, in T
+    pub resolution: f64,
+}
+
+///# Astroid Point
+/// Switching field at one angle, `None` when the system does not switch up
+/// to the maximum field. The components are along the easy axis and the
+/// in-plane axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AstroidPoint {
+    pub angle: f64,
+    pub switching_field: Option<f64>,
+    pub parallel_field: Option<f64>,
+    pub perpendicular_field: Option<f64>,
+}
+
+impl Default for AstroidSweep {
+    /// Angles from 5 to 85 degrees, suited to a macrospin of the default material
+    fn default() -> Self {
+        Self {
+            easy_axis: EASY_AXIS,
+            in_plane_axis: [0.0, 1.0, 0.0],
+            angles: (1..18).map(|k| 5.0 * k as f64).collect(),
+            max_field: 0.1,
+            resolution: 1e-4,
+        }
+    }
+}
+
+impl AstroidSweep {
+    ///# Run Sweep
+    /// For every angle the system is saturated along the easy axis and
+    /// relaxed in the reversal field. The switching field, where the average
+    /// magnetization turns against the easy axis, is found by bisection.
+    pub fn run(&self, system: &MicromagneticSystem) -> Vec<AstroidPoint> {
+        self.angles
+            .iter()
+            .map(|&angle| {
+                let switching_field = self.switching_field(system, angle);
+                let radians = angle.to_radians();
+                AstroidPoint {
+                    angle,
+                    switching_field,
+                    parallel_field: switching_field.map(|b| -b * radians.cos()),
+                    perpendicular_field: switching_field.map(|b| b * radians.sin()),
+                }
+            })
+            .collect()
+    }
+
+    ///# Switching Field
+    /// Bisection between zero and the maximum field, assuming the
+    /// switched state persists at larger fields.
+    pub fn switching_field(&self, system: &MicromagneticSystem, angle: f64) -> Option<f64> {
+        if !self.switches(system, angle, self.max_field) {
+            return None;
+        }
+        let mut lower = 0.0;
+        let mut upper = self.max_field;
+        while upper - lower > self.resolution {
+            let middle = 0.5 * (lower + upper);
+            if self.switches(system, angle, middle) {
+                upper = middle;
+            } else {
+                lower = middle;
+            }
+        }
+        Some(upper)
+    }
+
+    ///# Switches
+    /// Relax the saturated system in the reversal field of the given
+    /// magnitude and report whether it ends up against the easy axis.
+    fn switches(&self, system: &MicromagneticSystem, angle: f64, field: f64) -> bool {
+        let mut system = system.clone();
+        for cell in 0..system.size() {
+            system.set_magnetization(cell, Array1::from_vec(self.easy_axis.to_vec()));
+        }
+        let radians = angle.to_radians();
+        let mut applied_field = [0.0; 3];
+        for k in 0..3 {
+            applied_field[k] = field
+                * (-self.easy_axis[k] * radians.cos() + self.in_plane_axis[k] * radians.sin());
+        }
+        system.set_applied_field(applied_field);
+        for _ in 0..MAX_RELAXATION_ROUNDS {
+            let outcome = system.minimize_energy_until(|_, _| true);
+            if !matches!(outcome, MinimizationOutcome::NotConverged { .. }) {
+                break;
+            }
+        }
+
+        let m = system.average_magnetization();
+        m[0] * self.easy_axis[0] + m[1] * self.easy_axis[1] + m[2] * self.easy_axis[2] < 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SATURATION_MAGNETIZATION, UNIAXIAL_ANISOTROPY_CONSTANT};
+
+    #[test]
+    /// Test a macrospin against the Stoner-Wohlfarth astroid
+    fn test_stoner_wohlfarth_astroid() {
+        let sweep = AstroidSweep {
+            angles: vec![20.0, 45.0, 70.0],
+            ..AstroidSweep::default()
+        };
+        let points = sweep.run(&MicromagneticSystem::new(1));
+
+        // B_sw = B_K / (cos^(2/3) + sin^(2/3))^(3/2) with B_K = 2 K / Ms
+        let anisotropy_field = 2.0 * UNIAXIAL_ANISOTROPY_CONSTANT / SATURATION_MAGNETIZATION;
+        for point in points {
+            let radians = point.angle.to_radians();
+            let expected = anisotropy_field
+                / (radians.cos().powf(2.0 / 3.0) + radians.sin().powf(2.0 / 3.0)).powf(1.5);
+            let switching_field = point.switching_field.unwrap();
+            assert!((switching_field - expected).abs() < 0.05 * expected);
+            let parallel = point.parallel_field.unwrap();
+            let perpendicular = point.perpendicular_field.unwrap();
+            assert!((parallel.hypot(perpendicular) - switching_field).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    /// Test that a too small maximum field reports no switching
+    fn test_no_switching() {
+        let sweep = AstroidSweep {
+            angles: vec![45.0],
+            max_field: 0.01,
+            ..AstroidSweep::default()
+        };
+        let points = sweep.run(&MicromagneticSystem::new(1));
+        assert_eq!(points[0].switching_field, None);
+    }
+}
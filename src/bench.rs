@@ -0,0 +1,294 @@
+use crate::config::SimulationConfig;
+use crate::dipolar::prism_demagnetization_factors;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::parallel::with_threads;
+use crate::{PERMEABILITY_OF_FREE_SPACE, SATURATION_MAGNETIZATION, UNIAXIAL_ANISOTROPY_CONSTANT};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Field in T of the benchmark cases, far above the anisotropy field 2K/Ms
+const BENCHMARK_FIELD: f64 = 1.0;
+
+///# Benchmark Result
+/// Timings of one grid size and thread count combination
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    // Number of cells in the grid
+    pub size: usize,
+    // Number of worker threads
+    pub threads: usize,
+    // Number of field evaluations and relaxation steps that were timed
+    pub steps: usize,
+    // Average time of one effective field evaluation
+    pub field_time: Duration,
+    // Total time of the relaxation steps
+    pub relaxation_time: Duration,
+}
+
+///# Scaling Benchmark
+/// Times the effective field evaluation and a fixed number of
+/// relaxation steps for every combination of grid size and thread count.
+pub fn run_scaling_benchmark(
+    sizes: &[usize],
+    threads: &[usize],
+    steps: usize,
+) -> Result<Vec<BenchmarkResult>, Box<dyn Error>> {
+    let mut results = Vec::new();
+    for &size in sizes {
+        for &thread_count in threads {
+            let result = with_threads(thread_count, || {
+                let mut system = MicromagneticSystem::new(size);
+
+                // Average over several field evaluations
+                let start = Instant::now();
+                for _ in 0..steps {
+                    std::hint::black_box(system.compute_effective_field());
+                }
+                let field_time = start.elapsed() / steps.max(1) as u32;
+
+                let start = Instant::now();
+                for _ in 0..steps {
+                    system.relaxation_step();
+                }
+                let relaxation_time = start.elapsed();
+
+                BenchmarkResult {
+                    size,
+                    threads: thread_count,
+                    steps,
+                    field_time,
+                    relaxation_time,
+                }
+            })?;
+            results.push(result);
+        }
+    }
+    Ok(results)
+}
+
+///# Print Scaling Table
+/// The speedup is relative to the first thread count of the same grid size.
+pub fn print_scaling_table(results: &[BenchmarkResult]) {
+    crate::console!(
+        "{:>10} {:>8} {:>8} {:>14} {:>16} {:>8}",
+        "cells",
+        "threads",
+        "steps",
+        "field (ms)",
+        "relaxation (ms)",
+        "speedup"
+    );
+    let mut reference: Option<(usize, Duration)> = None;
+    for result in results {
+        let baseline = match reference {
+            Some((size, time)) if size == result.size => time,
+            _ => {
+                reference = Some((result.size, result.relaxation_time));
+                result.relaxation_time
+            }
+        };
+        crate::console!(
+            "{:>10} {:>8} {:>8} {:>14.4} {:>16.3} {:>8.2}",
+            result.size,
+            result.threads,
+            result.steps,
+            result.field_time.as_secs_f64() * 1e3,
+            result.relaxation_time.as_secs_f64() * 1e3,
+            baseline.as_secs_f64() / result.relaxation_time.as_secs_f64().max(f64::MIN_POSITIVE),
+        );
+    }
+}
+
+///# Benchmark Case
+/// One generated configuration. The analytic cases relax into a uniform
+/// state whose total energy in J is known in closed form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkCase {
+    // File name of the configuration without the extension
+    pub name: String,
+    // Energy terms in addition to exchange and Zeeman
+    pub terms: &'static str,
+    pub config: SimulationConfig,
+    pub reference_energy: Option<f64>,
+}
+
+///# Benchmark Cases
+/// A family of configurations for every chain length, each tier enabling
+/// more energy terms than the one before: uniaxial anisotropy with the
+/// field along the easy axis, a field saturating the chain along the hard
+/// axis, the shape anisotropy of the prism of the chain, and the exact
+/// dipolar field, which has no closed-form reference. The cells are
+/// `cell_size` in m long.
+pub fn benchmark_cases(sizes: &[usize], cell_size: f64) -> Vec<BenchmarkCase> {
+    let cell_volume = cell_size.powi(3);
+    let mut cases = Vec::new();
+    for &size in sizes {
+        let cells = size as f64;
+        let base = SimulationConfig {
+            number_of_cells: size,
+            cell_size,
+            applied_field: [BENCHMARK_FIELD, 0.0, 0.0],
+            ..SimulationConfig::default()
+        };
+        // Uniform state along the easy axis x
+        let easy_axis_energy = -(UNIAXIAL_ANISOTROPY_CONSTANT
+            + SATURATION_MAGNETIZATION * BENCHMARK_FIELD)
+            * cell_volume
+            * cells;
+        let dimensions = [cells * cell_size, cell_size, cell_size];
+        let shape_energy = 0.5
+            * PERMEABILITY_OF_FREE_SPACE
+            * SATURATION_MAGNETIZATION
+            * SATURATION_MAGNETIZATION
+            * prism_demagnetization_factors(dimensions)[0]
+            * cell_volume
+            * cells;
+        let mut case = |tier: &str, terms, config, reference_energy| {
+            cases.push(BenchmarkCase {
+                name: format!("{}-{}", tier, size),
+                terms,
+                config,
+                reference_energy,
+            })
+        };
+        case(
+            "uniaxial",
+            "anisotropy",
+            base.clone(),
+            Some(easy_axis_energy),
+        );
+        case(
+            "hard-axis",
+            "anisotropy",
+            SimulationConfig {
+                applied_field: [0.0, 0.0, BENCHMARK_FIELD],
+                ..base.clone()
+            },
+            Some(-SATURATION_MAGNETIZATION * BENCHMARK_FIELD * cell_volume * cells),
+        );
+        case(
+            "shape",
+            "anisotropy, shape anisotropy",
+            SimulationConfig {
+                sample_dimensions: Some(dimensions),
+                ..base.clone()
+            },
+            Some(easy_axis_energy + shape_energy),
+        );
+        case(
+            "dipolar",
+            "anisotropy, dipolar",
+            SimulationConfig {
+                dipolar_interaction: true,
+                ..base
+            },
+            None,
+        );
+    }
+    cases
+}
+
+///# Write Benchmark Cases
+/// Write every case as `<name>.toml` into the directory, with the reference
+/// energy in a comment on top, and all references into `references.json`.
+pub fn write_benchmark_cases(
+    cases: &[BenchmarkCase],
+    directory: &Path,
+) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(directory)?;
+    let mut references = Vec::new();
+    for case in cases {
+        let file = format!("{}.toml", case.name);
+        let mut text = format!(
+            "## Benchmark {}: exchange, Zeeman, {}\n",
+            case.name, case.terms
+        );
+        if let Some(energy) = case.reference_energy {
+            text.push_str(&format!(
+                "## Total energy of the relaxed state: {:e} J\n",
+                energy
+            ));
+        }
+        text.push('\n');
+        text.push_str(&toml::to_string(&case.config)?);
+        fs::write(directory.join(&file), text)?;
+        references.push(serde_json::json!({
+            "config": file,
+            "cells": case.config.number_of_cells,
+            "terms": case.terms,
+            "reference_energy": case.reference_energy,
+        }));
+    }
+    fs::write(
+        directory.join("references.json"),
+        serde_json::to_string_pretty(&references)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that every size and thread count combination is timed
+    fn test_scaling_benchmark() {
+        let results = run_scaling_benchmark(&[10, 20], &[1, 2], 3).unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[1].size, 10);
+        assert_eq!(results[1].threads, 2);
+        assert!(results.iter().all(|r| r.steps == 3));
+        print_scaling_table(&results);
+    }
+
+    #[test]
+    /// Test that the analytic cases relax into their reference energy
+    fn test_benchmark_cases() {
+        let cases = benchmark_cases(&[8, 16], 2.0e-9);
+        assert_eq!(cases.len(), 8);
+        assert_eq!(cases[4].name, "uniaxial-16");
+        assert!(cases[3].config.dipolar_interaction && cases[3].reference_energy.is_none());
+
+        for case in cases.iter().filter(|case| case.config.number_of_cells == 8) {
+            let mut system = case.config.build_system().unwrap();
+            let field = case.config.applied_field;
+            for cell in 0..8 {
+                system.set_magnetization(cell, ndarray::array![0.8, 0.0, 0.6]);
+            }
+            system.minimize_energy_until(|_, _| true);
+            let energy = system.compute_energies().total();
+            if let Some(reference) = case.reference_energy {
+                assert!(
+                    (energy - reference).abs() < 1e-6 * reference.abs(),
+                    "{}: {} != {}",
+                    case.name,
+                    energy,
+                    reference
+                );
+            }
+            let m = system.average_magnetization();
+            assert!(
+                (0..3).all(|k| (m[k] - field[k]).abs() < 1e-3),
+                "{}",
+                case.name
+            );
+        }
+
+        let directory = std::env::temp_dir().join("energy_relaxation_gen_bench_test");
+        let _ = fs::remove_dir_all(&directory);
+        write_benchmark_cases(&cases, &directory).unwrap();
+        let text = fs::read_to_string(directory.join("shape-8.toml")).unwrap();
+        assert!(text.contains("Total energy of the relaxed state"));
+        assert_eq!(SimulationConfig::from_toml(&text).unwrap(), cases[2].config);
+        let references: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(directory.join("references.json")).unwrap())
+                .unwrap();
+        let reference = cases[2].reference_energy.unwrap();
+        let written = references[2]["reference_energy"].as_f64().unwrap();
+        assert!((written - reference).abs() < 1e-12 * reference.abs());
+        assert!(references[3]["reference_energy"].is_null());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,243 @@
+use crate::config::SimulationConfig;
+use crate::magnetic_moments::{Energies, MicromagneticSystem};
+use crate::ovf::read_ovf;
+use calamine::{open_workbook, Data, Reader, Xlsx};
+use ndarray::Array1;
+use std::error::Error;
+use std::path::Path;
+
+///# Run Result
+/// Final state of one run with its energies and, when known, the
+/// configuration it was computed with.
+#[derive(Debug, Clone)]
+pub struct RunResult {
+    pub name: String,
+    pub magnetizations: Vec<Array1<f64>>,
+    // Edge length in m of the cells
+    pub cell_size: f64,
+    pub energies: Energies,
+    pub average_magnetization: [f64; 3],
+    pub config: Option<SimulationConfig>,
+}
+
+impl RunResult {
+    ///# Result of a System
+    pub fn from_system(
+        name: &str,
+        system: &MicromagneticSystem,
+        config: Option<SimulationConfig>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            magnetizations: system.get_magnetizations(),
+            cell_size: system.get_cell_size(),
+            energies: system.compute_energies(),
+            average_magnetization: system.average_magnetization(),
+            config,
+        }
+    }
+
+    ///# Load Result
+    /// Read the state from an OVF file or from the vectors.xlsx written by
+    /// `relax`. The energies are evaluated with the system of the config
+    /// file, or with the default material when none is given.
+    pub fn load(state: &Path, config: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let magnetizations = if state.extension().is_some_and(|e| e == "xlsx") {
+            read_vectors_xlsx(state)?
+        } else {
+            read_ovf(state)?
+                .vectors
+                .iter()
+                .map(|v| Array1::from_vec(v.to_vec()))
+                .collect()
+        };
+        let config = config.map(SimulationConfig::load).transpose()?;
+        let system = match &config {
+            Some(config) => {
+                let mut system = config.build_system()?;
+                if system.size() != magnetizations.len() {
+                    return Err(format!(
+                        "{} has {} cells, the configuration {}",
+                        state.display(),
+                        magnetizations.len(),
+                        system.size()
+                    )
+                    .into());
+                }
+                for (i, m) in magnetizations.iter().enumerate() {
+                    system.set_magnetization(i, m.clone());
+                }
+                system
+            }
+            None => MicromagneticSystem::from_magnetizations(magnetizations),
+        };
+        Ok(Self::from_system(
+            &state.display().to_string(),
+            &system,
+            config,
+        ))
+    }
+}
+
+///# Read Vectors Workbook
+/// Magnetization vectors from the X, Y, Z columns of the first sheet,
+/// below the header row.
+pub fn read_vectors_xlsx(path: &Path) -> Result<Vec<Array1<f64>>, Box<dyn Error>> {
+    let mut workbook: Xlsx<_> = open_workbook(path)?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| format!("{} has no worksheet", path.display()))??;
+    let number = |cell: &Data| match cell {
+        Data::Float(value) => Some(*value),
+        Data::Int(value) => Some(*value as f64),
+        _ => None,
+    };
+    range
+        .rows()
+        .skip(1)
+        .enumerate()
+        .map(|(i, row)| {
+            let vector: Option<Vec<f64>> = row.iter().take(3).map(number).collect();
+            match vector {
+                Some(vector) if vector.len() == 3 => Ok(Array1::from_vec(vector)),
+                _ => Err(format!("row {} of {} is not a vector", i + 2, path.display()).into()),
+            }
+        })
+        .collect()
+}
+
+///# Parameter Difference
+/// Configuration option with a different value in at least one run,
+/// `None` for runs without a configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterDifference {
+    // Dotted path of the option, e.g. "edge_roughness.amplitude"
+    pub parameter: String,
+    pub values: Vec<Option<String>>,
+}
+
+///# Parameter Differences
+/// Options whose values differ between the runs with a configuration, in
+/// the order of the configuration. An option missing in one of them, like
+/// an unset table, counts as a different value.
+pub fn parameter_differences(runs: &[RunResult]) -> Vec<ParameterDifference> {
+    let flattened: Vec<Vec<(String, String)>> = runs
+        .iter()
+        .map(|run| {
+            let mut options = Vec::new();
+            if let Some(config) = &run.config {
+                let table = toml::Table::try_from(config).expect("configurations serialize");
+                flatten("", &toml::Value::Table(table), &mut options);
+            }
+            options
+        })
+        .collect();
+    let mut parameters: Vec<&String> = Vec::new();
+    for (parameter, _) in flattened.iter().flatten() {
+        if !parameters.contains(&parameter) {
+            parameters.push(parameter);
+        }
+    }
+    parameters
+        .into_iter()
+        .filter_map(|parameter| {
+            let values: Vec<Option<String>> = flattened
+                .iter()
+                .map(|options| {
+                    options
+                        .iter()
+                        .find(|(key, _)| key == parameter)
+                        .map(|(_, value)| value.clone())
+                })
+                .collect();
+            let mut known = values
+                .iter()
+                .zip(runs)
+                .filter(|(_, run)| run.config.is_some())
+                .map(|(value, _)| value);
+            let first = known.next();
+            known
+                .any(|value| Some(value) != first)
+                .then(|| ParameterDifference {
+                    parameter: parameter.clone(),
+                    values,
+                })
+        })
+        .collect()
+}
+
+// Leaf values of nested tables with dotted keys, arrays stay single values
+fn flatten(prefix: &str, value: &toml::Value, options: &mut Vec<(String, String)>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&path, value, options);
+            }
+        }
+        _ => options.push((prefix.to_string(), value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_xlsxwriter::Workbook;
+
+    #[test]
+    /// Test loading a vectors workbook and the parameter differences
+    fn test_compare_runs() {
+        let directory = std::env::temp_dir().join("energy_relaxation_comparison_test");
+        std::fs::create_dir_all(&directory).unwrap();
+        let state = directory.join("vectors.xlsx");
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        worksheet.write_row(0, 0, ["X", "Y", "Z"]).unwrap();
+        worksheet.write_row(1, 0, [0.0, 0.0, 1.0]).unwrap();
+        worksheet.write_row(2, 0, [1.0, 0.0, 0.0]).unwrap();
+        workbook.save(&state).unwrap();
+        let config = directory.join("simulation.toml");
+        std::fs::write(&config, "number_of_cells = 2\napplied_field = [0, 0, 1]").unwrap();
+
+        let loaded = RunResult::load(&state, Some(&config)).unwrap();
+        assert_eq!(loaded.magnetizations[1], ndarray::array![1.0, 0.0, 0.0]);
+        assert_eq!(loaded.average_magnetization, [0.5, 0.0, 0.5]);
+        std::fs::write(&config, "number_of_cells = 3").unwrap();
+        assert!(RunResult::load(&state, Some(&config)).is_err());
+
+        let mut rough = SimulationConfig::from_toml("number_of_cells = 2").unwrap();
+        rough.edge_roughness = SimulationConfig::example().edge_roughness;
+        let system = MicromagneticSystem::new(2);
+        let runs = [
+            RunResult::from_system("smooth", &system, loaded.config.clone()),
+            RunResult::from_system("rough", &system, Some(rough)),
+            RunResult::from_system("unknown", &system, None),
+        ];
+        let differences = parameter_differences(&runs);
+        let parameters: Vec<&str> = differences.iter().map(|d| d.parameter.as_str()).collect();
+        assert!(parameters.contains(&"applied_field"));
+        assert!(parameters.contains(&"edge_roughness.amplitude"));
+        assert!(!parameters.contains(&"minimizer"));
+        let field = &differences[parameters
+            .iter()
+            .position(|&p| p == "applied_field")
+            .unwrap()];
+        assert_eq!(field.values[0], Some("[0.0, 0.0, 1.0]".to_string()));
+        assert_eq!(field.values[2], None);
+        // Identical configurations have no differences
+        assert!(parameter_differences(&runs[..1]).is_empty());
+
+        let report = directory.join("report.xlsx");
+        crate::export_to_excel::export_comparison(&runs, &differences, &report).unwrap();
+        let workbook: Xlsx<_> = open_workbook(&report).unwrap();
+        assert_eq!(
+            workbook.sheet_names(),
+            vec!["Profiles", "Energies", "Parameters"]
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,1324 @@
+use crate::absorbing::{AbsorbingBoundaries, AbsorbingSides};
+use crate::anisotropy_profile::AnisotropyProfile;
+use crate::convergence::{ConvergencePolicy, EnergyPlateau, RetryPolicy};
+use crate::damping_profile::DampingProfile;
+use crate::decimation::Decimation;
+use crate::demag::{DipolarMethod, DEFAULT_PERIODIC_IMAGES};
+use crate::dipolar::prism_demagnetization_factors;
+use crate::dmi::DmiType;
+use crate::grid::{BoundaryCondition, Grid};
+use crate::hooks::CompletionHooks;
+use crate::initial_state::InitialState;
+use crate::magnetic_moments::{DampingSchedule, MicromagneticSystem, Minimizer, OscillationPolicy};
+use crate::material::{Material, MaterialDatabase};
+use crate::neighbors::NeighborList;
+use crate::output::CollisionPolicy;
+use crate::parallel::ReductionOrder;
+use crate::parameters::{AdaptiveTimeStep, SimulationParameters};
+use crate::protocol::ProtocolStep;
+use crate::readout::{Magnetoresistance, MagnetoresistanceModel};
+use crate::region::{Region, Shape};
+use crate::roughness::EdgeRoughness;
+use crate::sampling::Sampler;
+use crate::spin_accumulation::SpinAccumulation;
+use crate::spin_transfer::SpinTransferTorque;
+use crate::temperature::{MagnetizationLaw, TemperatureScaling};
+use crate::texture::{DispersionDistribution, TextureDispersion};
+use crate::thermal_field::ThermalField;
+use crate::time_series::{TimeSeriesColumn, DEFAULT_COLUMNS};
+use crate::two_temperature::TwoTemperatureModel;
+use crate::{EXTERNAL_FIELD, MAX_ITERATIONS_NUMBER, SPATIAL_DISCRETION_STEP, TIME_STEP};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Path that names the standard input instead of a configuration file
+pub const STDIN_PATH: &str = "-";
+
+///# Simulation Configuration
+/// Description of a run, read from a TOML or JSON file.
+///
+/// ```toml
+/// number_of_cells = 60
+/// grid = [60, 20, 1]
+/// cell_size = 2.0e-9
+/// applied_field = [0.0, 0.0, 0.5]
+/// field_gradient = [1.0e6, 0.0, 0.0]
+/// extra_neighbors = [[0, 59]]
+/// boundary_condition = { type = "fixed", direction = [1.0, 0.0, 0.0] }
+/// temperature = 300.0
+/// sample_dimensions = [60.0e-9, 20.0e-9, 2.0e-9]
+/// adaptive_damping = 1.0
+/// relaxation_time_step = 2.0e-13
+/// max_iterations = 10000
+/// max_walltime = 3600.0
+/// reduction_order = "deterministic"
+/// output_directory = "runs/relax"
+/// output_collision = "suffix"
+/// dipolar_method = "fft"
+/// periodic_images = 10
+/// minimizer = "relaxation"
+/// oscillation_policy = "reduce_step_size"
+/// time_series_columns = ["mz", "total_energy", "wall_position", "max_torque"]
+/// materials_file = "materials.json"
+///
+/// [material]
+/// A = 1.3e-11
+/// Ms = 8.6e5
+/// K = 0.0
+/// axis = [1.0, 0.0, 0.0]
+/// alpha = 0.01
+///
+/// [initial_state]
+/// type = "uniform"
+/// direction = [1.0, 0.0, 0.0]
+///
+/// [ensemble_sampler]
+/// type = "sobol"
+/// seed = 3
+///
+/// [[regions]]
+/// material = "Cobalt"
+/// start = 0
+/// end = 20
+/// damping = 0.05
+///
+/// [[regions]]
+/// material = "vacuum"
+/// shape = { type = "sphere", center = [45.0e-9, 0.0, 0.0], radius = 2.0e-9 }
+///
+/// [[protocol]]
+/// type = "ramp"
+/// field = [0.0, 0.0, -0.5]
+/// steps = 10
+/// measure = true
+///
+/// [convergence]
+/// max_change = 1.0e-6
+/// max_torque = 10.0
+///
+/// [retry]
+/// attempts = 2
+/// switch_minimizer = true
+/// time_step_factor = 0.5
+///
+/// [adaptive_time_step]
+/// tolerance = 1.0e-2
+/// min_step = 1.0e-15
+/// max_step = 1.0e-11
+///
+/// [decimation]
+/// cell_stride = 10
+/// single_precision = true
+///
+/// [anisotropy_profile]
+/// type = "linear"
+/// start = 1.0e6
+/// end = 1.0e4
+///
+/// [damping_profile]
+/// type = "linear"
+/// start = 0.1
+/// end = 0.01
+///
+/// [edge_roughness]
+/// amplitude = 0.1
+/// correlation_length = 5.0e-9
+///
+/// [texture_dispersion]
+/// angle = 0.05
+///
+/// [temperature_scaling]
+/// curie_temperature = 1388.0
+///
+/// [two_temperature_model]
+/// electron_heat_capacity = 1.065e3
+/// lattice_heat_capacity = 2.2e6
+/// coupling = 3.6e17
+///
+/// [absorbing_boundaries]
+/// width = 200
+/// max_damping = 1.0
+///
+/// [magnetoresistance]
+/// model = "tmr"
+/// start = 40
+/// end = 50
+/// reference = [1.0, 0.0, 0.0]
+/// parallel_resistance = 1.0e3
+/// ratio = 1.0
+///
+/// [spin_accumulation]
+/// current_density = 1.0e11
+/// polarization = 0.7
+/// diffusion_constant = 1.0e-3
+/// spin_flip_time = 1.0e-12
+/// exchange_time = 1.0e-14
+///
+/// [spin_transfer]
+/// current_density = 1.0e11
+/// polarization = 0.7
+/// polarizer = [1.0, 0.0, 0.0]
+/// thickness = 2.0e-9
+///
+/// [thermal_field]
+/// temperature = 300.0
+/// seed = 7
+///
+/// [hooks]
+/// on_finish = ["notify-send 'relaxation finished'"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimulationConfig {
+    // Number of cells in the 1D grid
+    #[serde(default = "default_number_of_cells")]
+    pub number_of_cells: usize,
+    // 2D or 3D mesh [nx, ny, nz] of cells instead of the chain of number_of_cells
+    #[serde(default)]
+    pub grid: Option<Grid>,
+    // Edge length of the cubic cells in m
+    #[serde(default = "default_cell_size")]
+    pub cell_size: f64,
+    // JSON material database, relative paths are resolved against the config file
+    #[serde(default)]
+    pub materials_file: Option<PathBuf>,
+    // Material of the cells outside the regions, the built-in constants when unset
+    #[serde(default)]
+    pub material: Option<Material>,
+    // Random, uniform or the state of an OVF file
+    #[serde(default)]
+    pub initial_state: InitialState,
+    // Initial states of the ensemble replicas in place of the initial state,
+    // independent random states when unset
+    #[serde(default)]
+    pub ensemble_sampler: Option<Sampler>,
+    // Cell ranges with a material from the database, the rest keeps the default material
+    #[serde(default)]
+    pub regions: Vec<RegionConfig>,
+    // Field and temperature steps of the protocol command, executed in order
+    #[serde(default)]
+    pub protocol: Vec<ProtocolStep>,
+    // Include the dipole-dipole (demagnetizing) field
+    #[serde(default)]
+    pub dipolar_interaction: bool,
+    // "direct" O(N^2) sum of point dipoles or "fft" convolution with the demagnetizing tensor
+    #[serde(default)]
+    pub dipolar_method: DipolarMethod,
+    // Copies of the grid on either side along the periodic axes in the dipolar field
+    #[serde(default = "default_periodic_images")]
+    pub periodic_images: usize,
+    // Edge lengths of the rectangular sample in m, adds its shape anisotropy
+    #[serde(default)]
+    pub sample_dimensions: Option<[f64; 3]>,
+    // Uniform applied field B = mu0 H in T
+    #[serde(default = "default_applied_field")]
+    pub applied_field: [f64; 3],
+    // Linear change dB/dx of the applied field along the chain in T/m
+    #[serde(default)]
+    pub field_gradient: [f64; 3],
+    // Exchange-coupled cell pairs beyond the chain, e.g. [[0, 59]] closes a ring
+    #[serde(default)]
+    pub extra_neighbors: Vec<[usize; 2]>,
+    // Free, periodic or fixed outer faces of the grid
+    #[serde(default)]
+    pub boundary_condition: BoundaryCondition,
+    // Temperature in K at which the temperature scaling evaluates the materials
+    #[serde(default)]
+    pub temperature: f64,
+    // "relaxation" or "spherical_conjugate_gradient"
+    #[serde(default)]
+    pub minimizer: Minimizer,
+    // "ignore", "reduce_step_size" or "switch_minimizer" when the relaxation oscillates
+    #[serde(default)]
+    pub oscillation_policy: OscillationPolicy,
+    // Damping used by the minimizer far from equilibrium, the material damping when unset
+    #[serde(default)]
+    pub adaptive_damping: Option<f64>,
+    // Pseudo time step of the relaxation in s
+    #[serde(default = "default_relaxation_time_step")]
+    pub relaxation_time_step: f64,
+    // Steps after which a minimization gives up
+    #[serde(default = "default_max_iterations")]
+    pub max_iterations: usize,
+    // Wall-clock budget of the relaxation in s, it stops unconverged and saves the state
+    #[serde(default)]
+    pub max_walltime: Option<f64>,
+    // "adaptive" or "deterministic" for energies that do not depend on the thread count
+    #[serde(default)]
+    pub reduction_order: ReductionOrder,
+    // Directory of the run outputs with a copy of the config and run.log, the working directory when unset
+    #[serde(default)]
+    pub output_directory: Option<PathBuf>,
+    // "error", "suffix" or "overwrite" when the output directory exists
+    #[serde(default)]
+    pub output_collision: CollisionPolicy,
+    // Criteria that all have to hold for a minimization to converge
+    #[serde(default)]
+    pub convergence: ConvergencePolicy,
+    // Restarts of a minimization that reached max_iterations, none when unset
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+    // Step size control of the relaxation and the dynamics, fixed steps when unset
+    #[serde(default)]
+    pub adaptive_time_step: Option<AdaptiveTimeStep>,
+    // Every k-th cell and snapshot of the exports, optionally in single precision
+    #[serde(default)]
+    pub decimation: Decimation,
+    // Graded anisotropy over the whole chain, applied after the regions
+    #[serde(default)]
+    pub anisotropy_profile: Option<AnisotropyProfile>,
+    // Graded damping over the whole chain, applied after the anisotropy profile
+    #[serde(default)]
+    pub damping_profile: Option<DampingProfile>,
+    // Random cross section along the chain, applied after the anisotropy profile
+    #[serde(default)]
+    pub edge_roughness: Option<EdgeRoughness>,
+    // Random tilt of the easy axis of every cell, applied after the edge roughness
+    #[serde(default)]
+    pub texture_dispersion: Option<TextureDispersion>,
+    // Ms(T), K(T) and A(T) from the constants at zero temperature, applied after the texture
+    #[serde(default)]
+    pub temperature_scaling: Option<TemperatureScaling>,
+    // Electron and lattice heating of laser driven runs, from the temperature
+    #[serde(default)]
+    pub two_temperature_model: Option<TwoTemperatureModel>,
+    // Layers of rising damping at the chain ends that absorb spin waves, applied last
+    #[serde(default)]
+    pub absorbing_boundaries: Option<AbsorbingBoundaries>,
+    // GMR/TMR readout of a free layer, adds the resistance column
+    #[serde(default)]
+    pub magnetoresistance: Option<Magnetoresistance>,
+    // Drift-diffusion spin accumulation of a current, adds its torque to the dynamics
+    #[serde(default)]
+    pub spin_accumulation: Option<SpinAccumulation>,
+    // Slonczewski torque of a current through a fixed layer, adds its field to the dynamics
+    #[serde(default)]
+    pub spin_transfer: Option<SpinTransferTorque>,
+    // Brown's fluctuating field of the dynamics at a finite temperature
+    #[serde(default)]
+    pub thermal_field: Option<ThermalField>,
+    // Observables written to the time series of the dynamics
+    #[serde(default = "default_time_series_columns")]
+    pub time_series_columns: Vec<TimeSeriesColumn>,
+    // Commands run when the run finishes or fails
+    #[serde(default)]
+    pub hooks: CompletionHooks,
+}
+
+///# Region Configuration
+/// Cells `start..end` or the cells of a shape are made of the named
+/// material, optionally with a damping constant of their own. The material
+/// "vacuum" empties the cells unless the database defines it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegionConfig {
+    pub material: String,
+    #[serde(default)]
+    pub start: usize,
+    #[serde(default)]
+    pub end: usize,
+    // Box, cylinder or sphere in m instead of the cell range
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shape: Option<Shape>,
+    // Gilbert damping of the region instead of that of the material
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub damping: Option<f64>,
+}
+
+fn default_number_of_cells() -> usize {
+    50
+}
+
+fn default_cell_size() -> f64 {
+    SPATIAL_DISCRETION_STEP
+}
+
+fn default_applied_field() -> [f64; 3] {
+    EXTERNAL_FIELD
+}
+
+fn default_relaxation_time_step() -> f64 {
+    TIME_STEP
+}
+
+fn default_periodic_images() -> usize {
+    DEFAULT_PERIODIC_IMAGES
+}
+
+fn default_max_iterations() -> usize {
+    MAX_ITERATIONS_NUMBER
+}
+
+fn default_time_series_columns() -> Vec<TimeSeriesColumn> {
+    DEFAULT_COLUMNS.to_vec()
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            number_of_cells: default_number_of_cells(),
+            grid: None,
+            cell_size: default_cell_size(),
+            materials_file: None,
+            material: None,
+            initial_state: InitialState::default(),
+            ensemble_sampler: None,
+            regions: Vec::new(),
+            protocol: Vec::new(),
+            dipolar_interaction: false,
+            dipolar_method: DipolarMethod::default(),
+            periodic_images: default_periodic_images(),
+            sample_dimensions: None,
+            applied_field: default_applied_field(),
+            field_gradient: [0.0; 3],
+            extra_neighbors: Vec::new(),
+            boundary_condition: BoundaryCondition::default(),
+            temperature: 0.0,
+            minimizer: Minimizer::default(),
+            oscillation_policy: OscillationPolicy::default(),
+            adaptive_damping: None,
+            relaxation_time_step: default_relaxation_time_step(),
+            max_iterations: default_max_iterations(),
+            max_walltime: None,
+            reduction_order: ReductionOrder::default(),
+            output_directory: None,
+            output_collision: CollisionPolicy::default(),
+            convergence: ConvergencePolicy::default(),
+            retry: None,
+            adaptive_time_step: None,
+            decimation: Decimation::default(),
+            anisotropy_profile: None,
+            damping_profile: None,
+            edge_roughness: None,
+            texture_dispersion: None,
+            temperature_scaling: None,
+            two_temperature_model: None,
+            absorbing_boundaries: None,
+            magnetoresistance: None,
+            spin_accumulation: None,
+            spin_transfer: None,
+            thermal_field: None,
+            time_series_columns: default_time_series_columns(),
+            hooks: CompletionHooks::default(),
+        }
+    }
+}
+
+// Description of every option in the order of the example file. Tables
+// come last, TOML assigns the keys after a table header to that table.
+const OPTION_DESCRIPTIONS: [(&str, &str); 44] = [
+    ("number_of_cells", "Number of cells in the 1D grid, each cell_size long"),
+    (
+        "grid",
+        "Cells [nx, ny, nz] of a 2D or 3D mesh instead of the chain, exchange-coupled to their face\n\
+         neighbors, regions, profiles and readouts count the cells as x + nx (y + ny z)",
+    ),
+    ("cell_size", "Edge length of the cubic cells in m"),
+    (
+        "materials_file",
+        "JSON material database, relative paths are resolved against the config file, e.g.\n\
+         { \"Cobalt\": { \"A\": 3.0e-11, \"Ms\": 1.4e6, \"K\": 5.2e5, \"axis\": [0, 0, 1], \"alpha\": 0.02 } }",
+    ),
+    (
+        "dipolar_interaction",
+        "Include the dipole-dipole (demagnetizing) field",
+    ),
+    (
+        "dipolar_method",
+        "\"direct\" O(N^2) sum of point dipoles or \"fft\" O(N log N) convolution with the\n\
+         demagnetizing tensor of the cubic cells, which includes their self-demagnetization",
+    ),
+    (
+        "periodic_images",
+        "Copies of the grid on either side along the periodic axes of a periodic boundary_condition\n\
+         that the dipolar field sums over, so that periodic films have no charges at their cut edges",
+    ),
+    (
+        "sample_dimensions",
+        "Edge lengths of the rectangular sample in m, adds its shape anisotropy",
+    ),
+    ("applied_field", "Uniform applied field B = mu0 H in T"),
+    (
+        "field_gradient",
+        "Linear change dB/dx of the applied field along the chain in T/m",
+    ),
+    (
+        "extra_neighbors",
+        "Exchange-coupled cell pairs beyond the chain, e.g. [[0, 49]] closes a ring,\n\
+         further pairs attach branches at junctions",
+    ),
+    (
+        "temperature",
+        "Temperature in K at which the temperature scaling evaluates the materials",
+    ),
+    (
+        "minimizer",
+        "\"relaxation\" or \"spherical_conjugate_gradient\"",
+    ),
+    (
+        "oscillation_policy",
+        "\"ignore\", \"reduce_step_size\" or \"switch_minimizer\" when the relaxation oscillates",
+    ),
+    (
+        "adaptive_damping",
+        "Damping used by the minimizer far from equilibrium, the material damping when unset",
+    ),
+    (
+        "relaxation_time_step",
+        "Pseudo time step of the relaxation minimizer in s, larger steps relax faster until they oscillate",
+    ),
+    (
+        "max_iterations",
+        "Steps after which a minimization stops unconverged",
+    ),
+    (
+        "max_walltime",
+        "Wall-clock budget of the relax command in s, when it is used up the relaxation stops\n\
+         unconverged and saves the state to state.ovf, continue with --initial state.ovf",
+    ),
+    (
+        "reduction_order",
+        "\"adaptive\" or \"deterministic\" parallel energy sums, deterministic ones add fixed chunks\n\
+         in a fixed order and are bit for bit reproducible with any number of threads",
+    ),
+    (
+        "output_directory",
+        "Directory of the outputs of the relax, dynamics, ensemble and ringdown commands,\n\
+         created per run with a copy of the config as config.toml, a run.log and a\n\
+         summary.json, the working directory when unset",
+    ),
+    (
+        "output_collision",
+        "\"error\", \"suffix\" (the first free name-1, name-2, ...) or \"overwrite\" when the output directory exists",
+    ),
+    (
+        "time_series_columns",
+        "Observables of the dynamics time series: mx, my, mz, exchange_energy, anisotropy_energy,\n\
+         zeeman_energy, dipolar_energy, dmi_energy, total_energy, wall_position, max_torque, resistance",
+    ),
+    (
+        "boundary_condition",
+        "Exchange at the outer faces of the grid along the axes with more than one cell, type\n\
+         \"free\" (alias \"neumann\") for free surfaces, \"periodic\" to couple opposite faces along\n\
+         the axes with at least three cells, which the dipolar field repeats along, or \"fixed\"\n\
+         (direction) for ghost cells pinned along the direction beyond every outer face",
+    ),
+    (
+        "material",
+        "Material of every cell outside the regions: exchange stiffness A in J/m, Ms in A/m,\n\
+         anisotropy K in J/m^3, easy axis, damping alpha, optional A2 and DMI D in J/m^2 of type\n\
+         dmi = \"interfacial\" or \"bulk\", the built-in constants when unset",
+    ),
+    (
+        "initial_state",
+        "Magnetization at the start, type \"random\", \"uniform\" (direction) or \"file\" (path of\n\
+         an OVF file with one node per cell, e.g. a saved state.ovf), --initial of relax replaces it",
+    ),
+    (
+        "ensemble_sampler",
+        "Initial states of the ensemble replicas in place of initial_state, type \"random\"\n\
+         (independent), \"sobol\" (low-discrepancy sequence) or \"stratified\" (Latin hypercube) over\n\
+         the directions of all cells with a seed, independent random states when unset",
+    ),
+    (
+        "regions",
+        "Cell ranges start..end or shapes of type \"box\" (min, max), \"cylinder\" (center, radius)\n\
+         or \"sphere\" (center, radius) in m with a material from the database or \"vacuum\" and an\n\
+         optional damping that replaces the damping of the material, the rest keeps the default\n\
+         material. The regions are numbered from 1 in this order for the region columns of the\n\
+         exported cells, a later region overrides an earlier one",
+
+    ),
+    (
+        "protocol",
+        "Steps of the protocol command in order, type \"set_field\" (field), \"set_temperature\"\n\
+         (temperature), \"ramp\" (field, temperature, steps, measure), \"wait\" (duration),\n\
+         \"relax\" or \"measure\" (label), measurements record the time series columns",
+    ),
+    (
+        "convergence",
+        "Criteria that all have to hold for a minimization to converge, by default max_change = 1e-6:\n\
+         max_change of a cell in one relaxation step, max_torque |m x H| in A/m, max_dm_dt |dm/dt|\n\
+         of the LLG equation in 1/s and energy_plateau, the total energy changing by less than\n\
+         relative_change in each of the last steps steps, the message names the criteria met",
+    ),
+    (
+        "retry",
+        "Further attempts of a minimization that reached max_iterations, continuing from its state:\n\
+         switch_minimizer to the other minimizer, a relaxation time step scaled by time_step_factor\n\
+         per attempt and the adaptive damping up to damping, the settings return afterwards",
+    ),
+    (
+        "adaptive_time_step",
+        "Step doubling control of the relaxation and dynamics time steps, which start from their\n\
+         fixed values: the error of a step relative to its largest change stays below tolerance,\n\
+         the step stays within min_step..max_step in s",
+    ),
+    (
+        "decimation",
+        "Down-sampling of vectors.xlsx and the dynamics snapshots: every cell_stride-th cell of every\n\
+         snapshot_stride-th snapshot, with f32 magnetizations and Binary 4 OVF data if single_precision",
+    ),
+    (
+        "anisotropy_profile",
+        "Graded anisotropy over the whole chain, applied after the regions,\n\
+         type \"linear\" (start, end), \"exponential\" (start, decay_length) or \"file\" (path)",
+    ),
+    (
+        "damping_profile",
+        "Graded Gilbert damping over the whole chain, applied after the anisotropy profile,\n\
+         type \"linear\" (start, end), \"exponential\" (start, end, decay_length) or \"file\" (path)",
+    ),
+    (
+        "edge_roughness",
+        "Random cross section along the chain, applied after the anisotropy profile",
+    ),
+    (
+        "texture_dispersion",
+        "Random tilt of the easy axis of every cell by an angle in rad, applied after the edge roughness,\n\
+         distribution \"gaussian\" (rms tilt per direction) or \"uniform\" (within a cone)",
+    ),
+    (
+        "temperature_scaling",
+        "Scale the zero temperature Ms, K, A and D to the temperature, applied after the texture,\n\
+         Ms(T) by the magnetization type \"bloch\" or \"critical\" (exponent) below curie_temperature,\n\
+         K(T) = K(0) m^anisotropy_exponent (Callen-Callen, 3), A(T) and D(T) with m^exchange_exponent (2)",
+    ),
+    (
+        "two_temperature_model",
+        "Electron heat capacity gamma T_e in J/(m^3 K^2), lattice heat capacity in J/(m^3 K), coupling\n\
+         in W/(m^3 K) and optional substrate_cooling_time in s of the optical-switching command,\n\
+         which starts at the temperature and scales the materials with the temperature scaling",
+    ),
+    (
+        "absorbing_boundaries",
+        "Layers of width cells at the chain ends whose damping rises quadratically to max_damping,\n\
+         absorbing spin waves instead of reflecting them, sides \"both\", \"start\" or \"end\"",
+    ),
+    (
+        "magnetoresistance",
+        "Resistance of a spin valve (model \"gmr\") or tunnel junction (\"tmr\") with the free layer\n\
+         start..end and a fixed layer along reference, from parallel_resistance in Ohm and ratio\n\
+         (R_AP - R_P) / R_P, in the resistance column of the time series and protocol measurements",
+    ),
+    (
+        "spin_accumulation",
+        "Steady-state spin accumulation of a current_density in A/m^2 along the chain with the\n\
+         polarization, diffusion_constant in m^2/s, spin_flip_time and exchange_time in s, whose\n\
+         torque acts in the dynamics, vacuum cells conduct as a nonmagnetic spacer",
+    ),
+    (
+        "spin_transfer",
+        "Slonczewski torque of a current_density in A/m^2 through a fixed layer along polarizer\n\
+         into a free layer of thickness in m, with the polarization, asymmetry (default 1) and\n\
+         field_like ratio (default 0), acts in the dynamics and the stt-switching diagram",
+    ),
+    (
+        "thermal_field",
+        "Brown's thermal field of the dynamics at the temperature in K, with a reproducible noise\n\
+         from the seed, needs a fixed time step and converges with the Heun integrator",
+    ),
+    (
+        "hooks",
+        "Shell commands run when the run finishes or fails, the summary JSON is on stdin",
+    ),
+];
+
+impl SimulationConfig {
+    ///# Example Configuration
+    /// The defaults with an example value for every option that is unset
+    /// or empty by default, on a film of 50 x 10 cells.
+    pub fn example() -> Self {
+        let grid = Grid::new(50, 10, 1);
+        let cell_size = default_cell_size();
+        Self {
+            grid: Some(grid),
+            materials_file: Some(PathBuf::from("materials.json")),
+            material: Some(Material {
+                exchange_constant: 1.3e-11,
+                saturation_magnetization: 8.6e5,
+                anisotropy_constant: 0.0,
+                easy_axis: [1.0, 0.0, 0.0],
+                damping: 0.01,
+                second_neighbor_exchange_constant: 0.0,
+                dmi_constant: 0.0,
+                dmi_type: DmiType::Interfacial,
+            }),
+            initial_state: InitialState::Uniform {
+                direction: [1.0, 0.0, 0.0],
+            },
+            ensemble_sampler: Some(Sampler::Sobol { seed: 3 }),
+            regions: vec![
+                RegionConfig {
+                    material: "Cobalt".to_string(),
+                    start: 0,
+                    end: 20,
+                    shape: None,
+                    damping: Some(0.05),
+                },
+                RegionConfig {
+                    material: "vacuum".to_string(),
+                    start: 0,
+                    end: 0,
+                    shape: Some(Shape::Sphere {
+                        center: [45.0e-9, 0.0, 0.0],
+                        radius: 2.0e-9,
+                    }),
+                    damping: None,
+                },
+            ],
+            protocol: vec![
+                ProtocolStep::Relax,
+                ProtocolStep::Ramp {
+                    field: Some([0.0, 0.0, -0.5]),
+                    temperature: None,
+                    steps: 10,
+                    measure: true,
+                },
+            ],
+            sample_dimensions: Some([
+                grid.nx as f64 * cell_size,
+                grid.ny as f64 * cell_size,
+                grid.nz as f64 * cell_size,
+            ]),
+            // Closes the first row of the film into a ring
+            extra_neighbors: vec![[0, grid.nx - 1]],
+            boundary_condition: BoundaryCondition::Fixed {
+                direction: [1.0, 0.0, 0.0],
+            },
+            temperature: 300.0,
+            adaptive_damping: Some(1.0),
+            max_walltime: Some(3600.0),
+            reduction_order: ReductionOrder::Deterministic,
+            output_directory: Some(PathBuf::from("runs/relax")),
+            output_collision: CollisionPolicy::Suffix,
+            convergence: ConvergencePolicy {
+                max_change: Some(1.0e-6),
+                max_torque: Some(10.0),
+                max_dm_dt: None,
+                energy_plateau: Some(EnergyPlateau {
+                    relative_change: 1.0e-12,
+                    steps: 20,
+                }),
+            },
+            retry: Some(RetryPolicy {
+                attempts: 2,
+                switch_minimizer: true,
+                time_step_factor: 0.5,
+                damping: None,
+            }),
+            adaptive_time_step: Some(AdaptiveTimeStep {
+                tolerance: 1.0e-2,
+                min_step: 1.0e-15,
+                max_step: 1.0e-11,
+            }),
+            decimation: Decimation {
+                cell_stride: 10,
+                snapshot_stride: 5,
+                single_precision: true,
+            },
+            anisotropy_profile: Some(AnisotropyProfile::Linear {
+                start: 1.0e6,
+                end: 1.0e4,
+            }),
+            damping_profile: Some(DampingProfile::Linear {
+                start: 0.1,
+                end: 0.01,
+            }),
+            edge_roughness: Some(EdgeRoughness {
+                amplitude: 0.1,
+                correlation_length: 5.0e-9,
+                seed: 0,
+            }),
+            texture_dispersion: Some(TextureDispersion {
+                angle: 0.05,
+                distribution: DispersionDistribution::Gaussian,
+                seed: 0,
+            }),
+            temperature_scaling: Some(TemperatureScaling {
+                curie_temperature: 1388.0,
+                magnetization: MagnetizationLaw::Bloch,
+                anisotropy_exponent: 3.0,
+                exchange_exponent: 2.0,
+            }),
+            two_temperature_model: Some(TwoTemperatureModel {
+                electron_heat_capacity: 1.065e3,
+                lattice_heat_capacity: 2.2e6,
+                coupling: 3.6e17,
+                substrate_cooling_time: Some(1.0e-10),
+            }),
+            absorbing_boundaries: Some(AbsorbingBoundaries {
+                width: 200,
+                max_damping: 1.0,
+                sides: AbsorbingSides::Both,
+            }),
+            magnetoresistance: Some(Magnetoresistance {
+                model: MagnetoresistanceModel::Tmr,
+                start: 0,
+                end: grid.size(),
+                reference: [1.0, 0.0, 0.0],
+                parallel_resistance: 1.0e3,
+                ratio: 1.0,
+            }),
+            spin_accumulation: Some(SpinAccumulation {
+                current_density: 1.0e11,
+                polarization: 0.7,
+                diffusion_constant: 1.0e-3,
+                spin_flip_time: 1.0e-12,
+                exchange_time: 1.0e-14,
+            }),
+            spin_transfer: Some(SpinTransferTorque {
+                current_density: 1.0e11,
+                polarization: 0.7,
+                polarizer: [1.0, 0.0, 0.0],
+                thickness: 2.0e-9,
+                asymmetry: 1.0,
+                field_like: 0.0,
+            }),
+            thermal_field: Some(ThermalField {
+                temperature: 300.0,
+                seed: 7,
+            }),
+            hooks: CompletionHooks {
+                on_finish: vec!["notify-send 'relaxation finished'".to_string()],
+                on_failure: Vec::new(),
+            },
+            ..Self::default()
+        }
+    }
+
+    ///# Example TOML
+    /// Commented configuration file with every option, generated from the
+    /// defaults and `example`. Descriptions start with `##`, the options
+    /// that are off by default are commented out with `#`, so the file
+    /// reads as the defaults until they are uncommented.
+    pub fn example_toml() -> String {
+        let defaults = toml::Table::try_from(Self::default()).expect("the defaults serialize");
+        let example = toml::Table::try_from(Self::example()).expect("the example serializes");
+        let mut text = String::from("## Energy_Relaxation simulation configuration\n");
+        for (key, description) in OPTION_DESCRIPTIONS {
+            text.push('\n');
+            for line in description.lines() {
+                text.push_str(&format!("## {}\n", line.trim()));
+            }
+            let value = &example[key];
+            let mut option = toml::Table::new();
+            option.insert(key.to_string(), value.clone());
+            let option = toml::to_string(&option).expect("options serialize");
+            let enabled = defaults.get(key) == Some(value);
+            for line in option.lines().filter(|line| !line.is_empty()) {
+                if enabled {
+                    text.push_str(&format!("{}\n", line));
+                } else {
+                    text.push_str(&format!("# {}\n", line));
+                }
+            }
+        }
+        text
+    }
+
+    ///# Load Configuration
+    /// Files ending in `.json` are read as JSON, everything else as TOML.
+    /// `-` reads the configuration from the standard input, relative paths
+    /// in it are resolved against the working directory.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if path == Path::new(STDIN_PATH) {
+            return Self::from_reader(std::io::stdin().lock());
+        }
+        let text = fs::read_to_string(path)?;
+        let mut config = if path.extension().is_some_and(|e| e == "json") {
+            Self::from_json(&text)?
+        } else {
+            Self::from_toml(&text)?
+        };
+        if let Some(directory) = path.parent() {
+            if let Some(file) = config.materials_file.as_ref().filter(|f| f.is_relative()) {
+                config.materials_file = Some(directory.join(file));
+            }
+            if let Some(profile) = config.anisotropy_profile.as_mut() {
+                profile.resolve_path(directory);
+            }
+            if let Some(profile) = config.damping_profile.as_mut() {
+                profile.resolve_path(directory);
+            }
+            config.initial_state.resolve_path(directory);
+        }
+        Ok(config)
+    }
+
+    ///# Parse TOML Configuration
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(text)?)
+    }
+
+    ///# Parse JSON Configuration
+    pub fn from_json(text: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    ///# Read Configuration
+    /// Read a whole TOML or JSON configuration from a stream without a file
+    /// name, as JSON when it starts with `{`.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        if text.trim_start().starts_with('{') {
+            Self::from_json(&text)
+        } else {
+            Self::from_toml(&text)
+        }
+    }
+
+    ///# Load Material Database
+    /// The database named by `materials_file`, empty when none is given.
+    pub fn material_database(&self) -> Result<MaterialDatabase, Box<dyn Error>> {
+        match &self.materials_file {
+            Some(path) => MaterialDatabase::load(path),
+            None => Ok(MaterialDatabase::default()),
+        }
+    }
+
+    ///# Simulation Parameters
+    /// The validated values every cell of the system starts with, before
+    /// the regions and profiles.
+    pub fn parameters(&self) -> Result<SimulationParameters, Box<dyn Error>> {
+        let material = match self.material {
+            Some(material) => material
+                .validated()
+                .map_err(|e| format!("The material {}", e))?,
+            None => Material::default(),
+        };
+        let parameters = SimulationParameters::default()
+            .with_material(material)
+            .with_applied_field(self.applied_field)
+            .with_time_step(self.relaxation_time_step)
+            .with_max_iterations(self.max_iterations)
+            .with_convergence(self.convergence)
+            .with_adaptive_time_step(self.adaptive_time_step)
+            .with_boundary_condition(self.boundary_condition)
+            .with_cell_size(self.cell_size);
+        parameters.validate()?;
+        Ok(parameters)
+    }
+
+    ///# Grid
+    /// The mesh of the cells, the chain of `number_of_cells` cells unless a grid is given.
+    pub fn grid(&self) -> Grid {
+        self.grid.unwrap_or(Grid::chain(self.number_of_cells))
+    }
+
+    ///# Build System
+    /// Create the system, assign the region materials from the database and
+    /// set the initial state.
+    pub fn build_system(&self) -> Result<MicromagneticSystem, Box<dyn Error>> {
+        let database = self.material_database()?;
+        let parameters = self.parameters()?;
+        let grid = self.grid();
+        let cells = grid.size();
+        let mut system = MicromagneticSystem::with_parameters(cells, parameters);
+        system.set_grid(grid)?;
+        system.set_dipolar_method(self.dipolar_method);
+        system.set_periodic_images(self.periodic_images);
+        system.set_dipolar_interaction(self.dipolar_interaction);
+        if let Some(dimensions) = self.sample_dimensions {
+            if !dimensions.iter().all(|&length| length > 0.0) {
+                return Err("The sample dimensions must be positive".into());
+            }
+            system.set_demagnetization_factors(prism_demagnetization_factors(dimensions));
+        }
+        system.set_field_gradient(self.field_gradient);
+        if !self.extra_neighbors.is_empty() {
+            let edges: Vec<(usize, usize)> = system
+                .get_neighbor_list()
+                .edges()
+                .chain(self.extra_neighbors.iter().map(|&[a, b]| (a, b)))
+                .collect();
+            system.set_neighbor_list(NeighborList::from_edges(cells, &edges)?)?;
+        }
+        system.set_minimizer(self.minimizer);
+        system.set_oscillation_policy(self.oscillation_policy);
+        if let Some(retry) = &self.retry {
+            retry.validate()?;
+        }
+        system.set_retry_policy(self.retry);
+        self.decimation.validate()?;
+        match &self.magnetoresistance {
+            Some(readout) => readout.validate(cells)?,
+            None if self
+                .time_series_columns
+                .contains(&TimeSeriesColumn::Resistance) =>
+            {
+                return Err("The resistance column needs a magnetoresistance readout".into());
+            }
+            None => {}
+        }
+        if let Some(model) = &self.spin_accumulation {
+            model.validate()?;
+        }
+        if let Some(model) = &self.spin_transfer {
+            model.validate()?;
+        }
+        if let Some(thermal) = &self.thermal_field {
+            thermal.validate()?;
+        }
+        system.set_reduction_order(self.reduction_order);
+        if let Some(maximum) = self.adaptive_damping {
+            system.set_damping_schedule(DampingSchedule::Adaptive { maximum });
+        }
+        // Region ids follow the order of the list
+        for region in &self.regions {
+            let mut material = match database.get(&region.material) {
+                Some(material) => *material,
+                None if region.material == "vacuum" => Material::vacuum(),
+                None => {
+                    return Err(format!(
+                        "Unknown material '{}', available: {:?}",
+                        region.material,
+                        database.names()
+                    )
+                    .into())
+                }
+            };
+            let shape = match region.shape {
+                Some(_) if region.start != 0 || region.end != 0 => {
+                    return Err(format!(
+                        "Region of '{}' has both a shape and a cell range",
+                        region.material
+                    )
+                    .into())
+                }
+                Some(shape) => shape,
+                None => Shape::Range {
+                    start: region.start,
+                    end: region.end,
+                },
+            };
+            if region.start > region.end || region.end > cells {
+                return Err(format!(
+                    "Region {}..{} of '{}' is outside the {} cells",
+                    region.start, region.end, region.material, cells
+                )
+                .into());
+            }
+            if let Some(damping) = region.damping {
+                if damping < 0.0 {
+                    return Err(format!(
+                        "The damping of the region of '{}' must not be negative",
+                        region.material
+                    )
+                    .into());
+                }
+                material.damping = damping;
+            }
+            system.add_region(&Region { shape, material })?;
+        }
+        if let Some(profile) = &self.anisotropy_profile {
+            profile.apply(&mut system, 0..cells)?;
+        }
+        if let Some(profile) = &self.damping_profile {
+            profile.apply(&mut system, 0..cells)?;
+        }
+        if let Some(roughness) = &self.edge_roughness {
+            roughness.apply(&mut system, 0..cells)?;
+        }
+        if let Some(dispersion) = &self.texture_dispersion {
+            dispersion.apply(&mut system, 0..cells)?;
+        }
+        if let Some(scaling) = &self.temperature_scaling {
+            scaling.apply(&mut system, 0..cells, self.temperature)?;
+        }
+        if let Some(boundaries) = &self.absorbing_boundaries {
+            boundaries.apply(&mut system, 0..cells)?;
+        }
+        self.initial_state.apply(&mut system)?;
+        Ok(system)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATABASE: &str = r#"{
+        "Cobalt": { "A": 3.0e-11, "Ms": 1.4e6, "K": 5.2e5, "axis": [0, 0, 1], "alpha": 0.02 }
+    }"#;
+
+    #[test]
+    /// Test the defaults of an empty configuration
+    fn test_defaults() {
+        let config = SimulationConfig::from_toml("").unwrap();
+        assert_eq!(config, SimulationConfig::default());
+        assert!(SimulationConfig::from_toml("unknown = 1").is_err());
+    }
+
+    #[test]
+    /// Test that a field gradient leaves the uniform field unchanged
+    fn test_field_gradient() {
+        let config = SimulationConfig::from_toml("field_gradient = [1.0e6, 0, 0]").unwrap();
+        let system = config.build_system().unwrap();
+        assert_eq!(system.get_applied_field(), crate::EXTERNAL_FIELD);
+        assert_eq!(system.get_field_gradient(), [1.0e6, 0.0, 0.0]);
+    }
+
+    #[test]
+    /// Test selecting the spherical conjugate gradient minimizer
+    fn test_minimizer() {
+        let config = SimulationConfig::from_toml("minimizer = \"spherical_conjugate_gradient\"");
+        assert_eq!(
+            config.unwrap().minimizer,
+            Minimizer::SphericalConjugateGradient
+        );
+        assert!(SimulationConfig::from_toml("minimizer = \"newton\"").is_err());
+    }
+
+    #[test]
+    /// Test selecting the oscillation policy
+    fn test_oscillation_policy() {
+        let config = SimulationConfig::from_toml("oscillation_policy = \"switch_minimizer\"");
+        assert_eq!(
+            config.unwrap().oscillation_policy,
+            OscillationPolicy::SwitchMinimizer
+        );
+    }
+
+    #[test]
+    /// Test that extra neighbors close a chain into a ring
+    fn test_extra_neighbors() {
+        let config =
+            SimulationConfig::from_toml("number_of_cells = 4\nextra_neighbors = [[0, 3]]").unwrap();
+        let system = config.build_system().unwrap();
+        assert_eq!(system.get_neighbor_list(), &NeighborList::ring(4));
+        let config = SimulationConfig::from_toml("number_of_cells = 4\nextra_neighbors = [[0, 4]]");
+        assert!(config.unwrap().build_system().is_err());
+    }
+
+    #[test]
+    /// Test extra neighbors on top of the face neighbors of a grid
+    fn test_grid_extra_neighbors() {
+        let config = SimulationConfig::from_toml("grid = [3, 2, 1]\nextra_neighbors = [[0, 5]]");
+        let system = config.unwrap().build_system().unwrap();
+        assert_eq!(system.size(), 6);
+        assert_eq!(system.get_neighbor_list().neighbors(0), &[1, 3, 5]);
+    }
+
+    #[test]
+    /// Test the cell size of the system and that it must be positive
+    fn test_cell_size() {
+        let config = SimulationConfig::from_toml("cell_size = 2.0e-9").unwrap();
+        assert_eq!(config.build_system().unwrap().get_cell_size(), 2.0e-9);
+        let config = SimulationConfig::from_toml("cell_size = 0.0").unwrap();
+        assert!(config.build_system().is_err());
+    }
+
+    #[test]
+    /// Test the temperature scaling of the saturation magnetization
+    fn test_temperature_scaling() {
+        let text = "temperature = 500.0\n[temperature_scaling]\ncurie_temperature = 1000.0";
+        let materials = SimulationConfig::from_toml(text)
+            .unwrap()
+            .build_system()
+            .unwrap()
+            .get_materials();
+        let m = 1.0 - 0.5_f64.powf(1.5);
+        let ms = crate::SATURATION_MAGNETIZATION * m;
+        assert!((materials[0].saturation_magnetization - ms).abs() < 1e-9 * ms);
+        let text = "temperature = 1500.0\n[temperature_scaling]\ncurie_temperature = 1000.0";
+        assert!(SimulationConfig::from_toml(text)
+            .unwrap()
+            .build_system()
+            .is_err());
+    }
+
+    #[test]
+    /// Test the demagnetizing factors of the sample dimensions
+    fn test_sample_dimensions() {
+        let config = SimulationConfig::from_toml("sample_dimensions = [1e-9, 1e-9, 1e-9]").unwrap();
+        let factors = config.build_system().unwrap().get_demagnetization_factors();
+        assert!(factors.iter().all(|n| (n - 1.0 / 3.0).abs() < 1e-12));
+        let config = SimulationConfig::from_toml("sample_dimensions = [1e-9, 0, 1e-9]").unwrap();
+        assert!(config.build_system().is_err());
+    }
+
+    #[test]
+    /// Test selecting the time series columns
+    fn test_time_series_columns() {
+        let config = SimulationConfig::from_toml("time_series_columns = [\"mx\", \"max_torque\"]");
+        assert_eq!(
+            config.unwrap().time_series_columns,
+            vec![TimeSeriesColumn::Mx, TimeSeriesColumn::MaxTorque]
+        );
+        assert!(SimulationConfig::from_toml("time_series_columns = [\"power\"]").is_err());
+    }
+
+    #[test]
+    /// Test the default material and the initial state of the configuration
+    fn test_material_and_initial_state() {
+        let text = "number_of_cells = 3\n[material]\nA = 1.3e-11\nMs = 8.6e5\nK = 0.0\n\
+                    axis = [0, 2, 0]\nalpha = 0.01\n[initial_state]\ntype = \"uniform\"\n\
+                    direction = [0, 0, -1]";
+        let system = SimulationConfig::from_toml(text)
+            .unwrap()
+            .build_system()
+            .unwrap();
+        let material = system.get_materials()[2];
+        assert_eq!(material.saturation_magnetization, 8.6e5);
+        assert_eq!(material.easy_axis, [0.0, 1.0, 0.0]);
+        assert_eq!(system.average_magnetization(), [0.0, 0.0, -1.0]);
+        let config = SimulationConfig::from_toml(&text.replace("alpha = 0.01", "alpha = -0.01"));
+        assert!(config.unwrap().build_system().is_err());
+    }
+
+    #[test]
+    /// Test that the resistance column needs a magnetoresistance readout
+    fn test_resistance_column() {
+        let config = SimulationConfig::from_toml("time_series_columns = [\"resistance\"]").unwrap();
+        assert!(config.build_system().is_err());
+        let text = "time_series_columns = [\"resistance\"]\n[magnetoresistance]\nstart = 40\n\
+                    end = 50\nreference = [1, 0, 0]\nparallel_resistance = 100.0\nratio = 0.1";
+        let config = SimulationConfig::from_toml(text).unwrap();
+        assert!(config.build_system().is_ok());
+    }
+
+    #[test]
+    /// Test that streams are TOML unless they hold a JSON object
+    fn test_from_reader() {
+        let json = SimulationConfig::from_reader(" {\"number_of_cells\": 7}".as_bytes()).unwrap();
+        let toml = SimulationConfig::from_reader("number_of_cells = 7\n".as_bytes()).unwrap();
+        assert_eq!(json, toml);
+        assert_eq!(toml.number_of_cells, 7);
+        assert!(SimulationConfig::from_reader("{ number_of_cells = 7 }".as_bytes()).is_err());
+    }
+
+    #[test]
+    /// Test that the example file covers every option and parses back
+    fn test_example_toml() {
+        let text = SimulationConfig::example_toml();
+        assert_eq!(
+            SimulationConfig::from_toml(&text).unwrap(),
+            SimulationConfig::default()
+        );
+        let uncommented: String = text
+            .lines()
+            .filter(|line| !line.starts_with("##"))
+            .map(|line| format!("{}\n", line.strip_prefix("# ").unwrap_or(line)))
+            .collect();
+        assert_eq!(
+            SimulationConfig::from_toml(&uncommented).unwrap(),
+            SimulationConfig::example()
+        );
+
+        // A new option needs a description to appear in the example
+        let example = toml::Table::try_from(SimulationConfig::example()).unwrap();
+        let mut keys: Vec<&str> = example.keys().map(String::as_str).collect();
+        let mut described: Vec<&str> = OPTION_DESCRIPTIONS.iter().map(|(key, _)| *key).collect();
+        keys.sort_unstable();
+        described.sort_unstable();
+        assert_eq!(keys, described);
+    }
+
+    #[test]
+    /// Test that the example builds a system on its grid
+    fn test_example_builds() {
+        let example = SimulationConfig::example();
+        // Only the vacuum region resolves without the database file
+        let config = SimulationConfig {
+            materials_file: None,
+            regions: vec![example.regions[1].clone()],
+            ..example
+        };
+        let system = config.build_system().unwrap();
+        assert_eq!(system.get_grid(), Grid::new(50, 10, 1));
+        assert_eq!(system.get_neighbor_list().neighbors(0), &[1, 49, 50]);
+        assert!(system.get_materials()[45].is_vacuum());
+    }
+
+    #[test]
+    /// Test assigning a database material to a region
+    fn test_regions_from_database() {
+        let directory = std::env::temp_dir().join("energy_relaxation_config_test");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("materials.json"), DATABASE).unwrap();
+        let config_path = directory.join("simulation.toml");
+        fs::write(
+            &config_path,
+            "number_of_cells = 6\nmaterials_file = \"materials.json\"\n\
+             [[regions]]\nmaterial = \"Cobalt\"\nstart = 2\nend = 4\n",
+        )
+        .unwrap();
+
+        let config = SimulationConfig::load(&config_path).unwrap();
+        let system = config.build_system().unwrap();
+        let saturation_magnetizations = system.get_saturation_magnetizations();
+        assert_eq!(saturation_magnetizations[2], 1.4e6);
+        assert_eq!(saturation_magnetizations[3], 1.4e6);
+        assert_eq!(
+            saturation_magnetizations[4],
+            crate::SATURATION_MAGNETIZATION
+        );
+
+        assert_eq!(system.get_materials()[2].damping, 0.02);
+
+        // A region damping replaces the damping of the material
+        let mut damped = config.clone();
+        damped.regions[0].damping = Some(0.5);
+        let materials = damped.build_system().unwrap().get_materials();
+        assert_eq!(materials[3].damping, 0.5);
+        assert_eq!(materials[3].saturation_magnetization, 1.4e6);
+        assert_eq!(materials[4].damping, crate::DAMPING_CONSTANT);
+        damped.regions[0].damping = Some(-0.5);
+        assert!(damped.build_system().is_err());
+
+        let mut unknown = config.clone();
+        unknown.regions[0].material = String::from("Iron");
+        assert!(unknown.build_system().is_err());
+
+        // A vacuum notch in the Cobalt region gets the next id
+        let mut notched = config.clone();
+        notched.regions.push(RegionConfig {
+            material: String::from("vacuum"),
+            start: 0,
+            end: 0,
+            shape: Some(Shape::Sphere {
+                center: [3.0 * crate::SPATIAL_DISCRETION_STEP, 0.0, 0.0],
+                radius: 0.0,
+            }),
+            damping: None,
+        });
+        let system = notched.build_system().unwrap();
+        assert_eq!(system.get_regions(), &[0, 0, 1, 2, 0, 0]);
+        assert!(system.is_vacuum(3) && !system.is_vacuum(2));
+        notched.regions[1].end = 4;
+        assert!(notched.build_system().is_err());
+    }
+
+    #[test]
+    /// Test a graded anisotropy profile from a relative file
+    fn test_anisotropy_profile() {
+        let directory = std::env::temp_dir().join("energy_relaxation_profile_test");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("profile.txt"),
+            "0 1e6
+3e-9 1e3
+",
+        )
+        .unwrap();
+        let config_path = directory.join("simulation.toml");
+        fs::write(
+            &config_path,
+            "number_of_cells = 4
+[anisotropy_profile]
+type = \"file\"\npath = \"profile.txt\"\n",
+        )
+        .unwrap();
+
+        let config = SimulationConfig::load(&config_path).unwrap();
+        let materials = config.build_system().unwrap().get_materials();
+        assert_eq!(materials[0].anisotropy_constant, 1.0e6);
+        assert_eq!(materials[3].anisotropy_constant, 1.0e3);
+        assert!(SimulationConfig::from_toml("[anisotropy_profile]\ntype = \"cubic\"").is_err());
+    }
+}
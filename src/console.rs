@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set when stdout carries machine readable output, e.g. the summary of a
+// run in a pipeline, so the console messages have to go to stderr
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+///# Send Console To Stderr
+/// Route the messages printed with `console!` to stderr instead of stdout.
+pub fn send_to_stderr(enabled: bool) {
+    TO_STDERR.store(enabled, Ordering::Relaxed);
+}
+
+///# Console On Stderr
+pub fn on_stderr() -> bool {
+    TO_STDERR.load(Ordering::Relaxed)
+}
+
+///# Console Message
+/// Print a line like `println!`, on stderr when the console is sent there.
+#[macro_export]
+macro_rules! console {
+    ($($arg:tt)*) => {
+        if $crate::console::on_stderr() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that the console switches between stdout and stderr
+    fn test_send_to_stderr() {
+        assert!(!on_stderr());
+        send_to_stderr(true);
+        assert!(on_stderr());
+        crate::console!("Console message on stderr");
+        send_to_stderr(false);
+        assert!(!on_stderr());
+        crate::console!();
+    }
+}
@@ -0,0 +1,292 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::TOLERANCE;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+///# Convergence Policy
+/// Criteria a minimization has to meet at the same time to count as
+/// converged, each with its own tolerance. Unset criteria are not checked.
+/// The default is the largest change of a cell below TOLERANCE.
+///
+/// ```toml
+/// [convergence]
+/// max_change = 1.0e-6
+/// max_torque = 10.0
+/// max_dm_dt = 1.0e9
+/// energy_plateau = { relative_change = 1.0e-12, steps = 20 }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConvergencePolicy {
+    // Largest change |dm| of a cell in one relaxation step. The conjugate
+    // gradient compares the change a relaxation step would make at its torque.
+    #[serde(default)]
+    pub max_change: Option<f64>,
+    // Largest torque |m x H_eff| of a cell in A/m
+    #[serde(default)]
+    pub max_torque: Option<f64>,
+    // Largest |dm/dt| of a cell in 1/s from the Landau-Lifshitz-Gilbert
+    // equation with the damping of the materials
+    #[serde(default)]
+    pub max_dm_dt: Option<f64>,
+    #[serde(default)]
+    pub energy_plateau: Option<EnergyPlateau>,
+}
+
+///# Energy Plateau
+/// The total energy changed by less than `relative_change` of its magnitude
+/// in each of the last `steps` steps.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnergyPlateau {
+    pub relative_change: f64,
+    pub steps: usize,
+}
+
+impl Default for ConvergencePolicy {
+    fn default() -> Self {
+        Self {
+            max_change: Some(TOLERANCE),
+            max_torque: None,
+            max_dm_dt: None,
+            energy_plateau: None,
+        }
+    }
+}
+
+impl ConvergencePolicy {
+    ///# Validate
+    /// At least one criterion is set and every tolerance is positive.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.max_change.is_none()
+            && self.max_torque.is_none()
+            && self.max_dm_dt.is_none()
+            && self.energy_plateau.is_none()
+        {
+            return Err("The convergence policy needs at least one criterion".into());
+        }
+        let positive = |tolerance: Option<f64>| tolerance.is_none_or(|t| t > 0.0);
+        if !positive(self.max_change)
+            || !positive(self.max_torque)
+            || !positive(self.max_dm_dt)
+            || !positive(self.energy_plateau.map(|p| p.relative_change))
+        {
+            return Err("The convergence tolerances must be positive".into());
+        }
+        if self.energy_plateau.is_some_and(|p| p.steps == 0) {
+            return Err("The energy plateau needs at least one step".into());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConvergencePolicy {
+    /// The criteria with their tolerances, e.g. for the convergence message.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut criteria = Vec::new();
+        if let Some(tolerance) = self.max_change {
+            criteria.push(format!("max change < {:e}", tolerance));
+        }
+        if let Some(tolerance) = self.max_torque {
+            criteria.push(format!("max torque < {:e} A/m", tolerance));
+        }
+        if let Some(tolerance) = self.max_dm_dt {
+            criteria.push(format!("max dm/dt < {:e} 1/s", tolerance));
+        }
+        if let Some(plateau) = self.energy_plateau {
+            criteria.push(format!(
+                "relative energy change <= {:e} in {} steps",
+                plateau.relative_change, plateau.steps
+            ));
+        }
+        write!(f, "{}", criteria.join(", "))
+    }
+}
+
+///# Retry Policy
+/// Further minimizations after one ends at the iteration limit, each
+/// continuing from the state it left with its own iteration limit. The
+/// retries may use the other minimizer, scale the relaxation time step by
+/// `time_step_factor` once per retry or relax with the adaptive damping up
+/// to `damping`. The settings are restored after the last retry.
+///
+/// ```toml
+/// [retry]
+/// attempts = 2
+/// switch_minimizer = true
+/// time_step_factor = 0.5
+/// damping = 1.0
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    pub attempts: usize,
+    // Relaxation instead of conjugate gradient and vice versa
+    #[serde(default)]
+    pub switch_minimizer: bool,
+    #[serde(default = "default_time_step_factor")]
+    pub time_step_factor: f64,
+    // Maximum of the adaptive damping schedule of the retries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub damping: Option<f64>,
+}
+
+fn default_time_step_factor() -> f64 {
+    1.0
+}
+
+impl RetryPolicy {
+    ///# Validate
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !self.time_step_factor.is_finite() || self.time_step_factor <= 0.0 {
+            return Err("The time step factor of the retries must be positive".into());
+        }
+        if self
+            .damping
+            .is_some_and(|damping| !damping.is_finite() || damping < 0.0)
+        {
+            return Err("The damping of the retries must not be negative".into());
+        }
+        Ok(())
+    }
+}
+
+///# Convergence Monitor
+/// Checks a policy after every step of one minimization, counting the
+/// length of the current energy plateau.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvergenceMonitor {
+    pub policy: ConvergencePolicy,
+    previous_energy: Option<f64>,
+    plateau: usize,
+}
+
+impl ConvergenceMonitor {
+    pub fn new(policy: ConvergencePolicy) -> Self {
+        Self {
+            policy,
+            previous_energy: None,
+            plateau: 0,
+        }
+    }
+
+    ///# Converged
+    /// Whether the state after a step with the largest change `max_change`
+    /// meets every criterion. Call once per step, the plateau only grows
+    /// with the calls. Torque and energy are only evaluated when needed.
+    pub fn converged(&mut self, max_change: f64, system: &MicromagneticSystem) -> bool {
+        let mut converged = self.policy.max_change.is_none_or(|t| max_change < t);
+        if let Some(plateau) = self.policy.energy_plateau {
+            let energy = system.compute_energies().total();
+            let flat = self.previous_energy.is_some_and(|previous| {
+                (energy - previous).abs() <= plateau.relative_change * energy.abs()
+            });
+            self.plateau = if flat { self.plateau + 1 } else { 0 };
+            self.previous_energy = Some(energy);
+            converged &= self.plateau >= plateau.steps;
+        }
+        converged
+            && self
+                .policy
+                .max_torque
+                .is_none_or(|t| system.compute_max_torque() < t)
+            && self
+                .policy
+                .max_dm_dt
+                .is_none_or(|t| system.compute_max_dm_dt() < t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magnetic_moments::{MinimizationOutcome, Minimizer};
+    use ndarray::array;
+
+    // Chain tilted away from the field along x
+    fn tilted() -> MicromagneticSystem {
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.3, 0.0]; 10]);
+        system.set_applied_field([1.0, 0.0, 0.0]);
+        system
+    }
+
+    #[test]
+    /// Test that every criterion of the policy has to hold
+    fn test_convergence_policy() {
+        assert!(ConvergencePolicy::default().validate().is_ok());
+        let empty = ConvergencePolicy {
+            max_change: None,
+            ..ConvergencePolicy::default()
+        };
+        assert!(empty.validate().is_err());
+        let plateau = EnergyPlateau {
+            relative_change: 1e-12,
+            steps: 5,
+        };
+        let strict = ConvergencePolicy {
+            max_change: Some(1e-3),
+            max_torque: Some(10.0),
+            max_dm_dt: None,
+            energy_plateau: Some(plateau),
+        };
+        assert!(strict.validate().is_ok());
+        assert_eq!(
+            strict.to_string(),
+            "max change < 1e-3, max torque < 1e1 A/m, relative energy change <= 1e-12 in 5 steps"
+        );
+        assert!(ConvergencePolicy {
+            max_torque: Some(-1.0),
+            ..strict
+        }
+        .validate()
+        .is_err());
+
+        let mut monitor = ConvergenceMonitor::new(strict);
+        let system = tilted();
+        // The change alone is not enough while the torque is large
+        assert!(system.compute_max_torque() > 10.0);
+        assert!(!monitor.converged(0.0, &system));
+        let mut aligned = tilted();
+        for cell in 0..10 {
+            aligned.set_magnetization(cell, array![1.0, 0.0, 0.0]);
+        }
+        for _ in 0..plateau.steps {
+            assert!(!monitor.converged(0.0, &aligned));
+        }
+        assert!(monitor.converged(0.0, &aligned));
+        assert!(!monitor.converged(1e-2, &aligned));
+
+        for minimizer in [Minimizer::Relaxation, Minimizer::SphericalConjugateGradient] {
+            let mut system = tilted();
+            system.set_minimizer(minimizer);
+            system.set_convergence_policy(strict);
+            let outcome = system.minimize_energy_until(|_, _| true);
+            assert!(matches!(outcome, MinimizationOutcome::Converged { .. }));
+            assert!(system.compute_max_torque() < 10.0);
+        }
+    }
+
+    #[test]
+    /// Test the dm/dt of the damped precession and the criterion on it
+    fn test_max_dm_dt() {
+        // |dm/dt| = gamma |m x H| / sqrt(1 + alpha^2) for a unit m
+        let system = tilted();
+        let damping = system.get_materials()[0].damping;
+        let expected = crate::GILBERT_GYROMAGNETIC_RATIO * system.compute_max_torque()
+            / (1.0 + damping * damping).sqrt();
+        assert!((system.compute_max_dm_dt() - expected).abs() < 1e-9 * expected);
+        let dm_dt = ConvergencePolicy {
+            max_change: None,
+            max_dm_dt: Some(1e6),
+            ..ConvergencePolicy::default()
+        };
+        assert!(dm_dt.validate().is_ok());
+        assert_eq!(dm_dt.to_string(), "max dm/dt < 1e6 1/s");
+        let mut system = tilted();
+        system.set_convergence_policy(dm_dt);
+        let outcome = system.minimize_energy_until(|_, _| true);
+        assert!(matches!(outcome, MinimizationOutcome::Converged { .. }));
+        assert!(system.compute_max_dm_dt() < 1e6);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::material::Material;
+use crate::neighbors::NeighborList;
+use ndarray::Array1;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+///# Centerline
+/// Arc-length parametrized centerline of a curved wire with constant
+/// curvature and torsion.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Centerline {
+    // Circle of the radius in m in the xy plane
+    Ring { radius: f64 },
+    // Helix around the z axis, rising by the pitch in m per turn
+    Helix { radius: f64, pitch: f64 },
+}
+
+///# Frenet Frame
+/// Local tangent, normal and binormal of the centerline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrenetFrame {
+    pub tangent: [f64; 3],
+    pub normal: [f64; 3],
+    pub binormal: [f64; 3],
+}
+
+impl Centerline {
+    // Radius and rise per radian of the helix, the ring has no rise
+    fn radius_and_rise(&self) -> (f64, f64) {
+        match *self {
+            Self::Ring { radius } => (radius, 0.0),
+            Self::Helix { radius, pitch } => (radius, pitch / (2.0 * PI)),
+        }
+    }
+
+    // Length of a single turn in m
+    fn turn_length(&self) -> f64 {
+        let (radius, rise) = self.radius_and_rise();
+        2.0 * PI * radius.hypot(rise)
+    }
+
+    ///# Curvature
+    /// Curvature of the centerline in 1/m.
+    pub fn curvature(&self) -> f64 {
+        let (radius, rise) = self.radius_and_rise();
+        radius / (radius * radius + rise * rise)
+    }
+
+    ///# Torsion
+    /// Torsion of the centerline in 1/m.
+    pub fn torsion(&self) -> f64 {
+        let (radius, rise) = self.radius_and_rise();
+        rise / (radius * radius + rise * rise)
+    }
+
+    ///# Point
+    /// Position at the arc length `s` in m.
+    pub fn point(&self, s: f64) -> [f64; 3] {
+        let (radius, rise) = self.radius_and_rise();
+        let phi = 2.0 * PI * s / self.turn_length();
+        [radius * phi.cos(), radius * phi.sin(), rise * phi]
+    }
+
+    ///# Frame
+    /// Frenet frame at the arc length `s` in m.
+    pub fn frame(&self, s: f64) -> FrenetFrame {
+        let (radius, rise) = self.radius_and_rise();
+        let phi = 2.0 * PI * s / self.turn_length();
+        let speed = radius.hypot(rise);
+        let (sin, cos) = phi.sin_cos();
+        FrenetFrame {
+            tangent: [-radius * sin / speed, radius * cos / speed, rise / speed],
+            normal: [-cos, -sin, 0.0],
+            binormal: [rise * sin / speed, -rise * cos / speed, radius / speed],
+        }
+    }
+}
+
+///# Curved Wire
+/// Chain of `cells` along a centerline, spaced by `cell_size` in m of arc
+/// length. The easy axis of every cell follows the local tangent, which
+/// models the shape anisotropy of a thin wire. A ring whose cells cover
+/// the whole circle is closed by making the last cell an exchange neighbor
+/// of the first.
+///
+/// The exchange is evaluated between the lab-frame magnetizations, so it
+/// contains the curvature-induced effective anisotropy and DMI-like terms
+/// of the local frame, see `curvature_anisotropy_density` and
+/// `curvature_dmi_constants`. The dipolar field and the field gradient
+/// still place the cells on a straight line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurvedWire {
+    pub centerline: Centerline,
+    pub material: Material,
+    pub cells: usize,
+    pub cell_size: f64,
+}
+
+impl CurvedWire {
+    ///# Closed Ring
+    /// Ring of `cells` whose circumference is exactly `cells` cell sizes.
+    pub fn ring(cells: usize, cell_size: f64, material: Material) -> Self {
+        Self {
+            centerline: Centerline::Ring {
+                radius: cells as f64 * cell_size / (2.0 * PI),
+            },
+            material,
+            cells,
+            cell_size,
+        }
+    }
+
+    ///# Arc Length
+    /// Arc length in m at the center of the cell.
+    pub fn arc_length(&self, cell: usize) -> f64 {
+        (cell as f64 + 0.5) * self.cell_size
+    }
+
+    ///# Frame of a Cell
+    pub fn frame(&self, cell: usize) -> FrenetFrame {
+        self.centerline.frame(self.arc_length(cell))
+    }
+
+    ///# Is Closed
+    /// A ring is closed when its cells cover the circumference to within half a cell.
+    pub fn is_closed(&self) -> bool {
+        matches!(self.centerline, Centerline::Ring { .. })
+            && (self.cells as f64 * self.cell_size - self.centerline.turn_length()).abs()
+                < 0.5 * self.cell_size
+    }
+
+    ///# Build System
+    /// Wire without applied field, magnetized along the local tangent.
+    pub fn build(&self) -> MicromagneticSystem {
+        let tangents: Vec<Array1<f64>> = (0..self.cells)
+            .map(|cell| Array1::from_vec(self.frame(cell).tangent.to_vec()))
+            .collect();
+        let mut system = MicromagneticSystem::from_magnetizations(tangents);
+        system.set_applied_field([0.0; 3]);
+        system.set_cell_size(self.cell_size);
+        for cell in 0..self.cells {
+            let material = Material {
+                easy_axis: self.frame(cell).tangent,
+                ..self.material
+            };
+            system.set_material(cell, material);
+        }
+        if self.is_closed() {
+            system
+                .set_neighbor_list(NeighborList::ring(self.cells))
+                .expect("the ring covers every cell of the wire");
+        }
+        system
+    }
+
+    ///# Local Components
+    /// Components of every magnetization along the tangent, normal and binormal.
+    pub fn local_components(&self, system: &MicromagneticSystem) -> Vec<[f64; 3]> {
+        let dot = |a: &[f64; 3], m: &Array1<f64>| (0..3).map(|k| a[k] * m[k]).sum::<f64>();
+        system
+            .get_magnetizations()
+            .iter()
+            .enumerate()
+            .map(|(cell, m)| {
+                let frame = self.frame(cell);
+                [
+                    dot(&frame.tangent, m),
+                    dot(&frame.normal, m),
+                    dot(&frame.binormal, m),
+                ]
+            })
+            .collect()
+    }
+
+    ///# Curvature-Induced Anisotropy Density
+    /// Energy density in J/m^3 that the exchange contributes in the local frame
+    /// for the local components `[m_T, m_N, m_B]`,
+    /// A [k^2 (m_T^2 + m_N^2) + t^2 (m_N^2 + m_B^2) - 2 k t m_T m_B].
+    pub fn curvature_anisotropy_density(&self, local: [f64; 3]) -> f64 {
+        let (curvature, torsion) = (self.centerline.curvature(), self.centerline.torsion());
+        let [t, n, b] = local;
+        self.material.exchange_constant
+            * (curvature * curvature * (t * t + n * n) + torsion * torsion * (n * n + b * b)
+                - 2.0 * curvature * torsion * t * b)
+    }
+
+    ///# Curvature-Induced DMI Constants
+    /// Strengths 2 A k and 2 A t in J/m^2 of the DMI-like terms
+    /// (m_T m_N' - m_N m_T') and (m_N m_B' - m_B m_N') in the local frame.
+    pub fn curvature_dmi_constants(&self) -> (f64, f64) {
+        let exchange_constant = self.material.exchange_constant;
+        (
+            2.0 * exchange_constant * self.centerline.curvature(),
+            2.0 * exchange_constant * self.centerline.torsion(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the Frenet frame and constant curvature and torsion of a helix
+    fn test_helix_frame() {
+        let helix = Centerline::Helix {
+            radius: 10e-9,
+            pitch: 20e-9,
+        };
+        let rise = 20e-9 / (2.0 * PI);
+        let squared = 10e-9_f64.powi(2) + rise * rise;
+        assert!((helix.curvature() - 10e-9 / squared).abs() < 1e-6 * helix.curvature());
+        assert!((helix.torsion() - rise / squared).abs() < 1e-6 * helix.torsion());
+
+        let (s, ds) = (7e-9, 1e-12);
+        let frame = helix.frame(s);
+        let (before, after) = (helix.point(s - ds), helix.point(s + ds));
+        for k in 0..3 {
+            let tangent = (after[k] - before[k]) / (2.0 * ds);
+            assert!((tangent - frame.tangent[k]).abs() < 1e-6);
+        }
+        // dT/ds = k N
+        let (t_before, t_after) = (helix.frame(s - ds).tangent, helix.frame(s + ds).tangent);
+        for k in 0..3 {
+            let derivative = (t_after[k] - t_before[k]) / (2.0 * ds);
+            assert!(
+                (derivative - helix.curvature() * frame.normal[k]).abs() < 1e-3 * helix.curvature()
+            );
+        }
+    }
+
+    #[test]
+    /// Test that the exchange energy of the vortex state of a ring is the curvature-induced anisotropy
+    fn test_ring_curvature_energy() {
+        let wire = CurvedWire::ring(40, 2.0e-9, Material::default());
+        assert!(wire.is_closed());
+        let mut system = wire.build();
+        let expected: f64 = (0..wire.cells)
+            .map(|_| wire.curvature_anisotropy_density([1.0, 0.0, 0.0]) * system.cell_volume())
+            .sum();
+        let exchange = system.compute_energies().exchange;
+        // The finite differences are short by (k dx)^2 / 12
+        assert!((exchange - expected).abs() < 5e-3 * expected);
+
+        // The closed vortex is stable against the tangential anisotropy and exchange
+        system.minimize_energy();
+        assert!(wire
+            .local_components(&system)
+            .iter()
+            .all(|m| m[0].abs() > 0.999));
+        assert!(system.get_interlayer_couplings().is_empty());
+        assert_eq!(system.get_neighbor_list().neighbors(0), &[1, 39]);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::anisotropy_profile::parse_profile;
+use crate::magnetic_moments::MicromagneticSystem;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+///# Damping Profile
+/// Gilbert damping constant varying along the chain, e.g. the enhanced
+/// damping near the interface of a multilayer or the gradient of a doped
+/// sample. Positions are measured from the first cell of the range. The
+/// damping enters both the dynamics and the relaxation steps.
+///
+/// ```toml
+/// [damping_profile]
+/// type = "exponential"
+/// start = 0.1
+/// end = 0.01
+/// decay_length = 3.0e-9
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum DampingProfile {
+    // alpha changes linearly from start at the first cell to end at the last cell
+    Linear {
+        start: f64,
+        end: f64,
+    },
+    // alpha = end + (start - end) exp(-x / decay_length) with x in m
+    Exponential {
+        start: f64,
+        end: f64,
+        decay_length: f64,
+    },
+    // Text file with one alpha per cell, or "position alpha" rows in m that
+    // are interpolated linearly
+    File {
+        path: PathBuf,
+    },
+}
+
+impl DampingProfile {
+    ///# Profile Values
+    /// Damping constant of each of `cells` consecutive cells with the edge
+    /// length `cell_size`.
+    pub fn values(&self, cells: usize, cell_size: f64) -> Result<Vec<f64>, Box<dyn Error>> {
+        let values = match self {
+            DampingProfile::Linear { start, end } => (0..cells)
+                .map(|i| {
+                    let fraction = if cells > 1 {
+                        i as f64 / (cells - 1) as f64
+                    } else {
+                        0.0
+                    };
+                    start + (end - start) * fraction
+                })
+                .collect(),
+            DampingProfile::Exponential {
+                start,
+                end,
+                decay_length,
+            } => {
+                if *decay_length <= 0.0 {
+                    return Err("The decay length of the damping profile must be positive".into());
+                }
+                (0..cells)
+                    .map(|i| {
+                        let x = i as f64 * cell_size;
+                        end + (start - end) * (-x / decay_length).exp()
+                    })
+                    .collect()
+            }
+            DampingProfile::File { path } => {
+                let text = fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                parse_profile(&text, cells, cell_size)?
+            }
+        };
+        if values.iter().any(|&damping| damping < 0.0) {
+            return Err("The damping profile must not be negative".into());
+        }
+        Ok(values)
+    }
+
+    ///# Apply Profile
+    /// Set the damping constant of the cells in the range, vacuum cells are skipped.
+    pub fn apply(
+        &self,
+        system: &mut MicromagneticSystem,
+        cells: Range<usize>,
+    ) -> Result<(), Box<dyn Error>> {
+        if cells.end > system.size() {
+            return Err(format!(
+                "Damping profile over {}..{} is outside the {} cells",
+                cells.start,
+                cells.end,
+                system.size()
+            )
+            .into());
+        }
+        let values = self.values(cells.len(), system.get_cell_size())?;
+        let materials = system.get_materials();
+        for (cell, damping) in cells.zip(values) {
+            if system.is_vacuum(cell) {
+                continue;
+            }
+            let mut material = materials[cell];
+            material.damping = damping;
+            system.set_material(cell, material);
+        }
+        Ok(())
+    }
+
+    ///# Resolve Path
+    /// Make a relative profile file relative to the given directory.
+    pub fn resolve_path(&mut self, directory: &Path) {
+        if let DampingProfile::File { path } = self {
+            if path.is_relative() {
+                *path = directory.join(&*path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DAMPING_CONSTANT;
+
+    #[test]
+    /// Test the profiles and their effect on the relaxation and dynamics
+    fn test_damping_profile() {
+        let linear = DampingProfile::Linear {
+            start: 0.0,
+            end: 0.75,
+        };
+        assert_eq!(linear.values(4, 1e-9).unwrap(), vec![0.0, 0.25, 0.5, 0.75]);
+        let exponential = DampingProfile::Exponential {
+            start: 0.1,
+            end: 0.01,
+            decay_length: 1.0e-9,
+        };
+        let values = exponential.values(3, 1e-9).unwrap();
+        assert_eq!(values[0], 0.1);
+        assert!((values[2] - (0.01 + 0.09 * (-2.0f64).exp())).abs() < 1e-15);
+        let negative = DampingProfile::Linear {
+            start: 0.1,
+            end: -0.1,
+        };
+        assert!(negative.values(3, 1e-9).is_err());
+
+        let mut system = MicromagneticSystem::new(6);
+        system.set_saturation_magnetization(4, 0.0);
+        linear.apply(&mut system, 1..5).unwrap();
+        let dampings: Vec<f64> = system.get_materials().iter().map(|m| m.damping).collect();
+        assert_eq!(dampings[0], DAMPING_CONSTANT);
+        assert_eq!(dampings[1], 0.0);
+        assert_eq!(dampings[3], 0.5);
+        // The vacuum cell keeps its material
+        assert_eq!(dampings[4], DAMPING_CONSTANT);
+        assert!(linear.apply(&mut system, 3..7).is_err());
+
+        // Undamped cells do not move in a relaxation step
+        system.set_applied_field([0.0, 0.0, 1.0]);
+        let before = system.get_magnetizations();
+        system.relaxation_step();
+        let after = system.get_magnetizations();
+        let change = |cell: usize| (&after[cell] - &before[cell]).mapv(f64::abs).sum();
+        assert!(change(1) < 1e-12);
+        assert!(change(2) > 1e-6);
+    }
+}
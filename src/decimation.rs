@@ -0,0 +1,155 @@
+use crate::grid::Grid;
+use ndarray::Array1;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+///# Decimation
+/// Down-sampling of large exports: every `cell_stride`-th cell along each
+/// axis of the grid, starting with the first, of every `snapshot_stride`-th
+/// snapshot, with the
+/// magnetization rounded to single precision if requested. The default
+/// keeps everything at full precision.
+///
+/// ```toml
+/// [decimation]
+/// cell_stride = 10
+/// snapshot_stride = 5
+/// single_precision = true
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Decimation {
+    #[serde(default = "default_stride")]
+    pub cell_stride: usize,
+    #[serde(default = "default_stride")]
+    pub snapshot_stride: usize,
+    // f32 values, Binary 4 OVF data
+    #[serde(default)]
+    pub single_precision: bool,
+}
+
+fn default_stride() -> usize {
+    1
+}
+
+impl Default for Decimation {
+    fn default() -> Self {
+        Self {
+            cell_stride: default_stride(),
+            snapshot_stride: default_stride(),
+            single_precision: false,
+        }
+    }
+}
+
+impl Decimation {
+    ///# Validate
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.cell_stride == 0 || self.snapshot_stride == 0 {
+            return Err("The decimation strides must be at least 1".into());
+        }
+        Ok(())
+    }
+
+    ///# Kept Cells
+    /// Indices of the exported cells of the grid, with x varying fastest.
+    pub fn cells(&self, grid: Grid) -> impl Iterator<Item = usize> {
+        let stride = self.cell_stride.max(1);
+        (0..grid.nz).step_by(stride).flat_map(move |z| {
+            (0..grid.ny).step_by(stride).flat_map(move |y| {
+                (0..grid.nx)
+                    .step_by(stride)
+                    .map(move |x| grid.index(x, y, z))
+            })
+        })
+    }
+
+    ///# Decimated Grid
+    /// The grid of the exported cells.
+    pub fn grid(&self, grid: Grid) -> Grid {
+        let stride = self.cell_stride.max(1);
+        Grid::new(
+            grid.nx.div_ceil(stride),
+            grid.ny.div_ceil(stride),
+            grid.nz.div_ceil(stride),
+        )
+    }
+
+    ///# Kept Snapshot
+    /// Whether the snapshot with the index among all sampled ones is exported.
+    pub fn keeps_snapshot(&self, index: usize) -> bool {
+        index.is_multiple_of(self.snapshot_stride.max(1))
+    }
+
+    ///# Value
+    /// The value as it is exported, rounded to the nearest f32 in single precision.
+    pub fn value(&self, value: f64) -> f64 {
+        if self.single_precision {
+            value as f32 as f64
+        } else {
+            value
+        }
+    }
+
+    ///# Decimate Magnetizations
+    /// The exported magnetizations of the kept cells of the grid.
+    pub fn magnetizations(&self, grid: Grid, magnetizations: &[Array1<f64>]) -> Vec<[f64; 3]> {
+        self.cells(grid)
+            .map(|cell| {
+                let m = &magnetizations[cell];
+                [self.value(m[0]), self.value(m[1]), self.value(m[2])]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    /// Test the kept cells, snapshots and the rounding of the values
+    fn test_decimation() {
+        assert!(Decimation::default().validate().is_ok());
+        let decimation = Decimation {
+            cell_stride: 3,
+            snapshot_stride: 2,
+            single_precision: true,
+        };
+        assert!(decimation.validate().is_ok());
+        assert_eq!(
+            decimation.cells(Grid::chain(7)).collect::<Vec<_>>(),
+            vec![0, 3, 6]
+        );
+        assert_eq!((0..5).filter(|&i| decimation.keeps_snapshot(i)).count(), 3);
+
+        let third = 1.0 / 3.0;
+        let magnetizations = vec![array![third, 0.0, 1.0]; 4];
+        let decimated = decimation.magnetizations(Grid::chain(4), &magnetizations);
+        assert_eq!(decimated.len(), 2);
+        assert_eq!(decimated[1][0], third as f32 as f64);
+        assert_ne!(decimated[1][0], third);
+        assert_eq!(
+            Decimation::default().magnetizations(Grid::chain(4), &magnetizations)[3][0],
+            third
+        );
+
+        let invalid = Decimation {
+            cell_stride: 0,
+            ..Decimation::default()
+        };
+        assert!(invalid.validate().is_err());
+        // Every third cell along both axes of a 7 x 4 film
+        let film = Grid::new(7, 4, 1);
+        assert_eq!(
+            decimation.cells(film).collect::<Vec<_>>(),
+            vec![0, 3, 6, 21, 24, 27]
+        );
+        assert_eq!(decimation.grid(film), Grid::new(3, 2, 1));
+
+        let parsed: Decimation = toml::from_str("cell_stride = 4").unwrap();
+        assert_eq!(parsed.snapshot_stride, 1);
+        assert!(!parsed.single_precision);
+    }
+}
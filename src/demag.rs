@@ -0,0 +1,429 @@
+use crate::grid::Grid;
+use crate::parallel::map_cells;
+use ndarray::Array1;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fmt;
+use std::sync::Arc;
+
+// Displacements in cells beyond which the tensor is that of point dipoles,
+// the Newell functions lose precision to cancellation far away
+const POINT_DIPOLE_DISTANCE: f64 = 30.0;
+
+// Repetitions of a periodic grid on either side along a periodic axis
+pub const DEFAULT_PERIODIC_IMAGES: usize = 10;
+
+///# Dipolar Method
+/// How the dipole-dipole field of the dipolar interaction is computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DipolarMethod {
+    // O(N^2) sum over point dipoles, see `direct_dipolar_field_at`
+    #[default]
+    Direct,
+    // O(N log N) convolution with the demagnetizing tensor of the cubic cells
+    Fft,
+}
+
+///# Demagnetization Kernel
+/// Demagnetizing tensor N of the cubic cells of a grid (Newell, Williams
+/// and Dunlop, J. Geophys. Res. 98, 9551 (1993)), the field at cell i is
+/// H_i = -sum_j N(r_i - r_j) Ms_j m_j. The tensor averages the field of
+/// a homogeneously magnetized cube over the target cube, so it is exact
+/// for the cell discretization including the self term -Ms m / 3 of a
+/// cell, and the uniformly magnetized grid has the demagnetizing factors
+/// of the prism. The convolution runs on a grid zero padded to twice the
+/// size along every axis with more than one cell, which keeps the
+/// periodic FFT convolution free of wrap-around.
+///
+/// Along periodic axes the grid is not padded, and the tensor of every
+/// displacement sums the nearest image and `images` repetitions of the
+/// grid on either side, the macro geometry of a finite stack of copies.
+/// A periodic film then has no magnetic charges at its cut edges, and
+/// its uniform states approach the demagnetizing factors of the infinite
+/// film with the number of images.
+pub struct DemagnetizationKernel {
+    grid: Grid,
+    // Axes along which the grid repeats, and the repetitions on either side
+    periodic: [bool; 3],
+    images: usize,
+    // Padded size along x, y and z
+    padded: [usize; 3],
+    // Fourier transforms of Nxx, Nyy, Nzz, Nxy, Nxz, Nyz on the padded grid
+    tensor: [Vec<Complex<f64>>; 6],
+    // Forward and inverse plans along x, y and z
+    forward: [Arc<dyn Fft<f64>>; 3],
+    inverse: [Arc<dyn Fft<f64>>; 3],
+}
+
+impl fmt::Debug for DemagnetizationKernel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DemagnetizationKernel")
+            .field("grid", &self.grid)
+            .field("periodic", &self.periodic)
+            .field("images", &self.images)
+            .field("padded", &self.padded)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DemagnetizationKernel {
+    ///# New Demagnetization Kernel
+    /// Tabulate and transform the tensor of every displacement within the grid.
+    pub fn new(grid: Grid) -> Self {
+        Self::with_periodic_images(grid, [false; 3], 0)
+    }
+
+    ///# Periodic Demagnetization Kernel
+    /// Tabulate the tensor of a grid that repeats along the given axes,
+    /// with `images` copies on either side of it.
+    pub fn with_periodic_images(grid: Grid, periodic: [bool; 3], images: usize) -> Self {
+        let dimensions = [grid.nx, grid.ny, grid.nz];
+        let padded = std::array::from_fn(|axis| {
+            let n = dimensions[axis];
+            if n > 1 && !periodic[axis] {
+                2 * n
+            } else {
+                n
+            }
+        });
+        let total = padded[0] * padded[1] * padded[2];
+        let repetitions = images as isize;
+        // Displacements of a padded index, negative ones wrap around. Along
+        // a periodic axis the nearest image and its repetitions.
+        let displacements = |index: usize, axis: usize| -> Vec<f64> {
+            let (size, n) = (padded[axis], dimensions[axis]);
+            if periodic[axis] {
+                let nearest = if 2 * index <= n {
+                    index as f64
+                } else {
+                    index as f64 - n as f64
+                };
+                (-repetitions..=repetitions)
+                    .map(|copy| nearest + (copy * n as isize) as f64)
+                    .collect()
+            } else if index < n {
+                vec![index as f64]
+            } else if index > size - n {
+                vec![index as f64 - size as f64]
+            } else {
+                Vec::new()
+            }
+        };
+        let entries = map_cells(total, |index| {
+            let xs = displacements(index % padded[0], 0);
+            let ys = displacements((index / padded[0]) % padded[1], 1);
+            let zs = displacements(index / (padded[0] * padded[1]), 2);
+            let mut entry = [0.0; 6];
+            for &x in &xs {
+                for &y in &ys {
+                    for &z in &zs {
+                        let tensor = demagnetization_tensor([x, y, z]);
+                        for k in 0..6 {
+                            entry[k] += tensor[k];
+                        }
+                    }
+                }
+            }
+            entry
+        });
+
+        let mut planner = FftPlanner::new();
+        let forward = padded.map(|n| planner.plan_fft_forward(n));
+        let inverse = padded.map(|n| planner.plan_fft_inverse(n));
+        let tensor = std::array::from_fn(|component| {
+            let mut data: Vec<Complex<f64>> = entries
+                .iter()
+                .map(|entry| Complex::new(entry[component], 0.0))
+                .collect();
+            transform(&mut data, padded, &forward);
+            data
+        });
+        Self {
+            grid,
+            periodic,
+            images,
+            padded,
+            tensor,
+            forward,
+            inverse,
+        }
+    }
+
+    ///# Grid
+    pub fn grid(&self) -> Grid {
+        self.grid
+    }
+
+    ///# Periodic Axes
+    /// Axes along which the grid repeats and the copies on either side.
+    pub fn periodic_images(&self) -> ([bool; 3], usize) {
+        (self.periodic, self.images)
+    }
+
+    ///# Demagnetizing Field
+    /// Field in A/m of every cell for the magnetizations and saturation
+    /// magnetizations of the cells, vacuum cells carry no moment.
+    pub fn field(
+        &self,
+        magnetizations: &[Array1<f64>],
+        saturation_magnetizations: &[f64],
+    ) -> Vec<Array1<f64>> {
+        let size = self.grid.size();
+        assert_eq!(magnetizations.len(), size, "one magnetization per cell");
+        let [px, py, _] = self.padded;
+        let total = self.tensor[0].len();
+        let padded_index = |cell: usize| {
+            let [x, y, z] = self.grid.coordinates(cell);
+            x + px * (y + py * z)
+        };
+
+        let moments = map_cells(3, |component| {
+            let mut data = vec![Complex::new(0.0, 0.0); total];
+            for cell in 0..size {
+                data[padded_index(cell)] = Complex::new(
+                    saturation_magnetizations[cell] * magnetizations[cell][component],
+                    0.0,
+                );
+            }
+            transform(&mut data, self.padded, &self.forward);
+            data
+        });
+
+        // Symmetric tensor component of the row and column
+        let element = |row: usize, column: usize| match (row.min(column), row.max(column)) {
+            (0, 0) => 0,
+            (1, 1) => 1,
+            (2, 2) => 2,
+            (0, 1) => 3,
+            (0, 2) => 4,
+            _ => 5,
+        };
+        let fields = map_cells(3, |row| {
+            let mut data: Vec<Complex<f64>> = (0..total)
+                .map(|k| {
+                    -(0..3)
+                        .map(|column| self.tensor[element(row, column)][k] * moments[column][k])
+                        .sum::<Complex<f64>>()
+                })
+                .collect();
+            transform(&mut data, self.padded, &self.inverse);
+            data
+        });
+
+        let scale = 1.0 / total as f64;
+        (0..size)
+            .map(|cell| {
+                let k = padded_index(cell);
+                Array1::from_iter(fields.iter().map(|field| field[k].re * scale))
+            })
+            .collect()
+    }
+}
+
+///# Demagnetization Tensor
+/// Components [Nxx, Nyy, Nzz, Nxy, Nxz, Nyz] between two cubic cells at
+/// the displacement in units of the cell size. The tensor of a cell with
+/// itself is 1/3 on the diagonal.
+pub fn demagnetization_tensor(displacement: [f64; 3]) -> [f64; 6] {
+    let [x, y, z] = displacement;
+    let distance_squared = x * x + y * y + z * z;
+    if distance_squared > POINT_DIPOLE_DISTANCE * POINT_DIPOLE_DISTANCE {
+        // N = (I / r^3 - 3 r r / r^5) / (4 pi) of a point dipole
+        let distance = distance_squared.sqrt();
+        let factor = 1.0 / (4.0 * PI * distance_squared * distance);
+        let off_diagonal = |a: f64, b: f64| -3.0 * factor * a * b / distance_squared;
+        return [
+            factor + off_diagonal(x, x),
+            factor + off_diagonal(y, y),
+            factor + off_diagonal(z, z),
+            off_diagonal(x, y),
+            off_diagonal(x, z),
+            off_diagonal(y, z),
+        ];
+    }
+    [
+        second_difference(newell_f, [x, y, z]),
+        second_difference(newell_f, [y, x, z]),
+        second_difference(newell_f, [z, y, x]),
+        second_difference(newell_g, [x, y, z]),
+        second_difference(newell_g, [x, z, y]),
+        second_difference(newell_g, [y, z, x]),
+    ]
+}
+
+// Sum over the 27 neighbors of the displacement with the weights 2 for a
+// zero and -1 for a unit offset along each axis, divided by 4 pi
+fn second_difference(function: fn(f64, f64, f64) -> f64, [x, y, z]: [f64; 3]) -> f64 {
+    let weight = |offset: i32| if offset == 0 { 2.0 } else { -1.0 };
+    let mut sum = 0.0;
+    for i in -1..=1 {
+        for j in -1..=1 {
+            for k in -1..=1 {
+                sum += weight(i)
+                    * weight(j)
+                    * weight(k)
+                    * function(x + i as f64, y + j as f64, z + k as f64);
+            }
+        }
+    }
+    sum / (4.0 * PI)
+}
+
+// Newell's f, whose second differences give the diagonal components
+fn newell_f(x: f64, y: f64, z: f64) -> f64 {
+    let (x, y, z) = (x.abs(), y.abs(), z.abs());
+    let (x2, y2, z2) = (x * x, y * y, z * z);
+    let r = (x2 + y2 + z2).sqrt();
+    let mut value = (2.0 * x2 - y2 - z2) * r / 6.0;
+    if y > 0.0 && x2 + z2 > 0.0 {
+        value += 0.5 * y * (z2 - x2) * (y / (x2 + z2).sqrt()).asinh();
+    }
+    if z > 0.0 && x2 + y2 > 0.0 {
+        value += 0.5 * z * (y2 - x2) * (z / (x2 + y2).sqrt()).asinh();
+    }
+    if x > 0.0 && y * z > 0.0 {
+        value -= x * y * z * (y * z / (x * r)).atan();
+    }
+    value
+}
+
+// Newell's g, whose second differences give the off-diagonal components,
+// odd in x and y
+fn newell_g(x: f64, y: f64, z: f64) -> f64 {
+    let sign = x.signum() * y.signum();
+    let (x, y, z) = (x.abs(), y.abs(), z.abs());
+    if x == 0.0 || y == 0.0 {
+        return 0.0;
+    }
+    let (x2, y2, z2) = (x * x, y * y, z * z);
+    let r = (x2 + y2 + z2).sqrt();
+    let mut value = -x * y * r / 3.0;
+    if z > 0.0 {
+        value += x * y * z * (z / (x2 + y2).sqrt()).asinh();
+        value -= z * z2 / 6.0 * (x * y / (z * r)).atan();
+        value -= 0.5 * z * y2 * (x * z / (y * r)).atan();
+        value -= 0.5 * z * x2 * (y * z / (x * r)).atan();
+    }
+    value += y / 6.0 * (3.0 * z2 - y2) * (x / (y2 + z2).sqrt()).asinh();
+    value += x / 6.0 * (3.0 * z2 - x2) * (y / (x2 + z2).sqrt()).asinh();
+    sign * value
+}
+
+// In-place 3D FFT of data with x varying fastest
+fn transform(data: &mut [Complex<f64>], padded: [usize; 3], plans: &[Arc<dyn Fft<f64>>; 3]) {
+    let [px, py, pz] = padded;
+    if px > 1 {
+        plans[0].process(data);
+    }
+    let mut line = Vec::new();
+    for (axis, stride, length) in [(1, px, py), (2, px * py, pz)] {
+        if length == 1 {
+            continue;
+        }
+        line.resize(length, Complex::new(0.0, 0.0));
+        let lines = data.len() / length;
+        for start in (0..lines).map(|k| (k % stride) + (k / stride) * stride * length) {
+            for (j, value) in line.iter_mut().enumerate() {
+                *value = data[start + j * stride];
+            }
+            plans[axis].process(&mut line);
+            for (j, value) in line.iter().enumerate() {
+                data[start + j * stride] = *value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dipolar::prism_demagnetization_factors;
+    use crate::SPATIAL_DISCRETION_STEP;
+
+    #[test]
+    /// Test the tensor and the FFT field against the prism and a direct convolution
+    fn test_demagnetization_kernel() {
+        let own = demagnetization_tensor([0.0; 3]);
+        for k in 0..3 {
+            assert!((own[k] - 1.0 / 3.0).abs() < 1e-12);
+            assert!(own[k + 3].abs() < 1e-12);
+        }
+        // The cells look like point dipoles from afar, on both sides of the switch
+        for distance in [29.9, 30.1] {
+            let tensor = demagnetization_tensor([distance, 0.0, 0.0]);
+            let dipole = 1.0 / (4.0 * PI * distance.powi(3));
+            assert!((tensor[0] + 2.0 * dipole).abs() < 1e-5 * dipole);
+            assert!((tensor[1] - dipole).abs() < 1e-5 * dipole);
+        }
+        let tensor = demagnetization_tensor([2.0, -1.0, 3.0]);
+        assert!((tensor[0] + tensor[1] + tensor[2]).abs() < 1e-12);
+        assert!(tensor[3] > 0.0 && tensor[4] < 0.0 && tensor[5] > 0.0);
+
+        // The uniformly magnetized block has the demagnetizing factors of the prism
+        let grid = Grid::new(8, 4, 2);
+        let size = grid.size();
+        let kernel = DemagnetizationKernel::new(grid);
+        let ms = vec![1.0e6; size];
+        let direction = [0.6, 0.0, 0.8];
+        let uniform = vec![Array1::from_vec(direction.to_vec()); size];
+        let field = kernel.field(&uniform, &ms);
+        let factors =
+            prism_demagnetization_factors([8.0, 4.0, 2.0].map(|n| n * SPATIAL_DISCRETION_STEP));
+        for k in 0..3 {
+            let average = field.iter().map(|h| h[k]).sum::<f64>() / size as f64;
+            let expected = -1.0e6 * factors[k] * direction[k];
+            assert!((average - expected).abs() < 1e-9 * 1.0e6);
+        }
+
+        // A periodic film has the same field in every cell of a uniform
+        // state, close to that of the infinite film
+        let film = Grid::new(8, 8, 1);
+        let periodic = DemagnetizationKernel::with_periodic_images(film, [true, true, false], 20);
+        let ms = vec![1.0e6; film.size()];
+        for (k, limit) in [(0, 0.0), (2, 1.0)] {
+            let mut direction = [0.0; 3];
+            direction[k] = 1.0;
+            let uniform = vec![Array1::from_vec(direction.to_vec()); film.size()];
+            let field = periodic.field(&uniform, &ms);
+            let factor = -field[0][k] / 1.0e6;
+            assert!((factor - limit).abs() < 0.01);
+            assert!(field
+                .iter()
+                .all(|h| (h[k] - field[0][k]).abs() < 1e-9 * 1.0e6));
+        }
+        assert_eq!(periodic.periodic_images(), ([true, true, false], 20));
+
+        // The FFT convolution agrees with the sum over every pair of cells
+        let magnetizations: Vec<Array1<f64>> = (0..size)
+            .map(|i| {
+                let angle = i as f64;
+                Array1::from_vec(vec![angle.cos(), angle.sin() * 0.6, angle.sin() * 0.8])
+            })
+            .collect();
+        let mut ms = ms;
+        ms[5] = 0.0;
+        let field = kernel.field(&magnetizations, &ms);
+        for i in [0, 5, 17, size - 1] {
+            let position = grid.coordinates(i);
+            let mut expected = [0.0; 3];
+            for j in 0..size {
+                let source = grid.coordinates(j);
+                let n = demagnetization_tensor(std::array::from_fn(|c| {
+                    position[c] as f64 - source[c] as f64
+                }));
+                let tensor = [[n[0], n[3], n[4]], [n[3], n[1], n[5]], [n[4], n[5], n[2]]];
+                for r in 0..3 {
+                    for c in 0..3 {
+                        expected[r] -= tensor[r][c] * ms[j] * magnetizations[j][c];
+                    }
+                }
+            }
+            for r in 0..3 {
+                assert!((field[i][r] - expected[r]).abs() < 1e-9 * 1.0e6);
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::magnetic_moments::{
+    DampingSchedule, MicromagneticSystem, MinimizationOutcome, Minimizer,
+};
+use std::fmt;
+
+// Steps at the end of the history that the stall and oscillation checks look at
+const DIAGNOSIS_WINDOW: usize = 200;
+// Relative energy change over the window below which the energy is flat
+const STALL_ENERGY_CHANGE: f64 = 1e-12;
+// Fraction of the steps in the window that may raise the energy
+const OSCILLATION_FRACTION: f64 = 0.25;
+
+///# Recommendation
+/// Parameter change that is likely to help an unconverged minimization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recommendation {
+    // The torque was still decreasing when the iteration limit was reached
+    IncreaseIterations,
+    // The relaxation is slow with the material damping
+    EnableAdaptiveDamping,
+    // The energy rises in many steps, the step is too large
+    ReduceStepSize,
+    // The energy no longer changes, but the torque is above the tolerance
+    SwitchMinimizer,
+}
+
+impl fmt::Display for Recommendation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Self::IncreaseIterations => "the torque was still decreasing, allow more iterations",
+            Self::EnableAdaptiveDamping => "set adaptive_damping to speed up the relaxation",
+            Self::ReduceStepSize => {
+                "the energy oscillates, set oscillation_policy = \"reduce_step_size\""
+            }
+            Self::SwitchMinimizer => {
+                "the energy stalled, try minimizer = \"spherical_conjugate_gradient\""
+            }
+        };
+        write!(f, "{}", text)
+    }
+}
+
+///# Convergence Diagnostics
+/// History of a minimization with flags for the usual ways it fails
+/// and the parameter changes that are likely to help.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvergenceDiagnostics {
+    pub outcome: MinimizationOutcome,
+    // Total energy in J before the first and after every step
+    pub energies: Vec<f64>,
+    // Maximum torque |m x H| in A/m before the first and after every step
+    pub torques: Vec<f64>,
+    // The energy stopped changing before convergence
+    pub stalled: bool,
+    // The energy rose in many of the last steps
+    pub oscillating: bool,
+    pub recommendations: Vec<Recommendation>,
+}
+
+impl ConvergenceDiagnostics {
+    ///# Diagnose
+    /// Judge a recorded minimization of `system` from its history.
+    pub fn diagnose(
+        system: &MicromagneticSystem,
+        outcome: MinimizationOutcome,
+        energies: Vec<f64>,
+        torques: Vec<f64>,
+    ) -> Self {
+        let converged = matches!(outcome, MinimizationOutcome::Converged { .. });
+        let window = DIAGNOSIS_WINDOW.min(energies.len().saturating_sub(1));
+        let recent = &energies[energies.len() - window - 1..];
+        let (mut stalled, mut oscillating) = (false, false);
+        if !converged && window > 0 {
+            let scale = recent.iter().fold(0.0_f64, |scale, e| scale.max(e.abs()));
+            let change = (recent[window] - recent[0]).abs();
+            stalled = change <= STALL_ENERGY_CHANGE * scale;
+            let rising = recent.windows(2).filter(|pair| pair[1] > pair[0]).count();
+            oscillating = rising as f64 > OSCILLATION_FRACTION * window as f64;
+        }
+
+        let mut recommendations = Vec::new();
+        if matches!(outcome, MinimizationOutcome::NotConverged { .. }) {
+            let recent_torques = &torques[torques.len() - window - 1..];
+            if oscillating {
+                recommendations.push(Recommendation::ReduceStepSize);
+            }
+            if stalled && system.get_minimizer() == Minimizer::Relaxation {
+                recommendations.push(Recommendation::SwitchMinimizer);
+            }
+            if !oscillating && !stalled {
+                if system.get_damping_schedule() == DampingSchedule::Material
+                    && system.get_minimizer() == Minimizer::Relaxation
+                {
+                    recommendations.push(Recommendation::EnableAdaptiveDamping);
+                }
+                if recent_torques[window] < recent_torques[0] {
+                    recommendations.push(Recommendation::IncreaseIterations);
+                }
+            }
+        }
+        Self {
+            outcome,
+            energies,
+            torques,
+            stalled,
+            oscillating,
+            recommendations,
+        }
+    }
+
+    ///# Converged
+    pub fn converged(&self) -> bool {
+        matches!(self.outcome, MinimizationOutcome::Converged { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magnetic_moments::OscillationPolicy;
+    use crate::material::Material;
+    use ndarray::array;
+
+    #[test]
+    /// Test the diagnostics of a converged and of an oscillating minimization
+    fn test_convergence_diagnostics() {
+        let mut system = MicromagneticSystem::new(5);
+        let diagnostics = system.minimize_energy_with_diagnostics(|_, _| true);
+        assert!(diagnostics.converged());
+        assert_eq!(diagnostics.energies.len(), diagnostics.torques.len());
+        assert!(diagnostics.energies.last() <= diagnostics.energies.first());
+        assert!(diagnostics.torques.last() < diagnostics.torques.first());
+        assert!(!diagnostics.stalled && !diagnostics.oscillating);
+        assert!(diagnostics.recommendations.is_empty());
+
+        let hard = Material {
+            anisotropy_constant: 1e8,
+            damping: 1.0,
+            ..Material::default()
+        };
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![0.6, 0.3, 0.5]; 3]);
+        for cell in 0..3 {
+            system.set_material(cell, hard);
+        }
+        system.set_oscillation_policy(OscillationPolicy::Ignore);
+        let diagnostics = system.minimize_energy_with_diagnostics(|_, _| true);
+        assert!(!diagnostics.converged());
+        assert!(diagnostics.oscillating);
+        assert_eq!(
+            diagnostics.recommendations,
+            vec![Recommendation::ReduceStepSize]
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use crate::grid::Grid;
+use ndarray::Array1;
+use std::f64::consts::PI;
+
+///# Direct Dipolar Field
+/// Exact dipole-dipole sum of the field at cell `i` of the grid in A/m,
+/// treating every other cell as a point dipole of moment Ms V m, with the
+/// volume V of a cube of the edge length `cell_size`.
+///
+/// H_i = 1/(4 pi) sum_j [3 (mu_j . r) r / r^5 - mu_j / r^3],  r = r_i - r_j
+///
+/// The cost is O(N) per cell and O(N^2) for the whole system, which is
+/// meant for systems of up to a few thousand cells and as a reference for
+/// faster demagnetization methods. The self term of a cubic cell,
+/// -Ms m / 3, is parallel to m, exerts no torque and is left out.
+pub fn direct_dipolar_field_at(
+    grid: &Grid,
+    cell_size: f64,
+    i: usize,
+    magnetizations: &[Array1<f64>],
+    saturation_magnetizations: &[f64],
+) -> Array1<f64> {
+    periodic_dipolar_field_at(
+        grid,
+        cell_size,
+        i,
+        magnetizations,
+        saturation_magnetizations,
+        [false; 3],
+        0,
+    )
+}
+
+///# Periodic Dipolar Field
+/// Direct dipole-dipole sum at cell `i` of a grid that repeats along the
+/// periodic axes. Every other cell contributes its nearest image and the
+/// `images` repetitions of it on either side, so the field only depends
+/// on the displacements modulo the grid and a periodic film has no cut
+/// edges. Only the cell itself is left out of the sum.
+pub fn periodic_dipolar_field_at(
+    grid: &Grid,
+    cell_size: f64,
+    i: usize,
+    magnetizations: &[Array1<f64>],
+    saturation_magnetizations: &[f64],
+    periodic: [bool; 3],
+    images: usize,
+) -> Array1<f64> {
+    let field = dipolar_field_of_cells(
+        grid,
+        cell_size,
+        grid.position(i, cell_size),
+        magnetizations,
+        saturation_magnetizations,
+        periodic,
+        images,
+    );
+    Array1::from_vec(field.to_vec())
+}
+
+///# Dipolar Field at a Point
+/// Field in A/m of all cells, as point dipoles, at an arbitrary position in
+/// m. Points inside the sample are meaningless, and the point dipoles
+/// describe the cubic cells well from a distance of a few cell sizes on.
+pub fn dipolar_field_at_point(
+    grid: &Grid,
+    cell_size: f64,
+    point: [f64; 3],
+    magnetizations: &[Array1<f64>],
+    saturation_magnetizations: &[f64],
+) -> [f64; 3] {
+    dipolar_field_of_cells(
+        grid,
+        cell_size,
+        point,
+        magnetizations,
+        saturation_magnetizations,
+        [false; 3],
+        0,
+    )
+}
+
+// Sum of the point dipole fields of the cells and their periodic images at
+// the target, leaving out a cell at the target
+fn dipolar_field_of_cells(
+    grid: &Grid,
+    cell_size: f64,
+    target: [f64; 3],
+    magnetizations: &[Array1<f64>],
+    saturation_magnetizations: &[f64],
+    periodic: [bool; 3],
+    images: usize,
+) -> [f64; 3] {
+    let lengths = [grid.nx, grid.ny, grid.nz].map(|n| n as f64 * cell_size);
+    let repetitions = images as isize;
+    let copies = |axis: usize| -> Vec<f64> {
+        if !periodic[axis] {
+            return vec![0.0];
+        }
+        (-repetitions..=repetitions)
+            .map(|copy| copy as f64 * lengths[axis])
+            .collect()
+    };
+    let (xs, ys, zs) = (copies(0), copies(1), copies(2));
+    let mut field = [0.0; 3];
+    for (j, m) in magnetizations.iter().enumerate() {
+        if saturation_magnetizations[j] == 0.0 {
+            continue;
+        }
+        let source = grid.position(j, cell_size);
+        // The nearest image along the periodic axes, ties go to the
+        // positive one as in the FFT kernel despite the rounding of the
+        // positions
+        let nearest: [f64; 3] = std::array::from_fn(|k| {
+            let r = target[k] - source[k];
+            if periodic[k] {
+                r - lengths[k] * (r / lengths[k] + 0.5 - 1e-9).floor()
+            } else {
+                r
+            }
+        });
+        let moment_volume = saturation_magnetizations[j] * cell_size.powi(3);
+        let moment = [
+            moment_volume * m[0],
+            moment_volume * m[1],
+            moment_volume * m[2],
+        ];
+        for &x in &xs {
+            for &y in &ys {
+                for &z in &zs {
+                    let r = [nearest[0] + x, nearest[1] + y, nearest[2] + z];
+                    let distance_squared = r[0] * r[0] + r[1] * r[1] + r[2] * r[2];
+                    if distance_squared == 0.0 {
+                        continue;
+                    }
+                    let distance = distance_squared.sqrt();
+                    let moment_dot_r = moment[0] * r[0] + moment[1] * r[1] + moment[2] * r[2];
+                    for k in 0..3 {
+                        field[k] += (3.0 * moment_dot_r * r[k] / distance_squared - moment[k])
+                            / (4.0 * PI * distance_squared * distance);
+                    }
+                }
+            }
+        }
+    }
+    field
+}
+
+///# Prism Demagnetization Factors
+/// Demagnetizing factors [Nx, Ny, Nz] of a homogeneously magnetized
+/// rectangular prism with the edge lengths `dimensions` in m along x, y
+/// and z, from the closed form of A. Aharoni, J. Appl. Phys. 83, 3432 (1998).
+/// They sum to one, and -Ms N m is the average demagnetizing field.
+pub fn prism_demagnetization_factors(dimensions: [f64; 3]) -> [f64; 3] {
+    let [a, b, c] = dimensions.map(|length| 0.5 * length);
+    [
+        aharoni_factor(b, c, a),
+        aharoni_factor(c, a, b),
+        aharoni_factor(a, b, c),
+    ]
+}
+
+// Demagnetizing factor along the half edge c of a prism with the half edges a, b, c
+fn aharoni_factor(a: f64, b: f64, c: f64) -> f64 {
+    let (a2, b2, c2) = (a * a, b * b, c * c);
+    let abc = (a2 + b2 + c2).sqrt();
+    let ab = (a2 + b2).sqrt();
+    let bc = (b2 + c2).sqrt();
+    let ac = (a2 + c2).sqrt();
+    let factor = (b2 - c2) / (2.0 * b * c) * ((abc - a) / (abc + a)).ln()
+        + (a2 - c2) / (2.0 * a * c) * ((abc - b) / (abc + b)).ln()
+        + b / (2.0 * c) * ((ab + a) / (ab - a)).ln()
+        + a / (2.0 * c) * ((ab + b) / (ab - b)).ln()
+        + c / (2.0 * a) * ((bc - b) / (bc + b)).ln()
+        + c / (2.0 * b) * ((ac - a) / (ac + a)).ln()
+        + 2.0 * (a * b / (c * abc)).atan()
+        + (a * a2 + b * b2 - 2.0 * c * c2) / (3.0 * a * b * c)
+        + (a2 + b2 - 2.0 * c2) / (3.0 * a * b * c) * abc
+        + c / (a * b) * (ac + bc)
+        - ((a2 + b2).powf(1.5) + (b2 + c2).powf(1.5) + (c2 + a2).powf(1.5)) / (3.0 * a * b * c);
+    factor / PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    /// Test the demagnetizing factors of a cube, a thin film and a long rod
+    fn test_prism_demagnetization_factors() {
+        let cube = prism_demagnetization_factors([1e-9; 3]);
+        assert!(cube.iter().all(|n| (n - 1.0 / 3.0).abs() < 1e-12));
+        for dimensions in [
+            [100e-9, 100e-9, 1e-9],
+            [2e-9, 2e-9, 20e-9],
+            [1e-9, 2e-9, 3e-9],
+        ] {
+            let factors = prism_demagnetization_factors(dimensions);
+            assert!((factors.iter().sum::<f64>() - 1.0).abs() < 1e-10);
+        }
+        let film = prism_demagnetization_factors([100e-9, 100e-9, 1e-9]);
+        assert!(film[2] > 0.96 && film[0] < 0.02);
+        let rod = prism_demagnetization_factors([2e-9, 2e-9, 20e-9]);
+        assert!(rod[2] < 0.05 && rod[0] > 0.47);
+        // Reference value for the 1 x 2 x 3 prism
+        let prism = prism_demagnetization_factors([1e-9, 2e-9, 3e-9]);
+        assert!((prism[2] - 0.182818).abs() < 1e-6);
+    }
+
+    #[test]
+    /// Test the field of a single dipole on its axis and beside it
+    fn test_two_dipoles() {
+        let saturation_magnetizations = [1.0e6, 1.0e6];
+        let cell_size: f64 = 2.0e-9;
+        let moment = 1.0e6 * cell_size.powi(3);
+        let distance = cell_size;
+
+        // Head to tail along the chain: H = 2 mu / (4 pi r^3)
+        let along = [array![1.0, 0.0, 0.0], array![1.0, 0.0, 0.0]];
+        let chain = Grid::chain(2);
+        let field =
+            direct_dipolar_field_at(&chain, cell_size, 0, &along, &saturation_magnetizations);
+        let expected = 2.0 * moment / (4.0 * PI * distance.powi(3));
+        assert!((field[0] - expected).abs() < 1e-9 * expected);
+
+        // Side by side: H = -mu / (4 pi r^3)
+        let beside = [array![0.0, 0.0, 1.0], array![0.0, 0.0, 1.0]];
+        let field =
+            direct_dipolar_field_at(&chain, cell_size, 0, &beside, &saturation_magnetizations);
+        assert!((field[2] + expected / 2.0).abs() < 1e-9 * expected);
+        assert!(field[0].abs() < 1e-9 * expected);
+
+        // On a grid the cells follow their positions, here along z
+        let column = Grid::new(1, 1, 2);
+        let field =
+            direct_dipolar_field_at(&column, cell_size, 0, &beside, &saturation_magnetizations);
+        assert!((field[2] - expected).abs() < 1e-9 * expected);
+    }
+
+    #[test]
+    /// Test the lattice sum of the periodic images and its invariance under
+    /// translations of the state
+    fn test_periodic_images() {
+        let grid = Grid::new(4, 3, 1);
+        let cell_size = crate::SPATIAL_DISCRETION_STEP;
+        let moment = 1.0e6 * cell_size.powi(3);
+        let length = 4.0 * cell_size;
+        // A single dipole along z three cells behind the target, whose
+        // nearest image is one cell ahead
+        let mut ms = vec![0.0; 12];
+        ms[3] = 1.0e6;
+        let up = vec![array![0.0, 0.0, 1.0]; 12];
+        let field =
+            periodic_dipolar_field_at(&grid, cell_size, 0, &up, &ms, [true, false, false], 3);
+        let expected: f64 = (-3..=3)
+            .map(|copy| {
+                let r = (copy as f64 * length - cell_size).abs();
+                -moment / (4.0 * PI * r.powi(3))
+            })
+            .sum();
+        assert!((field[2] - expected).abs() < 1e-12 * expected.abs());
+
+        // Shifting the state along the periodic axis shifts the field
+        let magnetizations: Vec<Array1<f64>> = (0..12)
+            .map(|i| {
+                let angle = i as f64;
+                array![angle.cos(), angle.sin(), 0.5]
+            })
+            .collect();
+        let ms: Vec<f64> = (0..12).map(|i| 1.0e6 - 5.0e4 * i as f64).collect();
+        let shift = |cell: usize| {
+            let [x, y, z] = grid.coordinates(cell);
+            grid.index((x + 1) % 4, y, z)
+        };
+        let mut shifted = magnetizations.clone();
+        let mut shifted_ms = ms.clone();
+        for cell in 0..12 {
+            shifted[shift(cell)] = magnetizations[cell].clone();
+            shifted_ms[shift(cell)] = ms[cell];
+        }
+        for cell in 0..12 {
+            let field = periodic_dipolar_field_at(
+                &grid,
+                cell_size,
+                cell,
+                &magnetizations,
+                &ms,
+                [true, false, false],
+                5,
+            );
+            let moved = periodic_dipolar_field_at(
+                &grid,
+                cell_size,
+                shift(cell),
+                &shifted,
+                &shifted_ms,
+                [true, false, false],
+                5,
+            );
+            let scale = field.mapv(f64::abs).sum();
+            assert!((&field - &moved).mapv(f64::abs).sum() < 1e-12 * scale);
+        }
+    }
+
+    #[test]
+    /// Test that vacuum cells do not contribute
+    fn test_vacuum_source() {
+        let magnetizations = [array![1.0, 0.0, 0.0], array![0.0, 0.0, 0.0]];
+        let field =
+            direct_dipolar_field_at(&Grid::chain(2), 1e-9, 0, &magnetizations, &[1.0e6, 0.0]);
+        assert!(field.iter().all(|&x| x == 0.0));
+    }
+}
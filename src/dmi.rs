@@ -0,0 +1,148 @@
+use crate::grid::Grid;
+use crate::material::Material;
+use serde::{Deserialize, Serialize};
+
+///# DMI Type
+/// Symmetry of the Dzyaloshinskii-Moriya interaction of a material
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DmiType {
+    // Interface to a heavy metal below the film (C_nv about z), favors
+    // cycloids and Neel skyrmions, E = D [m_z div m - (m . grad) m_z]
+    #[default]
+    Interfacial,
+    // Non-centrosymmetric bulk crystal such as B20 FeGe, favors helices and
+    // Bloch skyrmions, E = D m . curl m
+    Bulk,
+}
+
+///# DMI Vector
+/// D d of a bond from a cell of the material along the unit direction in
+/// J/m^2, with d = z x u for the interfacial and d = -u for the bulk DMI.
+/// The energy of the bond is V / dx D d . (m_i x m_j), which is the energy
+/// density above between neighboring cells.
+pub fn dmi_vector(material: &Material, direction: [f64; 3]) -> [f64; 3] {
+    let d = material.dmi_constant;
+    let [x, y, z] = direction;
+    match material.dmi_type {
+        DmiType::Interfacial => [-d * y, d * x, 0.0],
+        DmiType::Bulk => [-d * x, -d * y, -d * z],
+    }
+}
+
+///# Bond DMI Vector
+/// Mean of the DMI vectors of the two cells of a bond from `i` to `j`,
+/// zero for neighbors that are not next to each other along an axis of
+/// the grid. A neighbor at the far end of an axis, as closing a ring,
+/// counts as the next cell past the end.
+pub fn bond_dmi_vector(
+    grid: &Grid,
+    i: usize,
+    j: usize,
+    first: &Material,
+    second: &Material,
+) -> [f64; 3] {
+    if first.dmi_constant == 0.0 && second.dmi_constant == 0.0 {
+        return [0.0; 3];
+    }
+    let (a, b) = (grid.coordinates(i), grid.coordinates(j));
+    let axes: Vec<usize> = (0..3).filter(|&k| a[k] != b[k]).collect();
+    let [axis] = axes[..] else {
+        return [0.0; 3];
+    };
+    let step = b[axis] as isize - a[axis] as isize;
+    let mut direction = [0.0; 3];
+    direction[axis] = if step.abs() == 1 {
+        step.signum() as f64
+    } else {
+        -step.signum() as f64
+    };
+    let (p, q) = (dmi_vector(first, direction), dmi_vector(second, direction));
+    std::array::from_fn(|k| 0.5 * (p[k] + q[k]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magnetic_moments::{MicromagneticSystem, Minimizer};
+    use crate::parameters::SimulationParameters;
+    use crate::{CELL_VOLUME, PERMEABILITY_OF_FREE_SPACE, SPATIAL_DISCRETION_STEP};
+    use ndarray::{array, Array1};
+
+    #[test]
+    /// Test the DMI field against the energy and the pitch of a relaxed cycloid
+    fn test_dmi() {
+        let material = Material {
+            exchange_constant: 0.0,
+            anisotropy_constant: 0.0,
+            dmi_constant: 3.0e-3,
+            ..Material::default()
+        };
+        let grid = Grid::new(4, 3, 2);
+        let ring = Grid::chain(5);
+        assert_eq!(
+            bond_dmi_vector(&ring, 4, 0, &material, &material),
+            [0.0, 3.0e-3, 0.0]
+        );
+        assert_eq!(bond_dmi_vector(&grid, 0, 5, &material, &material), [0.0; 3]);
+
+        // The field is -dE/dm / (mu0 Ms V) along every tangent
+        for dmi_type in [DmiType::Interfacial, DmiType::Bulk] {
+            let material = Material {
+                dmi_type,
+                ..material
+            };
+            let parameters = SimulationParameters::default()
+                .with_material(material)
+                .with_applied_field([0.0; 3]);
+            let mut system = MicromagneticSystem::on_grid(grid, parameters);
+            let field = system.compute_effective_field();
+            let cell = grid.index(1, 1, 1);
+            let m = system.get_magnetizations()[cell].clone();
+            let tangent = {
+                let t = array![m[1], -m[0], 0.0];
+                let norm = t.dot(&t).sqrt();
+                t / norm
+            };
+            let epsilon = 1e-6;
+            let energy_at = |system: &mut MicromagneticSystem, step: f64| {
+                system.set_magnetization(cell, &m + &(step * &tangent));
+                system.compute_energies().dmi
+            };
+            let derivative = (energy_at(&mut system, epsilon) - energy_at(&mut system, -epsilon))
+                / (2.0 * epsilon);
+            let expected = -PERMEABILITY_OF_FREE_SPACE
+                * material.saturation_magnetization
+                * CELL_VOLUME
+                * field[cell].dot(&tangent);
+            assert!((derivative - expected).abs() < 1e-6 * expected.abs());
+        }
+
+        // Exchange and interfacial DMI turn neighbors in the xz plane by
+        // tan(theta) = D dx / (2 A), the sign of D sets the sense
+        let exchange_constant = 1.0e-11;
+        let material = Material {
+            exchange_constant,
+            dmi_constant: 4.0e-3,
+            ..material
+        };
+        let size = 40;
+        let parameters = SimulationParameters::default()
+            .with_material(material)
+            .with_applied_field([0.0; 3]);
+        let mut system = MicromagneticSystem::with_parameters(size, parameters);
+        for i in 0..size {
+            let angle = 0.1 * i as f64;
+            system.set_magnetization(i, array![angle.cos(), 0.0, angle.sin()]);
+        }
+        system.set_minimizer(Minimizer::SphericalConjugateGradient);
+        system.minimize_energy();
+        let m = system.get_magnetizations();
+        let expected = (4.0e-3 * SPATIAL_DISCRETION_STEP / (2.0 * exchange_constant)).atan();
+        let (a, b): (&Array1<f64>, &Array1<f64>) = (&m[size / 2], &m[size / 2 + 1]);
+        assert!(a[1].abs() < 1e-6);
+        let turn = (a[0] * b[2] - a[2] * b[0]).atan2(a.dot(b));
+        assert!((turn - expected).abs() < 2e-4);
+        assert!(system.compute_energies().dmi < 0.0);
+    }
+}
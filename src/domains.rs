@@ -0,0 +1,269 @@
+use ndarray::Array1;
+
+///# Magnetic Domain
+/// A run of neighboring cells whose magnetization points
+/// along the same direction of the easy axis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Domain {
+    // Index of the first cell of the domain
+    pub start: usize,
+    // Index of the last cell of the domain (inclusive)
+    pub end: usize,
+    // +1 if the magnetization is parallel to the easy axis, -1 if antiparallel
+    pub orientation: i8,
+}
+
+impl Domain {
+    ///# Domain Size
+    /// Number of cells in the domain
+    pub fn size(&self) -> usize {
+        self.end - self.start + 1
+    }
+}
+
+///# Domain Statistics
+/// Summary of the domain structure of a magnetization profile
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainStatistics {
+    // The domains in the order they appear along the chain
+    pub domains: Vec<Domain>,
+    // Cell indices at which a new domain starts (the domain walls)
+    pub boundaries: Vec<usize>,
+}
+
+impl DomainStatistics {
+    ///# Domain Count
+    pub fn count(&self) -> usize {
+        self.domains.len()
+    }
+
+    ///# Mean Domain Size
+    /// Average number of cells per domain, zero for an empty profile
+    pub fn mean_size(&self) -> f64 {
+        if self.domains.is_empty() {
+            return 0.0;
+        }
+        let total: usize = self.domains.iter().map(Domain::size).sum();
+        total as f64 / self.domains.len() as f64
+    }
+}
+
+///# Domain Analysis
+/// Segments the magnetization profile into domains by the sign of
+/// the projection of the magnetization on the easy axis.
+/// Cells with a vanishing projection are attached to the current domain,
+/// so only a real sign change counts as a domain wall. Before the first
+/// cell with a sign, at the start or behind vacuum, they have no domain
+/// to join and are skipped.
+/// Vacuum cells (zero magnetization) end the current domain without a wall.
+pub fn analyze_domains(magnetizations: &[Array1<f64>], easy_axis: &[f64; 3]) -> DomainStatistics {
+    let axis = Array1::from_vec(easy_axis.to_vec());
+    let mut domains: Vec<Domain> = Vec::new();
+    let mut boundaries: Vec<usize> = Vec::new();
+    let mut after_vacuum = false;
+
+    for (i, m) in magnetizations.iter().enumerate() {
+        if m.dot(m) == 0.0 {
+            after_vacuum = true;
+            continue;
+        }
+        let projection = m.dot(&axis);
+        if projection == 0.0 && (after_vacuum || domains.is_empty()) {
+            continue;
+        }
+        let orientation: i8 = if projection < 0.0 { -1 } else { 1 };
+
+        match domains.last_mut() {
+            // A magnetic cell behind vacuum starts a separate domain
+            _ if after_vacuum => {
+                after_vacuum = false;
+                domains.push(Domain {
+                    start: i,
+                    end: i,
+                    orientation,
+                });
+            }
+            // Same orientation (or no preferred one) extends the current domain
+            Some(domain) if domain.orientation == orientation || projection == 0.0 => {
+                domain.end = i;
+            }
+            // A sign change starts a new domain
+            Some(_) => {
+                boundaries.push(i);
+                domains.push(Domain {
+                    start: i,
+                    end: i,
+                    orientation,
+                });
+            }
+            None => domains.push(Domain {
+                start: i,
+                end: i,
+                orientation,
+            }),
+        }
+    }
+
+    DomainStatistics {
+        domains,
+        boundaries,
+    }
+}
+
+///# Wall Position
+/// Position in m of the first domain wall, where the projection on the
+/// easy axis changes sign between two neighboring magnetic cells. The
+/// zero crossing is interpolated linearly between the centers of the cells
+/// with the edge length `cell_size`.
+pub fn wall_position(
+    magnetizations: &[Array1<f64>],
+    easy_axis: &[f64; 3],
+    cell_size: f64,
+) -> Option<f64> {
+    let axis = Array1::from_vec(easy_axis.to_vec());
+    magnetizations.windows(2).enumerate().find_map(|(i, pair)| {
+        let (left, right) = (pair[0].dot(&axis), pair[1].dot(&axis));
+        let magnetic = pair[0].dot(&pair[0]) > 0.0 && pair[1].dot(&pair[1]) > 0.0;
+        (magnetic && left * right < 0.0).then(|| (i as f64 + left / (left - right)) * cell_size)
+    })
+}
+
+///# Wall Width
+/// Lilley width pi Delta in m of the wall profile, with Delta taken from
+/// the steepest slope of the easy axis projection, m = tanh(x / Delta)
+/// for a Bloch wall. `None` when the profile has no domain wall.
+pub fn wall_width(
+    magnetizations: &[Array1<f64>],
+    easy_axis: &[f64; 3],
+    cell_size: f64,
+) -> Option<f64> {
+    wall_position(magnetizations, easy_axis, cell_size)?;
+    let axis = Array1::from_vec(easy_axis.to_vec());
+    let slope = magnetizations
+        .windows(2)
+        .filter(|pair| pair[0].dot(&pair[0]) > 0.0 && pair[1].dot(&pair[1]) > 0.0)
+        .map(|pair| (pair[1].dot(&axis) - pair[0].dot(&axis)).abs() / cell_size)
+        .fold(0.0, f64::max);
+    Some(std::f64::consts::PI / slope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SPATIAL_DISCRETION_STEP;
+    use ndarray::array;
+
+    #[test]
+    /// Test a profile with a single domain wall in the middle
+    fn test_two_domains() {
+        let mut magnetizations = vec![array![1.0, 0.0, 0.0]; 4];
+        magnetizations.extend(vec![array![-1.0, 0.0, 0.0]; 6]);
+        let statistics = analyze_domains(&magnetizations, &[1.0, 0.0, 0.0]);
+        assert_eq!(statistics.count(), 2);
+        assert_eq!(statistics.boundaries, vec![4]);
+        assert_eq!(statistics.domains[0].size(), 4);
+        assert_eq!(statistics.domains[1].orientation, -1);
+        assert!((statistics.mean_size() - 5.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    /// Test that cells perpendicular to the easy axis do not split a domain
+    fn test_perpendicular_cells() {
+        let magnetizations = vec![
+            array![1.0, 0.0, 0.0],
+            array![0.0, 0.0, 1.0],
+            array![1.0, 0.0, 0.0],
+        ];
+        let statistics = analyze_domains(&magnetizations, &[1.0, 0.0, 0.0]);
+        assert_eq!(statistics.count(), 1);
+        assert!(statistics.boundaries.is_empty());
+    }
+
+    #[test]
+    /// Test that perpendicular cells before the first signed cell do not
+    /// start a domain of their own
+    fn test_leading_perpendicular_cells() {
+        let magnetizations = vec![
+            array![0.0, 0.0, 1.0],
+            array![-1.0, 0.0, 0.0],
+            array![0.0, 0.0, 0.0],
+            array![0.0, 1.0, 0.0],
+            array![-1.0, 0.0, 0.0],
+        ];
+        let statistics = analyze_domains(&magnetizations, &[1.0, 0.0, 0.0]);
+        assert_eq!(statistics.count(), 2);
+        assert!(statistics.boundaries.is_empty());
+        assert_eq!(statistics.domains[0].start, 1);
+        assert_eq!(statistics.domains[1].start, 4);
+        assert!(statistics.domains.iter().all(|d| d.orientation == -1));
+    }
+
+    #[test]
+    /// Test that vacuum separates domains without counting as a wall
+    fn test_vacuum_gap() {
+        let magnetizations = vec![
+            array![1.0, 0.0, 0.0],
+            array![0.0, 0.0, 0.0],
+            array![1.0, 0.0, 0.0],
+            array![1.0, 0.0, 0.0],
+        ];
+        let statistics = analyze_domains(&magnetizations, &[1.0, 0.0, 0.0]);
+        assert_eq!(statistics.count(), 2);
+        assert!(statistics.boundaries.is_empty());
+        assert!((statistics.mean_size() - 1.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    /// Test the empty profile
+    fn test_empty_profile() {
+        let statistics = analyze_domains(&[], &[1.0, 0.0, 0.0]);
+        assert_eq!(statistics.count(), 0);
+        assert_eq!(statistics.mean_size(), 0.0);
+    }
+
+    #[test]
+    /// Test the interpolated position of a domain wall
+    fn test_wall_position() {
+        let profile = vec![
+            array![1.0, 0.0, 0.0],
+            array![0.5, 0.866, 0.0],
+            array![-0.25, 0.968, 0.0],
+            array![-1.0, 0.0, 0.0],
+        ];
+        let position = wall_position(&profile, &[1.0, 0.0, 0.0], SPATIAL_DISCRETION_STEP).unwrap();
+        assert!((position - (1.0 + 2.0 / 3.0) * SPATIAL_DISCRETION_STEP).abs() < 1e-21);
+        assert_eq!(
+            wall_position(&profile[..2], &[1.0, 0.0, 0.0], SPATIAL_DISCRETION_STEP),
+            None
+        );
+        // Vacuum between opposite domains is not a wall
+        let separated = vec![
+            array![1.0, 0.0, 0.0],
+            array![0.0, 0.0, 0.0],
+            array![-1.0, 0.0, 0.0],
+        ];
+        assert_eq!(
+            wall_position(&separated, &[1.0, 0.0, 0.0], SPATIAL_DISCRETION_STEP),
+            None
+        );
+    }
+
+    #[test]
+    /// Test the width of a tanh wall profile
+    fn test_wall_width() {
+        let delta = 5.0;
+        let profile: Vec<Array1<f64>> = (0..40)
+            .map(|i| {
+                let theta = 2.0 * ((i as f64 - 19.5) / delta).exp().atan();
+                array![-theta.cos(), theta.sin(), 0.0]
+            })
+            .collect();
+        let width = wall_width(&profile, &[1.0, 0.0, 0.0], SPATIAL_DISCRETION_STEP).unwrap();
+        let expected = std::f64::consts::PI * delta * SPATIAL_DISCRETION_STEP;
+        assert!((width - expected).abs() < 0.01 * expected);
+        assert_eq!(
+            wall_width(&profile[..10], &[1.0, 0.0, 0.0], SPATIAL_DISCRETION_STEP),
+            None
+        );
+    }
+}
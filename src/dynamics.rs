@@ -0,0 +1,1029 @@
+use crate::domains::wall_position;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::parameters::AdaptiveTimeStep;
+use crate::quaternion::Quaternion;
+use crate::spin_accumulation::SpinAccumulation;
+use crate::spin_transfer::SpinTransferTorque;
+use crate::spin_waves::MagnetizationHistory;
+use crate::stop_conditions::StopCondition;
+use crate::thermal_field::ThermalField;
+use crate::{DYNAMICS_TIME_STEP, EASY_AXIS};
+use ndarray::Array1;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+///# Time Dependent Field
+/// Applied field B = mu0 H in T as a function of the simulated time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeDependentField {
+    Constant([f64; 3]),
+    Rotating(RotatingField),
+}
+
+///# Rotating Field
+/// Field of constant magnitude rotating about the plane normal, starting
+/// along the first axis and turning towards the second, plus a static bias.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RotatingField {
+    // Magnitude of the rotating part in T
+    pub amplitude: f64,
+    // Rotation frequency in Hz, negative values rotate backwards
+    pub frequency: f64,
+    // Orthonormal axes spanning the rotation plane
+    pub first_axis: [f64; 3],
+    pub second_axis: [f64; 3],
+    // Phase at t = 0 in rad
+    pub phase: f64,
+    // Static field added to the rotating part in T
+    pub bias: [f64; 3],
+}
+
+impl RotatingField {
+    ///# New Rotating Field
+    /// Rotation in the plane spanned by the two axes. The second axis is
+    /// orthogonalized against the first, so any two non-parallel vectors work.
+    pub fn new(
+        amplitude: f64,
+        frequency: f64,
+        first_axis: [f64; 3],
+        second_axis: [f64; 3],
+    ) -> Result<Self, String> {
+        let first_axis = normalized(first_axis).ok_or("The first rotation axis is zero")?;
+        let projection = dot(&second_axis, &first_axis);
+        let second_axis = normalized([
+            second_axis[0] - projection * first_axis[0],
+            second_axis[1] - projection * first_axis[1],
+            second_axis[2] - projection * first_axis[2],
+        ])
+        .ok_or("The rotation axes must not be parallel")?;
+        Ok(Self {
+            amplitude,
+            frequency,
+            first_axis,
+            second_axis,
+            phase: 0.0,
+            bias: [0.0; 3],
+        })
+    }
+
+    ///# Field at Time
+    pub fn at(&self, time: f64) -> [f64; 3] {
+        let angle = 2.0 * PI * self.frequency * time + self.phase;
+        let (sin, cos) = angle.sin_cos();
+        let mut field = self.bias;
+        for k in 0..3 {
+            field[k] += self.amplitude * (cos * self.first_axis[k] + sin * self.second_axis[k]);
+        }
+        field
+    }
+}
+
+impl TimeDependentField {
+    ///# Field at Time
+    pub fn at(&self, time: f64) -> [f64; 3] {
+        match self {
+            TimeDependentField::Constant(field) => *field,
+            TimeDependentField::Rotating(rotating) => rotating.at(time),
+        }
+    }
+}
+
+///# Antenna Profile
+/// Distribution of the antenna field over its cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AntennaProfile {
+    // Same field in every cell, like a wide stripline
+    #[default]
+    Uniform,
+    // Gaussian about the center with a standard deviation of a quarter of the width
+    Gaussian,
+    // sin^2 bell that vanishes smoothly at both edges, which excites fewer short waves
+    Hann,
+}
+
+impl FromStr for AntennaProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(AntennaProfile::Uniform),
+            "gaussian" => Ok(AntennaProfile::Gaussian),
+            "hann" => Ok(AntennaProfile::Hann),
+            _ => Err(format!(
+                "unknown antenna profile {}, use uniform, gaussian or hann",
+                s
+            )),
+        }
+    }
+}
+
+///# Antenna
+/// Microwave field b(t) = amplitude sin(2 pi f t + phase) along a fixed
+/// direction, confined to the cells `start..=end` and weighted by the
+/// profile. Placed at one end of the chain it launches spin waves that
+/// propagate along it, a frequency above the uniform resonance is needed
+/// for them to propagate rather than decay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Antenna {
+    pub start: usize,
+    pub end: usize,
+    // Peak field in T
+    pub amplitude: f64,
+    // Frequency in Hz
+    pub frequency: f64,
+    // Unit vector of the field
+    pub direction: [f64; 3],
+    pub profile: AntennaProfile,
+    // Phase at t = 0 in rad
+    pub phase: f64,
+}
+
+impl Antenna {
+    ///# New Antenna
+    /// Uniform antenna over the given cells, the direction is normalized.
+    pub fn new(
+        start: usize,
+        end: usize,
+        amplitude: f64,
+        frequency: f64,
+        direction: [f64; 3],
+    ) -> Result<Self, String> {
+        if start > end {
+            return Err("The antenna must not end before it starts".to_string());
+        }
+        let direction = normalized(direction).ok_or("The antenna field direction is zero")?;
+        Ok(Self {
+            start,
+            end,
+            amplitude,
+            frequency,
+            direction,
+            profile: AntennaProfile::default(),
+            phase: 0.0,
+        })
+    }
+
+    ///# Profile Weight
+    /// Relative field strength in a cell, zero outside the antenna.
+    pub fn weight(&self, cell: usize) -> f64 {
+        if cell < self.start || cell > self.end {
+            return 0.0;
+        }
+        let width = (self.end - self.start + 1) as f64;
+        // Position of the cell center from the start of the antenna in cells
+        let position = (cell - self.start) as f64 + 0.5;
+        match self.profile {
+            AntennaProfile::Uniform => 1.0,
+            AntennaProfile::Gaussian => {
+                let sigma = 0.25 * width;
+                (-0.5 * ((position - 0.5 * width) / sigma).powi(2)).exp()
+            }
+            AntennaProfile::Hann => (PI * position / width).sin().powi(2),
+        }
+    }
+
+    ///# Fields at Time
+    /// Antenna field of every cell of a chain of the given size.
+    pub fn fields(&self, size: usize, time: f64) -> Vec<[f64; 3]> {
+        let oscillation = (2.0 * PI * self.frequency * time + self.phase).sin();
+        (0..size)
+            .map(|cell| {
+                let b = self.amplitude * self.weight(cell) * oscillation;
+                self.direction.map(|d| d * b)
+            })
+            .collect()
+    }
+}
+
+///# Moving Frame
+/// Keeps the first domain wall in the middle of the chain by shifting the
+/// simulated window along with it, see `MicromagneticSystem::shift_frame`,
+/// so a driven wall can travel much farther than the length of the chain.
+/// The laboratory position of the wall is the position in the window plus
+/// the frame offset of the system.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovingFrame {
+    // Distance in cells the wall may drift from the center before the window follows
+    pub tolerance: usize,
+    pub easy_axis: [f64; 3],
+}
+
+impl Default for MovingFrame {
+    fn default() -> Self {
+        Self {
+            tolerance: 1,
+            easy_axis: EASY_AXIS,
+        }
+    }
+}
+
+impl MovingFrame {
+    ///# Follow Wall
+    /// Shift the window when the wall has drifted from the center by more
+    /// than the tolerance and return the shift in cells, zero without a wall.
+    pub fn follow(&self, system: &mut MicromagneticSystem) -> isize {
+        let Some(position) = wall_position(
+            &system.get_magnetizations(),
+            &self.easy_axis,
+            system.get_cell_size(),
+        ) else {
+            return 0;
+        };
+        let center = 0.5 * (system.size() as f64 - 1.0);
+        let drift = position / system.get_cell_size() - center;
+        if drift.abs() <= self.tolerance as f64 {
+            return 0;
+        }
+        let shift = drift.round() as isize;
+        system.shift_frame(shift);
+        shift
+    }
+}
+
+///# Spin Update
+/// How a stage of the integrator changes the magnetization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpinUpdate {
+    // Add the change and renormalize m
+    #[default]
+    Normalize,
+    // Rotate m by a unit quaternion about the LLG angular velocity, which
+    // keeps |m| = 1 without renormalizing over long runs
+    Quaternion,
+}
+
+///# Integrator
+/// Explicit Runge-Kutta scheme of a time step. The stages are taken on the
+/// Cartesian m or on the rotation of m, depending on the spin update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integrator {
+    // First order with one field evaluation per step, needs short steps
+    Euler,
+    // Second order predictor-corrector with two field evaluations
+    #[default]
+    Heun,
+    // Classical fourth order Runge-Kutta with four field evaluations
+    RungeKutta4,
+}
+
+impl Integrator {
+    // Butcher tableau: stage times as fractions of the step, the weights of
+    // the earlier rates in every stage and the weights of the final update
+    fn tableau(self) -> (&'static [f64], &'static [&'static [f64]], &'static [f64]) {
+        match self {
+            Integrator::Euler => (&[0.0], &[&[]], &[1.0]),
+            Integrator::Heun => (&[0.0, 1.0], &[&[], &[1.0]], &[0.5, 0.5]),
+            Integrator::RungeKutta4 => (
+                &[0.0, 0.5, 0.5, 1.0],
+                &[&[], &[0.5], &[0.0, 0.5], &[0.0, 0.0, 1.0]],
+                &[1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0],
+            ),
+        }
+    }
+
+    ///# Order
+    /// Order of the global error in the time step.
+    pub fn order(self) -> i32 {
+        match self {
+            Integrator::Euler => 1,
+            Integrator::Heun => 2,
+            Integrator::RungeKutta4 => 4,
+        }
+    }
+}
+
+impl FromStr for Integrator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "euler" => Ok(Integrator::Euler),
+            "heun" => Ok(Integrator::Heun),
+            "rk4" => Ok(Integrator::RungeKutta4),
+            _ => Err(format!("unknown integrator {}, use euler, heun or rk4", s)),
+        }
+    }
+}
+
+///# Dynamics Run
+/// Time integration of the full Landau-Lifshitz-Gilbert equation,
+/// precession and damping, with the selected integrator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DynamicsRun {
+    // Time step in s, the first step of an adaptive run
+    pub time_step: f64,
+    // Step size control from step doubling, the fixed time step when unset
+    pub adaptive_time_step: Option<AdaptiveTimeStep>,
+    pub applied_field: TimeDependentField,
+    pub integrator: Integrator,
+    pub spin_update: SpinUpdate,
+    // Local excitation on top of the applied field
+    pub antenna: Option<Antenna>,
+    // Window that follows a domain wall after every step
+    pub moving_frame: Option<MovingFrame>,
+    // Torque of the steady-state spin accumulation of a current
+    pub spin_accumulation: Option<SpinAccumulation>,
+    // Slonczewski torque of a current perpendicular to the layers
+    pub spin_transfer: Option<SpinTransferTorque>,
+    // Brown's fluctuating field of a finite temperature, meant for a fixed
+    // time step
+    pub thermal_field: Option<ThermalField>,
+    // Simulated time between outputs in s, a step that would pass a
+    // multiple of it is shortened to end on it
+    pub output_interval: Option<f64>,
+}
+
+impl DynamicsRun {
+    ///# New Dynamics Run
+    /// Run with the default time step in the given applied field.
+    pub fn new(applied_field: TimeDependentField) -> Self {
+        Self {
+            time_step: DYNAMICS_TIME_STEP,
+            adaptive_time_step: None,
+            applied_field,
+            integrator: Integrator::default(),
+            spin_update: SpinUpdate::default(),
+            antenna: None,
+            moving_frame: None,
+            spin_accumulation: None,
+            spin_transfer: None,
+            thermal_field: None,
+            output_interval: None,
+        }
+    }
+
+    // Applied, antenna, spin accumulation and spin transfer field at the
+    // given time, plus the thermal field of the step
+    fn set_fields(
+        &self,
+        system: &mut MicromagneticSystem,
+        time: f64,
+        thermal: Option<&[[f64; 3]]>,
+    ) {
+        system.set_applied_field(self.applied_field.at(time));
+        let mut fields = self
+            .antenna
+            .as_ref()
+            .map(|antenna| antenna.fields(system.size(), time));
+        if let Some(model) = &self.spin_accumulation {
+            let accumulation = model.solve(system);
+            fields = add_fields(fields, &model.fields(system, &accumulation));
+        }
+        if let Some(model) = &self.spin_transfer {
+            fields = add_fields(fields, &model.fields(system));
+        }
+        if let Some(thermal) = thermal {
+            fields = add_fields(fields, thermal);
+        }
+        if let Some(fields) = fields {
+            system
+                .set_local_fields(fields)
+                .expect("the local fields cover every cell");
+        }
+    }
+
+    ///# Step
+    /// Advance the system from `time` by one time step. Every stage sets
+    /// the fields of its time, and the final update starts again from the
+    /// state at `time`.
+    pub fn step(&self, system: &mut MicromagneticSystem, time: f64) {
+        self.step_by(system, time, self.time_step);
+    }
+
+    // One step of the given size
+    fn step_by(&self, system: &mut MicromagneticSystem, time: f64, time_step: f64) {
+        let initial = directions(system);
+        // The noise is held over the stages of the step
+        let thermal = self
+            .thermal_field
+            .map(|thermal| thermal.fields(system, time, time_step));
+        let (times, stages, weights) = self.integrator.tableau();
+        let mut rates = Vec::with_capacity(weights.len());
+        for (&fraction, &coefficients) in times.iter().zip(stages) {
+            if !rates.is_empty() {
+                self.advance(system, &initial, &rates, coefficients, time_step);
+            }
+            self.set_fields(system, time + fraction * time_step, thermal.as_deref());
+            rates.push(self.rate(system));
+        }
+        self.advance(system, &initial, &rates, weights, time_step);
+    }
+
+    // Step doubling from `time` with at most the given step, clipped to the
+    // end of the run. Rejected steps are repeated with a smaller step from
+    // the same state. Returns the accepted step and the next step.
+    fn adaptive_step(
+        &self,
+        system: &mut MicromagneticSystem,
+        adaptive: &AdaptiveTimeStep,
+        time: f64,
+        remaining: f64,
+        mut time_step: f64,
+    ) -> (f64, f64) {
+        let initial = directions(system);
+        loop {
+            let step = time_step.min(remaining);
+            self.step_by(system, time, step);
+            let full = directions(system);
+            restore(system, &initial);
+            self.step_by(system, time, 0.5 * step);
+            self.step_by(system, time + 0.5 * step, 0.5 * step);
+            let halves = directions(system);
+            let (accepted, next) = adaptive.control(
+                step,
+                max_difference(&full, &halves),
+                max_difference(&initial, &halves),
+                self.integrator.order(),
+            );
+            if accepted {
+                return (step, next);
+            }
+            restore(system, &initial);
+            time_step = next;
+        }
+    }
+
+    // dm/dt of the normalized update or the angular velocity of the
+    // quaternion update, for every cell
+    fn rate(&self, system: &MicromagneticSystem) -> Vec<[f64; 3]> {
+        match self.spin_update {
+            SpinUpdate::Normalize => system
+                .compute_llg_derivative()
+                .iter()
+                .map(|d| [d[0], d[1], d[2]])
+                .collect(),
+            SpinUpdate::Quaternion => system.compute_llg_angular_velocity(),
+        }
+    }
+
+    // Set m to the initial state advanced by the time step times the
+    // weighted rates: added and renormalized, or as a rotation of m about
+    // the weighted angular velocity
+    fn advance(
+        &self,
+        system: &mut MicromagneticSystem,
+        initial: &[[f64; 3]],
+        rates: &[Vec<[f64; 3]>],
+        weights: &[f64],
+        time_step: f64,
+    ) {
+        for (cell, m) in initial.iter().enumerate() {
+            let mut increment = [0.0; 3];
+            for (rate, &weight) in rates.iter().zip(weights) {
+                for c in 0..3 {
+                    increment[c] += time_step * weight * rate[cell][c];
+                }
+            }
+            match self.spin_update {
+                SpinUpdate::Normalize => system.set_magnetization(
+                    cell,
+                    Array1::from_vec(vec![
+                        m[0] + increment[0],
+                        m[1] + increment[1],
+                        m[2] + increment[2],
+                    ]),
+                ),
+                SpinUpdate::Quaternion => system.set_rotated_magnetization(
+                    cell,
+                    Quaternion::from_rotation_vector(increment).rotate(*m),
+                ),
+            }
+        }
+    }
+
+    ///# Run
+    /// Integrate for the given duration in s. The observer sees the time and
+    /// the state at the start and after every step. Returns the final time.
+    pub fn run<F: FnMut(f64, &MicromagneticSystem)>(
+        &self,
+        system: &mut MicromagneticSystem,
+        duration: f64,
+        observer: F,
+    ) -> f64 {
+        self.run_until(
+            system,
+            duration,
+            &mut |_, _, _: &MicromagneticSystem| false,
+            observer,
+        )
+    }
+
+    ///# Run with Stop Condition
+    /// Same as `run`, but ends early after the first observed state in
+    /// which the condition holds. With an adaptive time step the observer
+    /// and the condition see every accepted step, and the last step ends
+    /// at the duration. With an output interval every multiple of it up to
+    /// the duration is observed at exactly that time, whatever the steps.
+    pub fn run_until<F: FnMut(f64, &MicromagneticSystem)>(
+        &self,
+        system: &mut MicromagneticSystem,
+        duration: f64,
+        condition: &mut dyn StopCondition,
+        mut observer: F,
+    ) -> f64 {
+        let steps = (duration / self.time_step).round() as usize;
+        self.set_fields(system, 0.0, None);
+        observer(0.0, system);
+        let mut time = 0.0;
+        if condition.should_stop(0, time, system) {
+            return time;
+        }
+        let interval = self.output_interval.filter(|&interval| interval > 0.0);
+        // Time of the next output, the duration without an interval or
+        // within rounding of it
+        let target = |outputs: usize| {
+            interval.map_or(duration, |interval| {
+                let time = outputs as f64 * interval;
+                if time > duration - 1e-9 * interval {
+                    duration
+                } else {
+                    time
+                }
+            })
+        };
+        let mut outputs = 1;
+        if let Some(adaptive) = &self.adaptive_time_step {
+            let mut time_step = self.time_step.clamp(adaptive.min_step, adaptive.max_step);
+            let mut step = 0;
+            while time < duration {
+                let end = target(outputs);
+                let (taken, next) =
+                    self.adaptive_step(system, adaptive, time, end - time, time_step);
+                time_step = if taken < end - time {
+                    time += taken;
+                    next
+                } else {
+                    time = end;
+                    outputs += 1;
+                    // The clipped step says little about the step size
+                    next.max(time_step)
+                };
+                step += 1;
+                if let Some(frame) = &self.moving_frame {
+                    frame.follow(system);
+                }
+                observer(time, system);
+                if condition.should_stop(step, time, system) {
+                    break;
+                }
+            }
+            return time;
+        }
+        if interval.is_some() {
+            // Steps count from the last output, so the times between two
+            // outputs are exact multiples of the time step after it
+            let (mut start, mut since, mut step) = (0.0, 0, 0);
+            while time < duration {
+                let end = target(outputs);
+                let full = start + (since + 1) as f64 * self.time_step;
+                if full >= end - 1e-9 * self.time_step {
+                    self.step_by(system, time, end - time);
+                    (time, start, since) = (end, end, 0);
+                    outputs += 1;
+                } else {
+                    self.step(system, time);
+                    time = full;
+                    since += 1;
+                }
+                step += 1;
+                if let Some(frame) = &self.moving_frame {
+                    frame.follow(system);
+                }
+                observer(time, system);
+                if condition.should_stop(step, time, system) {
+                    break;
+                }
+            }
+            return time;
+        }
+        for step in 1..=steps {
+            self.step(system, time);
+            if let Some(frame) = &self.moving_frame {
+                frame.follow(system);
+            }
+            time = step as f64 * self.time_step;
+            observer(time, system);
+            if condition.should_stop(step, time, system) {
+                break;
+            }
+        }
+        time
+    }
+    ///# Trajectory
+    /// Integrate for the given duration and record the magnetization of
+    /// every cell once per `interval` of simulated time, from t = 0 on.
+    /// The steps end on every multiple of the interval.
+    pub fn trajectory(
+        &self,
+        system: &mut MicromagneticSystem,
+        duration: f64,
+        interval: f64,
+    ) -> MagnetizationHistory {
+        let mut history = MagnetizationHistory::new(interval);
+        let run = Self {
+            output_interval: Some(interval),
+            ..*self
+        };
+        run.run(system, duration, |time, system| {
+            history.record(time, system);
+        });
+        history
+    }
+}
+
+// Sum of the local fields of two sources
+fn add_fields(fields: Option<Vec<[f64; 3]>>, other: &[[f64; 3]]) -> Option<Vec<[f64; 3]>> {
+    Some(match fields {
+        Some(fields) => fields
+            .iter()
+            .zip(other)
+            .map(|(a, b)| std::array::from_fn(|c| a[c] + b[c]))
+            .collect(),
+        None => other.to_vec(),
+    })
+}
+
+// Magnetization of every cell
+fn directions(system: &MicromagneticSystem) -> Vec<[f64; 3]> {
+    system
+        .get_magnetizations()
+        .iter()
+        .map(|m| [m[0], m[1], m[2]])
+        .collect()
+}
+
+// Set the magnetizations back to a state of the same system
+fn restore(system: &mut MicromagneticSystem, directions: &[[f64; 3]]) {
+    for (cell, m) in directions.iter().enumerate() {
+        system.set_rotated_magnetization(cell, *m);
+    }
+}
+
+// Largest absolute difference of a magnetization component
+fn max_difference(first: &[[f64; 3]], second: &[[f64; 3]]) -> f64 {
+    first
+        .iter()
+        .zip(second)
+        .flat_map(|(a, b)| (0..3).map(move |c| (a[c] - b[c]).abs()))
+        .fold(0.0, f64::max)
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalized(v: [f64; 3]) -> Option<[f64; 3]> {
+    let norm = dot(&v, &v).sqrt();
+    (norm > 0.0).then(|| [v[0] / norm, v[1] / norm, v[2] / norm])
+}
+
+///# Rotation Plane Axes
+/// Axes of a named plane such as "xy", the rotation turns from the first
+/// to the second letter.
+pub fn plane_axes(plane: &str) -> Option<([f64; 3], [f64; 3])> {
+    let axis = |c: char| match c {
+        'x' => Some([1.0, 0.0, 0.0]),
+        'y' => Some([0.0, 1.0, 0.0]),
+        'z' => Some([0.0, 0.0, 1.0]),
+        _ => None,
+    };
+    let mut letters = plane.chars();
+    let (first, second) = (letters.next()?, letters.next()?);
+    if letters.next().is_some() || first == second {
+        return None;
+    }
+    Some((axis(first)?, axis(second)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SPATIAL_DISCRETION_STEP;
+    use ndarray::array;
+
+    #[test]
+    /// Test that fixed and adaptive steps end on every output time
+    fn test_output_interval() {
+        let start = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 1.0]; 4]);
+        let field = TimeDependentField::Constant([0.0, 0.0, 1.0]);
+        let times = |run: DynamicsRun, duration: f64| {
+            let mut system = start.clone();
+            let mut times = Vec::new();
+            run.run(&mut system, duration, |time, _| times.push(time));
+            (times, system)
+        };
+
+        // An interval that is no multiple of the time step
+        let fixed = DynamicsRun {
+            time_step: 1e-14,
+            output_interval: Some(2.5e-14),
+            ..DynamicsRun::new(field)
+        };
+        let (observed, _) = times(fixed, 1e-13);
+        // Every output is a multiple of the interval, with the steps
+        // counted from it
+        let outputs: Vec<f64> = (0..4).map(|k| k as f64 * 2.5e-14).collect();
+        let mut expected = Vec::new();
+        for &output in &outputs {
+            expected.extend([output, output + 1e-14, output + 2e-14]);
+        }
+        expected.push(1e-13);
+        assert_eq!(observed, expected);
+        // A multiple of the time step keeps the steps
+        let (_, plain) = times(DynamicsRun::new(field), 1e-12);
+        let (_, landed) = times(
+            DynamicsRun {
+                output_interval: Some(1e-13),
+                ..DynamicsRun::new(field)
+            },
+            1e-12,
+        );
+        assert!(max_difference(&directions(&plain), &directions(&landed)) < 1e-12);
+
+        let adaptive = DynamicsRun {
+            time_step: 1e-15,
+            adaptive_time_step: Some(AdaptiveTimeStep {
+                tolerance: 1e-3,
+                min_step: 1e-17,
+                max_step: 3e-13,
+            }),
+            output_interval: Some(1e-12),
+            ..DynamicsRun::new(field)
+        };
+        let (observed, _) = times(adaptive, 1e-11);
+        for k in 0..=10 {
+            assert!(observed.contains(&(k as f64 * 1e-12).min(1e-11)));
+        }
+        assert_eq!(observed.last(), Some(&1e-11));
+        // A sampler of the interval records every output
+        let mut history = MagnetizationHistory::new(1e-12);
+        let mut samples = 0;
+        adaptive.run(&mut start.clone(), 1e-11, |time, system| {
+            samples += history.record(time, system) as usize;
+        });
+        assert_eq!(samples, 11);
+    }
+
+    #[test]
+    /// Test the field of a rotating field over one period
+    fn test_rotating_field() {
+        let rotating = RotatingField::new(0.1, 1e9, [2.0, 0.0, 0.0], [1.0, 1.0, 0.0]).unwrap();
+        assert_eq!(rotating.second_axis, [0.0, 1.0, 0.0]);
+        let quarter = rotating.at(0.25e-9);
+        assert!(quarter[0].abs() < 1e-12 && (quarter[1] - 0.1).abs() < 1e-12);
+        let full = rotating.at(1e-9);
+        assert!((full[0] - 0.1).abs() < 1e-12 && full[1].abs() < 1e-12);
+        assert!(RotatingField::new(0.1, 1e9, [1.0, 0.0, 0.0], [3.0, 0.0, 0.0]).is_err());
+        assert_eq!(plane_axes("zx"), Some(([0.0, 0.0, 1.0], [1.0, 0.0, 0.0])));
+        assert_eq!(plane_axes("xx"), None);
+    }
+
+    #[test]
+    /// Test the profiles and the oscillation of an antenna field
+    fn test_antenna_field() {
+        let mut antenna = Antenna::new(2, 5, 0.01, 1e10, [0.0, 2.0, 0.0]).unwrap();
+        assert_eq!(antenna.direction, [0.0, 1.0, 0.0]);
+        let fields = antenna.fields(8, 0.25e-10);
+        assert_eq!(fields[1], [0.0; 3]);
+        assert!((fields[2][1] - 0.01).abs() < 1e-15 && (fields[5][1] - 0.01).abs() < 1e-15);
+        assert_eq!(fields[6], [0.0; 3]);
+        assert!(antenna.fields(8, 0.0)[3][1].abs() < 1e-18);
+
+        for profile in [AntennaProfile::Gaussian, AntennaProfile::Hann] {
+            antenna.profile = profile;
+            assert!((antenna.weight(2) - antenna.weight(5)).abs() < 1e-12);
+            assert!(antenna.weight(3) > antenna.weight(2));
+            assert!(antenna.weight(3) <= 1.0);
+        }
+        assert_eq!("hann".parse(), Ok(AntennaProfile::Hann));
+        assert!(Antenna::new(5, 2, 0.01, 1e10, [0.0, 1.0, 0.0]).is_err());
+    }
+
+    #[test]
+    /// Test that an antenna at one end excites the chain from there
+    fn test_antenna_excitation() {
+        let size = 300;
+        let mut system =
+            MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]; size]);
+        let antenna = Antenna::new(0, 3, 0.01, 5e10, [0.0, 1.0, 0.0]).unwrap();
+        let run = DynamicsRun {
+            antenna: Some(antenna),
+            ..DynamicsRun::new(TimeDependentField::Constant([0.1, 0.0, 0.0]))
+        };
+        run.run(&mut system, 50.0 * run.time_step, |_, _| {});
+
+        let deviation = |cell: usize| {
+            let m = &system.get_magnetizations()[cell];
+            m[1].hypot(m[2])
+        };
+        assert!(deviation(1) > 1e-6);
+        assert!(deviation(1) > deviation(20));
+        assert!(deviation(20) > 0.0);
+        // Within 50 steps the excitation spreads by at most two cells per step
+        assert_eq!(deviation(250), 0.0);
+        // The fields of a longer chain do not fit
+        assert!(system
+            .set_local_fields(antenna.fields(size + 1, 0.0))
+            .is_err());
+    }
+
+    #[test]
+    /// Test that the moving frame keeps a driven wall centered and
+    /// reproduces its motion in a chain long enough to hold it
+    fn test_moving_frame() {
+        let wall = |size: usize| {
+            let mut system = MicromagneticSystem::new(size);
+            let center = 0.5 * (size as f64 - 1.0);
+            for i in 0..size {
+                let mut material = system.get_materials()[i];
+                material.anisotropy_constant = 4e6;
+                material.damping = 1.0;
+                system.set_material(i, material);
+                let x = (i as f64 - center - 0.3) / 2.3;
+                system.set_magnetization(i, array![-x.tanh(), 1.0 / x.cosh(), 0.0]);
+            }
+            system
+        };
+        let run = DynamicsRun::new(TimeDependentField::Constant([1.0, 0.0, 0.0]));
+        let duration = 50e-12;
+        let mut long = wall(50);
+        run.run(&mut long, duration, |_, _| {});
+        let start = 24.5 * SPATIAL_DISCRETION_STEP;
+        let travel = wall_position(&long.get_magnetizations(), &EASY_AXIS, long.get_cell_size())
+            .unwrap()
+            - start;
+
+        let mut short = wall(20);
+        let moving = DynamicsRun {
+            moving_frame: Some(MovingFrame::default()),
+            ..run
+        };
+        let mut drift: f64 = 0.0;
+        moving.run(&mut short, duration, |_, system| {
+            let x = wall_position(
+                &system.get_magnetizations(),
+                &EASY_AXIS,
+                system.get_cell_size(),
+            )
+            .unwrap();
+            drift = drift.max((x / SPATIAL_DISCRETION_STEP - 9.5).abs());
+        });
+        // Without the moving frame the wall would reach the end of the short chain
+        assert!(travel > 9.5 * SPATIAL_DISCRETION_STEP);
+        assert!(drift < 2.0);
+        let position = wall_position(
+            &short.get_magnetizations(),
+            &EASY_AXIS,
+            short.get_cell_size(),
+        )
+        .unwrap();
+        let moved = position + short.frame_offset() - 9.5 * SPATIAL_DISCRETION_STEP;
+        assert!((moved - travel).abs() < 0.25 * SPATIAL_DISCRETION_STEP);
+    }
+
+    #[test]
+    /// Test the Larmor precession of an undamped macrospin
+    fn test_larmor_precession() {
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]]);
+        let mut material = system.get_materials()[0];
+        material.anisotropy_constant = 0.0;
+        material.damping = 0.0;
+        system.set_material(0, material);
+
+        // Half a Larmor period in 0.1 T along z turns m from +x to -x
+        let field = 0.1;
+        let frequency = crate::GILBERT_GYROMAGNETIC_RATIO * field
+            / crate::PERMEABILITY_OF_FREE_SPACE
+            / (2.0 * PI);
+        let run = DynamicsRun::new(TimeDependentField::Constant([0.0, 0.0, field]));
+        run.run(&mut system, 0.5 / frequency, |_, _| {});
+        let m = &system.get_magnetizations()[0];
+        assert!((m[0] + 1.0).abs() < 1e-3);
+        assert!((m.dot(m) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    /// Test that a damped macrospin follows a strong slowly rotating field
+    fn test_follows_rotating_field() {
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]]);
+        let mut material = system.get_materials()[0];
+        material.damping = 1.0;
+        system.set_material(0, material);
+
+        let rotating = RotatingField::new(1.0, 1e8, [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]).unwrap();
+        let run = DynamicsRun {
+            time_step: 1e-13,
+            ..DynamicsRun::new(TimeDependentField::Rotating(rotating))
+        };
+        let time = run.run(&mut system, 2.5e-9, |_, _| {});
+
+        // After a quarter turn the moment lags slightly behind the field
+        let field = rotating.at(time);
+        let m: Array1<f64> = system.get_magnetizations()[0].clone();
+        let alignment = m.dot(&array![field[0], field[1], field[2]]);
+        assert!(alignment > 0.99 && m[1] > 0.9);
+    }
+
+    #[test]
+    /// Test the order of the integrators on a damped precessing macrospin
+    fn test_integrator_order() {
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]]);
+        let mut material = system.get_materials()[0];
+        material.anisotropy_constant = 0.0;
+        material.damping = 0.1;
+        system.set_material(0, material);
+        let duration = 2e-11;
+        let final_state = |integrator: Integrator, time_step: f64| {
+            let mut system = system.clone();
+            let run = DynamicsRun {
+                time_step,
+                integrator,
+                ..DynamicsRun::new(TimeDependentField::Constant([0.0, 0.0, 1.0]))
+            };
+            run.run(&mut system, duration, |_, _| {});
+            system.get_magnetizations()[0].clone()
+        };
+        let reference = final_state(Integrator::RungeKutta4, 1e-15);
+
+        // Halving the step divides the error by 2^order
+        for integrator in [Integrator::Euler, Integrator::Heun, Integrator::RungeKutta4] {
+            let error = |time_step: f64| {
+                let difference = final_state(integrator, time_step) - &reference;
+                difference.dot(&difference).sqrt()
+            };
+            let ratio = error(2e-13) / error(1e-13);
+            let expected = 2.0f64.powi(integrator.order());
+            assert!(
+                (ratio / expected - 1.0).abs() < 0.2,
+                "{:?} {}",
+                integrator,
+                ratio
+            );
+        }
+        assert_eq!("rk4".parse(), Ok(Integrator::RungeKutta4));
+
+        // Step doubling grows a short first step and ends at the duration,
+        // closer to the reference than the fixed step in fewer steps
+        let run = DynamicsRun {
+            time_step: 1e-15,
+            adaptive_time_step: Some(AdaptiveTimeStep {
+                tolerance: 1e-5,
+                min_step: 1e-16,
+                max_step: 1e-12,
+            }),
+            ..DynamicsRun::new(TimeDependentField::Constant([0.0, 0.0, 1.0]))
+        };
+        let mut adaptive = system.clone();
+        let mut steps = 0;
+        let end = run.run(&mut adaptive, duration, |time, _| {
+            if time > 0.0 {
+                steps += 1;
+            }
+        });
+        assert_eq!(end, duration);
+        assert!(steps < (duration / 1e-13) as usize);
+        let difference = &adaptive.get_magnetizations()[0] - &reference;
+        let fixed = final_state(Integrator::Heun, 1e-13) - &reference;
+        assert!(difference.dot(&difference) < fixed.dot(&fixed));
+
+        // The trajectory holds the start and every interval after it
+        let run = DynamicsRun::new(TimeDependentField::Constant([0.0, 0.0, 1.0]));
+        let mut precessing = system.clone();
+        let history = run.trajectory(&mut precessing, 1e-11, 1e-12);
+        assert_eq!(history.samples.len(), 11);
+        assert_eq!(history.samples[0][0], [1.0, 0.0, 0.0]);
+        let last = &precessing.get_magnetizations()[0];
+        assert_eq!(history.samples[10][0], [last[0], last[1], last[2]]);
+    }
+
+    #[test]
+    /// Test that the quaternion update keeps |m| = 1 and precesses exactly
+    /// in a uniform field, where the normalized update accumulates a phase error
+    fn test_quaternion_update() {
+        let mut errors = Vec::new();
+        for spin_update in [SpinUpdate::Normalize, SpinUpdate::Quaternion] {
+            let mut system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]]);
+            let mut material = system.get_materials()[0];
+            material.anisotropy_constant = 0.0;
+            material.damping = 0.0;
+            system.set_material(0, material);
+
+            let field = 0.1;
+            let run = DynamicsRun {
+                time_step: 1e-13,
+                spin_update,
+                ..DynamicsRun::new(TimeDependentField::Constant([0.0, 0.0, field]))
+            };
+            let time = run.run(&mut system, 2e-9, |_, _| {});
+            let angle = crate::GILBERT_GYROMAGNETIC_RATIO * field
+                / crate::PERMEABILITY_OF_FREE_SPACE
+                * time;
+            let m = &system.get_magnetizations()[0];
+            let difference = m - &array![angle.cos(), angle.sin(), 0.0];
+            errors.push(difference.dot(&difference).sqrt());
+            assert!((m.dot(m) - 1.0).abs() < 1e-13);
+        }
+        assert!(errors[1] < 1e-12);
+        assert!(errors[0] > 1e3 * errors[1]);
+    }
+}
@@ -0,0 +1,92 @@
+use ndarray::Array2;
+
+// Sweeps of the Jacobi iteration before giving up on further accuracy
+const MAX_SWEEPS: usize = 100;
+
+///# Symmetric Eigendecomposition
+/// Eigenvalues of a real symmetric matrix in ascending order with the
+/// normalized eigenvectors as the columns of the returned matrix, by
+/// cyclic Jacobi rotations. The matrices of the mode and stability
+/// analyses of short chains are small and dense, where Jacobi is simple
+/// and accurate also for tiny eigenvalues.
+pub fn symmetric_eigen(matrix: &Array2<f64>) -> (Vec<f64>, Array2<f64>) {
+    let n = matrix.nrows();
+    assert_eq!(n, matrix.ncols(), "the matrix must be square");
+    let mut a = matrix.clone();
+    let mut vectors = Array2::eye(n);
+
+    let norm: f64 = a.iter().map(|x| x * x).sum();
+    for _ in 0..MAX_SWEEPS {
+        let mut off_diagonal = 0.0;
+        for p in 0..n {
+            for q in p + 1..n {
+                off_diagonal += a[[p, q]] * a[[p, q]];
+            }
+        }
+        if off_diagonal <= 1e-30 * norm {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[[p, q]] == 0.0 {
+                    continue;
+                }
+                // Rotation angle that zeroes a_pq, the smaller root is more accurate
+                let theta = (a[[q, q]] - a[[p, p]]) / (2.0 * a[[p, q]]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for k in 0..n {
+                    let (kp, kq) = (a[[k, p]], a[[k, q]]);
+                    a[[k, p]] = c * kp - s * kq;
+                    a[[k, q]] = s * kp + c * kq;
+                }
+                for k in 0..n {
+                    let (pk, qk) = (a[[p, k]], a[[q, k]]);
+                    a[[p, k]] = c * pk - s * qk;
+                    a[[q, k]] = s * pk + c * qk;
+                }
+                for k in 0..n {
+                    let (kp, kq) = (vectors[[k, p]], vectors[[k, q]]);
+                    vectors[[k, p]] = c * kp - s * kq;
+                    vectors[[k, q]] = s * kp + c * kq;
+                }
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| a[[i, i]].total_cmp(&a[[j, j]]));
+    let values = order.iter().map(|&i| a[[i, i]]).collect();
+    let sorted = Array2::from_shape_fn((n, n), |(k, j)| vectors[[k, order[j]]]);
+    (values, sorted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    /// Test that the decomposition reproduces the matrix
+    fn test_symmetric_eigen() {
+        let matrix = array![
+            [4.0, 1.0, -2.0, 2.0],
+            [1.0, 2.0, 0.0, 1.0],
+            [-2.0, 0.0, 3.0, -2.0],
+            [2.0, 1.0, -2.0, -1.0],
+        ];
+        let (values, vectors) = symmetric_eigen(&matrix);
+        assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+        let trace: f64 = values.iter().sum();
+        assert!((trace - 8.0).abs() < 1e-12);
+        for (j, &value) in values.iter().enumerate() {
+            let vector = vectors.column(j);
+            let residual = matrix.dot(&vector) - value * &vector;
+            assert!(residual.dot(&residual).sqrt() < 1e-12);
+            assert!((vector.dot(&vector) - 1.0).abs() < 1e-12);
+        }
+        let (values, _) = symmetric_eigen(&Array2::from_diag(&array![3.0, -1.0]));
+        assert_eq!(values, vec![-1.0, 3.0]);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::domains::wall_position;
+use crate::magnetic_moments::{Energies, MicromagneticSystem, MinimizationOutcome};
+use crate::{PERMEABILITY_OF_FREE_SPACE, TOLERANCE};
+use ndarray::Array1;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Bisection steps of the projection of a state onto the constraint
+const PROJECTION_STEPS: usize = 200;
+
+///# Reaction Coordinate
+/// Quantity held fixed by the constrained relaxation. Both are the average
+/// projection of the magnetization on an axis, which a uniform field along
+/// the axis changes, so a single Lagrange multiplier holds them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReactionCoordinate {
+    // Average of m . axis over the magnetic cells, within -1..1
+    AverageMagnetization { axis: [f64; 3] },
+    // Position in m of a wall from +easy_axis at the start to -easy_axis at
+    // the end of a chain of magnetic cells. A wall far from the ends turns
+    // the average projection into (2 x / dx + 1) / N - 1 over N cells.
+    WallPosition { easy_axis: [f64; 3] },
+}
+
+impl ReactionCoordinate {
+    ///# Axis
+    /// Unit vector of the projection.
+    pub fn axis(&self) -> [f64; 3] {
+        let (ReactionCoordinate::AverageMagnetization { axis }
+        | ReactionCoordinate::WallPosition { easy_axis: axis }) = *self;
+        let norm = axis.iter().map(|c| c * c).sum::<f64>().sqrt();
+        axis.map(|c| c / norm)
+    }
+
+    ///# Measure
+    /// Current value of the coordinate, `None` for a wall position of a
+    /// state without a wall.
+    pub fn measure(&self, system: &MicromagneticSystem) -> Option<f64> {
+        let axis = self.axis();
+        match self {
+            ReactionCoordinate::AverageMagnetization { .. } => {
+                let m = system.average_magnetization();
+                Some(m[0] * axis[0] + m[1] * axis[1] + m[2] * axis[2])
+            }
+            ReactionCoordinate::WallPosition { .. } => {
+                wall_position(&system.get_magnetizations(), &axis, system.get_cell_size())
+            }
+        }
+    }
+
+    ///# Target Average
+    /// Average projection on the axis that corresponds to the value of the
+    /// coordinate for the given number of magnetic cells of the cell size.
+    fn target_average(&self, value: f64, magnetic_cells: usize, cell_size: f64) -> f64 {
+        match self {
+            ReactionCoordinate::AverageMagnetization { .. } => value,
+            ReactionCoordinate::WallPosition { .. } => {
+                (2.0 * value / cell_size + 1.0) / magnetic_cells as f64 - 1.0
+            }
+        }
+    }
+}
+
+///# Constrained State
+/// Result of a constrained relaxation. The multiplier is the field in T
+/// along the axis that the constraint exerts, the applied field that would
+/// hold the relaxed state in equilibrium without the constraint. The slope
+/// of the energy along the average projection is N Ms V times the
+/// multiplier for N cells of the same material.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConstrainedState {
+    pub outcome: MinimizationOutcome,
+    pub multiplier: f64,
+}
+
+///# Relax Constrained
+/// Minimize the energy with the reaction coordinate held at the value by a
+/// Lagrange multiplier. The state is first projected onto the constraint by
+/// tilting every cell along the axis, then each damping-only relaxation
+/// step gets a uniform extra field along the axis whose strength is solved
+/// for so that the step keeps the average projection at the target. The
+/// iteration limit and the time step are those of the system.
+pub fn relax_constrained(
+    system: &mut MicromagneticSystem,
+    coordinate: ReactionCoordinate,
+    value: f64,
+) -> Result<ConstrainedState, Box<dyn Error>> {
+    let cells: Vec<usize> = (0..system.size())
+        .filter(|&i| !system.is_vacuum(i))
+        .collect();
+    if cells.is_empty() {
+        return Err("The constrained relaxation needs magnetic cells".into());
+    }
+    let target = coordinate.target_average(value, cells.len(), system.get_cell_size());
+    if target.is_nan() || target.abs() >= 1.0 {
+        return Err(format!("The reaction coordinate {} is out of reach", value).into());
+    }
+    let axis = coordinate.axis();
+    let axis_vector = Array1::from_vec(axis.to_vec());
+    project(system, &cells, &axis_vector, target);
+
+    let total = target * cells.len() as f64;
+    let mut multiplier = 0.0;
+    for iteration in 0..system.get_max_iterations() {
+        let h_eff = system.compute_effective_field();
+        let magnetizations = system.get_magnetizations();
+        // Relaxation changes from the effective field and from a unit field
+        // along the axis, the change is linear in the field
+        let changes: Vec<Array1<f64>> = cells
+            .iter()
+            .map(|&i| system.compute_relaxation_change_at(i, &h_eff[i]))
+            .collect();
+        let pulls: Vec<Array1<f64>> = cells
+            .iter()
+            .map(|&i| system.compute_relaxation_change_at(i, &axis_vector))
+            .collect();
+        let mut reached = 0.0;
+        let mut response = 0.0;
+        for (k, &i) in cells.iter().enumerate() {
+            reached += (&magnetizations[i] + &changes[k]).dot(&axis_vector);
+            response += pulls[k].dot(&axis_vector);
+        }
+        // Saturated along the axis no field can move the projection
+        multiplier = if response > 0.0 {
+            (total - reached) / response
+        } else {
+            0.0
+        };
+
+        let mut max_change: f64 = 0.0;
+        for (k, &i) in cells.iter().enumerate() {
+            let change = &changes[k] + &(multiplier * &pulls[k]);
+            max_change = change.iter().map(|c| c.abs()).fold(max_change, f64::max);
+            system.set_magnetization(i, &magnetizations[i] + &change);
+        }
+        if max_change < TOLERANCE {
+            return Ok(ConstrainedState {
+                outcome: MinimizationOutcome::Converged {
+                    iterations: iteration,
+                },
+                multiplier: PERMEABILITY_OF_FREE_SPACE * multiplier,
+            });
+        }
+    }
+    Ok(ConstrainedState {
+        outcome: MinimizationOutcome::NotConverged {
+            iterations: system.get_max_iterations(),
+        },
+        multiplier: PERMEABILITY_OF_FREE_SPACE * multiplier,
+    })
+}
+
+///# Project
+/// Tilt every cell to m + s axis, normalized, with s found by bisection so
+/// that the average projection is the target. The projection of every cell
+/// grows with s, so the average does too.
+fn project(system: &mut MicromagneticSystem, cells: &[usize], axis: &Array1<f64>, target: f64) {
+    let magnetizations = system.get_magnetizations();
+    let tilted = |s: f64, m: &Array1<f64>| {
+        let tilted = m + &(s * axis);
+        let norm = tilted.dot(&tilted).sqrt();
+        if norm > 0.0 {
+            tilted / norm
+        } else {
+            m.clone()
+        }
+    };
+    let average = |s: f64| {
+        cells
+            .iter()
+            .map(|&i| tilted(s, &magnetizations[i]).dot(axis))
+            .sum::<f64>()
+            / cells.len() as f64
+    };
+    // Cells along the axis do not tilt, so the bracket is limited
+    let (mut lower, mut upper) = (-1.0, 1.0);
+    for _ in 0..PROJECTION_STEPS {
+        if average(lower) <= target {
+            break;
+        }
+        lower *= 2.0;
+    }
+    for _ in 0..PROJECTION_STEPS {
+        if average(upper) >= target {
+            break;
+        }
+        upper *= 2.0;
+    }
+    for _ in 0..PROJECTION_STEPS {
+        let middle = 0.5 * (lower + upper);
+        if average(middle) < target {
+            lower = middle;
+        } else {
+            upper = middle;
+        }
+    }
+    let s = 0.5 * (lower + upper);
+    for &i in cells {
+        system.set_magnetization(i, tilted(s, &magnetizations[i]));
+    }
+}
+
+///# Energy Scan
+/// Energy profile along a reaction coordinate from constrained
+/// relaxations. Each value starts from the relaxed state of the previous
+/// one, so the scan follows a continuous path, e.g. over the barrier
+/// between two minima, without the full nudged elastic band.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyScan {
+    pub coordinate: ReactionCoordinate,
+    // Values of the coordinate in the order they are visited
+    pub values: Vec<f64>,
+}
+
+///# Scan Point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanPoint {
+    pub value: f64,
+    // Coordinate of the relaxed state, the wall position found by
+    // domains::wall_position can differ slightly from the constrained one
+    pub measured: Option<f64>,
+    pub energies: Energies,
+    pub state: ConstrainedState,
+}
+
+impl EnergyScan {
+    ///# Linear Scan
+    /// `count` values evenly spaced from `start` to `end`.
+    pub fn linear(coordinate: ReactionCoordinate, start: f64, end: f64, count: usize) -> Self {
+        let values = (0..count)
+            .map(|k| {
+                if count > 1 {
+                    start + (end - start) * k as f64 / (count - 1) as f64
+                } else {
+                    start
+                }
+            })
+            .collect();
+        Self { coordinate, values }
+    }
+
+    ///# Run Scan
+    /// Relax a copy of the system at every value of the coordinate.
+    pub fn run(&self, system: &MicromagneticSystem) -> Result<Vec<ScanPoint>, Box<dyn Error>> {
+        let mut system = system.clone();
+        self.values
+            .iter()
+            .map(|&value| {
+                let state = relax_constrained(&mut system, self.coordinate, value)?;
+                Ok(ScanPoint {
+                    value,
+                    measured: self.coordinate.measure(&system),
+                    energies: system.compute_energies(),
+                    state,
+                })
+            })
+            .collect()
+    }
+}
+
+///# Energy Barrier
+/// Highest total energy of the profile above its first point in J, the
+/// barrier out of the starting minimum. `None` for an empty profile.
+pub fn energy_barrier(points: &[ScanPoint]) -> Option<f64> {
+    let first = points.first()?.energies.total();
+    let highest = points
+        .iter()
+        .map(|point| point.energies.total())
+        .fold(f64::NEG_INFINITY, f64::max);
+    Some(highest - first)
+}
+
+///# Write Energy Profile
+/// Tab-separated coordinate, measured coordinate, energies in J and the
+/// multiplier in T of every point of a scan.
+pub fn write_energy_profile(path: &Path, points: &[ScanPoint]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "# coordinate\tmeasured\tE_total (J)\tE_exchange (J)\tE_anisotropy (J)\tE_Zeeman (J)\tE_dipolar (J)\tE_DMI (J)\tmultiplier (T)\tconverged"
+    )?;
+    for point in points {
+        let energies = &point.energies;
+        writeln!(
+            writer,
+            "{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{}",
+            point.value,
+            point.measured.unwrap_or(f64::NAN),
+            energies.total(),
+            energies.exchange,
+            energies.anisotropy,
+            energies.zeeman,
+            energies.dipolar,
+            energies.dmi,
+            point.state.multiplier,
+            matches!(point.state.outcome, MinimizationOutcome::Converged { .. })
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::parameters::SimulationParameters;
+    use crate::{CELL_VOLUME, SPATIAL_DISCRETION_STEP};
+    use ndarray::array;
+
+    #[test]
+    /// Test the energy profile of a rotating macrospin chain and of a moving wall
+    fn test_energy_scan() {
+        // A uniform chain held at mx = c has E = -N K V c^2, and the
+        // multiplier holds it against the anisotropy field, -2 K c / Ms
+        let material = Material::default();
+        let size = 4;
+        let parameters = SimulationParameters::default()
+            .with_material(material)
+            .with_applied_field([0.0; 3]);
+        let mut system = MicromagneticSystem::with_parameters(size, parameters);
+        for i in 0..size {
+            system.set_magnetization(i, array![1.0, 0.0, 0.1 * (i + 1) as f64]);
+        }
+        let coordinate = ReactionCoordinate::AverageMagnetization {
+            axis: [2.0, 0.0, 0.0],
+        };
+        let scan = EnergyScan::linear(coordinate, 0.9, 0.0, 4);
+        assert!((scan.values[1] - 0.6).abs() < 1e-15);
+        let points = scan.run(&system).unwrap();
+        let k = material.anisotropy_constant;
+        for point in &points {
+            assert!(matches!(
+                point.state.outcome,
+                MinimizationOutcome::Converged { .. }
+            ));
+            assert!((point.measured.unwrap() - point.value).abs() < 1e-9);
+            let c = point.value;
+            let expected = -(size as f64) * k * CELL_VOLUME * c * c;
+            assert!((point.energies.total() - expected).abs() < 1e-4 * k * CELL_VOLUME);
+            let field = -2.0 * k * c / material.saturation_magnetization;
+            assert!((point.state.multiplier - field).abs() < 1e-6);
+        }
+        let barrier = energy_barrier(&points).unwrap();
+        assert!((barrier - size as f64 * k * CELL_VOLUME * 0.81).abs() < 1e-3 * barrier);
+        assert!(relax_constrained(&mut system, coordinate, 1.0).is_err());
+
+        // A wall away from the ends moves freely, its energy is the
+        // 4 sqrt(A K) of the cross section wherever it is held
+        let material = Material {
+            anisotropy_constant: 1.0e6,
+            ..Material::default()
+        };
+        let size = 60;
+        let parameters = SimulationParameters::default()
+            .with_material(material)
+            .with_applied_field([0.0; 3]);
+        let mut system = MicromagneticSystem::with_parameters(size, parameters);
+        for i in 0..size {
+            let x = (i as f64 - 20.0) / 4.0;
+            system.set_magnetization(i, array![-x.tanh(), 0.0, 1.0 / x.cosh()]);
+        }
+        let coordinate = ReactionCoordinate::WallPosition {
+            easy_axis: [1.0, 0.0, 0.0],
+        };
+        let scan = EnergyScan {
+            coordinate,
+            values: vec![25.0e-9, 34.5e-9],
+        };
+        let points = scan.run(&system).unwrap();
+        let k = material.anisotropy_constant;
+        let wall_energy =
+            4.0 * (material.exchange_constant * k).sqrt() * SPATIAL_DISCRETION_STEP.powi(2);
+        let uniform = -(size as f64) * k * CELL_VOLUME;
+        for point in &points {
+            assert!((point.measured.unwrap() - point.value).abs() < 0.05 * SPATIAL_DISCRETION_STEP);
+            let energy = point.energies.total() - uniform;
+            assert!((energy - wall_energy).abs() < 0.02 * wall_energy);
+            assert!(point.state.multiplier.abs() < 1e-3);
+        }
+        let shift = points[1].energies.total() - points[0].energies.total();
+        assert!(shift.abs() < 1e-4 * wall_energy);
+        let path = std::env::temp_dir().join("energy_relaxation_energy_profile_test.txt");
+        write_energy_profile(&path, &points).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text.lines().count(), 3);
+    }
+}
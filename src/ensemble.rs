@@ -0,0 +1,182 @@
+use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+use crate::parallel::map_cells;
+use crate::summation::compensated_sum;
+
+///# Replica Observables
+/// Observables of a single relaxation of the ensemble
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicaObservables {
+    // Average magnetization of the final state
+    pub average_magnetization: [f64; 3],
+    // Time at which <m> along the switching axis first changed sign, if it did
+    pub switching_time: Option<f64>,
+    // Total energy of the final state in J
+    pub final_energy: f64,
+    pub outcome: MinimizationOutcome,
+}
+
+///# Statistic
+/// Mean and standard error of the mean of a set of samples
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistic {
+    pub mean: f64,
+    pub standard_error: f64,
+    pub samples: usize,
+}
+
+impl Statistic {
+    ///# Statistic from Samples
+    /// Uses the unbiased sample variance, the error is zero for fewer than two
+    /// samples and the mean is NaN without samples.
+    pub fn from_samples(samples: &[f64]) -> Self {
+        let n = samples.len();
+        let mean = compensated_sum(samples.iter().cloned()) / n as f64;
+        let standard_error = if n > 1 {
+            let variance =
+                compensated_sum(samples.iter().map(|x| (x - mean).powi(2))) / (n - 1) as f64;
+            (variance / n as f64).sqrt()
+        } else {
+            0.0
+        };
+        Self {
+            mean,
+            standard_error,
+            samples: n,
+        }
+    }
+}
+
+///# Ensemble Statistics
+/// Aggregated observables over all replicas
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnsembleStatistics {
+    pub average_magnetization: [Statistic; 3],
+    // Only replicas that switched contribute to the switching time
+    pub switching_time: Statistic,
+    // Fraction of replicas that switched
+    pub switched_fraction: f64,
+    pub final_energy: Statistic,
+    pub replicas: usize,
+}
+
+impl EnsembleStatistics {
+    ///# Ensemble Statistics from Replicas
+    pub fn from_replicas(replicas: &[ReplicaObservables]) -> Self {
+        let component = |k: usize| {
+            let samples: Vec<f64> = replicas
+                .iter()
+                .map(|r| r.average_magnetization[k])
+                .collect();
+            Statistic::from_samples(&samples)
+        };
+        let switching_times: Vec<f64> = replicas.iter().filter_map(|r| r.switching_time).collect();
+        let energies: Vec<f64> = replicas.iter().map(|r| r.final_energy).collect();
+        Self {
+            average_magnetization: [component(0), component(1), component(2)],
+            switched_fraction: switching_times.len() as f64 / replicas.len().max(1) as f64,
+            switching_time: Statistic::from_samples(&switching_times),
+            final_energy: Statistic::from_samples(&energies),
+            replicas: replicas.len(),
+        }
+    }
+}
+
+///# Relax Replica
+/// Relax one system and record its observables. The switching time is
+/// the pseudo time of the relaxation steps before the switch.
+pub fn relax_replica(
+    mut system: MicromagneticSystem,
+    switching_axis: &[f64; 3],
+) -> ReplicaObservables {
+    let projection = |system: &MicromagneticSystem| -> f64 {
+        let m = system.average_magnetization();
+        (0..3).map(|k| m[k] * switching_axis[k]).sum()
+    };
+    let initial_sign = projection(&system).signum();
+    let mut switching_time = None;
+    let start = system.relaxation_time();
+    let outcome = system.minimize_energy_until(|_, system| {
+        if switching_time.is_none() && projection(system).signum() == -initial_sign {
+            switching_time = Some(system.relaxation_time() - start);
+        }
+        true
+    });
+    ReplicaObservables {
+        average_magnetization: system.average_magnetization(),
+        switching_time,
+        final_energy: system.compute_energies().total(),
+        outcome,
+    }
+}
+
+///# Run Ensemble
+/// Relax `replicas` systems created by `make_system` (given the replica
+/// index) in parallel and aggregate their observables.
+pub fn run_ensemble<F>(
+    replicas: usize,
+    make_system: F,
+    switching_axis: &[f64; 3],
+) -> (Vec<ReplicaObservables>, EnsembleStatistics)
+where
+    F: Fn(usize) -> MicromagneticSystem + Sync + Send,
+{
+    let observables = map_cells(replicas, |r| relax_replica(make_system(r), switching_axis));
+    let statistics = EnsembleStatistics::from_replicas(&observables);
+    (observables, statistics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    /// Test the mean and standard error of known samples
+    fn test_statistic() {
+        let statistic = Statistic::from_samples(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(statistic.mean, 2.5);
+        assert!((statistic.standard_error - (5.0f64 / 12.0).sqrt()).abs() < 1e-12);
+        assert_eq!(Statistic::from_samples(&[7.0]).standard_error, 0.0);
+    }
+
+    #[test]
+    /// Test that a state antiparallel to the field switches
+    fn test_switching_replica() {
+        let mut system = MicromagneticSystem::new(6);
+        for i in 0..6 {
+            system.set_magnetization(i, array![0.1, 0.0, -1.0]);
+        }
+        let observables = relax_replica(system, &[0.0, 0.0, 1.0]);
+        assert!(observables.switching_time.unwrap() > 0.0);
+        assert!(observables.average_magnetization[2] > 0.9);
+    }
+
+    #[test]
+    /// Test aggregating an ensemble of random initial states
+    fn test_run_ensemble() {
+        let (replicas, statistics) =
+            run_ensemble(4, |_| MicromagneticSystem::new(8), &[0.0, 0.0, 1.0]);
+        assert_eq!(replicas.len(), 4);
+        assert_eq!(statistics.replicas, 4);
+        assert_eq!(statistics.final_energy.samples, 4);
+        assert!(statistics.average_magnetization[2].mean > 0.9);
+        assert!(statistics.final_energy.standard_error >= 0.0);
+    }
+
+    #[test]
+    /// Test that the switching is measured along a field that is not along z
+    fn test_in_plane_switching() {
+        let field = [-0.5, 0.0, 0.0];
+        let make_system = |_| {
+            let mut system = MicromagneticSystem::new(6);
+            system.set_applied_field(field);
+            for i in 0..6 {
+                system.set_magnetization(i, array![1.0, 0.1, 0.0]);
+            }
+            system
+        };
+        let (replicas, statistics) = run_ensemble(2, make_system, &field);
+        assert_eq!(statistics.switched_fraction, 1.0);
+        assert!(replicas.iter().all(|r| r.average_magnetization[0] < -0.9));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+///# Minimization Event
+/// Owned description of the progress of a minimization, so subscribers
+/// never borrow the system that is being relaxed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MinimizationEvent {
+    // Emitted every `iteration_interval` steps, starting with step 0
+    Iteration {
+        step: usize,
+        energy: f64,
+        max_torque: f64,
+        average_magnetization: [f64; 3],
+    },
+    // Emitted every `snapshot_interval` steps with the magnetization of every cell
+    Snapshot {
+        step: usize,
+        magnetizations: Vec<[f64; 3]>,
+    },
+    // Emitted once when the minimization has ended
+    Finished(MinimizationOutcome),
+}
+
+///# Event Options
+/// How often a minimization reports its progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventOptions {
+    // Steps between two iteration events
+    pub iteration_interval: usize,
+    // Steps between two snapshot events, no snapshots when unset
+    pub snapshot_interval: Option<usize>,
+}
+
+impl Default for EventOptions {
+    fn default() -> Self {
+        Self {
+            iteration_interval: 100,
+            snapshot_interval: None,
+        }
+    }
+}
+
+///# Minimize with Events
+/// Minimize the energy of the system and send its progress to the channel.
+/// The minimization stops with `Stopped` as soon as the receiver is dropped,
+/// so a frontend cancels a run by dropping its end of the channel.
+pub fn minimize_with_events(
+    system: &mut MicromagneticSystem,
+    sender: Sender<MinimizationEvent>,
+    options: EventOptions,
+) -> MinimizationOutcome {
+    let due = |step: usize, interval: usize| step.is_multiple_of(interval.max(1));
+    let outcome = system.minimize_energy_until(|step, system| {
+        let mut connected = true;
+        if due(step, options.iteration_interval) {
+            let m = system.average_magnetization();
+            connected &= sender
+                .send(MinimizationEvent::Iteration {
+                    step,
+                    energy: system.compute_energies().total(),
+                    max_torque: system.compute_max_torque(),
+                    average_magnetization: [m[0], m[1], m[2]],
+                })
+                .is_ok();
+        }
+        if options.snapshot_interval.is_some_and(|i| due(step, i)) {
+            let magnetizations = system
+                .get_magnetizations()
+                .iter()
+                .map(|m| [m[0], m[1], m[2]])
+                .collect();
+            connected &= sender
+                .send(MinimizationEvent::Snapshot {
+                    step,
+                    magnetizations,
+                })
+                .is_ok();
+        }
+        connected
+    });
+    // A dropped receiver only means nobody is listening any more
+    let _ = sender.send(MinimizationEvent::Finished(outcome));
+    outcome
+}
+
+///# Minimization Handle
+/// A minimization running on its own thread with its event stream. The
+/// stream ends after the `Finished` event.
+pub struct MinimizationHandle {
+    pub events: Receiver<MinimizationEvent>,
+    thread: JoinHandle<(MicromagneticSystem, MinimizationOutcome)>,
+}
+
+impl MinimizationHandle {
+    ///# Wait
+    /// Wait for the minimization to end and return the relaxed system.
+    pub fn wait(self) -> thread::Result<(MicromagneticSystem, MinimizationOutcome)> {
+        self.thread.join()
+    }
+}
+
+///# Spawn Minimization
+/// Move the system to a new thread and minimize its energy there, a GUI
+/// receives the events from `events` without polling the system.
+pub fn spawn_minimization(
+    mut system: MicromagneticSystem,
+    options: EventOptions,
+) -> MinimizationHandle {
+    let (sender, events) = channel();
+    let thread = thread::spawn(move || {
+        let outcome = minimize_with_events(&mut system, sender, options);
+        (system, outcome)
+    });
+    MinimizationHandle { events, thread }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the event stream of a minimization on its own thread
+    fn test_minimization_events() {
+        let options = EventOptions {
+            iteration_interval: 50,
+            snapshot_interval: Some(200),
+        };
+        let handle = spawn_minimization(MicromagneticSystem::new(10), options);
+        let events: Vec<MinimizationEvent> = handle.events.iter().collect();
+        let (system, outcome) = handle.wait().unwrap();
+        assert!(matches!(outcome, MinimizationOutcome::Converged { .. }));
+        assert!(matches!(
+            events[0],
+            MinimizationEvent::Iteration { step: 0, .. }
+        ));
+        assert!(matches!(
+            &events[1],
+            MinimizationEvent::Snapshot { step: 0, magnetizations } if magnetizations.len() == 10
+        ));
+        assert_eq!(events.last(), Some(&MinimizationEvent::Finished(outcome)));
+        let steps: Vec<usize> = events
+            .iter()
+            .filter_map(|event| match event {
+                MinimizationEvent::Iteration { step, .. } => Some(*step),
+                _ => None,
+            })
+            .collect();
+        assert!(steps.len() > 2 && steps.iter().all(|step| step.is_multiple_of(50)));
+        let final_torque = system.compute_max_torque();
+        assert!(events.iter().any(
+            |event| matches!(event, MinimizationEvent::Iteration { max_torque, .. } if *max_torque > final_torque)
+        ));
+
+        // Dropping the receiver cancels the run
+        let (sender, receiver) = channel();
+        drop(receiver);
+        let mut system = MicromagneticSystem::new(10);
+        let outcome = minimize_with_events(&mut system, sender, EventOptions::default());
+        assert_eq!(outcome, MinimizationOutcome::Stopped { iterations: 0 });
+    }
+}
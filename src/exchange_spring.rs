@@ -0,0 +1,181 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::material::Material;
+use crate::summation::compensated_sum;
+use ndarray::Array1;
+
+// Angle between the outermost soft cell and the easy axis that counts as nucleation
+const NUCLEATION_ANGLE_DEGREES: f64 = 10.0;
+// Tilt of the reversal field away from the easy axis, breaks the symmetry
+const FIELD_TILT_DEGREES: f64 = 1.0;
+
+///# Exchange Spring Bilayer
+/// A hard layer (cells `0..hard_cells`) exchange coupled to a soft layer
+/// (the following `soft_cells`). The soft surface is the last cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeSpringBilayer {
+    pub hard: Material,
+    pub soft: Material,
+    pub hard_cells: usize,
+    pub soft_cells: usize,
+}
+
+impl Default for ExchangeSpringBilayer {
+    /// An NdFeB-like hard layer coupled to the built-in iron-like material
+    fn default() -> Self {
+        Self {
+            hard: Material {
+                exchange_constant: 7.7e-12,
+                saturation_magnetization: 1.28e6,
+                anisotropy_constant: 4.3e6,
+                ..Material::default()
+            },
+            soft: Material::default(),
+            hard_cells: 10,
+            soft_cells: 10,
+        }
+    }
+}
+
+///# Nucleation Sweep
+/// Result of a reversal field sweep of an exchange spring bilayer.
+/// The fields are the magnitudes of the reversal field in T.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NucleationSweep {
+    pub fields: Vec<f64>,
+    // Average projection of m on the easy axis in the soft and hard layer
+    pub soft_projection: Vec<f64>,
+    pub hard_projection: Vec<f64>,
+    // Field at which the soft surface first deviates by more than NUCLEATION_ANGLE_DEGREES
+    pub nucleation_field: Option<f64>,
+    // Field at which the hard layer reverses
+    pub switching_field: Option<f64>,
+}
+
+impl ExchangeSpringBilayer {
+    ///# Build System
+    /// Bilayer saturated along the easy axis of the hard layer without applied field.
+    pub fn build(&self) -> MicromagneticSystem {
+        let size = self.hard_cells + self.soft_cells;
+        let mut system = MicromagneticSystem::new(size);
+        system.set_applied_field([0.0; 3]);
+        for cell in 0..size {
+            let material = if cell < self.hard_cells {
+                self.hard
+            } else {
+                self.soft
+            };
+            system.set_material(cell, material);
+            system.set_magnetization(cell, Array1::from_vec(self.hard.easy_axis.to_vec()));
+        }
+        system
+    }
+
+    ///# Measure Nucleation Field
+    /// Start from the saturated bilayer and increase a field antiparallel
+    /// to the easy axis (tilted slightly to break the symmetry) in steps of
+    /// `field_step` up to `max_field`, relaxing at every step.
+    pub fn measure_nucleation_field(&self, field_step: f64, max_field: f64) -> NucleationSweep {
+        let axis = self.hard.easy_axis;
+        let direction = tilted_direction(&[-axis[0], -axis[1], -axis[2]], FIELD_TILT_DEGREES);
+        let mut system = self.build();
+        let mut sweep = NucleationSweep::default();
+        let nucleation_projection = NUCLEATION_ANGLE_DEGREES.to_radians().cos();
+        let size = self.hard_cells + self.soft_cells;
+
+        let steps = (max_field / field_step).round() as usize;
+        for step in 0..=steps {
+            let field = step as f64 * field_step;
+            system.set_applied_field([
+                field * direction[0],
+                field * direction[1],
+                field * direction[2],
+            ]);
+            system.minimize_energy();
+
+            let projections: Vec<f64> = system
+                .get_magnetizations()
+                .iter()
+                .map(|m| m.dot(&Array1::from_vec(axis.to_vec())))
+                .collect();
+            let average =
+                |cells: &[f64]| compensated_sum(cells.iter().cloned()) / cells.len().max(1) as f64;
+            let soft_projection = average(&projections[self.hard_cells..]);
+            let hard_projection = average(&projections[..self.hard_cells]);
+
+            if sweep.nucleation_field.is_none() && projections[size - 1] < nucleation_projection {
+                sweep.nucleation_field = Some(field);
+            }
+            if sweep.switching_field.is_none() && hard_projection < 0.0 {
+                sweep.switching_field = Some(field);
+            }
+            sweep.fields.push(field);
+            sweep.soft_projection.push(soft_projection);
+            sweep.hard_projection.push(hard_projection);
+            if sweep.switching_field.is_some() {
+                break;
+            }
+        }
+        sweep
+    }
+}
+
+///# Tilted Direction
+/// Unit vector tilted away from the axis by the given angle, used to
+/// break the symmetry of fields applied along an easy axis.
+pub(crate) fn tilted_direction(axis: &[f64; 3], tilt_degrees: f64) -> [f64; 3] {
+    // Any vector perpendicular to the axis
+    let helper = if axis[2].abs() < 0.9 {
+        [0.0, 0.0, 1.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+    let mut perpendicular = [
+        axis[1] * helper[2] - axis[2] * helper[1],
+        axis[2] * helper[0] - axis[0] * helper[2],
+        axis[0] * helper[1] - axis[1] * helper[0],
+    ];
+    let norm = perpendicular.iter().map(|x| x * x).sum::<f64>().sqrt();
+    for component in perpendicular.iter_mut() {
+        *component /= norm;
+    }
+    let tilt = tilt_degrees.to_radians();
+    [
+        axis[0] * tilt.cos() + perpendicular[0] * tilt.sin(),
+        axis[1] * tilt.cos() + perpendicular[1] * tilt.sin(),
+        axis[2] * tilt.cos() + perpendicular[2] * tilt.sin(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the layer construction of the bilayer
+    fn test_build() {
+        let bilayer = ExchangeSpringBilayer::default();
+        let system = bilayer.build();
+        let materials = system.get_materials();
+        assert_eq!(system.size(), 20);
+        assert_eq!(materials[9], bilayer.hard);
+        assert_eq!(materials[10], bilayer.soft);
+        assert_eq!(system.get_applied_field(), [0.0; 3]);
+        assert_eq!(system.average_magnetization(), bilayer.hard.easy_axis);
+    }
+
+    #[test]
+    /// Test that the soft layer nucleates near the Kronmueller estimate
+    fn test_nucleation_field() {
+        let bilayer = ExchangeSpringBilayer {
+            hard_cells: 4,
+            soft_cells: 10,
+            ..ExchangeSpringBilayer::default()
+        };
+        let sweep = bilayer.measure_nucleation_field(0.1, 0.6);
+        let nucleation_field = sweep.nucleation_field.unwrap();
+        // mu0 H_N = 2 K_s / Ms + pi^2 A_s / (2 Ms t^2) is about 0.66 T for 10 nm
+        assert!(nucleation_field > 0.2 && nucleation_field < 1.0);
+        assert!(sweep.switching_field.is_none());
+        assert!(sweep.soft_projection[6] < sweep.hard_projection[6]);
+    }
+}
@@ -1,30 +1,35 @@
-use rust_xlsxwriter::Workbook;
+use crate::comparison::{ParameterDifference, RunResult};
+use crate::domains::DomainStatistics;
+use crate::ensemble::{EnsembleStatistics, ReplicaObservables, Statistic};
+use crate::hysteresis::{Branch, HysteresisPoint};
+use crate::spin_waves::ModeMap;
+use crate::SPATIAL_DISCRETION_STEP;
 use ndarray::Array1;
+use rust_xlsxwriter::{Chart, ChartType, Workbook};
 use std::error::Error;
 use std::path::Path;
 
-/// Export the magnetization vectors to an Excel file.
-pub fn export(magnetizations: Vec<Array1<f64>>) -> Result<(), Box<dyn Error>> {
-
+/// Export the magnetization vectors to an Excel file, each with the region
+/// id of its cell.
+pub fn export(
+    magnetizations: Vec<Array1<f64>>,
+    regions: &[usize],
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
     // Create a new workbook and worksheet
-    let path = Path::new("vectors.xlsx");
     let mut workbook = Workbook::new();
     let worksheet = workbook.add_worksheet();
 
     // Write header
-    worksheet.write_row(0, 0, ["X", "Y", "Z"])?;
+    worksheet.write_row(0, 0, ["X", "Y", "Z", "Region"])?;
 
     // Write vector data
     // The first row is the header, so we start from the second row
-    for (i, vector) in magnetizations.iter().enumerate() {
+    for (i, (vector, region)) in magnetizations.iter().zip(regions).enumerate() {
         worksheet.write_row(
             (i + 1) as u32,
             0,
-            [
-                vector[0] as f64,
-                vector[1] as f64,
-                vector[2] as f64,
-            ]
+            [vector[0], vector[1], vector[2], *region as f64],
         )?;
     }
     // Save the workbook
@@ -32,3 +37,317 @@ pub fn export(magnetizations: Vec<Array1<f64>>) -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+/// Export the domain statistics as a small summary table.
+pub fn export_domains(
+    statistics: &DomainStatistics,
+    cell_size: f64,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut workbook = Workbook::new();
+
+    // Summary sheet with the domain count, mean size and wall positions
+    let summary = workbook.add_worksheet().set_name("Summary")?;
+    summary.write_row(0, 0, ["Quantity", "Value"])?;
+    summary.write(1, 0, "Domain count")?;
+    summary.write(1, 1, statistics.count() as f64)?;
+    summary.write(2, 0, "Mean size (cells)")?;
+    summary.write(2, 1, statistics.mean_size())?;
+    summary.write(3, 0, "Mean size (m)")?;
+    summary.write(3, 1, statistics.mean_size() * cell_size)?;
+    summary.write(4, 0, "Boundaries")?;
+    for (j, boundary) in statistics.boundaries.iter().enumerate() {
+        summary.write(4, (j + 1) as u16, *boundary as f64)?;
+    }
+
+    // One row per domain
+    let table = workbook.add_worksheet().set_name("Domains")?;
+    table.write_row(0, 0, ["Domain", "Start", "End", "Size", "Orientation"])?;
+    for (i, domain) in statistics.domains.iter().enumerate() {
+        table.write_row(
+            (i + 1) as u32,
+            0,
+            [
+                i as f64,
+                domain.start as f64,
+                domain.end as f64,
+                domain.size() as f64,
+                domain.orientation as f64,
+            ],
+        )?;
+    }
+
+    workbook.save(path)?;
+
+    Ok(())
+}
+
+/// Export the ensemble statistics and the observables of every replica.
+pub fn export_ensemble(
+    statistics: &EnsembleStatistics,
+    replicas: &[ReplicaObservables],
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut workbook = Workbook::new();
+
+    // Mean and standard error of each observable
+    let summary = workbook.add_worksheet().set_name("Statistics")?;
+    summary.write_row(0, 0, ["Observable", "Mean", "Standard error", "Samples"])?;
+    let rows: [(&str, &Statistic); 5] = [
+        ("<mx>", &statistics.average_magnetization[0]),
+        ("<my>", &statistics.average_magnetization[1]),
+        ("<mz>", &statistics.average_magnetization[2]),
+        ("Switching time (s)", &statistics.switching_time),
+        ("Final energy (J)", &statistics.final_energy),
+    ];
+    for (i, (name, statistic)) in rows.iter().enumerate() {
+        let row = (i + 1) as u32;
+        summary.write(row, 0, *name)?;
+        summary.write_row(
+            row,
+            1,
+            [
+                statistic.mean,
+                statistic.standard_error,
+                statistic.samples as f64,
+            ],
+        )?;
+    }
+    summary.write(6, 0, "Switched fraction")?;
+    summary.write(6, 1, statistics.switched_fraction)?;
+
+    // One row per replica, an empty cell when the replica did not switch
+    let table = workbook.add_worksheet().set_name("Replicas")?;
+    table.write_row(
+        0,
+        0,
+        [
+            "Replica",
+            "<mx>",
+            "<my>",
+            "<mz>",
+            "Switching time (s)",
+            "Final energy (J)",
+        ],
+    )?;
+    for (i, replica) in replicas.iter().enumerate() {
+        let row = (i + 1) as u32;
+        table.write(row, 0, i as f64)?;
+        table.write_row(row, 1, replica.average_magnetization)?;
+        if let Some(switching_time) = replica.switching_time {
+            table.write(row, 4, switching_time)?;
+        }
+        table.write(row, 5, replica.final_energy)?;
+    }
+
+    workbook.save(path)?;
+
+    Ok(())
+}
+
+/// Export a hysteresis loop, one row per field in the order of the sweep,
+/// with a chart of the magnetization along the field of both branches.
+pub fn export_hysteresis(points: &[HysteresisPoint], path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut workbook = Workbook::new();
+
+    let table = workbook.add_worksheet().set_name("Loop")?;
+    table.write_row(
+        0,
+        0,
+        [
+            "B (T)",
+            "Branch",
+            "<mx>",
+            "<my>",
+            "<mz>",
+            "m_B",
+            "Energy (J)",
+        ],
+    )?;
+    for (i, point) in points.iter().enumerate() {
+        let row = (i + 1) as u32;
+        table.write(row, 0, point.field)?;
+        table.write(row, 1, point.branch.label())?;
+        table.write_row(row, 2, point.average_magnetization)?;
+        table.write_row(row, 5, [point.magnetization, point.energy])?;
+    }
+
+    // The descending branch comes first, the ascending branch after it
+    let turn = points
+        .iter()
+        .position(|point| point.branch == Branch::Ascending)
+        .unwrap_or(points.len()) as u32;
+    let mut chart = Chart::new(ChartType::ScatterStraight);
+    chart.title().set_name("Hysteresis loop");
+    chart.x_axis().set_name("B (T)");
+    chart.y_axis().set_name("m_B");
+    for (name, first, last) in [
+        ("Descending", 1, turn),
+        ("Ascending", turn, points.len() as u32),
+    ] {
+        if last > first {
+            chart
+                .add_series()
+                .set_name(name)
+                .set_categories(("Loop", first, 0, last, 0))
+                .set_values(("Loop", first, 5, last, 5));
+        }
+    }
+    table.insert_chart(1, 8, &chart)?;
+
+    workbook.save(path)?;
+
+    Ok(())
+}
+
+/// Export spin wave mode maps, one sheet per frequency with the amplitude
+/// and phase of every cell of the edge length `cell_size` and component.
+pub fn export_mode_maps(
+    maps: &[ModeMap],
+    cell_size: f64,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut workbook = Workbook::new();
+
+    for (j, map) in maps.iter().enumerate() {
+        let sheet = workbook
+            .add_worksheet()
+            .set_name(format!("Mode {}", j + 1))?;
+        sheet.write(0, 0, "Frequency (Hz)")?;
+        sheet.write(0, 1, map.frequency)?;
+        sheet.write_row(
+            1,
+            0,
+            [
+                "Cell",
+                "x (m)",
+                "|mx|",
+                "Phase mx (rad)",
+                "|my|",
+                "Phase my (rad)",
+                "|mz|",
+                "Phase mz (rad)",
+            ],
+        )?;
+        for (i, (amplitude, phase)) in map.amplitude.iter().zip(&map.phase).enumerate() {
+            sheet.write_row(
+                (i + 2) as u32,
+                0,
+                [
+                    i as f64,
+                    i as f64 * cell_size,
+                    amplitude[0],
+                    phase[0],
+                    amplitude[1],
+                    phase[1],
+                    amplitude[2],
+                    phase[2],
+                ],
+            )?;
+        }
+    }
+
+    workbook.save(path)?;
+
+    Ok(())
+}
+
+/// Export a comparison of several runs: the profiles of all runs side by
+/// side with one overlay chart per component, an energy table and the
+/// configuration options that differ between the runs.
+pub fn export_comparison(
+    runs: &[RunResult],
+    differences: &[ParameterDifference],
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut workbook = Workbook::new();
+
+    // Three columns mx, my, mz per run after the cell position, placed
+    // with the cell size of the first run
+    let cell_size = runs
+        .first()
+        .map_or(SPATIAL_DISCRETION_STEP, |run| run.cell_size);
+    let profiles = workbook.add_worksheet().set_name("Profiles")?;
+    profiles.write_row(0, 0, ["Cell", "x (m)"])?;
+    let cells = runs
+        .iter()
+        .map(|run| run.magnetizations.len())
+        .max()
+        .unwrap_or(0);
+    for i in 0..cells {
+        profiles.write_row((i + 1) as u32, 0, [i as f64, i as f64 * cell_size])?;
+    }
+    for (r, run) in runs.iter().enumerate() {
+        let column = (2 + 3 * r) as u16;
+        for (k, component) in ["mx", "my", "mz"].iter().enumerate() {
+            profiles.write(0, column + k as u16, format!("{} {}", run.name, component))?;
+        }
+        for (i, m) in run.magnetizations.iter().enumerate() {
+            profiles.write_row((i + 1) as u32, column, [m[0], m[1], m[2]])?;
+        }
+    }
+    for (k, component) in ["mx", "my", "mz"].iter().enumerate() {
+        let mut chart = Chart::new(ChartType::Scatter);
+        chart.title().set_name(*component);
+        chart.x_axis().set_name("x (m)");
+        for (r, run) in runs.iter().enumerate() {
+            let column = (2 + 3 * r + k) as u16;
+            let last = run.magnetizations.len().max(1) as u32;
+            chart
+                .add_series()
+                .set_name(run.name.as_str())
+                .set_categories(("Profiles", 1, 1, last, 1))
+                .set_values(("Profiles", 1, column, last, column));
+        }
+        profiles.insert_chart((1 + 16 * k) as u32, (3 + 3 * runs.len()) as u16, &chart)?;
+    }
+
+    // One row of energies and averages per run
+    let energies = workbook.add_worksheet().set_name("Energies")?;
+    energies.write_row(
+        0,
+        0,
+        [
+            "Run",
+            "Exchange (J)",
+            "Anisotropy (J)",
+            "Zeeman (J)",
+            "Dipolar (J)",
+            "Total (J)",
+            "<mx>",
+            "<my>",
+            "<mz>",
+        ],
+    )?;
+    for (r, run) in runs.iter().enumerate() {
+        let row = (r + 1) as u32;
+        let e = &run.energies;
+        energies.write(row, 0, run.name.as_str())?;
+        energies.write_row(
+            row,
+            1,
+            [e.exchange, e.anisotropy, e.zeeman, e.dipolar, e.total()],
+        )?;
+        energies.write_row(row, 6, run.average_magnetization)?;
+    }
+
+    // Differing options, an empty cell for runs without a configuration
+    let parameters = workbook.add_worksheet().set_name("Parameters")?;
+    parameters.write(0, 0, "Parameter")?;
+    for (r, run) in runs.iter().enumerate() {
+        parameters.write(0, (r + 1) as u16, run.name.as_str())?;
+    }
+    for (p, difference) in differences.iter().enumerate() {
+        let row = (p + 1) as u32;
+        parameters.write(row, 0, difference.parameter.as_str())?;
+        for (r, value) in difference.values.iter().enumerate() {
+            if let Some(value) = value {
+                parameters.write(row, (r + 1) as u16, value.as_str())?;
+            }
+        }
+    }
+
+    workbook.save(path)?;
+
+    Ok(())
+}
@@ -0,0 +1,441 @@
+use crate::dynamics::{DynamicsRun, TimeDependentField};
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::material::Material;
+use crate::spin_waves::{excite_ringdown, find_peaks, ringdown};
+use ndarray::Array1;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+///# Fit Parameter
+/// Material constant adjusted by the fit, set uniformly in all magnetic
+/// cells. The values are searched on a logarithmic scale and stay positive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FitParameter {
+    ExchangeConstant,
+    AnisotropyConstant,
+    Damping,
+}
+
+impl FitParameter {
+    pub fn name(self) -> &'static str {
+        match self {
+            FitParameter::ExchangeConstant => "A (J/m)",
+            FitParameter::AnisotropyConstant => "K (J/m^3)",
+            FitParameter::Damping => "alpha",
+        }
+    }
+
+    fn get(self, material: &Material) -> f64 {
+        match self {
+            FitParameter::ExchangeConstant => material.exchange_constant,
+            FitParameter::AnisotropyConstant => material.anisotropy_constant,
+            FitParameter::Damping => material.damping,
+        }
+    }
+
+    fn set(self, material: &mut Material, value: f64) {
+        match self {
+            FitParameter::ExchangeConstant => material.exchange_constant = value,
+            FitParameter::AnisotropyConstant => material.anisotropy_constant = value,
+            FitParameter::Damping => material.damping = value,
+        }
+    }
+}
+
+impl FromStr for FitParameter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "A" => Ok(FitParameter::ExchangeConstant),
+            "K" => Ok(FitParameter::AnisotropyConstant),
+            "alpha" => Ok(FitParameter::Damping),
+            _ => Err(format!("unknown fit parameter {}, use A, K or alpha", s)),
+        }
+    }
+}
+
+///# Measurement
+/// Experimental data the simulation is fitted to. Fields are in T along
+/// the field direction of the fit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Measurement {
+    // Average magnetization along the field, the fields are swept in the given order
+    Hysteresis {
+        fields: Vec<f64>,
+        magnetization: Vec<f64>,
+    },
+    // Resonance frequency in Hz at each bias field
+    Fmr {
+        fields: Vec<f64>,
+        frequencies: Vec<f64>,
+    },
+}
+
+impl Measurement {
+    ///# Load Hysteresis Loop
+    /// Text file with "field m" rows, `#` starts a comment.
+    pub fn load_hysteresis(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let (fields, magnetization) = read_columns(path)?;
+        Ok(Measurement::Hysteresis {
+            fields,
+            magnetization,
+        })
+    }
+
+    ///# Load FMR Data
+    /// Text file with "field frequency" rows in T and Hz.
+    pub fn load_fmr(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let (fields, frequencies) = read_columns(path)?;
+        Ok(Measurement::Fmr {
+            fields,
+            frequencies,
+        })
+    }
+
+    pub fn fields(&self) -> &[f64] {
+        match self {
+            Measurement::Hysteresis { fields, .. } | Measurement::Fmr { fields, .. } => fields,
+        }
+    }
+
+    pub fn values(&self) -> &[f64] {
+        match self {
+            Measurement::Hysteresis { magnetization, .. } => magnetization,
+            Measurement::Fmr { frequencies, .. } => frequencies,
+        }
+    }
+}
+
+fn read_columns(path: &Path) -> Result<(Vec<f64>, Vec<f64>), Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let mut first = Vec::new();
+    let mut second = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let values: Result<Vec<f64>, _> = line.split_whitespace().map(str::parse).collect();
+        match values.ok().as_deref() {
+            Some(&[x, y]) => {
+                first.push(x);
+                second.push(y);
+            }
+            _ => {
+                return Err(format!(
+                    "line {} of {} needs two numbers",
+                    number + 1,
+                    path.display()
+                )
+                .into())
+            }
+        }
+    }
+    if first.is_empty() {
+        return Err(format!("{} has no data", path.display()).into());
+    }
+    Ok((first, second))
+}
+
+///# Fit Result
+/// Best-fit values in the order of the fitted parameters, with the
+/// simulated data and the residuals simulated - measured at every point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FitResult {
+    pub values: Vec<f64>,
+    pub simulated: Vec<f64>,
+    pub residuals: Vec<f64>,
+    pub rms_residual: f64,
+    pub evaluations: usize,
+}
+
+///# Fit Problem
+/// Adjusts material constants of a template system until the simulated
+/// loop or resonance frequencies match the measurement, by a Nelder-Mead
+/// search on the logarithm of the parameters. Hysteresis points are
+/// relaxed in sequence from saturation along the first field, FMR points
+/// are relaxed in their bias field and rung down. Along an exact hard
+/// axis the saturated state is an unstable equilibrium the minimizer does
+/// not leave, so the field direction should carry the misalignment of the
+/// experiment.
+#[derive(Debug, Clone)]
+pub struct FitProblem {
+    pub system: MicromagneticSystem,
+    pub parameters: Vec<FitParameter>,
+    pub measurement: Measurement,
+    // Unit vector of the applied field
+    pub field_direction: [f64; 3],
+    pub max_evaluations: usize,
+    // Simulated time of each ringdown in s
+    pub ringdown_duration: f64,
+}
+
+impl FitProblem {
+    pub fn new(
+        system: MicromagneticSystem,
+        parameters: Vec<FitParameter>,
+        measurement: Measurement,
+        field_direction: [f64; 3],
+    ) -> Self {
+        Self {
+            system,
+            parameters,
+            measurement,
+            field_direction,
+            max_evaluations: 200,
+            ringdown_duration: 2e-9,
+        }
+    }
+
+    ///# Initial Values
+    /// The parameters of the first magnetic cell of the template.
+    pub fn initial_values(&self) -> Vec<f64> {
+        let materials = self.system.get_materials();
+        let material = materials
+            .iter()
+            .find(|m| m.saturation_magnetization > 0.0)
+            .copied()
+            .unwrap_or_default();
+        self.parameters.iter().map(|p| p.get(&material)).collect()
+    }
+
+    ///# Simulate
+    /// Simulated data at the measured fields for the given parameter values.
+    pub fn simulate(&self, values: &[f64]) -> Vec<f64> {
+        let mut system = self.system.clone();
+        for (cell, mut material) in system.get_materials().into_iter().enumerate() {
+            if material.saturation_magnetization > 0.0 {
+                for (parameter, &value) in self.parameters.iter().zip(values) {
+                    parameter.set(&mut material, value);
+                }
+                system.set_material(cell, material);
+            }
+        }
+        let direction = Array1::from_vec(self.field_direction.to_vec());
+        let field = |b: f64| self.field_direction.map(|d| d * b);
+        match &self.measurement {
+            Measurement::Hysteresis { fields, .. } => {
+                saturate(&mut system, &direction, fields[0].signum());
+                fields
+                    .iter()
+                    .map(|&b| {
+                        system.set_applied_field(field(b));
+                        system.minimize_energy();
+                        let m = system.average_magnetization();
+                        m[0] * direction[0] + m[1] * direction[1] + m[2] * direction[2]
+                    })
+                    .collect()
+            }
+            Measurement::Fmr { fields, .. } => fields
+                .iter()
+                .map(|&b| {
+                    let mut system = system.clone();
+                    saturate(&mut system, &direction, b.signum());
+                    system.set_applied_field(field(b));
+                    system.minimize_energy();
+                    excite_ringdown(&mut system, 2f64.to_radians());
+                    let run = DynamicsRun::new(TimeDependentField::Constant(field(b)));
+                    let history = ringdown(&mut system, &run, self.ringdown_duration, 5e-12);
+                    let (frequencies, power) = history.power_spectrum();
+                    // Only the strongest peak passes the threshold
+                    find_peaks(&frequencies, &power, 0.999)
+                        .first()
+                        .copied()
+                        .unwrap_or(0.0)
+                })
+                .collect(),
+        }
+    }
+
+    ///# Fit
+    pub fn fit(&self) -> Result<FitResult, Box<dyn Error>> {
+        let measured = self.measurement.values();
+        if self.measurement.fields().len() != measured.len() || measured.is_empty() {
+            return Err("The measurement needs one value per field".into());
+        }
+        if self.parameters.is_empty() {
+            return Err("No parameters to fit".into());
+        }
+        let initial = self.initial_values();
+        if initial.iter().any(|&value| value <= 0.0) {
+            return Err("Only positive parameters can be fitted".into());
+        }
+        // Residuals are weighted by the scale of the data, so loops and
+        // frequencies give comparable objectives
+        let scale = measured
+            .iter()
+            .fold(0.0f64, |s, v| s.max(v.abs()))
+            .max(f64::MIN_POSITIVE);
+        let objective = |logarithms: &[f64]| {
+            let values: Vec<f64> = logarithms.iter().map(|l| l.exp()).collect();
+            self.simulate(&values)
+                .iter()
+                .zip(measured)
+                .map(|(s, m)| ((s - m) / scale).powi(2))
+                .sum::<f64>()
+        };
+        let start: Vec<f64> = initial.iter().map(|v| v.ln()).collect();
+        let (best, evaluations) = nelder_mead(objective, &start, 0.2, self.max_evaluations);
+
+        let values: Vec<f64> = best.iter().map(|l| l.exp()).collect();
+        let simulated = self.simulate(&values);
+        let residuals: Vec<f64> = simulated.iter().zip(measured).map(|(s, m)| s - m).collect();
+        let rms_residual =
+            (residuals.iter().map(|r| r * r).sum::<f64>() / residuals.len() as f64).sqrt();
+        Ok(FitResult {
+            values,
+            simulated,
+            residuals,
+            rms_residual,
+            evaluations,
+        })
+    }
+}
+
+// Start along the field, tilted a little so that a hard axis field still
+// has a direction to fall towards
+fn saturate(system: &mut MicromagneticSystem, direction: &Array1<f64>, sign: f64) {
+    let sign = if sign < 0.0 { -1.0 } else { 1.0 };
+    let helper = if direction[0].abs() < 0.9 {
+        Array1::from_vec(vec![1.0, 0.0, 0.0])
+    } else {
+        Array1::from_vec(vec![0.0, 1.0, 0.0])
+    };
+    let perpendicular = &helper - direction.dot(&helper) * direction;
+    let start = sign * direction + 0.05 * perpendicular;
+    for cell in 0..system.size() {
+        system.set_magnetization(cell, start.clone());
+    }
+}
+
+// Minimize f with the Nelder-Mead simplex method from `start` with an
+// initial simplex of edge `step`, returning the best point and the number
+// of evaluations
+pub(crate) fn nelder_mead<F: Fn(&[f64]) -> f64>(
+    f: F,
+    start: &[f64],
+    step: f64,
+    max_evaluations: usize,
+) -> (Vec<f64>, usize) {
+    let n = start.len();
+    let mut simplex: Vec<(Vec<f64>, f64)> = Vec::with_capacity(n + 1);
+    simplex.push((start.to_vec(), f(start)));
+    for k in 0..n {
+        let mut vertex = start.to_vec();
+        vertex[k] += step;
+        let value = f(&vertex);
+        simplex.push((vertex, value));
+    }
+    let mut evaluations = n + 1;
+    let blend = |a: &[f64], b: &[f64], t: f64| -> Vec<f64> {
+        a.iter().zip(b).map(|(a, b)| a + t * (b - a)).collect()
+    };
+
+    while evaluations < max_evaluations {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (best, worst) = (simplex[0].1, simplex[n].1);
+        let size = simplex[1..]
+            .iter()
+            .flat_map(|(v, _)| v.iter().zip(&simplex[0].0).map(|(a, b)| (a - b).abs()))
+            .fold(0.0, f64::max);
+        if worst - best <= 1e-12 * best.abs() + 1e-30 || size < 1e-4 {
+            break;
+        }
+        let mut centroid = vec![0.0; n];
+        for (vertex, _) in &simplex[..n] {
+            for (c, v) in centroid.iter_mut().zip(vertex) {
+                *c += v / n as f64;
+            }
+        }
+
+        let reflected = blend(&centroid, &simplex[n].0, -1.0);
+        let reflected_value = f(&reflected);
+        evaluations += 1;
+        if reflected_value < best {
+            let expanded = blend(&centroid, &simplex[n].0, -2.0);
+            let expanded_value = f(&expanded);
+            evaluations += 1;
+            simplex[n] = if expanded_value < reflected_value {
+                (expanded, expanded_value)
+            } else {
+                (reflected, reflected_value)
+            };
+        } else if reflected_value < simplex[n - 1].1 {
+            simplex[n] = (reflected, reflected_value);
+        } else {
+            let contracted = blend(&centroid, &simplex[n].0, 0.5);
+            let contracted_value = f(&contracted);
+            evaluations += 1;
+            if contracted_value < worst {
+                simplex[n] = (contracted, contracted_value);
+            } else {
+                // Shrink towards the best vertex
+                let first = simplex[0].0.clone();
+                for (vertex, value) in simplex.iter_mut().skip(1) {
+                    *vertex = blend(&first, vertex, 0.5);
+                    *value = f(vertex);
+                }
+                evaluations += n;
+            }
+        }
+    }
+    simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+    (simplex.swap_remove(0).0, evaluations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magnetic_moments::Minimizer;
+    use crate::UNIAXIAL_ANISOTROPY_CONSTANT;
+
+    #[test]
+    /// Test the simplex search on a curved valley
+    fn test_nelder_mead() {
+        let rosenbrock = |x: &[f64]| (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2);
+        let (best, evaluations) = nelder_mead(rosenbrock, &[-1.2, 1.0], 0.5, 2000);
+        assert!((best[0] - 1.0).abs() < 1e-3 && (best[1] - 1.0).abs() < 1e-3);
+        assert!(evaluations < 2000);
+    }
+
+    #[test]
+    /// Test recovering the anisotropy constant from a hard axis loop
+    fn test_fit_anisotropy() {
+        // The easy axis is x, the anisotropy field 2K/Ms is about 56 mT and
+        // the field is tilted by 3 degrees from the hard axis
+        let fields = vec![0.08, 0.05, 0.04, 0.03, 0.02];
+        // The relaxation creeps along the soft mode close to the hard axis
+        let mut template = MicromagneticSystem::new(2);
+        template.set_minimizer(Minimizer::SphericalConjugateGradient);
+        let measurement = Measurement::Hysteresis {
+            fields: fields.clone(),
+            magnetization: Vec::new(),
+        };
+        let mut problem = FitProblem::new(
+            template,
+            vec![FitParameter::AnisotropyConstant],
+            measurement,
+            [0.05, 0.0, (1.0f64 - 0.05 * 0.05).sqrt()],
+        );
+        let magnetization = problem.simulate(&[UNIAXIAL_ANISOTROPY_CONSTANT]);
+        assert!(magnetization[0] > magnetization[1]);
+        problem.measurement = Measurement::Hysteresis {
+            fields,
+            magnetization,
+        };
+
+        let mut guess = problem.system.get_materials()[0];
+        guess.anisotropy_constant = 0.7 * UNIAXIAL_ANISOTROPY_CONSTANT;
+        for cell in 0..2 {
+            problem.system.set_material(cell, guess);
+        }
+        let result = problem.fit().unwrap();
+        let relative = result.values[0] / UNIAXIAL_ANISOTROPY_CONSTANT - 1.0;
+        assert!(relative.abs() < 0.01, "K off by {}", relative);
+        assert!(result.rms_residual < 1e-2);
+        assert_eq!(result.residuals.len(), 5);
+    }
+}
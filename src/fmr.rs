@@ -0,0 +1,270 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::normal_modes::{tangent_hessian, TangentHessian};
+use crate::parallel::map_cells;
+use crate::spin_waves::ModeMap;
+use crate::{GILBERT_GYROMAGNETIC_RATIO, PERMEABILITY_OF_FREE_SPACE};
+use ndarray::Array2;
+use rustfft::num_complex::Complex;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+///# Driven Response
+/// Steady state of the magnetization under a harmonic drive field
+/// B(t) = Re[b e^(i omega t)] of a single frequency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrivenResponse {
+    // Time-averaged power absorbed from the drive in W
+    pub absorbed_power: f64,
+    // Complex amplitude of the average magnetization
+    pub average: [Complex<f64>; 3],
+    // Amplitude and phase of the oscillation of every cell at the drive frequency
+    pub response: ModeMap,
+}
+
+///# Steady State Solver
+/// Frequency-domain solution of the damped LLG equation linearized around
+/// a relaxed state. In the tangent frame of every cell the small
+/// deviations obey du/dt = D (h - H u / (mu0 Ms V)) with the Hessian H
+/// and D = -gamma / (1 + alpha^2) (J - alpha), where J rotates by 90°
+/// about the magnetization. For a drive at omega the amplitudes follow
+/// from one complex linear system (i omega + D H / (mu0 Ms V)) u = D h,
+/// so a whole absorption spectrum costs no time stepping. The response
+/// is only meaningful around a stable minimum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SteadyStateSolver {
+    size: usize,
+    hessian: TangentHessian,
+    // D H / (mu0 Ms V) in 1/s
+    generator: Array2<f64>,
+    // 2x2 blocks of D in m/(A s) of the magnetic cells
+    coupling: Vec<[[f64; 2]; 2]>,
+    // mu0 Ms V of the magnetic cells in J/T
+    weights: Vec<f64>,
+}
+
+impl SteadyStateSolver {
+    ///# New Steady State Solver
+    /// Linearize the system around its current state.
+    pub fn new(system: &MicromagneticSystem) -> Self {
+        let hessian = tangent_hessian(system);
+        let materials = system.get_materials();
+        let volume = system.cell_volume();
+        let weights: Vec<f64> = hessian
+            .cells
+            .iter()
+            .map(|&i| PERMEABILITY_OF_FREE_SPACE * materials[i].saturation_magnetization * volume)
+            .collect();
+        let coupling: Vec<[[f64; 2]; 2]> = hessian
+            .cells
+            .iter()
+            .map(|&i| {
+                let alpha = materials[i].damping;
+                let factor = -GILBERT_GYROMAGNETIC_RATIO / (1.0 + alpha * alpha);
+                [[-alpha * factor, -factor], [factor, -alpha * factor]]
+            })
+            .collect();
+        let n = hessian.matrix.nrows();
+        let generator = Array2::from_shape_fn((n, n), |(r, s)| {
+            let block = &coupling[r / 2];
+            let row = 2 * (r / 2);
+            (block[r % 2][0] * hessian.matrix[[row, s]]
+                + block[r % 2][1] * hessian.matrix[[row + 1, s]])
+                / weights[r / 2]
+        });
+        Self {
+            size: system.size(),
+            hessian,
+            generator,
+            coupling,
+            weights,
+        }
+    }
+
+    ///# Response
+    /// Steady state under the drive field amplitudes in T of every cell at
+    /// the frequency in Hz.
+    pub fn response(&self, frequency: f64, drive: &[[f64; 3]]) -> DrivenResponse {
+        assert_eq!(drive.len(), self.size, "one drive field per cell");
+        let omega = 2.0 * PI * frequency;
+        let n = self.generator.nrows();
+        let cells = &self.hessian.cells;
+
+        // Tangential drive in A/m and the right-hand side D h
+        let field: Vec<f64> = (0..n)
+            .map(|r| {
+                let frame = &self.hessian.frames[r / 2][r % 2];
+                let b = &drive[cells[r / 2]];
+                (0..3).map(|c| frame[c] * b[c]).sum::<f64>() / PERMEABILITY_OF_FREE_SPACE
+            })
+            .collect();
+        let mut rhs: Vec<Complex<f64>> = (0..n)
+            .map(|r| {
+                let block = &self.coupling[r / 2];
+                let row = 2 * (r / 2);
+                Complex::new(
+                    block[r % 2][0] * field[row] + block[r % 2][1] * field[row + 1],
+                    0.0,
+                )
+            })
+            .collect();
+        let mut matrix = Array2::from_shape_fn((n, n), |(r, s)| {
+            Complex::new(self.generator[[r, s]], if r == s { omega } else { 0.0 })
+        });
+        solve(&mut matrix, &mut rhs);
+
+        // <mu0 Ms V h . du/dt> = -omega / 2 mu0 Ms V Im(h* . u) with a real h
+        let absorbed_power = -0.5
+            * omega
+            * (0..n)
+                .map(|r| self.weights[r / 2] * field[r] * rhs[r].im)
+                .sum::<f64>();
+
+        let mut average = [Complex::new(0.0, 0.0); 3];
+        let mut amplitude = vec![[0.0; 3]; self.size];
+        let mut phase = vec![[0.0; 3]; self.size];
+        for (k, &i) in cells.iter().enumerate() {
+            let frame = &self.hessian.frames[k];
+            for c in 0..3 {
+                let value = rhs[2 * k] * frame[0][c] + rhs[2 * k + 1] * frame[1][c];
+                average[c] += value / self.size as f64;
+                amplitude[i][c] = value.norm();
+                phase[i][c] = value.arg();
+            }
+        }
+        DrivenResponse {
+            absorbed_power,
+            average,
+            response: ModeMap {
+                frequency,
+                amplitude,
+                phase,
+            },
+        }
+    }
+
+    ///# Absorption Spectrum
+    /// Steady states under a uniform drive field amplitude in T at every frequency in Hz.
+    pub fn spectrum(&self, frequencies: &[f64], drive: [f64; 3]) -> Vec<DrivenResponse> {
+        let drive = vec![drive; self.size];
+        map_cells(frequencies.len(), |j| self.response(frequencies[j], &drive))
+    }
+}
+
+// Gaussian elimination with partial pivoting, the solution replaces rhs
+fn solve(matrix: &mut Array2<Complex<f64>>, rhs: &mut [Complex<f64>]) {
+    let n = rhs.len();
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|&a, &b| {
+                matrix[[a, column]]
+                    .norm()
+                    .total_cmp(&matrix[[b, column]].norm())
+            })
+            .unwrap();
+        if pivot != column {
+            for s in column..n {
+                matrix.swap([pivot, s], [column, s]);
+            }
+            rhs.swap(pivot, column);
+        }
+        let diagonal = matrix[[column, column]];
+        for row in column + 1..n {
+            let factor = matrix[[row, column]] / diagonal;
+            if factor == Complex::new(0.0, 0.0) {
+                continue;
+            }
+            for s in column..n {
+                let value = matrix[[column, s]];
+                matrix[[row, s]] -= factor * value;
+            }
+            let value = rhs[column];
+            rhs[row] -= factor * value;
+        }
+    }
+    for row in (0..n).rev() {
+        let mut value = rhs[row];
+        for s in row + 1..n {
+            value -= matrix[[row, s]] * rhs[s];
+        }
+        rhs[row] = value / matrix[[row, row]];
+    }
+}
+
+///# Write Spectrum
+/// Tab-separated absorbed power and complex average magnetization per frequency.
+pub fn write_spectrum(path: &Path, spectrum: &[DrivenResponse]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "# f (Hz)\tP (W)\tRe mx\tIm mx\tRe my\tIm my\tRe mz\tIm mz"
+    )?;
+    for point in spectrum {
+        write!(
+            writer,
+            "{:e}\t{:e}",
+            point.response.frequency, point.absorbed_power
+        )?;
+        for value in &point.average {
+            write!(writer, "\t{:e}\t{:e}", value.re, value.im)?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::{SATURATION_MAGNETIZATION, UNIAXIAL_ANISOTROPY_CONSTANT};
+    use ndarray::array;
+
+    #[test]
+    /// Test the absorption peak of a uniform chain against the Kittel frequency
+    fn test_absorption_spectrum() {
+        let size = 6;
+        let mut system = MicromagneticSystem::new(size);
+        for i in 0..size {
+            system.set_magnetization(i, array![1.0, 0.0, 0.0]);
+            system.set_material(
+                i,
+                Material {
+                    damping: 0.01,
+                    ..Material::default()
+                },
+            );
+        }
+        system.set_applied_field([0.1, 0.0, 0.0]);
+        let solver = SteadyStateSolver::new(&system);
+
+        // omega = gamma (H + H_K), the uniform drive excites no spin waves
+        let mu0_ms = PERMEABILITY_OF_FREE_SPACE * SATURATION_MAGNETIZATION;
+        let field = 0.1 / PERMEABILITY_OF_FREE_SPACE + 2.0 * UNIAXIAL_ANISOTROPY_CONSTANT / mu0_ms;
+        let kittel = GILBERT_GYROMAGNETIC_RATIO * field / (2.0 * PI);
+        let frequencies: Vec<f64> = (-20..=20)
+            .map(|j| kittel * (1.0 + 1e-3 * j as f64))
+            .collect();
+        let spectrum = solver.spectrum(&frequencies, [0.0, 1e-4, 0.0]);
+        assert!(spectrum.iter().all(|point| point.absorbed_power > 0.0));
+        let peak = (0..spectrum.len())
+            .max_by(|&a, &b| {
+                spectrum[a]
+                    .absorbed_power
+                    .total_cmp(&spectrum[b].absorbed_power)
+            })
+            .unwrap();
+        assert_eq!(peak, 20);
+        // The line has the half width alpha omega
+        let half_width = spectrum[30].absorbed_power / spectrum[20].absorbed_power;
+        assert!((half_width - 0.5).abs() < 0.05);
+
+        // In resonance the drive along y leads the precession by 90°
+        let my = spectrum[20].average[1];
+        assert!(my.re.abs() < 0.05 * my.im.abs());
+        assert!(spectrum[20].average[0].norm() < 1e-6 * my.norm());
+        let amplitude = &spectrum[20].response.amplitude;
+        assert!((amplitude[0][1] - amplitude[size - 1][1]).abs() < 1e-6 * amplitude[0][1]);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::neighbors::NeighborList;
+use serde::{Deserialize, Serialize};
+
+///# Grid
+/// Regular mesh of nx x ny x nz cubic cells, whose edge length is the
+/// cell size of the system. The cells are stored in one list with x
+/// varying fastest, then y, then z, as in OVF files, so the chain is the
+/// grid of nx cells. Each cell couples by exchange to its up to six face
+/// neighbors, which gives the 7-point finite difference Laplacian of the
+/// exchange field. The second neighbor exchange couples the cells two
+/// apart along an axis, not across the diagonal of a face. The moving frame,
+/// resampling and the domain analysis treat the cells as a chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "[usize; 3]", into = "[usize; 3]")]
+pub struct Grid {
+    pub nx: usize,
+    pub ny: usize,
+    pub nz: usize,
+}
+
+impl From<[usize; 3]> for Grid {
+    fn from([nx, ny, nz]: [usize; 3]) -> Self {
+        Self::new(nx, ny, nz)
+    }
+}
+
+impl From<Grid> for [usize; 3] {
+    fn from(grid: Grid) -> Self {
+        [grid.nx, grid.ny, grid.nz]
+    }
+}
+
+///# Boundary Condition
+/// Exchange stencil at the outer faces of the grid along the axes with
+/// more than one cell, e.g. the ends of a chain or the edges of a film.
+///
+/// ```toml
+/// boundary_condition = { type = "fixed", direction = [0.0, 0.0, 1.0] }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BoundaryCondition {
+    // Free surfaces, dm/dn = 0, the edge cells only couple inwards
+    #[default]
+    #[serde(alias = "neumann")]
+    Free,
+    // The last cell along an axis couples to the first one, along the
+    // axes with at least three cells
+    Periodic,
+    // A ghost cell with the given magnetization beyond every outer face,
+    // which pins the surface direction as an exchange coupled neighbor
+    Fixed {
+        direction: [f64; 3],
+    },
+}
+
+impl BoundaryCondition {
+    ///# Validate
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let BoundaryCondition::Fixed { direction } = self {
+            let norm = direction.iter().map(|c| c * c).sum::<f64>().sqrt();
+            if !norm.is_finite() || norm == 0.0 {
+                return Err("The fixed boundary needs a nonzero direction".into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Grid {
+    ///# New Grid
+    pub fn new(nx: usize, ny: usize, nz: usize) -> Self {
+        Self { nx, ny, nz }
+    }
+
+    ///# Chain
+    /// The 1D grid of `size` cells along x.
+    pub fn chain(size: usize) -> Self {
+        Self::new(size, 1, 1)
+    }
+
+    ///# Size
+    /// Number of cells.
+    pub fn size(&self) -> usize {
+        self.nx * self.ny * self.nz
+    }
+
+    ///# Is Chain
+    pub fn is_chain(&self) -> bool {
+        self.ny == 1 && self.nz == 1
+    }
+
+    ///# Cell Index
+    pub fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + self.nx * (y + self.ny * z)
+    }
+
+    ///# Cell Coordinates
+    /// Integer coordinates (x, y, z) of a cell.
+    pub fn coordinates(&self, cell: usize) -> [usize; 3] {
+        [
+            cell % self.nx,
+            (cell / self.nx) % self.ny,
+            cell / (self.nx * self.ny),
+        ]
+    }
+
+    ///# Cell Position
+    /// Center of a cell in m for cells with the edge length `cell_size`,
+    /// the first cell is at the origin.
+    pub fn position(&self, cell: usize, cell_size: f64) -> [f64; 3] {
+        self.coordinates(cell)
+            .map(|coordinate| coordinate as f64 * cell_size)
+    }
+
+    ///# In Line
+    /// Whether the face neighbors a, middle and b follow each other along
+    /// one axis in one direction, also across a periodic boundary.
+    pub fn in_line(&self, a: usize, middle: usize, b: usize) -> bool {
+        let first = self.step(a, middle);
+        first.is_some() && first == self.step(middle, b)
+    }
+
+    // Axis and direction of the bond from a to its face neighbor b
+    fn step(&self, a: usize, b: usize) -> Option<(usize, isize)> {
+        let (from, to) = (self.coordinates(a), self.coordinates(b));
+        let lengths = [self.nx, self.ny, self.nz];
+        let mut axes = (0..3).filter(|&k| from[k] != to[k]);
+        let axis = axes.next()?;
+        if axes.next().is_some() {
+            return None;
+        }
+        // Only axes of at least three cells wrap
+        let last = lengths[axis] - 1;
+        let wraps = last >= 2;
+        match (from[axis], to[axis]) {
+            (f, t) if t == f + 1 || (wraps && f == last && t == 0) => Some((axis, 1)),
+            (f, t) if f == t + 1 || (wraps && f == 0 && t == last) => Some((axis, -1)),
+            _ => None,
+        }
+    }
+
+    ///# Checkerboard Color
+    /// 0 or 1, face neighbors have different colors except across the
+    /// periodic wrap of an axis with an odd number of cells.
+    pub fn color(&self, cell: usize) -> usize {
+        self.coordinates(cell).iter().sum::<usize>() % 2
+    }
+
+    ///# Face Neighbor Edges
+    /// Every pair of cells that share a face once, as (lower, higher) cell.
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        let mut edges = Vec::new();
+        for cell in 0..self.size() {
+            let [x, y, z] = self.coordinates(cell);
+            if x + 1 < self.nx {
+                edges.push((cell, self.index(x + 1, y, z)));
+            }
+            if y + 1 < self.ny {
+                edges.push((cell, self.index(x, y + 1, z)));
+            }
+            if z + 1 < self.nz {
+                edges.push((cell, self.index(x, y, z + 1)));
+            }
+        }
+        edges
+    }
+
+    ///# Periodic Edges
+    /// The pairs of cells on opposite outer faces that close the grid
+    /// into a ring, torus or 3-torus along every axis with at least three
+    /// cells, as (lower, higher) cell.
+    pub fn periodic_edges(&self) -> Vec<(usize, usize)> {
+        let mut edges = Vec::new();
+        for cell in 0..self.size() {
+            let [x, y, z] = self.coordinates(cell);
+            if x == 0 && self.nx > 2 {
+                edges.push((cell, self.index(self.nx - 1, y, z)));
+            }
+            if y == 0 && self.ny > 2 {
+                edges.push((cell, self.index(x, self.ny - 1, z)));
+            }
+            if z == 0 && self.nz > 2 {
+                edges.push((cell, self.index(x, y, self.nz - 1)));
+            }
+        }
+        edges
+    }
+
+    ///# Periodic Axes
+    /// The axes with at least three cells for periodic boundaries, along
+    /// which the grid closes into a ring for the exchange and repeats for
+    /// the dipolar field.
+    pub fn periodic_axes(&self, boundary_condition: BoundaryCondition) -> [bool; 3] {
+        let periodic = boundary_condition == BoundaryCondition::Periodic;
+        [self.nx, self.ny, self.nz].map(|n| periodic && n > 2)
+    }
+
+    ///# Boundary Faces
+    /// Number of outer faces of a cell along the axes with more than one
+    /// cell.
+    pub fn boundary_faces(&self, cell: usize) -> usize {
+        let coordinates = self.coordinates(cell);
+        [self.nx, self.ny, self.nz]
+            .iter()
+            .zip(coordinates)
+            .filter(|&(&n, _)| n > 1)
+            .map(|(&n, c)| usize::from(c == 0) + usize::from(c + 1 == n))
+            .sum()
+    }
+
+    ///# Neighbor List
+    /// The face neighbors of every cell.
+    pub fn neighbor_list(&self) -> NeighborList {
+        NeighborList::from_edges(self.size(), &self.edges())
+            .expect("grid edges stay within the grid")
+    }
+
+    ///# Neighbor List with Boundary Condition
+    /// The face neighbors of every cell and, for periodic boundaries, the
+    /// cell on the opposite face.
+    pub fn neighbor_list_with(&self, boundary_condition: BoundaryCondition) -> NeighborList {
+        if boundary_condition != BoundaryCondition::Periodic {
+            return self.neighbor_list();
+        }
+        let edges: Vec<(usize, usize)> = self
+            .edges()
+            .into_iter()
+            .chain(self.periodic_edges())
+            .collect();
+        NeighborList::from_edges(self.size(), &edges).expect("grid edges stay within the grid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the indexing and the face neighbors of a 3D grid
+    fn test_grid() {
+        let grid = Grid::new(4, 3, 2);
+        assert_eq!(grid.size(), 24);
+        assert!(!grid.is_chain() && Grid::chain(5).is_chain());
+        let cell = grid.index(1, 2, 1);
+        assert_eq!(cell, 21);
+        assert_eq!(grid.coordinates(cell), [1, 2, 1]);
+        assert_eq!(grid.position(cell, 2.0e-9)[1], 4.0e-9);
+
+        // Corner, edge, face and interior-free 4 x 3 x 2 grid
+        let neighbors = grid.neighbor_list();
+        assert_eq!(neighbors.neighbors(0), &[1, 4, 12]);
+        assert_eq!(neighbors.neighbors(grid.index(1, 1, 0)).len(), 5);
+        let edges = 3 * 3 * 2 + 4 * 2 * 2 + 4 * 3;
+        assert_eq!(neighbors.edges().count(), edges);
+        assert!(neighbors
+            .edges()
+            .all(|(a, b)| grid.color(a) != grid.color(b)));
+        assert_eq!(Grid::chain(4).neighbor_list(), NeighborList::chain(4));
+
+        let parsed: Grid = serde_json::from_str("[4, 3, 2]").unwrap();
+        assert_eq!(parsed, grid);
+
+        // The 4 x 3 faces wrap, the two layers along z do not
+        let periodic = grid.neighbor_list_with(BoundaryCondition::Periodic);
+        assert_eq!(periodic.neighbors(0), &[1, 3, 4, 8, 12]);
+        assert_eq!(periodic.edges().count(), edges + 3 * 2 + 4 * 2);
+        assert_eq!(
+            Grid::chain(5).neighbor_list_with(BoundaryCondition::Periodic),
+            NeighborList::ring(5)
+        );
+        // Second neighbors in line, also across the periodic faces
+        assert!(grid.in_line(3, 0, 1) && grid.in_line(grid.index(0, 2, 0), 0, 4));
+        assert!(!grid.in_line(1, 0, 4) && !grid.in_line(12, 0, 12));
+        assert_eq!(grid.boundary_faces(0), 3);
+        assert_eq!(grid.boundary_faces(grid.index(1, 1, 0)), 1);
+        assert_eq!(Grid::chain(4).boundary_faces(1), 0);
+        let fixed: BoundaryCondition =
+            serde_json::from_str(r#"{"type": "fixed", "direction": [0.0, 0.0, 0.0]}"#).unwrap();
+        assert!(fixed.validate().is_err());
+        let neumann: BoundaryCondition = serde_json::from_str(r#"{"type": "neumann"}"#).unwrap();
+        assert_eq!(neumann, BoundaryCondition::Free);
+    }
+}
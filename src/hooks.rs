@@ -0,0 +1,187 @@
+use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+///# Run Status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Finished,
+    Failed,
+}
+
+///# Run Summary
+/// JSON document handed to the completion hooks of a run or sweep.
+/// Fields that do not apply to the run are left out.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunSummary {
+    // Name of the command, e.g. "relax" or "ensemble"
+    pub run: String,
+    pub status: RunStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub converged: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_energy: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_magnetization: Option<[f64; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RunSummary {
+    ///# Summary of a Relaxation
+    pub fn relaxed(run: &str, system: &MicromagneticSystem, outcome: MinimizationOutcome) -> Self {
+        let (converged, iterations) = match outcome {
+            MinimizationOutcome::Converged { iterations } => (true, iterations),
+            MinimizationOutcome::NotConverged { iterations }
+            | MinimizationOutcome::Stopped { iterations } => (false, iterations),
+        };
+        Self {
+            run: run.to_string(),
+            status: RunStatus::Finished,
+            converged: Some(converged),
+            iterations: Some(iterations),
+            total_energy: Some(system.compute_energies().total()),
+            average_magnetization: Some(system.average_magnetization()),
+            error: None,
+        }
+    }
+
+    ///# Summary of a Finished Sweep
+    pub fn finished(run: &str) -> Self {
+        Self {
+            run: run.to_string(),
+            status: RunStatus::Finished,
+            converged: None,
+            iterations: None,
+            total_energy: None,
+            average_magnetization: None,
+            error: None,
+        }
+    }
+
+    ///# Summary of a Failed Run
+    pub fn failed(run: &str, error: &dyn std::fmt::Display) -> Self {
+        Self {
+            status: RunStatus::Failed,
+            error: Some(error.to_string()),
+            ..Self::finished(run)
+        }
+    }
+}
+
+///# Completion Hooks
+/// Shell commands executed when a run ends, e.g. to notify a chat or
+/// send an email from a cluster job. The summary JSON is written to the
+/// standard input of the command and is also available in the
+/// `ENERGY_RELAXATION_SUMMARY` environment variable, so a webhook is a
+/// `curl -d @- https://...` away.
+///
+/// ```toml
+/// [hooks]
+/// on_finish = ["curl -s -H 'Content-Type: application/json' -d @- https://example.org/hook"]
+/// on_failure = ["mail -s 'run failed' me@example.org"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompletionHooks {
+    // Commands run after a successful run
+    #[serde(default)]
+    pub on_finish: Vec<String>,
+    // Commands run when the run fails
+    #[serde(default)]
+    pub on_failure: Vec<String>,
+}
+
+impl CompletionHooks {
+    ///# Notify
+    /// Run the hooks matching the status of the summary one after the
+    /// other and collect the failures, a failing hook does not stop the
+    /// following ones.
+    pub fn notify(&self, summary: &RunSummary) -> Vec<Box<dyn Error>> {
+        let commands = match summary.status {
+            RunStatus::Finished => &self.on_finish,
+            RunStatus::Failed => &self.on_failure,
+        };
+        let json = serde_json::to_string(summary).expect("summaries serialize to JSON");
+        commands
+            .iter()
+            .filter_map(|command| run_hook(command, &json).err())
+            .collect()
+    }
+}
+
+fn run_hook(command: &str, json: &str) -> Result<(), Box<dyn Error>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("ENERGY_RELAXATION_SUMMARY", json)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    // Hooks that do not read their input close the pipe early
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(json.as_bytes());
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(format!("hook `{}` exited with {}", command, status).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that the matching hooks receive the summary JSON
+    fn test_completion_hooks() {
+        let directory = std::env::temp_dir().join("energy_relaxation_hooks_test");
+        std::fs::create_dir_all(&directory).unwrap();
+        let stdin_copy = directory.join("stdin.json");
+        let env_copy = directory.join("env.json");
+        let hooks = CompletionHooks {
+            on_finish: vec![
+                format!("cat > {}", stdin_copy.display()),
+                "exit 3".to_string(),
+                format!(
+                    "printf '%s' \"$ENERGY_RELAXATION_SUMMARY\" > {}",
+                    env_copy.display()
+                ),
+            ],
+            on_failure: vec![format!("cat > {}", directory.join("failed").display())],
+        };
+
+        let system = MicromagneticSystem::new(4);
+        let outcome = MinimizationOutcome::Converged { iterations: 12 };
+        let summary = RunSummary::relaxed("relax", &system, outcome);
+        let errors = hooks.notify(&summary);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("exit 3"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&stdin_copy).unwrap()).unwrap();
+        assert_eq!(json["run"], "relax");
+        assert_eq!(json["status"], "finished");
+        assert_eq!(json["iterations"], 12);
+        assert!(json.get("error").is_none());
+        assert_eq!(
+            std::fs::read_to_string(&env_copy).unwrap(),
+            serde_json::to_string(&summary).unwrap()
+        );
+        assert!(!directory.join("failed").exists());
+
+        let failed = RunSummary::failed("ensemble", &"no such material");
+        assert!(hooks.notify(&failed).is_empty());
+        let json = std::fs::read_to_string(directory.join("failed")).unwrap();
+        assert_eq!(
+            json,
+            r#"{"run":"ensemble","status":"failed","error":"no such material"}"#
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+use crate::EASY_AXIS;
+use ndarray::Array1;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Weak fields near the switching field relax slowly, so a relaxation is
+// continued for up to this many iteration limits before giving up
+const MAX_RELAXATION_ROUNDS: usize = 50;
+
+///# Branch
+/// Half of a hysteresis loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Branch {
+    // From +max_field to -max_field
+    Descending,
+    // From -max_field back to +max_field
+    Ascending,
+}
+
+impl Branch {
+    ///# Label
+    pub fn label(self) -> &'static str {
+        match self {
+            Branch::Descending => "descending",
+            Branch::Ascending => "ascending",
+        }
+    }
+}
+
+///# Hysteresis Loop
+/// Quasi-static field sweep along an axis from +max_field to -max_field
+/// and back. The system starts saturated along +axis, and every field is
+/// relaxed from the state of the previous field. The field is tilted off
+/// the axis by a small angle, as the misalignment of a magnetometer: in an
+/// exactly antiparallel field the state feels no torque and never switches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HysteresisLoop {
+    // Direction of the field, normalized by the sweep
+    pub axis: [f64; 3],
+    // Largest field B = mu0 H in T
+    pub max_field: f64,
+    // Field steps of each branch
+    pub steps: usize,
+    // Angle of the field off the axis in degrees
+    pub tilt: f64,
+}
+
+///# Hysteresis Point
+/// Relaxed state at one field of the loop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HysteresisPoint {
+    // Signed field magnitude of the sweep in T
+    pub field: f64,
+    pub branch: Branch,
+    pub average_magnetization: [f64; 3],
+    // Projection of the average magnetization on the axis
+    pub magnetization: f64,
+    // Total energy in J
+    pub energy: f64,
+    pub converged: bool,
+}
+
+impl Default for HysteresisLoop {
+    /// Easy axis loop up to 0.1 T in steps of 5 mT, past the anisotropy
+    /// field of the default material
+    fn default() -> Self {
+        Self {
+            axis: EASY_AXIS,
+            max_field: 0.1,
+            steps: 40,
+            tilt: 1.0,
+        }
+    }
+}
+
+impl HysteresisLoop {
+    ///# Fields
+    /// Fields of the loop in T in the order of the sweep, +max_field to
+    /// -max_field and back, the turning point only once.
+    pub fn fields(&self) -> Vec<(f64, Branch)> {
+        let step = 2.0 * self.max_field / self.steps as f64;
+        let descending =
+            (0..=self.steps).map(|k| (self.max_field - k as f64 * step, Branch::Descending));
+        let ascending =
+            (1..=self.steps).map(|k| (-self.max_field + k as f64 * step, Branch::Ascending));
+        descending.chain(ascending).collect()
+    }
+
+    ///# Run Loop
+    /// Sweep a copy of the system through the fields of the loop.
+    pub fn run(
+        &self,
+        system: &MicromagneticSystem,
+    ) -> Result<Vec<HysteresisPoint>, Box<dyn Error>> {
+        let norm = self.axis.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            return Err("The field axis is zero".into());
+        }
+        if self.max_field.is_nan() || self.max_field <= 0.0 {
+            return Err("The maximum field must be positive".into());
+        }
+        if self.steps == 0 {
+            return Err("The loop needs at least one field step".into());
+        }
+        let axis = self.axis.map(|x| x / norm);
+        let mut system = system.clone();
+        let helper = if axis[0].abs() < 0.9 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        };
+        let along = (0..3).map(|k| helper[k] * axis[k]).sum::<f64>();
+        let perpendicular = {
+            let p: [f64; 3] = std::array::from_fn(|k| helper[k] - along * axis[k]);
+            let norm = p.iter().map(|x| x * x).sum::<f64>().sqrt();
+            p.map(|x| x / norm)
+        };
+        let (sin, cos) = self.tilt.to_radians().sin_cos();
+        let direction: [f64; 3] = std::array::from_fn(|k| cos * axis[k] + sin * perpendicular[k]);
+        for cell in 0..system.size() {
+            system.set_magnetization(cell, Array1::from_vec(axis.to_vec()));
+        }
+
+        let mut points = Vec::with_capacity(2 * self.steps + 1);
+        for (field, branch) in self.fields() {
+            system.set_applied_field(direction.map(|x| x * field));
+            let converged = relax(&mut system);
+            let m = system.average_magnetization();
+            points.push(HysteresisPoint {
+                field,
+                branch,
+                average_magnetization: m,
+                magnetization: (0..3).map(|k| m[k] * axis[k]).sum(),
+                energy: system.compute_energies().total(),
+                converged,
+            });
+        }
+        Ok(points)
+    }
+}
+
+///# Relax
+/// Relax for up to the given number of iteration limits and report
+/// whether the relaxation converged.
+fn relax(system: &mut MicromagneticSystem) -> bool {
+    for _ in 0..MAX_RELAXATION_ROUNDS {
+        match system.minimize_energy_until(|_, _| true) {
+            MinimizationOutcome::NotConverged { .. } => {}
+            outcome => return matches!(outcome, MinimizationOutcome::Converged { .. }),
+        }
+    }
+    false
+}
+
+///# Coercive Field
+/// Field in T at which the magnetization of the branch first changes sign,
+/// interpolated between the two points around the crossing. `None` when
+/// the branch does not switch.
+pub fn coercive_field(points: &[HysteresisPoint], branch: Branch) -> Option<f64> {
+    crossing(
+        points,
+        branch,
+        |point| point.magnetization,
+        |point| point.field,
+    )
+}
+
+///# Remanence
+/// Magnetization along the axis of the branch at zero field, interpolated
+/// between the two points around it.
+pub fn remanence(points: &[HysteresisPoint], branch: Branch) -> Option<f64> {
+    crossing(
+        points,
+        branch,
+        |point| point.field,
+        |point| point.magnetization,
+    )
+}
+
+// Value of `output` where `input` first changes sign along the branch
+fn crossing(
+    points: &[HysteresisPoint],
+    branch: Branch,
+    input: impl Fn(&HysteresisPoint) -> f64,
+    output: impl Fn(&HysteresisPoint) -> f64,
+) -> Option<f64> {
+    let branch: Vec<&HysteresisPoint> = points.iter().filter(|p| p.branch == branch).collect();
+    branch.windows(2).find_map(|pair| {
+        let (a, b) = (input(pair[0]), input(pair[1]));
+        if a == 0.0 {
+            return Some(output(pair[0]));
+        }
+        (a * b < 0.0).then(|| {
+            let fraction = a / (a - b);
+            output(pair[0]) + fraction * (output(pair[1]) - output(pair[0]))
+        })
+    })
+}
+
+///# Write Hysteresis CSV
+/// Comma-separated M-H table with one row per field in the order of the
+/// sweep.
+pub fn write_hysteresis_csv(path: &Path, points: &[HysteresisPoint]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "B (T),branch,<mx>,<my>,<mz>,m_B,E (J),converged")?;
+    for point in points {
+        let m = point.average_magnetization;
+        writeln!(
+            writer,
+            "{:e},{},{:e},{:e},{:e},{:e},{:e},{}",
+            point.field,
+            point.branch.label(),
+            m[0],
+            m[1],
+            m[2],
+            point.magnetization,
+            point.energy,
+            point.converged
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SATURATION_MAGNETIZATION, UNIAXIAL_ANISOTROPY_CONSTANT};
+
+    #[test]
+    /// Test the square easy axis loop of a macrospin against the
+    /// Stoner-Wohlfarth coercivity
+    fn test_hysteresis_loop() {
+        let sweep = HysteresisLoop {
+            max_field: 0.08,
+            steps: 32,
+            ..HysteresisLoop::default()
+        };
+        let fields = sweep.fields();
+        assert_eq!(fields.len(), 65);
+        assert_eq!(fields[32], (-0.08, Branch::Descending));
+        assert_eq!(fields[64].1, Branch::Ascending);
+        assert!((fields[64].0 - 0.08).abs() < 1e-15);
+
+        let points = sweep.run(&MicromagneticSystem::new(1)).unwrap();
+        // B_sw = B_K / (cos^(2/3) + sin^(2/3))^(3/2) of the tilt with
+        // B_K = 2 K / Ms, within one field step
+        let (sin, cos) = sweep.tilt.to_radians().sin_cos();
+        let anisotropy_field = 2.0 * UNIAXIAL_ANISOTROPY_CONSTANT
+            / SATURATION_MAGNETIZATION
+            / (cos.powf(2.0 / 3.0) + sin.powf(2.0 / 3.0)).powf(1.5);
+        let down = coercive_field(&points, Branch::Descending).unwrap();
+        let up = coercive_field(&points, Branch::Ascending).unwrap();
+        assert!((down + anisotropy_field).abs() < 5e-3);
+        assert!((up - anisotropy_field).abs() < 5e-3);
+        assert!((remanence(&points, Branch::Descending).unwrap() - 1.0).abs() < 1e-2);
+        assert!((remanence(&points, Branch::Ascending).unwrap() + 1.0).abs() < 1e-2);
+
+        // A hard axis loop has no coercivity
+        let hard = HysteresisLoop {
+            axis: [0.0, 0.0, 1.0],
+            ..sweep
+        };
+        let points = hard.run(&MicromagneticSystem::new(1)).unwrap();
+        assert!(remanence(&points, Branch::Descending).unwrap().abs() < 1e-2);
+
+        let path = std::env::temp_dir().join("energy_relaxation_hysteresis_test.csv");
+        write_hysteresis_csv(&path, &points).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text.lines().count(), 66);
+        assert!(text.lines().nth(1).unwrap().starts_with("8e-2,descending,"));
+        assert!(HysteresisLoop { steps: 0, ..sweep }
+            .run(&MicromagneticSystem::new(1))
+            .is_err());
+    }
+}
@@ -0,0 +1,367 @@
+use crate::grid::Grid;
+use crate::magnetic_moments::MicromagneticSystem;
+use ndarray::Array1;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use serde::Deserialize;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+///# Magnetization Component
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Component {
+    X,
+    Y,
+    #[default]
+    Z,
+}
+
+impl Component {
+    pub fn index(self) -> usize {
+        match self {
+            Component::X => 0,
+            Component::Y => 1,
+            Component::Z => 2,
+        }
+    }
+}
+
+impl FromStr for Component {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x" | "mx" => Ok(Component::X),
+            "y" | "my" => Ok(Component::Y),
+            "z" | "mz" => Ok(Component::Z),
+            _ => Err(format!("unknown component {}", s)),
+        }
+    }
+}
+
+///# Color Map
+/// Mapping of a normalized component in [-1, 1] to a color. The heatmap
+/// runs from blue at -1 over white at 0 to red at +1. The HSL map is the
+/// mumax3 color wheel of the whole vector: the hue is the in-plane angle
+/// and the lightness runs from black at mz = -1 to white at mz = +1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMap {
+    Grayscale,
+    #[default]
+    Heatmap,
+    Hsl,
+}
+
+impl ColorMap {
+    ///# Color of a Component
+    /// The HSL map shades a single component like an out-of-plane vector.
+    pub fn color(self, value: f64) -> [u8; 3] {
+        let value = value.clamp(-1.0, 1.0);
+        let channel = |fraction: f64| (255.0 * fraction).round() as u8;
+        match self {
+            ColorMap::Grayscale => {
+                let gray = channel(0.5 * (value + 1.0));
+                [gray, gray, gray]
+            }
+            ColorMap::Heatmap if value >= 0.0 => {
+                let fade = channel(1.0 - value);
+                [255, fade, fade]
+            }
+            ColorMap::Heatmap => {
+                let fade = channel(1.0 + value);
+                [fade, fade, 255]
+            }
+            ColorMap::Hsl => hsl_color(&[0.0, 0.0, value]),
+        }
+    }
+}
+
+///# HSL Color
+/// mumax3 color wheel of a unit vector at full saturation.
+pub fn hsl_color(m: &[f64; 3]) -> [u8; 3] {
+    let hue = m[1].atan2(m[0]).to_degrees().rem_euclid(360.0) / 60.0;
+    let lightness = 0.5 * (m[2].clamp(-1.0, 1.0) + 1.0);
+    let chroma = 1.0 - (2.0 * lightness - 1.0).abs();
+    let second = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as usize {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let offset = lightness - 0.5 * chroma;
+    [r, g, b].map(|c| (255.0 * (c + offset)).round() as u8)
+}
+
+impl FromStr for ColorMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grayscale" => Ok(ColorMap::Grayscale),
+            "heatmap" => Ok(ColorMap::Heatmap),
+            "hsl" => Ok(ColorMap::Hsl),
+            _ => Err(format!("unknown color map {}", s)),
+        }
+    }
+}
+
+///# Render Component
+/// Color of every cell for one magnetization component, in the order of
+/// the cells. A 2D state is stored in rows with x running fastest. The
+/// HSL map colors the whole vector and ignores the component.
+pub fn render_component(
+    magnetizations: &[Array1<f64>],
+    component: Component,
+    color_map: ColorMap,
+) -> Vec<[u8; 3]> {
+    magnetizations
+        .iter()
+        .map(|m| match color_map {
+            ColorMap::Hsl => hsl_color(&[m[0], m[1], m[2]]),
+            _ => color_map.color(m[component.index()]),
+        })
+        .collect()
+}
+
+///# Write PNG
+/// Write the cell colors of a `width` cells wide state as a PNG image,
+/// drawing every cell as a square of `scale` pixels. The first row of
+/// cells is at the bottom of the image, a 1D chain is a single row.
+pub fn write_png(
+    path: &Path,
+    colors: &[[u8; 3]],
+    width: usize,
+    scale: u32,
+) -> Result<(), Box<dyn Error>> {
+    let size = image_size(colors.len(), width, scale)?;
+    let root = BitMapBackend::new(path, size).into_drawing_area();
+    draw_cells(&root, colors, width, scale)?;
+    root.present()?;
+    Ok(())
+}
+
+///# Write GIF
+/// Write equally sized frames of cell colors as an animated GIF showing
+/// `frame_rate` frames per second, laid out as in `write_png`.
+pub fn write_gif(
+    path: &Path,
+    frames: &[Vec<[u8; 3]>],
+    width: usize,
+    scale: u32,
+    frame_rate: f64,
+) -> Result<(), Box<dyn Error>> {
+    let cells = frames.first().map_or(0, Vec::len);
+    let size = image_size(cells, width, scale)?;
+    if frames.iter().any(|frame| frame.len() != cells) {
+        return Err("all frames must have the same number of cells".into());
+    }
+    let valid = frame_rate > 0.0;
+    if !valid {
+        return Err(format!("invalid frame rate {}", frame_rate).into());
+    }
+    let delay = (1000.0 / frame_rate).round() as u32;
+    let root = BitMapBackend::gif(path, size, delay)?.into_drawing_area();
+    for frame in frames {
+        draw_cells(&root, frame, width, scale)?;
+        root.present()?;
+    }
+    Ok(())
+}
+
+///# Image Width
+/// Cells per image row of the grid, nx, so a film renders as nx x ny
+/// cells. A 3D grid has no single image.
+pub fn image_width(grid: Grid) -> Result<usize, Box<dyn Error>> {
+    if grid.nz > 1 {
+        return Err(format!(
+            "only chains and films can be rendered, not a {}x{}x{} grid",
+            grid.nx, grid.ny, grid.nz
+        )
+        .into());
+    }
+    Ok(grid.nx)
+}
+
+// Pixel size of an image of `cells` in rows of `width` cells
+fn image_size(cells: usize, width: usize, scale: u32) -> Result<(u32, u32), Box<dyn Error>> {
+    if width == 0 || cells == 0 || !cells.is_multiple_of(width) || scale == 0 {
+        return Err(format!(
+            "cannot draw {} cells in rows of {} at scale {}",
+            cells, width, scale
+        )
+        .into());
+    }
+    Ok((width as u32 * scale, (cells / width) as u32 * scale))
+}
+
+fn draw_cells(
+    root: &DrawingArea<BitMapBackend, Shift>,
+    colors: &[[u8; 3]],
+    width: usize,
+    scale: u32,
+) -> Result<(), Box<dyn Error>> {
+    let height = colors.len() / width;
+    for (index, &[r, g, b]) in colors.iter().enumerate() {
+        let column = (index % width) as u32;
+        let row = (height - 1 - index / width) as u32;
+        for py in row * scale..(row + 1) * scale {
+            for px in column * scale..(column + 1) * scale {
+                root.draw_pixel((px as i32, py as i32), &RGBColor(r, g, b))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+///# Export Component PNG
+/// Render one component of a state with the color map and write it as a PNG.
+pub fn export_component_png(
+    path: &Path,
+    magnetizations: &[Array1<f64>],
+    width: usize,
+    component: Component,
+    color_map: ColorMap,
+    scale: u32,
+) -> Result<(), Box<dyn Error>> {
+    let colors = render_component(magnetizations, component, color_map);
+    write_png(path, &colors, width, scale)
+}
+
+///# Animation Recorder
+/// Collects a rendered frame of the state every `interval` relaxation
+/// steps, to be fed with the observer of the minimizer.
+#[derive(Debug, Clone)]
+pub struct AnimationRecorder {
+    pub interval: usize,
+    pub component: Component,
+    pub color_map: ColorMap,
+    pub frames: Vec<Vec<[u8; 3]>>,
+}
+
+impl AnimationRecorder {
+    pub fn new(interval: usize, component: Component, color_map: ColorMap) -> Self {
+        Self {
+            interval: interval.max(1),
+            component,
+            color_map,
+            frames: Vec::new(),
+        }
+    }
+
+    ///# Record Step
+    /// Render the state if the step is a multiple of the frame interval.
+    pub fn record(&mut self, step: usize, system: &MicromagneticSystem) {
+        if step.is_multiple_of(self.interval) {
+            let colors =
+                render_component(&system.get_magnetizations(), self.component, self.color_map);
+            self.frames.push(colors);
+        }
+    }
+
+    ///# Record Final State
+    /// Append the final state unless it already is the last frame.
+    pub fn finish(&mut self, system: &MicromagneticSystem) {
+        let colors = render_component(&system.get_magnetizations(), self.component, self.color_map);
+        if self.frames.last() != Some(&colors) {
+            self.frames.push(colors);
+        }
+    }
+
+    ///# Write Animation
+    pub fn write_gif(
+        &self,
+        path: &Path,
+        width: usize,
+        scale: u32,
+        frame_rate: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        write_gif(path, &self.frames, width, scale, frame_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    /// Test the color maps and the PNG file of a chain
+    fn test_export_component_png() {
+        assert_eq!(ColorMap::Grayscale.color(-1.0), [0, 0, 0]);
+        assert_eq!(ColorMap::Grayscale.color(1.0), [255, 255, 255]);
+        assert_eq!(ColorMap::Heatmap.color(0.0), [255, 255, 255]);
+        assert_eq!(ColorMap::Heatmap.color(1.0), [255, 0, 0]);
+        assert_eq!(ColorMap::Heatmap.color(-1.0), [0, 0, 255]);
+        assert_eq!("mz".parse(), Ok(Component::Z));
+
+        let magnetizations = vec![array![1.0, 0.0, 0.0], array![0.0, 0.0, -1.0]];
+        let colors = render_component(&magnetizations, Component::Z, ColorMap::Heatmap);
+        assert_eq!(colors, vec![[255, 255, 255], [0, 0, 255]]);
+
+        let path = std::env::temp_dir().join("energy_relaxation_component.png");
+        export_component_png(
+            &path,
+            &magnetizations,
+            2,
+            Component::Z,
+            ColorMap::Heatmap,
+            4,
+        )
+        .unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+        // The IHDR chunk holds the width and height in pixels
+        assert_eq!(&bytes[16..24], &[0, 0, 0, 8, 0, 0, 0, 4]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(write_png(&path, &colors, 3, 4).is_err());
+        assert_eq!(image_width(Grid::new(3, 2, 1)).unwrap(), 3);
+        assert!(image_width(Grid::new(3, 2, 2)).is_err());
+    }
+
+    #[test]
+    /// Test the hues and lightness of the HSL color wheel
+    fn test_hsl_color() {
+        assert_eq!(hsl_color(&[1.0, 0.0, 0.0]), [255, 0, 0]);
+        assert_eq!(hsl_color(&[0.0, 1.0, 0.0]), [128, 255, 0]);
+        assert_eq!(hsl_color(&[-1.0, 0.0, 0.0]), [0, 255, 255]);
+        assert_eq!(hsl_color(&[0.0, -1.0, 0.0]), [128, 0, 255]);
+        assert_eq!(hsl_color(&[0.0, 0.0, 1.0]), [255, 255, 255]);
+        assert_eq!(hsl_color(&[0.0, 0.0, -1.0]), [0, 0, 0]);
+        // Tilting out of plane lightens the hue toward white
+        let tilted = hsl_color(&[0.6, 0.0, 0.8]);
+        assert_eq!(tilted, [255, 204, 204]);
+
+        let magnetizations = vec![ndarray::array![0.0, 1.0, 0.0]];
+        let colors = render_component(&magnetizations, Component::Z, ColorMap::Hsl);
+        assert_eq!(colors, vec![[128, 255, 0]]);
+    }
+
+    #[test]
+    /// Test recording the relaxation of a chain as an animated GIF
+    fn test_animation_recorder() {
+        let mut system = MicromagneticSystem::new(4);
+        let mut recorder = AnimationRecorder::new(100, Component::X, ColorMap::Heatmap);
+        system.minimize_energy_with(|step, system| recorder.record(step, system));
+        recorder.finish(&system);
+        assert!(recorder.frames.len() >= 2);
+        assert!(recorder.frames.iter().all(|frame| frame.len() == 4));
+
+        let path = std::env::temp_dir().join("energy_relaxation_animation.gif");
+        recorder.write_gif(&path, 4, 2, 10.0).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..6], b"GIF89a");
+        // The logical screen width and height are little endian
+        assert_eq!(&bytes[6..10], &[8, 0, 2, 0]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(recorder.write_gif(&path, 4, 2, 0.0).is_err());
+        assert!(write_gif(&path, &[], 4, 2, 10.0).is_err());
+    }
+}
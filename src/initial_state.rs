@@ -0,0 +1,126 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::ovf::read_ovf;
+use ndarray::Array1;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+///# Initial State
+/// Magnetization a configured system starts from.
+///
+/// ```toml
+/// [initial_state]
+/// type = "uniform"
+/// direction = [1.0, 0.0, 1.0]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum InitialState {
+    // An independent random direction in every cell
+    #[default]
+    Random,
+    // The same direction in every cell, normalized
+    Uniform {
+        direction: [f64; 3],
+    },
+    // The directions of an OVF file with one node per cell, e.g. a saved state.ovf
+    File {
+        path: PathBuf,
+    },
+}
+
+impl InitialState {
+    ///# Apply Initial State
+    /// Set the magnetization of the magnetic cells, the materials are kept.
+    pub fn apply(&self, system: &mut MicromagneticSystem) -> Result<(), Box<dyn Error>> {
+        match self {
+            InitialState::Random => {}
+            InitialState::Uniform { direction } => {
+                if direction.iter().all(|&c| c == 0.0) {
+                    return Err("The initial direction must not be zero".into());
+                }
+                for cell in 0..system.size() {
+                    system.set_magnetization(cell, Array1::from_vec(direction.to_vec()));
+                }
+            }
+            InitialState::File { path } => {
+                let data = read_ovf(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                if data.vectors.len() != system.size() {
+                    return Err(format!(
+                        "{} has {} nodes for {} cells",
+                        path.display(),
+                        data.vectors.len(),
+                        system.size()
+                    )
+                    .into());
+                }
+                for (cell, vector) in data.vectors.iter().enumerate() {
+                    system.set_magnetization(cell, Array1::from_vec(vector.to_vec()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///# Resolve Path
+    /// Make a relative state file relative to the given directory.
+    pub fn resolve_path(&mut self, directory: &Path) {
+        if let InitialState::File { path } = self {
+            if path.is_relative() {
+                *path = directory.join(&*path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    /// Test the uniform state and the state read back from a saved file
+    fn test_initial_state() {
+        let mut system = MicromagneticSystem::new(3);
+        system.set_saturation_magnetization(1, 0.0);
+        let uniform = InitialState::Uniform {
+            direction: [0.0, 3.0, 4.0],
+        };
+        uniform.apply(&mut system).unwrap();
+        assert_eq!(system.get_magnetizations()[0], array![0.0, 0.6, 0.8]);
+        assert_eq!(system.get_magnetizations()[1], array![0.0, 0.0, 0.0]);
+        let zero = InitialState::Uniform {
+            direction: [0.0; 3],
+        };
+        assert!(zero.apply(&mut system).is_err());
+
+        let path = std::env::temp_dir().join("energy_relaxation_initial_state_test.ovf");
+        let saved = MicromagneticSystem::new(3);
+        saved.save_ovf(&path).unwrap();
+        let file = InitialState::File { path: path.clone() };
+        let mut restored = MicromagneticSystem::new(3);
+        file.apply(&mut restored).unwrap();
+        let mut short = MicromagneticSystem::new(2);
+        assert!(file.apply(&mut short).is_err());
+        std::fs::remove_file(&path).unwrap();
+        for (a, b) in restored
+            .get_magnetizations()
+            .iter()
+            .zip(saved.get_magnetizations())
+        {
+            assert!((a - &b).mapv(f64::abs).sum() < 1e-12);
+        }
+
+        let mut relative = InitialState::File {
+            path: PathBuf::from("state.ovf"),
+        };
+        relative.resolve_path(Path::new("runs"));
+        assert_eq!(
+            relative,
+            InitialState::File {
+                path: PathBuf::from("runs/state.ovf")
+            }
+        );
+    }
+}
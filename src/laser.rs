@@ -0,0 +1,242 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::two_temperature::{reduced_moment, UltrafastRun};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::f64::consts::{LN_2, PI};
+
+///# Laser Pulse
+/// Gaussian heat pulse in space and time. The absorbed `fluence` in J/m^2
+/// at the center of the spot is deposited over the `penetration_depth`,
+/// spot size and duration are full widths at half maximum, so the power
+/// density of a cell at x is
+/// P = F / d 2 sqrt(ln 2 / pi) / tau exp(-4 ln 2 ((x - x0)^2 / w^2 + (t - t0)^2 / tau^2)).
+///
+/// ```toml
+/// fluence = 10.0
+/// spot_size = 1.0e-6
+/// duration = 1.0e-13
+/// delay = 3.0e-13
+/// center = 25.0e-9
+/// penetration_depth = 15.0e-9
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaserPulse {
+    // Absorbed fluence at the center of the spot in J/m^2, 10 J/m^2 = 1 mJ/cm^2
+    pub fluence: f64,
+    // Full width at half maximum of the spot in m
+    pub spot_size: f64,
+    // Full width at half maximum of the pulse in s
+    pub duration: f64,
+    // Time of the pulse maximum in s
+    pub delay: f64,
+    // Position of the spot center along the chain in m
+    pub center: f64,
+    // Depth over which the fluence is absorbed in m
+    pub penetration_depth: f64,
+}
+
+impl LaserPulse {
+    ///# Validate
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let positive = [self.spot_size, self.duration, self.penetration_depth]
+            .iter()
+            .all(|&value| value > 0.0);
+        if !positive || self.fluence.is_nan() || self.fluence < 0.0 {
+            return Err(
+                "The laser fluence must not be negative, its spot size, duration and penetration depth must be positive"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+
+    ///# Power Density
+    /// Absorbed power density in W/m^3 at the x position in m and the time.
+    pub fn power_density(&self, position: f64, time: f64) -> f64 {
+        let distance = position - self.center;
+        let delay = time - self.delay;
+        let exponent = -4.0
+            * LN_2
+            * (distance * distance / (self.spot_size * self.spot_size)
+                + delay * delay / (self.duration * self.duration));
+        self.fluence / self.penetration_depth * 2.0 * (LN_2 / PI).sqrt() / self.duration
+            * exponent.exp()
+    }
+}
+
+///# Switching Report
+/// Outcome of a laser pulse applied to a relaxed state.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SwitchingReport {
+    // Whether the mean magnetization ends up reversed
+    pub toggled: bool,
+    // Fraction of the magnetic cells whose magnetization reversed
+    pub switched_fraction: f64,
+    // Mean magnetization of the relaxed state before the pulse
+    pub initial_magnetization: [f64; 3],
+    // Mean magnetization relaxed at the ambient temperature after the run
+    pub final_magnetization: [f64; 3],
+    // Highest electron temperature of any cell in K
+    pub peak_electron_temperature: f64,
+    // Lowest mean Ms(T) m / Ms(0) along the initial magnetization
+    pub minimum_moment: f64,
+}
+
+///# All-Optical Switching
+/// Relax the system in the field at the start, heat it with the source of
+/// the run for `duration` in s, relax it again at the ambient temperature
+/// in the field at the end and report whether the pulse reversed the
+/// magnetization. The materials of the system are the constants at zero
+/// temperature, the system itself is not changed.
+pub fn optical_switching(
+    system: &MicromagneticSystem,
+    run: &UltrafastRun,
+    duration: f64,
+) -> Result<SwitchingReport, Box<dyn Error>> {
+    let base = system.get_materials();
+    let mut heated = system.clone();
+    run.scaling
+        .apply(&mut heated, 0..base.len(), run.ambient_temperature)?;
+    heated.set_applied_field(run.dynamics.applied_field.at(0.0));
+    heated.minimize_energy();
+    let initial = heated.get_magnetizations();
+    let initial_magnetization = heated.average_magnetization();
+    let initial_magnetization = [0, 1, 2].map(|k| initial_magnetization[k]);
+    let norm = dot(&initial_magnetization, &initial_magnetization).sqrt();
+    if norm == 0.0 {
+        return Err("The relaxed state has no net magnetization to switch".into());
+    }
+    let direction = initial_magnetization.map(|c| c / norm);
+
+    // The run scales the materials from the zero temperature ones itself
+    for (cell, material) in base.iter().enumerate() {
+        heated.set_material(cell, *material);
+    }
+    let mut peak_electron_temperature = run.ambient_temperature;
+    let mut minimum_moment = f64::INFINITY;
+    run.run(&mut heated, duration, |_, system, state| {
+        let hottest = state.electron.iter().cloned().fold(f64::MIN, f64::max);
+        peak_electron_temperature = peak_electron_temperature.max(hottest);
+        let moment = reduced_moment(system, &base);
+        minimum_moment = minimum_moment.min(dot(&moment, &direction));
+    })?;
+
+    for (cell, material) in base.iter().enumerate() {
+        heated.set_material(cell, *material);
+    }
+    run.scaling
+        .apply(&mut heated, 0..base.len(), run.ambient_temperature)?;
+    heated.set_applied_field(run.dynamics.applied_field.at(duration));
+    heated.minimize_energy();
+    let final_magnetization = heated.average_magnetization();
+    let final_magnetization = [0, 1, 2].map(|k| final_magnetization[k]);
+
+    let magnetic: Vec<usize> = (0..base.len()).filter(|&i| !base[i].is_vacuum()).collect();
+    let switched = magnetic
+        .iter()
+        .filter(|&&i| initial[i].dot(&heated.get_magnetizations()[i]) < 0.0)
+        .count();
+    Ok(SwitchingReport {
+        toggled: dot(&final_magnetization, &direction) < 0.0,
+        switched_fraction: switched as f64 / magnetic.len().max(1) as f64,
+        initial_magnetization,
+        final_magnetization,
+        peak_electron_temperature,
+        minimum_moment,
+    })
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::{DynamicsRun, TimeDependentField};
+    use crate::temperature::TemperatureScaling;
+    use crate::two_temperature::{HeatSource, TwoTemperatureModel};
+    use crate::SPATIAL_DISCRETION_STEP;
+    use ndarray::array;
+
+    #[test]
+    /// Test that the pulse deposits the fluence at the spot center
+    fn test_laser_pulse() {
+        let pulse = LaserPulse {
+            fluence: 10.0,
+            spot_size: 20e-9,
+            duration: 1e-13,
+            delay: 5e-13,
+            center: 10.0 * SPATIAL_DISCRETION_STEP,
+            penetration_depth: 15e-9,
+        };
+        let time_step = 1e-15;
+        let deposited: f64 = (0..1000)
+            .map(|step| pulse.power_density(pulse.center, step as f64 * time_step) * time_step)
+            .sum();
+        assert!((deposited * pulse.penetration_depth / pulse.fluence - 1.0).abs() < 1e-6);
+        // Half the power at half the spot size from the center
+        let half = pulse.power_density(pulse.center + 10e-9, pulse.delay)
+            / pulse.power_density(pulse.center, pulse.delay);
+        assert!((half - 0.5).abs() < 1e-12);
+        assert!(LaserPulse {
+            duration: 0.0,
+            ..pulse
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    /// Test that only a heating pulse reverses the state in a reversed field
+    fn test_optical_switching() {
+        let size = 5;
+        let mut system =
+            MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]; size]);
+        for cell in 0..size {
+            let mut material = system.get_materials()[cell];
+            material.anisotropy_constant = 5e6;
+            material.damping = 1.0;
+            system.set_material(cell, material);
+        }
+        // Well below the cold switching field of about 3 T, far above the hot one
+        let field = [-2.0, 0.2, 0.0];
+        let pulse = LaserPulse {
+            fluence: 20.0,
+            spot_size: 1e-6,
+            duration: 1e-13,
+            delay: 3e-13,
+            center: 2.0 * SPATIAL_DISCRETION_STEP,
+            penetration_depth: 15e-9,
+        };
+        let mut run = UltrafastRun {
+            dynamics: DynamicsRun::new(TimeDependentField::Constant(field)),
+            model: TwoTemperatureModel {
+                electron_heat_capacity: 1.065e3,
+                lattice_heat_capacity: 2.2e6,
+                coupling: 3.6e17,
+                substrate_cooling_time: Some(2e-11),
+            },
+            scaling: TemperatureScaling::new(1000.0),
+            source: HeatSource::Laser(pulse),
+            ambient_temperature: 300.0,
+        };
+        let report = optical_switching(&system, &run, 4e-11).unwrap();
+        assert!(report.toggled);
+        assert_eq!(report.switched_fraction, 1.0);
+        assert!(report.initial_magnetization[0] > 0.9);
+        assert!(report.final_magnetization[0] < -0.9);
+        assert!(report.peak_electron_temperature > 1000.0);
+        assert!(report.minimum_moment < 0.2);
+
+        run.source = HeatSource::Laser(LaserPulse {
+            fluence: 0.0,
+            ..pulse
+        });
+        let report = optical_switching(&system, &run, 4e-11).unwrap();
+        assert!(!report.toggled);
+        assert_eq!(report.switched_fraction, 0.0);
+        assert_eq!(report.peak_electron_temperature, 300.0);
+    }
+}
@@ -0,0 +1,113 @@
+#![allow(clippy::needless_range_loop)]
+use std::f64;
+pub mod absorbing;
+pub mod anisotropy_profile;
+pub mod astroid;
+pub mod bench;
+pub mod comparison;
+pub mod config;
+pub mod console;
+pub mod convergence;
+pub mod curvilinear;
+pub mod damping_profile;
+pub mod decimation;
+pub mod demag;
+pub mod diagnostics;
+pub mod dipolar;
+pub mod dmi;
+pub mod domains;
+pub mod dynamics;
+pub mod eigen;
+pub mod energy_landscape;
+pub mod ensemble;
+pub mod events;
+pub mod exchange_spring;
+pub mod export_to_excel;
+pub mod fitting;
+pub mod fmr;
+pub mod grid;
+pub mod hooks;
+pub mod hysteresis;
+pub mod image_export;
+pub mod initial_state;
+pub mod laser;
+pub mod macrospin;
+pub mod magnetic_moments;
+#[cfg(feature = "mmap")]
+pub mod mapped_storage;
+pub mod material;
+pub mod mesh_convergence;
+pub mod mfm;
+pub mod neighbors;
+pub mod normal_modes;
+pub(crate) mod oscillation;
+pub mod output;
+pub mod ovf;
+pub mod parallel;
+pub mod parameters;
+pub mod phase_diagram;
+pub mod protocol;
+pub mod quaternion;
+pub mod readout;
+pub mod region;
+pub mod roughness;
+#[cfg(feature = "async")]
+pub mod runner;
+pub mod saf;
+pub mod sampling;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod sensitivity;
+pub mod skyrmion;
+pub mod snapshots;
+pub mod spherical;
+pub mod spin_accumulation;
+pub mod spin_transfer;
+pub mod spin_waves;
+pub mod stability;
+pub mod stop_conditions;
+pub mod stray_field;
+pub mod summary;
+pub mod summation;
+pub mod table;
+pub mod telegraph;
+pub mod temperature;
+pub mod texture;
+pub mod thermal_field;
+pub mod time_series;
+pub mod two_temperature;
+pub mod validation;
+pub mod vortex;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+// Constants for the simulation
+
+// Exchange interaction constants
+pub const MAGNETIC_EXCHANGE_CONSTANT: f64 = 2.1e-11;
+pub const SATURATION_MAGNETIZATION: f64 = 1.71e6;
+pub const PERMEABILITY_OF_FREE_SPACE: f64 = 4.0 * f64::consts::PI * 1.0e-7;
+// Default edge length in m of the cubic cells, and their volume, the cell
+// size of a system is set in its SimulationParameters
+pub const SPATIAL_DISCRETION_STEP: f64 = 1.0e-9;
+pub const CELL_VOLUME: f64 =
+    SPATIAL_DISCRETION_STEP * SPATIAL_DISCRETION_STEP * SPATIAL_DISCRETION_STEP;
+
+// Anisotropy interaction constant
+pub const UNIAXIAL_ANISOTROPY_CONSTANT: f64 = 4.8e4;
+pub const EASY_AXIS: [f64; 3] = [1.0, 0.0, 0.0];
+
+// Zeeman interaction constant
+pub const EXTERNAL_FIELD: [f64; 3] = [0.0, 0.0, 0.5];
+
+// Energy calculation constants
+pub const TIME_STEP: f64 = 2e-13;
+pub const DAMPING_CONSTANT: f64 = 0.2;
+// Gyromagnetic ratio in m/(A·s), the effective field is in A/m
+pub const GILBERT_GYROMAGNETIC_RATIO: f64 = 2.211e5;
+// Time step of the precessional dynamics in s, resolves the fastest exchange modes
+pub const DYNAMICS_TIME_STEP: f64 = 1e-14;
+
+// Iteration parameters
+pub const MAX_ITERATIONS_NUMBER: usize = 10000;
+pub const TOLERANCE: f64 = 1e-6;
@@ -0,0 +1,232 @@
+use crate::eigen::symmetric_eigen;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::normal_modes::{tangent_frame, tangent_hessian};
+use ndarray::{Array1, Array2};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+///# Macrospin
+/// Single moment with uniaxial anisotropy,
+/// E = -K V (m . e)^2 - Ms V m . B,
+/// that responds to small fields like the relaxed full system. Exchange,
+/// dipolar and any other interaction enter through the effective
+/// constants, so the model holds near the state it was fitted around.
+/// Serialized to JSON it parametrizes a device model of the element.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Macrospin {
+    // Total moment Ms V in A m^2, below the saturated moment for a nonuniform state
+    pub moment: f64,
+    // Volume of the magnetic cells in m^3
+    pub volume: f64,
+    // Effective anisotropy constant in J/m^3, negative for a hard axis
+    pub anisotropy_constant: f64,
+    // Unit vector of the effective axis, on the side of the moment
+    pub easy_axis: [f64; 3],
+    // Direction of the moment in the relaxed state
+    pub magnetization: [f64; 3],
+}
+
+impl Macrospin {
+    ///# Effective Saturation Magnetization
+    /// Moment per volume in A/m.
+    pub fn saturation_magnetization(&self) -> f64 {
+        self.moment / self.volume
+    }
+
+    ///# Effective Anisotropy Field
+    /// 2 K V / (Ms V) in T.
+    pub fn anisotropy_field(&self) -> f64 {
+        2.0 * self.anisotropy_constant * self.volume / self.moment
+    }
+
+    ///# Energy
+    /// Energy in J of the moment along `m` in the applied field in T.
+    pub fn energy(&self, m: [f64; 3], field: [f64; 3]) -> f64 {
+        let along_axis = dot(&m, &self.easy_axis);
+        -self.anisotropy_constant * self.volume * along_axis * along_axis
+            - self.moment * dot(&m, &field)
+    }
+}
+
+///# Fit Macrospin
+/// Relaxes the system and fits the macrospin to the response of the total
+/// moment to small uniform fields perpendicular to it. The response is
+/// the linear limit, solved with the tangent Hessian of the relaxed state
+/// instead of relaxing again in probe fields, so it does not depend on
+/// the convergence of the minimizer. The inverse of the transverse
+/// susceptibility is the stiffness of the moment in T, which determines
+/// the anisotropy field of a uniaxial macrospin at equilibrium and the
+/// angle of its axis from the moment, the transverse part of the applied
+/// field the side to which the axis is tilted.
+pub fn fit_macrospin(system: &MicromagneticSystem) -> Result<Macrospin, Box<dyn Error>> {
+    let mut relaxed = system.clone();
+    relaxed.minimize_energy();
+    let total = total_moment(&relaxed);
+    let moment = dot(&total, &total).sqrt();
+    if moment == 0.0 {
+        return Err("The relaxed state has no net moment".into());
+    }
+    let magnetization = total.map(|c| c / moment);
+    let frame = tangent_frame(&Array1::from_vec(magnetization.to_vec()));
+    let field = relaxed.get_applied_field();
+
+    // A field b along frame[a] exerts the generalized force Ms V b (frame[a] . t)
+    // on the rotation of a cell along its tangent t, the rotations follow from
+    // the inverse Hessian
+    let hessian = tangent_hessian(&relaxed);
+    let (values, vectors) = symmetric_eigen(&hessian.matrix);
+    if values.first().is_some_and(|&v| v <= 0.0) {
+        return Err("The relaxed state is not a stable minimum".into());
+    }
+    let materials = relaxed.get_materials();
+    let cell_volume = relaxed.cell_volume();
+    let cell_moment = |k: usize| materials[hessian.cells[k]].saturation_magnetization * cell_volume;
+    // susceptibility[[b, a]] = d(m . frame[b]) / d(B . frame[a])
+    let mut susceptibility = Array2::zeros((2, 2));
+    for a in 0..2 {
+        let force = Array1::from_shape_fn(values.len(), |r| {
+            cell_moment(r / 2) * dot(&frame[a], &hessian.frames[r / 2][r % 2])
+        });
+        let modal = vectors.t().dot(&force) / &Array1::from_vec(values.clone());
+        let rotation = vectors.dot(&modal);
+        for b in 0..2 {
+            susceptibility[[b, a]] = (0..values.len())
+                .map(|r| {
+                    cell_moment(r / 2) * rotation[r] * dot(&frame[b], &hessian.frames[r / 2][r % 2])
+                })
+                .sum::<f64>()
+                / moment;
+        }
+    }
+    let determinant = susceptibility[[0, 0]] * susceptibility[[1, 1]]
+        - susceptibility[[0, 1]] * susceptibility[[1, 0]];
+    if determinant <= 0.0 {
+        return Err("The total moment is not stable against transverse fields".into());
+    }
+    let off_diagonal = -0.5 * (susceptibility[[0, 1]] + susceptibility[[1, 0]]) / determinant;
+    let stiffness = Array2::from_shape_vec(
+        (2, 2),
+        vec![
+            susceptibility[[1, 1]] / determinant,
+            off_diagonal,
+            off_diagonal,
+            susceptibility[[0, 0]] / determinant,
+        ],
+    )
+    .expect("2 x 2 matrix");
+    let (values, vectors) = symmetric_eigen(&stiffness);
+
+    // With kappa = 2 K / Ms, the axis e = cos(phi) m + sin(phi) t and
+    // c = kappa cos^2(phi) + m . B, the stiffness is c perpendicular to t
+    // and c - kappa sin^2(phi) along it. Either eigenvector may be t, for
+    // an easy axis the soft and for a hard axis the stiff one. In
+    // equilibrium kappa cos(phi) sin(phi) t = -B_perpendicular, which
+    // selects the eigenvector along the transverse field.
+    let parallel = dot(&magnetization, &field);
+    let transverse = frame.map(|t| dot(&t, &field));
+    let transverse_norm = transverse[0].hypot(transverse[1]);
+    let candidates = [(0, 1), (1, 0)].map(|(axis, other)| {
+        let kappa = 2.0 * values[other] - values[axis] - parallel;
+        let cos_squared = if kappa == 0.0 {
+            1.0
+        } else {
+            (values[other] - parallel) / kappa
+        };
+        let direction = [vectors[[0, axis]], vectors[[1, axis]]];
+        let alignment = if transverse_norm > 0.0 {
+            (direction[0] * transverse[0] + direction[1] * transverse[1]).abs() / transverse_norm
+        } else {
+            0.0
+        };
+        (kappa, cos_squared, direction, alignment)
+    });
+    let (kappa, cos_squared, direction, _) = candidates
+        .into_iter()
+        .filter(|&(_, cos_squared, _, _)| (-1e-6..=1.0 + 1e-6).contains(&cos_squared))
+        .max_by(|a, b| a.3.total_cmp(&b.3))
+        .ok_or("The response does not fit a uniaxial macrospin")?;
+
+    let (cos, sin) = (
+        cos_squared.clamp(0.0, 1.0).sqrt(),
+        (1.0 - cos_squared).clamp(0.0, 1.0).sqrt(),
+    );
+    let tilt = if kappa * (direction[0] * transverse[0] + direction[1] * transverse[1]) > 0.0 {
+        -1.0
+    } else {
+        1.0
+    };
+    let easy_axis = [0, 1, 2].map(|k| {
+        cos * magnetization[k]
+            + tilt * sin * (direction[0] * frame[0][k] + direction[1] * frame[1][k])
+    });
+    let volume = hessian.cells.len() as f64 * cell_volume;
+    Ok(Macrospin {
+        moment,
+        volume,
+        anisotropy_constant: 0.5 * kappa * moment / volume,
+        easy_axis,
+        magnetization,
+    })
+}
+
+// Sum of Ms V m over the cells in A m^2
+fn total_moment(system: &MicromagneticSystem) -> [f64; 3] {
+    let materials = system.get_materials();
+    let volume = system.cell_volume();
+    let mut total = [0.0; 3];
+    for (m, material) in system.get_magnetizations().iter().zip(&materials) {
+        for k in 0..3 {
+            total[k] += material.saturation_magnetization * volume * m[k];
+        }
+    }
+    total
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magnetic_moments::Minimizer;
+    use crate::{CELL_VOLUME, SATURATION_MAGNETIZATION, UNIAXIAL_ANISOTROPY_CONSTANT};
+    use ndarray::array;
+
+    #[test]
+    /// Test that a uniform chain reduces to its own material constants
+    fn test_fit_macrospin() {
+        let size = 3;
+        let mut system = MicromagneticSystem::new(size);
+        for i in 0..size {
+            system.set_magnetization(i, array![1.0, 0.0, 0.0]);
+        }
+        system.set_minimizer(Minimizer::SphericalConjugateGradient);
+        // A transverse field tilts the moment away from the axis
+        system.set_applied_field([0.01, 0.02, 0.0]);
+        let macrospin = fit_macrospin(&system).unwrap();
+
+        let volume = size as f64 * CELL_VOLUME;
+        assert!((macrospin.volume - volume).abs() < 1e-12 * volume);
+        assert!(
+            (macrospin.saturation_magnetization() / SATURATION_MAGNETIZATION - 1.0).abs() < 1e-9
+        );
+        assert!((macrospin.anisotropy_constant / UNIAXIAL_ANISOTROPY_CONSTANT - 1.0).abs() < 1e-3);
+        assert!((macrospin.easy_axis[0] - 1.0).abs() < 1e-6);
+        assert!(macrospin.magnetization[1] > 0.1);
+
+        // The relaxed direction is a minimum of the fitted model up to the
+        // convergence of the relaxation
+        let energy = |m: [f64; 3]| macrospin.energy(m, system.get_applied_field());
+        let m = macrospin.magnetization;
+        let tilted = |angle: f64| {
+            [
+                m[0] * angle.cos() - m[1] * angle.sin(),
+                m[0] * angle.sin() + m[1] * angle.cos(),
+                0.0,
+            ]
+        };
+        assert!(energy(tilted(1e-2)) > energy(m));
+        assert!(energy(tilted(-1e-2)) > energy(m));
+    }
+}
@@ -1,33 +1,274 @@
-use std::time;
-
-use crate::DAMPING_CONSTANT;
+use crate::convergence::{ConvergenceMonitor, ConvergencePolicy, RetryPolicy};
+use crate::demag::{DemagnetizationKernel, DipolarMethod, DEFAULT_PERIODIC_IMAGES};
+use crate::diagnostics::ConvergenceDiagnostics;
+use crate::dipolar::periodic_dipolar_field_at;
+use crate::dmi::bond_dmi_vector;
+use crate::grid::{BoundaryCondition, Grid};
+use crate::material::Material;
+use crate::neighbors::NeighborList;
+use crate::oscillation::OscillationDetector;
+use crate::ovf::{read_ovf, write_ovf, OvfData};
+use crate::parallel::{map_cells, sum_cells, ReductionOrder};
+use crate::parameters::{AdaptiveTimeStep, SimulationParameters};
+use crate::region::Region;
+use crate::spherical::minimize_spherical_until;
+use crate::stop_conditions::StopCondition;
+use crate::summation::{compensated_sum, CompensatedSum};
 use crate::EASY_AXIS;
-use crate::EXTERNAL_FIELD;
 use crate::GILBERT_GYROMAGNETIC_RATIO;
-use crate::MAGNETIC_EXCHANGE_CONSTANT;
-use crate::MAX_ITERATIONS_NUMBER;
 use crate::PERMEABILITY_OF_FREE_SPACE;
-use crate::SATURATION_MAGNETIZATION;
-use crate::SPATIAL_DISCRETION_STEP;
-use crate::TIME_STEP;
 use crate::TOLERANCE;
-use crate::UNIAXIAL_ANISOTROPY_CONSTANT;
 use ndarray::{array, Array1};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+///# Update Scheme
+/// Order in which the cells are updated during a relaxation step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpdateScheme {
+    // All cells are updated from the field of the previous state
+    #[default]
+    Jacobi,
+    // Even cells are updated first, then odd cells from the already updated
+    // even cells. The fields of a color are all computed before any of its
+    // cells is written, so each half sweep runs in parallel without races.
+    // Couplings within a color read the state from before the half sweep.
+    RedBlack,
+}
+
+///# Minimizer
+/// Algorithm used by the energy minimization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Minimizer {
+    // Damping-only relaxation steps of the Cartesian magnetization
+    #[default]
+    Relaxation,
+    // Conjugate gradient on the (theta, phi) angles of every cell
+    SphericalConjugateGradient,
+}
+
+///# Oscillation Policy
+/// Reaction of the relaxation minimizer to an oscillating relaxation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OscillationPolicy {
+    // Keep going with the same step
+    Ignore,
+    // Halve the relaxation step, down to MIN_RELAXATION_STEP_SCALE
+    #[default]
+    ReduceStepSize,
+    // Continue with the spherical conjugate gradient
+    SwitchMinimizer,
+}
+
+// Smallest fraction of the time step the relaxation step is reduced to
+const MIN_RELAXATION_STEP_SCALE: f64 = 1.0 / 64.0;
+
+///# Damping Schedule
+/// Damping used by the energy minimizer. It only sets the speed of the
+/// relaxation, the dynamics always use the material damping.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DampingSchedule {
+    // The damping constant of each cell's material
+    #[default]
+    Material,
+    // Far from equilibrium the given damping is used, blending back to the
+    // material damping as the maximum change approaches the tolerance.
+    // The damping-only step is fastest at a damping of 1.
+    Adaptive {
+        maximum: f64,
+    },
+}
+
+// Maximum change above which the adaptive schedule uses its full damping
+const ADAPTIVE_DAMPING_FAR_CHANGE: f64 = 1e-3;
+
+///# Minimization Outcome
+/// How an energy minimization ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinimizationOutcome {
+    // The maximum change dropped below the tolerance
+    Converged { iterations: usize },
+    // The iteration limit was reached first
+    NotConverged { iterations: usize },
+    // The observer asked to stop
+    Stopped { iterations: usize },
+}
+
+impl MinimizationOutcome {
+    // The same outcome after `steps` earlier steps
+    fn after(self, steps: usize) -> Self {
+        match self {
+            Self::Converged { iterations } => Self::Converged {
+                iterations: steps + iterations,
+            },
+            Self::NotConverged { iterations } => Self::NotConverged {
+                iterations: steps + iterations,
+            },
+            Self::Stopped { iterations } => Self::Stopped {
+                iterations: steps + iterations,
+            },
+        }
+    }
+}
+
+///# Energies
+/// Contributions to the magnetic energy in J
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct Energies {
+    pub exchange: f64,
+    pub anisotropy: f64,
+    pub zeeman: f64,
+    pub dipolar: f64,
+    pub dmi: f64,
+}
+
+impl Energies {
+    ///# Total Energy
+    pub fn total(&self) -> f64 {
+        compensated_sum([
+            self.exchange,
+            self.anisotropy,
+            self.zeeman,
+            self.dipolar,
+            self.dmi,
+        ])
+    }
+}
+
+///# Energy Gradient
+/// Derivatives of the total energy of a fixed configuration with respect
+/// to the exchange and anisotropy constants of every cell and the uniform
+/// applied field. At an equilibrium they are also the derivatives of the
+/// relaxed energy, since the configuration responds only to second order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EnergyGradient {
+    // dE/dA_i of the nearest neighbor exchange in J / (J/m), zero in vacuum
+    pub exchange_constant: Vec<f64>,
+    // dE/dK_i in J / (J/m^3)
+    pub anisotropy_constant: Vec<f64>,
+    // dE/dB in J/T, the derivative with respect to H is mu0 times this
+    pub applied_field: [f64; 3],
+}
+
+impl EnergyGradient {
+    ///# Uniform Exchange Derivative
+    /// dE/dA for the exchange constants of all cells changed together.
+    pub fn total_exchange_constant(&self) -> f64 {
+        compensated_sum(self.exchange_constant.iter().copied())
+    }
+
+    ///# Uniform Anisotropy Derivative
+    /// dE/dK for the anisotropy constants of all cells changed together.
+    pub fn total_anisotropy_constant(&self) -> f64 {
+        compensated_sum(self.anisotropy_constant.iter().copied())
+    }
+}
+
+///# Interlayer Coupling
+/// RKKY coupling between two cells across a spacer with the energy
+/// -J m_first . m_second - B (m_first . m_second)^2 per interface area.
+/// Negative J couples antiparallel, negative B favors 90 degree alignment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterlayerCoupling {
+    pub first: usize,
+    pub second: usize,
+    // Coupling constant J in J/m^2
+    pub coupling: f64,
+    // Biquadratic coupling constant B in J/m^2
+    pub biquadratic: f64,
+}
 
 ///# Micromagnetic System
 /// Struct to represent the magnetic system
+#[derive(Debug, Clone)]
 pub struct MicromagneticSystem {
     // Magnetization vectors
     magnetizations: Vec<Array1<f64>>,
     // Number particles
     size: usize,
+    // Material constants of each cell, zero Ms marks a vacuum cell
+    materials: Vec<Material>,
+    // Region id of each cell, 0 outside every region
+    regions: Vec<usize>,
+    // Ordering of the cell updates in the relaxation step
+    update_scheme: UpdateScheme,
+    // Algorithm of the energy minimization
+    minimizer: Minimizer,
+    // Reaction to an oscillating relaxation
+    oscillation_policy: OscillationPolicy,
+    // Criteria under which a minimization counts as converged
+    convergence_policy: ConvergencePolicy,
+    // Restarts of a minimization that reached the iteration limit
+    retry_policy: Option<RetryPolicy>,
+    // Energies of every observed state of the last minimization, not
+    // recorded when unset
+    energy_history: Option<Vec<Energies>>,
+    // Order of the parallel energy sums
+    reduction_order: ReductionOrder,
+    // Pseudo time step of the relaxation in s
+    time_step: f64,
+    // Step size control of the relaxation, the fixed time step when unset
+    adaptive_time_step: Option<AdaptiveTimeStep>,
+    // Fraction of the time step used by the relaxation step
+    relaxation_step_scale: f64,
+    // Pseudo time in s the minimizations have advanced the state by
+    relaxation_time: f64,
+    // Steps after which a minimization gives up
+    max_iterations: usize,
+    // Damping of the minimizer
+    damping_schedule: DampingSchedule,
+    // Weight of the adaptive damping, 1 far from and 0 at equilibrium
+    adaptive_damping_weight: f64,
+    // Include the dipole-dipole field in the effective field
+    dipolar_interaction: bool,
+    // Direct sum or FFT convolution of the dipole-dipole field
+    dipolar_method: DipolarMethod,
+    // Copies of the grid on either side along the periodic axes in the
+    // dipolar field
+    periodic_images: usize,
+    // Tensor of the FFT method, shared between clones of the system
+    demagnetization_kernel: Option<Arc<DemagnetizationKernel>>,
+    // Diagonal demagnetizing tensor of the whole sample, acting on every cell
+    demagnetization_factors: [f64; 3],
+    // Applied field B = mu0 H in T
+    applied_field: [f64; 3],
+    // Spatial derivative dB/dx of the applied field in T/m, zero at the first cell
+    field_gradient: [f64; 3],
+    // Additional field of every cell in T, e.g. of an antenna, empty when unused
+    local_fields: Vec<[f64; 3]>,
+    // Position in m of the first cell in the laboratory, moved by `shift_frame`
+    frame_offset: f64,
+    // RKKY couplings across spacers
+    interlayer_couplings: Vec<InterlayerCoupling>,
+    // Exchange-coupled nearest neighbors of every cell, a chain by default
+    neighbor_list: NeighborList,
+    // Mesh of the cells, a chain along x by default
+    grid: Grid,
+    // Edge length in m of the cubic cells
+    cell_size: f64,
+    // Exchange stencil at the outer faces of the grid
+    boundary_condition: BoundaryCondition,
 }
 
 impl MicromagneticSystem {
     ///# New Micromagnetic System
     /// Initialize the micromagnetic system with random magnetizations
+    /// and the built-in constants.
     pub fn new(size: usize) -> Self {
+        Self::with_parameters(size, SimulationParameters::default())
+    }
+
+    ///# Micromagnetic System with Parameters
+    /// Random magnetizations with the material, field, time step and
+    /// convergence criteria of the parameters.
+    pub fn with_parameters(size: usize, parameters: SimulationParameters) -> Self {
         let mut magnetizations = vec![Array1::zeros(3); size];
         for i in 0..size {
             let mut rng = rand::rng();
@@ -41,14 +282,672 @@ impl MicromagneticSystem {
         Self {
             magnetizations,
             size,
+            materials: vec![parameters.material; size],
+            regions: vec![0; size],
+            update_scheme: UpdateScheme::default(),
+            minimizer: Minimizer::default(),
+            oscillation_policy: OscillationPolicy::default(),
+            convergence_policy: parameters.convergence,
+            retry_policy: None,
+            energy_history: None,
+            reduction_order: ReductionOrder::default(),
+            time_step: parameters.time_step,
+            adaptive_time_step: parameters.adaptive_time_step,
+            relaxation_step_scale: 1.0,
+            relaxation_time: 0.0,
+            max_iterations: parameters.max_iterations,
+            damping_schedule: DampingSchedule::default(),
+            adaptive_damping_weight: 1.0,
+            dipolar_interaction: false,
+            dipolar_method: DipolarMethod::default(),
+            periodic_images: DEFAULT_PERIODIC_IMAGES,
+            demagnetization_kernel: None,
+            demagnetization_factors: [0.0; 3],
+            applied_field: parameters.applied_field,
+            field_gradient: [0.0; 3],
+            local_fields: Vec::new(),
+            frame_offset: 0.0,
+            interlayer_couplings: Vec::new(),
+            neighbor_list: Grid::chain(size).neighbor_list_with(parameters.boundary_condition),
+            grid: Grid::chain(size),
+            cell_size: parameters.cell_size,
+            boundary_condition: parameters.boundary_condition,
+        }
+    }
+
+    ///# Micromagnetic System on a Grid
+    /// Random magnetizations on a 2D or 3D mesh, every cell coupled by
+    /// exchange to its face neighbors.
+    pub fn on_grid(grid: Grid, parameters: SimulationParameters) -> Self {
+        let mut system = Self::with_parameters(grid.size(), parameters);
+        system
+            .set_grid(grid)
+            .expect("the system holds every cell of the grid");
+        system
+    }
+
+    ///# Micromagnetic System from Magnetizations
+    /// Initialize the system from given magnetization vectors.
+    /// The vectors are normalized, zero vectors become vacuum cells.
+    pub fn from_magnetizations(magnetizations: Vec<Array1<f64>>) -> Self {
+        let size = magnetizations.len();
+        let mut system = Self::new(size);
+        for (i, m) in magnetizations.into_iter().enumerate() {
+            let norm = m.dot(&m).sqrt();
+            if norm == 0.0 {
+                system.set_saturation_magnetization(i, 0.0);
+            } else {
+                system.magnetizations[i] = m / norm;
+            }
         }
+        system
+    }
+
+    ///# Micromagnetic System from OVF
+    /// Load a state written by OOMMF or mumax3. The nodes are laid out as
+    /// a chain with x varying fastest. When the file stores the magnetization
+    /// in A/m, the length of each vector is taken as the saturation
+    /// magnetization of the cell. The x step size of the file becomes the
+    /// cell size.
+    pub fn from_ovf(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let data = read_ovf(path)?;
+        let magnetizations: Vec<Array1<f64>> = data
+            .vectors
+            .iter()
+            .map(|v| Array1::from_vec(v.to_vec()))
+            .collect();
+        let mut system = Self::from_magnetizations(magnetizations.clone());
+        system.set_grid(Grid::from(data.nodes))?;
+        if data.step_sizes[0] > 0.0 {
+            system.set_cell_size(data.step_sizes[0]);
+        }
+        if data.value_unit == "A/m" {
+            for (i, m) in magnetizations.iter().enumerate() {
+                system.set_saturation_magnetization(i, m.dot(m).sqrt());
+            }
+        }
+        Ok(system)
+    }
+
+    ///# Save State as OVF
+    /// Write the normalized magnetizations as an OVF 2.0 file that
+    /// `from_ovf` reads back, e.g. to continue an interrupted relaxation.
+    pub fn save_ovf(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let data = OvfData {
+            nodes: self.grid.into(),
+            step_sizes: [self.cell_size; 3],
+            value_unit: "1".to_string(),
+            vectors: self
+                .magnetizations
+                .iter()
+                .map(|m| [m[0], m[1], m[2]])
+                .collect(),
+        };
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_ovf(&mut writer, &data, "m", 0.0)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    ///# Set Update Scheme
+    pub fn set_update_scheme(&mut self, update_scheme: UpdateScheme) {
+        self.update_scheme = update_scheme;
+    }
+
+    ///# Set Minimizer
+    pub fn set_minimizer(&mut self, minimizer: Minimizer) {
+        self.minimizer = minimizer;
+    }
+
+    ///# Get Minimizer
+    pub fn get_minimizer(&self) -> Minimizer {
+        self.minimizer
+    }
+
+    ///# Set Oscillation Policy
+    pub fn set_oscillation_policy(&mut self, oscillation_policy: OscillationPolicy) {
+        self.oscillation_policy = oscillation_policy;
+    }
+
+    ///# Set Time Step
+    /// Pseudo time step of the relaxation in s.
+    pub fn set_time_step(&mut self, time_step: f64) {
+        self.time_step = time_step;
+    }
+
+    ///# Get Time Step
+    pub fn get_time_step(&self) -> f64 {
+        self.time_step
+    }
+
+    ///# Set Adaptive Time Step
+    /// Let the relaxation choose its time step from a local error estimate,
+    /// starting from the current time step. `None` keeps the step fixed.
+    pub fn set_adaptive_time_step(&mut self, adaptive_time_step: Option<AdaptiveTimeStep>) {
+        self.adaptive_time_step = adaptive_time_step;
+    }
+
+    ///# Get Adaptive Time Step
+    pub fn get_adaptive_time_step(&self) -> Option<AdaptiveTimeStep> {
+        self.adaptive_time_step
+    }
+
+    ///# Relaxation Time
+    /// Pseudo time in s of the relaxation steps taken so far, the sum of
+    /// the accepted steps of an adaptive time step. A conjugate gradient
+    /// iteration counts as one time step.
+    pub fn relaxation_time(&self) -> f64 {
+        self.relaxation_time
+    }
+
+    // Count pseudo time of a minimizer without relaxation steps
+    pub(crate) fn advance_relaxation_time(&mut self, time: f64) {
+        self.relaxation_time += time;
+    }
+
+    ///# Set Maximum Iterations
+    pub fn set_max_iterations(&mut self, max_iterations: usize) {
+        self.max_iterations = max_iterations;
+    }
+
+    ///# Get Maximum Iterations
+    pub fn get_max_iterations(&self) -> usize {
+        self.max_iterations
+    }
+
+    ///# Set Convergence Policy
+    pub fn set_convergence_policy(&mut self, convergence_policy: ConvergencePolicy) {
+        self.convergence_policy = convergence_policy;
+    }
+
+    ///# Get Convergence Policy
+    pub fn get_convergence_policy(&self) -> ConvergencePolicy {
+        self.convergence_policy
+    }
+
+    ///# Set Retry Policy
+    /// Retry a minimization that reached the iteration limit, `None` gives
+    /// up at the first limit.
+    pub fn set_retry_policy(&mut self, retry_policy: Option<RetryPolicy>) {
+        self.retry_policy = retry_policy;
+    }
+
+    ///# Get Retry Policy
+    pub fn get_retry_policy(&self) -> Option<RetryPolicy> {
+        self.retry_policy
+    }
+
+    ///# Record Energy History
+    /// Record the energies of the state before the first and after every
+    /// step of each minimization. The energies are evaluated once more per
+    /// step, so the history is off by default.
+    pub fn record_energy_history(&mut self, record: bool) {
+        self.energy_history = record.then(Vec::new);
+    }
+
+    ///# Energy History
+    /// Energies of the last minimization with the history recorded, empty
+    /// when it is off.
+    pub fn energy_history(&self) -> &[Energies] {
+        self.energy_history.as_deref().unwrap_or_default()
+    }
+
+    ///# Energy Rises
+    /// Steps of the energy history after which the total energy rose by
+    /// more than the rounding of the largest energy, none for a monotonic
+    /// relaxation.
+    pub fn energy_rises(&self) -> Vec<usize> {
+        let totals: Vec<f64> = self.energy_history().iter().map(Energies::total).collect();
+        let scale = totals.iter().fold(0.0_f64, |scale, e| scale.max(e.abs()));
+        (1..totals.len())
+            .filter(|&step| totals[step] - totals[step - 1] > 1e-12 * scale)
+            .collect()
+    }
+
+    ///# Set Reduction Order
+    /// Choose `Deterministic` for energies that are bit for bit the same
+    /// with any number of threads, e.g. for regression tests.
+    pub fn set_reduction_order(&mut self, reduction_order: ReductionOrder) {
+        self.reduction_order = reduction_order;
+    }
+
+    ///# Set Damping Schedule
+    /// Choose the damping used by the energy minimizer.
+    pub fn set_damping_schedule(&mut self, damping_schedule: DampingSchedule) {
+        self.damping_schedule = damping_schedule;
+        self.adaptive_damping_weight = 1.0;
+    }
+
+    ///# Get Damping Schedule
+    pub fn get_damping_schedule(&self) -> DampingSchedule {
+        self.damping_schedule
+    }
+
+    ///# Set Applied Field
+    /// Set the uniform applied field B = mu0 H in T.
+    pub fn set_applied_field(&mut self, applied_field: [f64; 3]) {
+        self.applied_field = applied_field;
+    }
+
+    ///# Get Applied Field
+    pub fn get_applied_field(&self) -> [f64; 3] {
+        self.applied_field
+    }
+
+    ///# Set Field Gradient
+    /// Make the applied field vary linearly along the chain,
+    /// B(x) = B0 + G x with x measured from the first cell and G in T/m.
+    pub fn set_field_gradient(&mut self, field_gradient: [f64; 3]) {
+        self.field_gradient = field_gradient;
+    }
+
+    ///# Get Field Gradient
+    pub fn get_field_gradient(&self) -> [f64; 3] {
+        self.field_gradient
+    }
+
+    ///# Set Local Fields
+    /// Add a field in T to every cell on top of the applied field, one
+    /// vector per cell. An empty vector removes the local fields.
+    pub fn set_local_fields(&mut self, local_fields: Vec<[f64; 3]>) -> Result<(), Box<dyn Error>> {
+        if !local_fields.is_empty() && local_fields.len() != self.size {
+            return Err(format!(
+                "{} local fields for {} cells, one per cell is needed",
+                local_fields.len(),
+                self.size
+            )
+            .into());
+        }
+        self.local_fields = local_fields;
+        Ok(())
+    }
+
+    ///# Shift Frame
+    /// Move the simulated window by whole cells along the chain, forward
+    /// for positive `cells`. The magnetization moves with the laboratory,
+    /// so structures shift back by `cells` inside the window, and the cells
+    /// that enter the window copy the edge cell they replace, continuing
+    /// the domain beyond it. Materials and local fields stay with the
+    /// window, which suits a uniform wire. The gradient of the applied
+    /// field is evaluated at the laboratory positions.
+    pub fn shift_frame(&mut self, cells: isize) {
+        if self.size == 0 || cells == 0 {
+            return;
+        }
+        let last = self.size as isize - 1;
+        // Vacuum cells stay empty and keep magnetic cells from copying them
+        self.magnetizations = (0..self.size as isize)
+            .map(|i| {
+                let source = &self.magnetizations[(i + cells).clamp(0, last) as usize];
+                let current = &self.magnetizations[i as usize];
+                if current.dot(current) == 0.0 || source.dot(source) == 0.0 {
+                    current.clone()
+                } else {
+                    source.clone()
+                }
+            })
+            .collect();
+        self.frame_offset += cells as f64 * self.cell_size;
+    }
+
+    ///# Get Frame Offset
+    /// Laboratory position in m of the first cell, zero until the frame is shifted.
+    pub fn frame_offset(&self) -> f64 {
+        self.frame_offset
+    }
+
+    ///# Applied Field at a Cell
+    /// Uniform applied field plus the gradient term and the local field, in T.
+    pub fn applied_field_at(&self, i: usize) -> [f64; 3] {
+        let x = self.frame_offset + self.grid.position(i, self.cell_size)[0];
+        let local = self.local_fields.get(i).copied().unwrap_or([0.0; 3]);
+        [
+            self.applied_field[0] + self.field_gradient[0] * x + local[0],
+            self.applied_field[1] + self.field_gradient[1] * x + local[1],
+            self.applied_field[2] + self.field_gradient[2] * x + local[2],
+        ]
+    }
+
+    ///# Add Interlayer Coupling
+    /// Couple two cells, usually the interface cells on both sides of a
+    /// nonmagnetic spacer, with the RKKY constant in J/m^2.
+    pub fn add_interlayer_coupling(&mut self, first: usize, second: usize, coupling: f64) {
+        self.interlayer_couplings.push(InterlayerCoupling {
+            first,
+            second,
+            coupling,
+            biquadratic: 0.0,
+        });
+    }
+
+    ///# Add Biquadratic Coupling
+    /// Couple two cells with the biquadratic constant B in J/m^2, as found
+    /// across spacers with competing or fluctuating bilinear coupling.
+    pub fn add_biquadratic_coupling(&mut self, first: usize, second: usize, biquadratic: f64) {
+        self.interlayer_couplings.push(InterlayerCoupling {
+            first,
+            second,
+            coupling: 0.0,
+            biquadratic,
+        });
+    }
+
+    ///# Get Interlayer Couplings
+    pub fn get_interlayer_couplings(&self) -> &[InterlayerCoupling] {
+        &self.interlayer_couplings
+    }
+
+    ///# Set Neighbor List
+    /// Couple the cells by exchange along the given connectivity instead
+    /// of the chain, e.g. to close a ring or attach a branch.
+    pub fn set_neighbor_list(&mut self, neighbor_list: NeighborList) -> Result<(), Box<dyn Error>> {
+        if neighbor_list.size() != self.size {
+            return Err(format!(
+                "The neighbor list covers {} cells instead of {}",
+                neighbor_list.size(),
+                self.size
+            )
+            .into());
+        }
+        self.neighbor_list = neighbor_list;
+        Ok(())
+    }
+
+    ///# Get Neighbor List
+    pub fn get_neighbor_list(&self) -> &NeighborList {
+        &self.neighbor_list
+    }
+
+    ///# Set Grid
+    /// Arrange the cells on the mesh and couple them to their face
+    /// neighbors, replacing the neighbor list.
+    pub fn set_grid(&mut self, grid: Grid) -> Result<(), Box<dyn Error>> {
+        if grid.size() != self.size {
+            return Err(format!(
+                "The {}x{}x{} grid holds {} cells instead of {}",
+                grid.nx,
+                grid.ny,
+                grid.nz,
+                grid.size(),
+                self.size
+            )
+            .into());
+        }
+        self.neighbor_list = grid.neighbor_list_with(self.boundary_condition);
+        self.grid = grid;
+        self.update_demagnetization_kernel();
+        Ok(())
+    }
+
+    ///# Get Grid
+    pub fn get_grid(&self) -> Grid {
+        self.grid
+    }
+
+    ///# Set Cell Size
+    /// Edge length in m of the cubic cells, which scales the exchange, DMI
+    /// and interlayer fields and the volume of the energy terms.
+    pub fn set_cell_size(&mut self, cell_size: f64) {
+        self.cell_size = cell_size;
+    }
+
+    ///# Get Cell Size
+    pub fn get_cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    ///# Cell Volume
+    /// Volume in m^3 of one cell.
+    pub fn cell_volume(&self) -> f64 {
+        self.cell_size * self.cell_size * self.cell_size
+    }
+
+    ///# Set Boundary Condition
+    /// Free, periodic or fixed outer faces of the grid. The face neighbors
+    /// of the grid replace the neighbor list, with the wrap-around edges of
+    /// periodic boundaries, along whose axes the dipolar field repeats too.
+    pub fn set_boundary_condition(&mut self, boundary_condition: BoundaryCondition) {
+        self.boundary_condition = boundary_condition;
+        self.neighbor_list = self.grid.neighbor_list_with(boundary_condition);
+        self.update_demagnetization_kernel();
+    }
+
+    ///# Get Boundary Condition
+    pub fn get_boundary_condition(&self) -> BoundaryCondition {
+        self.boundary_condition
+    }
+
+    // Magnetization of the ghost cells and the outer faces of a cell at a
+    // fixed boundary
+    fn fixed_boundary(&self, i: usize) -> Option<(Array1<f64>, f64)> {
+        let BoundaryCondition::Fixed { direction } = self.boundary_condition else {
+            return None;
+        };
+        let faces = self.grid.boundary_faces(i);
+        if faces == 0 {
+            return None;
+        }
+        let ghost = Array1::from_vec(direction.to_vec());
+        let norm = ghost.dot(&ghost).sqrt();
+        Some((ghost / norm, faces as f64))
+    }
+
+    ///# Set Dipolar Interaction
+    /// Enable the dipole-dipole field, by default the exact O(N^2) sum
+    /// meant for small systems and macrospin clusters.
+    pub fn set_dipolar_interaction(&mut self, enabled: bool) {
+        self.dipolar_interaction = enabled;
+        self.update_demagnetization_kernel();
+    }
+
+    ///# Set Dipolar Method
+    /// Compute the dipole-dipole field by the direct sum or by the FFT
+    /// convolution with the demagnetizing tensor of the grid.
+    pub fn set_dipolar_method(&mut self, method: DipolarMethod) {
+        self.dipolar_method = method;
+        self.update_demagnetization_kernel();
+    }
+
+    ///# Get Dipolar Method
+    pub fn get_dipolar_method(&self) -> DipolarMethod {
+        self.dipolar_method
+    }
+
+    ///# Set Periodic Images
+    /// Copies of the grid on either side along the periodic axes of
+    /// periodic boundaries that the dipolar field sums over, in both
+    /// methods. Zero keeps only the nearest image of every cell.
+    pub fn set_periodic_images(&mut self, images: usize) {
+        self.periodic_images = images;
+        self.update_demagnetization_kernel();
+    }
+
+    ///# Get Periodic Images
+    pub fn get_periodic_images(&self) -> usize {
+        self.periodic_images
+    }
+
+    // Periodic axes of the dipolar field and the copies on either side,
+    // none without periodic axes
+    fn dipolar_periodicity(&self) -> ([bool; 3], usize) {
+        let axes = self.grid.periodic_axes(self.boundary_condition);
+        let images = if axes.contains(&true) {
+            self.periodic_images
+        } else {
+            0
+        };
+        (axes, images)
+    }
+
+    // Tabulate the tensor of the FFT method for the grid, only when in use
+    fn update_demagnetization_kernel(&mut self) {
+        let needed = self.dipolar_interaction && self.dipolar_method == DipolarMethod::Fft;
+        let (axes, images) = self.dipolar_periodicity();
+        if !needed {
+            self.demagnetization_kernel = None;
+        } else if self.demagnetization_kernel.as_ref().is_none_or(|kernel| {
+            kernel.grid() != self.grid || kernel.periodic_images() != (axes, images)
+        }) {
+            self.demagnetization_kernel = Some(Arc::new(
+                DemagnetizationKernel::with_periodic_images(self.grid, axes, images),
+            ));
+        }
+    }
+
+    ///# Set Demagnetization Factors
+    /// Shape anisotropy from the diagonal demagnetizing tensor [Nx, Ny, Nz]
+    /// of the sample, e.g. from `prism_demagnetization_factors`. Every cell
+    /// feels the field -Ms N m of a homogeneously magnetized sample.
+    pub fn set_demagnetization_factors(&mut self, demagnetization_factors: [f64; 3]) {
+        self.demagnetization_factors = demagnetization_factors;
+    }
+
+    ///# Get Demagnetization Factors
+    pub fn get_demagnetization_factors(&self) -> [f64; 3] {
+        self.demagnetization_factors
+    }
+
+    ///# Dipolar Field
+    /// Dipole-dipole field at every cell, from the FFT convolution when
+    /// that method is in use and else summed directly in parallel.
+    pub fn compute_dipolar_field(&self) -> Vec<Array1<f64>> {
+        let saturation_magnetizations = self.get_saturation_magnetizations();
+        if let Some(kernel) = &self.demagnetization_kernel {
+            return kernel.field(&self.magnetizations, &saturation_magnetizations);
+        }
+        let (periodic, images) = self.dipolar_periodicity();
+        map_cells(self.size, |i| {
+            if self.is_vacuum(i) {
+                return Array1::zeros(3);
+            }
+            periodic_dipolar_field_at(
+                &self.grid,
+                self.cell_size,
+                i,
+                &self.magnetizations,
+                &saturation_magnetizations,
+                periodic,
+                images,
+            )
+        })
+    }
+
+    ///# Set Saturation Magnetization
+    /// Set the saturation magnetization of a single cell.
+    /// A value of zero turns the cell into vacuum: its magnetization is
+    /// zeroed and it is skipped by every field term, energy sum and update.
+    pub fn set_saturation_magnetization(&mut self, cell: usize, saturation_magnetization: f64) {
+        let material = Material {
+            saturation_magnetization,
+            ..self.materials[cell]
+        };
+        self.set_material(cell, material);
+    }
+
+    ///# Set Material
+    /// Set all material constants of a single cell.
+    /// A material with zero saturation magnetization turns the cell into vacuum.
+    pub fn set_material(&mut self, cell: usize, material: Material) {
+        self.materials[cell] = material;
+        if material.is_vacuum() {
+            self.magnetizations[cell] = Array1::zeros(3);
+        } else if self.magnetizations[cell].dot(&self.magnetizations[cell]) == 0.0 {
+            // A cell that was vacuum before needs a direction again
+            self.magnetizations[cell] = Array1::from_vec(material.easy_axis.to_vec());
+        }
+    }
+
+    ///# Add Region
+    /// Assign the material of the region to the cells of its shape and
+    /// return the id of the region, one more than the highest id so far.
+    pub fn add_region(&mut self, region: &Region) -> Result<usize, Box<dyn Error>> {
+        region.shape.validate(&self.grid)?;
+        let id = self.regions.iter().max().map_or(1, |highest| highest + 1);
+        for cell in region.shape.cells(&self.grid, self.cell_size) {
+            self.set_material(cell, region.material);
+            self.regions[cell] = id;
+        }
+        Ok(id)
+    }
+
+    ///# Get Regions
+    /// Region id of every cell, 0 for the cells outside every region.
+    pub fn get_regions(&self) -> &[usize] {
+        &self.regions
+    }
+
+    ///# Get Materials
+    pub fn get_materials(&self) -> Vec<Material> {
+        self.materials.clone()
+    }
+
+    ///# Get Saturation Magnetizations
+    pub fn get_saturation_magnetizations(&self) -> Vec<f64> {
+        self.materials
+            .iter()
+            .map(|material| material.saturation_magnetization)
+            .collect()
+    }
+
+    ///# Vacuum Check
+    /// A cell with zero saturation magnetization carries no moment.
+    pub fn is_vacuum(&self, cell: usize) -> bool {
+        self.materials[cell].is_vacuum()
+    }
+
+    ///# Easy Axis
+    /// Easy axis of the first magnetic cell, along which the domains and
+    /// the wall of the chain are counted. The built-in axis when every cell
+    /// is vacuum.
+    pub fn easy_axis(&self) -> [f64; 3] {
+        (0..self.size)
+            .find(|&i| !self.is_vacuum(i))
+            .map_or(EASY_AXIS, |i| self.materials[i].easy_axis)
     }
 
     ///# Total Effective Field Calculation
     /// Compute the total effective field at each cell by
     /// calculating and summing the exchange, anisotropy, and Zeeman fields.
-    fn compute_effective_field(&self) -> Vec<Array1<f64>> {
-        let mut h_eff: Vec<Array1<f64>> = vec![Array1::zeros(3); self.size];
+    /// The cells are independent, so they are evaluated in parallel
+    /// when the `parallel` feature is enabled.
+    pub(crate) fn compute_effective_field(&self) -> Vec<Array1<f64>> {
+        let mut h_eff = map_cells(self.size, |i| self.compute_effective_field_at(i));
+        self.add_demagnetizing_field(&mut h_eff, 0..self.size);
+        h_eff
+    }
+
+    // FFT dipolar field of the whole system added to the fields of the
+    // given cells, which `compute_effective_field_at` leaves out
+    fn add_demagnetizing_field(
+        &self,
+        h_eff: &mut [Array1<f64>],
+        cells: impl IntoIterator<Item = usize>,
+    ) {
+        if self.demagnetization_kernel.is_none() {
+            return;
+        }
+        let h_dipolar = self.compute_dipolar_field();
+        for (h, i) in h_eff.iter_mut().zip(cells) {
+            if !self.is_vacuum(i) {
+                *h += &h_dipolar[i];
+            }
+        }
+    }
+
+    ///# Effective Field at a Cell
+    /// Sum of the exchange, anisotropy, and Zeeman fields at a single cell.
+    /// Only the cell and its nearest neighbors are read, and the direct
+    /// dipolar sum. The FFT dipolar field needs the whole system and is
+    /// added by `compute_effective_field`.
+    pub(crate) fn compute_effective_field_at(&self, i: usize) -> Array1<f64> {
+        let mut h_eff: Array1<f64> = Array1::zeros(3);
+
+        // Vacuum cells carry no moment and feel no field
+        if self.is_vacuum(i) {
+            return h_eff;
+        }
+        let material = &self.materials[i];
+        let easy_axis = Array1::from_vec(material.easy_axis.to_vec());
 
         // Exchange Field Calculation
         // Finds the effective field at each cell using a finite difference method
@@ -57,13 +956,106 @@ impl MicromagneticSystem {
         // which tends to align them to minimize energy.
         // This interaction smoothens spatial variations in magnetization and
         // penalizes sharp changes, creating a preference for uniform magnetization.
-        for i in 1..(self.size - 1) {
-            h_eff[i] = h_eff[i].clone()
-                + (2.0 * MAGNETIC_EXCHANGE_CONSTANT
-                    / (SATURATION_MAGNETIZATION * PERMEABILITY_OF_FREE_SPACE))
-                    * (self.magnetizations[i + 1].clone() - 2.0 * self.magnetizations[i].clone()
-                        + self.magnetizations[i - 1].clone())
-                    / (SPATIAL_DISCRETION_STEP * SPATIAL_DISCRETION_STEP);
+        // Vacuum neighbors do not couple, so they act as free surfaces, as do the
+        // outer faces of the grid unless the boundary condition is periodic or fixed.
+        // A negative exchange constant turns the field against the neighbors,
+        // which couples them antiparallel.
+        // Between different materials the harmonic mean of the exchange
+        // constants sets the coupling. The neighbors come from the neighbor
+        // list, which is the chain unless rings or junctions were set up.
+        for &j in self.neighbor_list.neighbors(i) {
+            if self.is_vacuum(j) {
+                continue;
+            }
+            let exchange_constant = material.interface_exchange_constant(&self.materials[j]);
+            h_eff = h_eff
+                + (2.0 * exchange_constant
+                    / (material.saturation_magnetization * PERMEABILITY_OF_FREE_SPACE))
+                    * (&self.magnetizations[j] - &self.magnetizations[i])
+                    / (self.cell_size * self.cell_size);
+        }
+        // A fixed boundary couples the outer faces to ghost cells of the
+        // same material
+        if let Some((ghost, faces)) = self.fixed_boundary(i) {
+            h_eff = h_eff
+                + (2.0 * faces * material.exchange_constant
+                    / (material.saturation_magnetization * PERMEABILITY_OF_FREE_SPACE))
+                    * (ghost - &self.magnetizations[i])
+                    / (self.cell_size * self.cell_size);
+        }
+
+        // Dzyaloshinskii-Moriya Field
+        // The antisymmetric exchange D d . (m_i x m_j) of a bond pulls m_i
+        // towards d x m_j, which cants neighbors into a chiral rotation.
+        for &j in self.neighbor_list.neighbors(i) {
+            if self.is_vacuum(j) {
+                continue;
+            }
+            let d = bond_dmi_vector(&self.grid, i, j, material, &self.materials[j]);
+            if d == [0.0; 3] {
+                continue;
+            }
+            let m = &self.magnetizations[j];
+            let d_cross_m = array![
+                d[1] * m[2] - d[2] * m[1],
+                d[2] * m[0] - d[0] * m[2],
+                d[0] * m[1] - d[1] * m[0]
+            ];
+            h_eff = h_eff
+                + d_cross_m
+                    / (material.saturation_magnetization
+                        * PERMEABILITY_OF_FREE_SPACE
+                        * self.cell_size);
+        }
+
+        // Second Neighbor Exchange Field
+        // Cells two apart couple with the same finite difference normalization,
+        // as long as the cell between them is magnetic. At a junction of a
+        // chain every path over a common neighbor couples once, on a grid
+        // only the cells in line along an axis.
+        for &middle in self.neighbor_list.neighbors(i) {
+            if self.is_vacuum(middle) {
+                continue;
+            }
+            for &j in self.neighbor_list.neighbors(middle) {
+                if j == i || self.is_vacuum(j) || !self.second_neighbors(i, middle, j) {
+                    continue;
+                }
+                let exchange_constant =
+                    material.interface_second_neighbor_exchange_constant(&self.materials[j]);
+                if exchange_constant == 0.0 {
+                    continue;
+                }
+                h_eff = h_eff
+                    + (2.0 * exchange_constant
+                        / (material.saturation_magnetization * PERMEABILITY_OF_FREE_SPACE))
+                        * (&self.magnetizations[j] - &self.magnetizations[i])
+                        / (self.cell_size * self.cell_size);
+            }
+        }
+
+        // Interlayer Exchange Field
+        // The RKKY energy acts on the interface area of the cell,
+        // so the field scales with the inverse cell thickness.
+        // The biquadratic term adds 2 B (m . m_partner) to the coupling.
+        for coupling in &self.interlayer_couplings {
+            let partner = if coupling.first == i {
+                coupling.second
+            } else if coupling.second == i {
+                coupling.first
+            } else {
+                continue;
+            };
+            if self.is_vacuum(partner) {
+                continue;
+            }
+            let alignment = self.magnetizations[i].dot(&self.magnetizations[partner]);
+            h_eff = h_eff
+                + (coupling.coupling + 2.0 * coupling.biquadratic * alignment)
+                    / (material.saturation_magnetization
+                        * PERMEABILITY_OF_FREE_SPACE
+                        * self.cell_size)
+                    * &self.magnetizations[partner];
         }
 
         // Anisotropy Field Calculation
@@ -73,71 +1065,376 @@ impl MicromagneticSystem {
         // or shape, which imposes a preferred direction (easy axis) for magnetization.
         // This preferred direction minimizes the anisotropy energy when the
         // magnetization aligns with it.
-        for i in 0..self.size {
-            //Dot product of the magnetization and the easy axis
-            let scalar_product_of_the_magnetization_and_the_easy_axis =
-                self.magnetizations[i].dot(&Array1::from_vec(EASY_AXIS.to_vec()));
+        //Dot product of the magnetization and the easy axis
+        let scalar_product_of_the_magnetization_and_the_easy_axis =
+            self.magnetizations[i].dot(&easy_axis);
 
-            h_eff[i] = h_eff[i].clone()
-                + 2.0
-                    * UNIAXIAL_ANISOTROPY_CONSTANT
-                    * scalar_product_of_the_magnetization_and_the_easy_axis
-                    / (SATURATION_MAGNETIZATION * PERMEABILITY_OF_FREE_SPACE)
-                    * Array1::from_vec(EASY_AXIS.to_vec());
-        }
+        h_eff = h_eff
+            + 2.0
+                * material.anisotropy_constant
+                * scalar_product_of_the_magnetization_and_the_easy_axis
+                / (material.saturation_magnetization * PERMEABILITY_OF_FREE_SPACE)
+                * easy_axis;
 
         // Zeeman Field
-        // We take the Zeeman field as a constant external field in the z-direction.
+        // We take the Zeeman field as a uniform external field, by default in the z-direction,
+        // plus an optional linear gradient along the chain.
         // The Zeeman field represents the interaction of the magnetization
         // with an external magnetic field. This interaction tries to
         // align the magnetization with the external field direction
         // to minimize the Zeeman energy.
-        for i in 0..self.size {
-            h_eff[i] = h_eff[i].clone()
-                + Array1::from_vec(EXTERNAL_FIELD.to_vec()) / (PERMEABILITY_OF_FREE_SPACE);
+        h_eff = h_eff
+            + Array1::from_vec(self.applied_field_at(i).to_vec()) / (PERMEABILITY_OF_FREE_SPACE);
+
+        // Dipolar Field
+        // The long range magnetostatic interaction between the cells,
+        // summed directly over all other cells and their periodic images.
+        if self.dipolar_interaction && self.demagnetization_kernel.is_none() {
+            let saturation_magnetizations = self.get_saturation_magnetizations();
+            let (periodic, images) = self.dipolar_periodicity();
+            h_eff = h_eff
+                + periodic_dipolar_field_at(
+                    &self.grid,
+                    self.cell_size,
+                    i,
+                    &self.magnetizations,
+                    &saturation_magnetizations,
+                    periodic,
+                    images,
+                );
+        }
+
+        // Shape Anisotropy Field
+        // The average demagnetizing field -Ms N m of the sample shape.
+        for k in 0..3 {
+            h_eff[k] -= material.saturation_magnetization
+                * self.demagnetization_factors[k]
+                * self.magnetizations[i][k];
         }
 
         // returns the total effective field
         h_eff
     }
 
-    fn compute_magnetic_energy_density(&self) -> f64 {
-        let mut magnetic_energy_density = 0.0;
+    ///# Energies
+    /// Exchange, anisotropy, Zeeman, dipolar and DMI energy of the system
+    /// in J, zero for the terms that are off. Every cell is a cube with the edge length of the cell size.
+    pub fn compute_energies(&self) -> Energies {
+        // The terms are accumulated with compensated summation, so that small
+        // energy differences of large systems stay meaningful. The per-cell
+        // terms are summed in parallel in the reduction order of the system.
+        let mut exchange = CompensatedSum::default();
 
         //Exchange energy
-        for i in 1..(self.size - 1) {
-            magnetic_energy_density += -MAGNETIC_EXCHANGE_CONSTANT
-                * self.magnetizations[i].dot(&self.magnetizations[i + 1])
-                / (SATURATION_MAGNETIZATION * PERMEABILITY_OF_FREE_SPACE);
+        // A |grad m|^2 with the gradient taken between neighboring cells
+        for (i, j) in self.neighbor_list.edges() {
+            if self.is_vacuum(i) || self.is_vacuum(j) {
+                continue;
+            }
+            let exchange_constant =
+                self.materials[i].interface_exchange_constant(&self.materials[j]);
+            let difference = &self.magnetizations[j] - &self.magnetizations[i];
+            exchange += exchange_constant * difference.dot(&difference)
+                / (self.cell_size * self.cell_size)
+                * self.cell_volume();
+        }
+        for i in 0..self.size {
+            if self.is_vacuum(i) {
+                continue;
+            }
+            if let Some((ghost, faces)) = self.fixed_boundary(i) {
+                let difference = ghost - &self.magnetizations[i];
+                exchange +=
+                    faces * self.materials[i].exchange_constant * difference.dot(&difference)
+                        / (self.cell_size * self.cell_size)
+                        * self.cell_volume();
+            }
+        }
+
+        // D d . (m_i x m_j) of every bond with the DMI vector from i to j
+        let mut dmi = CompensatedSum::default();
+        for (i, j) in self.neighbor_list.edges() {
+            if self.is_vacuum(i) || self.is_vacuum(j) {
+                continue;
+            }
+            let d = bond_dmi_vector(&self.grid, i, j, &self.materials[i], &self.materials[j]);
+            if d == [0.0; 3] {
+                continue;
+            }
+            let (a, b) = (&self.magnetizations[i], &self.magnetizations[j]);
+            let a_cross_b = [
+                a[1] * b[2] - a[2] * b[1],
+                a[2] * b[0] - a[0] * b[2],
+                a[0] * b[1] - a[1] * b[0],
+            ];
+            dmi += (0..3).map(|k| d[k] * a_cross_b[k]).sum::<f64>() * self.cell_volume()
+                / self.cell_size;
+        }
+
+        // A2 |m_k - m_i|^2 between cells two apart across a magnetic cell,
+        // once for every pair of second neighbors around the middle cell
+        for middle in 0..self.size {
+            if self.is_vacuum(middle) {
+                continue;
+            }
+            let neighbors = self.neighbor_list.neighbors(middle);
+            for (a, &i) in neighbors.iter().enumerate() {
+                for &k in &neighbors[a + 1..] {
+                    if self.is_vacuum(i)
+                        || self.is_vacuum(k)
+                        || !self.second_neighbors(i, middle, k)
+                    {
+                        continue;
+                    }
+                    let exchange_constant = self.materials[i]
+                        .interface_second_neighbor_exchange_constant(&self.materials[k]);
+                    let difference = &self.magnetizations[k] - &self.magnetizations[i];
+                    exchange += exchange_constant * difference.dot(&difference)
+                        / (self.cell_size * self.cell_size)
+                        * self.cell_volume();
+                }
+            }
+        }
+
+        // -J m_1 . m_2 - B (m_1 . m_2)^2 over the interface area of the coupled cells
+        for coupling in &self.interlayer_couplings {
+            if self.is_vacuum(coupling.first) || self.is_vacuum(coupling.second) {
+                continue;
+            }
+            let alignment =
+                self.magnetizations[coupling.first].dot(&self.magnetizations[coupling.second]);
+            exchange += -(coupling.coupling * alignment + coupling.biquadratic * alignment.powi(2))
+                * self.cell_size
+                * self.cell_size;
         }
 
         //Anisotropy energy
-        for i in 0..self.size {
+        // -K (m . e)^2
+        let anisotropy = sum_cells(self.size, self.reduction_order, |i| {
+            if self.is_vacuum(i) {
+                return 0.0;
+            }
+            let material = &self.materials[i];
             let scalar_product_of_the_magnetization_and_the_easy_axis =
-                self.magnetizations[i].dot(&Array1::from_vec(EASY_AXIS.to_vec()));
-            magnetic_energy_density += -UNIAXIAL_ANISOTROPY_CONSTANT
-                * scalar_product_of_the_magnetization_and_the_easy_axis;
-        }
+                self.magnetizations[i].dot(&Array1::from_vec(material.easy_axis.to_vec()));
+            -material.anisotropy_constant
+                * scalar_product_of_the_magnetization_and_the_easy_axis.powi(2)
+                * self.cell_volume()
+        });
 
         //Zeeman energy
-        for i in 0..self.size {
+        // -Ms m . B with the external field B = mu0 H given in T
+        let zeeman = sum_cells(self.size, self.reduction_order, |i| {
+            if self.is_vacuum(i) {
+                return 0.0;
+            }
             let external_field_dot_m =
-                self.magnetizations[i].dot(&Array1::from_vec(EXTERNAL_FIELD.to_vec()));
-            magnetic_energy_density += -external_field_dot_m;
+                self.magnetizations[i].dot(&Array1::from_vec(self.applied_field_at(i).to_vec()));
+            -self.materials[i].saturation_magnetization * external_field_dot_m * self.cell_volume()
+        });
+
+        //Dipolar energy
+        // -mu0/2 Ms m . H_dip, the factor 1/2 avoids counting each pair twice
+        let mut dipolar = 0.0;
+        if self.dipolar_interaction {
+            let h_dipolar = self.compute_dipolar_field();
+            dipolar += sum_cells(self.size, self.reduction_order, |i| {
+                -0.5 * PERMEABILITY_OF_FREE_SPACE
+                    * self.materials[i].saturation_magnetization
+                    * self.magnetizations[i].dot(&h_dipolar[i])
+                    * self.cell_volume()
+            });
+        }
+
+        // mu0/2 Ms^2 sum_k N_k m_k^2 of the shape anisotropy
+        if self.demagnetization_factors != [0.0; 3] {
+            dipolar += sum_cells(self.size, self.reduction_order, |i| {
+                let saturation_magnetization = self.materials[i].saturation_magnetization;
+                let m = &self.magnetizations[i];
+                0.5 * PERMEABILITY_OF_FREE_SPACE
+                    * saturation_magnetization
+                    * saturation_magnetization
+                    * (0..3)
+                        .map(|k| self.demagnetization_factors[k] * m[k] * m[k])
+                        .sum::<f64>()
+                    * self.cell_volume()
+            });
         }
 
-        magnetic_energy_density
+        Energies {
+            exchange: exchange.value(),
+            anisotropy,
+            zeeman,
+            dipolar,
+            dmi: dmi.value(),
+        }
     }
 
-    fn compute_magnetization_change(
-        &self,
-    ) -> Vec<Array1<f64>> {
+    ///# Energy Gradient
+    /// Analytic derivatives of `compute_energies().total()` with the
+    /// magnetization held fixed. The energy is linear in K and B, and the
+    /// exchange depends on A through the interface constants of the
+    /// neighboring cells.
+    pub fn compute_energy_gradient(&self) -> EnergyGradient {
+        let mut exchange_constant = vec![0.0; self.size];
+        for (i, j) in self.neighbor_list.edges() {
+            if self.is_vacuum(i) || self.is_vacuum(j) {
+                continue;
+            }
+            let (left, right) =
+                self.materials[i].interface_exchange_derivatives(&self.materials[j]);
+            let difference = &self.magnetizations[j] - &self.magnetizations[i];
+            let stiffness = difference.dot(&difference) / (self.cell_size * self.cell_size)
+                * self.cell_volume();
+            exchange_constant[i] += left * stiffness;
+            exchange_constant[j] += right * stiffness;
+        }
+        for i in 0..self.size {
+            if self.is_vacuum(i) {
+                continue;
+            }
+            if let Some((ghost, faces)) = self.fixed_boundary(i) {
+                let difference = ghost - &self.magnetizations[i];
+                exchange_constant[i] += faces * difference.dot(&difference)
+                    / (self.cell_size * self.cell_size)
+                    * self.cell_volume();
+            }
+        }
+
+        let mut anisotropy_constant = vec![0.0; self.size];
+        let mut applied_field = [CompensatedSum::default(); 3];
+        for i in 0..self.size {
+            if self.is_vacuum(i) {
+                continue;
+            }
+            let material = &self.materials[i];
+            let projection =
+                self.magnetizations[i].dot(&Array1::from_vec(material.easy_axis.to_vec()));
+            anisotropy_constant[i] = -projection.powi(2) * self.cell_volume();
+            // The field gradient term does not depend on the uniform field
+            for k in 0..3 {
+                applied_field[k] += -material.saturation_magnetization
+                    * self.magnetizations[i][k]
+                    * self.cell_volume();
+            }
+        }
+
+        EnergyGradient {
+            exchange_constant,
+            anisotropy_constant,
+            applied_field: applied_field.map(|sum| sum.value()),
+        }
+    }
+
+    ///# Magnetic Energy Density
+    /// Total energy divided by the volume of the magnetic (non-vacuum) cells, in J/m^3.
+    pub fn compute_magnetic_energy_density(&self) -> f64 {
+        let magnetic_cells = (0..self.size).filter(|&i| !self.is_vacuum(i)).count();
+        if magnetic_cells == 0 {
+            return 0.0;
+        }
+        self.compute_energies().total() / (magnetic_cells as f64 * self.cell_volume())
+    }
+
+    ///# Average Magnetization
+    /// Average of the normalized magnetization over the magnetic cells.
+    pub fn average_magnetization(&self) -> [f64; 3] {
+        let mut sum = [CompensatedSum::default(); 3];
+        let mut magnetic_cells = 0;
+        for i in 0..self.size {
+            if self.is_vacuum(i) {
+                continue;
+            }
+            for k in 0..3 {
+                sum[k] += self.magnetizations[i][k];
+            }
+            magnetic_cells += 1;
+        }
+        let mut average = [0.0; 3];
+        if magnetic_cells > 0 {
+            for k in 0..3 {
+                average[k] = sum[k].value() / magnetic_cells as f64;
+            }
+        }
+        average
+    }
+
+    ///# Staggered Magnetization
+    /// Average of (-1)^i m_i over the magnetic cells, the order parameter
+    /// of an antiferromagnetic chain.
+    pub fn staggered_magnetization(&self) -> [f64; 3] {
+        let mut sum = [CompensatedSum::default(); 3];
+        let mut magnetic_cells = 0;
+        for i in 0..self.size {
+            if self.is_vacuum(i) {
+                continue;
+            }
+            let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+            for k in 0..3 {
+                sum[k] += sign * self.magnetizations[i][k];
+            }
+            magnetic_cells += 1;
+        }
+        let mut staggered = [0.0; 3];
+        if magnetic_cells > 0 {
+            for k in 0..3 {
+                staggered[k] = sum[k].value() / magnetic_cells as f64;
+            }
+        }
+        staggered
+    }
+
+    ///# Magnetization Change
+    /// Change of the magnetization over one time step according to
+    /// the full Landau-Lifshitz-Gilbert equation (precession and damping).
+    pub fn compute_magnetization_change(&self) -> Vec<Array1<f64>> {
+        self.compute_llg_derivative()
+            .into_iter()
+            .map(|derivative| self.time_step * derivative)
+            .collect()
+    }
+
+    ///# Maximum Torque
+    /// Largest |m x H_eff| over the cells in A/m, zero in equilibrium.
+    pub fn compute_max_torque(&self) -> f64 {
+        let h_eff = self.compute_effective_field();
+        let mut max_torque: f64 = 0.0;
+        for i in 0..self.size {
+            let m = &self.magnetizations[i];
+            let h = &h_eff[i];
+            let m_cross_h = [
+                m[1] * h[2] - m[2] * h[1],
+                m[2] * h[0] - m[0] * h[2],
+                m[0] * h[1] - m[1] * h[0],
+            ];
+            max_torque = max_torque.max(m_cross_h.iter().map(|c| c * c).sum::<f64>().sqrt());
+        }
+        max_torque
+    }
+
+    ///# Maximum dm/dt
+    /// Largest |dm/dt| over the cells in 1/s from the Landau-Lifshitz-Gilbert
+    /// equation with the damping of the materials, zero in equilibrium.
+    pub fn compute_max_dm_dt(&self) -> f64 {
+        self.compute_llg_derivative()
+            .iter()
+            .map(|dm_dt| dm_dt.dot(dm_dt).sqrt())
+            .fold(0.0, f64::max)
+    }
+
+    ///# LLG Derivative
+    /// Time derivative dm/dt of every cell from the Landau-Lifshitz-Gilbert
+    /// equation, zero for vacuum cells.
+    pub(crate) fn compute_llg_derivative(&self) -> Vec<Array1<f64>> {
         let mut partial_derivative_of_the_magnetization_with_respect_to_time: Vec<Array1<f64>> =
             vec![Array1::zeros(3); self.size];
-        let mut magnetization_change: Vec<Array1<f64>> = vec![Array1::zeros(3); self.size];
 
         let h_eff = self.compute_effective_field();
         for i in 0..self.size {
+            // Vacuum cells carry no moment and do not evolve
+            if self.is_vacuum(i) {
+                continue;
+            }
             let m = &self.magnetizations[i];
             let h = &h_eff[i];
             let m_cross_h = array![
@@ -150,69 +1447,225 @@ impl MicromagneticSystem {
                 m[2] * m_cross_h[0] - m[0] * m_cross_h[2],
                 m[0] * m_cross_h[1] - m[1] * m_cross_h[0]
             ];
+            let damping = self.materials[i].damping;
             partial_derivative_of_the_magnetization_with_respect_to_time[i] =
-                -GILBERT_GYROMAGNETIC_RATIO / (1.0 + DAMPING_CONSTANT.powi(2))
-                    * (m_cross_h + DAMPING_CONSTANT * m_cross_m_cross_h);
-            magnetization_change[i] = TIME_STEP
-                * &partial_derivative_of_the_magnetization_with_respect_to_time[i];
+                -GILBERT_GYROMAGNETIC_RATIO / (1.0 + damping.powi(2))
+                    * (m_cross_h + damping * m_cross_m_cross_h);
         }
 
-        magnetization_change
+        partial_derivative_of_the_magnetization_with_respect_to_time
     }
 
-    fn compute_energy_change(&mut self) -> f64 {
+    ///# LLG Angular Velocity
+    /// Vector Omega with dm/dt = Omega x m for every cell in rad/s,
+    /// Omega = gamma / (1 + alpha^2) (H + alpha m x H). Zero for vacuum cells.
+    pub(crate) fn compute_llg_angular_velocity(&self) -> Vec<[f64; 3]> {
+        let h_eff = self.compute_effective_field();
+        (0..self.size)
+            .map(|i| {
+                if self.is_vacuum(i) {
+                    return [0.0; 3];
+                }
+                let m = &self.magnetizations[i];
+                let h = &h_eff[i];
+                let m_cross_h = [
+                    m[1] * h[2] - m[2] * h[1],
+                    m[2] * h[0] - m[0] * h[2],
+                    m[0] * h[1] - m[1] * h[0],
+                ];
+                let damping = self.materials[i].damping;
+                let prefactor = GILBERT_GYROMAGNETIC_RATIO / (1.0 + damping.powi(2));
+                [
+                    prefactor * (h[0] + damping * m_cross_h[0]),
+                    prefactor * (h[1] + damping * m_cross_h[1]),
+                    prefactor * (h[2] + damping * m_cross_h[2]),
+                ]
+            })
+            .collect()
+    }
+
+    ///# Energy Change
+    /// Energy change associated with one Landau-Lifshitz-Gilbert time step.
+    pub fn compute_energy_change(&self) -> f64 {
         let magnetization_change = self.compute_magnetization_change();
         let h_eff = self.compute_effective_field();
-        let mut energy_change = 0.0;
+        let mut energy_change = CompensatedSum::default();
         for i in 0..self.size {
-            let m = &self.magnetizations[i];
             let h = &h_eff[i];
             let h_dot_magnetization_change = h.dot(&magnetization_change[i]);
-            energy_change=-h_dot_magnetization_change*SATURATION_MAGNETIZATION*PERMEABILITY_OF_FREE_SPACE;
+            energy_change += -h_dot_magnetization_change
+                * self.materials[i].saturation_magnetization
+                * PERMEABILITY_OF_FREE_SPACE;
+        }
+        energy_change.value()
+    }
+
+    /// #Relaxation Step
+    /// Perform a single relaxation step to minimize energy
+    /// using the damping term of the Landau-Lifshitz-Gilbert equation
+    /// and the computed effective field and check for convergence.
+    /// Also, renormalize the magnetization so that it stays of unit length.
+    pub(crate) fn relaxation_step(&mut self) -> f64 {
+        let max_change = match self.adaptive_time_step {
+            Some(adaptive) => self.adaptive_relaxation_step(adaptive),
+            None => {
+                self.relaxation_time += self.relaxation_step_scale * self.time_step;
+                self.fixed_relaxation_step()
+            }
+        };
+        // Logarithmic distance of the maximum change from the tolerance
+        if let DampingSchedule::Adaptive { .. } = self.damping_schedule {
+            self.adaptive_damping_weight = ((max_change / TOLERANCE).ln()
+                / (ADAPTIVE_DAMPING_FAR_CHANGE / TOLERANCE).ln())
+            .clamp(0.0, 1.0);
+        }
+        max_change
+    }
+
+    ///# Fixed Relaxation Step
+    /// One step of the update scheme with the current time step.
+    fn fixed_relaxation_step(&mut self) -> f64 {
+        match self.update_scheme {
+            UpdateScheme::Jacobi => self.jacobi_relaxation_step(),
+            UpdateScheme::RedBlack => self.red_black_relaxation_step(),
+        }
+    }
+
+    ///# Adaptive Relaxation Step
+    /// Step doubling of the first order relaxation step: a full step is
+    /// compared with two half steps from the same state. Rejected steps are
+    /// repeated from that state with a smaller step, the accepted state is
+    /// the one of the two half steps and the time step is set to the next
+    /// step. Returns the largest component change of the accepted step.
+    fn adaptive_relaxation_step(&mut self, adaptive: AdaptiveTimeStep) -> f64 {
+        let initial = self.magnetizations.clone();
+        let mut step = self.time_step.clamp(adaptive.min_step, adaptive.max_step);
+        loop {
+            self.time_step = step;
+            self.fixed_relaxation_step();
+            let full = std::mem::replace(&mut self.magnetizations, initial.clone());
+            self.time_step = 0.5 * step;
+            self.fixed_relaxation_step();
+            self.fixed_relaxation_step();
+            let change = max_component_difference(&initial, &self.magnetizations);
+            let difference = max_component_difference(&full, &self.magnetizations);
+            let (accepted, next) = adaptive.control(step, difference, change, 1);
+            self.time_step = next;
+            if accepted {
+                self.relaxation_time += self.relaxation_step_scale * step;
+                return change;
+            }
+            self.magnetizations.clone_from(&initial);
+            step = next;
+        }
+    }
+
+    ///# Relaxation Damping
+    /// Damping of a cell in the minimizer according to the damping schedule.
+    fn relaxation_damping(&self, i: usize) -> f64 {
+        let damping = self.materials[i].damping;
+        match self.damping_schedule {
+            DampingSchedule::Material => damping,
+            DampingSchedule::Adaptive { maximum } => {
+                damping + (maximum - damping) * self.adaptive_damping_weight
+            }
         }
-        energy_change
     }
 
-    
-
-
-
-    /// #Relaxation Step
-    /// Perform a single relaxation step to minimize energy
-    /// using the damping term of the Landau-Lifshitz-Gilbert equation
-    /// and the computed effective field and check for convergence.
-    /// Also, clamp the magnetization to [-1, 1] so that it is normalized.
-    fn relaxation_step(&mut self) -> f64 {
+    ///# Jacobi Relaxation Step
+    /// Every cell is updated from the effective field of the previous state.
+    fn jacobi_relaxation_step(&mut self) -> f64 {
         // calculate the effective field
         let h_eff = self.compute_effective_field();
-        let mut max_change: f64 = 0.0;
+
+        // Calculate the change in magnetization of each cell
+        let changes_of_magnetization = map_cells(self.size, |i| {
+            self.compute_relaxation_change_at(i, &h_eff[i])
+        });
 
         // Goes through each cell and updates the magnetization
-        for i in 0..self.size {
-            // Calculate the change in magnetization
-            let change_of_magnetization = -DAMPING_CONSTANT
-                * GILBERT_GYROMAGNETIC_RATIO
-                * h_eff[i].clone()
-                * SATURATION_MAGNETIZATION;
-
-            // Calculate the maximum change in magnetization
-            // and update the magnetization
-            max_change = max_change.max(
-                change_of_magnetization
-                    .iter()
-                    .map(|&x| x.abs())
-                    .fold(0.0, f64::max),
-            );
+        let mut max_change: f64 = 0.0;
+        for (i, change_of_magnetization) in changes_of_magnetization.iter().enumerate() {
+            max_change = max_change.max(self.apply_change(i, change_of_magnetization));
+        }
+
+        max_change
+    }
 
-            // Update magnetization and normalize it
-            self.magnetizations[i] = &self.magnetizations[i] + &change_of_magnetization;
-            let norm = self.magnetizations[i].dot(&self.magnetizations[i]).sqrt();
-            self.magnetizations[i] /= norm;
+    ///# Red-Black Relaxation Step
+    /// Gauss-Seidel style step: the even cells of the checkerboard are
+    /// updated first and the odd cells then see their already relaxed
+    /// neighbors. The changes of a color are computed in parallel and
+    /// written back after, so a cell sees the cells of its own color as
+    /// they were before the half sweep. Only the face neighbors are
+    /// guaranteed to have the other color, and not across the periodic
+    /// wrap of an odd axis. The second neighbors, the extra neighbors of
+    /// the exchange and the DMI, and the dipolar field can couple cells of
+    /// the same color, which then relax like in the Jacobi scheme.
+    fn red_black_relaxation_step(&mut self) -> f64 {
+        let mut max_change: f64 = 0.0;
+        for color in 0..2 {
+            let cells: Vec<usize> = (0..self.size)
+                .filter(|&i| self.grid.color(i) == color)
+                .collect();
+            let mut h_eff = map_cells(cells.len(), |k| self.compute_effective_field_at(cells[k]));
+            self.add_demagnetizing_field(&mut h_eff, cells.iter().copied());
+            let changes_of_magnetization = map_cells(cells.len(), |k| {
+                self.compute_relaxation_change_at(cells[k], &h_eff[k])
+            });
+            for (k, change_of_magnetization) in changes_of_magnetization.iter().enumerate() {
+                max_change = max_change.max(self.apply_change(cells[k], change_of_magnetization));
+            }
         }
 
         max_change
     }
 
+    ///# Apply Change
+    /// Add the change to the magnetization of a cell, renormalize it and
+    /// return the largest absolute component of the change.
+    fn apply_change(&mut self, i: usize, change_of_magnetization: &Array1<f64>) -> f64 {
+        // Skip vacuum cells, normalizing their zero vector would produce NaNs
+        if self.is_vacuum(i) {
+            return 0.0;
+        }
+
+        // Update magnetization and normalize it
+        self.magnetizations[i] = &self.magnetizations[i] + change_of_magnetization;
+        let norm = self.magnetizations[i].dot(&self.magnetizations[i]).sqrt();
+        self.magnetizations[i] /= norm;
+
+        // Calculate the maximum change in magnetization
+        change_of_magnetization
+            .iter()
+            .map(|&x| x.abs())
+            .fold(0.0, f64::max)
+    }
+
+    ///# Relaxation Change at a Cell
+    /// Change in magnetization of a single cell from the damping term only,
+    /// the precession does not change the energy.
+    pub(crate) fn compute_relaxation_change_at(&self, i: usize, h: &Array1<f64>) -> Array1<f64> {
+        if self.is_vacuum(i) {
+            return Array1::zeros(3);
+        }
+        let m = &self.magnetizations[i];
+        let m_cross_h = array![
+            m[1] * h[2] - m[2] * h[1],
+            m[2] * h[0] - m[0] * h[2],
+            m[0] * h[1] - m[1] * h[0]
+        ];
+        let m_cross_m_cross_h = array![
+            m[1] * m_cross_h[2] - m[2] * m_cross_h[1],
+            m[2] * m_cross_h[0] - m[0] * m_cross_h[2],
+            m[0] * m_cross_h[1] - m[1] * m_cross_h[0]
+        ];
+        let damping = self.relaxation_damping(i);
+        -self.relaxation_step_scale * self.time_step * damping * GILBERT_GYROMAGNETIC_RATIO
+            / (1.0 + damping.powi(2))
+            * m_cross_m_cross_h
+    }
+
     ///# Energy Minimization check
     /// Checks if the energy has converged or if the maximum number
     /// of iterations has been reached.
@@ -221,24 +1674,187 @@ impl MicromagneticSystem {
     /// If energy stops decreasing between steps or falls below a tolerance,
     /// it’s a sign that the system has stabilized.
     pub fn minimize_energy(&mut self) {
+        self.minimize_energy_with(|_, _| {});
+    }
+
+    ///# Energy Minimization with Observer
+    /// Same as `minimize_energy`, but calls `observer` with the number of
+    /// steps performed so far, once before the first step and after every step.
+    pub fn minimize_energy_with<F: FnMut(usize, &Self)>(&mut self, mut observer: F) {
+        self.minimize_energy_until(|step, system| {
+            observer(step, system);
+            true
+        });
+    }
+
+    ///# Diagnosed Energy Minimization
+    /// Same as `minimize_energy_until`, but records the total energy and the
+    /// maximum torque after every step and judges the run from them.
+    pub fn minimize_energy_with_diagnostics<F: FnMut(usize, &Self) -> bool>(
+        &mut self,
+        mut observer: F,
+    ) -> ConvergenceDiagnostics {
+        let (mut energies, mut torques) = (Vec::new(), Vec::new());
+        let outcome = self.minimize_energy_until(|step, system| {
+            energies.push(system.compute_energies().total());
+            torques.push(system.compute_max_torque());
+            observer(step, system)
+        });
+        ConvergenceDiagnostics::diagnose(self, outcome, energies, torques)
+    }
+
+    ///# Energy Minimization with Stop Condition
+    /// Same as `minimize_energy_until`, stopping as soon as the condition
+    /// holds for the current state.
+    pub fn minimize_energy_until_condition(
+        &mut self,
+        condition: &mut dyn StopCondition,
+    ) -> MinimizationOutcome {
+        self.minimize_energy_until(|step, system| !condition.should_stop(step, 0.0, system))
+    }
+
+    ///# Stoppable Energy Minimization
+    /// Same as `minimize_energy_with`, but the minimization stops early
+    /// as soon as `observer` returns `false`. The steps are those of the
+    /// selected minimizer, and a minimization that reaches the iteration
+    /// limit is retried as set by the retry policy. The steps of the
+    /// retries count on from those before.
+    pub fn minimize_energy_until<F: FnMut(usize, &Self) -> bool>(
+        &mut self,
+        mut observer: F,
+    ) -> MinimizationOutcome {
+        let Some(mut history) = self.energy_history.take() else {
+            return self.minimize_energy_retried(observer);
+        };
+        history.clear();
+        let outcome = self.minimize_energy_retried(|step, system| {
+            history.push(system.compute_energies());
+            observer(step, system)
+        });
+        self.energy_history = Some(history);
+        outcome
+    }
+
+    // Minimization with the restarts of the retry policy
+    fn minimize_energy_retried<F: FnMut(usize, &Self) -> bool>(
+        &mut self,
+        mut observer: F,
+    ) -> MinimizationOutcome {
+        let mut outcome = self.minimize_energy_once(&mut observer);
+        let Some(policy) = self.retry_policy else {
+            return outcome;
+        };
+        let (minimizer, time_step, damping_schedule, adaptive_damping_weight) = (
+            self.minimizer,
+            self.time_step,
+            self.damping_schedule,
+            self.adaptive_damping_weight,
+        );
+        for attempt in 1..=policy.attempts {
+            let MinimizationOutcome::NotConverged { iterations: offset } = outcome else {
+                break;
+            };
+            if policy.switch_minimizer {
+                self.minimizer = match minimizer {
+                    Minimizer::Relaxation => Minimizer::SphericalConjugateGradient,
+                    Minimizer::SphericalConjugateGradient => Minimizer::Relaxation,
+                };
+            }
+            self.time_step = time_step * policy.time_step_factor.powi(attempt as i32);
+            if let Some(maximum) = policy.damping {
+                self.damping_schedule = DampingSchedule::Adaptive { maximum };
+            }
+            crate::console!(
+                "Retry {} of {} with the {:?} minimizer and a time step of {:e} s.",
+                attempt,
+                policy.attempts,
+                self.minimizer,
+                self.time_step
+            );
+            // The state at the restart has already been observed
+            outcome = self
+                .minimize_energy_once(|step, system| step == 0 || observer(offset + step, system))
+                .after(offset);
+        }
+        self.minimizer = minimizer;
+        self.time_step = time_step;
+        self.damping_schedule = damping_schedule;
+        self.adaptive_damping_weight = adaptive_damping_weight;
+        outcome
+    }
+
+    // One minimization up to the iteration limit
+    fn minimize_energy_once<F: FnMut(usize, &Self) -> bool>(
+        &mut self,
+        mut observer: F,
+    ) -> MinimizationOutcome {
+        if self.minimizer == Minimizer::SphericalConjugateGradient {
+            return minimize_spherical_until(self, observer);
+        }
+        if !observer(0, self) {
+            return MinimizationOutcome::Stopped { iterations: 0 };
+        }
+        self.relaxation_step_scale = 1.0;
+        let mut detector = OscillationDetector::new(&self.magnetizations);
+        let mut monitor = ConvergenceMonitor::new(self.convergence_policy);
         // Maximum number of iterations
-        for iter in 0..MAX_ITERATIONS_NUMBER {
+        for iter in 0..self.max_iterations {
             let max_change = self.relaxation_step();
-            if max_change < TOLERANCE {
-                println!("Converged after {} iterations.", iter);
-                return;
+            let keep_going = observer(iter + 1, self);
+            if monitor.converged(max_change, self) {
+                crate::console!(
+                    "Converged after {} iterations ({}).",
+                    iter + 1,
+                    monitor.policy
+                );
+                return MinimizationOutcome::Converged {
+                    iterations: iter + 1,
+                };
+            }
+            if !keep_going {
+                return MinimizationOutcome::Stopped {
+                    iterations: iter + 1,
+                };
+            }
+            if self.oscillation_policy == OscillationPolicy::Ignore
+                || !detector.update(&self.magnetizations, max_change)
+            {
+                continue;
+            }
+            if self.oscillation_policy == OscillationPolicy::ReduceStepSize
+                && self.relaxation_step_scale > MIN_RELAXATION_STEP_SCALE
+            {
+                self.relaxation_step_scale *= 0.5;
+                crate::console!(
+                    "Oscillation detected, reducing the relaxation step to {} of the time step.",
+                    self.relaxation_step_scale
+                );
+                detector.reset(&self.magnetizations);
+            } else if self.oscillation_policy == OscillationPolicy::SwitchMinimizer {
+                crate::console!(
+                    "Oscillation detected, switching to the spherical conjugate gradient."
+                );
+                // The state at the switch has already been observed
+                let offset = iter + 1;
+                return minimize_spherical_until(self, |step, system| {
+                    step == 0 || observer(offset + step, system)
+                })
+                .after(offset);
             }
         }
-        println!(
+        crate::console!(
             "Warning: Did not converge within {} iterations.",
-            MAX_ITERATIONS_NUMBER
+            self.max_iterations
         );
+        MinimizationOutcome::NotConverged {
+            iterations: self.max_iterations,
+        }
     }
 
     ///# Print Magnetizations
     pub fn print_magnetizations(&self) {
         for (i, m) in self.magnetizations.iter().enumerate() {
-            println!("Cell {}: m = {}", i, m);
+            crate::console!("Cell {}: m = {}", i, m);
         }
     }
 
@@ -246,11 +1862,179 @@ impl MicromagneticSystem {
     pub fn get_magnetizations(&self) -> Vec<Array1<f64>> {
         self.magnetizations.clone()
     }
+
+    ///# Set Magnetization
+    /// Set the direction of the magnetization of a single cell.
+    /// The vector is normalized, vacuum cells keep a zero magnetization.
+    pub fn set_magnetization(&mut self, cell: usize, magnetization: Array1<f64>) {
+        let norm = magnetization.dot(&magnetization).sqrt();
+        if self.is_vacuum(cell) || norm == 0.0 {
+            return;
+        }
+        self.magnetizations[cell] = magnetization / norm;
+    }
+
+    ///# Resample
+    /// Interpolate the state onto `new_size` cells over the same length, so
+    /// the cell size scales with `size / new_size` and cell j samples the
+    /// old chain at the same fraction of its length. The magnetization is
+    /// interpolated linearly between magnetic cells and renormalized, the
+    /// materials and regions are taken from the nearest cell, the interlayer
+    /// couplings join the facing cells of the ranges the coupled cells turn
+    /// into.
+    /// A relaxed coarse state is a good starting guess for a fine one. The
+    /// cells of a 2D or 3D grid have no common fraction of a length, so only
+    /// chains can be resampled.
+    pub fn resample(&self, new_size: usize) -> Result<Self, Box<dyn Error>> {
+        if !self.grid.is_chain() {
+            return Err(format!(
+                "Only chains can be resampled, not a {}x{}x{} grid",
+                self.grid.nx, self.grid.ny, self.grid.nz
+            )
+            .into());
+        }
+        let scale = self.size as f64 / new_size.max(1) as f64;
+        // Position of the new cell j in units of the old cells
+        let position =
+            |j: usize| ((j as f64 + 0.5) * scale - 0.5).clamp(0.0, self.size as f64 - 1.0);
+        let nearest = |j: usize| (position(j).round() as usize).min(self.size.saturating_sub(1));
+
+        let mut resampled = self.clone();
+        resampled.size = new_size;
+        // Local fields belong to the old cells
+        resampled.local_fields.clear();
+        if self.size == 0 || new_size == 0 {
+            resampled.magnetizations = vec![Array1::zeros(3); new_size];
+            resampled.materials = vec![Material::vacuum(); new_size];
+            resampled.regions = vec![0; new_size];
+            resampled.interlayer_couplings.clear();
+            resampled.neighbor_list = NeighborList::chain(new_size);
+            resampled.grid = Grid::chain(new_size);
+            resampled.update_demagnetization_kernel();
+            return Ok(resampled);
+        }
+        resampled.cell_size = self.cell_size * scale;
+        resampled.materials = (0..new_size).map(|j| self.materials[nearest(j)]).collect();
+        resampled.regions = (0..new_size).map(|j| self.regions[nearest(j)]).collect();
+        resampled.magnetizations = (0..new_size)
+            .map(|j| {
+                let closest = nearest(j);
+                if self.is_vacuum(closest) {
+                    return Array1::zeros(3);
+                }
+                let x = position(j);
+                let (left, right) = (x.floor() as usize, (x.ceil() as usize).min(self.size - 1));
+                // Only interpolate within a magnetic region
+                if self.is_vacuum(left) || self.is_vacuum(right) {
+                    return self.magnetizations[closest].clone();
+                }
+                let weight = x - left as f64;
+                let m = &self.magnetizations[left] * (1.0 - weight)
+                    + &self.magnetizations[right] * weight;
+                let norm = m.dot(&m).sqrt();
+                // Exactly antiparallel neighbors have no interpolated direction
+                if norm < 1e-12 {
+                    self.magnetizations[closest].clone()
+                } else {
+                    m / norm
+                }
+            })
+            .collect();
+        // New cells covered by the old cell i
+        let first_new_cell = |i: usize| ((i as f64 / scale).ceil() as usize).min(new_size - 1);
+        let last_new_cell = |i: usize| {
+            ((((i + 1) as f64 / scale).ceil() as usize).saturating_sub(1))
+                .clamp(first_new_cell(i), new_size - 1)
+        };
+        resampled.interlayer_couplings = self
+            .interlayer_couplings
+            .iter()
+            .map(|coupling| {
+                // Keep the coupled faces of the two cells next to each other
+                let (first, second) = if coupling.first < coupling.second {
+                    (
+                        last_new_cell(coupling.first),
+                        first_new_cell(coupling.second),
+                    )
+                } else {
+                    (
+                        first_new_cell(coupling.first),
+                        last_new_cell(coupling.second),
+                    )
+                };
+                InterlayerCoupling {
+                    first,
+                    second,
+                    coupling: coupling.coupling,
+                    biquadratic: coupling.biquadratic,
+                }
+            })
+            .filter(|coupling| coupling.first != coupling.second)
+            .collect();
+        // Consecutive new cells stay coupled when the chain between the old
+        // cells they sample is, other edges such as the one closing a ring
+        // join the outer new cells of the two old cells
+        let old = &self.neighbor_list;
+        let connected = |a: usize, b: usize| (a..b).all(|k| old.neighbors(k).contains(&(k + 1)));
+        let mut edges: Vec<(usize, usize)> = (1..new_size)
+            .filter(|&j| connected(nearest(j - 1), nearest(j)))
+            .map(|j| (j - 1, j))
+            .collect();
+        edges.extend(
+            old.edges()
+                .filter(|&(a, b)| b > a + 1)
+                .map(|(a, b)| (first_new_cell(a), last_new_cell(b)))
+                .filter(|&(a, b)| a != b),
+        );
+        resampled.neighbor_list =
+            NeighborList::from_edges(new_size, &edges).expect("the edges join new cells");
+        resampled.grid = Grid::chain(new_size);
+        resampled.update_demagnetization_kernel();
+        Ok(resampled)
+    }
+
+    // Whether the cells a and b around their common neighbor middle couple
+    // by the second neighbor exchange, any path on a chain with its own
+    // connectivity and only the straight ones on a grid
+    fn second_neighbors(&self, a: usize, middle: usize, b: usize) -> bool {
+        self.grid.is_chain() || self.grid.in_line(a, middle, b)
+    }
+
+    ///# Set Rotated Magnetization
+    /// Set a magnetization that is already of unit length, such as the
+    /// result of a rotation, without renormalizing it.
+    pub(crate) fn set_rotated_magnetization(&mut self, cell: usize, magnetization: [f64; 3]) {
+        if self.is_vacuum(cell) {
+            return;
+        }
+        self.magnetizations[cell] = Array1::from_vec(magnetization.to_vec());
+    }
+
+    ///# Size
+    /// Number of cells in the system
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+///# Maximum Component Difference
+/// Largest absolute difference of a magnetization component between two
+/// states of the same system.
+fn max_component_difference(first: &[Array1<f64>], second: &[Array1<f64>]) -> f64 {
+    first
+        .iter()
+        .zip(second)
+        .map(|(a, b)| (a - b).iter().map(|x| x.abs()).fold(0.0, f64::max))
+        .fold(0.0, f64::max)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        CELL_VOLUME, EXTERNAL_FIELD, MAGNETIC_EXCHANGE_CONSTANT, SATURATION_MAGNETIZATION,
+        SPATIAL_DISCRETION_STEP, UNIAXIAL_ANISOTROPY_CONSTANT,
+    };
 
     #[test]
     /// Test the initialization of the MicromagneticSystem
@@ -258,11 +2042,10 @@ mod tests {
         let size = 10;
         let system = MicromagneticSystem::new(size);
         assert_eq!(system.size, size);
+        // The initial magnetizations are random but normalized
         for m in &system.magnetizations {
             assert_eq!(m.len(), 3);
-            assert!((m[0] - (2.0 * std::f64::consts::PI / size as f64).sin()).abs() < f64::EPSILON);
-            assert!((m[1] - (2.0 * std::f64::consts::PI / size as f64).cos()).abs() < f64::EPSILON);
-            assert!((m[2] - (std::f64::consts::PI / size as f64).sin()).abs() < f64::EPSILON);
+            assert!((m.dot(m).sqrt() - 1.0).abs() < 1e-12);
         }
     }
 
@@ -276,7 +2059,8 @@ mod tests {
         // Check if the effective field is calculated correctly
         // This is a simple check, more detailed checks can be added
         for h in &h_eff {
-            assert!(h.iter().all(|&x| x.abs() > 0.0));
+            assert!(h.iter().all(|&x| x.is_finite()));
+            assert!(h.dot(h) > 0.0);
         }
     }
 
@@ -289,7 +2073,7 @@ mod tests {
         assert!(max_change > 0.0);
         // Check if the magnetization values are clamped between -1 and 1
         for m in &system.magnetizations {
-            assert!(m.iter().all(|&x| x >= -1.0 && x <= 1.0));
+            assert!(m.iter().all(|&x| (-1.0..=1.0).contains(&x)));
         }
     }
 
@@ -304,6 +2088,913 @@ mod tests {
         assert!(max_change < TOLERANCE);
     }
 
+    #[test]
+    /// Test that the adaptive time step relaxes a tilted chain in fewer steps
+    fn test_adaptive_time_step() {
+        let size = 10;
+        let relax = |adaptive_time_step: Option<AdaptiveTimeStep>| {
+            let parameters = SimulationParameters::default()
+                .with_applied_field([0.5, 0.0, 0.0])
+                .with_adaptive_time_step(adaptive_time_step);
+            let mut system = MicromagneticSystem::with_parameters(size, parameters);
+            for i in 0..size {
+                let angle = 1.2 + 0.05 * i as f64;
+                system.set_magnetization(i, array![angle.cos(), angle.sin(), 0.0]);
+            }
+            (system.minimize_energy_until(|_, _| true), system)
+        };
+        let (fixed, fixed_system) = relax(None);
+        let adaptive_time_step = AdaptiveTimeStep {
+            tolerance: 1e-2,
+            min_step: 1e-15,
+            max_step: 1e-11,
+        };
+        let (adaptive, adaptive_system) = relax(Some(adaptive_time_step));
+        let (
+            MinimizationOutcome::Converged { iterations: n },
+            MinimizationOutcome::Converged { iterations: k },
+        ) = (fixed, adaptive)
+        else {
+            panic!("both relaxations converge");
+        };
+        assert!(k < n);
+        let (e, f) = (
+            fixed_system.compute_energies().total(),
+            adaptive_system.compute_energies().total(),
+        );
+        assert!((e - f).abs() < 1e-6 * e.abs());
+        assert!(adaptive_system.get_time_step() > crate::TIME_STEP);
+
+        // A step larger than the error allows is rejected and shrunk
+        let (accepted, next) = adaptive_time_step.control(1e-12, 1e-2, 1e-2, 1);
+        assert!(!accepted && next < 1e-12);
+        let (accepted, next) = adaptive_time_step.control(1e-12, 1e-8, 1e-2, 1);
+        assert!(accepted && next == 5e-12);
+    }
+
+    #[test]
+    /// Test that vacuum cells carry no field and stay zero during relaxation
+    fn test_vacuum_cells() {
+        let size = 10;
+        let mut system = MicromagneticSystem::new(size);
+        system.set_saturation_magnetization(4, 0.0);
+        system.set_saturation_magnetization(5, 0.0);
+        let h_eff = system.compute_effective_field();
+        assert!(h_eff[4].iter().all(|&x| x == 0.0));
+        system.minimize_energy();
+        for (i, m) in system.get_magnetizations().iter().enumerate() {
+            assert!(m.iter().all(|&x| x.is_finite()));
+            if i == 4 || i == 5 {
+                assert!(m.iter().all(|&x| x == 0.0));
+            } else {
+                assert!((m.dot(m).sqrt() - 1.0).abs() < 1e-12);
+            }
+        }
+        assert!(system.compute_magnetic_energy_density().is_finite());
+    }
+
+    #[test]
+    /// Test that a vacuum neighbor acts as a free surface for the exchange field
+    fn test_vacuum_neighbor_exchange() {
+        let size = 5;
+        let mut system = MicromagneticSystem::new(size);
+        for i in 0..size {
+            system.magnetizations[i] = array![0.0, 0.0, 1.0];
+        }
+        system.set_saturation_magnetization(3, 0.0);
+        let h_eff = system.compute_effective_field();
+        // Cell 2 sees only the aligned cell 1, so no exchange field remains
+        let zeeman_z = EXTERNAL_FIELD[2] / PERMEABILITY_OF_FREE_SPACE;
+        assert!((h_eff[2][2] - zeeman_z).abs() < 1e-9 * zeeman_z);
+    }
+
+    #[test]
+    /// Test the energy minimization with the red-black update ordering
+    fn test_minimize_energy_red_black() {
+        let size = 10;
+        let mut system = MicromagneticSystem::new(size);
+        system.set_update_scheme(UpdateScheme::RedBlack);
+        system.minimize_energy();
+        let max_change = system.relaxation_step();
+        assert!(max_change < TOLERANCE);
+        for m in &system.magnetizations {
+            assert!((m.dot(m).sqrt() - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    /// Test that the adaptive damping schedule relaxes to the same state in fewer steps
+    fn test_adaptive_damping() {
+        let initial: Vec<Array1<f64>> = (0..10)
+            .map(|i| {
+                let (a, b) = (i as f64 * 2.3, i as f64 * 1.1);
+                array![a.cos() * b.sin(), a.sin() * b.sin(), b.cos()]
+            })
+            .collect();
+        let mut material = MicromagneticSystem::from_magnetizations(initial.clone());
+        let mut adaptive = MicromagneticSystem::from_magnetizations(initial);
+        adaptive.set_damping_schedule(DampingSchedule::Adaptive { maximum: 1.0 });
+
+        let MinimizationOutcome::Converged { iterations: slow } =
+            material.minimize_energy_until(|_, _| true)
+        else {
+            panic!("material damping did not converge");
+        };
+        let MinimizationOutcome::Converged { iterations: fast } =
+            adaptive.minimize_energy_until(|_, _| true)
+        else {
+            panic!("adaptive damping did not converge");
+        };
+        assert!(fast < slow / 2 + slow / 4);
+        let (e_slow, e_fast) = (
+            material.compute_energies().total(),
+            adaptive.compute_energies().total(),
+        );
+        assert!((e_slow - e_fast).abs() < 1e-6 * e_slow.abs());
+    }
+
+    #[test]
+    /// Test that an oscillating relaxation of a hard, strongly damped chain converges
+    fn test_oscillation_policy() {
+        let hard = Material {
+            anisotropy_constant: 1e8,
+            damping: 1.0,
+            ..Material::default()
+        };
+        let oscillating = || {
+            let mut system =
+                MicromagneticSystem::from_magnetizations(vec![array![0.6, 0.3, 0.5]; 3]);
+            for cell in 0..3 {
+                system.set_material(cell, hard);
+            }
+            system
+        };
+
+        let mut ignored = oscillating();
+        ignored.set_oscillation_policy(OscillationPolicy::Ignore);
+        assert!(matches!(
+            ignored.minimize_energy_until(|_, _| true),
+            MinimizationOutcome::NotConverged { .. }
+        ));
+        for policy in [
+            OscillationPolicy::ReduceStepSize,
+            OscillationPolicy::SwitchMinimizer,
+        ] {
+            let mut system = oscillating();
+            system.set_oscillation_policy(policy);
+            let mut observed = 0;
+            let outcome = system.minimize_energy_until(|iteration, _| {
+                assert_eq!(iteration, observed);
+                observed += 1;
+                true
+            });
+            assert!(matches!(outcome, MinimizationOutcome::Converged { .. }));
+            let axis = Array1::from_vec(hard.easy_axis.to_vec());
+            assert!(system
+                .get_magnetizations()
+                .iter()
+                .all(|m| m.dot(&axis).abs() > 0.999));
+        }
+    }
+
+    #[test]
+    /// Test that the recorded energy terms add up and never rise during a
+    /// relaxation
+    fn test_energy_history() {
+        let mut system = MicromagneticSystem::from_magnetizations(vec![
+            array![1.0, 0.2, 0.1],
+            array![0.3, 1.0, 0.0],
+            array![-0.2, 0.4, 1.0],
+            array![0.0, -1.0, 0.5],
+        ]);
+        system.set_applied_field([0.0, 0.1, 0.2]);
+        system.minimize_energy();
+        assert!(system.energy_history().is_empty());
+
+        system.set_magnetization(1, array![0.0, 0.0, -1.0]);
+        system.record_energy_history(true);
+        let mut observed = 0;
+        system.minimize_energy_with(|_, _| observed += 1);
+        let history = system.energy_history();
+        assert_eq!(history.len(), observed);
+        assert!(history.len() > 2);
+        assert_eq!(history[history.len() - 1], system.compute_energies());
+        for energies in history {
+            let sum = energies.exchange + energies.anisotropy + energies.zeeman;
+            assert!((energies.total() - sum).abs() <= 1e-12 * sum.abs());
+        }
+        assert!(history[0].total() > history[history.len() - 1].total());
+        assert_eq!(system.energy_rises(), Vec::<usize>::new());
+
+        // A new minimization starts a new history
+        system.minimize_energy();
+        assert_eq!(system.energy_history().len(), 2);
+        system.record_energy_history(false);
+        assert!(system.energy_history().is_empty());
+    }
+
+    #[test]
+    /// Test that a retry with the other minimizer restores the settings and
+    /// converges where the relaxation gives up
+    fn test_retry_policy() {
+        let hard = Material {
+            anisotropy_constant: 1e8,
+            damping: 1.0,
+            ..Material::default()
+        };
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![0.6, 0.3, 0.5]; 3]);
+        for cell in 0..3 {
+            system.set_material(cell, hard);
+        }
+        system.set_oscillation_policy(OscillationPolicy::Ignore);
+        system.set_max_iterations(1000);
+        let policy = RetryPolicy {
+            attempts: 2,
+            switch_minimizer: true,
+            time_step_factor: 0.5,
+            damping: None,
+        };
+        assert!(RetryPolicy {
+            time_step_factor: 0.0,
+            ..policy
+        }
+        .validate()
+        .is_err());
+        system.set_retry_policy(Some(policy));
+        let time_step = system.get_time_step();
+        let mut observed = 0;
+        let outcome = system.minimize_energy_until(|iteration, _| {
+            assert_eq!(iteration, observed);
+            observed += 1;
+            true
+        });
+        match outcome {
+            MinimizationOutcome::Converged { iterations } => {
+                assert!(iterations > 1000);
+                assert_eq!(iterations + 1, observed);
+            }
+            _ => panic!("the retry did not converge: {:?}", outcome),
+        }
+        assert_eq!(system.get_minimizer(), Minimizer::Relaxation);
+        assert_eq!(system.get_time_step(), time_step);
+        let axis = Array1::from_vec(hard.easy_axis.to_vec());
+        assert!(system
+            .get_magnetizations()
+            .iter()
+            .all(|m| m.dot(&axis).abs() > 0.999));
+    }
+
+    #[test]
+    /// Test that the exchange field follows the energy on a ring with a
+    /// junction and that a ring survives resampling
+    fn test_neighbor_list_exchange() {
+        let size = 8;
+        let mut system = MicromagneticSystem::new(size);
+        system.set_applied_field([0.0; 3]);
+        for i in 0..size {
+            let mut material = system.get_materials()[i];
+            material.second_neighbor_exchange_constant = -0.2 * MAGNETIC_EXCHANGE_CONSTANT;
+            system.set_material(i, material);
+        }
+        // Ring 0..=6 with a branch to cell 7 at cell 6 and a shortcut 2-5
+        let mut edges: Vec<(usize, usize)> = (1..7).map(|i| (i - 1, i)).collect();
+        edges.extend([(0, 6), (6, 7), (2, 5)]);
+        system
+            .set_neighbor_list(NeighborList::from_edges(size, &edges).unwrap())
+            .unwrap();
+        assert!(system
+            .set_neighbor_list(NeighborList::ring(size + 1))
+            .is_err());
+
+        let field = system.compute_effective_field();
+        let epsilon = 1e-6;
+        for i in 0..size {
+            let m = system.get_magnetizations()[i].clone();
+            let tangent = array![m[1], -m[0], 0.0];
+            let energy = |sign: f64| {
+                let mut probe = system.clone();
+                probe.set_magnetization(i, &m + &(sign * epsilon * &tangent));
+                probe.compute_energies().total()
+            };
+            let derivative = (energy(1.0) - energy(-1.0)) / (2.0 * epsilon);
+            let expected = -PERMEABILITY_OF_FREE_SPACE
+                * SATURATION_MAGNETIZATION
+                * CELL_VOLUME
+                * field[i].dot(&tangent);
+            assert!(
+                (derivative - expected).abs() < 1e-6 * expected.abs().max(1e-24),
+                "cell {}",
+                i
+            );
+        }
+
+        // A uniform winding costs the same exchange in every cell of a ring
+        let mut ring = MicromagneticSystem::new(size);
+        ring.set_neighbor_list(NeighborList::ring(size)).unwrap();
+        for i in 0..size {
+            let phi = 2.0 * std::f64::consts::PI * i as f64 / size as f64;
+            ring.set_magnetization(i, array![phi.cos(), phi.sin(), 0.0]);
+        }
+        let stiffness = 2.0 * (1.0 - (2.0 * std::f64::consts::PI / size as f64).cos());
+        let exchange = size as f64 * MAGNETIC_EXCHANGE_CONSTANT * stiffness * CELL_VOLUME
+            / (SPATIAL_DISCRETION_STEP * SPATIAL_DISCRETION_STEP);
+        let energies = ring.compute_energies();
+        assert!((energies.exchange - exchange).abs() < 1e-9 * exchange);
+        let fine = ring.resample(2 * size).unwrap();
+        assert_eq!(fine.get_neighbor_list(), &NeighborList::ring(2 * size));
+    }
+
+    #[test]
+    /// Test the exchange stencil of a 3D grid, its red-black relaxation and
+    /// the OVF round trip of the grid
+    fn test_grid_exchange() {
+        let grid = Grid::new(3, 3, 3);
+        let parameters = SimulationParameters::default().with_applied_field([0.0; 3]);
+        let mut system = MicromagneticSystem::on_grid(grid, parameters);
+        for i in 0..grid.size() {
+            system.set_magnetization(i, array![1.0, 0.0, 0.0]);
+        }
+        let center = grid.index(1, 1, 1);
+        assert_eq!(system.get_neighbor_list().neighbors(center).len(), 6);
+        assert!(system.set_grid(Grid::new(3, 3, 2)).is_err());
+        assert_eq!(system.get_grid(), grid);
+        system.set_magnetization(center, array![0.0, 1.0, 0.0]);
+
+        // 2 A / (mu0 Ms dx^2) from each of the six face neighbors along x
+        let exchange = 2.0 * MAGNETIC_EXCHANGE_CONSTANT
+            / (PERMEABILITY_OF_FREE_SPACE
+                * SATURATION_MAGNETIZATION
+                * SPATIAL_DISCRETION_STEP
+                * SPATIAL_DISCRETION_STEP);
+        let field = system.compute_effective_field();
+        assert!((field[center][0] - 6.0 * exchange).abs() < 1e-9 * exchange);
+        // A V |m_i - m_j|^2 / dx^2 of the six bonds at 90 degrees
+        let energy = 12.0 * MAGNETIC_EXCHANGE_CONSTANT * CELL_VOLUME
+            / (SPATIAL_DISCRETION_STEP * SPATIAL_DISCRETION_STEP);
+        let exchange_energy = system.compute_energies().exchange;
+        assert!((exchange_energy - energy).abs() < 1e-9 * energy);
+
+        // A fixed canted start, a random one can stall in a twisted state
+        let mut relaxed = MicromagneticSystem::on_grid(grid, parameters);
+        for i in 0..grid.size() {
+            let angle = 0.3 * i as f64;
+            relaxed.set_magnetization(i, array![1.0, 0.5 * angle.sin(), 0.5 * angle.cos()]);
+        }
+        relaxed.set_update_scheme(UpdateScheme::RedBlack);
+        relaxed.minimize_energy();
+        let m = relaxed.average_magnetization();
+        assert!(m[0].abs() > 0.999);
+
+        let path = std::env::temp_dir().join("energy_relaxation_grid_test.ovf");
+        relaxed.save_ovf(&path).unwrap();
+        let restored = MicromagneticSystem::from_ovf(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored.get_grid(), grid);
+        assert_eq!(restored.get_neighbor_list(), &grid.neighbor_list());
+    }
+
+    #[test]
+    /// Test that a larger cell weakens the exchange field with 1 / dx^2 and
+    /// scales the exchange energy with dx and the anisotropy energy with dx^3
+    fn test_cell_size() {
+        let grid = Grid::new(3, 3, 3);
+        let twisted = |cell_size: f64| {
+            let parameters = SimulationParameters::default()
+                .with_applied_field([0.0; 3])
+                .with_cell_size(cell_size);
+            let mut system = MicromagneticSystem::on_grid(grid, parameters);
+            for i in 0..grid.size() {
+                system.set_magnetization(i, array![1.0, 0.0, 0.0]);
+            }
+            system.set_magnetization(grid.index(1, 1, 1), array![0.0, 1.0, 0.0]);
+            system
+        };
+        let fine = twisted(SPATIAL_DISCRETION_STEP);
+        let coarse = twisted(2.0 * SPATIAL_DISCRETION_STEP);
+        assert_eq!(coarse.get_cell_size(), 2.0 * SPATIAL_DISCRETION_STEP);
+        assert_eq!(coarse.cell_volume(), 8.0 * CELL_VOLUME);
+
+        let center = grid.index(1, 1, 1);
+        let field = fine.compute_effective_field()[center][0];
+        let coarse_field = coarse.compute_effective_field()[center][0];
+        assert!((coarse_field - 0.25 * field).abs() < 1e-9 * field.abs());
+        let (energies, coarse_energies) = (fine.compute_energies(), coarse.compute_energies());
+        assert!(
+            (coarse_energies.exchange - 2.0 * energies.exchange).abs() < 1e-9 * energies.exchange
+        );
+        assert!(
+            (coarse_energies.anisotropy - 8.0 * energies.anisotropy).abs()
+                < 1e-9 * energies.anisotropy.abs()
+        );
+    }
+
+    #[test]
+    /// Test the spin spiral of a frustrated chain with cos q = -A / (4 A2)
+    fn test_second_neighbor_exchange() {
+        let frustrated = Material {
+            anisotropy_constant: 0.0,
+            second_neighbor_exchange_constant: -0.5 * MAGNETIC_EXCHANGE_CONSTANT,
+            damping: 1.0,
+            ..Material::default()
+        };
+        let cells = 30;
+        let mut system = MicromagneticSystem::from_magnetizations(
+            (0..cells)
+                .map(|i| {
+                    let angle = 1.0 * i as f64;
+                    array![angle.cos(), angle.sin(), 0.01]
+                })
+                .collect(),
+        );
+        system.set_applied_field([0.0; 3]);
+        for cell in 0..cells {
+            system.set_material(cell, frustrated);
+        }
+        let initial_energy = system.compute_energies().exchange;
+        system.set_minimizer(Minimizer::SphericalConjugateGradient);
+        system.minimize_energy();
+        assert!(system.compute_energies().exchange < initial_energy);
+
+        // Angle between neighbors away from the free ends
+        let expected = (0.5_f64).acos();
+        let magnetizations = system.get_magnetizations();
+        for i in 10..20 {
+            let angle = magnetizations[i]
+                .dot(&magnetizations[i + 1])
+                .clamp(-1.0, 1.0)
+                .acos();
+            assert!((angle - expected).abs() < 0.02, "cell {}: {}", i, angle);
+        }
+    }
+
+    #[test]
+    /// Test that the second neighbor exchange of a film couples the cells
+    /// two apart along the axes and not across the face diagonals
+    fn test_grid_second_neighbor_exchange() {
+        let grid = Grid::new(3, 3, 1);
+        let second_neighbor_exchange_constant = 0.5 * MAGNETIC_EXCHANGE_CONSTANT;
+        let film = |second_neighbor_exchange_constant: f64| {
+            let material = Material {
+                second_neighbor_exchange_constant,
+                ..Material::default()
+            };
+            let parameters = SimulationParameters::default()
+                .with_material(material)
+                .with_applied_field([0.0; 3]);
+            let mut system = MicromagneticSystem::on_grid(grid, parameters);
+            for i in 0..grid.size() {
+                system.set_magnetization(i, array![1.0, 0.0, 0.0]);
+            }
+            system.set_magnetization(0, array![0.0, 1.0, 0.0]);
+            system
+        };
+        let (coupled, plain) = (film(second_neighbor_exchange_constant), film(0.0));
+
+        // The corner couples to the cells 2 and 6, |m_0 - m_j|^2 = 2 each,
+        // A2 V |m_0 - m_j|^2 / dx^2 = 4 A2 dx in total
+        let expected = 4.0 * second_neighbor_exchange_constant * SPATIAL_DISCRETION_STEP;
+        let energy = coupled.compute_energies().exchange - plain.compute_energies().exchange;
+        assert!((energy - expected).abs() < 1e-9 * expected);
+
+        // 2 A2 / (mu0 Ms dx^2) (m_2 + m_6 - 2 m_0) on the corner
+        let coefficient = 2.0 * second_neighbor_exchange_constant
+            / (PERMEABILITY_OF_FREE_SPACE
+                * SATURATION_MAGNETIZATION
+                * SPATIAL_DISCRETION_STEP
+                * SPATIAL_DISCRETION_STEP);
+        let field = &coupled.compute_effective_field()[0] - &plain.compute_effective_field()[0];
+        let expected = array![2.0, -2.0, 0.0] * coefficient;
+        assert!((&field - &expected).mapv(f64::abs).sum() < 1e-9 * coefficient);
+    }
+
+    #[test]
+    /// Test the Neel state and the spin flop of an antiferromagnetic chain
+    fn test_antiferromagnetic_chain() {
+        // B_E = 4|A|/(Ms dx^2) = 0.4 T and B_K = 2K/Ms = 0.02 T give the
+        // spin flop field sqrt(B_K (2 B_E - B_K)) of about 0.12 T
+        let antiferromagnet = Material {
+            exchange_constant: -1e-13,
+            saturation_magnetization: 1e6,
+            anisotropy_constant: 1e4,
+            damping: 1.0,
+            ..Material::default()
+        };
+        let axis = antiferromagnet.easy_axis;
+        let cells = 20;
+        let neel = |field: f64| {
+            let mut system = MicromagneticSystem::from_magnetizations(
+                (0..cells)
+                    .map(|i| {
+                        let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+                        array![sign * axis[0] + 0.05, sign * axis[1] + 0.05, sign * axis[2]]
+                    })
+                    .collect(),
+            );
+            for cell in 0..cells {
+                system.set_material(cell, antiferromagnet);
+            }
+            system.set_applied_field([field * axis[0], field * axis[1], field * axis[2]]);
+            system.set_minimizer(Minimizer::SphericalConjugateGradient);
+            assert!(matches!(
+                system.minimize_energy_until(|_, _| true),
+                MinimizationOutcome::Converged { .. }
+            ));
+            let staggered = system.staggered_magnetization();
+            (0..3).map(|k| staggered[k] * axis[k]).sum::<f64>().abs()
+        };
+        assert!(neel(0.0) > 0.999);
+        assert!(neel(0.05) > 0.99);
+        assert!(neel(0.3) < 0.01);
+    }
+
+    #[test]
+    /// Test that the shape anisotropy of a thin film pulls the magnetization into the plane
+    fn test_demagnetization_factors() {
+        let factors = crate::dipolar::prism_demagnetization_factors([100e-9, 100e-9, 1e-9]);
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![0.1, 0.0, 1.0]; 5]);
+        for cell in 0..5 {
+            system.set_material(
+                cell,
+                Material {
+                    anisotropy_constant: 0.0,
+                    damping: 1.0,
+                    ..Material::default()
+                },
+            );
+        }
+        system.set_applied_field([0.0; 3]);
+        system.set_demagnetization_factors(factors);
+        let perpendicular = system.compute_energies().dipolar;
+        system.minimize_energy();
+        let in_plane = system.compute_energies().dipolar;
+        // Nx / Nz of the in-plane state
+        assert!(in_plane < (factors[0] / factors[2] + 1e-3) * perpendicular);
+        assert!(system
+            .get_magnetizations()
+            .iter()
+            .all(|m| m[2].abs() < 1e-2));
+    }
+
+    #[test]
+    /// Test resampling a domain wall onto a finer and a coarser mesh
+    fn test_resample() {
+        let angle = |x: f64| std::f64::consts::PI * x;
+        let wall = |cells: usize| {
+            (0..cells)
+                .map(|i| {
+                    let x = (i as f64 + 0.5) / cells as f64;
+                    array![angle(x).cos(), angle(x).sin(), 0.0]
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut system = MicromagneticSystem::from_magnetizations(wall(20));
+        system.set_saturation_magnetization(19, 0.0);
+        system.add_interlayer_coupling(9, 11, -1e-3);
+
+        let fine = system.resample(40).unwrap();
+        assert_eq!(fine.size(), 40);
+        assert_eq!(fine.get_cell_size(), 0.5 * system.get_cell_size());
+        let expected = wall(40);
+        for (i, m) in fine
+            .get_magnetizations()
+            .iter()
+            .enumerate()
+            .skip(1)
+            .take(36)
+        {
+            assert!((m.dot(m) - 1.0).abs() < 1e-12);
+            assert!(m.dot(&expected[i]) > 0.999, "cell {}", i);
+        }
+        assert!(fine.is_vacuum(38) && fine.is_vacuum(39) && !fine.is_vacuum(37));
+        let coupling = fine.get_interlayer_couplings()[0];
+        assert_eq!((coupling.first, coupling.second), (19, 22));
+
+        let coarse = system.resample(10).unwrap();
+        assert_eq!(coarse.get_cell_size(), 2.0 * system.get_cell_size());
+        assert!(coarse.is_vacuum(9) && !coarse.is_vacuum(8));
+        assert!(coarse.get_magnetizations()[4].dot(&wall(10)[4]) > 0.999);
+    }
+
+    #[test]
+    /// Test that a 2D grid is not resampled as if its cells were a chain
+    fn test_resample_grid() {
+        let grid = Grid::new(4, 3, 1);
+        let system = MicromagneticSystem::on_grid(grid, SimulationParameters::default());
+        let error = system.resample(24).unwrap_err();
+        assert!(error.to_string().contains("4x3x1"));
+        assert_eq!(system.get_grid(), grid);
+    }
+
+    #[test]
+    /// Test initializing the system from given magnetizations
+    fn test_from_magnetizations() {
+        let system = MicromagneticSystem::from_magnetizations(vec![
+            array![2.0, 0.0, 0.0],
+            array![0.0, 0.0, 0.0],
+            array![0.0, 3.0, 4.0],
+        ]);
+        assert_eq!(system.size, 3);
+        assert!(system.is_vacuum(1));
+        assert_eq!(system.magnetizations[0], array![1.0, 0.0, 0.0]);
+        assert_eq!(system.magnetizations[2], array![0.0, 0.6, 0.8]);
+    }
+
+    #[test]
+    /// Test that a saved state is read back with its vacuum cells
+    fn test_save_ovf() {
+        let system = MicromagneticSystem::from_magnetizations(vec![
+            array![0.0, 0.6, 0.8],
+            array![0.0, 0.0, 0.0],
+            array![1.0, 0.0, 0.0],
+        ]);
+        let path = std::env::temp_dir().join("energy_relaxation_state_test.ovf");
+        system.save_ovf(&path).unwrap();
+        let loaded = MicromagneticSystem::from_ovf(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.get_magnetizations(), system.get_magnetizations());
+        assert!(loaded.is_vacuum(1));
+    }
+
+    #[test]
+    /// Test the energy contributions of a uniform state along the easy axis
+    fn test_energies() {
+        let size = 4;
+        let mut system = MicromagneticSystem::new(size);
+        for i in 0..size {
+            system.magnetizations[i] = array![1.0, 0.0, 0.0];
+        }
+        let energies = system.compute_energies();
+        assert_eq!(energies.exchange, 0.0);
+        assert_eq!(energies.zeeman, 0.0);
+        let expected = -UNIAXIAL_ANISOTROPY_CONSTANT * CELL_VOLUME * size as f64;
+        assert!((energies.anisotropy - expected).abs() < 1e-12 * expected.abs());
+        assert!(
+            (system.compute_magnetic_energy_density() + UNIAXIAL_ANISOTROPY_CONSTANT).abs() < 1e-6
+        );
+        assert_eq!(system.average_magnetization(), [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    /// Test the analytic energy gradient against finite differences
+    fn test_energy_gradient() {
+        let mut system = MicromagneticSystem::new(5);
+        system.set_material(
+            3,
+            Material {
+                exchange_constant: 0.4 * MAGNETIC_EXCHANGE_CONSTANT,
+                anisotropy_constant: 2.0 * UNIAXIAL_ANISOTROPY_CONSTANT,
+                ..Material::default()
+            },
+        );
+        system.set_field_gradient([1.0e6, 0.0, 0.0]);
+        let gradient = system.compute_energy_gradient();
+        let energy = system.compute_energies().total();
+
+        let relative = 1e-6;
+        for cell in 0..5 {
+            let material = system.get_materials()[cell];
+            let mut perturbed = system.clone();
+            perturbed.set_material(
+                cell,
+                Material {
+                    exchange_constant: material.exchange_constant * (1.0 + relative),
+                    ..material
+                },
+            );
+            let step = material.exchange_constant * relative;
+            let numeric = (perturbed.compute_energies().total() - energy) / step;
+            let analytic = gradient.exchange_constant[cell];
+            assert!((numeric - analytic).abs() < 1e-4 * analytic.abs() + 1e-30);
+
+            let mut perturbed = system.clone();
+            let step = material.anisotropy_constant * relative;
+            perturbed.set_material(
+                cell,
+                Material {
+                    anisotropy_constant: material.anisotropy_constant + step,
+                    ..material
+                },
+            );
+            let numeric = (perturbed.compute_energies().total() - energy) / step;
+            let analytic = gradient.anisotropy_constant[cell];
+            assert!((numeric - analytic).abs() < 1e-6 * analytic.abs() + 1e-30);
+        }
+
+        // The energy is linear in the field, so a large step is exact
+        let mut field = system.get_applied_field();
+        field[1] += 0.1;
+        let mut perturbed = system.clone();
+        perturbed.set_applied_field(field);
+        let numeric = (perturbed.compute_energies().total() - energy) / 0.1;
+        assert!((numeric - gradient.applied_field[1]).abs() < 1e-9 * numeric.abs());
+        let total = gradient.total_anisotropy_constant();
+        assert!((-5.0 * CELL_VOLUME..0.0).contains(&total));
+    }
+
+    #[test]
+    /// Test that the exchange field of every boundary condition is the
+    /// gradient of its energy and that periodic faces couple
+    fn test_boundary_conditions() {
+        let size = 10;
+        let twisted: Vec<Array1<f64>> = (0..size)
+            .map(|i| {
+                let angle = 0.3 * i as f64;
+                array![angle.cos(), angle.sin(), 0.2]
+            })
+            .collect();
+        let mut free = MicromagneticSystem::from_magnetizations(twisted);
+        free.set_applied_field([0.0; 3]);
+        let mut periodic = free.clone();
+        periodic.set_boundary_condition(BoundaryCondition::Periodic);
+        assert_eq!(periodic.get_neighbor_list(), &NeighborList::ring(size));
+        let different = &free.magnetizations[size - 1] - &free.magnetizations[0];
+        let bond = MAGNETIC_EXCHANGE_CONSTANT * different.dot(&different)
+            / (SPATIAL_DISCRETION_STEP * SPATIAL_DISCRETION_STEP)
+            * CELL_VOLUME;
+        let wrap = periodic.compute_energies().exchange - free.compute_energies().exchange;
+        assert!((wrap - bond).abs() < 1e-9 * bond);
+
+        let mut fixed = free.clone();
+        fixed.set_boundary_condition(BoundaryCondition::Fixed {
+            direction: [0.0, 0.0, 2.0],
+        });
+        assert_eq!(fixed.get_neighbor_list(), free.get_neighbor_list());
+        let epsilon = 1e-6;
+        for system in [&free, &periodic, &fixed] {
+            for cell in [0, 4, size - 1] {
+                let field = system.compute_effective_field_at(cell);
+                let scale = field.dot(&field).sqrt();
+                for k in 0..3 {
+                    let energy = |shift: f64| {
+                        let mut perturbed = system.clone();
+                        perturbed.magnetizations[cell][k] += shift;
+                        perturbed.compute_energies().total()
+                    };
+                    let numeric = -(energy(epsilon) - energy(-epsilon))
+                        / (2.0
+                            * epsilon
+                            * PERMEABILITY_OF_FREE_SPACE
+                            * SATURATION_MAGNETIZATION
+                            * CELL_VOLUME);
+                    assert!((numeric - field[k]).abs() < 1e-5 * scale);
+                }
+            }
+        }
+        // Only the end cells feel the ghost cells
+        let pull = &fixed.compute_effective_field_at(0) - &free.compute_effective_field_at(0);
+        assert!(pull[2] > 0.0);
+        assert_eq!(
+            fixed.compute_effective_field_at(4),
+            free.compute_effective_field_at(4)
+        );
+    }
+
+    #[test]
+    /// Test the Zeeman field and energy of a linear field gradient
+    fn test_field_gradient() {
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![0.0, 0.0, 1.0]; 3]);
+        system.set_applied_field([0.0, 0.0, 0.1]);
+        system.set_field_gradient([0.0, 0.0, 1.0e7]);
+        // 1e7 T/m changes the field by 0.01 T per nm
+        assert_eq!(system.applied_field_at(0), [0.0, 0.0, 0.1]);
+        assert!((system.applied_field_at(2)[2] - 0.12).abs() < 1e-15);
+
+        let zeeman = system.compute_energies().zeeman;
+        let expected = -SATURATION_MAGNETIZATION * (0.1 + 0.11 + 0.12) * CELL_VOLUME;
+        assert!((zeeman - expected).abs() < 1e-12 * expected.abs());
+    }
+
+    #[test]
+    /// Test that the anisotropy field follows the material of the cell
+    fn test_per_cell_material() {
+        let size = 3;
+        let mut system = MicromagneticSystem::new(size);
+        for i in 0..size {
+            system.magnetizations[i] = array![0.0, 0.0, 1.0];
+        }
+        let hard = Material {
+            anisotropy_constant: 5.0e5,
+            saturation_magnetization: 1.0e6,
+            easy_axis: [0.0, 0.0, 1.0],
+            ..Material::default()
+        };
+        system.set_material(0, hard);
+        let h_eff = system.compute_effective_field();
+        let expected = 2.0 * 5.0e5 / (1.0e6 * PERMEABILITY_OF_FREE_SPACE)
+            + EXTERNAL_FIELD[2] / PERMEABILITY_OF_FREE_SPACE;
+        assert!((h_eff[0][2] - expected).abs() < 1e-9 * expected);
+        assert_eq!(h_eff[0][0], 0.0);
+        // The default material has its easy axis along x, perpendicular to m
+        assert!((h_eff[2][2] - EXTERNAL_FIELD[2] / PERMEABILITY_OF_FREE_SPACE).abs() < 1e-6);
+    }
+
+    #[test]
+    /// Test that the dipolar interaction favors magnetization along the chain
+    fn test_dipolar_shape_anisotropy() {
+        let size = 6;
+        let mut system = MicromagneticSystem::new(size);
+        system.set_dipolar_interaction(true);
+        let mut energy_along = |m: Array1<f64>| {
+            for i in 0..size {
+                system.set_magnetization(i, m.clone());
+            }
+            system.compute_energies().dipolar
+        };
+        let along_chain = energy_along(array![1.0, 0.0, 0.0]);
+        let across_chain = energy_along(array![0.0, 1.0, 0.0]);
+        assert!(along_chain < 0.0);
+        assert!(along_chain < across_chain);
+    }
+
+    #[test]
+    /// Test the FFT demagnetizing field of a film against the prism and the direct sum
+    fn test_fft_dipolar_field() {
+        let grid = Grid::new(8, 8, 1);
+        let mut system = MicromagneticSystem::on_grid(grid, SimulationParameters::default());
+        system.set_dipolar_interaction(true);
+        system.set_dipolar_method(DipolarMethod::Fft);
+        let volume = grid.size() as f64 * CELL_VOLUME;
+        let factors = crate::dipolar::prism_demagnetization_factors([8e-9, 8e-9, 1e-9]);
+        for k in [0, 2] {
+            let mut direction = [0.0; 3];
+            direction[k] = 1.0;
+            for i in 0..grid.size() {
+                system.set_magnetization(i, Array1::from_vec(direction.to_vec()));
+            }
+            let expected = 0.5
+                * PERMEABILITY_OF_FREE_SPACE
+                * SATURATION_MAGNETIZATION.powi(2)
+                * factors[k]
+                * volume;
+            let dipolar = system.compute_energies().dipolar;
+            assert!((dipolar - expected).abs() < 1e-9 * expected);
+        }
+
+        // Far from each other the cells act as point dipoles
+        let mut fft = system.clone();
+        for i in 0..grid.size() {
+            fft.set_saturation_magnetization(i, if i == 0 { 1.0e6 } else { 0.0 });
+        }
+        fft.set_saturation_magnetization(63, 1.0e6);
+        fft.set_magnetization(0, array![0.0, 1.0, 0.0]);
+        let mut direct = fft.clone();
+        direct.set_dipolar_method(DipolarMethod::Direct);
+        // The FFT field includes the self-demagnetization -Ms m / 3 of a cell
+        let own = 1.0e6 / 3.0 * &fft.get_magnetizations()[63];
+        let a = &fft.compute_dipolar_field()[63] + &own;
+        let b = direct.compute_dipolar_field()[63].clone();
+        assert!((&a - &b).mapv(f64::abs).sum() < 1e-3 * b.mapv(f64::abs).sum());
+
+        // The relaxation sees the FFT field in both update schemes
+        system.set_update_scheme(UpdateScheme::RedBlack);
+        system.set_magnetization(10, array![0.0, 0.6, 0.8]);
+        let energy = system.compute_energies().total();
+        system.relaxation_step();
+        assert!(system.compute_energies().total() < energy);
+        assert!(system.resample(4).is_err());
+        let mut chain = MicromagneticSystem::new(8);
+        chain.set_dipolar_method(DipolarMethod::Fft);
+        assert!(chain.resample(4).unwrap().get_dipolar_method() == DipolarMethod::Fft);
+    }
+
+    #[test]
+    /// Test that the dipolar field of a periodic film sees no cut edges
+    fn test_periodic_dipolar_field() {
+        let grid = Grid::new(6, 6, 1);
+        for method in [DipolarMethod::Direct, DipolarMethod::Fft] {
+            let mut system = MicromagneticSystem::on_grid(grid, SimulationParameters::default());
+            system.set_dipolar_interaction(true);
+            system.set_dipolar_method(method);
+            for i in 0..grid.size() {
+                system.set_magnetization(i, array![1.0, 0.0, 0.0]);
+            }
+            let free = system.compute_dipolar_field();
+            // The edges perpendicular to m carry charges
+            assert!((free[0][0] - free[grid.index(2, 2, 0)][0]).abs() > 1e3);
+            system.set_boundary_condition(BoundaryCondition::Periodic);
+            assert_eq!(system.get_periodic_images(), DEFAULT_PERIODIC_IMAGES);
+            let periodic = system.compute_dipolar_field();
+            for h in &periodic {
+                assert!((h - &periodic[0]).mapv(f64::abs).sum() < 1e-6 * SATURATION_MAGNETIZATION);
+            }
+            let energy = system.compute_energies().dipolar;
+            system.set_periodic_images(2);
+            assert!(system.compute_energies().dipolar != energy);
+        }
+    }
+
+    #[test]
+    /// Test that the energy decreases during the minimization
+    fn test_energy_decreases() {
+        let size = 10;
+        let mut system = MicromagneticSystem::new(size);
+        let initial_energy = system.compute_energies().total();
+        let mut steps = 0;
+        system.minimize_energy_with(|step, _| steps = step);
+        assert!(steps > 0);
+        assert!(system.compute_energies().total() < initial_energy);
+    }
+
     #[test]
     /// Test the print_magnetizations function
     fn test_print_magnetizations() {
@@ -322,7 +3013,7 @@ mod tests {
         assert_eq!(magnetizations.len(), size);
         for m in magnetizations {
             assert_eq!(m.len(), 3);
-            assert!(m.iter().all(|&x| x >= -1.0 && x <= 1.0));
+            assert!(m.iter().all(|&x| (-1.0..=1.0).contains(&x)));
         }
     }
 }
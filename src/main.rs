@@ -1,44 +1,350 @@
-use magnetic_moments::MicromagneticSystem;
-use export_to_excel::export;
-use std::f64;
-mod magnetic_moments;
-mod export_to_excel;
+use energy_relaxation::astroid::AstroidSweep;
+use energy_relaxation::bench::{
+    benchmark_cases, print_scaling_table, run_scaling_benchmark, write_benchmark_cases,
+};
+use energy_relaxation::comparison::{parameter_differences, RunResult};
+use energy_relaxation::config::{SimulationConfig, STDIN_PATH};
+use energy_relaxation::console;
+use energy_relaxation::curvilinear::{Centerline, CurvedWire};
+use energy_relaxation::domains::analyze_domains;
+use energy_relaxation::dynamics::{
+    plane_axes, Antenna, AntennaProfile, DynamicsRun, Integrator, MovingFrame, RotatingField,
+    SpinUpdate, TimeDependentField,
+};
+use energy_relaxation::energy_landscape::{
+    energy_barrier, write_energy_profile, EnergyScan, ReactionCoordinate,
+};
+use energy_relaxation::ensemble::run_ensemble;
+use energy_relaxation::exchange_spring::ExchangeSpringBilayer;
+use energy_relaxation::export_to_excel::{
+    export, export_comparison, export_domains, export_ensemble, export_hysteresis, export_mode_maps,
+};
+use energy_relaxation::fitting::{FitParameter, FitProblem, Measurement};
+use energy_relaxation::fmr::{write_spectrum, SteadyStateSolver};
+use energy_relaxation::hooks::RunSummary;
+use energy_relaxation::hysteresis::{
+    coercive_field, remanence, write_hysteresis_csv, Branch, HysteresisLoop,
+};
+use energy_relaxation::image_export::{
+    export_component_png, image_width, AnimationRecorder, ColorMap, Component,
+};
+use energy_relaxation::laser::{optical_switching, LaserPulse};
+use energy_relaxation::macrospin::fit_macrospin;
+use energy_relaxation::magnetic_moments::MicromagneticSystem;
+use energy_relaxation::material::Material;
+use energy_relaxation::mesh_convergence::MeshConvergenceStudy;
+use energy_relaxation::mfm::MfmScan;
+use energy_relaxation::normal_modes::normal_modes;
+use energy_relaxation::output::OutputDirectory;
+use energy_relaxation::parameters::AdaptiveTimeStep;
+use energy_relaxation::phase_diagram::{
+    write_phase_image, write_phase_table, MagneticState, PhaseDiagram, PhaseDiagramStart,
+};
+use energy_relaxation::protocol::ProtocolEngine;
+use energy_relaxation::saf::SyntheticAntiferromagnet;
+#[cfg(feature = "scripting")]
+use energy_relaxation::scripting::{write_script_measurements, Script};
+use energy_relaxation::sensitivity::{Observable, SensitivityAnalysis, SensitivityParameter};
+use energy_relaxation::skyrmion::{write_skyrmion_trajectory, SkyrmionTracker};
+use energy_relaxation::snapshots::{SnapshotFormat, SnapshotWriter};
+use energy_relaxation::spin_transfer::{
+    switching_boundary, write_switching_boundary, write_switching_map, SpinTransferTorque,
+    SwitchingDiagram,
+};
+use energy_relaxation::spin_waves::{
+    excite_ringdown, ringdown, MagnetizationHistory, DEFAULT_PEAK_THRESHOLD,
+};
+use energy_relaxation::stability::{classify_stability, Stability, DEFAULT_SOFT_THRESHOLD};
+use energy_relaxation::stop_conditions::{StopCondition, WallTime};
+use energy_relaxation::stray_field::{compute_stray_field, line_points, write_stray_field};
+use energy_relaxation::summary::RunRecord;
+use energy_relaxation::table::TableWriter;
+use energy_relaxation::telegraph::{read_time_series_column, write_dwell_times, TelegraphAnalysis};
+use energy_relaxation::thermal_field::ThermalField;
+use energy_relaxation::time_series::{TimeSeriesColumn, TimeSeriesWriter};
+use energy_relaxation::two_temperature::{HeatSource, UltrafastRun};
+use energy_relaxation::validation::compare_with_ovf;
+use energy_relaxation::vortex::{write_core_trajectories, CoreTracker};
+#[cfg(feature = "websocket")]
+use energy_relaxation::websocket::{LiveServer, LiveStream};
+use energy_relaxation::{DYNAMICS_TIME_STEP, EASY_AXIS};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::SystemTime;
 
+// Number of relaxation steps between two rows of table.txt
+const TABLE_INTERVAL: usize = 100;
+// Edge length in pixels of one cell in the exported images
+const IMAGE_CELL_PIXELS: u32 = 8;
+// Edge length in pixels of one field point in the phase diagram image
+const PHASE_POINT_PIXELS: u32 = 16;
 
-/// Constants for the simulation
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args
+        .windows(2)
+        .any(|pair| pair[0] == "--config" && pair[1] == STDIN_PATH)
+    {
+        console::send_to_stderr(true);
+    }
+    match args.first().map(String::as_str) {
+        Some("bench") => run_command("bench", &args[1..], bench),
+        Some("gen-bench") => run_command("gen-bench", &args[1..], gen_bench),
+        Some("relax") => run_command("relax", &args[1..], relax),
+        Some("compare") => run_command("compare", &args[1..], compare),
+        Some("ensemble") => run_command("ensemble", &args[1..], ensemble),
+        Some("nucleation") => run_command("nucleation", &args[1..], nucleation),
+        Some("spin-flop") => run_command("spin-flop", &args[1..], spin_flop),
+        Some("astroid") => run_command("astroid", &args[1..], astroid),
+        Some("hysteresis") => run_command("hysteresis", &args[1..], hysteresis),
+        Some("energy-scan") => run_command("energy-scan", &args[1..], energy_scan),
+        Some("phase-diagram") => run_command("phase-diagram", &args[1..], phase_diagram),
+        Some("stt-switching") => run_command("stt-switching", &args[1..], stt_switching),
+        Some("curved-wire") => run_command("curved-wire", &args[1..], curved_wire),
+        Some("dynamics") => run_command("dynamics", &args[1..], dynamics),
+        Some("ringdown") => run_command("ringdown", &args[1..], ringdown_modes),
+        Some("modes") => run_command("modes", &args[1..], modes),
+        Some("fmr") => run_command("fmr", &args[1..], fmr),
+        Some("telegraph") => run_command("telegraph", &args[1..], telegraph),
+        Some("stray-field") => run_command("stray-field", &args[1..], stray_field),
+        Some("mfm") => run_command("mfm", &args[1..], mfm),
+        Some("animate") => run_command("animate", &args[1..], animate),
+        Some("config") => config_command(&args[1..]),
+        Some("report") => run_command("report", &args[1..], report),
+        Some("fit") => run_command("fit", &args[1..], fit),
+        Some("sensitivity") => run_command("sensitivity", &args[1..], sensitivity),
+        Some("mesh-convergence") => run_command("mesh-convergence", &args[1..], mesh_convergence),
+        Some("macrospin") => run_command("macrospin", &args[1..], macrospin),
+        Some("optical-switching") => {
+            run_command("optical-switching", &args[1..], optical_switching_command)
+        }
+        Some("protocol") => run_command("protocol", &args[1..], protocol),
+        #[cfg(feature = "scripting")]
+        Some("script") => run_command("script", &args[1..], script),
+        Some(command) if !command.starts_with("--") => {
+            eprintln!("Unknown command: {}", command);
+            ExitCode::FAILURE
+        }
+        _ => run_command("relax", &args, relax),
+    }
+}
 
-// Exchange interaction constants
-const MAGNETIC_EXCHANGE_CONSTANT: f64 = 2.1e-11;
-const SATURATION_MAGNETIZATION: f64 = 1.71e6;
-const PERMEABILITY_OF_FREE_SPACE: f64 = 4.0 * f64::consts::PI * 1.0e-7;
-const SPATIAL_DISCRETION_STEP: f64 = 1.0e-9;
+// Outcome of a command: the summary handed to the hooks, or the error
+// printed to stderr
+type CommandResult = Result<RunSummary, String>;
 
-// Anisotropy interaction constant 
-const UNIAXIAL_ANISOTROPY_CONSTANT: f64 = 4.8e4;
-const EASY_AXIS: [f64; 3] = [1.0, 0.0, 0.0];
+///# Run
+/// One invocation of a command with the configuration of its `--config`.
+struct Run {
+    command: &'static str,
+    config: SimulationConfig,
+}
 
-// Zeeman interaction constant
-const EXTERNAL_FIELD: [f64;3] = [0.0,0.0,0.5];
+impl Run {
+    ///# Summary of the Finished Run
+    fn finished(&self) -> RunSummary {
+        RunSummary::finished(self.command)
+    }
+}
 
-// Energy calculation constants
-const TIME_STEP: f64 = 1e-15;
-const DAMPING_CONSTANT: f64 = 0.2;
-const GILBERT_GYROMAGNETIC_RATIO: f64 = 1.83e10;
+/// Load the `--config` of the command line, run the command and run the
+/// completion hooks of the configuration when it finishes or fails.
+fn run_command(
+    command: &'static str,
+    args: &[String],
+    body: fn(&mut Run, &[String]) -> CommandResult,
+) -> ExitCode {
+    let mut config = SimulationConfig::default();
+    // The options come in pairs, the last --config wins
+    for pair in args.chunks(2) {
+        if let [option, path] = pair {
+            if option == "--config" {
+                match SimulationConfig::load(Path::new(path)) {
+                    Ok(loaded) => config = loaded,
+                    Err(e) => {
+                        eprintln!("Failed to load config {}: {}", path, e);
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+        }
+    }
+    let mut run = Run { command, config };
+    match body(&mut run, args) {
+        Ok(summary) => {
+            notify(&run.config, &summary);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            notify(&run.config, &RunSummary::failed(command, &e));
+            ExitCode::FAILURE
+        }
+    }
+}
 
-// Iteration parameters
-const MAX_ITERATIONS_NUMBER: usize = 10000;
-const TOLERANCE: f64 = 1e-6;
-  
+/// Relax a random chain, the system described by `--config simulation.toml`,
+/// or the state given by `--initial state.ovf`, and export the result.
+/// `--image mz.png` also renders one component of the relaxed chain or film as a PNG,
+/// `--websocket 127.0.0.1:9001` streams the run to browsers (feature `websocket`).
+/// `--stability 5` prints the lowest Hessian eigenvalues and flags saddle points.
+/// The files go into the `output_directory` of the config, by default the
+/// working directory. `--config -` reads the config from stdin, as with every
+/// command, and prints the summary JSON to stdout while the console output
+/// goes to stderr, the same for ensemble, dynamics and ringdown.
+fn relax(run: &mut Run, args: &[String]) -> CommandResult {
+    let started = SystemTime::now();
+    let config = run.config.clone();
+    let mut initial_state = None;
+    let mut image = None;
+    let mut component = Component::default();
+    let mut color_map = ColorMap::default();
+    let mut stability_modes = None;
+    #[cfg(feature = "websocket")]
+    let mut live = None;
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        match (option.as_str(), options.next()) {
+            // Loaded by run_command
+            ("--config", Some(_)) => {}
+            ("--initial", Some(path)) => initial_state = Some(path),
+            ("--image", Some(path)) => image = Some(path),
+            ("--component", Some(value)) if value.parse::<Component>().is_ok() => {
+                component = value.parse().unwrap()
+            }
+            ("--color-map", Some(value)) if value.parse::<ColorMap>().is_ok() => {
+                color_map = value.parse().unwrap()
+            }
+            ("--stability", Some(value)) if value.parse::<usize>().is_ok() => {
+                stability_modes = value.parse().ok()
+            }
+            #[cfg(feature = "websocket")]
+            ("--websocket", Some(address)) => match LiveServer::bind(address.as_str()) {
+                Ok(server) => {
+                    console!("Streaming live state on ws://{}", server.local_addr());
+                    live = Some(LiveStream::new(server));
+                }
+                Err(e) => return Err(format!("Failed to listen on {}: {}", address, e)),
+            },
+            _ => {
+                return Err(
+                    "Usage: relax [--config simulation.toml] [--initial state.ovf] \
+                     [--image mz.png] [--component x|y|z] [--color-map heatmap|grayscale|hsl] \
+                     [--stability 5]"
+                        .into(),
+                )
+            }
+        }
+    }
 
-fn main() {
-    // Number of cells in the 1D grid
-    let number_of_cells = 50;
+    let output = open_output(&config, "relax", args)?;
 
     // Initialize the micromagnetic system
-    let mut system = MicromagneticSystem::new(number_of_cells);
+    let system = match initial_state {
+        Some(path) => MicromagneticSystem::from_ovf(Path::new(path)),
+        None => config.build_system(),
+    };
+    let mut system = match system {
+        Ok(system) => system,
+        Err(e) => {
+            let e = format!("Failed to set up the system: {}", e);
+            let summary = RunSummary::failed("relax", &e);
+            write_summary(&output, RunRecord::new(summary, &config, started));
+            return Err(e);
+        }
+    };
+    // A film renders as nx x ny cells, a 3D grid has no image
+    if let (Some(path), Err(e)) = (image, image_width(system.get_grid())) {
+        return Err(format!("Cannot render {}: {}", path, e));
+    }
 
-    // Perform energy minimization
-    system.minimize_energy();
+    // Perform energy minimization, recording a mumax3-style table
+    let table_path = output.file("table.txt");
+    let mut table = match TableWriter::create(&table_path) {
+        Ok(table) => Some(table),
+        Err(e) => {
+            eprintln!("Failed to create {}: {}", table_path.display(), e);
+            None
+        }
+    };
+    let mut walltime = config.max_walltime.map(WallTime::from_secs);
+    let mut out_of_time = false;
+    let diagnostics = system.minimize_energy_with_diagnostics(|step, system| {
+        #[cfg(feature = "websocket")]
+        if let Some(live) = &live {
+            live.observe(step, system);
+        }
+        if let Some(walltime) = walltime.as_mut() {
+            if walltime.should_stop(step, 0.0, system) {
+                out_of_time = true;
+                return false;
+            }
+        }
+        if step % TABLE_INTERVAL != 0 {
+            return true;
+        }
+        if let Some(writer) = table.as_mut() {
+            if let Err(e) = writer.write_row(system.relaxation_time(), system) {
+                eprintln!("Failed to write {}: {}", table_path.display(), e);
+                table = None;
+            }
+        }
+        true
+    });
+    if let Some(Err(e)) = table.as_mut().map(TableWriter::flush) {
+        eprintln!("Failed to write {}: {}", table_path.display(), e);
+    }
+    log(&output, &format!("Outcome: {:?}", diagnostics.outcome));
+    #[cfg(feature = "websocket")]
+    if let Some(live) = &live {
+        use energy_relaxation::magnetic_moments::MinimizationOutcome::*;
+        let (Converged { iterations } | NotConverged { iterations } | Stopped { iterations }) =
+            diagnostics.outcome;
+        live.finish(iterations, diagnostics.converged(), &system);
+    }
+    for recommendation in &diagnostics.recommendations {
+        console!("Hint: {}", recommendation);
+    }
+    // Keep the unconverged state so that the next job can continue from it
+    if out_of_time {
+        console!(
+            "Wall-clock budget of {} s used up, the relaxation did not converge",
+            config.max_walltime.unwrap_or_default()
+        );
+        let state = output.file("state.ovf");
+        match system.save_ovf(&state) {
+            Ok(()) => console!(
+                "Saved the state to {0}, continue with --initial {0}",
+                state.display()
+            ),
+            Err(e) => eprintln!("Failed to save {}: {}", state.display(), e),
+        }
+        log(
+            &output,
+            "Wall-clock budget used up, state saved to state.ovf",
+        );
+    }
+    // Classify the relaxed state by the lowest eigenvalues of the Hessian
+    if let Some(count) = stability_modes {
+        let report = classify_stability(&system, count, DEFAULT_SOFT_THRESHOLD);
+        console!(
+            "Stability: {}, lowest eigenvalues {:?} T",
+            report.stability,
+            report.lowest_eigenvalues
+        );
+        if let Stability::Saddle { .. } = report.stability {
+            console!("Warning: the relaxation stopped on a saddle point, perturb the state and relax again");
+        }
+        for mode in &report.soft_modes {
+            console!("Soft mode with eigenvalue {:e} T", mode.eigenvalue);
+        }
+    }
+    let summary = RunSummary::relaxed("relax", &system, diagnostics.outcome);
+    write_summary(
+        &output,
+        RunRecord::new(summary.clone(), &config, started).with_final_state(&system),
+    );
 
     // Retrieve the normalized magnetization vectors
     let magnetizations = system.get_magnetizations();
@@ -46,8 +352,2254 @@ fn main() {
     // Output the final magnetization state
     system.print_magnetizations();
 
-    // Export the magnetization vectors to an Excel file
-    if let Err(e) = export(magnetizations) {
+    // Segment the relaxed profile into domains along the easy axis
+    let domains = analyze_domains(&magnetizations, &system.easy_axis());
+    console!(
+        "Domains: {} (mean size {:.2} cells, boundaries at {:?})",
+        domains.count(),
+        domains.mean_size(),
+        domains.boundaries
+    );
+    if let Err(e) = export_domains(
+        &domains,
+        system.get_cell_size(),
+        &output.file("domains.xlsx"),
+    ) {
+        eprintln!("Failed to export domains: {}", e);
+    }
+
+    // Render the chosen component of the chain or film as a color-mapped image
+    if let Some(path) = image {
+        let width = system.get_grid().nx;
+        if let Err(e) = export_component_png(
+            Path::new(path),
+            &magnetizations,
+            width,
+            component,
+            color_map,
+            IMAGE_CELL_PIXELS,
+        ) {
+            eprintln!("Failed to export {}: {}", path, e);
+        }
+    }
+
+    // Export the magnetization vectors and regions of the kept cells to an Excel file
+    let kept: Vec<usize> = config.decimation.cells(system.get_grid()).collect();
+    let exported = kept
+        .iter()
+        .map(|&cell| magnetizations[cell].clone())
+        .collect();
+    let regions: Vec<usize> = kept
+        .iter()
+        .map(|&cell| system.get_regions()[cell])
+        .collect();
+    if let Err(e) = export(exported, &regions, &output.file("vectors.xlsx")) {
         eprintln!("Failed to export magnetizations: {}", e);
     }
-}
\ No newline at end of file
+
+    Ok(summary)
+}
+
+/// Relax the configured problem and compare it with a reference state.
+/// Usage: `compare --reference state.ovf [--config simulation.toml]`
+fn compare(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut reference = None;
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        match (option.as_str(), options.next()) {
+            // Loaded by run_command
+            ("--config", Some(_)) => {}
+            ("--reference", Some(path)) => reference = Some(path),
+            _ => reference = None,
+        }
+    }
+    let Some(reference) = reference else {
+        return Err("Usage: compare --reference state.ovf [--config simulation.toml]".into());
+    };
+
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    system.minimize_energy();
+
+    let report = compare_with_ovf(Path::new(reference), &system)
+        .map_err(|e| format!("Comparison failed: {}", e))?;
+    console!("{}", report);
+    Ok(run.finished())
+}
+
+/// Relax an ensemble of random initial states, or of the states of the
+/// ensemble sampler, and export mean values with standard errors to
+/// ensemble.xlsx.
+/// Usage: `ensemble [--replicas 16] [--config simulation.toml]`
+fn ensemble(run: &mut Run, args: &[String]) -> CommandResult {
+    let started = SystemTime::now();
+    let config = run.config.clone();
+    let mut replicas = 16;
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        match (option.as_str(), options.next()) {
+            // Loaded by run_command
+            ("--config", Some(_)) => {}
+            ("--replicas", Some(value)) if value.parse::<usize>().is_ok_and(|n| n > 0) => {
+                replicas = value.parse().unwrap_or(replicas)
+            }
+            _ => return Err("Usage: ensemble [--replicas 16] [--config simulation.toml]".into()),
+        }
+    }
+    let system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    let cells = system.size();
+    let applied_field = system.get_applied_field();
+    let output = open_output(&config, "ensemble", args)?;
+
+    // The sampled states replace the initial state of every replica
+    let initial_states = config
+        .ensemble_sampler
+        .map(|sampler| sampler.initial_states(replicas, cells));
+    // Switching is measured along the applied field
+    let (observables, statistics) = run_ensemble(
+        replicas,
+        |replica| {
+            let mut system = config.build_system().expect("config was validated");
+            if let Some(states) = &initial_states {
+                for (cell, m) in states[replica].iter().enumerate() {
+                    system.set_magnetization(cell, ndarray::arr1(m));
+                }
+            }
+            system
+        },
+        &applied_field,
+    );
+    let m = &statistics.average_magnetization;
+    console!(
+        "<m> = ({:.6} ± {:.6}, {:.6} ± {:.6}, {:.6} ± {:.6})",
+        m[0].mean,
+        m[0].standard_error,
+        m[1].mean,
+        m[1].standard_error,
+        m[2].mean,
+        m[2].standard_error
+    );
+    console!(
+        "Final energy = {:e} ± {:e} J, switched fraction {:.3}",
+        statistics.final_energy.mean,
+        statistics.final_energy.standard_error,
+        statistics.switched_fraction
+    );
+    export_ensemble(&statistics, &observables, &output.file("ensemble.xlsx"))
+        .map_err(|e| format!("Failed to export the ensemble: {}", e))?;
+    let summary = RunSummary {
+        total_energy: Some(statistics.final_energy.mean),
+        average_magnetization: Some(m.each_ref().map(|component| component.mean)),
+        ..RunSummary::finished("ensemble")
+    };
+    write_summary(&output, RunRecord::new(summary.clone(), &config, started));
+    Ok(summary)
+}
+
+/// Compare the final states of several runs in one workbook.
+/// Usage: `report --run vectors.xlsx[,simulation.toml] --run state.ovf ... [--output report.xlsx]
+/// [--config simulation.toml]`
+fn report(run: &mut Run, args: &[String]) -> CommandResult {
+    let mut runs = Vec::new();
+    let mut output = "report.xlsx";
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        match (option.as_str(), options.next()) {
+            ("--run", Some(entry)) => {
+                // The state file and the optional configuration it was computed with
+                let (state, config) = match entry.split_once(',') {
+                    Some((state, config)) => (state, Some(Path::new(config))),
+                    None => (entry.as_str(), None),
+                };
+                match RunResult::load(Path::new(state), config) {
+                    Ok(result) => runs.push(result),
+                    Err(e) => return Err(format!("Failed to load {}: {}", entry, e)),
+                }
+            }
+            ("--output", Some(path)) => output = path,
+            // Loaded by run_command for the completion hooks
+            ("--config", Some(_)) => {}
+            _ => runs.clear(),
+        }
+    }
+    if runs.len() < 2 {
+        return Err(
+            "Usage: report --run vectors.xlsx[,simulation.toml] --run state.ovf ... \
+             [--output report.xlsx] [--config simulation.toml]"
+                .into(),
+        );
+    }
+
+    console!(
+        "{:<32} {:>16} {:>10} {:>10} {:>10}",
+        "Run",
+        "E_total (J)",
+        "<mx>",
+        "<my>",
+        "<mz>"
+    );
+    for run in &runs {
+        let m = run.average_magnetization;
+        console!(
+            "{:<32} {:>16.6e} {:>10.6} {:>10.6} {:>10.6}",
+            run.name,
+            run.energies.total(),
+            m[0],
+            m[1],
+            m[2]
+        );
+    }
+    let differences = parameter_differences(&runs);
+    for difference in &differences {
+        let values: Vec<&str> = difference
+            .values
+            .iter()
+            .map(|value| value.as_deref().unwrap_or("-"))
+            .collect();
+        console!("{}: {}", difference.parameter, values.join(" | "));
+    }
+    export_comparison(&runs, &differences, Path::new(output))
+        .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    console!("Comparison of {} runs written to {}", runs.len(), output);
+    Ok(run.finished())
+}
+
+/// Fit material constants of the configured system to a measured loop or
+/// FMR frequencies and print the best-fit values with the residuals.
+/// Usage: `fit --hysteresis loop.txt | --fmr fmr.txt [--parameters K,A,alpha]
+/// [--direction 0,0,1] [--evaluations 200] [--config simulation.toml]`
+fn fit(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut measurement = None;
+    let mut parameters = vec![FitParameter::AnisotropyConstant];
+    let mut direction = EASY_AXIS;
+    let mut evaluations = 200;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--hysteresis" | "--fmr" => {
+                let path = Path::new(value);
+                let loaded = if option == "--fmr" {
+                    Measurement::load_fmr(path)
+                } else {
+                    Measurement::load_hysteresis(path)
+                };
+                match loaded {
+                    Ok(loaded) => {
+                        measurement = Some(loaded);
+                        Some(())
+                    }
+                    Err(e) => return Err(format!("Failed to load {}: {}", value, e)),
+                }
+            }
+            "--parameters" => value
+                .split(',')
+                .map(|p| p.trim().parse().ok())
+                .collect::<Option<Vec<FitParameter>>>()
+                .map(|v| parameters = v),
+            "--direction" => parse_vector(value)
+                .filter(|v| v.iter().any(|&c| c != 0.0))
+                .map(|v| {
+                    let norm = v.iter().map(|c| c * c).sum::<f64>().sqrt();
+                    direction = v.map(|c| c / norm)
+                }),
+            "--evaluations" => value.parse().ok().map(|v| evaluations = v),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid fit option: {} {}", option, value));
+        }
+    }
+    let Some(measurement) = measurement else {
+        return Err(
+            "Usage: fit --hysteresis loop.txt | --fmr fmr.txt [--parameters K,A,alpha]".into(),
+        );
+    };
+
+    let system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    let mut problem = FitProblem::new(system, parameters, measurement, direction);
+    problem.max_evaluations = evaluations;
+    let initial = problem.initial_values();
+    let result = problem.fit().map_err(|e| format!("Fit failed: {}", e))?;
+
+    console!("{:<12} {:>14} {:>14}", "Parameter", "initial", "best fit");
+    for ((parameter, initial), value) in problem.parameters.iter().zip(&initial).zip(&result.values)
+    {
+        console!(
+            "{:<12} {:>14.6e} {:>14.6e}",
+            parameter.name(),
+            initial,
+            value
+        );
+    }
+    console!(
+        "{:>12} {:>14} {:>14} {:>14}",
+        "B (T)",
+        "measured",
+        "simulated",
+        "residual"
+    );
+    let fields = problem.measurement.fields();
+    let measured = problem.measurement.values();
+    for i in 0..fields.len() {
+        console!(
+            "{:>12.6} {:>14.6e} {:>14.6e} {:>14.6e}",
+            fields[i],
+            measured[i],
+            result.simulated[i],
+            result.residuals[i]
+        );
+    }
+    console!(
+        "RMS residual {:e} after {} evaluations",
+        result.rms_residual,
+        result.evaluations
+    );
+    Ok(run.finished())
+}
+
+/// Relax the configured system with every parameter lowered and raised by
+/// `--step` and print the normalized sensitivities of the observables.
+/// Usage: `sensitivity [--config simulation.toml] [--parameters A,K,Ms,B]
+/// [--observables energy,wall_width,coercivity] [--step 0.01]`
+fn sensitivity(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut analysis = SensitivityAnalysis::default();
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--parameters" => value
+                .split(',')
+                .map(|p| p.trim().parse().ok())
+                .collect::<Option<Vec<SensitivityParameter>>>()
+                .map(|v| analysis.parameters = v),
+            "--observables" => value
+                .split(',')
+                .map(|o| o.trim().parse().ok())
+                .collect::<Option<Vec<Observable>>>()
+                .map(|v| analysis.observables = v),
+            "--step" => value
+                .parse()
+                .ok()
+                .filter(|&step: &f64| step > 0.0 && step < 1.0)
+                .map(|step| analysis.relative_step = step),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid sensitivity option: {} {}", option, value));
+        }
+    }
+
+    let system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    let report = analysis.run(&system);
+
+    let format = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.6e}", v));
+    console!("{:<12} {:>14}", "Observable", "baseline");
+    for (observable, baseline) in analysis.observables.iter().zip(&report.baseline) {
+        console!("{:<12} {:>14}", observable.name(), format(*baseline));
+    }
+    console!(
+        "{:<10} {:<12} {:>14} {:>14} {:>12}",
+        "Parameter",
+        "Observable",
+        "lowered",
+        "raised",
+        "d ln/d ln"
+    );
+    for s in &report.sensitivities {
+        console!(
+            "{:<10} {:<12} {:>14} {:>14} {:>12}",
+            s.parameter.name(),
+            s.observable.name(),
+            format(s.lower),
+            format(s.upper),
+            s.sensitivity
+                .map_or("-".to_string(), |v| format!("{:.4}", v))
+        );
+    }
+    Ok(run.finished())
+}
+
+/// Rerun the configured chain at several cell sizes and print how the
+/// observables converge towards the finest mesh, with a warning when the
+/// configured cell size is too coarse for `--tolerance`.
+/// Usage: `mesh-convergence [--config simulation.toml] [--cell-sizes 2e-9,1e-9,0.5e-9]
+/// [--observables energy,wall_width,coercivity] [--tolerance 0.01]`
+fn mesh_convergence(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut study = MeshConvergenceStudy::default();
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--cell-sizes" => parse_values(value)
+                .filter(|sizes| sizes.iter().all(|&dx| dx > 0.0))
+                .map(|sizes| study.cell_sizes = sizes),
+            "--observables" => value
+                .split(',')
+                .map(|o| o.trim().parse().ok())
+                .collect::<Option<Vec<Observable>>>()
+                .map(|v| study.observables = v),
+            "--tolerance" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| study.tolerance = v),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!(
+                "Invalid mesh-convergence option: {} {}",
+                option, value
+            ));
+        }
+    }
+
+    let report = config
+        .build_system()
+        .and_then(|system| study.run(&system))
+        .map_err(|e| format!("Failed to run the mesh convergence study: {}", e))?;
+
+    let format = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.6e}", v));
+    let mut header = format!("{:>12} {:>6}", "dx (m)", "cells");
+    for observable in &report.observables {
+        header += &format!(" {:>14} {:>10}", observable.name(), "deviation");
+    }
+    console!("{}", header);
+    for point in &report.points {
+        let mut row = format!("{:>12.4e} {:>6}", point.cell_size, point.cells);
+        for (value, deviation) in point.values.iter().zip(&point.deviations) {
+            row += &format!(
+                " {:>14} {:>10}",
+                format(*value),
+                deviation.map_or("-".to_string(), |d| format!("{:.3} %", 100.0 * d))
+            );
+        }
+        console!("{}", row);
+    }
+    for &observable in &report.observables {
+        if let Some(order) = report.observed_order(observable) {
+            console!("Observed order of the {}: {:.2}", observable.name(), order);
+        }
+    }
+    console!(
+        "Exchange length {:e} m, wall parameter {:e} m",
+        report.exchange_length,
+        report.wall_parameter
+    );
+    match report.coarsest_converged(study.tolerance) {
+        Some(cell_size) => console!(
+            "Converged within {} from a cell size of {:e} m",
+            study.tolerance,
+            cell_size
+        ),
+        None => console!("Not converged within {}", study.tolerance),
+    }
+    for warning in &report.warnings {
+        console!("Warning: {}", warning);
+    }
+    Ok(run.finished())
+}
+
+/// Relax the configured system and reduce it to an effective macrospin,
+/// fitted to the response of the total moment to small transverse fields.
+/// `--output macrospin.json` also writes the parameters for a device model.
+/// Usage: `macrospin [--config simulation.toml] [--output macrospin.json]`
+fn macrospin(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut output = None;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--output" => (!value.is_empty()).then(|| output = Some(value.to_string())),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid macrospin option: {} {}", option, value));
+        }
+    }
+
+    let system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    let macrospin =
+        fit_macrospin(&system).map_err(|e| format!("Failed to fit the macrospin: {}", e))?;
+    let [ex, ey, ez] = macrospin.easy_axis;
+    let [mx, my, mz] = macrospin.magnetization;
+    console!("Moment Ms V        = {:.6e} A m^2", macrospin.moment);
+    console!("Volume V           = {:.6e} m^3", macrospin.volume);
+    console!(
+        "Effective Ms       = {:.6e} A/m",
+        macrospin.saturation_magnetization()
+    );
+    console!(
+        "Effective K        = {:.6e} J/m^3",
+        macrospin.anisotropy_constant
+    );
+    console!(
+        "Anisotropy field   = {:.6e} T",
+        macrospin.anisotropy_field()
+    );
+    console!("Easy axis          = ({:.6}, {:.6}, {:.6})", ex, ey, ez);
+    console!("Moment direction   = ({:.6}, {:.6}, {:.6})", mx, my, mz);
+    if let Some(path) = output {
+        let json = serde_json::to_string_pretty(&macrospin).expect("the macrospin serializes");
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    Ok(run.finished())
+}
+
+/// Heat the relaxed configured system with a Gaussian laser pulse through
+/// the two-temperature model and report whether the pulse toggles its
+/// magnetization. The config needs `temperature` as the ambient
+/// temperature, `temperature_scaling` and `two_temperature_model`.
+/// `--output switching.json` also writes the report.
+/// Usage: `optical-switching [--config simulation.toml] [--fluence 10] [--spot-size 1e-6]
+/// [--pulse-duration 1e-13] [--delay 3e-13] [--center x] [--penetration-depth 15e-9]
+/// [--field bx,by,bz] [--duration 5e-11] [--output switching.json]`
+fn optical_switching_command(run: &mut Run, args: &[String]) -> CommandResult {
+    let mut config = run.config.clone();
+    let mut pulse = LaserPulse {
+        fluence: 10.0,
+        spot_size: 1e-6,
+        duration: 1e-13,
+        delay: 3e-13,
+        center: f64::NAN,
+        penetration_depth: 15e-9,
+    };
+    let mut field = None;
+    let mut duration = 5e-11;
+    let mut output = None;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--fluence" => value.parse().ok().map(|v| pulse.fluence = v),
+            "--spot-size" => value.parse().ok().map(|v| pulse.spot_size = v),
+            "--pulse-duration" => value.parse().ok().map(|v| pulse.duration = v),
+            "--delay" => value.parse().ok().map(|v| pulse.delay = v),
+            "--center" => value.parse().ok().map(|v| pulse.center = v),
+            "--penetration-depth" => value.parse().ok().map(|v| pulse.penetration_depth = v),
+            "--field" => parse_vector(value).map(|v| field = Some(v)),
+            "--duration" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| duration = v),
+            "--output" => (!value.is_empty()).then(|| output = Some(value.to_string())),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!(
+                "Invalid optical-switching option: {} {}",
+                option, value
+            ));
+        }
+    }
+
+    let (Some(scaling), Some(model)) = (config.temperature_scaling, config.two_temperature_model)
+    else {
+        return Err("The config needs temperature_scaling and two_temperature_model".into());
+    };
+    // The run scales the zero temperature materials itself
+    config.temperature_scaling = None;
+    let system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    if pulse.center.is_nan() {
+        pulse.center = 0.5 * (system.size().max(1) - 1) as f64 * system.get_cell_size();
+    }
+    let simulation = UltrafastRun {
+        dynamics: DynamicsRun::new(TimeDependentField::Constant(
+            field.unwrap_or(config.applied_field),
+        )),
+        model,
+        scaling,
+        source: HeatSource::Laser(pulse),
+        ambient_temperature: config.temperature,
+    };
+    let report = optical_switching(&system, &simulation, duration)
+        .map_err(|e| format!("Failed to run the pulse: {}", e))?;
+    let [ix, iy, iz] = report.initial_magnetization;
+    let [fx, fy, fz] = report.final_magnetization;
+    console!("Initial <m>          = ({:.6}, {:.6}, {:.6})", ix, iy, iz);
+    console!("Final <m>            = ({:.6}, {:.6}, {:.6})", fx, fy, fz);
+    console!(
+        "Peak electron T      = {:.1} K",
+        report.peak_electron_temperature
+    );
+    console!("Minimum moment       = {:.6}", report.minimum_moment);
+    console!(
+        "Switched cells       = {:.1} %",
+        100.0 * report.switched_fraction
+    );
+    console!(
+        "The pulse {} the magnetization",
+        if report.toggled {
+            "toggles"
+        } else {
+            "does not toggle"
+        }
+    );
+    if let Some(path) = output {
+        let json = serde_json::to_string_pretty(&report).expect("the report serializes");
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    Ok(run.finished())
+}
+
+/// Execute the protocol steps of the config in order and write the
+/// measurements with the time series columns to protocol.txt. With a
+/// `temperature_scaling` the run starts at `temperature`.
+/// Usage: `protocol --config simulation.toml [--output protocol.txt]`
+fn protocol(run: &mut Run, args: &[String]) -> CommandResult {
+    let mut config = run.config.clone();
+    let mut output = String::from("protocol.txt");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--output" => (!value.is_empty()).then(|| output = value.to_string()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid protocol option: {} {}", option, value));
+        }
+    }
+    if config.protocol.is_empty() {
+        return Err("The config has no protocol steps".into());
+    }
+
+    let engine = ProtocolEngine {
+        scaling: config.temperature_scaling.take(),
+        temperature: config.temperature,
+        columns: config.time_series_columns.clone(),
+        time_step: DYNAMICS_TIME_STEP,
+        readout: config.magnetoresistance,
+    };
+    // The engine scales the zero temperature materials itself
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    let measurements = engine
+        .run(&mut system, &config.protocol)
+        .map_err(|e| format!("The protocol failed: {}", e))?;
+    let written = std::fs::File::create(&output)
+        .and_then(|file| engine.write_measurements(std::io::BufWriter::new(file), &measurements));
+    written.map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    console!(
+        "{} steps executed, {} measurements written to {}",
+        config.protocol.len(),
+        measurements.len(),
+        output
+    );
+    Ok(run.finished())
+}
+
+/// Relax the configured system, or integrate its dynamics for `--duration`
+/// in s, with the field term, stop condition and measurements of a rhai
+/// script, and write the measurements to script.csv (feature `scripting`).
+/// Usage: `script --script custom.rhai [--config simulation.toml] [--duration 1e-9]
+/// [--output script.csv]`
+#[cfg(feature = "scripting")]
+fn script(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut path = None;
+    let mut duration = None;
+    let mut output = String::from("script.csv");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--script" => (!value.is_empty()).then(|| path = Some(value.to_string())),
+            "--duration" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| duration = Some(v)),
+            "--output" => (!value.is_empty()).then(|| output = value.to_string()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid script option: {} {}", option, value));
+        }
+    }
+    let Some(path) = path else {
+        return Err(
+            "Usage: script --script custom.rhai [--config simulation.toml] \
+                    [--duration 1e-9] [--output script.csv]"
+                .into(),
+        );
+    };
+
+    let script =
+        Script::load(Path::new(&path)).map_err(|e| format!("Failed to load {}: {}", path, e))?;
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    let report = match duration {
+        Some(duration) => {
+            let simulation =
+                DynamicsRun::new(TimeDependentField::Constant(system.get_applied_field()));
+            script.run_dynamics(&simulation, &mut system, duration)
+        }
+        None => script.relax(&mut system).map(|(_, report)| report),
+    }
+    .map_err(|e| format!("The script failed: {}", e))?;
+    let written = std::fs::File::create(&output).and_then(|file| {
+        write_script_measurements(std::io::BufWriter::new(file), &report.measurements)
+    });
+    written.map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    console!(
+        "{} steps{}, {} measurements written to {}",
+        report.steps,
+        if report.stopped {
+            ", stopped by the script"
+        } else {
+            ""
+        },
+        report.measurements.len(),
+        output
+    );
+    Ok(run.finished())
+}
+
+/// Write a commented example configuration with every option and its default.
+/// Usage: `config init [--output simulation.toml]`, `-` prints it instead
+fn config_command(args: &[String]) -> ExitCode {
+    let output = match args {
+        [init] if init == "init" => "simulation.toml",
+        [init, option, path] if init == "init" && option == "--output" => path.as_str(),
+        _ => {
+            eprintln!("Usage: config init [--output simulation.toml]");
+            return ExitCode::FAILURE;
+        }
+    };
+    let text = SimulationConfig::example_toml();
+    if output == "-" {
+        print!("{}", text);
+        return ExitCode::SUCCESS;
+    }
+    // Never overwrite a configuration that may have been edited
+    if Path::new(output).exists() {
+        eprintln!("{} already exists", output);
+        return ExitCode::FAILURE;
+    }
+    if let Err(e) = std::fs::write(output, text) {
+        eprintln!("Failed to write {}: {}", output, e);
+        return ExitCode::FAILURE;
+    }
+    console!("Example configuration written to {}", output);
+    ExitCode::SUCCESS
+}
+
+// Create the output directory of a run and log the command line in it
+fn open_output(
+    config: &SimulationConfig,
+    command: &str,
+    args: &[String],
+) -> Result<OutputDirectory, String> {
+    let output = OutputDirectory::for_config(config)
+        .map_err(|e| format!("Failed to create the output directory: {}", e))?;
+    if config.output_directory.is_some() {
+        console!("Writing the outputs to {}", output.path().display());
+    }
+    log(&output, &format!("{} {}", command, args.join(" ")));
+    Ok(output)
+}
+
+// Write the summary.json of a run into its output directory, and to stdout
+// in a pipeline
+fn write_summary(output: &OutputDirectory, record: RunRecord) {
+    let path = output.file("summary.json");
+    if let Err(e) = record.write(&path) {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+    }
+    // The console is on stderr in a pipeline, so stdout only carries the summary
+    if !console::on_stderr() {
+        return;
+    }
+    let json = serde_json::to_string(&record).expect("the summary serializes");
+    if let Err(e) = writeln!(io::stdout(), "{}", json) {
+        eprintln!("Failed to write the summary to stdout: {}", e);
+    }
+}
+
+fn log(output: &OutputDirectory, message: &str) {
+    if let Err(e) = output.log(message) {
+        eprintln!("Failed to write the run log: {}", e);
+    }
+}
+
+/// Run the completion hooks of the configuration, failing hooks only warn.
+fn notify(config: &SimulationConfig, summary: &RunSummary) {
+    for e in config.hooks.notify(summary) {
+        eprintln!("Completion hook failed: {}", e);
+    }
+}
+
+/// Sweep a reversal field over a hard/soft exchange spring bilayer and
+/// report the nucleation field of the soft layer.
+/// Usage: `nucleation [--hard-cells 10] [--soft-cells 10] [--step 0.05] [--max-field 2] [--config simulation.toml]`
+fn nucleation(run: &mut Run, args: &[String]) -> CommandResult {
+    let mut bilayer = ExchangeSpringBilayer::default();
+    let mut field_step = 0.05;
+    let mut max_field = 2.0;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--hard-cells" => value.parse().ok().map(|v| bilayer.hard_cells = v),
+            "--soft-cells" => value.parse().ok().map(|v| bilayer.soft_cells = v),
+            "--step" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| field_step = v),
+            "--max-field" => value.parse().ok().map(|v| max_field = v),
+            // Loaded by run_command for the completion hooks
+            "--config" => Some(()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid nucleation option: {} {}", option, value));
+        }
+    }
+    if bilayer.soft_cells == 0 {
+        return Err("The soft layer needs at least one cell".into());
+    }
+
+    let sweep = bilayer.measure_nucleation_field(field_step, max_field);
+    console!("{:>10} {:>12} {:>12}", "B (T)", "<m.e> soft", "<m.e> hard");
+    for i in 0..sweep.fields.len() {
+        console!(
+            "{:>10.3} {:>12.6} {:>12.6}",
+            sweep.fields[i],
+            sweep.soft_projection[i],
+            sweep.hard_projection[i]
+        );
+    }
+    match sweep.nucleation_field {
+        Some(field) => console!("Nucleation field: {:.3} T", field),
+        None => console!("No nucleation up to {:.3} T", max_field),
+    }
+    if let Some(field) = sweep.switching_field {
+        console!("Hard layer switching field: {:.3} T", field);
+    }
+    Ok(run.finished())
+}
+
+/// Relax a wire along a closed ring, or along a helix when a pitch is given,
+/// and report the curvature-induced terms and the local magnetization. The
+/// cells have the cell size of the config.
+/// Usage: `curved-wire [--cells 40] [--radius 6.4e-9] [--pitch 20e-9] [--config simulation.toml]`
+fn curved_wire(run: &mut Run, args: &[String]) -> CommandResult {
+    let cell_size = run.config.cell_size;
+    let mut wire = CurvedWire::ring(40, cell_size, Default::default());
+    let mut radius = None;
+    let mut pitch = None;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--cells" => value
+                .parse()
+                .ok()
+                .filter(|&v: &usize| v > 0)
+                .map(|v| wire = CurvedWire::ring(v, cell_size, wire.material)),
+            "--radius" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| radius = Some(v)),
+            "--pitch" => value.parse().ok().map(|v| pitch = Some(v)),
+            // Loaded by run_command for the completion hooks
+            "--config" => Some(()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid curved-wire option: {} {}", option, value));
+        }
+    }
+    let Centerline::Ring {
+        radius: ring_radius,
+    } = wire.centerline
+    else {
+        unreachable!("the wire starts as a ring");
+    };
+    let radius = radius.unwrap_or(ring_radius);
+    wire.centerline = match pitch {
+        Some(pitch) => Centerline::Helix { radius, pitch },
+        None => Centerline::Ring { radius },
+    };
+
+    let (curvature_dmi, torsion_dmi) = wire.curvature_dmi_constants();
+    console!(
+        "Curvature {:e} 1/m, torsion {:e} 1/m, closed: {}",
+        wire.centerline.curvature(),
+        wire.centerline.torsion(),
+        wire.is_closed()
+    );
+    console!(
+        "Curvature-induced DMI {:e} J/m^2 (curvature), {:e} J/m^2 (torsion)",
+        curvature_dmi,
+        torsion_dmi
+    );
+
+    let mut system = wire.build();
+    system.minimize_energy();
+    let local = wire.local_components(&system);
+    let average = |k: usize| local.iter().map(|m| m[k]).sum::<f64>() / local.len().max(1) as f64;
+    let anisotropy: f64 = local
+        .iter()
+        .map(|&m| wire.curvature_anisotropy_density(m) * system.cell_volume())
+        .sum();
+    console!(
+        "<m_T> = {:.6}, <m_N> = {:.6}, <m_B> = {:.6}",
+        average(0),
+        average(1),
+        average(2)
+    );
+    console!(
+        "Curvature-induced anisotropy energy {:e} J, total energy {:e} J",
+        anisotropy,
+        system.compute_energies().total()
+    );
+    Ok(run.finished())
+}
+
+/// Sweep a field along the easy axis of a synthetic antiferromagnet and
+/// report the spin flop and saturation fields.
+/// Usage: `spin-flop [--layer-cells 3] [--spacer-cells 1] [--coupling -5e-4] [--biquadratic 0]
+/// [--step 0.01] [--max-field 1] [--config simulation.toml]`
+fn spin_flop(run: &mut Run, args: &[String]) -> CommandResult {
+    let mut saf = SyntheticAntiferromagnet::default();
+    let mut field_step = 0.01;
+    let mut max_field = 1.0;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--layer-cells" => value
+                .parse()
+                .ok()
+                .filter(|&v: &usize| v > 0)
+                .map(|v| saf.layer_cells = v),
+            "--spacer-cells" => value.parse().ok().map(|v| saf.spacer_cells = v),
+            "--coupling" => value.parse().ok().map(|v| saf.coupling = v),
+            "--biquadratic" => value.parse().ok().map(|v| saf.biquadratic_coupling = v),
+            "--step" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| field_step = v),
+            "--max-field" => value.parse().ok().map(|v| max_field = v),
+            // Loaded by run_command for the completion hooks
+            "--config" => Some(()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid spin-flop option: {} {}", option, value));
+        }
+    }
+
+    let sweep = saf.measure_spin_flop_field(field_step, max_field);
+    console!("{:>10} {:>12} {:>12}", "B (T)", "<m.e> net", "<m.e> stag");
+    for i in 0..sweep.fields.len() {
+        console!(
+            "{:>10.3} {:>12.6} {:>12.6}",
+            sweep.fields[i],
+            sweep.net_projection[i],
+            sweep.staggered_projection[i]
+        );
+    }
+    match sweep.spin_flop_field {
+        Some(field) => console!("Spin flop field: {:.3} T", field),
+        None => console!("No spin flop up to {:.3} T", max_field),
+    }
+    if let Some(field) = sweep.saturation_field {
+        console!("Saturation field: {:.3} T", field);
+    }
+    Ok(run.finished())
+}
+
+/// Sweep the field angle and magnitude and print the switching boundary of
+/// a macrospin, or of the system described by `--config`.
+/// Usage: `astroid [--config simulation.toml] [--angle-step 5] [--max-field 0.1]`
+fn astroid(run: &mut Run, args: &[String]) -> CommandResult {
+    let mut system = MicromagneticSystem::new(1);
+    let mut sweep = AstroidSweep::default();
+    let mut angle_step = 5.0;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => match SimulationConfig::load(Path::new(value))
+                .and_then(|config| config.build_system())
+            {
+                Ok(built) => {
+                    system = built;
+                    Some(())
+                }
+                Err(e) => return Err(format!("Failed to set up the system from {}: {}", value, e)),
+            },
+            "--angle-step" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0 && v < 90.0)
+                .map(|v| angle_step = v),
+            "--max-field" => value.parse().ok().map(|v| sweep.max_field = v),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid astroid option: {} {}", option, value));
+        }
+    }
+    // The torque vanishes exactly along the easy and hard axes, so they are left out
+    sweep.angles = (1..)
+        .map(|k| k as f64 * angle_step)
+        .take_while(|&angle| angle < 90.0)
+        .collect();
+
+    let points = sweep.run(&system);
+    console!(
+        "{:>10} {:>12} {:>12} {:>12}",
+        "angle",
+        "B_sw (T)",
+        "B_par (T)",
+        "B_perp (T)"
+    );
+    for point in points {
+        match (
+            point.switching_field,
+            point.parallel_field,
+            point.perpendicular_field,
+        ) {
+            (Some(field), Some(parallel), Some(perpendicular)) => console!(
+                "{:>10.2} {:>12.6} {:>12.6} {:>12.6}",
+                point.angle,
+                field,
+                parallel,
+                perpendicular
+            ),
+            _ => console!("{:>10.2} {:>12}", point.angle, "none"),
+        }
+    }
+    Ok(run.finished())
+}
+
+/// Map the energy along a reaction coordinate with constrained relaxations
+/// of the configured system: the average projection of m on `--axis`, or
+/// the position of the wall in the initial state along the easy axis. The
+/// wall positions default to the middle half of the chain.
+/// Usage: `energy-scan [--config simulation.toml] [--coordinate magnetization|wall]
+/// [--axis 0,0,1] [--from 0.9] [--to -0.9] [--points 19] [--output energy_profile.txt]`
+fn energy_scan(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut wall = false;
+    let mut axis = [0.0, 0.0, 1.0];
+    let mut start = None;
+    let mut end = None;
+    let mut count = 19;
+    let mut output = String::from("energy_profile.txt");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--coordinate" => match value {
+                "magnetization" | "wall" => {
+                    wall = value == "wall";
+                    Some(())
+                }
+                _ => None,
+            },
+            "--axis" => parse_vector(value)
+                .filter(|v| v.iter().any(|&c| c != 0.0))
+                .map(|v| axis = v),
+            "--from" => value.parse().ok().map(|v| start = Some(v)),
+            "--to" => value.parse().ok().map(|v| end = Some(v)),
+            "--points" => value
+                .parse()
+                .ok()
+                .filter(|&v: &usize| v > 0)
+                .map(|v| count = v),
+            "--output" => (!value.is_empty()).then(|| output = value.to_string()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid energy-scan option: {} {}", option, value));
+        }
+    }
+
+    let system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    let length = system.size() as f64 * system.get_cell_size();
+    let (coordinate, start, end) = if wall {
+        let easy_axis = system.easy_axis();
+        let coordinate = ReactionCoordinate::WallPosition { easy_axis };
+        (
+            coordinate,
+            start.unwrap_or(0.25 * length),
+            end.unwrap_or(0.75 * length),
+        )
+    } else {
+        let coordinate = ReactionCoordinate::AverageMagnetization { axis };
+        (coordinate, start.unwrap_or(0.9), end.unwrap_or(-0.9))
+    };
+
+    let points = EnergyScan::linear(coordinate, start, end, count)
+        .run(&system)
+        .map_err(|e| format!("Failed to scan the energy: {}", e))?;
+    write_energy_profile(Path::new(&output), &points)
+        .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    console!("{:>14} {:>14} {:>14}", "coordinate", "E (J)", "B_c (T)");
+    for point in &points {
+        console!(
+            "{:>14.6e} {:>14.6e} {:>14.6e}",
+            point.value,
+            point.energies.total(),
+            point.state.multiplier
+        );
+    }
+    if let Some(barrier) = energy_barrier(&points) {
+        console!("Barrier from the first point: {:e} J", barrier);
+    }
+    console!("Energy profile written to {}", output);
+    Ok(run.finished())
+}
+
+/// Sweep the field along `--axis` from +`--max-field` in T to minus it and
+/// back in `--steps` steps per branch, relaxing the configured system at
+/// every field, and write the M-H table as CSV and as an Excel workbook with
+/// a chart of the loop. The field is tilted off the axis by `--tilt` degrees.
+/// Usage: `hysteresis [--config simulation.toml] [--axis 1,0,0] [--max-field 0.1]
+/// [--steps 40] [--tilt 1] [--output hysteresis.csv] [--excel hysteresis.xlsx]`
+fn hysteresis(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut sweep = HysteresisLoop::default();
+    let mut axis = None;
+    let mut output = String::from("hysteresis.csv");
+    let mut excel = String::from("hysteresis.xlsx");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--axis" => parse_vector(value)
+                .filter(|v| v.iter().any(|&c| c != 0.0))
+                .map(|v| axis = Some(v)),
+            "--max-field" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| sweep.max_field = v),
+            "--steps" => value
+                .parse()
+                .ok()
+                .filter(|&v: &usize| v > 0)
+                .map(|v| sweep.steps = v),
+            "--tilt" => value.parse().ok().map(|v| sweep.tilt = v),
+            "--output" => (!value.is_empty()).then(|| output = value.to_string()),
+            "--excel" => (!value.is_empty()).then(|| excel = value.to_string()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid hysteresis option: {} {}", option, value));
+        }
+    }
+
+    let system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    // The easy axis of the material unless an axis is given
+    sweep.axis = axis.unwrap_or_else(|| system.easy_axis());
+    let points = sweep
+        .run(&system)
+        .map_err(|e| format!("Failed to run the hysteresis loop: {}", e))?;
+    write_hysteresis_csv(Path::new(&output), &points)
+        .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    export_hysteresis(&points, Path::new(&excel))
+        .map_err(|e| format!("Failed to export {}: {}", excel, e))?;
+    let format = |value: Option<f64>| value.map_or("none".to_string(), |v| format!("{:.6}", v));
+    for branch in [Branch::Descending, Branch::Ascending] {
+        console!(
+            "{:>10} branch: B_c = {} T, m_r = {}",
+            branch.label(),
+            format(coercive_field(&points, branch)),
+            format(remanence(&points, branch))
+        );
+    }
+    let unconverged = points.iter().filter(|p| !p.converged).count();
+    if unconverged > 0 {
+        console!("Warning: {} fields did not converge", unconverged);
+    }
+    console!("Hysteresis loop written to {} and {}", output, excel);
+    Ok(run.finished())
+}
+
+/// Relax the configured system over a grid of applied fields (Bx, 0, Bz)
+/// in T and classify every relaxed state, written to the `--output` table
+/// and drawn in the `--image` PNG with Bx to the right and Bz upwards.
+/// `--start` relaxes every point from the easy axis, from the configured
+/// state, or from the previous point of the same Bz for the hysteretic branch.
+/// Usage: `phase-diagram [--config simulation.toml] [--bx -0.1,0.1] [--bz -0.1,0.1]
+/// [--points 21] [--start easy-axis|system|sweep] [--output phase_diagram.txt]
+/// [--image phase_diagram.png]`
+fn phase_diagram(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut bx = (-0.1, 0.1);
+    let mut bz = (-0.1, 0.1);
+    let mut count = 21;
+    let mut start = PhaseDiagramStart::default();
+    let mut output = String::from("phase_diagram.txt");
+    let mut image = String::from("phase_diagram.png");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--bx" => parse_values(value)
+                .filter(|v| v.len() == 2)
+                .map(|v| bx = (v[0], v[1])),
+            "--bz" => parse_values(value)
+                .filter(|v| v.len() == 2)
+                .map(|v| bz = (v[0], v[1])),
+            "--points" => value
+                .parse()
+                .ok()
+                .filter(|&v: &usize| v > 0)
+                .map(|v| count = v),
+            "--start" => match value {
+                "easy-axis" => Some(PhaseDiagramStart::EasyAxis),
+                "system" => Some(PhaseDiagramStart::System),
+                "sweep" => Some(PhaseDiagramStart::Sweep),
+                _ => None,
+            }
+            .map(|v| start = v),
+            "--output" => (!value.is_empty()).then(|| output = value.to_string()),
+            "--image" => (!value.is_empty()).then(|| image = value.to_string()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!(
+                "Invalid phase-diagram option: {} {}",
+                option, value
+            ));
+        }
+    }
+
+    let system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    let diagram = PhaseDiagram {
+        start,
+        easy_axis: system.easy_axis(),
+        ..PhaseDiagram::linear(bx, bz, count)
+    };
+    let points = diagram
+        .run(&system)
+        .map_err(|e| format!("Failed to run the phase diagram: {}", e))?;
+    write_phase_table(Path::new(&output), &points)
+        .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    write_phase_image(
+        Path::new(&image),
+        &points,
+        diagram.hx.len(),
+        PHASE_POINT_PIXELS,
+    )
+    .map_err(|e| format!("Failed to write {}: {}", image, e))?;
+    for state in [
+        MagneticState::Saturated,
+        MagneticState::Parallel,
+        MagneticState::Antiparallel,
+        MagneticState::Canted,
+        MagneticState::MultiDomain,
+        MagneticState::NonUniform,
+    ] {
+        let found = points.iter().filter(|p| p.state == state).count();
+        if found > 0 {
+            let [r, g, b] = state.color();
+            console!(
+                "{:>14} {:>5} points, color #{:02x}{:02x}{:02x}",
+                state.label(),
+                found,
+                r,
+                g,
+                b
+            );
+        }
+    }
+    let unconverged = points.iter().filter(|p| !p.converged).count();
+    if unconverged > 0 {
+        console!("Warning: {} points did not converge", unconverged);
+    }
+    console!("Phase diagram written to {} and {}", output, image);
+    Ok(run.finished())
+}
+
+/// Sweep spin-transfer current pulses of every `--currents` density in A/m^2
+/// and `--durations` in s through the free layer of the configured
+/// `spin_transfer` torque, starting antiparallel to the polarizer, and write
+/// the switch/no-switch map to `--output` and the critical current density
+/// of every duration to `--boundary`. Without a configured torque the
+/// polarizer lies along the easy axis of a 2 nm layer with P = 0.7, and the
+/// currents default to 0.5..5 times its critical current density. The pulses
+/// and the relaxations run in the bias `--field` in T instead of the
+/// configured field, zero by default. A configured thermal field acts
+/// during the pulses, for thermally activated switching.
+/// Usage: `stt-switching [--config simulation.toml] [--currents 1e11,2e11]
+/// [--durations 1e-10,1e-9] [--field 0,0,0] [--time-step 1e-13] [--tilt 0.05]
+/// [--output switching.txt] [--boundary switching_boundary.txt]`
+fn stt_switching(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut currents = None;
+    let mut durations = vec![1e-10, 2e-10, 5e-10, 1e-9, 2e-9, 5e-9];
+    let mut field = [0.0; 3];
+    let mut time_step = 1e-13;
+    let mut tilt = 0.05;
+    let mut output = String::from("switching.txt");
+    let mut boundary_output = String::from("switching_boundary.txt");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--currents" => parse_values(value)
+                .filter(|v| !v.is_empty())
+                .map(|v| currents = Some(v)),
+            "--durations" => parse_values(value)
+                .filter(|v| !v.is_empty())
+                .map(|v| durations = v),
+            "--field" => parse_vector(value).map(|v| field = v),
+            "--time-step" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| time_step = v),
+            "--tilt" => value.parse().ok().map(|v| tilt = v),
+            "--output" => (!value.is_empty()).then(|| output = value.to_string()),
+            "--boundary" => (!value.is_empty()).then(|| boundary_output = value.to_string()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!(
+                "Invalid stt-switching option: {} {}",
+                option, value
+            ));
+        }
+    }
+
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    system.set_applied_field(field);
+    let material = (0..system.size())
+        .find(|&i| !system.is_vacuum(i))
+        .map_or_else(Material::default, |i| system.get_materials()[i]);
+    let torque = config.spin_transfer.unwrap_or(SpinTransferTorque {
+        current_density: 0.0,
+        polarization: 0.7,
+        polarizer: material.easy_axis,
+        thickness: 2e-9,
+        asymmetry: 1.0,
+        field_like: 0.0,
+    });
+    let critical = torque.critical_current_density(&material);
+    if config.thermal_field.is_some() && config.adaptive_time_step.is_some() {
+        return Err("The thermal field needs a fixed time step".into());
+    }
+    let diagram = SwitchingDiagram {
+        torque,
+        current_densities: currents
+            .unwrap_or_else(|| (1..=10).map(|k| 0.5 * k as f64 * critical).collect()),
+        durations,
+        dynamics: DynamicsRun {
+            time_step,
+            adaptive_time_step: config.adaptive_time_step,
+            thermal_field: config.thermal_field,
+            ..DynamicsRun::new(TimeDependentField::Constant(field))
+        },
+        initial_tilt: tilt,
+    };
+    let points = diagram
+        .run(&system)
+        .map_err(|e| format!("Failed to run the switching diagram: {}", e))?;
+    let boundary = switching_boundary(&points);
+    write_switching_map(Path::new(&output), &points)
+        .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    write_switching_boundary(Path::new(&boundary_output), &boundary)
+        .map_err(|e| format!("Failed to write {}: {}", boundary_output, e))?;
+    console!("Macrospin critical current density: {:e} A/m^2", critical);
+    for point in &boundary {
+        match point.critical_current_density {
+            Some(current) => console!("t = {:e} s: j_c = {:e} A/m^2", point.duration, current),
+            None => console!("t = {:e} s: no switching", point.duration),
+        }
+    }
+    console!(
+        "Switching map written to {} and boundary to {}",
+        output,
+        boundary_output
+    );
+    Ok(run.finished())
+}
+
+/// Integrate the LLG equation in a constant or rotating field, record
+/// table.txt and the averaged m(t) in timeseries.txt every `--sample-interval` s.
+/// A rotating field is given as amplitude in T and frequency in Hz. With
+/// `--mode-frequencies` the spatial spin wave maps at these frequencies in Hz
+/// are exported to modes.xlsx. `--antenna` adds a local microwave field
+/// given as first cell, last cell, amplitude in T and frequency in Hz.
+/// `--moving-frame` shifts the window to keep the domain wall within the
+/// given number of cells of the center. `--snapshots` streams the state of
+/// every cell to a CSV file or a directory of OVF files, once per
+/// `--snapshot-interval`, which defaults to the sample interval.
+/// `--cores` tracks the vortex and antivortex cores of a 2D grid once per
+/// snapshot interval and writes their trajectories for gyration studies.
+/// `--skyrmion` fits the radius and center of a skyrmion of a 2D grid once
+/// per snapshot interval, writes the trajectory and reports the drift
+/// velocity and the skyrmion Hall angle from the `--drive-direction` in xy.
+/// `--temperature` adds Brown's thermal field at the temperature in K, with
+/// the noise of `--seed`, and overrides the config table.
+/// `--adaptive-time-step` controls the step by step doubling, given as
+/// tolerance, min and max step in s, and overrides the config table. The
+/// steps end on every multiple of the shorter of the sample and snapshot
+/// intervals, so the samples and snapshots are taken at exact times with
+/// fixed and adaptive steps alike when one interval is a multiple of the
+/// other.
+/// Usage: `dynamics [--config simulation.toml] [--duration 1e-9] [--time-step 1e-14]
+/// [--sample-interval 1e-12] [--field bx,by,bz] [--rotating 0.1,1e9] [--plane xy]
+/// [--mode-frequencies 1e10,2e10] [--integrator euler|heun|rk4] [--spin-update normalize|quaternion]
+/// [--adaptive-time-step 1e-5,1e-16,1e-12]
+/// [--antenna 0,9,0.001,2e10] [--antenna-direction 0,1,0] [--antenna-profile uniform|gaussian|hann]
+/// [--moving-frame 1] [--snapshots snapshots.csv] [--snapshot-format csv|ovf] [--snapshot-interval 1e-11]
+/// [--cores cores.txt] [--skyrmion skyrmion.txt] [--drive-direction 1,0] [--temperature 300] [--seed 0]`
+fn dynamics(run: &mut Run, args: &[String]) -> CommandResult {
+    let started = SystemTime::now();
+    let config = run.config.clone();
+    let mut duration = 1e-9;
+    let mut time_step = None;
+    let mut sample_interval = 1e-12;
+    let mut field = None;
+    let mut rotating = None;
+    let mut plane = plane_axes("xy").expect("valid plane");
+    let mut mode_frequencies = Vec::new();
+    let mut integrator = Integrator::default();
+    let mut spin_update = SpinUpdate::default();
+    let mut adaptive_time_step = None;
+    let mut antenna = None;
+    let mut antenna_direction = [0.0, 1.0, 0.0];
+    let mut antenna_profile = AntennaProfile::default();
+    let mut moving_frame = None;
+    let mut snapshots = None;
+    let mut snapshot_format = SnapshotFormat::default();
+    let mut snapshot_interval = None;
+    let mut cores = None;
+    let mut skyrmion = None;
+    let mut drive_direction = [1.0, 0.0];
+    let mut temperature = None;
+    let mut seed = None;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--duration" => value.parse().ok().map(|v| duration = v),
+            "--time-step" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| time_step = Some(v)),
+            "--sample-interval" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v >= 0.0)
+                .map(|v| sample_interval = v),
+            "--field" => parse_vector(value).map(|v| field = Some(v)),
+            "--rotating" => parse_values(value)
+                .filter(|v| v.len() == 2)
+                .map(|v| rotating = Some((v[0], v[1]))),
+            "--plane" => plane_axes(value).map(|axes| plane = axes),
+            "--mode-frequencies" => parse_values(value).map(|v| mode_frequencies = v),
+            "--integrator" => value.parse().ok().map(|v| integrator = v),
+            "--spin-update" => match value {
+                "normalize" => Some(SpinUpdate::Normalize),
+                "quaternion" => Some(SpinUpdate::Quaternion),
+                _ => None,
+            }
+            .map(|v| spin_update = v),
+            "--adaptive-time-step" => parse_values(value)
+                .filter(|v| v.len() == 3)
+                .map(|v| AdaptiveTimeStep {
+                    tolerance: v[0],
+                    min_step: v[1],
+                    max_step: v[2],
+                })
+                .filter(|adaptive| adaptive.validate().is_ok())
+                .map(|adaptive| adaptive_time_step = Some(adaptive)),
+            "--antenna" => parse_values(value)
+                .filter(|v| v.len() == 4 && v[0] >= 0.0 && v[1] >= 0.0)
+                .map(|v| antenna = Some((v[0] as usize, v[1] as usize, v[2], v[3]))),
+            "--antenna-direction" => parse_vector(value).map(|v| antenna_direction = v),
+            "--antenna-profile" => value.parse().ok().map(|p| antenna_profile = p),
+            "--moving-frame" => value.parse().ok().map(|tolerance| {
+                moving_frame = Some(MovingFrame {
+                    tolerance,
+                    ..MovingFrame::default()
+                })
+            }),
+            "--snapshots" => (!value.is_empty()).then(|| snapshots = Some(value.to_string())),
+            "--snapshot-format" => value.parse().ok().map(|f| snapshot_format = f),
+            "--snapshot-interval" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v >= 0.0)
+                .map(|v| snapshot_interval = Some(v)),
+            "--cores" => (!value.is_empty()).then(|| cores = Some(value.to_string())),
+            "--skyrmion" => (!value.is_empty()).then(|| skyrmion = Some(value.to_string())),
+            "--drive-direction" => parse_values(value)
+                .filter(|v| v.len() == 2 && (v[0] != 0.0 || v[1] != 0.0))
+                .map(|v| drive_direction = [v[0], v[1]]),
+            "--temperature" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v >= 0.0)
+                .map(|v| temperature = Some(v)),
+            "--seed" => value.parse().ok().map(|v| seed = Some(v)),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid dynamics option: {} {}", option, value));
+        }
+    }
+
+    if !mode_frequencies.is_empty() && sample_interval == 0.0 {
+        return Err("Mode maps need a positive --sample-interval".into());
+    }
+
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    // The constant field acts as the bias of a rotating field
+    let field = field.unwrap_or(config.applied_field);
+    let applied_field = match rotating {
+        Some((amplitude, frequency)) => {
+            match RotatingField::new(amplitude, frequency, plane.0, plane.1) {
+                Ok(mut rotating) => {
+                    rotating.bias = field;
+                    TimeDependentField::Rotating(rotating)
+                }
+                Err(e) => return Err(format!("Invalid rotating field: {}", e)),
+            }
+        }
+        None => TimeDependentField::Constant(field),
+    };
+    let mut simulation = DynamicsRun::new(applied_field);
+    simulation.integrator = integrator;
+    simulation.spin_update = spin_update;
+    simulation.moving_frame = moving_frame;
+    simulation.spin_accumulation = config.spin_accumulation;
+    simulation.spin_transfer = config.spin_transfer;
+    simulation.thermal_field = match (temperature, config.thermal_field) {
+        (Some(temperature), thermal) => Some(ThermalField {
+            temperature,
+            seed: seed.or(thermal.map(|t| t.seed)).unwrap_or(0),
+        }),
+        (None, Some(thermal)) => Some(ThermalField {
+            seed: seed.unwrap_or(thermal.seed),
+            ..thermal
+        }),
+        (None, None) => None,
+    };
+    simulation.adaptive_time_step = adaptive_time_step.or(config.adaptive_time_step);
+    if let Some(time_step) = time_step {
+        simulation.time_step = time_step;
+    }
+    if simulation.thermal_field.is_some() && simulation.adaptive_time_step.is_some() {
+        return Err("The thermal field needs a fixed time step".into());
+    }
+    simulation.output_interval = [
+        sample_interval,
+        snapshot_interval.unwrap_or(sample_interval),
+    ]
+    .into_iter()
+    .filter(|&interval| interval > 0.0)
+    .reduce(f64::min);
+    if let Some((start, end, amplitude, frequency)) = antenna {
+        match Antenna::new(start, end, amplitude, frequency, antenna_direction) {
+            Ok(mut antenna) => {
+                antenna.profile = antenna_profile;
+                simulation.antenna = Some(antenna);
+            }
+            Err(e) => return Err(format!("Invalid antenna: {}", e)),
+        }
+    }
+
+    let output = open_output(&config, "dynamics", args)?;
+    let mut table = TableWriter::create(&output.file("table.txt"))
+        .map_err(|e| format!("Failed to create table.txt: {}", e))?;
+    let mut time_series = match TimeSeriesWriter::create(
+        &output.file("timeseries.txt"),
+        sample_interval,
+        &config.time_series_columns,
+    ) {
+        Ok(time_series) => time_series.with_readout(config.magnetoresistance),
+        Err(e) => return Err(format!("Failed to create timeseries.txt: {}", e)),
+    };
+    let mut snapshot_writer = match &snapshots {
+        Some(path) => match SnapshotWriter::create(
+            Path::new(path),
+            snapshot_format,
+            snapshot_interval.unwrap_or(sample_interval),
+        ) {
+            Ok(writer) => Some(writer.with_decimation(config.decimation)),
+            Err(e) => return Err(format!("Failed to create {}: {}", path, e)),
+        },
+        None => None,
+    };
+    if (cores.is_some() || skyrmion.is_some()) && system.get_grid().ny < 2 {
+        return Err(
+            "Core and skyrmion tracking need a grid with more than one row of cells".into(),
+        );
+    }
+    let mut core_tracker = cores.as_ref().map(|_| {
+        CoreTracker::new(
+            snapshot_interval.unwrap_or(sample_interval),
+            system.get_cell_size(),
+        )
+    });
+    let mut skyrmion_tracker = skyrmion
+        .as_ref()
+        .map(|_| SkyrmionTracker::new(snapshot_interval.unwrap_or(sample_interval)));
+    // The per-cell history is only kept when mode maps are requested
+    let mut history = MagnetizationHistory::new(sample_interval);
+    let mut result = Ok(());
+    let mut step = 0;
+    simulation.run(&mut system, duration, |time, system| {
+        if !mode_frequencies.is_empty() {
+            history.record(time, system);
+        }
+        if step % TABLE_INTERVAL == 0 && result.is_ok() {
+            result = table.write_row(time, system);
+        }
+        if result.is_ok() {
+            result = time_series.record(time, system).map(|_| ());
+        }
+        if let (Some(writer), true) = (&mut snapshot_writer, result.is_ok()) {
+            result = writer.record(time, system).map(|_| ());
+        }
+        if let Some(tracker) = &mut core_tracker {
+            tracker.record(time, system);
+        }
+        if let Some(tracker) = &mut skyrmion_tracker {
+            tracker.record(time, system);
+        }
+        step += 1;
+    });
+    result
+        .and_then(|_| table.flush())
+        .and_then(|_| time_series.flush())
+        .map_err(|e| format!("Failed to write the output tables: {}", e))?;
+    let m = system.average_magnetization();
+    console!("Final <m> = ({:.6}, {:.6}, {:.6})", m[0], m[1], m[2]);
+    log(
+        &output,
+        &format!("Final <m> = ({:.6}, {:.6}, {:.6})", m[0], m[1], m[2]),
+    );
+    if moving_frame.is_some() {
+        console!("The window moved by {:e} m", system.frame_offset());
+    }
+    if let (Some(writer), Some(path)) = (&snapshot_writer, &snapshots) {
+        console!("{} snapshots written to {}", writer.count(), path);
+    }
+    if let (Some(tracker), Some(path)) = (&core_tracker, &cores) {
+        write_core_trajectories(Path::new(path), tracker.trajectories())
+            .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+        console!(
+            "{} core trajectories written to {}",
+            tracker.trajectories().len(),
+            path
+        );
+    }
+    if let (Some(tracker), Some(path)) = (&skyrmion_tracker, &skyrmion) {
+        write_skyrmion_trajectory(Path::new(path), tracker.samples())
+            .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+        if let Some((_, last)) = tracker.samples().last() {
+            console!(
+                "Skyrmion radius {:e} m, wall width {:e} m",
+                last.radius,
+                last.wall_width
+            );
+        }
+        match (tracker.velocity(), tracker.hall_angle(drive_direction)) {
+            (Some(v), Some(angle)) => {
+                let line = format!(
+                    "Skyrmion velocity ({:e}, {:e}) m/s, Hall angle {:.3} deg",
+                    v[0],
+                    v[1],
+                    angle.to_degrees()
+                );
+                console!("{}", line);
+                log(&output, &line);
+            }
+            _ => console!("The skyrmion did not move"),
+        }
+        console!(
+            "{} skyrmion fits written to {}",
+            tracker.samples().len(),
+            path
+        );
+    }
+    write_summary(
+        &output,
+        RunRecord::new(RunSummary::finished("dynamics"), &config, started)
+            .with_final_state(&system),
+    );
+
+    if !mode_frequencies.is_empty() {
+        let maps = history.mode_maps(&mode_frequencies);
+        export_mode_maps(&maps, system.get_cell_size(), &output.file("modes.xlsx"))
+            .map_err(|e| format!("Failed to export the mode maps: {}", e))?;
+    }
+    Ok(run.finished())
+}
+
+/// Relax the configured system, tilt it and let it ring down, then export
+/// the spatial profile of every detected eigenmode to eigenmodes.xlsx.
+/// Usage: `ringdown [--config simulation.toml] [--duration 2e-9]
+/// [--sample-interval 1e-12] [--tilt 5] [--threshold 0.05]`
+fn ringdown_modes(run: &mut Run, args: &[String]) -> CommandResult {
+    let started = SystemTime::now();
+    let config = run.config.clone();
+    let mut duration = 2e-9;
+    let mut sample_interval = 1e-12;
+    let mut tilt_degrees = 5.0;
+    let mut threshold = DEFAULT_PEAK_THRESHOLD;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let positive = || value.parse().ok().filter(|&v: &f64| v > 0.0);
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--duration" => positive().map(|v| duration = v),
+            "--sample-interval" => positive().map(|v| sample_interval = v),
+            "--tilt" => positive().map(|v| tilt_degrees = v),
+            "--threshold" => positive().map(|v| threshold = v),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid ringdown option: {} {}", option, value));
+        }
+    }
+
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    let output = open_output(&config, "ringdown", args)?;
+    system.minimize_energy();
+    excite_ringdown(&mut system, tilt_degrees.to_radians());
+
+    let simulation = DynamicsRun::new(TimeDependentField::Constant(config.applied_field));
+    let history = ringdown(&mut system, &simulation, duration, sample_interval);
+    let modes = history.eigenmodes(threshold);
+    if modes.is_empty() {
+        console!("No modes detected");
+    }
+    for (j, mode) in modes.iter().enumerate() {
+        console!("Mode {}: {:.4} GHz", j + 1, mode.frequency * 1e-9);
+        log(
+            &output,
+            &format!("Mode {}: {:.4} GHz", j + 1, mode.frequency * 1e-9),
+        );
+    }
+    export_mode_maps(
+        &modes,
+        system.get_cell_size(),
+        &output.file("eigenmodes.xlsx"),
+    )
+    .map_err(|e| format!("Failed to export the eigenmodes: {}", e))?;
+    write_summary(
+        &output,
+        RunRecord::new(RunSummary::finished("ringdown"), &config, started)
+            .with_final_state(&system),
+    );
+    Ok(run.finished())
+}
+
+/// Relax the configured system and compute its eigenmodes from the LLG
+/// equation linearized around the relaxed state, without time stepping.
+/// Usage: `modes [--config simulation.toml] [--count 10] [--output normal_modes.xlsx]`
+fn modes(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut count = 10;
+    let mut output = String::from("normal_modes.xlsx");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--count" => value.parse().ok().map(|v| count = v),
+            "--output" => (!value.is_empty()).then(|| output = value.to_string()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid modes option: {} {}", option, value));
+        }
+    }
+
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    system.minimize_energy();
+    let mut modes = normal_modes(&system).map_err(|e| format!("Mode analysis failed: {}", e))?;
+    modes.truncate(count);
+    for (j, mode) in modes.iter().enumerate() {
+        console!("Mode {}: {:.4} GHz", j + 1, mode.frequency * 1e-9);
+    }
+    export_mode_maps(&modes, system.get_cell_size(), Path::new(&output))
+        .map_err(|e| format!("Failed to export the modes: {}", e))?;
+    Ok(run.finished())
+}
+
+/// Relax the configured system and compute its FMR absorption spectrum
+/// from the steady state of the linearized LLG equation under a uniform
+/// drive field in T, `--points` frequencies from `--from` to `--to` in Hz.
+/// Usage: `fmr [--config simulation.toml] [--from 1e9] [--to 5e10] [--points 491]
+/// [--drive 0,1e-4,0] [--output fmr_spectrum.txt]`
+fn fmr(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut start = 1e9;
+    let mut end = 5e10;
+    let mut count = 491;
+    let mut drive = [0.0, 1e-4, 0.0];
+    let mut output = String::from("fmr_spectrum.txt");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let positive = || value.parse().ok().filter(|&v: &f64| v > 0.0);
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--from" => positive().map(|v| start = v),
+            "--to" => positive().map(|v| end = v),
+            "--points" => value
+                .parse()
+                .ok()
+                .filter(|&v: &usize| v > 0)
+                .map(|v| count = v),
+            "--drive" => parse_vector(value).map(|v| drive = v),
+            "--output" => (!value.is_empty()).then(|| output = value.to_string()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid fmr option: {} {}", option, value));
+        }
+    }
+
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    system.minimize_energy();
+    let frequencies: Vec<f64> = (0..count)
+        .map(|j| {
+            let fraction = if count > 1 {
+                j as f64 / (count - 1) as f64
+            } else {
+                0.0
+            };
+            start + (end - start) * fraction
+        })
+        .collect();
+    let spectrum = SteadyStateSolver::new(&system).spectrum(&frequencies, drive);
+    write_spectrum(Path::new(&output), &spectrum)
+        .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    if let Some(peak) = spectrum
+        .iter()
+        .max_by(|a, b| a.absorbed_power.total_cmp(&b.absorbed_power))
+    {
+        console!(
+            "Spectrum at {} frequencies written to {}, absorption peak at {:.4} GHz ({:e} W)",
+            spectrum.len(),
+            output,
+            peak.response.frequency * 1e-9,
+            peak.absorbed_power
+        );
+    }
+    Ok(run.finished())
+}
+
+/// Detect the switches of a superparamagnet in a time series of the
+/// dynamics and report the dwell times in the two wells and their histogram.
+/// Usage: `telegraph --input time_series.txt [--column mx] [--threshold 0.5] [--bins 10]
+/// [--output dwell_times.txt] [--config simulation.toml]`
+fn telegraph(run: &mut Run, args: &[String]) -> CommandResult {
+    let mut input = None;
+    let mut column = TimeSeriesColumn::Mx;
+    let mut threshold = 0.5;
+    let mut bins = 10;
+    let mut output = String::from("dwell_times.txt");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--input" => (!value.is_empty()).then(|| input = Some(value.to_string())),
+            "--column" => value.parse().ok().map(|c| column = c),
+            "--threshold" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| threshold = v),
+            "--bins" => value.parse().ok().filter(|&v| v > 0).map(|v| bins = v),
+            "--output" => (!value.is_empty()).then(|| output = value.to_string()),
+            // Loaded by run_command for the completion hooks
+            "--config" => Some(()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid telegraph option: {} {}", option, value));
+        }
+    }
+    let Some(input) = input else {
+        return Err("Give the time series with --input".into());
+    };
+
+    let analysis = read_time_series_column(Path::new(&input), column)
+        .and_then(|(times, values)| TelegraphAnalysis::analyze(&times, &values, threshold))
+        .map_err(|e| format!("Failed to analyze {}: {}", input, e))?;
+    write_dwell_times(Path::new(&output), &analysis)
+        .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    console!(
+        "{} switches, mean switching time {:e} s ({:e} switches/s)",
+        analysis.switching_times.len(),
+        analysis.mean_switching_time,
+        analysis.switching_rate()
+    );
+    for (name, statistics) in [("up", &analysis.up), ("down", &analysis.down)] {
+        console!(
+            "Well {}: {} complete stays, dwell time {:e} +- {:e} s",
+            name,
+            statistics.dwell_times.len(),
+            statistics.mean,
+            statistics.standard_deviation
+        );
+        for (edge, count) in statistics.histogram(bins) {
+            console!("  from {:e} s: {}", edge, count);
+        }
+    }
+    console!("Dwell times written to {}", output);
+    Ok(run.finished())
+}
+
+/// Relax the configured system and evaluate its stray field along a line
+/// of observation points, by default 10 nm above the chain.
+/// Usage: `stray-field [--config simulation.toml] [--from x,y,z] [--to x,y,z] [--points 101]
+/// [--output strayfield.txt]`
+fn stray_field(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut start = None;
+    let mut end = None;
+    let mut count = 101;
+    let mut output = String::from("strayfield.txt");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--from" => parse_vector(value).map(|v| start = Some(v)),
+            "--to" => parse_vector(value).map(|v| end = Some(v)),
+            "--points" => value
+                .parse()
+                .ok()
+                .filter(|&v: &usize| v > 0)
+                .map(|v| count = v),
+            "--output" => {
+                output = value.to_string();
+                Some(())
+            }
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid stray-field option: {} {}", option, value));
+        }
+    }
+
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    system.minimize_energy();
+
+    let height = 10.0 * system.get_cell_size();
+    let length = system.size() as f64 * system.get_cell_size();
+    let start = start.unwrap_or([-height, 0.0, height]);
+    let end = end.unwrap_or([length + height, 0.0, height]);
+    let points = line_points(start, end, count);
+    let fields = compute_stray_field(&system, &points);
+    write_stray_field(Path::new(&output), &points, &fields)
+        .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    let largest = fields
+        .iter()
+        .map(|h| h.iter().map(|c| c * c).sum::<f64>().sqrt())
+        .fold(0.0, f64::max);
+    console!(
+        "Stray field at {} points written to {}, largest |H| = {:e} A/m",
+        points.len(),
+        output,
+        largest
+    );
+    Ok(run.finished())
+}
+
+/// Relax the configured system and simulate an MFM image at the lift height.
+/// Usage: `mfm [--config simulation.toml] [--lift 10e-9] [--pixel 1e-9] [--output mfm.txt]`
+fn mfm(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut lift_height = 10e-9;
+    let mut pixel_size = 1e-9;
+    let mut output = String::from("mfm.txt");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let positive = || value.parse().ok().filter(|&v: &f64| v > 0.0);
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--lift" => positive().map(|v| lift_height = v),
+            "--pixel" => positive().map(|v| pixel_size = v),
+            "--output" => {
+                output = value.to_string();
+                Some(())
+            }
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid mfm option: {} {}", option, value));
+        }
+    }
+
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    system.minimize_energy();
+
+    let scan = MfmScan::over_chain(
+        system.size(),
+        system.get_cell_size(),
+        lift_height,
+        pixel_size,
+    );
+    let image = scan.run(&system);
+    image
+        .write_grid(Path::new(&output))
+        .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    console!(
+        "MFM image of {} x {} pixels at {:e} m lift written to {}",
+        scan.resolution.0,
+        scan.resolution.1,
+        lift_height,
+        output
+    );
+    Ok(run.finished())
+}
+
+/// Record the relaxation of the configured problem as an animated GIF.
+/// Usage: `animate [--config simulation.toml] [--interval 100] [--fps 10]
+/// [--component x|y|z] [--color-map heatmap|grayscale|hsl] [--output relaxation.gif]`
+fn animate(run: &mut Run, args: &[String]) -> CommandResult {
+    let config = run.config.clone();
+    let mut interval = 100;
+    let mut frame_rate = 10.0;
+    let mut component = Component::default();
+    let mut color_map = ColorMap::default();
+    let mut output = String::from("relaxation.gif");
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--config" => Some(()),
+            "--interval" => value
+                .parse()
+                .ok()
+                .filter(|&v: &usize| v > 0)
+                .map(|v| interval = v),
+            "--fps" => value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v > 0.0)
+                .map(|v| frame_rate = v),
+            "--component" => value.parse().ok().map(|v| component = v),
+            "--color-map" => value.parse().ok().map(|v| color_map = v),
+            "--output" => {
+                output = value.to_string();
+                Some(())
+            }
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid animate option: {} {}", option, value));
+        }
+    }
+
+    let mut system = config
+        .build_system()
+        .map_err(|e| format!("Failed to set up the system: {}", e))?;
+    let width = image_width(system.get_grid()).map_err(|e| format!("Cannot animate: {}", e))?;
+    let mut recorder = AnimationRecorder::new(interval, component, color_map);
+    system.minimize_energy_with(|step, system| recorder.record(step, system));
+    recorder.finish(&system);
+
+    recorder
+        .write_gif(Path::new(&output), width, IMAGE_CELL_PIXELS, frame_rate)
+        .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    console!(
+        "Animation of {} frames at {} fps written to {}",
+        recorder.frames.len(),
+        frame_rate,
+        output
+    );
+    Ok(run.finished())
+}
+
+/// Print a scaling table of the field evaluation and relaxation steps.
+/// Usage: `bench [--sizes 1000,10000] [--threads 1,2,4] [--steps 100] [--config simulation.toml]`
+fn bench(run: &mut Run, args: &[String]) -> CommandResult {
+    let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut sizes = vec![1_000, 10_000, 100_000];
+    let mut threads: Vec<usize> = (0..)
+        .map(|k| 1usize << k)
+        .take_while(|&t| t <= available)
+        .collect();
+    let mut steps = 20;
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--sizes" => parse_list(value).map(|v| sizes = v),
+            "--threads" => parse_list(value).map(|v| threads = v),
+            "--steps" => value.parse().ok().map(|v| steps = v),
+            // Loaded by run_command for the completion hooks
+            "--config" => Some(()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid bench option: {} {}", option, value));
+        }
+    }
+
+    let results = run_scaling_benchmark(&sizes, &threads, steps)
+        .map_err(|e| format!("Benchmark failed: {}", e))?;
+    print_scaling_table(&results);
+    Ok(run.finished())
+}
+
+/// Write a family of benchmark configurations of increasing size and
+/// enabled terms with the reference energies of the analytic cases. The
+/// cells have the cell size of the config.
+/// Usage: `gen-bench [--sizes 100,1000,10000] [--output bench] [--config simulation.toml]`
+fn gen_bench(run: &mut Run, args: &[String]) -> CommandResult {
+    let mut sizes = vec![100, 1_000, 10_000];
+    let mut output = "bench".to_string();
+
+    let mut options = args.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().map(String::as_str).unwrap_or("");
+        let parsed = match option.as_str() {
+            "--sizes" => parse_list(value).map(|v| sizes = v),
+            "--output" if !value.is_empty() => {
+                output = value.to_string();
+                Some(())
+            }
+            // Loaded by run_command for the completion hooks
+            "--config" => Some(()),
+            _ => None,
+        };
+        if parsed.is_none() {
+            return Err(format!("Invalid gen-bench option: {} {}", option, value));
+        }
+    }
+
+    let cases = benchmark_cases(&sizes, run.config.cell_size);
+    write_benchmark_cases(&cases, Path::new(&output))
+        .map_err(|e| format!("Failed to write the benchmark configurations: {}", e))?;
+    console!(
+        "{:>16} {:>10} {:>30} {:>24}",
+        "config",
+        "cells",
+        "terms",
+        "reference (J)"
+    );
+    for case in &cases {
+        let reference = case
+            .reference_energy
+            .map_or("-".to_string(), |energy| format!("{:e}", energy));
+        console!(
+            "{:>16} {:>10} {:>30} {:>24}",
+            case.name,
+            case.config.number_of_cells,
+            case.terms,
+            reference
+        );
+    }
+    console!("{} configurations written to {}", cases.len(), output);
+    Ok(run.finished())
+}
+
+/// Parse a comma separated list of positive integers.
+fn parse_list(value: &str) -> Option<Vec<usize>> {
+    value
+        .split(',')
+        .map(|v| v.trim().parse().ok().filter(|&n: &usize| n > 0))
+        .collect()
+}
+
+/// Parse a comma separated list of numbers.
+fn parse_values(value: &str) -> Option<Vec<f64>> {
+    value.split(',').map(|v| v.trim().parse().ok()).collect()
+}
+
+/// Parse a comma separated vector with three components.
+fn parse_vector(value: &str) -> Option<[f64; 3]> {
+    let values = parse_values(value)?;
+    values.try_into().ok()
+}
@@ -0,0 +1,336 @@
+#[cfg(not(unix))]
+compile_error!("The mmap feature is only supported on unix targets");
+
+use crate::grid::{BoundaryCondition, Grid};
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::parameters::SimulationParameters;
+use memmap2::MmapMut;
+use ndarray::Array1;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+// Bytes of the three components of a cell
+const CELL_BYTES: usize = 3 * size_of::<f64>();
+
+///# Mapped Magnetization
+/// Magnetization of a grid kept in a file that is mapped into memory, so
+/// the operating system pages the cells in and out and a grid that does
+/// not fit into the RAM can still be relaxed. The file holds the three
+/// components of every cell as native endian f64 values, with x varying
+/// fastest, then y, then z, and no header. A zero vector marks a vacuum
+/// cell. Changes reach the file when the store is flushed or dropped.
+pub struct MappedMagnetization {
+    grid: Grid,
+    map: MmapMut,
+}
+
+impl MappedMagnetization {
+    ///# Create Mapped Magnetization
+    /// Create or truncate the file at `path` and set every cell of the
+    /// grid to the normalized `direction`.
+    pub fn create(path: &Path, grid: Grid, direction: [f64; 3]) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(Self::bytes(grid)? as u64)?;
+        let mut store = Self::map(&file, grid)?;
+        for cell in 0..grid.size() {
+            store.set(cell, direction);
+        }
+        Ok(store)
+    }
+
+    ///# Open Mapped Magnetization
+    /// Map an existing file of the grid, e.g. to continue a relaxation.
+    pub fn open(path: &Path, grid: Grid) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        if file.metadata()?.len() != Self::bytes(grid)? as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} does not hold a {}x{}x{} grid",
+                    path.display(),
+                    grid.nx,
+                    grid.ny,
+                    grid.nz
+                ),
+            ));
+        }
+        Self::map(&file, grid)
+    }
+
+    // Size of the file of the grid in bytes
+    fn bytes(grid: Grid) -> io::Result<usize> {
+        if grid.size() == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the grid needs at least one cell",
+            ));
+        }
+        grid.size()
+            .checked_mul(CELL_BYTES)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the grid is too large"))
+    }
+
+    fn map(file: &File, grid: Grid) -> io::Result<Self> {
+        // SAFETY: the mapping is only valid as long as no other process
+        // truncates the file, which the store owns for its lifetime
+        let map = unsafe { MmapMut::map_mut(file)? };
+        Ok(Self { grid, map })
+    }
+
+    ///# Grid
+    pub fn grid(&self) -> Grid {
+        self.grid
+    }
+
+    ///# Get Magnetization
+    pub fn get(&self, cell: usize) -> [f64; 3] {
+        let bytes = &self.map[cell * CELL_BYTES..(cell + 1) * CELL_BYTES];
+        let mut magnetization = [0.0; 3];
+        for (component, chunk) in magnetization.iter_mut().zip(bytes.chunks_exact(8)) {
+            *component = f64::from_ne_bytes(chunk.try_into().unwrap());
+        }
+        magnetization
+    }
+
+    ///# Set Magnetization
+    /// Set the normalized direction of a cell, a zero vector makes the cell vacuum.
+    pub fn set(&mut self, cell: usize, magnetization: [f64; 3]) {
+        let norm = magnetization.iter().map(|c| c * c).sum::<f64>().sqrt();
+        let direction = if norm > 0.0 {
+            magnetization.map(|c| c / norm)
+        } else {
+            [0.0; 3]
+        };
+        let bytes = &mut self.map[cell * CELL_BYTES..(cell + 1) * CELL_BYTES];
+        for (component, chunk) in direction.iter().zip(bytes.chunks_exact_mut(8)) {
+            chunk.copy_from_slice(&component.to_ne_bytes());
+        }
+    }
+
+    ///# Average Magnetization
+    /// Mean direction of the magnetic cells, read in one pass over the file.
+    pub fn average_magnetization(&self) -> [f64; 3] {
+        let mut sum = [0.0; 3];
+        let mut count = 0usize;
+        for cell in 0..self.grid.size() {
+            let m = self.get(cell);
+            if m != [0.0; 3] {
+                for k in 0..3 {
+                    sum[k] += m[k];
+                }
+                count += 1;
+            }
+        }
+        sum.map(|c| c / count.max(1) as f64)
+    }
+
+    ///# Flush
+    /// Write the changed pages back to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        self.map.flush()
+    }
+}
+
+///# Chunked Relaxation
+/// Relaxes a mapped grid slab by slab along z, so only the slab of
+/// `layers` z layers is held as a system in memory. The layers next to
+/// the slab enter as the exchange field of their fixed magnetization, a
+/// local field on the outer layers of the slab, and the passes over all
+/// slabs are repeated until the largest change of a magnetization
+/// component in a pass falls below the tolerance. The halo only carries
+/// the nearest neighbor exchange, so the dipolar field, the DMI and the
+/// second neighbor exchange are not available, and the outer faces of
+/// the grid are free. The exchange to the fixed neighbors holds a slab
+/// back when the whole grid rotates, so a uniform rotation against a weak
+/// field or anisotropy takes many passes, and a relaxed coarse state is a
+/// better start than a random one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkedRelaxation {
+    // z layers relaxed together
+    pub layers: usize,
+    // Passes over all slabs after which the relaxation gives up
+    pub max_sweeps: usize,
+    // Largest change of a component in a pass of a relaxed grid
+    pub tolerance: f64,
+}
+
+impl Default for ChunkedRelaxation {
+    fn default() -> Self {
+        Self {
+            layers: 8,
+            max_sweeps: 1000,
+            tolerance: 1e-4,
+        }
+    }
+}
+
+///# Chunked Relaxation Report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkedReport {
+    pub sweeps: usize,
+    // Largest change of a component in the last pass
+    pub max_change: f64,
+    pub converged: bool,
+}
+
+impl ChunkedRelaxation {
+    ///# Validate
+    pub fn validate(&self, parameters: &SimulationParameters) -> Result<(), Box<dyn Error>> {
+        if self.layers == 0 || self.max_sweeps == 0 {
+            return Err("The chunked relaxation needs at least one layer and one sweep".into());
+        }
+        if self.tolerance.is_nan() || self.tolerance <= 0.0 {
+            return Err("The chunked relaxation tolerance must be positive".into());
+        }
+        if parameters.boundary_condition != BoundaryCondition::Free {
+            return Err("The chunked relaxation only supports free boundaries".into());
+        }
+        let material = &parameters.material;
+        if material.dmi_constant != 0.0 || material.second_neighbor_exchange_constant != 0.0 {
+            return Err(
+                "The chunked relaxation does not support the DMI or the second neighbor exchange"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+
+    ///# Relax
+    /// Relax the mapped magnetization with the material, field and solver
+    /// settings of the parameters, writing every relaxed slab back to the store.
+    pub fn relax(
+        &self,
+        store: &mut MappedMagnetization,
+        parameters: SimulationParameters,
+    ) -> Result<ChunkedReport, Box<dyn Error>> {
+        self.validate(&parameters)?;
+        let grid = store.grid();
+        let layer = grid.nx * grid.ny;
+        let material = parameters.material;
+        // Field in T of a neighbor with the magnetization m, 2 A m / (Ms dx^2),
+        // leaving out the part along m_i, which exerts no torque
+        let halo_coupling = if material.is_vacuum() {
+            0.0
+        } else {
+            2.0 * material.exchange_constant
+                / (material.saturation_magnetization * parameters.cell_size * parameters.cell_size)
+        };
+
+        let mut report = ChunkedReport {
+            sweeps: 0,
+            max_change: f64::INFINITY,
+            converged: false,
+        };
+        while report.sweeps < self.max_sweeps && !report.converged {
+            let mut max_change: f64 = 0.0;
+            for first in (0..grid.nz).step_by(self.layers) {
+                let last = (first + self.layers).min(grid.nz);
+                let offset = first * layer;
+                let cells = (last - first) * layer;
+                let mut system = MicromagneticSystem::on_grid(
+                    Grid::new(grid.nx, grid.ny, last - first),
+                    parameters,
+                );
+                for i in 0..cells {
+                    let m = store.get(offset + i);
+                    if m == [0.0; 3] {
+                        system.set_saturation_magnetization(i, 0.0);
+                    } else {
+                        system.set_magnetization(i, Array1::from_vec(m.to_vec()));
+                    }
+                }
+                let mut local_fields = vec![[0.0; 3]; cells];
+                for xy in 0..layer {
+                    if first > 0 {
+                        let m = store.get(offset - layer + xy);
+                        add_scaled(&mut local_fields[xy], halo_coupling, m);
+                    }
+                    if last < grid.nz {
+                        let m = store.get(last * layer + xy);
+                        add_scaled(&mut local_fields[cells - layer + xy], halo_coupling, m);
+                    }
+                }
+                system.set_local_fields(local_fields)?;
+                system.minimize_energy();
+
+                for (i, m) in system.get_magnetizations().iter().enumerate() {
+                    let old = store.get(offset + i);
+                    for k in 0..3 {
+                        max_change = max_change.max((m[k] - old[k]).abs());
+                    }
+                    store.set(offset + i, [m[0], m[1], m[2]]);
+                }
+            }
+            report.sweeps += 1;
+            report.max_change = max_change;
+            report.converged = max_change < self.tolerance;
+        }
+        store.flush()?;
+        Ok(report)
+    }
+}
+
+fn add_scaled(field: &mut [f64; 3], scale: f64, m: [f64; 3]) {
+    for k in 0..3 {
+        field[k] += scale * m[k];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that the store survives reopening and that the chunked
+    /// relaxation reaches the state of relaxing the whole grid at once
+    fn test_chunked_relaxation() {
+        let grid = Grid::new(2, 1, 6);
+        let path = std::env::temp_dir().join(format!(
+            "energy_relaxation_mapped_test_{}.bin",
+            std::process::id()
+        ));
+        let mut store = MappedMagnetization::create(&path, grid, [1.0, 0.0, 0.0]).unwrap();
+        let mut whole = MicromagneticSystem::on_grid(grid, SimulationParameters::default());
+        for cell in 0..grid.size() {
+            // A twist along z, which the exchange across the slabs unwinds
+            let angle = 0.4 * (cell / grid.nx) as f64;
+            let m = [angle.cos(), angle.sin(), 0.0];
+            store.set(cell, m);
+            whole.set_magnetization(cell, Array1::from_vec(m.to_vec()));
+        }
+        drop(store);
+        let mut store = MappedMagnetization::open(&path, grid).unwrap();
+        assert_eq!(store.get(grid.nx), [0.4f64.cos(), 0.4f64.sin(), 0.0]);
+
+        let relaxation = ChunkedRelaxation {
+            layers: 3,
+            tolerance: 1e-3,
+            ..ChunkedRelaxation::default()
+        };
+        let report = relaxation
+            .relax(&mut store, SimulationParameters::default())
+            .unwrap();
+        assert!(report.converged, "{:?}", report);
+        whole.minimize_energy();
+        let expected = whole.average_magnetization();
+        let average = store.average_magnetization();
+        for k in 0..3 {
+            assert!(
+                (average[k] - expected[k]).abs() < 1e-2,
+                "{:?} {:?}",
+                average,
+                expected
+            );
+        }
+        assert!(MappedMagnetization::open(&path, Grid::new(3, 1, 6)).is_err());
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
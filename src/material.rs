@@ -0,0 +1,232 @@
+use crate::dmi::DmiType;
+use crate::DAMPING_CONSTANT;
+use crate::EASY_AXIS;
+use crate::MAGNETIC_EXCHANGE_CONSTANT;
+use crate::SATURATION_MAGNETIZATION;
+use crate::UNIAXIAL_ANISOTROPY_CONSTANT;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+///# Material
+/// Material constants of a single cell
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Material {
+    // Exchange stiffness in J/m, negative values couple neighbors antiparallel
+    #[serde(rename = "A")]
+    pub exchange_constant: f64,
+    // Saturation magnetization in A/m, zero marks vacuum
+    #[serde(rename = "Ms")]
+    pub saturation_magnetization: f64,
+    // Uniaxial anisotropy constant in J/m^3
+    #[serde(rename = "K")]
+    pub anisotropy_constant: f64,
+    // Unit vector of the easy axis
+    #[serde(rename = "axis")]
+    pub easy_axis: [f64; 3],
+    // Gilbert damping constant
+    #[serde(rename = "alpha")]
+    pub damping: f64,
+    // Exchange stiffness between cells two apart in J/m, negative values frustrate
+    // the nearest-neighbor exchange and stabilize spin spirals for A2 < -A/4
+    #[serde(rename = "A2", default)]
+    pub second_neighbor_exchange_constant: f64,
+    // Dzyaloshinskii-Moriya constant in J/m^2, its sign sets the chirality
+    #[serde(rename = "D", default)]
+    pub dmi_constant: f64,
+    // "interfacial" or "bulk" symmetry of the DMI
+    #[serde(rename = "dmi", default)]
+    pub dmi_type: DmiType,
+}
+
+impl Default for Material {
+    /// The built-in material given by the crate constants
+    fn default() -> Self {
+        Self {
+            exchange_constant: MAGNETIC_EXCHANGE_CONSTANT,
+            saturation_magnetization: SATURATION_MAGNETIZATION,
+            anisotropy_constant: UNIAXIAL_ANISOTROPY_CONSTANT,
+            easy_axis: EASY_AXIS,
+            damping: DAMPING_CONSTANT,
+            second_neighbor_exchange_constant: 0.0,
+            dmi_constant: 0.0,
+            dmi_type: DmiType::default(),
+        }
+    }
+}
+
+impl Material {
+    ///# Vacuum
+    /// A cell without magnetic moment
+    pub fn vacuum() -> Self {
+        Self {
+            exchange_constant: 0.0,
+            saturation_magnetization: 0.0,
+            anisotropy_constant: 0.0,
+            second_neighbor_exchange_constant: 0.0,
+            dmi_constant: 0.0,
+            ..Self::default()
+        }
+    }
+
+    ///# Validated Material
+    /// The material with a normalized easy axis, invalid constants are rejected.
+    pub fn validated(self) -> Result<Self, String> {
+        let norm = self.easy_axis.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            return Err("has a zero easy axis".to_string());
+        }
+        if self.saturation_magnetization < 0.0 {
+            return Err("has a negative Ms".to_string());
+        }
+        if self.damping < 0.0 {
+            return Err("has a negative damping".to_string());
+        }
+        Ok(Self {
+            easy_axis: self.easy_axis.map(|x| x / norm),
+            ..self
+        })
+    }
+
+    ///# Is Vacuum
+    pub fn is_vacuum(&self) -> bool {
+        self.saturation_magnetization == 0.0
+    }
+
+    ///# Interface Exchange Constant
+    /// Exchange stiffness between two neighboring cells, taken as the
+    /// harmonic mean so that a weak material dominates the coupling.
+    /// Between a ferromagnet and an antiferromagnet the harmonic mean is
+    /// meaningless, there the arithmetic mean is used.
+    pub fn interface_exchange_constant(&self, other: &Material) -> f64 {
+        let (a, b) = (self.exchange_constant, other.exchange_constant);
+        if a * b < 0.0 {
+            return 0.5 * (a + b);
+        }
+        if a + b == 0.0 {
+            return 0.0;
+        }
+        2.0 * a * b / (a + b)
+    }
+
+    ///# Interface Exchange Derivatives
+    /// Partial derivatives of the interface exchange constant with respect
+    /// to the exchange constants of this and the other cell.
+    pub fn interface_exchange_derivatives(&self, other: &Material) -> (f64, f64) {
+        let (a, b) = (self.exchange_constant, other.exchange_constant);
+        // Two equal zero constants change together like one constant
+        if a * b < 0.0 || a + b == 0.0 {
+            return (0.5, 0.5);
+        }
+        let denominator = (a + b) * (a + b);
+        (2.0 * b * b / denominator, 2.0 * a * a / denominator)
+    }
+
+    ///# Interface Second Neighbor Exchange Constant
+    /// Exchange stiffness between two cells two apart. It may be negative,
+    /// so the arithmetic mean is used.
+    pub fn interface_second_neighbor_exchange_constant(&self, other: &Material) -> f64 {
+        0.5 * (self.second_neighbor_exchange_constant + other.second_neighbor_exchange_constant)
+    }
+}
+
+///# Material Database
+/// Named materials, read from a JSON object of the form
+/// `{ "Permalloy": { "A": 1.3e-11, "Ms": 8.6e5, "K": 0.0, "axis": [1, 0, 0], "alpha": 0.01 } }`,
+/// the second neighbor exchange "A2" and the DMI "D" with its type "dmi"
+/// are optional.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaterialDatabase {
+    materials: HashMap<String, Material>,
+}
+
+impl MaterialDatabase {
+    ///# Load Material Database
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        Self::from_json(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    ///# Parse Material Database
+    /// The easy axes are normalized, invalid constants are rejected.
+    pub fn from_json(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut materials: HashMap<String, Material> = serde_json::from_str(text)?;
+        for (name, material) in materials.iter_mut() {
+            *material = material
+                .validated()
+                .map_err(|e| format!("Material '{}' {}", name, e))?;
+        }
+        Ok(Self { materials })
+    }
+
+    ///# Get Material
+    pub fn get(&self, name: &str) -> Option<&Material> {
+        self.materials.get(name)
+    }
+
+    ///# Material Names
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.materials.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test parsing a material database
+    fn test_from_json() {
+        let database = MaterialDatabase::from_json(
+            r#"{
+                "Permalloy": { "A": 1.3e-11, "Ms": 8.6e5, "K": 0.0, "axis": [2, 0, 0], "alpha": 0.01 },
+                "Cobalt": { "A": 3.0e-11, "Ms": 1.4e6, "K": 5.2e5, "axis": [0, 0, 1], "alpha": 0.02, "A2": -1e-11 }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(database.names(), vec!["Cobalt", "Permalloy"]);
+        let permalloy = database.get("Permalloy").unwrap();
+        assert_eq!(permalloy.saturation_magnetization, 8.6e5);
+        assert_eq!(permalloy.easy_axis, [1.0, 0.0, 0.0]);
+        assert_eq!(permalloy.second_neighbor_exchange_constant, 0.0);
+        let cobalt = database.get("Cobalt").unwrap();
+        assert_eq!(cobalt.second_neighbor_exchange_constant, -1e-11);
+        assert!(database.get("Iron").is_none());
+    }
+
+    #[test]
+    /// Test the harmonic mean of the exchange constants
+    fn test_interface_exchange_constant() {
+        let soft = Material::default();
+        let hard = Material {
+            exchange_constant: 3.0 * soft.exchange_constant,
+            ..soft
+        };
+        let expected = 1.5 * soft.exchange_constant;
+        assert!((soft.interface_exchange_constant(&hard) - expected).abs() < 1e-24);
+        assert_eq!(soft.interface_exchange_constant(&Material::vacuum()), 0.0);
+
+        let antiferromagnet = Material {
+            exchange_constant: -soft.exchange_constant,
+            ..soft
+        };
+        let same = antiferromagnet.interface_exchange_constant(&antiferromagnet);
+        assert!((same + soft.exchange_constant).abs() < 1e-24);
+        let mixed = antiferromagnet.interface_exchange_constant(&hard);
+        assert!((mixed - soft.exchange_constant).abs() < 1e-24);
+    }
+
+    #[test]
+    /// Test that incomplete or invalid materials are rejected
+    fn test_invalid_materials() {
+        assert!(MaterialDatabase::from_json(r#"{ "X": { "A": 1e-11, "Ms": 1e6 } }"#).is_err());
+        assert!(MaterialDatabase::from_json(
+            r#"{ "X": { "A": 1e-11, "Ms": 1e6, "K": 0, "axis": [0, 0, 0], "alpha": 0.1 } }"#
+        )
+        .is_err());
+    }
+}
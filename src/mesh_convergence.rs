@@ -0,0 +1,333 @@
+use crate::astroid::AstroidSweep;
+use crate::domains::wall_width;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::sensitivity::Observable;
+use crate::{EASY_AXIS, PERMEABILITY_OF_FREE_SPACE};
+use std::error::Error;
+
+///# Mesh Convergence Study
+/// Reruns the same chain at several cell sizes and compares the
+/// observables with those of the finest mesh. A chain of length L is
+/// resampled onto L / dx cells of the cell size dx, the materials, the
+/// field gradient and the interlayer couplings stay as they are. The cross
+/// section of a chain is one cell, so the final energy is compared as the
+/// energy density, and the dipolar field of a thinner chain still differs. Finer
+/// meshes relax with a time step reduced by the square of the refinement
+/// and correspondingly more iterations, the explicit relaxation of the
+/// stiffer exchange is unstable otherwise.
+#[derive(Debug, Clone)]
+pub struct MeshConvergenceStudy {
+    // Cell sizes in m, the cell size of the system is always compared as
+    // the chosen one
+    pub cell_sizes: Vec<f64>,
+    pub observables: Vec<Observable>,
+    pub coercivity_sweep: AstroidSweep,
+    pub coercivity_angle: f64,
+    pub easy_axis: [f64; 3],
+    // Relative deviation from the finest mesh above which a cell size is
+    // too coarse
+    pub tolerance: f64,
+}
+
+///# Mesh Point
+/// Observables at one cell size, in the order of the study. The energy is
+/// the energy density in J/m^3 of the relaxed state, the wall width is in
+/// m and the coercivity in T.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshPoint {
+    pub cell_size: f64,
+    pub cells: usize,
+    pub values: Vec<Option<f64>>,
+    // Relative deviations from the finest mesh, `None` where an observable
+    // is undefined or vanishes on the finest mesh
+    pub deviations: Vec<Option<f64>>,
+}
+
+///# Mesh Convergence Report
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshConvergenceReport {
+    // From the coarsest to the finest cell size
+    pub points: Vec<MeshPoint>,
+    pub observables: Vec<Observable>,
+    // Smallest exchange length sqrt(2 A / (mu0 Ms^2)) and Bloch wall
+    // parameter sqrt(A / K) of the materials in m, the lengths the mesh
+    // has to resolve
+    pub exchange_length: f64,
+    pub wall_parameter: f64,
+    pub warnings: Vec<String>,
+}
+
+impl Default for MeshConvergenceStudy {
+    fn default() -> Self {
+        Self {
+            cell_sizes: vec![2.0e-9, 1.0e-9, 0.5e-9],
+            observables: vec![Observable::FinalEnergy, Observable::WallWidth],
+            coercivity_sweep: AstroidSweep {
+                resolution: 1e-5,
+                ..AstroidSweep::default()
+            },
+            coercivity_angle: 5.0,
+            easy_axis: EASY_AXIS,
+            tolerance: 0.01,
+        }
+    }
+}
+
+impl MeshConvergenceStudy {
+    ///# Run Study
+    /// The system is the problem at its chosen cell size. Its current state
+    /// is the starting state at every cell size.
+    pub fn run(
+        &self,
+        system: &MicromagneticSystem,
+    ) -> Result<MeshConvergenceReport, Box<dyn Error>> {
+        if self.cell_sizes.iter().any(|&dx| dx.is_nan() || dx <= 0.0) {
+            return Err("The cell sizes must be positive".into());
+        }
+        let chosen_size = system.get_cell_size();
+        let mut cell_sizes = self.cell_sizes.clone();
+        cell_sizes.push(chosen_size);
+        cell_sizes.sort_by(|a, b| b.total_cmp(a));
+        cell_sizes.dedup_by(|a, b| (*a - *b).abs() < 1e-6 * *b);
+
+        let mut points = cell_sizes
+            .iter()
+            .map(|&cell_size| {
+                let discretized = discretize(system, cell_size)?;
+                Ok(MeshPoint {
+                    cell_size,
+                    cells: discretized.size(),
+                    values: self.observe(&discretized),
+                    deviations: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<MeshPoint>, Box<dyn Error>>>()?;
+        let finest = points.last().map(|p| p.values.clone()).unwrap_or_default();
+        for point in &mut points {
+            point.deviations = point
+                .values
+                .iter()
+                .zip(&finest)
+                .map(|(value, reference)| match (value, reference) {
+                    (Some(value), Some(reference)) if *reference != 0.0 => {
+                        Some((value - reference).abs() / reference.abs())
+                    }
+                    _ => None,
+                })
+                .collect();
+        }
+
+        let materials = system.get_materials();
+        let magnetic = materials
+            .iter()
+            .filter(|m| m.saturation_magnetization > 0.0);
+        let exchange_length = magnetic
+            .clone()
+            .map(|m| {
+                (2.0 * m.exchange_constant.abs()
+                    / (PERMEABILITY_OF_FREE_SPACE * m.saturation_magnetization.powi(2)))
+                .sqrt()
+            })
+            .fold(f64::INFINITY, f64::min);
+        let wall_parameter = magnetic
+            .filter(|m| m.anisotropy_constant > 0.0)
+            .map(|m| (m.exchange_constant.abs() / m.anisotropy_constant).sqrt())
+            .fold(f64::INFINITY, f64::min);
+
+        let mut warnings = Vec::new();
+        let length = exchange_length.min(wall_parameter);
+        if chosen_size > length {
+            warnings.push(format!(
+                "The cell size {:e} m is larger than the exchange length {:e} m \
+                 and the wall parameter {:e} m",
+                chosen_size, exchange_length, wall_parameter
+            ));
+        }
+        let chosen = points
+            .iter()
+            .find(|p| (p.cell_size - chosen_size).abs() < 1e-6 * chosen_size)
+            .expect("the chosen cell size is part of the study");
+        let finest_size = cell_sizes[cell_sizes.len() - 1];
+        for (observable, deviation) in self.observables.iter().zip(&chosen.deviations) {
+            if let Some(deviation) = deviation.filter(|&d| d > self.tolerance) {
+                warnings.push(format!(
+                    "The cell size {:e} m is too coarse: the {} deviates by {:.3} % from {:e} m",
+                    chosen_size,
+                    observable.name(),
+                    100.0 * deviation,
+                    finest_size
+                ));
+            }
+        }
+
+        Ok(MeshConvergenceReport {
+            points,
+            observables: self.observables.clone(),
+            exchange_length,
+            wall_parameter,
+            warnings,
+        })
+    }
+
+    // The observables of the discretized chain
+    fn observe(&self, system: &MicromagneticSystem) -> Vec<Option<f64>> {
+        let relaxed = self
+            .observables
+            .iter()
+            .any(|&o| o != Observable::Coercivity)
+            .then(|| {
+                let mut relaxed = system.clone();
+                relaxed.minimize_energy();
+                relaxed
+            });
+        self.observables
+            .iter()
+            .map(|observable| match (observable, &relaxed) {
+                (Observable::FinalEnergy, Some(relaxed)) => {
+                    Some(relaxed.compute_magnetic_energy_density())
+                }
+                (Observable::WallWidth, Some(relaxed)) => wall_width(
+                    &relaxed.get_magnetizations(),
+                    &self.easy_axis,
+                    relaxed.get_cell_size(),
+                ),
+                (Observable::Coercivity, _) => self
+                    .coercivity_sweep
+                    .switching_field(system, self.coercivity_angle),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl MeshConvergenceReport {
+    ///# Coarsest Converged Cell Size
+    /// Largest cell size in m at which every observable and those of every
+    /// finer mesh are within the tolerance of the finest mesh.
+    pub fn coarsest_converged(&self, tolerance: f64) -> Option<f64> {
+        let converged = self
+            .points
+            .iter()
+            .rev()
+            .take_while(|p| {
+                p.deviations
+                    .iter()
+                    .all(|d| d.is_none_or(|d| d <= tolerance))
+            })
+            .count();
+        (converged > 0).then(|| self.points[self.points.len() - converged].cell_size)
+    }
+
+    ///# Observed Order
+    /// Convergence order p of an observable, O(dx) - O(0) ~ dx^p, from the
+    /// three finest meshes. `None` unless their cell sizes shrink by the
+    /// same ratio and the changes of the observable between them shrink
+    /// with the same sign.
+    pub fn observed_order(&self, observable: Observable) -> Option<f64> {
+        let k = self.observables.iter().position(|&o| o == observable)?;
+        let [coarse, middle, fine] = self
+            .points
+            .len()
+            .checked_sub(3)
+            .map(|n| &self.points[n..])?
+        else {
+            return None;
+        };
+        let ratio = coarse.cell_size / middle.cell_size;
+        if (middle.cell_size / fine.cell_size - ratio).abs() > 1e-6 * ratio {
+            return None;
+        }
+        let (a, b, c) = (coarse.values[k]?, middle.values[k]?, fine.values[k]?);
+        let quotient = (a - b) / (b - c);
+        (quotient > 1.0 && quotient.is_finite()).then(|| quotient.ln() / ratio.ln())
+    }
+}
+
+///# Discretize
+/// The chain of the system resampled onto cells of the size dx in m.
+pub fn discretize(
+    system: &MicromagneticSystem,
+    cell_size: f64,
+) -> Result<MicromagneticSystem, Box<dyn Error>> {
+    let refinement = system.get_cell_size() / cell_size;
+    let cells = ((system.size() as f64 * refinement).round() as usize).max(1);
+    let mut discretized = system.resample(cells)?;
+    discretized.set_cell_size(cell_size);
+    if refinement > 1.0 {
+        let stiffening = refinement.powi(2);
+        discretized.set_time_step(system.get_time_step() / stiffening);
+        discretized
+            .set_max_iterations((system.get_max_iterations() as f64 * stiffening).ceil() as usize);
+    }
+    Ok(discretized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::parameters::SimulationParameters;
+    use crate::SPATIAL_DISCRETION_STEP;
+    use ndarray::array;
+
+    #[test]
+    /// Test the convergence of a Bloch wall towards the continuum width
+    fn test_mesh_convergence() {
+        let material = Material {
+            anisotropy_constant: 1.0e6,
+            ..Material::default()
+        };
+        let size = 40;
+        let parameters = SimulationParameters::default()
+            .with_material(material)
+            .with_applied_field([0.0; 3]);
+        let mut system = MicromagneticSystem::with_parameters(size, parameters);
+        for i in 0..size {
+            let x = (i as f64 - 19.5) / 4.0;
+            system.set_magnetization(i, array![-x.tanh(), 1.0 / x.cosh(), 0.0]);
+        }
+
+        // Resampling keeps the physical length and the materials
+        let fine = discretize(&system, 0.5e-9).unwrap();
+        assert_eq!(fine.size(), 80);
+        assert_eq!(fine.get_cell_size(), 0.5e-9);
+        assert_eq!(fine.get_materials()[0], material);
+
+        let study = MeshConvergenceStudy {
+            cell_sizes: vec![4.0e-9, 2.0e-9, 0.5e-9],
+            tolerance: 1e-6,
+            ..MeshConvergenceStudy::default()
+        };
+        let report = study.run(&system).unwrap();
+        let sizes: Vec<f64> = report.points.iter().map(|p| p.cell_size).collect();
+        assert_eq!(sizes, [4.0e-9, 2.0e-9, 1.0e-9, 0.5e-9]);
+
+        // The discrete wall is narrower than pi sqrt(A / K) and widens
+        // towards it as the mesh is refined
+        let width = std::f64::consts::PI * report.wall_parameter;
+        let widths: Vec<f64> = report.points.iter().map(|p| p.values[1].unwrap()).collect();
+        assert!(widths.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((widths[3] - width).abs() < 0.02 * width);
+        let deviations: Vec<f64> = report
+            .points
+            .iter()
+            .map(|p| p.deviations[1].unwrap())
+            .collect();
+        assert!(deviations.windows(2).all(|pair| pair[0] > pair[1]));
+        // The finite differences converge to second order
+        let order = report.observed_order(Observable::WallWidth).unwrap();
+        assert!((order - 2.0).abs() < 0.5);
+        // 4 sqrt(A K) over the length of the chain above the uniform -K
+        let k = material.anisotropy_constant;
+        let energy = 4.0 * (material.exchange_constant * k).sqrt() / 40.0e-9 - k;
+        let finest = report.points[3].values[0].unwrap();
+        assert!((finest - energy).abs() < 0.005 * energy.abs());
+
+        // The 1 nm mesh misses the tight tolerance, but resolves the lengths
+        assert_eq!(report.warnings.len(), 2);
+        assert!(report.warnings[0].contains("energy"));
+        assert_eq!(report.coarsest_converged(1e-6), Some(0.5e-9));
+        assert_eq!(report.coarsest_converged(1.0), Some(4.0e-9));
+        assert!(report.exchange_length > SPATIAL_DISCRETION_STEP);
+    }
+}
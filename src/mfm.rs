@@ -0,0 +1,146 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::stray_field::{compute_stray_field, plane_points};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+///# MFM Scan
+/// Magnetic force microscopy scan of a rectangle in the xy plane at the
+/// lift height above the top surface of the sample. The contrast of a tip
+/// magnetized along z is the phase shift proportional to d^2 Hz / dz^2,
+/// taken by central differences over `derivative_step`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MfmScan {
+    // Height of the tip above the top surface in m
+    pub lift_height: f64,
+    // Scanned x and y ranges in m
+    pub x_range: (f64, f64),
+    pub y_range: (f64, f64),
+    // Number of pixels along x and y
+    pub resolution: (usize, usize),
+    // Step of the finite difference along z in m
+    pub derivative_step: f64,
+}
+
+///# MFM Image
+/// Contrast d^2 Hz / dz^2 in A/m^3 on the scan grid, rows of constant y
+/// with x running fastest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MfmImage {
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub contrast: Vec<f64>,
+}
+
+impl MfmScan {
+    ///# Scan over a Chain
+    /// Scan of the whole chain of `cells` of the cell size with a margin of
+    /// the lift height around it, with square pixels of `pixel_size` in m.
+    pub fn over_chain(cells: usize, cell_size: f64, lift_height: f64, pixel_size: f64) -> Self {
+        // The cells are centered on i dx, so the chain spans -dx/2 to (N - 1/2) dx
+        let x_range = (
+            -0.5 * cell_size - lift_height,
+            (cells as f64 - 0.5) * cell_size + lift_height,
+        );
+        let y_range = (-lift_height, lift_height);
+        let pixels = |range: (f64, f64)| ((range.1 - range.0) / pixel_size).round() as usize + 1;
+        Self {
+            lift_height,
+            x_range,
+            y_range,
+            resolution: (pixels(x_range), pixels(y_range)),
+            derivative_step: cell_size,
+        }
+    }
+
+    ///# Run Scan
+    pub fn run(&self, system: &MicromagneticSystem) -> MfmImage {
+        let (nx, ny) = self.resolution;
+        // The cells are centered on z = 0 and one cell thick
+        let height = 0.5 * system.get_cell_size() + self.lift_height;
+        let plane = |z: f64| {
+            plane_points(
+                [self.x_range.0, self.y_range.0, z],
+                [self.x_range.1 - self.x_range.0, 0.0, 0.0],
+                [0.0, self.y_range.1 - self.y_range.0, 0.0],
+                (nx, ny),
+            )
+        };
+        let h = self.derivative_step;
+        let below = compute_stray_field(system, &plane(height - h));
+        let center = compute_stray_field(system, &plane(height));
+        let above = compute_stray_field(system, &plane(height + h));
+        let contrast = (0..nx * ny)
+            .map(|n| (above[n][2] - 2.0 * center[n][2] + below[n][2]) / (h * h))
+            .collect();
+        let axis = |range: (f64, f64), count: usize| {
+            (0..count)
+                .map(|n| {
+                    let fraction = if count > 1 {
+                        n as f64 / (count - 1) as f64
+                    } else {
+                        0.0
+                    };
+                    range.0 + fraction * (range.1 - range.0)
+                })
+                .collect()
+        };
+        MfmImage {
+            x: axis(self.x_range, nx),
+            y: axis(self.y_range, ny),
+            contrast,
+        }
+    }
+}
+
+impl MfmImage {
+    ///# Contrast at a Pixel
+    pub fn at(&self, ix: usize, iy: usize) -> f64 {
+        self.contrast[iy * self.x.len() + ix]
+    }
+
+    ///# Write Grid
+    /// Plain text matrix with one line per row of constant y, preceded by
+    /// comment lines holding the x and y coordinates in m.
+    pub fn write_grid(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let join = |values: &[f64]| {
+            values
+                .iter()
+                .map(|v| format!("{:e}", v))
+                .collect::<Vec<_>>()
+                .join("\t")
+        };
+        writeln!(writer, "# d2Hz/dz2 (A/m^3), rows of constant y")?;
+        writeln!(writer, "# x (m): {}", join(&self.x))?;
+        writeln!(writer, "# y (m): {}", join(&self.y))?;
+        for row in self.contrast.chunks(self.x.len().max(1)) {
+            writeln!(writer, "{}", join(row))?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    /// Test the contrast over the two ends of a chain magnetized along x
+    fn test_mfm_contrast() {
+        let system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]; 20]);
+        let scan = MfmScan::over_chain(20, 1e-9, 5e-9, 1e-9);
+        assert_eq!(scan.resolution, (31, 11));
+        let image = scan.run(&system);
+        assert_eq!(image.contrast.len(), 31 * 11);
+
+        // The magnetic charges at the ends give opposite contrast,
+        // symmetric about the middle of the chain
+        let row = scan.resolution.1 / 2;
+        let (left, right) = (image.at(4, row), image.at(26, row));
+        assert!(left * right < 0.0);
+        assert!((left + right).abs() < 1e-6 * left.abs());
+        assert!(image.at(15, row).abs() < 1e-6 * left.abs());
+    }
+}
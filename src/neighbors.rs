@@ -0,0 +1,116 @@
+///# Neighbor List
+/// Exchange-coupled nearest neighbors of every cell in compressed form,
+/// the neighbors of cell i are `neighbors[offsets[i]..offsets[i + 1]]` in
+/// ascending order. A chain couples i to i - 1 and i + 1, further edges
+/// close rings or attach branches at junctions. Only the exchange follows
+/// the connectivity, the positions of the cells for the dipolar field and
+/// the field gradient stay those of the grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeighborList {
+    offsets: Vec<usize>,
+    neighbors: Vec<usize>,
+}
+
+impl NeighborList {
+    ///# Chain
+    pub fn chain(size: usize) -> Self {
+        let edges: Vec<(usize, usize)> = (1..size).map(|i| (i - 1, i)).collect();
+        Self::build(size, &edges)
+    }
+
+    ///# Ring
+    /// Chain whose last cell couples back to the first one.
+    pub fn ring(size: usize) -> Self {
+        let mut edges: Vec<(usize, usize)> = (1..size).map(|i| (i - 1, i)).collect();
+        if size > 2 {
+            edges.push((0, size - 1));
+        }
+        Self::build(size, &edges)
+    }
+
+    ///# From Edges
+    /// Neighbor list of the given undirected edges, duplicates count once.
+    pub fn from_edges(size: usize, edges: &[(usize, usize)]) -> Result<Self, String> {
+        for &(a, b) in edges {
+            if a >= size || b >= size {
+                return Err(format!(
+                    "The edge {}-{} is outside the {} cells",
+                    a, b, size
+                ));
+            }
+            if a == b {
+                return Err(format!("Cell {} cannot be its own neighbor", a));
+            }
+        }
+        Ok(Self::build(size, edges))
+    }
+
+    fn build(size: usize, edges: &[(usize, usize)]) -> Self {
+        let mut lists = vec![Vec::new(); size];
+        for &(a, b) in edges {
+            lists[a].push(b);
+            lists[b].push(a);
+        }
+        let mut offsets = Vec::with_capacity(size + 1);
+        let mut neighbors = Vec::new();
+        offsets.push(0);
+        for mut list in lists {
+            list.sort_unstable();
+            list.dedup();
+            neighbors.extend(list);
+            offsets.push(neighbors.len());
+        }
+        Self { offsets, neighbors }
+    }
+
+    ///# Size
+    /// Number of cells.
+    pub fn size(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    ///# Neighbors of a Cell
+    pub fn neighbors(&self, cell: usize) -> &[usize] {
+        &self.neighbors[self.offsets[cell]..self.offsets[cell + 1]]
+    }
+
+    ///# Edges
+    /// Every coupled pair once, as (lower, higher) cell.
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.size()).flat_map(move |i| {
+            self.neighbors(i)
+                .iter()
+                .filter(move |&&j| j > i)
+                .map(move |&j| (i, j))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test chains, rings and a junction
+    fn test_neighbor_list() {
+        let chain = NeighborList::chain(4);
+        assert_eq!(chain.neighbors(0), &[1]);
+        assert_eq!(chain.neighbors(2), &[1, 3]);
+        assert_eq!(NeighborList::chain(0).size(), 0);
+
+        let ring = NeighborList::ring(4);
+        assert_eq!(ring.neighbors(0), &[1, 3]);
+        assert_eq!(ring.edges().count(), 4);
+
+        // A T junction: a branch 3-4 attached to the middle of the chain 0-1-2
+        let junction =
+            NeighborList::from_edges(5, &[(0, 1), (1, 2), (1, 3), (3, 4), (2, 1)]).unwrap();
+        assert_eq!(junction.neighbors(1), &[0, 2, 3]);
+        assert_eq!(
+            junction.edges().collect::<Vec<_>>(),
+            vec![(0, 1), (1, 2), (1, 3), (3, 4)]
+        );
+        assert!(NeighborList::from_edges(3, &[(0, 3)]).is_err());
+        assert!(NeighborList::from_edges(3, &[(1, 1)]).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::eigen::symmetric_eigen;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::spin_waves::ModeMap;
+use crate::{GILBERT_GYROMAGNETIC_RATIO, PERMEABILITY_OF_FREE_SPACE};
+use ndarray::{Array1, Array2};
+use std::error::Error;
+use std::f64::consts::PI;
+
+// Rotation in rad of the finite difference probe of the effective field.
+// Apart from higher order couplings the field is linear in m, so the
+// probe only has to stay well above the rounding error.
+const PROBE_ANGLE: f64 = 1e-5;
+
+///# Tangent Hessian
+/// Second derivatives of the energy in J/rad^2 for small rotations of the
+/// magnetization of every magnetic cell out of its current direction,
+/// along the two axes of the cell's tangent frame. Rows and columns 2k
+/// and 2k + 1 belong to `cells[k]`. At an energy minimum the matrix is
+/// positive definite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TangentHessian {
+    // Indices of the magnetic cells, vacuum cells carry no degrees of freedom
+    pub cells: Vec<usize>,
+    // Two unit vectors perpendicular to the magnetization of each magnetic cell
+    pub frames: Vec<[[f64; 3]; 2]>,
+    pub matrix: Array2<f64>,
+}
+
+///# Tangent Hessian of a State
+/// The second derivatives are assembled from central differences of the
+/// effective field, so every interaction of the field enters without
+/// separate bookkeeping, plus the curvature of the unit sphere, which
+/// adds mu0 Ms V (m . H) on the diagonal.
+pub fn tangent_hessian(system: &MicromagneticSystem) -> TangentHessian {
+    let magnetizations = system.get_magnetizations();
+    let materials = system.get_materials();
+    let cells: Vec<usize> = (0..system.size())
+        .filter(|&i| !system.is_vacuum(i))
+        .collect();
+    let frames: Vec<[[f64; 3]; 2]> = cells
+        .iter()
+        .map(|&i| tangent_frame(&magnetizations[i]))
+        .collect();
+    let volume = system.cell_volume();
+    let weight =
+        |i: usize| PERMEABILITY_OF_FREE_SPACE * materials[i].saturation_magnetization * volume;
+
+    // d^2E / dm_i dm_j = -mu0 Ms_i V dH_i / dm_j
+    let n = 2 * cells.len();
+    let mut matrix = Array2::zeros((n, n));
+    let mut probe = system.clone();
+    for (l, &j) in cells.iter().enumerate() {
+        for b in 0..2 {
+            let direction = Array1::from_vec(frames[l][b].to_vec());
+            probe.set_magnetization(j, &magnetizations[j] + PROBE_ANGLE * &direction);
+            let plus = probe.compute_effective_field();
+            probe.set_magnetization(j, &magnetizations[j] - PROBE_ANGLE * &direction);
+            let minus = probe.compute_effective_field();
+            probe.set_magnetization(j, magnetizations[j].clone());
+            for (k, &i) in cells.iter().enumerate() {
+                let derivative = (&plus[i] - &minus[i]) / (2.0 * PROBE_ANGLE);
+                for a in 0..2 {
+                    let projection: f64 = (0..3).map(|c| frames[k][a][c] * derivative[c]).sum();
+                    matrix[[2 * k + a, 2 * l + b]] = -weight(i) * projection;
+                }
+            }
+        }
+    }
+
+    let field = system.compute_effective_field();
+    for (k, &i) in cells.iter().enumerate() {
+        let parallel = magnetizations[i].dot(&field[i]);
+        for a in 0..2 {
+            matrix[[2 * k + a, 2 * k + a]] += weight(i) * parallel;
+        }
+    }
+    // The probe leaves a tiny asymmetry of the rounding
+    let matrix = 0.5 * (&matrix + &matrix.t());
+    TangentHessian {
+        cells,
+        frames,
+        matrix,
+    }
+}
+
+///# Normal Modes
+/// Eigenfrequencies in Hz and profiles of the small oscillations of the
+/// undamped LLG equation linearized around the given relaxed state, in
+/// ascending order of frequency. The amplitudes are normalized to a
+/// largest component of 1, the phases are relative to the in-phase part
+/// of the mode. Damping lowers the frequency of the precession by the
+/// factor 1 / (1 + alpha^2) of the dynamics. Fails when the state is not
+/// a stable minimum, where some modes have no real frequency.
+pub fn normal_modes(system: &MicromagneticSystem) -> Result<Vec<ModeMap>, Box<dyn Error>> {
+    let hessian = tangent_hessian(system);
+    let n = hessian.matrix.nrows();
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+    let materials = system.get_materials();
+    let volume = system.cell_volume();
+
+    // In the tangent frame the LLG equation reads du/dt = c J dE/du with
+    // c = gamma / (mu0 Ms V) and the rotation J of every cell. Scaling the
+    // Hessian with sqrt(c) gives the symmetric stiffness S = L L^T in 1/s,
+    // and y = L^T u / sqrt(c) evolves with the antisymmetric generator
+    // L^T J L, whose eigenvalues are +-i omega.
+    let scale: Vec<f64> = (0..n)
+        .map(|r| {
+            let saturation_magnetization = materials[hessian.cells[r / 2]].saturation_magnetization;
+            (GILBERT_GYROMAGNETIC_RATIO
+                / (PERMEABILITY_OF_FREE_SPACE * saturation_magnetization * volume))
+                .sqrt()
+        })
+        .collect();
+    let stiffness = Array2::from_shape_fn((n, n), |(r, s)| {
+        scale[r] * hessian.matrix[[r, s]] * scale[s]
+    });
+    let (values, vectors) = symmetric_eigen(&stiffness);
+    let largest = values.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+    if values[0] <= 1e-12 * largest {
+        return Err(format!(
+            "The state is not a stable minimum, the lowest stiffness is {:e} 1/s",
+            values[0]
+        )
+        .into());
+    }
+    let root = Array2::from_shape_fn((n, n), |(r, s)| vectors[[r, s]] * values[s].sqrt());
+    let inverse_root_transpose =
+        Array2::from_shape_fn((n, n), |(r, s)| vectors[[r, s]] / values[s].sqrt());
+    let mut rotation = Array2::zeros((n, n));
+    for k in 0..n / 2 {
+        rotation[[2 * k, 2 * k + 1]] = -1.0;
+        rotation[[2 * k + 1, 2 * k]] = 1.0;
+    }
+    let generator = root.t().dot(&rotation).dot(&root);
+
+    // -generator^2 is symmetric with every omega^2 twice, for the in-phase
+    // and the quadrature part of the mode
+    let (squares, pairs) = symmetric_eigen(&generator.t().dot(&generator));
+    let displacement = |y: &Array1<f64>| -> Vec<[f64; 3]> {
+        let w = inverse_root_transpose.dot(y);
+        let mut vectors = vec![[0.0; 3]; system.size()];
+        for (k, &i) in hessian.cells.iter().enumerate() {
+            for c in 0..3 {
+                vectors[i][c] = scale[2 * k] * w[2 * k] * hessian.frames[k][0][c]
+                    + scale[2 * k + 1] * w[2 * k + 1] * hessian.frames[k][1][c];
+            }
+        }
+        vectors
+    };
+    let modes = (0..n)
+        .step_by(2)
+        .map(|j| {
+            let omega = (0.5 * (squares[j] + squares[j + 1])).max(0.0).sqrt();
+            let y = pairs.column(j).to_owned();
+            let in_phase = displacement(&y);
+            let quadrature = displacement(&(generator.dot(&y) / omega));
+
+            // m(t) = u cos(omega t) + u' sin(omega t) = Re[(u - i u') e^(i omega t)]
+            let mut amplitude = vec![[0.0; 3]; system.size()];
+            let mut phase = vec![[0.0; 3]; system.size()];
+            for i in 0..system.size() {
+                for c in 0..3 {
+                    amplitude[i][c] = in_phase[i][c].hypot(quadrature[i][c]);
+                    phase[i][c] = (-quadrature[i][c]).atan2(in_phase[i][c]);
+                }
+            }
+            let maximum = amplitude.iter().flatten().fold(0.0f64, |m, &a| m.max(a));
+            if maximum > 0.0 {
+                for vector in &mut amplitude {
+                    *vector = vector.map(|a| a / maximum);
+                }
+            }
+            ModeMap {
+                frequency: omega / (2.0 * PI),
+                amplitude,
+                phase,
+            }
+        })
+        .collect();
+    Ok(modes)
+}
+
+// Two unit vectors completing the magnetization to a right-handed frame
+pub(crate) fn tangent_frame(magnetization: &Array1<f64>) -> [[f64; 3]; 2] {
+    let m = magnetization / magnetization.dot(magnetization).sqrt();
+    let helper = if m[0].abs() < 0.9 {
+        Array1::from_vec(vec![1.0, 0.0, 0.0])
+    } else {
+        Array1::from_vec(vec![0.0, 1.0, 0.0])
+    };
+    let first = &helper - helper.dot(&m) * &m;
+    let first = &first / first.dot(&first).sqrt();
+    let second = [
+        m[1] * first[2] - m[2] * first[1],
+        m[2] * first[0] - m[0] * first[2],
+        m[0] * first[1] - m[1] * first[0],
+    ];
+    [[first[0], first[1], first[2]], second]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        MAGNETIC_EXCHANGE_CONSTANT, SATURATION_MAGNETIZATION, SPATIAL_DISCRETION_STEP,
+        UNIAXIAL_ANISOTROPY_CONSTANT,
+    };
+    use ndarray::array;
+
+    #[test]
+    /// Test the spin wave modes of a uniform chain with free ends
+    fn test_uniform_chain_modes() {
+        let size = 8;
+        let mut system = MicromagneticSystem::new(size);
+        for i in 0..size {
+            system.set_magnetization(i, array![1.0, 0.0, 0.0]);
+        }
+        system.set_applied_field([0.1, 0.0, 0.0]);
+        let modes = normal_modes(&system).unwrap();
+        assert_eq!(modes.len(), size);
+
+        // omega_n = gamma (H + H_K + H_ex 2 (1 - cos(n pi / N)))
+        let mu0_ms = PERMEABILITY_OF_FREE_SPACE * SATURATION_MAGNETIZATION;
+        let field = 0.1 / PERMEABILITY_OF_FREE_SPACE + 2.0 * UNIAXIAL_ANISOTROPY_CONSTANT / mu0_ms;
+        let exchange_field =
+            2.0 * MAGNETIC_EXCHANGE_CONSTANT / (mu0_ms * SPATIAL_DISCRETION_STEP.powi(2));
+        for (n, mode) in modes.iter().enumerate() {
+            let wave = 2.0 * (1.0 - (n as f64 * PI / size as f64).cos());
+            let expected =
+                GILBERT_GYROMAGNETIC_RATIO * (field + exchange_field * wave) / (2.0 * PI);
+            assert!((mode.frequency - expected).abs() < 1e-6 * expected);
+        }
+        // The uniform mode precesses circularly in every cell
+        for amplitude in &modes[0].amplitude {
+            assert!(amplitude[0].abs() < 1e-6);
+            assert!((amplitude[1] - 1.0).abs() < 1e-6);
+            assert!((amplitude[2] - 1.0).abs() < 1e-6);
+        }
+        let phase = &modes[0].phase[0];
+        let difference = (phase[1] - phase[2]).rem_euclid(2.0 * PI);
+        assert!((difference - 0.5 * PI).abs() < 1e-6 || (difference - 1.5 * PI).abs() < 1e-6);
+
+        // Against the field the state is a saddle without a real frequency
+        system.set_applied_field([-0.5, 0.0, 0.0]);
+        assert!(normal_modes(&system).is_err());
+    }
+}
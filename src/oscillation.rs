@@ -0,0 +1,123 @@
+use ndarray::Array1;
+
+// Consecutive steps whose changes point against each other that count as an oscillation
+const ALTERNATING_STEPS: usize = 5;
+// Cosine between successive changes below which they point against each other
+const ALTERNATING_COSINE: f64 = -0.5;
+// Window in steps over which the maximum change is watched for a plateau
+const PLATEAU_WINDOW: usize = 200;
+// Relative spread of the maximum change over the window that counts as flat
+const PLATEAU_SPREAD: f64 = 0.01;
+
+///# Oscillation Detector
+/// Watches the relaxation for the two signs of a step that is too large:
+/// successive changes of the magnetization that point against each other,
+/// and a maximum change that stays flat while the changes do not keep their
+/// direction. A slow but steady drift, as near a critical field or during a
+/// reversal, is not reported.
+pub(crate) struct OscillationDetector {
+    previous: Vec<Array1<f64>>,
+    previous_change: Option<Vec<Array1<f64>>>,
+    alternating_steps: usize,
+    // Maximum change and cosine to the previous change over the last window
+    history: Vec<(f64, f64)>,
+}
+
+impl OscillationDetector {
+    ///# New Oscillation Detector
+    /// Start watching from the given state.
+    pub(crate) fn new(magnetizations: &[Array1<f64>]) -> Self {
+        Self {
+            previous: magnetizations.to_vec(),
+            previous_change: None,
+            alternating_steps: 0,
+            history: Vec::with_capacity(PLATEAU_WINDOW),
+        }
+    }
+
+    ///# Update
+    /// Feed the state and the maximum change after a step, returns true
+    /// when the relaxation oscillates.
+    pub(crate) fn update(&mut self, magnetizations: &[Array1<f64>], max_change: f64) -> bool {
+        let change: Vec<Array1<f64>> = magnetizations
+            .iter()
+            .zip(&self.previous)
+            .map(|(m, previous)| m - previous)
+            .collect();
+        let cosine = match &self.previous_change {
+            Some(previous_change) => {
+                let (overlap, norm, previous_norm) = change.iter().zip(previous_change).fold(
+                    (0.0, 0.0, 0.0),
+                    |(overlap, norm, previous_norm), (a, b)| {
+                        (
+                            overlap + a.dot(b),
+                            norm + a.dot(a),
+                            previous_norm + b.dot(b),
+                        )
+                    },
+                );
+                let norms = (norm * previous_norm).sqrt();
+                if norms > 0.0 {
+                    overlap / norms
+                } else {
+                    1.0
+                }
+            }
+            None => 1.0,
+        };
+        if cosine < ALTERNATING_COSINE {
+            self.alternating_steps += 1;
+        } else {
+            self.alternating_steps = 0;
+        }
+        self.previous = magnetizations.to_vec();
+        self.previous_change = Some(change);
+
+        if self.history.len() == PLATEAU_WINDOW {
+            self.history.remove(0);
+        }
+        self.history.push((max_change, cosine));
+        let plateau = self.history.len() == PLATEAU_WINDOW && {
+            let (smallest, largest) = self.history.iter().fold(
+                (f64::INFINITY, 0.0_f64),
+                |(smallest, largest), &(change, _)| (smallest.min(change), largest.max(change)),
+            );
+            let mean_cosine =
+                self.history.iter().map(|&(_, cosine)| cosine).sum::<f64>() / PLATEAU_WINDOW as f64;
+            largest - smallest <= PLATEAU_SPREAD * largest && mean_cosine < 0.5
+        };
+        plateau || self.alternating_steps >= ALTERNATING_STEPS
+    }
+
+    ///# Reset
+    /// Forget the history, e.g. after the step size has changed.
+    pub(crate) fn reset(&mut self, magnetizations: &[Array1<f64>]) {
+        *self = Self::new(magnetizations);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    /// Test the detection of alternating changes and of a plateau
+    fn test_oscillation_detector() {
+        let states = [array![1.0, 0.1, 0.0], array![1.0, -0.1, 0.0]];
+        let mut detector = OscillationDetector::new(&[states[0].clone()]);
+        let detected: Vec<bool> = (1..=6)
+            .map(|n| detector.update(&[states[n % 2].clone()], 0.2))
+            .collect();
+        assert_eq!(detected, vec![false, false, false, false, false, true]);
+
+        // A steady drift is fine, circling with a constant change is a plateau
+        let state = |angle: f64| [array![angle.cos(), angle.sin(), 0.0]];
+        let mut detector = OscillationDetector::new(&state(0.0));
+        assert!((1..1000).all(|n| !detector.update(&state(0.01 * n as f64), 0.01)));
+        let cycle = |n: usize| state(std::f64::consts::FRAC_PI_2 * n as f64);
+        let mut detector = OscillationDetector::new(&cycle(0));
+        let first = (1..=PLATEAU_WINDOW).position(|n| detector.update(&cycle(n), 1.0));
+        assert_eq!(first, Some(PLATEAU_WINDOW - 1));
+    }
+}
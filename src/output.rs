@@ -0,0 +1,171 @@
+use crate::config::SimulationConfig;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// Copy of the configuration and log written into every run directory
+pub const CONFIG_FILE: &str = "config.toml";
+pub const LOG_FILE: &str = "run.log";
+
+///# Collision Policy
+/// What happens when the output directory of a run already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    // Refuse to start the run
+    #[default]
+    Error,
+    // Use the first free directory name-1, name-2, ...
+    Suffix,
+    // Delete the existing directory and its files
+    Overwrite,
+}
+
+///# Output Directory
+/// Directory that receives every file of one run. Runs without an output
+/// directory write into the working directory, as before.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputDirectory {
+    path: PathBuf,
+    log: bool,
+}
+
+impl OutputDirectory {
+    ///# Working Directory
+    /// Files go into the working directory and nothing is logged.
+    pub fn working_directory() -> Self {
+        Self {
+            path: PathBuf::new(),
+            log: false,
+        }
+    }
+
+    ///# Create Output Directory
+    /// Create the directory of a run, resolving a collision with an existing
+    /// one by the policy. The working directory and its parents are never
+    /// overwritten.
+    pub fn create(path: &Path, policy: CollisionPolicy) -> Result<Self, Box<dyn Error>> {
+        let mut path = path.to_path_buf();
+        if path.exists() {
+            match policy {
+                CollisionPolicy::Error => {
+                    return Err(
+                        format!("The output directory {} already exists", path.display()).into(),
+                    )
+                }
+                CollisionPolicy::Suffix => {
+                    let name = path
+                        .file_name()
+                        .ok_or("The output directory needs a name to add a suffix to")?
+                        .to_string_lossy()
+                        .into_owned();
+                    path = (1..)
+                        .map(|n| path.with_file_name(format!("{}-{}", name, n)))
+                        .find(|candidate| !candidate.exists())
+                        .expect("a free suffix exists");
+                }
+                CollisionPolicy::Overwrite => {
+                    let working = std::env::current_dir()?.canonicalize()?;
+                    if working.starts_with(path.canonicalize()?) {
+                        return Err(format!(
+                            "Refusing to overwrite {}, it contains the working directory",
+                            path.display()
+                        )
+                        .into());
+                    }
+                    if !path.is_dir() {
+                        return Err(format!("{} is not a directory", path.display()).into());
+                    }
+                    fs::remove_dir_all(&path)?;
+                }
+            }
+        }
+        fs::create_dir_all(&path)?;
+        Ok(Self { path, log: true })
+    }
+
+    ///# Output Directory for a Configuration
+    /// The `output_directory` of the configuration, created by its collision
+    /// policy with a copy of the configuration, or the working directory.
+    pub fn for_config(config: &SimulationConfig) -> Result<Self, Box<dyn Error>> {
+        let Some(path) = &config.output_directory else {
+            return Ok(Self::working_directory());
+        };
+        let directory = Self::create(path, config.output_collision)?;
+        fs::write(directory.file(CONFIG_FILE), toml::to_string(config)?)?;
+        Ok(directory)
+    }
+
+    ///# Path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    ///# File in the Directory
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    ///# Log
+    /// Append a line to the run log, nothing for the working directory.
+    pub fn log(&self, message: &str) -> io::Result<()> {
+        if !self.log {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.file(LOG_FILE))?;
+        writeln!(file, "{}", message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the collision policies and the files of a run directory
+    fn test_output_directory() {
+        let root = std::env::temp_dir().join("energy_relaxation_output_test");
+        let _ = fs::remove_dir_all(&root);
+        let config = SimulationConfig {
+            output_directory: Some(root.join("run")),
+            output_collision: CollisionPolicy::Error,
+            ..SimulationConfig::example()
+        };
+        let first = OutputDirectory::for_config(&config).unwrap();
+        assert_eq!(first.path(), root.join("run"));
+        let copy = fs::read_to_string(first.file(CONFIG_FILE)).unwrap();
+        assert_eq!(SimulationConfig::from_toml(&copy).unwrap(), config);
+        first.log("relax").unwrap();
+        first.log("converged").unwrap();
+        let log = fs::read_to_string(first.file(LOG_FILE)).unwrap();
+        assert_eq!(log, "relax\nconverged\n");
+
+        assert!(OutputDirectory::for_config(&config).is_err());
+        let suffixed = SimulationConfig {
+            output_collision: CollisionPolicy::Suffix,
+            ..config.clone()
+        };
+        let second = OutputDirectory::for_config(&suffixed).unwrap();
+        assert_eq!(second.path(), root.join("run-1"));
+        let third = OutputDirectory::for_config(&suffixed).unwrap();
+        assert_eq!(third.path(), root.join("run-2"));
+
+        let overwritten = SimulationConfig {
+            output_collision: CollisionPolicy::Overwrite,
+            ..config
+        };
+        let again = OutputDirectory::for_config(&overwritten).unwrap();
+        assert_eq!(again.path(), root.join("run"));
+        assert!(!again.file(LOG_FILE).exists());
+        assert!(OutputDirectory::create(Path::new("."), CollisionPolicy::Overwrite).is_err());
+
+        let working = OutputDirectory::working_directory();
+        assert_eq!(working.file("vectors.xlsx"), Path::new("vectors.xlsx"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
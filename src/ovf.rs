@@ -0,0 +1,369 @@
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+///# OVF Data
+/// Vector field read from an OVF 1.0 or 2.0 file (OOMMF, mumax3).
+/// The vectors are stored with x varying fastest, then y, then z.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OvfData {
+    // Number of nodes along x, y and z
+    pub nodes: [usize; 3],
+    // Cell size along x, y and z
+    pub step_sizes: [f64; 3],
+    // Unit of the stored values, e.g. "A/m" or "1"
+    pub value_unit: String,
+    // The vector of every node
+    pub vectors: Vec<[f64; 3]>,
+}
+
+///# Read OVF File
+pub fn read_ovf(path: &Path) -> Result<OvfData, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    parse_ovf(&bytes)
+}
+
+///# Parse OVF
+/// Parses the header and the first data segment of an OVF file.
+/// Supported data formats are `Text`, `Binary 4` and `Binary 8`.
+/// OVF 1.0 binary data is big endian, OVF 2.0 binary data little endian.
+pub fn parse_ovf(bytes: &[u8]) -> Result<OvfData, Box<dyn Error>> {
+    let mut version = 0;
+    let mut nodes = [0usize; 3];
+    let mut step_sizes = [0.0; 3];
+    let mut value_unit = String::from("1");
+    let mut value_dimension = 3;
+    let mut position = 0;
+
+    // Header lines start with '#' and hold 'key: value' pairs
+    while position < bytes.len() {
+        let end = bytes[position..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(bytes.len(), |p| position + p);
+        let line = String::from_utf8_lossy(&bytes[position..end])
+            .trim()
+            .to_string();
+        position = (end + 1).min(bytes.len());
+
+        if line.starts_with("# OOMMF OVF 2.0") {
+            version = 2;
+            continue;
+        }
+        if line.starts_with("# OOMMF") {
+            version = 1;
+            continue;
+        }
+        let Some(content) = line.strip_prefix('#') else {
+            continue;
+        };
+        let Some((key, value)) = content.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+
+        match key.as_str() {
+            "xnodes" => nodes[0] = value.parse()?,
+            "ynodes" => nodes[1] = value.parse()?,
+            "znodes" => nodes[2] = value.parse()?,
+            "xstepsize" => step_sizes[0] = value.parse()?,
+            "ystepsize" => step_sizes[1] = value.parse()?,
+            "zstepsize" => step_sizes[2] = value.parse()?,
+            "valuedim" => value_dimension = value.parse()?,
+            // OVF 1.0 uses valueunit, OVF 2.0 valueunits (one per component)
+            "valueunit" | "valueunits" => {
+                value_unit = value.split_whitespace().next().unwrap_or("1").to_string()
+            }
+            "begin" if value.to_lowercase().starts_with("data") => {
+                if version == 0 {
+                    return Err("Missing OOMMF OVF signature line".into());
+                }
+                if value_dimension != 3 {
+                    return Err(format!(
+                        "Only vector fields are supported, got valuedim {}",
+                        value_dimension
+                    )
+                    .into());
+                }
+                let count = nodes.iter().product::<usize>();
+                if count == 0 {
+                    return Err("Missing or zero xnodes/ynodes/znodes".into());
+                }
+                let format = value[4..].trim().to_lowercase();
+                let vectors = match format.as_str() {
+                    "text" => parse_text_data(&bytes[position..], count)?,
+                    "binary 4" => parse_binary_data(&bytes[position..], count, 4, version == 1)?,
+                    "binary 8" => parse_binary_data(&bytes[position..], count, 8, version == 1)?,
+                    _ => return Err(format!("Unsupported OVF data format: {}", format).into()),
+                };
+                return Ok(OvfData {
+                    nodes,
+                    step_sizes,
+                    value_unit,
+                    vectors,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Err("No data segment found in OVF file".into())
+}
+
+///# Write OVF
+/// Writes a vector field as an OVF 2.0 file with `Binary 8` data, which
+/// mumax3 and OOMMF read and `parse_ovf` reads back exactly. `time` in s
+/// goes into the description, as in the snapshots of mumax3.
+pub fn write_ovf<W: Write>(
+    writer: &mut W,
+    data: &OvfData,
+    title: &str,
+    time: f64,
+) -> io::Result<()> {
+    write_segment(writer, data, title, time, false)
+}
+
+///# Write Single Precision OVF
+/// Same as `write_ovf` with `Binary 4` data, half the size at f32 precision.
+pub fn write_ovf_single<W: Write>(
+    writer: &mut W,
+    data: &OvfData,
+    title: &str,
+    time: f64,
+) -> io::Result<()> {
+    write_segment(writer, data, title, time, true)
+}
+
+// Write the file with Binary 4 or Binary 8 data
+fn write_segment<W: Write>(
+    writer: &mut W,
+    data: &OvfData,
+    title: &str,
+    time: f64,
+    single_precision: bool,
+) -> io::Result<()> {
+    let [nx, ny, nz] = data.nodes;
+    let [dx, dy, dz] = data.step_sizes;
+    if data.vectors.len() != nx * ny * nz {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The number of vectors does not match the nodes",
+        ));
+    }
+    let unit = &data.value_unit;
+    writeln!(
+        writer,
+        "# OOMMF OVF 2.0\n# Segment count: 1\n# Begin: Segment"
+    )?;
+    writeln!(
+        writer,
+        "# Begin: Header\n# Title: {}\n# meshtype: rectangular",
+        title
+    )?;
+    writeln!(writer, "# meshunit: m\n# xmin: 0\n# ymin: 0\n# zmin: 0")?;
+    writeln!(
+        writer,
+        "# xmax: {:e}\n# ymax: {:e}\n# zmax: {:e}",
+        nx as f64 * dx,
+        ny as f64 * dy,
+        nz as f64 * dz
+    )?;
+    writeln!(
+        writer,
+        "# valuedim: 3\n# valuelabels: {0}_x {0}_y {0}_z",
+        title
+    )?;
+    writeln!(writer, "# valueunits: {0} {0} {0}", unit)?;
+    writeln!(writer, "# Desc: Total simulation time:  {:e}  s", time)?;
+    writeln!(
+        writer,
+        "# xbase: {:e}\n# ybase: {:e}\n# zbase: {:e}",
+        0.5 * dx,
+        0.5 * dy,
+        0.5 * dz
+    )?;
+    writeln!(
+        writer,
+        "# xnodes: {}\n# ynodes: {}\n# znodes: {}",
+        nx, ny, nz
+    )?;
+    writeln!(
+        writer,
+        "# xstepsize: {:e}\n# ystepsize: {:e}\n# zstepsize: {:e}",
+        dx, dy, dz
+    )?;
+    let format = if single_precision { 4 } else { 8 };
+    writeln!(writer, "# End: Header\n# Begin: Data Binary {}", format)?;
+    if single_precision {
+        writer.write_all(&1234567.0f32.to_le_bytes())?;
+    } else {
+        writer.write_all(&123456789012345.0f64.to_le_bytes())?;
+    }
+    for vector in &data.vectors {
+        for &value in vector {
+            if single_precision {
+                writer.write_all(&(value as f32).to_le_bytes())?;
+            } else {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    writeln!(writer, "\n# End: Data Binary {}\n# End: Segment", format)
+}
+
+///# Parse Text Data
+/// Whitespace separated values, lines starting with '#' end the block.
+fn parse_text_data(bytes: &[u8], count: usize) -> Result<Vec<[f64; 3]>, Box<dyn Error>> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut values = Vec::with_capacity(3 * count);
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            break;
+        }
+        for token in line.split_whitespace() {
+            values.push(token.parse::<f64>()?);
+        }
+    }
+    if values.len() < 3 * count {
+        return Err(format!("Expected {} values, found {}", 3 * count, values.len()).into());
+    }
+    Ok(values
+        .chunks_exact(3)
+        .take(count)
+        .map(|v| [v[0], v[1], v[2]])
+        .collect())
+}
+
+///# Parse Binary Data
+/// Binary blocks start with a check value that identifies the byte order.
+fn parse_binary_data(
+    bytes: &[u8],
+    count: usize,
+    width: usize,
+    big_endian: bool,
+) -> Result<Vec<[f64; 3]>, Box<dyn Error>> {
+    let needed = width * (3 * count + 1);
+    if bytes.len() < needed {
+        return Err(format!(
+            "Binary data block too short: {} of {} bytes",
+            bytes.len(),
+            needed
+        )
+        .into());
+    }
+    let read = |k: usize| -> f64 {
+        let chunk = &bytes[k * width..(k + 1) * width];
+        match (width, big_endian) {
+            (4, true) => f32::from_be_bytes(chunk.try_into().unwrap()) as f64,
+            (4, false) => f32::from_le_bytes(chunk.try_into().unwrap()) as f64,
+            (_, true) => f64::from_be_bytes(chunk.try_into().unwrap()),
+            (_, false) => f64::from_le_bytes(chunk.try_into().unwrap()),
+        }
+    };
+
+    let check_value = if width == 4 {
+        1234567.0
+    } else {
+        123456789012345.0
+    };
+    if read(0) != check_value {
+        return Err(format!("Invalid binary check value {}", read(0)).into());
+    }
+
+    Ok((0..count)
+        .map(|n| [read(3 * n + 1), read(3 * n + 2), read(3 * n + 3)])
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(version: &str, format: &str) -> String {
+        format!(
+            "# OOMMF OVF {}\n# Segment count: 1\n# Begin: Segment\n# Begin: Header\n\
+             # xnodes: 2\n# ynodes: 1\n# znodes: 1\n# xstepsize: 1e-9\n\
+             # ystepsize: 2e-9\n# zstepsize: 3e-9\n# valuedim: 3\n\
+             # valueunits: A/m A/m A/m\n# End: Header\n# Begin: Data {}\n",
+            version, format
+        )
+    }
+
+    #[test]
+    /// Test reading a text OVF 2.0 file
+    fn test_text_ovf() {
+        let file = header("2.0", "Text") + "1 0 0\n0 0 -1\n# End: Data Text\n# End: Segment\n";
+        let data = parse_ovf(file.as_bytes()).unwrap();
+        assert_eq!(data.nodes, [2, 1, 1]);
+        assert_eq!(data.step_sizes, [1e-9, 2e-9, 3e-9]);
+        assert_eq!(data.value_unit, "A/m");
+        assert_eq!(data.vectors, vec![[1.0, 0.0, 0.0], [0.0, 0.0, -1.0]]);
+    }
+
+    #[test]
+    /// Test reading little endian OVF 2.0 and big endian OVF 1.0 binary data
+    fn test_binary_ovf() {
+        let mut file = header("2.0", "Binary 8").into_bytes();
+        for value in [123456789012345.0, 0.5, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            file.extend_from_slice(&f64::to_le_bytes(value));
+        }
+        let data = parse_ovf(&file).unwrap();
+        assert_eq!(data.vectors, vec![[0.5, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+
+        let mut file = header("1.0", "Binary 4").into_bytes();
+        for value in [1234567.0f32, 0.0, 0.0, 1.0, -1.0, 0.0, 0.0] {
+            file.extend_from_slice(&f32::to_be_bytes(value));
+        }
+        let data = parse_ovf(&file).unwrap();
+        assert_eq!(data.vectors, vec![[0.0, 0.0, 1.0], [-1.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    /// Test that a written file reads back unchanged
+    fn test_write_ovf() {
+        let data = OvfData {
+            nodes: [3, 1, 1],
+            step_sizes: [1e-9, 1e-9, 1e-9],
+            value_unit: "1".to_string(),
+            vectors: vec![[1.0, 0.0, 0.0], [0.6, 0.8, 0.0], [0.0, 0.0, -1.0]],
+        };
+        let mut file = Vec::new();
+        write_ovf(&mut file, &data, "m", 1e-12).unwrap();
+        assert_eq!(parse_ovf(&file).unwrap(), data);
+        let text = String::from_utf8_lossy(&file);
+        assert!(text.contains("# Desc: Total simulation time:  1e-12  s"));
+        let wrong = OvfData {
+            nodes: [2, 1, 1],
+            ..data
+        };
+        assert!(write_ovf(&mut Vec::new(), &wrong, "m", 0.0).is_err());
+
+        assert!(write_ovf_single(&mut Vec::new(), &wrong, "m", 0.0).is_err());
+
+        // Binary 4 halves the data and rounds to f32
+        let third = OvfData {
+            nodes: [3, 1, 1],
+            vectors: vec![[1.0 / 3.0, 0.0, 0.0], [0.75, 0.5, 0.0], [0.0, 0.0, -1.0]],
+            ..wrong
+        };
+        let mut single = Vec::new();
+        write_ovf_single(&mut single, &third, "m", 0.0).unwrap();
+        assert!(String::from_utf8_lossy(&single).contains("# Begin: Data Binary 4"));
+        let read = parse_ovf(&single).unwrap();
+        assert_eq!(read.vectors[0][0], (1.0f64 / 3.0) as f32 as f64);
+        assert_eq!(read.vectors[1..], third.vectors[1..]);
+    }
+
+    #[test]
+    /// Test that a wrong check value is rejected
+    fn test_invalid_check_value() {
+        let mut file = header("2.0", "Binary 4").into_bytes();
+        for value in [1.0f32; 7] {
+            file.extend_from_slice(&f32::to_le_bytes(value));
+        }
+        assert!(parse_ovf(&file).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+// Helpers that run the per-cell loops on the rayon thread pool when the
+// `parallel` feature is enabled, and sequentially otherwise.
+
+use crate::summation::compensated_sum;
+#[cfg(feature = "parallel")]
+use crate::summation::CompensatedSum;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Cells per chunk of a deterministic sum, adaptive sums of fewer cells run sequentially
+pub const REDUCTION_CHUNK: usize = 1024;
+
+///# Reduction Order
+/// Order in which the per-cell terms of a parallel sum are added
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReductionOrder {
+    // Rayon splits the cells as its threads become free, so the rounding of
+    // the sum depends on the number of threads and the scheduling
+    #[default]
+    Adaptive,
+    // Fixed chunks of REDUCTION_CHUNK cells are summed in order and combined
+    // in a fixed pairwise tree, bit for bit the same for any number of
+    // threads and without the `parallel` feature
+    Deterministic,
+}
+
+///# Map Cells
+/// Evaluate `f` for every cell index and collect the results in order.
+#[cfg(feature = "parallel")]
+pub fn map_cells<T, F>(size: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync + Send,
+{
+    (0..size).into_par_iter().map(f).collect()
+}
+
+///# Map Cells
+/// Evaluate `f` for every cell index and collect the results in order.
+#[cfg(not(feature = "parallel"))]
+pub fn map_cells<T, F>(size: usize, f: F) -> Vec<T>
+where
+    F: Fn(usize) -> T,
+{
+    (0..size).map(f).collect()
+}
+
+///# Sum Cells
+/// Compensated sum of `f` over every cell index in the given order.
+#[cfg(feature = "parallel")]
+pub fn sum_cells<F>(size: usize, order: ReductionOrder, f: F) -> f64
+where
+    F: Fn(usize) -> f64 + Sync + Send,
+{
+    match order {
+        ReductionOrder::Deterministic => {
+            let partials: Vec<f64> = (0..size.div_ceil(REDUCTION_CHUNK))
+                .into_par_iter()
+                .map(|chunk| chunk_sum(size, chunk, &f))
+                .collect();
+            pairwise_sum(&partials)
+        }
+        ReductionOrder::Adaptive if size < REDUCTION_CHUNK => compensated_sum((0..size).map(f)),
+        ReductionOrder::Adaptive => (0..size)
+            .into_par_iter()
+            .fold(CompensatedSum::default, |mut sum, i| {
+                sum += f(i);
+                sum
+            })
+            .reduce(CompensatedSum::default, |mut sum, other| {
+                sum.merge(&other);
+                sum
+            })
+            .value(),
+    }
+}
+
+///# Sum Cells
+/// Compensated sum of `f` over every cell index in the given order.
+#[cfg(not(feature = "parallel"))]
+pub fn sum_cells<F>(size: usize, order: ReductionOrder, f: F) -> f64
+where
+    F: Fn(usize) -> f64,
+{
+    match order {
+        ReductionOrder::Deterministic => {
+            let partials: Vec<f64> = (0..size.div_ceil(REDUCTION_CHUNK))
+                .map(|chunk| chunk_sum(size, chunk, &f))
+                .collect();
+            pairwise_sum(&partials)
+        }
+        ReductionOrder::Adaptive => compensated_sum((0..size).map(f)),
+    }
+}
+
+// Compensated sum over one chunk of a deterministic reduction
+fn chunk_sum<F: Fn(usize) -> f64>(size: usize, chunk: usize, f: &F) -> f64 {
+    let start = chunk * REDUCTION_CHUNK;
+    compensated_sum((start..size.min(start + REDUCTION_CHUNK)).map(f))
+}
+
+// Sum of the halves, recursively, so the tree only depends on the length
+fn pairwise_sum(values: &[f64]) -> f64 {
+    match values {
+        [] => 0.0,
+        [value] => *value,
+        _ => {
+            let (left, right) = values.split_at(values.len() / 2);
+            pairwise_sum(left) + pairwise_sum(right)
+        }
+    }
+}
+
+///# With Threads
+/// Run `f` on a dedicated pool of `threads` worker threads.
+/// Without the `parallel` feature everything runs on the calling thread.
+#[cfg(feature = "parallel")]
+pub fn with_threads<R, F>(threads: usize, f: F) -> Result<R, rayon::ThreadPoolBuildError>
+where
+    R: Send,
+    F: FnOnce() -> R + Send,
+{
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()?;
+    Ok(pool.install(f))
+}
+
+///# With Threads
+/// Run `f` on a dedicated pool of `threads` worker threads.
+/// Without the `parallel` feature everything runs on the calling thread.
+#[cfg(not(feature = "parallel"))]
+pub fn with_threads<R, F>(_threads: usize, f: F) -> Result<R, std::convert::Infallible>
+where
+    F: FnOnce() -> R,
+{
+    Ok(f())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magnetic_moments::MicromagneticSystem;
+
+    #[test]
+    /// Test that a deterministic sum does not depend on the thread count
+    fn test_deterministic_sum() {
+        let size = 10 * REDUCTION_CHUNK + 17;
+        let term = |i: usize| ((i as f64) * 0.37).sin() * 10f64.powi((i % 7) as i32 - 3);
+        let sum = |threads: usize, order: ReductionOrder| {
+            with_threads(threads, || sum_cells(size, order, term)).unwrap()
+        };
+        let reference = sum(1, ReductionOrder::Deterministic);
+        for threads in [2, 3, 8] {
+            assert_eq!(
+                sum(threads, ReductionOrder::Deterministic).to_bits(),
+                reference.to_bits()
+            );
+        }
+        let sequential = compensated_sum((0..size).map(term));
+        assert!((sum(4, ReductionOrder::Adaptive) - sequential).abs() < 1e-12 * sequential.abs());
+        assert!((reference - sequential).abs() < 1e-12 * sequential.abs());
+        assert_eq!(sum_cells(0, ReductionOrder::Deterministic, term), 0.0);
+
+        let mut system = MicromagneticSystem::new(3 * REDUCTION_CHUNK);
+        system.set_reduction_order(ReductionOrder::Deterministic);
+        let energy = |threads: usize| {
+            with_threads(threads, || system.compute_energies().total().to_bits()).unwrap()
+        };
+        assert_eq!(energy(1), energy(5));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::convergence::ConvergencePolicy;
+use crate::grid::BoundaryCondition;
+use crate::material::Material;
+use crate::{EXTERNAL_FIELD, MAX_ITERATIONS_NUMBER, SPATIAL_DISCRETION_STEP, TIME_STEP};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Bounds of the change of the step size from one step to the next, and the
+// fraction of the optimal step that is taken to avoid rejections
+const MIN_STEP_FACTOR: f64 = 0.2;
+const MAX_STEP_FACTOR: f64 = 5.0;
+const STEP_SAFETY: f64 = 0.9;
+
+///# Simulation Parameters
+/// Runtime values of the constants a system starts with, so parameter
+/// sweeps need no recompilation. The default reproduces the built-in
+/// constants of the crate, the `with_` methods change one value each.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulationParameters {
+    // Material of every cell
+    pub material: Material,
+    // Applied field B = mu0 H in T
+    pub applied_field: [f64; 3],
+    // Pseudo time step of the relaxation in s
+    pub time_step: f64,
+    // Steps after which a minimization gives up
+    pub max_iterations: usize,
+    pub convergence: ConvergencePolicy,
+    // Step size control of the relaxation, the fixed time step when unset
+    pub adaptive_time_step: Option<AdaptiveTimeStep>,
+    // Exchange stencil at the outer faces of the grid
+    pub boundary_condition: BoundaryCondition,
+    // Edge length in m of the cubic cells
+    pub cell_size: f64,
+}
+
+///# Adaptive Time Step
+/// Step size controller with a local error estimate from step doubling:
+/// a full step is compared with two half steps, and the largest difference
+/// of a magnetization component, divided by 2^p - 1 for a method of order
+/// p, estimates the error of the full step. The error is taken relative to
+/// the largest component change of the step, so the controller also keeps
+/// the step below the stability limit of the stiff exchange modes near an
+/// equilibrium, where any absolute tolerance is eventually too loose.
+/// Steps with a larger error than the tolerance are repeated with a
+/// smaller step, and the next step is chosen to reach the tolerance,
+/// within the bounds.
+///
+/// ```toml
+/// [adaptive_time_step]
+/// tolerance = 1.0e-2
+/// min_step = 1.0e-15
+/// max_step = 1.0e-11
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdaptiveTimeStep {
+    // Largest accepted error of a step relative to its largest component change
+    pub tolerance: f64,
+    // Bounds of the step in s, a step at the lower bound is always accepted
+    pub min_step: f64,
+    pub max_step: f64,
+}
+
+impl AdaptiveTimeStep {
+    ///# Validate
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.tolerance.is_nan() || self.tolerance <= 0.0 {
+            return Err("The time step tolerance must be positive".into());
+        }
+        if self.min_step.is_nan() || self.min_step <= 0.0 || self.max_step < self.min_step {
+            return Err("The time step bounds must be positive and ordered".into());
+        }
+        Ok(())
+    }
+
+    ///# Control
+    /// Judge a step of the given size from the largest difference between
+    /// the full step and the two half steps of a method of the given order
+    /// and the largest component change of the step. Returns whether the
+    /// step is accepted and the size of the next step.
+    pub fn control(&self, step: f64, difference: f64, change: f64, order: i32) -> (bool, f64) {
+        let error = if change > 0.0 {
+            difference / ((2.0f64.powi(order) - 1.0) * change)
+        } else {
+            0.0
+        };
+        let accepted = error <= self.tolerance || step <= self.min_step;
+        let factor = if error > 0.0 {
+            (STEP_SAFETY * (self.tolerance / error).powf(1.0 / (order + 1) as f64))
+                .clamp(MIN_STEP_FACTOR, MAX_STEP_FACTOR)
+        } else {
+            MAX_STEP_FACTOR
+        };
+        (
+            accepted,
+            (step * factor).clamp(self.min_step, self.max_step),
+        )
+    }
+}
+
+impl Default for SimulationParameters {
+    fn default() -> Self {
+        Self {
+            material: Material::default(),
+            applied_field: EXTERNAL_FIELD,
+            time_step: TIME_STEP,
+            max_iterations: MAX_ITERATIONS_NUMBER,
+            convergence: ConvergencePolicy::default(),
+            adaptive_time_step: None,
+            boundary_condition: BoundaryCondition::default(),
+            cell_size: SPATIAL_DISCRETION_STEP,
+        }
+    }
+}
+
+impl SimulationParameters {
+    ///# With Material
+    pub fn with_material(self, material: Material) -> Self {
+        Self { material, ..self }
+    }
+
+    ///# With Applied Field
+    pub fn with_applied_field(self, applied_field: [f64; 3]) -> Self {
+        Self {
+            applied_field,
+            ..self
+        }
+    }
+
+    ///# With Time Step
+    pub fn with_time_step(self, time_step: f64) -> Self {
+        Self { time_step, ..self }
+    }
+
+    ///# With Maximum Iterations
+    pub fn with_max_iterations(self, max_iterations: usize) -> Self {
+        Self {
+            max_iterations,
+            ..self
+        }
+    }
+
+    ///# With Convergence Policy
+    pub fn with_convergence(self, convergence: ConvergencePolicy) -> Self {
+        Self {
+            convergence,
+            ..self
+        }
+    }
+
+    ///# With Adaptive Time Step
+    pub fn with_adaptive_time_step(self, adaptive_time_step: Option<AdaptiveTimeStep>) -> Self {
+        Self {
+            adaptive_time_step,
+            ..self
+        }
+    }
+
+    ///# With Boundary Condition
+    pub fn with_boundary_condition(self, boundary_condition: BoundaryCondition) -> Self {
+        Self {
+            boundary_condition,
+            ..self
+        }
+    }
+
+    ///# With Cell Size
+    pub fn with_cell_size(self, cell_size: f64) -> Self {
+        Self { cell_size, ..self }
+    }
+
+    ///# Validate
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.cell_size.is_nan() || self.cell_size <= 0.0 {
+            return Err("The cell size must be positive".into());
+        }
+        if self.time_step.is_nan() || self.time_step <= 0.0 {
+            return Err("The relaxation time step must be positive".into());
+        }
+        if self.max_iterations == 0 {
+            return Err("A minimization needs at least one iteration".into());
+        }
+        if self.material.damping < 0.0 {
+            return Err("The damping must not be negative".into());
+        }
+        if let Some(adaptive) = &self.adaptive_time_step {
+            adaptive.validate()?;
+        }
+        self.boundary_condition.validate()?;
+        self.convergence.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+
+    #[test]
+    /// Test that the parameters reach the system and its minimization
+    fn test_simulation_parameters() {
+        let default = MicromagneticSystem::new(4);
+        assert_eq!(default.get_time_step(), TIME_STEP);
+        assert_eq!(default.get_applied_field(), EXTERNAL_FIELD);
+        assert!(SimulationParameters::default().validate().is_ok());
+        assert!(SimulationParameters::default()
+            .with_time_step(0.0)
+            .validate()
+            .is_err());
+
+        let material = Material {
+            saturation_magnetization: 8.0e5,
+            ..Material::default()
+        };
+        let parameters = SimulationParameters::default()
+            .with_material(material)
+            .with_applied_field([0.0; 3])
+            .with_max_iterations(3);
+        let mut system = MicromagneticSystem::with_parameters(5, parameters);
+        assert!(system.get_materials().iter().all(|m| *m == material));
+        assert_eq!(system.get_applied_field(), [0.0; 3]);
+        let outcome = system.minimize_energy_until(|_, _| true);
+        assert_eq!(outcome, MinimizationOutcome::NotConverged { iterations: 3 });
+
+        // A larger relaxation step moves the cells further
+        let start = MicromagneticSystem::new(5);
+        let step = |time_step: f64| {
+            let mut system = start.clone();
+            system.set_time_step(time_step);
+            system.relaxation_step()
+        };
+        assert!(step(2.0 * TIME_STEP) > step(TIME_STEP));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::domains::analyze_domains;
+use crate::image_export::write_png;
+use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+use crate::EASY_AXIS;
+use ndarray::Array1;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Weak fields near a transition relax slowly, so a relaxation is continued
+// for up to this many iteration limits before giving up, as in the astroid
+const MAX_RELAXATION_ROUNDS: usize = 50;
+
+///# Magnetic State
+/// Class of a relaxed state. A single domain counts as saturated when it
+/// follows the applied field, as parallel or antiparallel when it stays
+/// along the easy axis and as canted in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MagneticState {
+    // Nearly every cell along the applied field
+    Saturated,
+    // One domain along the easy axis
+    Parallel,
+    // One domain against the easy axis
+    Antiparallel,
+    // One domain between the easy axis and the applied field
+    Canted,
+    // Domains of both orientations along the easy axis, separated by walls
+    MultiDomain,
+    // One domain by the easy axis projection, but far from uniform, e.g. a
+    // vortex or a spiral
+    NonUniform,
+}
+
+impl MagneticState {
+    ///# Label
+    /// Name of the state in the phase table.
+    pub fn label(self) -> &'static str {
+        match self {
+            MagneticState::Saturated => "saturated",
+            MagneticState::Parallel => "parallel",
+            MagneticState::Antiparallel => "antiparallel",
+            MagneticState::Canted => "canted",
+            MagneticState::MultiDomain => "multidomain",
+            MagneticState::NonUniform => "nonuniform",
+        }
+    }
+
+    ///# Color
+    /// Color of the state in the phase image.
+    pub fn color(self) -> [u8; 3] {
+        match self {
+            MagneticState::Saturated => [255, 255, 255],
+            MagneticState::Parallel => [214, 39, 40],
+            MagneticState::Antiparallel => [31, 119, 180],
+            MagneticState::Canted => [255, 187, 120],
+            MagneticState::MultiDomain => [44, 160, 44],
+            MagneticState::NonUniform => [127, 127, 127],
+        }
+    }
+}
+
+///# Classify State
+/// Class of the current state of the system in the applied field. A
+/// direction counts as followed when the average magnetization is within
+/// `threshold` of it in the cosine of the angle, and the state is uniform
+/// when the length of the average magnetization is at least `threshold`.
+pub fn classify_state(
+    system: &MicromagneticSystem,
+    easy_axis: &[f64; 3],
+    threshold: f64,
+) -> MagneticState {
+    let magnetizations = system.get_magnetizations();
+    if analyze_domains(&magnetizations, easy_axis)
+        .domains
+        .windows(2)
+        .any(|pair| pair[0].orientation != pair[1].orientation)
+    {
+        return MagneticState::MultiDomain;
+    }
+    let m = system.average_magnetization();
+    let length = dot(&m, &m).sqrt();
+    if length < threshold {
+        return MagneticState::NonUniform;
+    }
+    let field = system.get_applied_field();
+    let field_strength = dot(&field, &field).sqrt();
+    if field_strength > 0.0 && dot(&m, &field) / (length * field_strength) >= threshold {
+        return MagneticState::Saturated;
+    }
+    let projection = dot(&m, easy_axis) / (length * dot(easy_axis, easy_axis).sqrt());
+    if projection >= threshold {
+        MagneticState::Parallel
+    } else if projection <= -threshold {
+        MagneticState::Antiparallel
+    } else {
+        MagneticState::Canted
+    }
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+///# Phase Diagram Start
+/// State each point of the phase diagram is relaxed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PhaseDiagramStart {
+    // Saturated along the easy axis, the branch of a magnetized sample
+    #[default]
+    EasyAxis,
+    // The state of the given system, e.g. a relaxed wall
+    System,
+    // The relaxed state of the previous Hx of the same Hz, the first Hx
+    // starts from the given system, which traces the hysteretic branch
+    Sweep,
+}
+
+///# Phase Diagram
+/// Relaxation of the system over a grid of applied fields (Hx, 0, Hz),
+/// given as B = mu0 H in T, with the class of the relaxed state at every
+/// point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseDiagram {
+    pub hx: Vec<f64>,
+    pub hz: Vec<f64>,
+    pub start: PhaseDiagramStart,
+    pub easy_axis: [f64; 3],
+    // Cosine above which the state counts as along a direction
+    pub threshold: f64,
+}
+
+///# Phase Point
+/// Relaxed state at one field of the phase diagram
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhasePoint {
+    pub hx: f64,
+    pub hz: f64,
+    pub state: MagneticState,
+    pub average_magnetization: [f64; 3],
+    // Total energy in J
+    pub energy: f64,
+    // Number of domains along the easy axis
+    pub domains: usize,
+    pub converged: bool,
+}
+
+impl Default for PhaseDiagram {
+    /// 21 by 21 fields up to 0.1 T, which spans the anisotropy field of the
+    /// default material
+    fn default() -> Self {
+        Self::linear((-0.1, 0.1), (-0.1, 0.1), 21)
+    }
+}
+
+impl PhaseDiagram {
+    ///# Linear Phase Diagram
+    /// `points` equally spaced fields in T between the bounds of each axis.
+    pub fn linear(hx: (f64, f64), hz: (f64, f64), points: usize) -> Self {
+        let values = |(start, end): (f64, f64)| -> Vec<f64> {
+            if points < 2 {
+                return vec![start; points];
+            }
+            (0..points)
+                .map(|k| start + (end - start) * k as f64 / (points - 1) as f64)
+                .collect()
+        };
+        Self {
+            hx: values(hx),
+            hz: values(hz),
+            start: PhaseDiagramStart::default(),
+            easy_axis: EASY_AXIS,
+            threshold: 0.99,
+        }
+    }
+
+    ///# Run Phase Diagram
+    /// Relax a copy of the system at every field and classify the result.
+    /// The points are in rows of constant Hz with Hx running fastest.
+    pub fn run(&self, system: &MicromagneticSystem) -> Result<Vec<PhasePoint>, Box<dyn Error>> {
+        if self.hx.is_empty() || self.hz.is_empty() {
+            return Err("The phase diagram needs at least one field per axis".into());
+        }
+        if self.easy_axis.iter().all(|&x| x == 0.0) {
+            return Err("The easy axis is zero".into());
+        }
+        if self.threshold.is_nan() || self.threshold <= 0.0 || self.threshold > 1.0 {
+            return Err("The threshold must be within 0..1".into());
+        }
+        let mut points = Vec::with_capacity(self.hx.len() * self.hz.len());
+        for &hz in &self.hz {
+            let mut state = system.clone();
+            for &hx in &self.hx {
+                match self.start {
+                    PhaseDiagramStart::EasyAxis => {
+                        for cell in 0..state.size() {
+                            state
+                                .set_magnetization(cell, Array1::from_vec(self.easy_axis.to_vec()));
+                        }
+                    }
+                    PhaseDiagramStart::System => state = system.clone(),
+                    PhaseDiagramStart::Sweep => {}
+                }
+                state.set_applied_field([hx, 0.0, hz]);
+                let converged = relax(&mut state);
+                let m = state.average_magnetization();
+                points.push(PhasePoint {
+                    hx,
+                    hz,
+                    state: classify_state(&state, &self.easy_axis, self.threshold),
+                    average_magnetization: m,
+                    energy: state.compute_energies().total(),
+                    domains: analyze_domains(&state.get_magnetizations(), &self.easy_axis).count(),
+                    converged,
+                });
+            }
+        }
+        Ok(points)
+    }
+}
+
+///# Relax
+/// Relax for up to the given number of iteration limits and report
+/// whether the relaxation converged.
+fn relax(system: &mut MicromagneticSystem) -> bool {
+    for _ in 0..MAX_RELAXATION_ROUNDS {
+        match system.minimize_energy_until(|_, _| true) {
+            MinimizationOutcome::NotConverged { .. } => {}
+            outcome => return matches!(outcome, MinimizationOutcome::Converged { .. }),
+        }
+    }
+    false
+}
+
+///# Write Phase Table
+/// Tab-separated field, state and averages of every point, in the order of
+/// the run.
+pub fn write_phase_table(path: &Path, points: &[PhasePoint]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "# Bx (T)\tBz (T)\tstate\t<mx> ()\t<my> ()\t<mz> ()\tE (J)\tdomains\tconverged"
+    )?;
+    for point in points {
+        let m = point.average_magnetization;
+        writeln!(
+            writer,
+            "{:e}\t{:e}\t{}\t{:e}\t{:e}\t{:e}\t{:e}\t{}\t{}",
+            point.hx,
+            point.hz,
+            point.state.label(),
+            m[0],
+            m[1],
+            m[2],
+            point.energy,
+            point.domains,
+            point.converged
+        )?;
+    }
+    writer.flush()
+}
+
+///# Write Phase Image
+/// PNG of the states as squares of `scale` pixels, Hx to the right and Hz
+/// upwards, for the points of a phase diagram with `columns` values of Hx.
+pub fn write_phase_image(
+    path: &Path,
+    points: &[PhasePoint],
+    columns: usize,
+    scale: u32,
+) -> Result<(), Box<dyn Error>> {
+    let colors: Vec<[u8; 3]> = points.iter().map(|point| point.state.color()).collect();
+    write_png(path, &colors, columns, scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SATURATION_MAGNETIZATION, UNIAXIAL_ANISOTROPY_CONSTANT};
+
+    #[test]
+    /// Test the states of a macrospin against the Stoner-Wohlfarth model
+    fn test_phase_diagram() {
+        // B_K = 2 K / Ms, a hard axis field above it saturates the macrospin
+        // and a reversal field below it leaves it on its branch
+        use MagneticState::*;
+        let anisotropy_field = 2.0 * UNIAXIAL_ANISOTROPY_CONSTANT / SATURATION_MAGNETIZATION;
+        let diagram = PhaseDiagram {
+            hx: vec![-0.2 * anisotropy_field, 0.0, 0.2 * anisotropy_field],
+            hz: vec![0.0, 0.5 * anisotropy_field, 3.0 * anisotropy_field],
+            ..PhaseDiagram::default()
+        };
+        let system = MicromagneticSystem::new(1);
+        let points = diagram.run(&system).unwrap();
+        let states: Vec<MagneticState> = points.iter().map(|p| p.state).collect();
+        assert_eq!(
+            states,
+            [
+                Parallel, Parallel, Saturated, Canted, Canted, Canted, Saturated, Saturated,
+                Saturated
+            ]
+        );
+        // sin(theta) = Bz / B_K without an easy axis field
+        let canted = points[4].average_magnetization;
+        assert!((canted[2] - 0.5).abs() < 1e-2);
+        assert!(points.iter().all(|p| p.converged && p.domains == 1));
+
+        // Sweeping down from the branch along the easy axis switches only
+        // past the anisotropy field, a slight tilt breaks the symmetry
+        let sweep = PhaseDiagram {
+            hx: vec![0.0, -0.5 * anisotropy_field, -1.5 * anisotropy_field],
+            hz: vec![0.05 * anisotropy_field],
+            start: PhaseDiagramStart::Sweep,
+            ..PhaseDiagram::default()
+        };
+        let mut aligned = system.clone();
+        aligned.set_magnetization(0, Array1::from_vec(EASY_AXIS.to_vec()));
+        let states: Vec<MagneticState> = sweep
+            .run(&aligned)
+            .unwrap()
+            .iter()
+            .map(|p| p.state)
+            .collect();
+        assert_eq!(states, [Parallel, Parallel, Saturated]);
+
+        // Two domains along the chain
+        let mut wall = MicromagneticSystem::new(20);
+        for cell in 0..20 {
+            let sign = if cell < 10 { 1.0 } else { -1.0 };
+            wall.set_magnetization(cell, ndarray::array![sign, 0.0, 0.1]);
+        }
+        assert_eq!(classify_state(&wall, &EASY_AXIS, 0.99), MultiDomain);
+        assert!(PhaseDiagram::linear((0.0, 0.1), (0.0, 0.1), 0)
+            .run(&wall)
+            .is_err());
+    }
+}
@@ -0,0 +1,324 @@
+use crate::dynamics::{DynamicsRun, TimeDependentField};
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::material::Material;
+use crate::readout::Magnetoresistance;
+use crate::temperature::TemperatureScaling;
+use crate::time_series::{evaluate_columns, TimeSeriesColumn};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{self, Write};
+
+///# Protocol Step
+/// One segment of a measurement protocol, executed in the order of the
+/// configuration.
+///
+/// ```toml
+/// [[protocol]]
+/// type = "set_field"
+/// field = [0.1, 0.0, 0.0]
+///
+/// [[protocol]]
+/// type = "ramp"
+/// field = [-0.1, 0.0, 0.0]
+/// temperature = 400.0
+/// steps = 20
+/// measure = true
+///
+/// [[protocol]]
+/// type = "wait"
+/// duration = 1.0e-10
+///
+/// [[protocol]]
+/// type = "measure"
+/// label = "after the ramp"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ProtocolStep {
+    // Set the uniform applied field in T
+    SetField {
+        field: [f64; 3],
+    },
+    // Set the temperature in K, the materials follow the temperature scaling
+    SetTemperature {
+        temperature: f64,
+    },
+    // Change field and temperature linearly to the given values in equal
+    // steps and relax after each, measuring after each when `measure` is set
+    Ramp {
+        #[serde(default)]
+        field: Option<[f64; 3]>,
+        #[serde(default)]
+        temperature: Option<f64>,
+        steps: usize,
+        #[serde(default)]
+        measure: bool,
+    },
+    // Precess and damp in the current field for the duration in s
+    Wait {
+        duration: f64,
+    },
+    // Minimize the energy in the current field
+    Relax,
+    // Record the observables
+    Measure {
+        #[serde(default)]
+        label: String,
+    },
+}
+
+///# Protocol Measurement
+/// Observables recorded by a measure step or a measuring ramp.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolMeasurement {
+    // Index of the protocol step that recorded it
+    pub step: usize,
+    pub label: String,
+    // Simulated time of the wait steps so far in s
+    pub time: f64,
+    pub field: [f64; 3],
+    pub temperature: f64,
+    // Values of the measured columns
+    pub values: Vec<f64>,
+}
+
+///# Protocol Engine
+/// Executes the steps of a protocol on a system. The materials of the
+/// system at the start are the constants at zero temperature when a
+/// temperature scaling is given, temperature steps need one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolEngine {
+    pub scaling: Option<TemperatureScaling>,
+    // Temperature in K at the start
+    pub temperature: f64,
+    // Observables recorded by the measurements
+    pub columns: Vec<TimeSeriesColumn>,
+    // Integration time step of the wait steps in s
+    pub time_step: f64,
+    // Readout of the resistance column
+    pub readout: Option<Magnetoresistance>,
+}
+
+impl ProtocolEngine {
+    ///# Run Protocol
+    /// Execute the steps in order and return the measurements.
+    pub fn run(
+        &self,
+        system: &mut MicromagneticSystem,
+        steps: &[ProtocolStep],
+    ) -> Result<Vec<ProtocolMeasurement>, Box<dyn Error>> {
+        let base = system.get_materials();
+        let mut temperature = self.temperature;
+        if self.scaling.is_some() {
+            self.set_temperature(system, &base, temperature)?;
+        }
+        let mut time = 0.0;
+        let mut measurements = Vec::new();
+        for (index, step) in steps.iter().enumerate() {
+            let measure =
+                |system: &MicromagneticSystem, label: &str, temperature: f64| ProtocolMeasurement {
+                    step: index,
+                    label: label.to_string(),
+                    time,
+                    field: system.get_applied_field(),
+                    temperature,
+                    values: evaluate_columns(&self.columns, system, self.readout.as_ref()),
+                };
+            match step {
+                ProtocolStep::SetField { field } => system.set_applied_field(*field),
+                ProtocolStep::SetTemperature {
+                    temperature: target,
+                } => {
+                    self.set_temperature(system, &base, *target)?;
+                    temperature = *target;
+                }
+                ProtocolStep::Ramp {
+                    field,
+                    temperature: target,
+                    steps,
+                    measure: measuring,
+                } => {
+                    if *steps == 0 {
+                        return Err(format!("Ramp step {} needs at least one step", index).into());
+                    }
+                    let (start_field, start_temperature) =
+                        (system.get_applied_field(), temperature);
+                    for substep in 1..=*steps {
+                        let fraction = substep as f64 / *steps as f64;
+                        if let Some(field) = field {
+                            system.set_applied_field(
+                                [0, 1, 2].map(|k| {
+                                    start_field[k] + fraction * (field[k] - start_field[k])
+                                }),
+                            );
+                        }
+                        if let Some(target) = target {
+                            temperature =
+                                start_temperature + fraction * (target - start_temperature);
+                            self.set_temperature(system, &base, temperature)?;
+                        }
+                        system.minimize_energy();
+                        if *measuring {
+                            measurements.push(measure(system, "ramp", temperature));
+                        }
+                    }
+                }
+                ProtocolStep::Wait { duration } => {
+                    if duration.is_nan() || *duration < 0.0 {
+                        return Err(format!("Wait step {} has a negative duration", index).into());
+                    }
+                    let mut run =
+                        DynamicsRun::new(TimeDependentField::Constant(system.get_applied_field()));
+                    run.time_step = self.time_step;
+                    time += run.run(system, *duration, |_, _| {});
+                }
+                ProtocolStep::Relax => system.minimize_energy(),
+                ProtocolStep::Measure { label } => {
+                    measurements.push(measure(system, label, temperature))
+                }
+            }
+        }
+        Ok(measurements)
+    }
+
+    // Materials at the temperature from the zero temperature ones
+    fn set_temperature(
+        &self,
+        system: &mut MicromagneticSystem,
+        base: &[Material],
+        temperature: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let scaling = self
+            .scaling
+            .as_ref()
+            .ok_or("Temperature steps need a temperature scaling")?;
+        for (cell, material) in base.iter().enumerate() {
+            if !material.is_vacuum() {
+                system.set_material(cell, scaling.material_at(material, temperature)?);
+            }
+        }
+        Ok(())
+    }
+
+    ///# Write Measurements
+    /// Tab separated table with the step, label, time, field, temperature
+    /// and the measured columns.
+    pub fn write_measurements<W: Write>(
+        &self,
+        mut writer: W,
+        measurements: &[ProtocolMeasurement],
+    ) -> io::Result<()> {
+        write!(
+            writer,
+            "# step\tlabel\tt (s)\tB_x (T)\tB_y (T)\tB_z (T)\tT (K)"
+        )?;
+        for column in &self.columns {
+            write!(writer, "\t{}", column.header())?;
+        }
+        writeln!(writer)?;
+        for measurement in measurements {
+            let [bx, by, bz] = measurement.field;
+            write!(
+                writer,
+                "{}\t{}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}",
+                measurement.step,
+                measurement.label,
+                measurement.time,
+                bx,
+                by,
+                bz,
+                measurement.temperature
+            )?;
+            for value in &measurement.values {
+                write!(writer, "\t{:e}", value)?;
+            }
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DYNAMICS_TIME_STEP;
+    use ndarray::array;
+
+    #[test]
+    /// Test a field ramp, a heating step and the measurement table
+    fn test_protocol() {
+        let toml = r#"
+[[protocol]]
+type = "set_field"
+field = [0.0, 0.0, 0.0]
+
+[[protocol]]
+type = "relax"
+
+[[protocol]]
+type = "measure"
+label = "remanence"
+
+[[protocol]]
+type = "ramp"
+field = [0.0, 2.0, 0.0]
+steps = 2
+measure = true
+
+[[protocol]]
+type = "set_temperature"
+temperature = 500.0
+
+[[protocol]]
+type = "wait"
+duration = 1.0e-13
+
+[[protocol]]
+type = "measure"
+"#;
+        #[derive(Deserialize)]
+        struct File {
+            protocol: Vec<ProtocolStep>,
+        }
+        let steps = toml::from_str::<File>(toml).unwrap().protocol;
+        assert_eq!(steps[1], ProtocolStep::Relax);
+
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]; 3]);
+        let engine = ProtocolEngine {
+            scaling: Some(TemperatureScaling::new(1000.0)),
+            temperature: 0.0,
+            columns: vec![TimeSeriesColumn::Mx, TimeSeriesColumn::My],
+            time_step: DYNAMICS_TIME_STEP,
+            readout: None,
+        };
+        let measurements = engine.run(&mut system, &steps).unwrap();
+        assert_eq!(measurements.len(), 4);
+        assert_eq!(measurements[0].label, "remanence");
+        assert!((measurements[0].values[0] - 1.0).abs() < 1e-6);
+        // Both ramp fields exceed the anisotropy field of about 0.056 T
+        assert_eq!(measurements[1].field, [0.0, 1.0, 0.0]);
+        assert!(measurements[1].values[1] > 0.99);
+        assert_eq!(measurements[2].field, [0.0, 2.0, 0.0]);
+        assert_eq!(measurements[3].temperature, 500.0);
+        assert!((measurements[3].time - 1e-13).abs() < 1e-20);
+        let ms = system.get_materials()[0].saturation_magnetization;
+        let m = 1.0 - 0.5_f64.powf(1.5);
+        assert!((ms / crate::SATURATION_MAGNETIZATION - m).abs() < 1e-12);
+
+        let mut table = Vec::new();
+        engine
+            .write_measurements(&mut table, &measurements)
+            .unwrap();
+        let text = String::from_utf8(table).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].starts_with("2\tremanence\t"));
+        assert_eq!(lines[4].split('\t').count(), 9);
+
+        let cold = ProtocolEngine {
+            scaling: None,
+            ..engine
+        };
+        assert!(cold.run(&mut system, &steps[4..5]).is_err());
+    }
+}
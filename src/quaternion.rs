@@ -0,0 +1,78 @@
+///# Quaternion
+/// Rotation quaternion w + x i + y j + z k.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    ///# Identity
+    pub const IDENTITY: Quaternion = Quaternion {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    ///# From Rotation Vector
+    /// Rotation by the angle |v| in rad about the direction of v,
+    /// counterclockwise when looking against v.
+    pub fn from_rotation_vector(v: [f64; 3]) -> Self {
+        let angle = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        if angle == 0.0 {
+            return Self::IDENTITY;
+        }
+        let (sin, cos) = (0.5 * angle).sin_cos();
+        let scale = sin / angle;
+        Self {
+            w: cos,
+            x: scale * v[0],
+            y: scale * v[1],
+            z: scale * v[2],
+        }
+    }
+
+    ///# Rotate Vector
+    /// Computes q v q* without forming the rotation matrix,
+    /// v' = v + 2 w (u x v) + 2 u x (u x v) with u the vector part.
+    pub fn rotate(&self, v: [f64; 3]) -> [f64; 3] {
+        let u = [self.x, self.y, self.z];
+        let t = cross(&u, &v);
+        let t = [2.0 * t[0], 2.0 * t[1], 2.0 * t[2]];
+        let u_cross_t = cross(&u, &t);
+        [
+            v[0] + self.w * t[0] + u_cross_t[0],
+            v[1] + self.w * t[1] + u_cross_t[1],
+            v[2] + self.w * t[2] + u_cross_t[2],
+        ]
+    }
+}
+
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    /// Test a quarter turn about z and the identity
+    fn test_rotate() {
+        let q = Quaternion::from_rotation_vector([0.0, 0.0, FRAC_PI_2]);
+        let v = q.rotate([1.0, 0.0, 0.0]);
+        assert!(v[0].abs() < 1e-15 && (v[1] - 1.0).abs() < 1e-15 && v[2].abs() < 1e-15);
+        assert_eq!(
+            Quaternion::from_rotation_vector([0.0; 3]).rotate([0.3, 0.4, 0.5]),
+            [0.3, 0.4, 0.5]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+///# Magnetoresistance Model
+/// Angular dependence of the resistance on the angle theta between the
+/// free layer and the reference direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MagnetoresistanceModel {
+    // Spin valve, R = R_P + (R_AP - R_P) (1 - cos theta) / 2
+    #[default]
+    Gmr,
+    // Tunnel junction, the conductance G = G_P (1 + cos theta) / 2 + G_AP (1 - cos theta) / 2
+    Tmr,
+}
+
+///# Magnetoresistance
+/// Readout of a spin valve or magnetic tunnel junction: the free layer
+/// is made of the cells `start..end`, the fixed layer is magnetized along
+/// `reference` without being simulated. The ratio (R_AP - R_P) / R_P is
+/// the GMR or TMR ratio, e.g. 1.0 for 100 % TMR.
+///
+/// ```toml
+/// [magnetoresistance]
+/// model = "tmr"
+/// start = 40
+/// end = 50
+/// reference = [1.0, 0.0, 0.0]
+/// parallel_resistance = 1.0e3
+/// ratio = 1.0
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Magnetoresistance {
+    #[serde(default)]
+    pub model: MagnetoresistanceModel,
+    pub start: usize,
+    pub end: usize,
+    pub reference: [f64; 3],
+    // Resistance R_P of the parallel state in Ohm
+    pub parallel_resistance: f64,
+    pub ratio: f64,
+}
+
+impl Magnetoresistance {
+    ///# Validate
+    pub fn validate(&self, number_of_cells: usize) -> Result<(), Box<dyn Error>> {
+        if self.start >= self.end || self.end > number_of_cells {
+            return Err(format!(
+                "The free layer {}..{} is not within the {} cells",
+                self.start, self.end, number_of_cells
+            )
+            .into());
+        }
+        if self.reference.iter().all(|&c| c == 0.0) {
+            return Err("The reference direction must not be zero".into());
+        }
+        if self.parallel_resistance.is_nan() || self.parallel_resistance <= 0.0 {
+            return Err("The parallel resistance must be positive".into());
+        }
+        if self.ratio.is_nan() || self.ratio <= -1.0 {
+            return Err("The magnetoresistance ratio must be above -1".into());
+        }
+        Ok(())
+    }
+
+    ///# Cosine of the Angle
+    /// Average of cos theta over the magnetic cells of the free layer, NaN
+    /// when it has none.
+    pub fn cos_angle(&self, system: &MicromagneticSystem) -> f64 {
+        let norm = self.reference.iter().map(|c| c * c).sum::<f64>().sqrt();
+        let magnetizations = system.get_magnetizations();
+        let cells: Vec<usize> = (self.start..self.end.min(system.size()))
+            .filter(|&i| !system.is_vacuum(i))
+            .collect();
+        let sum: f64 = cells
+            .iter()
+            .map(|&i| {
+                (0..3)
+                    .map(|c| magnetizations[i][c] * self.reference[c])
+                    .sum::<f64>()
+            })
+            .sum();
+        sum / (norm * cells.len() as f64)
+    }
+
+    ///# Resistance at an Angle
+    /// Resistance in Ohm for the given cos theta.
+    pub fn resistance_at(&self, cos_angle: f64) -> f64 {
+        let parallel = self.parallel_resistance;
+        let antiparallel = parallel * (1.0 + self.ratio);
+        match self.model {
+            MagnetoresistanceModel::Gmr => {
+                parallel + (antiparallel - parallel) * 0.5 * (1.0 - cos_angle)
+            }
+            MagnetoresistanceModel::Tmr => {
+                let conductance =
+                    0.5 * (1.0 + cos_angle) / parallel + 0.5 * (1.0 - cos_angle) / antiparallel;
+                1.0 / conductance
+            }
+        }
+    }
+
+    ///# Resistance
+    /// Resistance in Ohm of the current state of the system.
+    pub fn resistance(&self, system: &MicromagneticSystem) -> f64 {
+        self.resistance_at(self.cos_angle(system))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    /// Test the resistance of the parallel, antiparallel and perpendicular states
+    fn test_magnetoresistance() {
+        let mut readout = Magnetoresistance {
+            model: MagnetoresistanceModel::Gmr,
+            start: 2,
+            end: 4,
+            reference: [2.0, 0.0, 0.0],
+            parallel_resistance: 100.0,
+            ratio: 0.5,
+        };
+        assert!(readout.validate(4).is_ok());
+        assert!(readout.validate(3).is_err());
+
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![-1.0, 0.0, 0.0]; 4]);
+        system.set_magnetization(2, array![1.0, 0.0, 0.0]);
+        system.set_magnetization(3, array![1.0, 0.0, 0.0]);
+        assert_eq!(readout.cos_angle(&system), 1.0);
+        assert_eq!(readout.resistance(&system), 100.0);
+        assert_eq!(readout.resistance_at(-1.0), 150.0);
+        assert_eq!(readout.resistance_at(0.0), 125.0);
+
+        // The tunnel junction adds the conductances of the two channels
+        readout.model = MagnetoresistanceModel::Tmr;
+        assert_eq!(readout.resistance_at(1.0), 100.0);
+        assert!((readout.resistance_at(-1.0) - 150.0).abs() < 1e-12);
+        assert!((readout.resistance_at(0.0) - 120.0).abs() < 1e-12);
+
+        // Vacuum cells do not count
+        system.set_magnetization(3, array![0.0, 1.0, 0.0]);
+        system.set_saturation_magnetization(3, 0.0);
+        assert_eq!(readout.cos_angle(&system), 1.0);
+        system.set_saturation_magnetization(2, 0.0);
+        assert!(readout.cos_angle(&system).is_nan());
+    }
+}
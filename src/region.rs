@@ -0,0 +1,174 @@
+use crate::grid::Grid;
+use crate::material::Material;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+///# Shape
+/// Cells of a region, as a range of cell indices or as a body in m that
+/// contains the centers of its cells. The first cell is at the origin and
+/// the cells count as x + nx (y + ny z).
+///
+/// ```toml
+/// [[regions]]
+/// material = "vacuum"
+/// shape = { type = "cylinder", center = [20e-9, 0.0], radius = 5e-9 }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Shape {
+    // Cells start..end
+    Range { start: usize, end: usize },
+    // Axis-aligned box between two corners in m, both included
+    Box { min: [f64; 3], max: [f64; 3] },
+    // Cylinder along z through the center (x, y) in m, a disk in a film
+    Cylinder { center: [f64; 2], radius: f64 },
+    // Ball around the center in m
+    Sphere { center: [f64; 3], radius: f64 },
+}
+
+impl Shape {
+    ///# Validate
+    /// A range must lie within the grid and a body must have a finite,
+    /// non-negative extent.
+    pub fn validate(&self, grid: &Grid) -> Result<(), Box<dyn Error>> {
+        match *self {
+            Shape::Range { start, end } => {
+                if start > end || end > grid.size() {
+                    return Err(format!(
+                        "Region {}..{} is outside the {} cells",
+                        start,
+                        end,
+                        grid.size()
+                    )
+                    .into());
+                }
+            }
+            Shape::Box { min, max } => {
+                if (0..3).any(|k| !min[k].is_finite() || !max[k].is_finite() || min[k] > max[k]) {
+                    return Err("The box of a region needs finite corners with min <= max".into());
+                }
+            }
+            Shape::Cylinder { radius, .. } | Shape::Sphere { radius, .. } => {
+                if !radius.is_finite() || radius < 0.0 {
+                    return Err("The radius of a region must not be negative".into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///# Contains
+    /// Whether the cell of the grid with the edge length `cell_size`
+    /// belongs to the shape.
+    pub fn contains(&self, grid: &Grid, cell_size: f64, cell: usize) -> bool {
+        // Allow for the rounding of the cell positions on the boundary
+        let tolerance = 1e-9 * cell_size;
+        let [x, y, z] = grid.position(cell, cell_size);
+        match *self {
+            Shape::Range { start, end } => (start..end).contains(&cell),
+            Shape::Box { min, max } => [x, y, z]
+                .iter()
+                .enumerate()
+                .all(|(k, &p)| p >= min[k] - tolerance && p <= max[k] + tolerance),
+            Shape::Cylinder { center, radius } => {
+                (x - center[0]).hypot(y - center[1]) <= radius + tolerance
+            }
+            Shape::Sphere { center, radius } => {
+                let squared: f64 = [x, y, z]
+                    .iter()
+                    .zip(center)
+                    .map(|(p, c)| (p - c).powi(2))
+                    .sum();
+                squared.sqrt() <= radius + tolerance
+            }
+        }
+    }
+
+    ///# Cells
+    /// Every cell of the grid in the shape, in ascending order.
+    pub fn cells(&self, grid: &Grid, cell_size: f64) -> Vec<usize> {
+        (0..grid.size())
+            .filter(|&cell| self.contains(grid, cell_size, cell))
+            .collect()
+    }
+}
+
+///# Region
+/// Cells of a shape made of one material, e.g. the hard layer of a bilayer
+/// or a notch of vacuum. `MicromagneticSystem::add_region` assigns the
+/// material and numbers the regions from 1 in the order they are added, so
+/// a later region overrides the cells it shares with an earlier one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub shape: Shape,
+    pub material: Material,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magnetic_moments::MicromagneticSystem;
+    use crate::parameters::SimulationParameters;
+    use crate::SPATIAL_DISCRETION_STEP;
+
+    #[test]
+    /// Test a soft/hard bilayer with a vacuum notch and the region ids
+    fn test_regions() {
+        let grid = Grid::new(10, 4, 2);
+        let mut system = MicromagneticSystem::on_grid(grid, SimulationParameters::default());
+        let cell = SPATIAL_DISCRETION_STEP;
+        let hard = Material {
+            anisotropy_constant: 1e6,
+            ..system.get_materials()[0]
+        };
+        // The upper layer is hard
+        let layer = system
+            .add_region(&Region {
+                shape: Shape::Range { start: 40, end: 80 },
+                material: hard,
+            })
+            .unwrap();
+        // A notch through both layers at the middle of the lower edge
+        let notch = system
+            .add_region(&Region {
+                shape: Shape::Cylinder {
+                    center: [4.5 * cell, 0.0],
+                    radius: 1.0 * cell,
+                },
+                material: Material::vacuum(),
+            })
+            .unwrap();
+        assert_eq!((layer, notch), (1, 2));
+
+        let regions = system.get_regions();
+        assert_eq!(regions[grid.index(0, 0, 0)], 0);
+        assert_eq!(regions[grid.index(0, 0, 1)], 1);
+        for z in 0..2 {
+            assert_eq!(regions[grid.index(4, 0, z)], 2);
+            assert_eq!(regions[grid.index(5, 0, z)], 2);
+            // The lower layer is outside every region
+            assert_eq!(regions[grid.index(4, 1, z)], z);
+        }
+        assert!(system.is_vacuum(grid.index(4, 0, 1)));
+        assert!(!system.is_vacuum(grid.index(4, 1, 1)));
+        assert_eq!(system.get_materials()[grid.index(9, 3, 1)], hard);
+
+        let corner = Shape::Box {
+            min: [0.0; 3],
+            max: [1.0 * cell, 1.0 * cell, 0.0],
+        };
+        assert_eq!(corner.cells(&grid, cell), vec![0, 1, 10, 11]);
+        let ball = Shape::Sphere {
+            center: [0.0; 3],
+            radius: 1.0 * cell,
+        };
+        assert_eq!(ball.cells(&grid, cell), vec![0, 1, 10, 40]);
+
+        let outside = Region {
+            shape: Shape::Range { start: 70, end: 81 },
+            material: hard,
+        };
+        assert!(system.add_region(&outside).is_err());
+        assert_eq!(system.get_regions().iter().max(), Some(&2));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::ops::Range;
+
+// Smallest remaining cross section of a cell, keeps deep notches from cutting the wire
+const MIN_CROSS_SECTION: f64 = 0.1;
+
+///# Edge Roughness
+/// Random variation of the wire cross section along the chain. The relative
+/// change of the cross section is a Gaussian profile with the rms `amplitude`
+/// and an exponential correlation over `correlation_length` in m. A cell whose
+/// cross section changes by the factor f carries the moment f Ms, and its
+/// shape-dominated anisotropy scales with f^2 Ms^2, i.e. K becomes f^2 K.
+/// The local anisotropy field 2K/(mu0 Ms) therefore varies by f, which pins
+/// domain walls and spreads the coercivity of rough wires.
+///
+/// ```toml
+/// [edge_roughness]
+/// amplitude = 0.1
+/// correlation_length = 5.0e-9
+/// seed = 7
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EdgeRoughness {
+    pub amplitude: f64,
+    pub correlation_length: f64,
+    // Seed of the random edge profile, the same seed gives the same wire
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl EdgeRoughness {
+    ///# Cross Section Factors
+    /// Relative cross section f of each of `cells` consecutive cells with
+    /// the edge length `cell_size`.
+    pub fn cross_sections(&self, cells: usize, cell_size: f64) -> Result<Vec<f64>, Box<dyn Error>> {
+        let valid = self.amplitude >= 0.0 && self.correlation_length > 0.0;
+        if !valid {
+            return Err(
+                "The roughness amplitude must not be negative and the correlation length must be positive"
+                    .into(),
+            );
+        }
+        // First order autoregressive process with unit variance and the
+        // correlation exp(-|x - x'| / correlation_length)
+        let correlation = (-cell_size / self.correlation_length).exp();
+        let innovation = (1.0 - correlation * correlation).sqrt();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut displacement = gaussian(&mut rng);
+        let mut factors = Vec::with_capacity(cells);
+        for _ in 0..cells {
+            factors.push((1.0 + self.amplitude * displacement).max(MIN_CROSS_SECTION));
+            displacement = correlation * displacement + innovation * gaussian(&mut rng);
+        }
+        Ok(factors)
+    }
+
+    ///# Apply Roughness
+    /// Scale Ms and K of the cells in the range, vacuum cells are skipped.
+    pub fn apply(
+        &self,
+        system: &mut MicromagneticSystem,
+        cells: Range<usize>,
+    ) -> Result<(), Box<dyn Error>> {
+        if cells.end > system.size() {
+            return Err(format!(
+                "Edge roughness over {}..{} is outside the {} cells",
+                cells.start,
+                cells.end,
+                system.size()
+            )
+            .into());
+        }
+        let factors = self.cross_sections(cells.len(), system.get_cell_size())?;
+        let materials = system.get_materials();
+        for (cell, factor) in cells.zip(factors) {
+            if system.is_vacuum(cell) {
+                continue;
+            }
+            let mut material = materials[cell];
+            material.saturation_magnetization *= factor;
+            material.anisotropy_constant *= factor * factor;
+            system.set_material(cell, material);
+        }
+        Ok(())
+    }
+}
+
+// Standard normal sample by the Box-Muller transform
+pub(crate) fn gaussian(rng: &mut StdRng) -> f64 {
+    let u: f64 = 1.0 - rng.random::<f64>();
+    let v: f64 = rng.random();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the statistics and the correlation of the cross section profile
+    fn test_cross_sections() {
+        let roughness = EdgeRoughness {
+            amplitude: 0.05,
+            correlation_length: 5e-9,
+            seed: 3,
+        };
+        let factors = roughness.cross_sections(100_000, 1e-9).unwrap();
+        assert_eq!(factors, roughness.cross_sections(100_000, 1e-9).unwrap());
+        let deviations: Vec<f64> = factors.iter().map(|f| (f - 1.0) / 0.05).collect();
+        let n = deviations.len() as f64;
+        let mean = deviations.iter().sum::<f64>() / n;
+        let variance = deviations.iter().map(|d| d * d).sum::<f64>() / n;
+        assert!(mean.abs() < 0.1 && (variance - 1.0).abs() < 0.1);
+        // Neighbors five cells apart are correlated by exp(-1)
+        let lagged = deviations
+            .iter()
+            .zip(&deviations[5..])
+            .map(|(a, b)| a * b)
+            .sum::<f64>()
+            / (n - 5.0);
+        assert!((lagged - (-1.0_f64).exp()).abs() < 0.05);
+
+        let invalid = EdgeRoughness {
+            correlation_length: 0.0,
+            ..roughness
+        };
+        assert!(invalid.cross_sections(10, 1e-9).is_err());
+    }
+
+    #[test]
+    /// Test that the roughness scales Ms and K of the magnetic cells only
+    fn test_apply_roughness() {
+        let mut system = MicromagneticSystem::new(20);
+        system.set_saturation_magnetization(4, 0.0);
+        let original = system.get_materials()[0];
+        let roughness = EdgeRoughness {
+            amplitude: 0.2,
+            correlation_length: 2e-9,
+            seed: 11,
+        };
+        roughness.apply(&mut system, 0..20).unwrap();
+        let factors = roughness.cross_sections(20, 1e-9).unwrap();
+        let materials = system.get_materials();
+        assert!(system.is_vacuum(4));
+        for cell in (0..20).filter(|&cell| cell != 4) {
+            let f = factors[cell];
+            let ms = original.saturation_magnetization * f;
+            assert!((materials[cell].saturation_magnetization - ms).abs() < 1e-9 * ms);
+            let k = original.anisotropy_constant * f * f;
+            assert!((materials[cell].anisotropy_constant - k).abs() < 1e-9 * k);
+        }
+        assert!(roughness.apply(&mut system, 0..21).is_err());
+    }
+}
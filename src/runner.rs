@@ -0,0 +1,189 @@
+use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::{JoinError, JoinHandle};
+
+///# Progress
+/// Events sent by a running relaxation
+#[derive(Debug, Clone, PartialEq)]
+pub enum Progress {
+    // Emitted every `progress_interval` steps
+    Step {
+        step: usize,
+        energy: f64,
+        average_magnetization: [f64; 3],
+    },
+    // Emitted once when the relaxation has ended
+    Finished(MinimizationOutcome),
+}
+
+///# Simulation Result
+#[derive(Debug, Clone)]
+pub struct SimulationResult {
+    pub system: MicromagneticSystem,
+    pub outcome: MinimizationOutcome,
+}
+
+///# Canceller
+/// Cloneable handle that stops a running relaxation at the next step
+#[derive(Debug, Clone, Default)]
+pub struct Canceller {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Canceller {
+    ///# Cancel
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    ///# Is Cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+///# Simulation Handle
+/// One launched relaxation with its progress stream
+pub struct SimulationHandle {
+    // Position of the job in the order of launch
+    pub id: usize,
+    // Progress events, the stream ends when the relaxation is done
+    pub progress: mpsc::UnboundedReceiver<Progress>,
+    canceller: Canceller,
+    task: JoinHandle<SimulationResult>,
+}
+
+impl SimulationHandle {
+    ///# Cancel
+    /// The relaxation stops at the next step and reports `Stopped`.
+    pub fn cancel(&self) {
+        self.canceller.cancel();
+    }
+
+    ///# Canceller
+    pub fn canceller(&self) -> Canceller {
+        self.canceller.clone()
+    }
+
+    ///# Wait
+    /// Wait for the relaxation to end and return the relaxed system.
+    pub async fn wait(self) -> Result<SimulationResult, JoinError> {
+        self.task.await
+    }
+}
+
+///# Simulation Runner
+/// Launches independent relaxations concurrently on the tokio blocking
+/// pool, with at most `max_concurrent` of them running at the same time,
+/// so a GUI or a web service can orchestrate many jobs without dedicating
+/// a thread to each waiting job.
+pub struct SimulationRunner {
+    permits: Arc<Semaphore>,
+    progress_interval: usize,
+    launched: usize,
+}
+
+impl SimulationRunner {
+    ///# New Simulation Runner
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            progress_interval: 100,
+            launched: 0,
+        }
+    }
+
+    ///# Set Progress Interval
+    /// Number of relaxation steps between two progress events
+    pub fn set_progress_interval(&mut self, progress_interval: usize) {
+        self.progress_interval = progress_interval.max(1);
+    }
+
+    ///# Spawn
+    /// Launch the relaxation of `system`. Must be called within a tokio runtime.
+    pub fn spawn(&mut self, mut system: MicromagneticSystem) -> SimulationHandle {
+        let (sender, progress) = mpsc::unbounded_channel();
+        let canceller = Canceller::default();
+        let permits = Arc::clone(&self.permits);
+        let progress_interval = self.progress_interval;
+        let job_canceller = canceller.clone();
+
+        let task = tokio::spawn(async move {
+            // Wait for a free slot without holding a thread
+            let permit = permits.acquire_owned().await;
+            let result = tokio::task::spawn_blocking(move || {
+                let outcome = system.minimize_energy_until(|step, system| {
+                    if step % progress_interval == 0 {
+                        // A dropped receiver only means nobody is listening
+                        let _ = sender.send(Progress::Step {
+                            step,
+                            energy: system.compute_energies().total(),
+                            average_magnetization: system.average_magnetization(),
+                        });
+                    }
+                    !job_canceller.is_cancelled()
+                });
+                let _ = sender.send(Progress::Finished(outcome));
+                SimulationResult { system, outcome }
+            })
+            .await;
+            drop(permit);
+            match result {
+                Ok(result) => result,
+                Err(e) => std::panic::resume_unwind(e.into_panic()),
+            }
+        });
+
+        self.launched += 1;
+        SimulationHandle {
+            id: self.launched - 1,
+            progress,
+            canceller,
+            task,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    /// Test that concurrent relaxations stream progress and converge
+    async fn test_concurrent_relaxations() {
+        let mut runner = SimulationRunner::new(2);
+        runner.set_progress_interval(500);
+        let handles: Vec<SimulationHandle> = (0..3)
+            .map(|_| runner.spawn(MicromagneticSystem::new(10)))
+            .collect();
+        assert_eq!(handles[2].id, 2);
+        for mut handle in handles {
+            let mut events = Vec::new();
+            while let Some(event) = handle.progress.recv().await {
+                events.push(event);
+            }
+            assert!(matches!(events[0], Progress::Step { step: 0, .. }));
+            let result = handle.wait().await.unwrap();
+            assert!(matches!(
+                result.outcome,
+                MinimizationOutcome::Converged { .. }
+            ));
+            assert_eq!(events.last(), Some(&Progress::Finished(result.outcome)));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    /// Test that a cancelled relaxation stops early
+    async fn test_cancellation() {
+        let mut runner = SimulationRunner::new(1);
+        let handle = runner.spawn(MicromagneticSystem::new(10));
+        handle.canceller().cancel();
+        let result = handle.wait().await.unwrap();
+        match result.outcome {
+            MinimizationOutcome::Stopped { .. } => {}
+            outcome => panic!("Expected a stopped relaxation, got {:?}", outcome),
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+// Bits of the Sobol coordinates, enough for 2^32 points
+const SOBOL_BITS: usize = 32;
+
+///# Sampler
+/// Points in the unit cube for the initial states of an ensemble. Two
+/// coordinates (u, v) of a point give the direction of one cell with
+/// mz = 2u - 1 and the azimuth 2 pi v, which maps a uniform density in
+/// the square to a uniform density on the sphere, so an evenly spread
+/// set of points gives evenly spread states.
+///
+/// ```toml
+/// [ensemble_sampler]
+/// type = "sobol"
+/// seed = 3
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Sampler {
+    // Independent uniform points
+    Random {
+        #[serde(default)]
+        seed: u64,
+    },
+    // Sobol sequence with random initial direction numbers and a random
+    // digital shift, every 2^m points of two coordinates of a cell fill
+    // the dyadic boxes of the square evenly
+    Sobol {
+        #[serde(default)]
+        seed: u64,
+    },
+    // Latin hypercube: every coordinate has exactly one point in each of
+    // as many equal strata as there are points
+    Stratified {
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+impl Sampler {
+    ///# Points
+    /// `count` points with `dimensions` coordinates in [0, 1) each, the
+    /// same for the same seed.
+    pub fn points(&self, count: usize, dimensions: usize) -> Vec<Vec<f64>> {
+        match *self {
+            Sampler::Random { seed } => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                (0..count)
+                    .map(|_| (0..dimensions).map(|_| rng.random()).collect())
+                    .collect()
+            }
+            Sampler::Sobol { seed } => sobol_points(count, dimensions, seed),
+            Sampler::Stratified { seed } => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut points = vec![vec![0.0; dimensions]; count];
+                for dimension in 0..dimensions {
+                    // Fisher-Yates shuffle of the strata over the points
+                    let mut strata: Vec<usize> = (0..count).collect();
+                    for k in (1..count).rev() {
+                        strata.swap(k, rng.random_range(0..=k));
+                    }
+                    for (point, stratum) in points.iter_mut().zip(strata) {
+                        point[dimension] = (stratum as f64 + rng.random::<f64>()) / count as f64;
+                    }
+                }
+                points
+            }
+        }
+    }
+
+    ///# Initial States
+    /// Unit directions of `cells` cells for each of `replicas` replicas.
+    pub fn initial_states(&self, replicas: usize, cells: usize) -> Vec<Vec<[f64; 3]>> {
+        self.points(replicas, 2 * cells)
+            .into_iter()
+            .map(|point| {
+                point
+                    .chunks(2)
+                    .map(|uv| sphere_direction(uv[0], uv[1]))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+///# Sphere Direction
+/// Unit vector with mz = 2u - 1 and the azimuth 2 pi v.
+pub fn sphere_direction(u: f64, v: f64) -> [f64; 3] {
+    let z = (2.0 * u - 1.0).clamp(-1.0, 1.0);
+    let r = (1.0 - z * z).sqrt();
+    let phi = 2.0 * PI * v;
+    [r * phi.cos(), r * phi.sin(), z]
+}
+
+// Points of the first dimensions of the Sobol sequence in Gray code order
+fn sobol_points(count: usize, dimensions: usize, seed: u64) -> Vec<Vec<f64>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let polynomials = primitive_polynomials(dimensions.saturating_sub(1));
+    let directions: Vec<([u32; SOBOL_BITS], u32)> = (0..dimensions)
+        .map(|dimension| {
+            let mut v = [0u32; SOBOL_BITS];
+            if dimension == 0 {
+                // The van der Corput sequence
+                for (k, v) in v.iter_mut().enumerate() {
+                    *v = 1 << (SOBOL_BITS - 1 - k);
+                }
+            } else {
+                let (degree, coefficients) = polynomials[dimension - 1];
+                // Odd initial direction numbers m_k < 2^k
+                for k in 1..=degree.min(SOBOL_BITS) {
+                    let m = 2 * rng.random_range(0..1u32 << (k - 1)) + 1;
+                    v[k - 1] = m << (SOBOL_BITS - k);
+                }
+                for k in degree..SOBOL_BITS {
+                    let mut next = v[k - degree] ^ (v[k - degree] >> degree);
+                    for i in 1..degree {
+                        if (coefficients >> (degree - 1 - i)) & 1 == 1 {
+                            next ^= v[k - i];
+                        }
+                    }
+                    v[k] = next;
+                }
+            }
+            (v, rng.random())
+        })
+        .collect();
+
+    let scale = 1.0 / (1u64 << SOBOL_BITS) as f64;
+    (0..count)
+        .map(|index| {
+            let gray = index ^ (index >> 1);
+            directions
+                .iter()
+                .map(|(v, shift)| {
+                    let x = (0..SOBOL_BITS)
+                        .filter(|&k| (gray >> k) & 1 == 1)
+                        .fold(*shift, |x, k| x ^ v[k]);
+                    x as f64 * scale
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// The first primitive polynomials over GF(2) by degree and then by their
+// coefficients, as the degree s and the s - 1 coefficients between the
+// leading and the constant term
+fn primitive_polynomials(count: usize) -> Vec<(usize, u64)> {
+    let mut polynomials = Vec::with_capacity(count);
+    let mut degree = 1;
+    while polynomials.len() < count {
+        let order = (1u64 << degree) - 1;
+        let factors = prime_factors(order);
+        for coefficients in 0..1u64 << (degree - 1) {
+            if polynomials.len() == count {
+                break;
+            }
+            let polynomial = (1 << degree) | (coefficients << 1) | 1;
+            let power = |exponent: u64| power_of_x(exponent, polynomial, degree);
+            // x generates the multiplicative group of GF(2^s)
+            if power(order) == 1 && factors.iter().all(|&q| power(order / q) != 1) {
+                polynomials.push((degree, coefficients));
+            }
+        }
+        degree += 1;
+    }
+    polynomials
+}
+
+// x^exponent modulo the polynomial of the given degree
+fn power_of_x(mut exponent: u64, polynomial: u64, degree: usize) -> u64 {
+    let multiply = |mut a: u64, mut b: u64| {
+        let mut product = 0;
+        while b != 0 {
+            if b & 1 == 1 {
+                product ^= a;
+            }
+            b >>= 1;
+            a <<= 1;
+            if (a >> degree) & 1 == 1 {
+                a ^= polynomial;
+            }
+        }
+        product
+    };
+    // x reduced, which is 1 modulo x + 1
+    let (mut result, mut base) = (1, multiply(1, 2));
+    while exponent != 0 {
+        if exponent & 1 == 1 {
+            result = multiply(result, base);
+        }
+        base = multiply(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn prime_factors(mut n: u64) -> Vec<u64> {
+    let mut factors = Vec::new();
+    let mut q = 2;
+    while q * q <= n {
+        if n.is_multiple_of(q) {
+            factors.push(q);
+            while n.is_multiple_of(q) {
+                n /= q;
+            }
+        }
+        q += 1;
+    }
+    if n > 1 {
+        factors.push(n);
+    }
+    factors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the net property of the Sobol points, the strata of the Latin
+    /// hypercube and the directions of the initial states
+    fn test_samplers() {
+        // x + 1, x^2 + x + 1, x^3 + x + 1, x^3 + x^2 + 1, x^4 + x + 1, ...
+        let polynomials = primitive_polynomials(10);
+        assert_eq!(
+            polynomials[..6],
+            [(1, 0), (2, 1), (3, 1), (3, 2), (4, 1), (4, 4)]
+        );
+        assert_eq!(polynomials.iter().filter(|(s, _)| *s == 5).count(), 4);
+        assert_eq!(
+            primitive_polynomials(20)
+                .iter()
+                .filter(|(s, _)| *s == 5)
+                .count(),
+            6
+        );
+
+        // The first two coordinates of 16 points fill the 4 x 4 boxes and
+        // the 16 x 1 and 1 x 16 strips once each
+        let sobol = Sampler::Sobol { seed: 5 };
+        let points = sobol.points(16, 6);
+        for (rows, columns) in [(4, 4), (16, 1), (1, 16)] {
+            let mut boxes = vec![0; rows * columns];
+            for point in &points {
+                let row = (point[0] * rows as f64) as usize;
+                let column = (point[1] * columns as f64) as usize;
+                boxes[row * columns + column] += 1;
+            }
+            assert!(boxes.iter().all(|&count| count == 1));
+        }
+        assert_eq!(points, sobol.points(16, 6));
+        assert_ne!(points, Sampler::Sobol { seed: 6 }.points(16, 6));
+
+        let stratified = Sampler::Stratified { seed: 1 }.points(10, 7);
+        for dimension in 0..7 {
+            let mut strata: Vec<usize> = stratified
+                .iter()
+                .map(|point| (point[dimension] * 10.0) as usize)
+                .collect();
+            strata.sort();
+            assert_eq!(strata, (0..10).collect::<Vec<_>>());
+        }
+
+        let samplers = [
+            Sampler::Random { seed: 2 },
+            sobol,
+            Sampler::Stratified { seed: 2 },
+        ];
+        for sampler in samplers {
+            let states = sampler.initial_states(32, 3);
+            assert_eq!(states.len(), 32);
+            for m in states.iter().flatten() {
+                assert!((m[0] * m[0] + m[1] * m[1] + m[2] * m[2] - 1.0).abs() < 1e-12);
+            }
+            // The states spread over both hemispheres of the first cell
+            let mean: f64 = states.iter().map(|state| state[0][2]).sum::<f64>() / 32.0;
+            assert!(mean.abs() < 0.3);
+        }
+    }
+}
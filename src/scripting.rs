@@ -0,0 +1,459 @@
+use crate::dynamics::DynamicsRun;
+use crate::grid::Grid;
+use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, FuncArgs, Map, Scope, AST, INT};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+
+// Operations a single call of a script function may take, so a runaway
+// loop fails the run instead of hanging it
+const MAX_OPERATIONS: u64 = 1_000_000;
+// Depth of nested function calls, which bounds the recursion of a script
+const MAX_CALL_LEVELS: usize = 64;
+
+// Columns written before the measured values, which a script cannot measure
+const RESERVED_KEYS: [&str; 2] = ["step", "time"];
+
+///# Script
+/// A rhai script with custom terms and callbacks, compiled once and
+/// called at runtime, so a run can be extended without recompiling the
+/// crate. Every function is optional, the script defines those it needs:
+///
+/// ```rhai
+/// // Field in T on top of the applied field at the cell center (x, y, z)
+/// // in m and the simulated time t in s, zero during relaxations
+/// fn field(x, y, z, t) {
+///     [0.0, 0.01 * sin(2.0 * PI() * 1.0e9 * t), 0.0]
+/// }
+///
+/// // Ends the run when it returns true
+/// fn stop(step, t, state) {
+///     state.m[2] > 0.9
+/// }
+///
+/// // Values recorded after every step, one column per key, an empty map
+/// // records nothing. The keys step and time are taken by the first columns.
+/// fn measure(step, t, state) {
+///     if step % 100 != 0 { return #{}; }
+///     #{ mz: state.m[2], energy: state.energy }
+/// }
+/// ```
+///
+/// `state` holds the total energy in J, the maximum torque in A/m and the
+/// average magnetization m of the current state.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+///# Script Measurement
+/// Values of the `measure` function after one step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptMeasurement {
+    pub step: usize,
+    // Simulated time in s, zero during relaxations
+    pub time: f64,
+    pub values: BTreeMap<String, f64>,
+}
+
+///# Script Report
+/// Steps of a scripted run, whether the `stop` function ended it, and the
+/// recorded measurements.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScriptReport {
+    pub steps: usize,
+    pub time: f64,
+    pub stopped: bool,
+    pub measurements: Vec<ScriptMeasurement>,
+}
+
+impl Script {
+    ///# Compile Script
+    pub fn compile(source: &str) -> Result<Self, Box<dyn Error>> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        let ast = engine.compile(source)?;
+        Ok(Self { engine, ast })
+    }
+
+    ///# Load Script
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::compile(&std::fs::read_to_string(path)?)
+    }
+
+    ///# Defines Function
+    /// Whether the script defines `field`, `stop`, `measure` or another function.
+    pub fn defines(&self, function: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == function)
+    }
+
+    // Call a function of the script without running its top level statements
+    fn call<T: std::any::Any>(
+        &self,
+        function: &str,
+        args: impl FuncArgs,
+    ) -> Result<T, Box<dyn Error>> {
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut Scope::new(),
+            &self.ast,
+            function,
+            args,
+        )?;
+        let type_name = result.type_name();
+        result.try_cast::<T>().ok_or_else(|| {
+            format!(
+                "The {} function of the script returned a {}",
+                function, type_name
+            )
+            .into()
+        })
+    }
+
+    ///# Field Term
+    /// The field of the `field` function at every cell of the grid with the
+    /// edge length `cell_size` in T, zero when the script defines none.
+    pub fn field_term(
+        &self,
+        grid: &Grid,
+        cell_size: f64,
+        time: f64,
+    ) -> Result<Vec<[f64; 3]>, Box<dyn Error>> {
+        if !self.defines("field") {
+            return Ok(vec![[0.0; 3]; grid.size()]);
+        }
+        (0..grid.size())
+            .map(|cell| {
+                let [x, y, z] = grid.position(cell, cell_size);
+                let field: Array = self.call("field", (x, y, z, time))?;
+                let components = field
+                    .iter()
+                    .map(number)
+                    .collect::<Option<Vec<f64>>>()
+                    .filter(|components| components.len() == 3)
+                    .ok_or("The field function must return three numbers")?;
+                Ok([components[0], components[1], components[2]])
+            })
+            .collect()
+    }
+
+    ///# Should Stop
+    /// The result of the `stop` function, false when the script defines none.
+    pub fn should_stop(
+        &self,
+        step: usize,
+        time: f64,
+        system: &MicromagneticSystem,
+    ) -> Result<bool, Box<dyn Error>> {
+        if !self.defines("stop") {
+            return Ok(false);
+        }
+        self.call("stop", (step as INT, time, state(system)))
+    }
+
+    ///# Measure
+    /// The values of the `measure` function, none when the script defines
+    /// no such function or it returned an empty map.
+    pub fn measure(
+        &self,
+        step: usize,
+        time: f64,
+        system: &MicromagneticSystem,
+    ) -> Result<Option<ScriptMeasurement>, Box<dyn Error>> {
+        if !self.defines("measure") {
+            return Ok(None);
+        }
+        self.measure_state(step, time, state(system))
+    }
+
+    fn measure_state(
+        &self,
+        step: usize,
+        time: f64,
+        state: Map,
+    ) -> Result<Option<ScriptMeasurement>, Box<dyn Error>> {
+        let map: Map = self.call("measure", (step as INT, time, state))?;
+        let values = map
+            .iter()
+            .map(|(key, value)| {
+                if RESERVED_KEYS.contains(&key.as_str()) {
+                    return Err(format!(
+                        "The measured value {} has the name of the {} column",
+                        key, key
+                    ));
+                }
+                number(value)
+                    .map(|value| (key.to_string(), value))
+                    .ok_or_else(|| format!("The measured value {} is not a number", key))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+        Ok((!values.is_empty()).then_some(ScriptMeasurement { step, time, values }))
+    }
+
+    // Run the stop and measure functions on a state, true to continue.
+    // The state passed to the script is only built when one of them is defined.
+    fn observe(
+        &self,
+        step: usize,
+        time: f64,
+        system: &MicromagneticSystem,
+        report: &mut ScriptReport,
+    ) -> Result<bool, Box<dyn Error>> {
+        report.steps = step;
+        report.time = time;
+        let (measures, stops) = (self.defines("measure"), self.defines("stop"));
+        if !measures && !stops {
+            return Ok(true);
+        }
+        let state = state(system);
+        if measures {
+            if let Some(measurement) = self.measure_state(step, time, state.clone())? {
+                report.measurements.push(measurement);
+            }
+        }
+        if stops {
+            report.stopped = self.call("stop", (step as INT, time, state))?;
+        }
+        Ok(!report.stopped)
+    }
+
+    ///# Relax
+    /// Minimize the energy with the field term of the script at t = 0 as the
+    /// local fields of the system, measuring and asking the `stop` function
+    /// after every step. The first error of the script ends the run.
+    pub fn relax(
+        &self,
+        system: &mut MicromagneticSystem,
+    ) -> Result<(MinimizationOutcome, ScriptReport), Box<dyn Error>> {
+        if self.defines("field") {
+            let fields = self.field_term(&system.get_grid(), system.get_cell_size(), 0.0)?;
+            system.set_local_fields(fields)?;
+        }
+        let mut report = ScriptReport::default();
+        let mut error = None;
+        let outcome = system.minimize_energy_until(|step, system| {
+            self.observe(step, 0.0, system, &mut report)
+                .unwrap_or_else(|e| {
+                    error = Some(e);
+                    false
+                })
+        });
+        match error {
+            Some(e) => Err(e),
+            None => Ok((outcome, report)),
+        }
+    }
+
+    ///# Run Dynamics
+    /// Integrate the LLG equation for the given duration in s with the
+    /// field term of the script evaluated at the start of every step and
+    /// held over its stages, like the thermal field. The field term takes
+    /// the place of the local fields of the antenna, the spin torques and
+    /// the thermal field, which the run must not use together with it, and
+    /// the steps are fixed. Without a field term the run keeps all its
+    /// options.
+    pub fn run_dynamics(
+        &self,
+        run: &DynamicsRun,
+        system: &mut MicromagneticSystem,
+        duration: f64,
+    ) -> Result<ScriptReport, Box<dyn Error>> {
+        let mut report = ScriptReport::default();
+        let mut error = None;
+        let mut observe = |step: usize, time: f64, system: &MicromagneticSystem| {
+            if error.is_some() {
+                return false;
+            }
+            self.observe(step, time, system, &mut report)
+                .unwrap_or_else(|e| {
+                    error = Some(e);
+                    false
+                })
+        };
+        if !self.defines("field") {
+            // The condition sees every observed state, so it runs both callbacks
+            run.run_until(
+                system,
+                duration,
+                &mut |step, time, system: &MicromagneticSystem| !observe(step, time, system),
+                |_, _| {},
+            );
+        } else {
+            if run.antenna.is_some()
+                || run.spin_accumulation.is_some()
+                || run.spin_transfer.is_some()
+                || run.thermal_field.is_some()
+            {
+                return Err("The field term of the script replaces the local fields of \
+                            the antenna, the spin torques and the thermal field"
+                    .into());
+            }
+            if run.adaptive_time_step.is_some() {
+                return Err("The field term of the script needs a fixed time step".into());
+            }
+            let steps = (duration / run.time_step).round() as usize;
+            let (grid, cell_size) = (system.get_grid(), system.get_cell_size());
+            let mut time = 0.0;
+            system.set_applied_field(run.applied_field.at(time));
+            if observe(0, time, system) {
+                for step in 1..=steps {
+                    let fields = self.field_term(&grid, cell_size, time);
+                    if let Err(e) = fields.and_then(|fields| system.set_local_fields(fields)) {
+                        error = Some(e);
+                        break;
+                    }
+                    run.step(system, time);
+                    if let Some(frame) = &run.moving_frame {
+                        frame.follow(system);
+                    }
+                    time = step as f64 * run.time_step;
+                    if !observe(step, time, system) {
+                        break;
+                    }
+                }
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(report),
+        }
+    }
+}
+
+// Energy, maximum torque and average magnetization passed to the script
+fn state(system: &MicromagneticSystem) -> Map {
+    let mut state = Map::new();
+    state.insert(
+        "energy".into(),
+        Dynamic::from_float(system.compute_energies().total()),
+    );
+    state.insert(
+        "max_torque".into(),
+        Dynamic::from_float(system.compute_max_torque()),
+    );
+    let m: Array = system
+        .average_magnetization()
+        .iter()
+        .map(|&c| Dynamic::from_float(c))
+        .collect();
+    state.insert("m".into(), Dynamic::from_array(m));
+    state
+}
+
+// A float or an integer of the script
+fn number(value: &Dynamic) -> Option<f64> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|value| value as f64))
+}
+
+///# Write Script Measurements
+/// Comma separated step, time and one column per measured key, in the
+/// order of the keys. A value a measurement did not record stays empty.
+pub fn write_script_measurements<W: Write>(
+    mut writer: W,
+    measurements: &[ScriptMeasurement],
+) -> io::Result<()> {
+    let keys: BTreeSet<&String> = measurements
+        .iter()
+        .flat_map(|measurement| measurement.values.keys())
+        .collect();
+    let mut header: Vec<String> = RESERVED_KEYS.iter().map(|key| key.to_string()).collect();
+    header.extend(keys.iter().map(|key| key.to_string()));
+    writeln!(writer, "{}", header.join(","))?;
+    for measurement in measurements {
+        let mut row = vec![measurement.step.to_string(), measurement.time.to_string()];
+        row.extend(keys.iter().map(|key| {
+            measurement
+                .values
+                .get(*key)
+                .map_or_else(String::new, |value| value.to_string())
+        }));
+        writeln!(writer, "{}", row.join(","))?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::TimeDependentField;
+    use ndarray::array;
+
+    #[test]
+    /// Test the field term, the stop condition and the measurements of a script
+    fn test_script_callbacks() {
+        let script = Script::compile(
+            "fn field(x, y, z, t) { [0.0, 0.0, 1.0e9 * x + t] }
+             fn stop(step, t, state) { step >= 3 }
+             fn measure(step, t, state) {
+                 if step % 2 == 1 { return #{}; }
+                 #{ mz: state.m[2], iteration: step }
+             }",
+        )
+        .unwrap();
+        assert!(script.defines("field") && !script.defines("torque"));
+        let fields = script.field_term(&Grid::chain(3), 2.0e-9, 0.5).unwrap();
+        assert_eq!(fields[2], [0.0, 0.0, 4.5]);
+
+        let mut system = MicromagneticSystem::new(4);
+        let (outcome, report) = script.relax(&mut system).unwrap();
+        assert_eq!(outcome, MinimizationOutcome::Stopped { iterations: 3 });
+        assert!(report.stopped);
+        let steps: Vec<usize> = report.measurements.iter().map(|m| m.step).collect();
+        assert_eq!(steps, vec![0, 2]);
+        assert_eq!(report.measurements[1].values["iteration"], 2.0);
+
+        let mut csv = Vec::new();
+        write_script_measurements(&mut csv, &report.measurements).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().next(), Some("step,time,iteration,mz"));
+        assert_eq!(csv.lines().count(), 3);
+    }
+
+    #[test]
+    /// Test that a script can neither measure the step and time columns
+    /// nor loop forever
+    fn test_script_limits() {
+        let system = MicromagneticSystem::new(2);
+        let reserved = Script::compile("fn measure(step, t, state) { #{ time: t } }").unwrap();
+        assert!(reserved.measure(0, 0.0, &system).is_err());
+
+        let endless = Script::compile("fn stop(step, t, state) { loop {} }").unwrap();
+        assert!(endless.should_stop(0, 0.0, &system).is_err());
+        let recursive =
+            Script::compile("fn deep(n) { deep(n + 1) } fn stop(step, t, state) { deep(0) }")
+                .unwrap();
+        assert!(recursive.should_stop(0, 0.0, &system).is_err());
+    }
+
+    #[test]
+    /// Test that a scripted field drives the dynamics like the same applied field
+    fn test_scripted_dynamics() {
+        let script = Script::compile("fn field(x, y, z, t) { [0.0, 0.0, 0.5] }").unwrap();
+        let run = DynamicsRun::new(TimeDependentField::Constant([0.0; 3]));
+        let mut scripted = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]]);
+        let report = script.run_dynamics(&run, &mut scripted, 1e-12).unwrap();
+        assert_eq!(report.steps, 100);
+        assert!(!report.stopped);
+
+        let applied = DynamicsRun::new(TimeDependentField::Constant([0.0, 0.0, 0.5]));
+        let mut expected = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]]);
+        applied.run(&mut expected, 1e-12, |_, _| {});
+        let (m, expected) = (
+            scripted.average_magnetization(),
+            expected.average_magnetization(),
+        );
+        for k in 0..3 {
+            assert!((m[k] - expected[k]).abs() < 1e-9, "{:?} {:?}", m, expected);
+        }
+
+        let failing = Script::compile("fn stop(step, t, state) { 1 }").unwrap();
+        assert!(failing.run_dynamics(&run, &mut scripted, 1e-12).is_err());
+    }
+}
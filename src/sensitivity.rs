@@ -0,0 +1,336 @@
+use crate::astroid::AstroidSweep;
+use crate::domains::wall_width;
+use crate::magnetic_moments::{EnergyGradient, MicromagneticSystem};
+use crate::EASY_AXIS;
+use std::str::FromStr;
+
+///# Sensitivity Parameter
+/// Input scaled by the perturbation. Material constants are scaled in
+/// every magnetic cell, so spatial profiles keep their shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensitivityParameter {
+    ExchangeConstant,
+    AnisotropyConstant,
+    SaturationMagnetization,
+    AppliedField,
+}
+
+impl SensitivityParameter {
+    pub const ALL: [SensitivityParameter; 4] = [
+        SensitivityParameter::ExchangeConstant,
+        SensitivityParameter::AnisotropyConstant,
+        SensitivityParameter::SaturationMagnetization,
+        SensitivityParameter::AppliedField,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SensitivityParameter::ExchangeConstant => "A",
+            SensitivityParameter::AnisotropyConstant => "K",
+            SensitivityParameter::SaturationMagnetization => "Ms",
+            SensitivityParameter::AppliedField => "B",
+        }
+    }
+
+    fn scale(self, system: &mut MicromagneticSystem, factor: f64) {
+        if self == SensitivityParameter::AppliedField {
+            system.set_applied_field(system.get_applied_field().map(|b| b * factor));
+            return;
+        }
+        for (cell, mut material) in system.get_materials().into_iter().enumerate() {
+            if material.saturation_magnetization > 0.0 {
+                match self {
+                    SensitivityParameter::ExchangeConstant => material.exchange_constant *= factor,
+                    SensitivityParameter::AnisotropyConstant => {
+                        material.anisotropy_constant *= factor
+                    }
+                    SensitivityParameter::SaturationMagnetization => {
+                        material.saturation_magnetization *= factor
+                    }
+                    SensitivityParameter::AppliedField => unreachable!(),
+                }
+                system.set_material(cell, material);
+            }
+        }
+    }
+
+    // d E / d ln(p) of the relaxed state for all cells scaled together,
+    // from the analytic energy gradient. The saturation magnetization also
+    // enters the dipolar energy quadratically and is left to the finite
+    // differences.
+    fn energy_derivative(
+        self,
+        relaxed: &MicromagneticSystem,
+        gradient: &EnergyGradient,
+    ) -> Option<f64> {
+        let materials = relaxed.get_materials();
+        match self {
+            SensitivityParameter::ExchangeConstant => Some(
+                materials
+                    .iter()
+                    .zip(&gradient.exchange_constant)
+                    .map(|(m, d)| m.exchange_constant * d)
+                    .sum(),
+            ),
+            SensitivityParameter::AnisotropyConstant => Some(
+                materials
+                    .iter()
+                    .zip(&gradient.anisotropy_constant)
+                    .map(|(m, d)| m.anisotropy_constant * d)
+                    .sum(),
+            ),
+            SensitivityParameter::AppliedField => Some(
+                (0..3)
+                    .map(|k| relaxed.get_applied_field()[k] * gradient.applied_field[k])
+                    .sum(),
+            ),
+            SensitivityParameter::SaturationMagnetization => None,
+        }
+    }
+}
+
+impl FromStr for SensitivityParameter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SensitivityParameter::ALL
+            .into_iter()
+            .find(|p| p.name() == s)
+            .ok_or_else(|| format!("unknown parameter {}, use A, K, Ms or B", s))
+    }
+}
+
+///# Observable
+/// Result of a relaxation whose sensitivity is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Observable {
+    // Total energy of the relaxed state in J
+    FinalEnergy,
+    // Width of the first domain wall of the relaxed state in m
+    WallWidth,
+    // Switching field of the saturated system in T, see `SensitivityAnalysis`
+    Coercivity,
+}
+
+impl Observable {
+    pub const ALL: [Observable; 3] = [
+        Observable::FinalEnergy,
+        Observable::WallWidth,
+        Observable::Coercivity,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Observable::FinalEnergy => "energy",
+            Observable::WallWidth => "wall_width",
+            Observable::Coercivity => "coercivity",
+        }
+    }
+}
+
+impl FromStr for Observable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Observable::ALL
+            .into_iter()
+            .find(|o| o.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown observable {}, use energy, wall_width or coercivity",
+                    s
+                )
+            })
+    }
+}
+
+///# Sensitivity
+/// Normalized sensitivity d ln(O) / d ln(p) from the observable at the
+/// lowered and raised parameter. A value of 1 means that a 1 % error of
+/// the parameter gives a 1 % error of the observable. `None` when the
+/// observable is undefined in one of the runs, e.g. without a wall, or
+/// vanishes at the baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sensitivity {
+    pub parameter: SensitivityParameter,
+    pub observable: Observable,
+    pub lower: Option<f64>,
+    pub upper: Option<f64>,
+    pub sensitivity: Option<f64>,
+}
+
+///# Sensitivity Report
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensitivityReport {
+    // Unperturbed value of every observable, in the order of the analysis
+    pub baseline: Vec<Option<f64>>,
+    pub sensitivities: Vec<Sensitivity>,
+}
+
+///# Sensitivity Analysis
+/// Scales every parameter by 1 -/+ `relative_step`, relaxes the template
+/// from its current state again and compares the observables by central
+/// differences. The final energy responds to A, K and B through the
+/// analytic energy gradient of the relaxed state instead, which needs no
+/// re-relaxation to be accurate. The coercivity is the switching field of
+/// `coercivity_sweep` at `coercivity_angle` degrees from the reversed easy
+/// axis; exactly antiparallel the saturated state does not switch at any
+/// field, and the sweep replaces the applied field, so B has no effect on it.
+#[derive(Debug, Clone)]
+pub struct SensitivityAnalysis {
+    pub parameters: Vec<SensitivityParameter>,
+    pub observables: Vec<Observable>,
+    pub relative_step: f64,
+    pub coercivity_sweep: AstroidSweep,
+    pub coercivity_angle: f64,
+    pub easy_axis: [f64; 3],
+}
+
+impl Default for SensitivityAnalysis {
+    fn default() -> Self {
+        Self {
+            parameters: SensitivityParameter::ALL.to_vec(),
+            observables: Observable::ALL.to_vec(),
+            relative_step: 0.01,
+            // A perturbation of 1 % moves the switching field by a few
+            // 1e-4 T, so the bisection has to be finer than for an astroid
+            coercivity_sweep: AstroidSweep {
+                resolution: 1e-6,
+                ..AstroidSweep::default()
+            },
+            coercivity_angle: 5.0,
+            easy_axis: EASY_AXIS,
+        }
+    }
+}
+
+impl SensitivityAnalysis {
+    ///# Run Analysis
+    pub fn run(&self, system: &MicromagneticSystem) -> SensitivityReport {
+        let (baseline, relaxed) = self.observe(system);
+        let gradient = relaxed.as_ref().map(|r| r.compute_energy_gradient());
+        let mut sensitivities = Vec::new();
+        for &parameter in &self.parameters {
+            let perturbed = |factor: f64| {
+                let mut system = system.clone();
+                parameter.scale(&mut system, factor);
+                self.observe(&system).0
+            };
+            let lower = perturbed(1.0 - self.relative_step);
+            let upper = perturbed(1.0 + self.relative_step);
+            for (k, &observable) in self.observables.iter().enumerate() {
+                let analytic = match (observable, &relaxed, &gradient) {
+                    (Observable::FinalEnergy, Some(relaxed), Some(gradient)) => {
+                        parameter.energy_derivative(relaxed, gradient)
+                    }
+                    _ => None,
+                };
+                let sensitivity = match (lower[k], baseline[k], upper[k], analytic) {
+                    (_, Some(center), _, Some(derivative)) if center != 0.0 => {
+                        Some(derivative / center)
+                    }
+                    (Some(lower), Some(center), Some(upper), _) if center != 0.0 => {
+                        Some((upper - lower) / (2.0 * self.relative_step * center))
+                    }
+                    _ => None,
+                };
+                sensitivities.push(Sensitivity {
+                    parameter,
+                    observable,
+                    lower: lower[k],
+                    upper: upper[k],
+                    sensitivity,
+                });
+            }
+        }
+        SensitivityReport {
+            baseline,
+            sensitivities,
+        }
+    }
+
+    // The observables of the system and its relaxed state, which is
+    // computed once when any observable other than the coercivity needs it
+    fn observe(
+        &self,
+        system: &MicromagneticSystem,
+    ) -> (Vec<Option<f64>>, Option<MicromagneticSystem>) {
+        let relaxed = self
+            .observables
+            .iter()
+            .any(|&o| o != Observable::Coercivity)
+            .then(|| {
+                let mut relaxed = system.clone();
+                relaxed.minimize_energy();
+                relaxed
+            });
+        let values = self
+            .observables
+            .iter()
+            .map(|observable| match (observable, &relaxed) {
+                (Observable::FinalEnergy, Some(relaxed)) => {
+                    Some(relaxed.compute_energies().total())
+                }
+                (Observable::WallWidth, Some(relaxed)) => wall_width(
+                    &relaxed.get_magnetizations(),
+                    &self.easy_axis,
+                    relaxed.get_cell_size(),
+                ),
+                (Observable::Coercivity, _) => self
+                    .coercivity_sweep
+                    .switching_field(system, self.coercivity_angle),
+                _ => None,
+            })
+            .collect();
+        (values, relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magnetic_moments::Minimizer;
+    use crate::{SATURATION_MAGNETIZATION, UNIAXIAL_ANISOTROPY_CONSTANT};
+    use ndarray::array;
+
+    #[test]
+    /// Test the sensitivities of a macrospin against Stoner-Wohlfarth
+    fn test_macrospin_sensitivity() {
+        let mut system = MicromagneticSystem::new(1);
+        system.set_magnetization(0, array![1.0, 0.0, 0.0]);
+        system.set_applied_field([0.0; 3]);
+        system.set_minimizer(Minimizer::SphericalConjugateGradient);
+        let analysis = SensitivityAnalysis {
+            parameters: vec![
+                SensitivityParameter::AnisotropyConstant,
+                SensitivityParameter::SaturationMagnetization,
+            ],
+            ..SensitivityAnalysis::default()
+        };
+        let report = analysis.run(&system);
+        let value = |parameter, observable| {
+            report
+                .sensitivities
+                .iter()
+                .find(|s| s.parameter == parameter && s.observable == observable)
+                .unwrap()
+                .sensitivity
+        };
+
+        // E = -K V in zero field, B_sw = 2 K / Ms times a factor of the angle
+        let (k, ms) = (
+            SensitivityParameter::AnisotropyConstant,
+            SensitivityParameter::SaturationMagnetization,
+        );
+        assert!(report.baseline[0].unwrap() < 0.0);
+        assert!((value(k, Observable::FinalEnergy).unwrap() - 1.0).abs() < 1e-6);
+        assert!(value(ms, Observable::FinalEnergy).unwrap().abs() < 1e-6);
+        assert_eq!(report.baseline[1], None);
+        assert_eq!(value(k, Observable::WallWidth), None);
+        let coercivity = report.baseline[2].unwrap();
+        let anisotropy_field = 2.0 * UNIAXIAL_ANISOTROPY_CONSTANT / SATURATION_MAGNETIZATION;
+        assert!(coercivity < anisotropy_field);
+        assert!((value(k, Observable::Coercivity).unwrap() - 1.0).abs() < 0.1);
+        assert!((value(ms, Observable::Coercivity).unwrap() + 1.0).abs() < 0.1);
+    }
+}
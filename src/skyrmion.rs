@@ -0,0 +1,312 @@
+use crate::fitting::nelder_mead;
+use crate::magnetic_moments::MicromagneticSystem;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Profile evaluations of one fit
+const MAX_FIT_EVALUATIONS: usize = 2000;
+
+///# Skyrmion Profile
+/// Fitted isolated skyrmion in the bottom xy layer of a grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkyrmionProfile {
+    // Laboratory position (x, y) of the center in m, x includes the offset
+    // of a moving frame
+    pub center: [f64; 2],
+    // Radius R in m, where mz crosses zero
+    pub radius: f64,
+    // Wall width w in m
+    pub wall_width: f64,
+    // Sign of mz of the background, the core points the other way
+    pub polarity: f64,
+    // Root mean square deviation of the polar angle from the fit in rad
+    pub residual: f64,
+}
+
+///# Polar Angle Profile
+/// Polar angle theta(r) = 2 atan(sinh(R / w) / sinh(r / w)) of the
+/// magnetization from the core, pi at the center, pi / 2 at the radius R
+/// and 0 in the background, for r, R and w in the same unit.
+pub fn polar_angle(r: f64, radius: f64, wall_width: f64) -> f64 {
+    let (a, b) = (radius / wall_width, r / wall_width);
+    if b == 0.0 {
+        return PI;
+    }
+    // sinh(a) / sinh(b) without the overflow of large arguments
+    let ratio = (a - b).exp() * (-(-2.0 * a).exp_m1()) / (-(-2.0 * b).exp_m1());
+    2.0 * ratio.atan()
+}
+
+///# Fit Skyrmion
+/// Center, radius and wall width of the skyrmion in the bottom layer of the
+/// grid. The background points along the average mz of the border cells,
+/// and the center, radius and wall width are fitted to the polar angle of
+/// every cell closer to the centroid of the reversed core than the nearest
+/// border, which leaves out the canting at the edges. `None` when no cell
+/// points against the background.
+pub fn fit_skyrmion(system: &MicromagneticSystem) -> Option<SkyrmionProfile> {
+    let grid = system.get_grid();
+    let magnetizations = system.get_magnetizations();
+    let cells: Vec<usize> = (0..grid.nx * grid.ny)
+        .filter(|&cell| !system.is_vacuum(cell))
+        .collect();
+    let border: f64 = cells
+        .iter()
+        .filter(|&&cell| {
+            let [x, y, _] = grid.coordinates(cell);
+            x == 0 || y == 0 || x + 1 == grid.nx || y + 1 == grid.ny
+        })
+        .map(|&cell| magnetizations[cell][2])
+        .sum();
+    let polarity = if border < 0.0 { -1.0 } else { 1.0 };
+
+    let (mut weight, mut cx, mut cy, mut core) = (0.0, 0.0, 0.0, 0);
+    for &cell in &cells {
+        let reversal = -polarity * magnetizations[cell][2];
+        if reversal > 0.0 {
+            let [x, y, _] = grid.coordinates(cell);
+            weight += reversal;
+            cx += reversal * x as f64;
+            cy += reversal * y as f64;
+            core += 1;
+        }
+    }
+    if core == 0 {
+        return None;
+    }
+    let (cx, cy) = (cx / weight, cy / weight);
+
+    // Position and polar angle from the background of the cells closer
+    // to the centroid than the border, in cells
+    let reach = cx
+        .min(cy)
+        .min(grid.nx as f64 - 1.0 - cx)
+        .min(grid.ny as f64 - 1.0 - cy);
+    let samples: Vec<(f64, f64, f64)> = cells
+        .iter()
+        .filter_map(|&cell| {
+            let [x, y, _] = grid.coordinates(cell);
+            let (x, y) = (x as f64, y as f64);
+            let theta = (polarity * magnetizations[cell][2]).clamp(-1.0, 1.0).acos();
+            ((x - cx).hypot(y - cy) <= reach).then_some((x, y, theta))
+        })
+        .collect();
+    // The center, log R and log w
+    let squared_error = |parameters: &[f64]| -> f64 {
+        let (radius, wall_width) = (parameters[2].exp(), parameters[3].exp());
+        samples
+            .iter()
+            .map(|&(x, y, theta)| {
+                let r = (x - parameters[0]).hypot(y - parameters[1]);
+                (polar_angle(r, radius, wall_width) - theta).powi(2)
+            })
+            .sum()
+    };
+    // Start from the radius of a disk with the area of the core
+    let start = [cx, cy, (core as f64 / PI).sqrt().max(0.5).ln(), 0.0];
+    let (best, _) = nelder_mead(squared_error, &start, 0.5, MAX_FIT_EVALUATIONS);
+    let residual = (squared_error(&best) / samples.len() as f64).sqrt();
+
+    let step = system.get_cell_size();
+    Some(SkyrmionProfile {
+        center: [system.frame_offset() + best[0] * step, best[1] * step],
+        radius: best[2].exp() * step,
+        wall_width: best[3].exp() * step,
+        polarity,
+        residual,
+    })
+}
+
+///# Skyrmion Tracker
+/// Fits the skyrmion once per sampling interval and keeps the trajectory
+/// of its center and its radius, for the velocity and the Hall angle
+/// under a current or field gradient drive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkyrmionTracker {
+    // Simulated time between two fits in s
+    sampling_interval: f64,
+    // Time of the next fit
+    next_sample: f64,
+    // Time in s and fitted profile of every sample with a skyrmion
+    samples: Vec<(f64, SkyrmionProfile)>,
+}
+
+impl SkyrmionTracker {
+    ///# New Skyrmion Tracker
+    /// A sampling interval of zero fits every recorded state.
+    pub fn new(sampling_interval: f64) -> Self {
+        Self {
+            sampling_interval,
+            next_sample: 0.0,
+            samples: Vec::new(),
+        }
+    }
+
+    ///# Record
+    /// Fit the skyrmion when the sampling time has been reached and report
+    /// whether it was found.
+    pub fn record(&mut self, t: f64, system: &MicromagneticSystem) -> bool {
+        // Allow for the rounding of accumulated time steps
+        if t < self.next_sample - 1e-9 * self.sampling_interval {
+            return false;
+        }
+        if self.sampling_interval > 0.0 {
+            let samples = (t / self.sampling_interval + 1e-9).floor() + 1.0;
+            self.next_sample = samples * self.sampling_interval;
+        }
+        match fit_skyrmion(system) {
+            Some(profile) => {
+                self.samples.push((t, profile));
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///# Samples
+    pub fn samples(&self) -> &[(f64, SkyrmionProfile)] {
+        &self.samples
+    }
+
+    ///# Velocity
+    /// Least squares drift velocity (vx, vy) of the center in m/s, `None`
+    /// with fewer than two samples.
+    pub fn velocity(&self) -> Option<[f64; 2]> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+        let n = self.samples.len() as f64;
+        let mean_t = self.samples.iter().map(|(t, _)| t).sum::<f64>() / n;
+        let variance: f64 = self.samples.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+        if variance == 0.0 {
+            return None;
+        }
+        Some(std::array::from_fn(|k| {
+            let mean = self.samples.iter().map(|(_, p)| p.center[k]).sum::<f64>() / n;
+            self.samples
+                .iter()
+                .map(|(t, p)| (t - mean_t) * (p.center[k] - mean))
+                .sum::<f64>()
+                / variance
+        }))
+    }
+
+    ///# Hall Angle
+    /// Angle in rad from the drive direction (x, y) to the drift velocity,
+    /// positive counterclockwise.
+    pub fn hall_angle(&self, drive: [f64; 2]) -> Option<f64> {
+        let [vx, vy] = self.velocity()?;
+        if vx == 0.0 && vy == 0.0 {
+            return None;
+        }
+        Some((drive[0] * vy - drive[1] * vx).atan2(drive[0] * vx + drive[1] * vy))
+    }
+}
+
+///# Write Skyrmion Trajectory
+/// Tab-separated time, center, radius and wall width of every sample.
+pub fn write_skyrmion_trajectory(
+    path: &Path,
+    samples: &[(f64, SkyrmionProfile)],
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "# t (s)\tx (m)\ty (m)\tR (m)\tw (m)\tresidual (rad)"
+    )?;
+    for (t, profile) in samples {
+        writeln!(
+            writer,
+            "{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}",
+            t,
+            profile.center[0],
+            profile.center[1],
+            profile.radius,
+            profile.wall_width,
+            profile.residual
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::parameters::SimulationParameters;
+    use crate::SPATIAL_DISCRETION_STEP;
+    use ndarray::array;
+
+    // Neel skyrmion of radius R and wall width w in cells at the center
+    fn texture(system: &mut MicromagneticSystem, center: [f64; 2], radius: f64, wall_width: f64) {
+        let grid = system.get_grid();
+        for cell in 0..system.size() {
+            let [x, y, _] = grid.coordinates(cell);
+            let (dx, dy) = (x as f64 - center[0], y as f64 - center[1]);
+            let theta = polar_angle(dx.hypot(dy), radius, wall_width);
+            let phi = dy.atan2(dx);
+            system.set_magnetization(
+                cell,
+                array![
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    -theta.cos()
+                ],
+            );
+        }
+    }
+
+    #[test]
+    /// Test the profile fit of a skyrmion and the Hall angle of its drift
+    fn test_skyrmion_fit() {
+        assert!((polar_angle(6.0, 6.0, 2.0) - PI / 2.0).abs() < 1e-12);
+        assert!(polar_angle(400.0, 6.0, 0.5) < 1e-12);
+
+        let grid = Grid::new(40, 40, 1);
+        let mut system = MicromagneticSystem::on_grid(grid, SimulationParameters::default());
+        let cell = SPATIAL_DISCRETION_STEP;
+        texture(&mut system, [19.3, 20.6], 6.0, 2.0);
+        let profile = fit_skyrmion(&system).unwrap();
+        // The background points down
+        assert_eq!(profile.polarity, -1.0);
+        assert!((profile.center[0] - 19.3 * cell).abs() < 0.1 * cell);
+        assert!((profile.center[1] - 20.6 * cell).abs() < 0.1 * cell);
+        assert!((profile.radius - 6.0 * cell).abs() < 0.05 * cell);
+        assert!((profile.wall_width - 2.0 * cell).abs() < 0.05 * cell);
+        assert!(profile.residual < 1e-3);
+
+        // Drift at 30 degrees from the drive along x
+        let mut tracker = SkyrmionTracker::new(1e-10);
+        let angle = 30f64.to_radians();
+        for k in 0..6 {
+            let shift = k as f64;
+            texture(
+                &mut system,
+                [12.0 + shift * angle.cos(), 14.0 + shift * angle.sin()],
+                5.0,
+                1.5,
+            );
+            assert!(tracker.record(k as f64 * 1e-10, &system));
+        }
+        let velocity = tracker.velocity().unwrap();
+        assert!((velocity[0].hypot(velocity[1]) - cell / 1e-10).abs() < 0.05 * cell / 1e-10);
+        let hall = tracker.hall_angle([1.0, 0.0]).unwrap();
+        assert!((hall - angle).abs() < 1e-2);
+        assert!((tracker.hall_angle([0.0, 1.0]).unwrap() - (angle - PI / 2.0)).abs() < 1e-2);
+
+        // A uniform film has no skyrmion
+        for cell in 0..system.size() {
+            system.set_magnetization(cell, array![0.0, 0.0, 1.0]);
+        }
+        assert!(fit_skyrmion(&system).is_none());
+        assert!(!tracker.record(1e-9, &system));
+
+        let path = std::env::temp_dir().join("energy_relaxation_skyrmion_test.txt");
+        write_skyrmion_trajectory(&path, tracker.samples()).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text.lines().count(), 7);
+    }
+}
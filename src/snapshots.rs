@@ -0,0 +1,293 @@
+use crate::decimation::Decimation;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::ovf::{write_ovf, write_ovf_single, OvfData};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+///# Snapshot Format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SnapshotFormat {
+    // One CSV file with a row per cell and snapshot
+    #[default]
+    Csv,
+    // A directory with one OVF 2.0 file per snapshot, m000000.ovf, m000001.ovf, ...
+    Ovf,
+}
+
+impl FromStr for SnapshotFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(SnapshotFormat::Csv),
+            "ovf" => Ok(SnapshotFormat::Ovf),
+            _ => Err(format!("unknown snapshot format {}, use csv or ovf", s)),
+        }
+    }
+}
+
+///# Snapshot Writer
+/// Writes the magnetization of every cell once per sampling interval and
+/// flushes each snapshot to disk as soon as it is recorded, so a run with
+/// many snapshots needs no more memory than a single one. The CSV columns
+/// are the time, the cell, the three coordinates of its laboratory
+/// position, where x includes the offset of a moving frame, the
+/// magnetization and the region id of the cell, 0 outside every region.
+/// A decimation keeps every k-th cell along each axis of every k-th
+/// sampled snapshot.
+pub struct SnapshotWriter {
+    // CSV file or OVF directory
+    path: PathBuf,
+    csv: Option<BufWriter<File>>,
+    // Simulated time between two snapshots in s
+    sampling_interval: f64,
+    // Time of the next snapshot to write
+    next_sample: f64,
+    decimation: Decimation,
+    // Snapshots sampled and written so far
+    sampled: usize,
+    count: usize,
+}
+
+impl SnapshotWriter {
+    ///# Create Snapshot Writer
+    /// Creates the CSV file with its header or the OVF directory. A sampling
+    /// interval of zero writes every recorded state.
+    pub fn create(path: &Path, format: SnapshotFormat, sampling_interval: f64) -> io::Result<Self> {
+        if sampling_interval.is_nan() || sampling_interval < 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The sampling interval must not be negative",
+            ));
+        }
+        let csv = match format {
+            SnapshotFormat::Csv => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                writeln!(writer, "t (s),cell,x (m),y (m),z (m),mx,my,mz,region")?;
+                Some(writer)
+            }
+            SnapshotFormat::Ovf => {
+                fs::create_dir_all(path)?;
+                None
+            }
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            csv,
+            sampling_interval,
+            next_sample: 0.0,
+            decimation: Decimation::default(),
+            sampled: 0,
+            count: 0,
+        })
+    }
+
+    ///# With Decimation
+    pub fn with_decimation(self, decimation: Decimation) -> Self {
+        Self { decimation, ..self }
+    }
+
+    ///# Record
+    /// Write a snapshot when the sampling time has been reached and report
+    /// whether it was written.
+    pub fn record(&mut self, t: f64, system: &MicromagneticSystem) -> io::Result<bool> {
+        // Allow for the rounding of accumulated time steps
+        if t < self.next_sample - 1e-9 * self.sampling_interval {
+            return Ok(false);
+        }
+        if self.sampling_interval > 0.0 {
+            let samples = (t / self.sampling_interval + 1e-9).floor() + 1.0;
+            self.next_sample = samples * self.sampling_interval;
+        }
+        self.sampled += 1;
+        if !self.decimation.keeps_snapshot(self.sampled - 1) {
+            return Ok(false);
+        }
+        let decimation = self.decimation;
+        let grid = system.get_grid();
+        let magnetizations = decimation.magnetizations(grid, &system.get_magnetizations());
+        match &mut self.csv {
+            Some(writer) => {
+                let cells = decimation.cells(grid);
+                let regions = system.get_regions();
+                for (cell, m) in cells.zip(&magnetizations) {
+                    let [x, y, z] = grid.position(cell, system.get_cell_size());
+                    let x = system.frame_offset() + x;
+                    if decimation.single_precision {
+                        let m = m.map(|value| value as f32);
+                        writeln!(
+                            writer,
+                            "{:e},{},{:e},{:e},{:e},{:e},{:e},{:e},{}",
+                            t, cell, x, y, z, m[0], m[1], m[2], regions[cell]
+                        )?;
+                    } else {
+                        writeln!(
+                            writer,
+                            "{:e},{},{:e},{:e},{:e},{:e},{:e},{:e},{}",
+                            t, cell, x, y, z, m[0], m[1], m[2], regions[cell]
+                        )?;
+                    }
+                }
+                writer.flush()?;
+            }
+            None => {
+                let step = decimation.cell_stride as f64 * system.get_cell_size();
+                let data = OvfData {
+                    nodes: decimation.grid(grid).into(),
+                    step_sizes: [step; 3],
+                    value_unit: "1".to_string(),
+                    vectors: magnetizations,
+                };
+                let path = self.path.join(format!("m{:06}.ovf", self.count));
+                let mut writer = BufWriter::new(File::create(path)?);
+                if decimation.single_precision {
+                    write_ovf_single(&mut writer, &data, "m", t)?;
+                } else {
+                    write_ovf(&mut writer, &data, "m", t)?;
+                }
+                writer.flush()?;
+            }
+        }
+        self.count += 1;
+        Ok(true)
+    }
+
+    ///# Snapshot Count
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::ovf::read_ovf;
+    use crate::parameters::SimulationParameters;
+    use crate::SPATIAL_DISCRETION_STEP;
+
+    #[test]
+    /// Test that both formats write one snapshot per sampling interval
+    fn test_snapshot_writer() {
+        let system = MicromagneticSystem::new(4);
+        let directory = std::env::temp_dir().join("energy_relaxation_snapshots_test");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        let csv = directory.join("snapshots.csv");
+        let ovf = directory.join("ovf");
+        for (path, format) in [(&csv, SnapshotFormat::Csv), (&ovf, SnapshotFormat::Ovf)] {
+            let mut writer = SnapshotWriter::create(path, format, 1e-12).unwrap();
+            for step in 0..=100 {
+                writer.record(step as f64 * 1e-14, &system).unwrap();
+            }
+            assert_eq!(writer.count(), 2);
+        }
+
+        let text = fs::read_to_string(&csv).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 1 + 2 * 4);
+        let row: Vec<f64> = lines[8].split(',').map(|v| v.parse().unwrap()).collect();
+        let m = &system.get_magnetizations()[3];
+        assert_eq!(
+            row,
+            vec![
+                1e-12,
+                3.0,
+                3.0 * SPATIAL_DISCRETION_STEP,
+                0.0,
+                0.0,
+                m[0],
+                m[1],
+                m[2],
+                0.0
+            ]
+        );
+
+        let data = read_ovf(&ovf.join("m000001.ovf")).unwrap();
+        assert_eq!(data.nodes, [4, 1, 1]);
+        assert_eq!(data.vectors[3], [m[0], m[1], m[2]]);
+        assert!(!ovf.join("m000002.ovf").exists());
+        assert!(SnapshotWriter::create(&csv, SnapshotFormat::Csv, -1.0).is_err());
+
+        // Every second cell of every third snapshot in single precision
+        let decimation = Decimation {
+            cell_stride: 2,
+            snapshot_stride: 3,
+            single_precision: true,
+        };
+        let decimated = directory.join("decimated.csv");
+        let decimated_ovf = directory.join("decimated");
+        for (path, format) in [
+            (&decimated, SnapshotFormat::Csv),
+            (&decimated_ovf, SnapshotFormat::Ovf),
+        ] {
+            let mut writer = SnapshotWriter::create(path, format, 0.0)
+                .unwrap()
+                .with_decimation(decimation);
+            let written = (0..7)
+                .filter(|&step| writer.record(step as f64 * 1e-14, &system).unwrap())
+                .count();
+            assert_eq!((written, writer.count()), (3, 3));
+        }
+        let text = fs::read_to_string(&decimated).unwrap();
+        let rows: Vec<Vec<f64>> = text
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(rows.len(), 3 * 2);
+        assert_eq!((rows[1][0], rows[1][1]), (0.0, 2.0));
+        assert_eq!((rows[2][0], rows[2][1]), (3e-14, 0.0));
+        // The shortest representation of the f32 value
+        assert_eq!(rows[1][5] as f32, system.get_magnetizations()[2][0] as f32);
+        let data = read_ovf(&decimated_ovf.join("m000002.ovf")).unwrap();
+        assert_eq!(data.nodes, [2, 1, 1]);
+        assert_eq!(data.step_sizes[0], 2.0 * SPATIAL_DISCRETION_STEP);
+        assert!(!decimated_ovf.join("m000003.ovf").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    /// Test that the snapshots of a film keep its rows and are decimated
+    /// along both axes
+    fn test_grid_snapshots() {
+        let grid = Grid::new(4, 3, 1);
+        let system = MicromagneticSystem::on_grid(grid, SimulationParameters::default());
+        let directory = std::env::temp_dir().join("energy_relaxation_grid_snapshots_test");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let decimation = Decimation {
+            cell_stride: 2,
+            ..Decimation::default()
+        };
+
+        let csv = directory.join("snapshots.csv");
+        let ovf = directory.join("ovf");
+        for (path, format) in [(&csv, SnapshotFormat::Csv), (&ovf, SnapshotFormat::Ovf)] {
+            let mut writer = SnapshotWriter::create(path, format, 0.0)
+                .unwrap()
+                .with_decimation(decimation);
+            assert!(writer.record(0.0, &system).unwrap());
+        }
+
+        let text = fs::read_to_string(&csv).unwrap();
+        let rows: Vec<Vec<f64>> = text
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+            .collect();
+        let cells: Vec<f64> = rows.iter().map(|row| row[1]).collect();
+        assert_eq!(cells, [0.0, 2.0, 8.0, 10.0]);
+        let cell = SPATIAL_DISCRETION_STEP;
+        assert_eq!(rows[3][2..5], [2.0 * cell, 2.0 * cell, 0.0]);
+
+        let data = read_ovf(&ovf.join("m000000.ovf")).unwrap();
+        assert_eq!(data.nodes, [2, 2, 1]);
+        let m = &system.get_magnetizations()[10];
+        assert_eq!(data.vectors[3], [m[0], m[1], m[2]]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,403 @@
+use crate::convergence::ConvergenceMonitor;
+use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+use crate::DAMPING_CONSTANT;
+use crate::GILBERT_GYROMAGNETIC_RATIO;
+use crate::PERMEABILITY_OF_FREE_SPACE;
+use crate::TIME_STEP;
+use crate::TOLERANCE;
+use ndarray::Array1;
+
+// Torque |m x H| in A/m at which the damping-only relaxation step with the
+// default damping changes m by less than the tolerance
+pub const TORQUE_TOLERANCE: f64 = TOLERANCE * (1.0 + DAMPING_CONSTANT * DAMPING_CONSTANT)
+    / (TIME_STEP * DAMPING_CONSTANT * GILBERT_GYROMAGNETIC_RATIO);
+// The local frames are re-anchored and the search direction reset this often
+const RESTART_INTERVAL: usize = 20;
+// Largest rotation of a single cell in the first trial step of a line search, in rad
+const INITIAL_MAX_ROTATION: f64 = 0.1;
+// Sufficient decrease constant of the Armijo condition
+const ARMIJO_CONSTANT: f64 = 1e-4;
+const MAX_BACKTRACKING_STEPS: usize = 40;
+
+///# Spherical Angles
+/// Polar angle from z and azimuth from x of a unit vector, in rad.
+pub fn to_spherical(m: &[f64; 3]) -> (f64, f64) {
+    (m[2].clamp(-1.0, 1.0).acos(), m[1].atan2(m[0]))
+}
+
+///# Unit Vector from Spherical Angles
+pub fn from_spherical(theta: f64, phi: f64) -> [f64; 3] {
+    [
+        theta.sin() * phi.cos(),
+        theta.sin() * phi.sin(),
+        theta.cos(),
+    ]
+}
+
+///# Spherical Parametrization
+/// Describes every magnetic cell by two unconstrained angles (theta, phi)
+/// in a local frame, m = sin(theta) cos(phi) u + sin(theta) sin(phi) v + cos(theta) w.
+/// The frame is anchored so that the current magnetization lies on its
+/// equator at (pi/2, 0), keeping the poles of the parametrization away.
+/// The unit length is built in, no renormalization is needed.
+#[derive(Debug, Clone)]
+pub struct SphericalParametrization {
+    // Magnetic cells in the order of the angle vector
+    cells: Vec<usize>,
+    // Local frame (u, v, w) of every magnetic cell
+    frames: Vec<[[f64; 3]; 3]>,
+}
+
+impl SphericalParametrization {
+    ///# Anchor Parametrization
+    /// Local frames for the current state of the system.
+    pub fn new(system: &MicromagneticSystem) -> Self {
+        let magnetizations = system.get_magnetizations();
+        let cells: Vec<usize> = (0..system.size())
+            .filter(|&i| !system.is_vacuum(i))
+            .collect();
+        let frames = cells
+            .iter()
+            .map(|&i| {
+                let u = [
+                    magnetizations[i][0],
+                    magnetizations[i][1],
+                    magnetizations[i][2],
+                ];
+                // Any unit vector perpendicular to u
+                let helper = if u[0].abs() < 0.9 {
+                    [1.0, 0.0, 0.0]
+                } else {
+                    [0.0, 1.0, 0.0]
+                };
+                let w = normalized(cross(&u, &helper));
+                let v = cross(&w, &u);
+                [u, v, w]
+            })
+            .collect();
+        Self { cells, frames }
+    }
+
+    ///# Number of Angles
+    pub fn len(&self) -> usize {
+        2 * self.cells.len()
+    }
+
+    ///# Empty
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    ///# Angles
+    /// Angles (theta, phi) of every magnetic cell of the system in the local frames.
+    pub fn angles(&self, system: &MicromagneticSystem) -> Vec<f64> {
+        let magnetizations = system.get_magnetizations();
+        let mut angles = Vec::with_capacity(self.len());
+        for (k, &i) in self.cells.iter().enumerate() {
+            let [u, v, w] = &self.frames[k];
+            let m = [
+                magnetizations[i][0],
+                magnetizations[i][1],
+                magnetizations[i][2],
+            ];
+            let (theta, phi) = to_spherical(&[dot(&m, u), dot(&m, v), dot(&m, w)]);
+            angles.push(theta);
+            angles.push(phi);
+        }
+        angles
+    }
+
+    ///# Apply Angles
+    /// Set the magnetization of the system from the angles.
+    pub fn apply(&self, system: &mut MicromagneticSystem, angles: &[f64]) {
+        for (k, &i) in self.cells.iter().enumerate() {
+            let local = from_spherical(angles[2 * k], angles[2 * k + 1]);
+            let m = self.to_global(k, &local);
+            system.set_magnetization(i, Array1::from_vec(m.to_vec()));
+        }
+    }
+
+    ///# Energy and Gradient
+    /// Set the angles, then return the total energy in J and its derivatives
+    /// with respect to the angles in J/rad.
+    /// dE/dm = -mu0 Ms V H_eff, so the chain rule only needs the effective field.
+    pub fn energy_and_gradient(
+        &self,
+        system: &mut MicromagneticSystem,
+        angles: &[f64],
+    ) -> (f64, Vec<f64>) {
+        self.apply(system, angles);
+        let h_eff = system.compute_effective_field();
+        let saturation_magnetizations = system.get_saturation_magnetizations();
+        let mut gradient = vec![0.0; self.len()];
+        for (k, &i) in self.cells.iter().enumerate() {
+            let (theta, phi) = (angles[2 * k], angles[2 * k + 1]);
+            let d_theta = self.to_global(
+                k,
+                &[
+                    theta.cos() * phi.cos(),
+                    theta.cos() * phi.sin(),
+                    -theta.sin(),
+                ],
+            );
+            let d_phi =
+                self.to_global(k, &[-theta.sin() * phi.sin(), theta.sin() * phi.cos(), 0.0]);
+            let h = [h_eff[i][0], h_eff[i][1], h_eff[i][2]];
+            let scale =
+                -PERMEABILITY_OF_FREE_SPACE * saturation_magnetizations[i] * system.cell_volume();
+            gradient[2 * k] = scale * dot(&h, &d_theta);
+            gradient[2 * k + 1] = scale * dot(&h, &d_phi);
+        }
+        (system.compute_energies().total(), gradient)
+    }
+
+    ///# Maximum Torque
+    /// Largest |m x H_eff| in A/m, recovered from the angle gradient.
+    pub fn max_torque(
+        &self,
+        system: &MicromagneticSystem,
+        angles: &[f64],
+        gradient: &[f64],
+    ) -> f64 {
+        let saturation_magnetizations = system.get_saturation_magnetizations();
+        let mut max_torque: f64 = 0.0;
+        for (k, &i) in self.cells.iter().enumerate() {
+            let sin_theta = angles[2 * k].sin().abs().max(1e-12);
+            let scale =
+                PERMEABILITY_OF_FREE_SPACE * saturation_magnetizations[i] * system.cell_volume();
+            let torque = gradient[2 * k].hypot(gradient[2 * k + 1] / sin_theta) / scale;
+            max_torque = max_torque.max(torque);
+        }
+        max_torque
+    }
+
+    fn to_global(&self, k: usize, local: &[f64; 3]) -> [f64; 3] {
+        let [u, v, w] = &self.frames[k];
+        let mut global = [0.0; 3];
+        for c in 0..3 {
+            global[c] = local[0] * u[c] + local[1] * v[c] + local[2] * w[c];
+        }
+        global
+    }
+}
+
+///# Spherical Conjugate Gradient Minimization
+/// Polak-Ribiere conjugate gradient descent on the unconstrained angles
+/// with a backtracking Armijo line search. Converges under the convergence
+/// policy of the system, the change criterion holds when the torque on
+/// every cell is below that tolerance times TORQUE_TOLERANCE / TOLERANCE,
+/// the change of a relaxation step at that torque. The observer is called as in
+/// `minimize_energy_until`, once per conjugate gradient iteration.
+pub fn minimize_spherical_until<F: FnMut(usize, &MicromagneticSystem) -> bool>(
+    system: &mut MicromagneticSystem,
+    mut observer: F,
+) -> MinimizationOutcome {
+    if !observer(0, system) {
+        return MinimizationOutcome::Stopped { iterations: 0 };
+    }
+    let mut parametrization = SphericalParametrization::new(system);
+    let mut angles = parametrization.angles(system);
+    let (mut energy, mut gradient) = parametrization.energy_and_gradient(system, &angles);
+    let mut direction: Vec<f64> = gradient.iter().map(|g| -g).collect();
+    let mut step_length = f64::NAN;
+    let mut monitor = ConvergenceMonitor::new(system.get_convergence_policy());
+    let max_iterations = system.get_max_iterations();
+
+    for iter in 0..max_iterations {
+        let torque = parametrization.max_torque(system, &angles, &gradient);
+        if parametrization.is_empty()
+            || monitor.converged(torque / TORQUE_TOLERANCE * TOLERANCE, system)
+        {
+            crate::console!("Converged after {} iterations ({}).", iter, monitor.policy);
+            return MinimizationOutcome::Converged { iterations: iter };
+        }
+
+        // Fall back to steepest descent when the direction does not descend
+        let mut slope = dot_slices(&gradient, &direction);
+        if slope >= 0.0 {
+            direction = gradient.iter().map(|g| -g).collect();
+            slope = dot_slices(&gradient, &direction);
+        }
+
+        // Backtracking line search, the first trial rotates no cell by more
+        // than INITIAL_MAX_ROTATION, later ones start from twice the last step
+        let largest = direction.iter().fold(0.0f64, |a, d| a.max(d.abs()));
+        let mut t = if step_length.is_nan() {
+            INITIAL_MAX_ROTATION / largest
+        } else {
+            (2.0 * step_length).min(INITIAL_MAX_ROTATION / largest)
+        };
+        let mut accepted = None;
+        for _ in 0..MAX_BACKTRACKING_STEPS {
+            let trial: Vec<f64> = angles
+                .iter()
+                .zip(&direction)
+                .map(|(a, d)| a + t * d)
+                .collect();
+            let (trial_energy, trial_gradient) =
+                parametrization.energy_and_gradient(system, &trial);
+            if trial_energy <= energy + ARMIJO_CONSTANT * t * slope {
+                accepted = Some((trial, trial_energy, trial_gradient));
+                break;
+            }
+            t *= 0.5;
+        }
+        let Some((new_angles, new_energy, new_gradient)) = accepted else {
+            // No decrease along the direction: the energy is flat to
+            // rounding, restore the last state and stop
+            parametrization.apply(system, &angles);
+            crate::console!(
+                "Converged after {} iterations (energy flat to rounding).",
+                iter
+            );
+            return MinimizationOutcome::Converged { iterations: iter };
+        };
+        step_length = t;
+
+        if (iter + 1) % RESTART_INTERVAL == 0 {
+            // Re-anchor the frames at the new state and restart the descent
+            parametrization = SphericalParametrization::new(system);
+            angles = parametrization.angles(system);
+            (energy, gradient) = parametrization.energy_and_gradient(system, &angles);
+            direction = gradient.iter().map(|g| -g).collect();
+            step_length = f64::NAN;
+        } else {
+            // Polak-Ribiere+ update of the search direction
+            let change: Vec<f64> = new_gradient
+                .iter()
+                .zip(&gradient)
+                .map(|(new, old)| new - old)
+                .collect();
+            let beta =
+                (dot_slices(&new_gradient, &change) / dot_slices(&gradient, &gradient)).max(0.0);
+            direction = new_gradient
+                .iter()
+                .zip(&direction)
+                .map(|(g, d)| -g + beta * d)
+                .collect();
+            angles = new_angles;
+            energy = new_energy;
+            gradient = new_gradient;
+        }
+
+        let time_step = system.get_time_step();
+        system.advance_relaxation_time(time_step);
+        if !observer(iter + 1, system) {
+            return MinimizationOutcome::Stopped {
+                iterations: iter + 1,
+            };
+        }
+    }
+    crate::console!(
+        "Warning: Did not converge within {} iterations.",
+        max_iterations
+    );
+    MinimizationOutcome::NotConverged {
+        iterations: max_iterations,
+    }
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn dot_slices(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalized(v: [f64; 3]) -> [f64; 3] {
+    let norm = dot(&v, &v).sqrt();
+    [v[0] / norm, v[1] / norm, v[2] / norm]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    fn test_chain(size: usize) -> MicromagneticSystem {
+        MicromagneticSystem::from_magnetizations(
+            (0..size)
+                .map(|i| {
+                    let (a, b) = (i as f64 * 2.3, i as f64 * 1.1);
+                    array![a.cos() * b.sin(), a.sin() * b.sin(), b.cos()]
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    /// Test the conversion between vectors and angles
+    fn test_spherical_roundtrip() {
+        let m = normalized([0.3, -0.5, 0.8]);
+        let (theta, phi) = to_spherical(&m);
+        let back = from_spherical(theta, phi);
+        for k in 0..3 {
+            assert!((m[k] - back[k]).abs() < 1e-14);
+        }
+
+        // The anchored frames put every cell on the equator
+        let system = test_chain(5);
+        let parametrization = SphericalParametrization::new(&system);
+        for (k, angle) in parametrization.angles(&system).iter().enumerate() {
+            let expected = if k % 2 == 0 {
+                std::f64::consts::FRAC_PI_2
+            } else {
+                0.0
+            };
+            assert!((angle - expected).abs() < 1e-7);
+        }
+    }
+
+    #[test]
+    /// Test the analytic gradient against finite differences of the energy
+    fn test_gradient() {
+        let mut system = test_chain(4);
+        let parametrization = SphericalParametrization::new(&system);
+        let mut angles = parametrization.angles(&system);
+        angles[2] += 0.3;
+        angles[5] -= 0.2;
+        let (_, gradient) = parametrization.energy_and_gradient(&mut system, &angles);
+        let delta = 1e-6;
+        for k in 0..angles.len() {
+            let mut plus = angles.clone();
+            let mut minus = angles.clone();
+            plus[k] += delta;
+            minus[k] -= delta;
+            let numeric = (parametrization.energy_and_gradient(&mut system, &plus).0
+                - parametrization.energy_and_gradient(&mut system, &minus).0)
+                / (2.0 * delta);
+            let scale = gradient.iter().fold(0.0f64, |a, g| a.max(g.abs()));
+            assert!((numeric - gradient[k]).abs() < 1e-5 * scale);
+        }
+    }
+
+    #[test]
+    /// Test that the conjugate gradient reaches the relaxed energy in fewer iterations
+    fn test_conjugate_gradient() {
+        let mut relaxed = test_chain(10);
+        let mut spherical = relaxed.clone();
+        relaxed.minimize_energy();
+        let outcome = minimize_spherical_until(&mut spherical, |_, _| true);
+        let MinimizationOutcome::Converged { iterations } = outcome else {
+            panic!("conjugate gradient did not converge: {:?}", outcome);
+        };
+        assert!(iterations < 1000);
+        let (e_relaxed, e_spherical) = (
+            relaxed.compute_energies().total(),
+            spherical.compute_energies().total(),
+        );
+        assert!(e_spherical <= e_relaxed + 1e-6 * e_relaxed.abs());
+        for m in spherical.get_magnetizations() {
+            assert!((m.dot(&m) - 1.0).abs() < 1e-12);
+        }
+    }
+}
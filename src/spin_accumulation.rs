@@ -0,0 +1,265 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::{GILBERT_GYROMAGNETIC_RATIO, PERMEABILITY_OF_FREE_SPACE};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Bohr magneton in J/T and elementary charge in C
+const BOHR_MAGNETON: f64 = 9.274_010_078_3e-24;
+pub(crate) const ELEMENTARY_CHARGE: f64 = 1.602_176_634e-19;
+
+///# Spin Accumulation
+/// Drift-diffusion model of the non-equilibrium spin density dm in A/m
+/// that a current along the chain carries (Zhang, Levy and Fert). The
+/// spin current J = beta mu_B / e j m - D d(dm)/dx is polarized by the
+/// magnetization, dm relaxes by spin flips and precesses about m:
+///
+/// 0 = -dJ/dx - dm / tau_sf - dm x m / tau_ex
+///
+/// with no spin current through the chain ends. Vacuum cells (Ms = 0)
+/// polarize nothing and stand for the nonmagnetic spacer of a spin
+/// valve. The accumulation relaxes within tau_sf, so it is solved in the
+/// steady state of the current magnetization at every field evaluation,
+/// and the transverse part exerts the torque of the field
+/// H = dm / (gamma tau_ex Ms) on m, which conserves the total angular
+/// momentum of m and dm.
+///
+/// ```toml
+/// [spin_accumulation]
+/// current_density = 1.0e11
+/// polarization = 0.7
+/// diffusion_constant = 1.0e-3
+/// spin_flip_time = 1.0e-12
+/// exchange_time = 1.0e-14
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpinAccumulation {
+    // Charge current density j along +x in A/m^2
+    pub current_density: f64,
+    // Spin polarization beta of the current
+    pub polarization: f64,
+    // Diffusion constant D in m^2/s
+    pub diffusion_constant: f64,
+    // Spin-flip relaxation time tau_sf in s, the diffusion length is sqrt(D tau_sf)
+    pub spin_flip_time: f64,
+    // Precession time tau_ex = hbar / J_sd in s of the s-d exchange
+    pub exchange_time: f64,
+}
+
+impl SpinAccumulation {
+    ///# Validate
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !self.current_density.is_finite() {
+            return Err("The current density must be finite".into());
+        }
+        if self.polarization.is_nan() || self.polarization.abs() > 1.0 {
+            return Err("The polarization must be within -1..1".into());
+        }
+        for (name, value) in [
+            ("diffusion constant", self.diffusion_constant),
+            ("spin-flip time", self.spin_flip_time),
+            ("exchange time", self.exchange_time),
+        ] {
+            if value.is_nan() || value <= 0.0 {
+                return Err(format!("The {} must be positive", name).into());
+            }
+        }
+        Ok(())
+    }
+
+    ///# Diffusion Length
+    /// lambda_sf = sqrt(D tau_sf) in m.
+    pub fn diffusion_length(&self) -> f64 {
+        (self.diffusion_constant * self.spin_flip_time).sqrt()
+    }
+
+    ///# Solve
+    /// Steady-state spin accumulation dm in A/m of every cell for the
+    /// current magnetization, from the block tridiagonal finite volume
+    /// system along the chain.
+    pub fn solve(&self, system: &MicromagneticSystem) -> Vec<[f64; 3]> {
+        let n = system.size();
+        if n == 0 {
+            return Vec::new();
+        }
+        let magnetizations = system.get_magnetizations();
+        let m: Vec<[f64; 3]> = (0..n)
+            .map(|i| {
+                if system.is_vacuum(i) {
+                    [0.0; 3]
+                } else {
+                    [
+                        magnetizations[i][0],
+                        magnetizations[i][1],
+                        magnetizations[i][2],
+                    ]
+                }
+            })
+            .collect();
+        let dx = system.get_cell_size();
+        let coupling = self.diffusion_constant / (dx * dx);
+        let drift = self.polarization * BOHR_MAGNETON / ELEMENTARY_CHARGE * self.current_density;
+
+        // Drift part of the spin current through the bond i, i + 1
+        let source: Vec<[f64; 3]> = (0..n - 1)
+            .map(|i| std::array::from_fn(|c| drift * 0.5 * (m[i][c] + m[i + 1][c])))
+            .collect();
+
+        // coupling dm_(i-1) + A_i dm_i + coupling dm_(i+1) = b_i with
+        // A_i = -(bonds coupling + 1 / tau_sf) I + [m_i]x / tau_ex
+        let diagonal = |i: usize| {
+            let bonds = usize::from(i > 0) + usize::from(i + 1 < n);
+            let decay = bonds as f64 * coupling + 1.0 / self.spin_flip_time;
+            let [x, y, z] = m[i].map(|c| c / self.exchange_time);
+            [[-decay, -z, y], [z, -decay, -x], [-y, x, -decay]]
+        };
+        let rhs = |i: usize| -> [f64; 3] {
+            std::array::from_fn(|c| {
+                let outgoing = if i + 1 < n { source[i][c] } else { 0.0 };
+                let incoming = if i > 0 { source[i - 1][c] } else { 0.0 };
+                (outgoing - incoming) / dx
+            })
+        };
+
+        // Block Thomas algorithm, the blocks off the diagonal are coupling I
+        let mut upper = vec![[[0.0; 3]; 3]; n];
+        let mut solution = vec![[0.0; 3]; n];
+        for i in 0..n {
+            let mut block = diagonal(i);
+            let mut value = rhs(i);
+            if i > 0 {
+                for r in 0..3 {
+                    for s in 0..3 {
+                        block[r][s] -= coupling * upper[i - 1][r][s];
+                    }
+                    value[r] -= coupling * solution[i - 1][r];
+                }
+            }
+            let inverse = invert(&block);
+            upper[i] = inverse.map(|row| row.map(|v| coupling * v));
+            solution[i] = apply(&inverse, &value);
+        }
+        for i in (0..n - 1).rev() {
+            let correction = apply(&upper[i], &solution[i + 1]);
+            for c in 0..3 {
+                solution[i][c] -= correction[c];
+            }
+        }
+        solution
+    }
+
+    ///# Torque Fields
+    /// Field mu0 dm / (gamma tau_ex Ms) in T on every cell for the
+    /// accumulation, zero in vacuum.
+    pub fn fields(&self, system: &MicromagneticSystem, accumulation: &[[f64; 3]]) -> Vec<[f64; 3]> {
+        let materials = system.get_materials();
+        accumulation
+            .iter()
+            .enumerate()
+            .map(|(i, dm)| {
+                let ms = materials[i].saturation_magnetization;
+                if system.is_vacuum(i) || ms == 0.0 {
+                    return [0.0; 3];
+                }
+                let factor = PERMEABILITY_OF_FREE_SPACE
+                    / (GILBERT_GYROMAGNETIC_RATIO * self.exchange_time * ms);
+                dm.map(|c| factor * c)
+            })
+            .collect()
+    }
+}
+
+// Inverse of a 3x3 matrix from the adjugate
+fn invert(a: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let cofactor = |r: usize, s: usize| {
+        let (r1, r2) = ((r + 1) % 3, (r + 2) % 3);
+        let (s1, s2) = ((s + 1) % 3, (s + 2) % 3);
+        a[r1][s1] * a[r2][s2] - a[r1][s2] * a[r2][s1]
+    };
+    let determinant: f64 = (0..3).map(|s| a[0][s] * cofactor(0, s)).sum();
+    std::array::from_fn(|r| std::array::from_fn(|s| cofactor(s, r) / determinant))
+}
+
+fn apply(a: &[[f64; 3]; 3], v: &[f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|r| (0..3).map(|s| a[r][s] * v[s]).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SPATIAL_DISCRETION_STEP;
+    use ndarray::array;
+
+    #[test]
+    /// Test the accumulation at the ends of a magnet and at a spin valve
+    fn test_spin_accumulation() {
+        let model = SpinAccumulation {
+            current_density: 1.0e11,
+            polarization: 0.7,
+            diffusion_constant: 1.0e-3,
+            spin_flip_time: 1.0e-13,
+            exchange_time: 1.0e-14,
+        };
+        assert!(model.validate().is_ok());
+        assert!(SpinAccumulation {
+            spin_flip_time: 0.0,
+            ..model
+        }
+        .validate()
+        .is_err());
+        assert!((model.diffusion_length() - 1.0e-8).abs() < 1e-20);
+
+        // The current drains spins from the start of a uniform magnet and
+        // piles them up at its end, decaying over the diffusion length
+        let size = 120;
+        let system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]; size]);
+        let dm = model.solve(&system);
+        assert!(dm[0][0] < 0.0);
+        assert!((dm[0][0] + dm[size - 1][0]).abs() < 1e-9 * dm[0][0].abs());
+        assert!(dm
+            .iter()
+            .all(|v| v[1].abs() + v[2].abs() < 1e-12 * dm[0][0].abs()));
+        let factor = 2.0 + (SPATIAL_DISCRETION_STEP / model.diffusion_length()).powi(2);
+        let ratio = 0.5 * (factor - (factor * factor - 4.0).sqrt());
+        assert!((dm[2][0] / dm[1][0] - ratio).abs() < 1e-4);
+        // Collinear accumulation exerts no torque
+        let fields = model.fields(&system, &dm);
+        let magnetizations = system.get_magnetizations();
+        for (field, m) in fields.iter().zip(&magnetizations) {
+            let transverse = field[1] * m[0] - field[0] * m[1];
+            assert!(transverse.abs() < 1e-12 * field[0].abs().max(1e-30));
+        }
+
+        // Fixed layer along x, spacer and a free layer along y
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]; 30]);
+        for i in 10..20 {
+            system.set_saturation_magnetization(i, 0.0);
+        }
+        for i in 20..30 {
+            system.set_magnetization(i, array![0.0, 1.0, 0.0]);
+        }
+        let dm = model.solve(&system);
+        // The spin current polarized along x reaches the free layer
+        assert!(dm[20][0].abs() > 1e-3 * dm[0][0].abs());
+        // Every spin polarized at the ends relaxes or is absorbed as torque
+        let m = system.get_magnetizations();
+        for c in 0..3 {
+            let balance: f64 = (0..30)
+                .map(|i| {
+                    let torque = [
+                        dm[i][1] * m[i][2] - dm[i][2] * m[i][1],
+                        dm[i][2] * m[i][0] - dm[i][0] * m[i][2],
+                        dm[i][0] * m[i][1] - dm[i][1] * m[i][0],
+                    ];
+                    dm[i][c] / model.spin_flip_time + torque[c] / model.exchange_time
+                })
+                .sum();
+            let scale = dm[0][0].abs() / model.spin_flip_time;
+            assert!(balance.abs() < 1e-9 * scale);
+        }
+        let fields = model.fields(&system, &dm);
+        assert!(fields[15] == [0.0; 3]);
+        // The free layer feels a torque towards the fixed layer
+        assert!(fields[20][0].abs() > 0.0);
+    }
+}
@@ -0,0 +1,377 @@
+use crate::dynamics::DynamicsRun;
+use crate::magnetic_moments::{MicromagneticSystem, MinimizationOutcome};
+use crate::material::Material;
+use crate::spin_accumulation::ELEMENTARY_CHARGE;
+use ndarray::Array1;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Reduced Planck constant in J s
+const REDUCED_PLANCK_CONSTANT: f64 = 1.054_571_817e-34;
+// Relaxations after a pulse are continued for up to this many iteration
+// limits, the state next to the barrier relaxes slowly
+const MAX_RELAXATION_ROUNDS: usize = 50;
+
+///# Spin-Transfer Torque
+/// Slonczewski torque of a current through a fixed layer magnetized along
+/// the polarizer p into a free layer of the given thickness, as in the
+/// pillar of an STT-MRAM cell. The torque acts as the field
+///
+/// B = hbar j / (e Ms d) [epsilon (m x p) + epsilon' p]
+///
+/// with epsilon = P L^2 / ((L^2 + 1) + (L^2 - 1) m . p) for the asymmetry
+/// L, so the damping-like part pulls m towards p for positive currents,
+/// and the field-like part epsilon' acts along p.
+///
+/// ```toml
+/// [spin_transfer]
+/// current_density = 1.0e11
+/// polarization = 0.7
+/// polarizer = [1.0, 0.0, 0.0]
+/// thickness = 2.0e-9
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpinTransferTorque {
+    // Charge current density j through the layers in A/m^2, positive
+    // currents favor the state parallel to the polarizer
+    pub current_density: f64,
+    // Spin polarization P of the current
+    pub polarization: f64,
+    // Magnetization of the fixed layer, normalized by the torque
+    pub polarizer: [f64; 3],
+    // Thickness d of the free layer in m
+    pub thickness: f64,
+    // Slonczewski asymmetry L, 1 for the same efficiency in both states
+    #[serde(default = "default_asymmetry")]
+    pub asymmetry: f64,
+    // Ratio epsilon' of the field-like to the current, zero by default
+    #[serde(default)]
+    pub field_like: f64,
+}
+
+fn default_asymmetry() -> f64 {
+    1.0
+}
+
+impl SpinTransferTorque {
+    ///# Validate
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !self.current_density.is_finite() || !self.field_like.is_finite() {
+            return Err("The current density and the field-like torque must be finite".into());
+        }
+        if self.polarization.is_nan() || self.polarization.abs() > 1.0 {
+            return Err("The polarization must be within -1..1".into());
+        }
+        if self.polarizer.iter().all(|&x| x == 0.0) {
+            return Err("The polarizer is zero".into());
+        }
+        if self.thickness.is_nan() || self.thickness <= 0.0 {
+            return Err("The free layer thickness must be positive".into());
+        }
+        if self.asymmetry.is_nan() || self.asymmetry <= 0.0 {
+            return Err("The asymmetry must be positive".into());
+        }
+        Ok(())
+    }
+
+    ///# Unit Polarizer
+    pub fn unit_polarizer(&self) -> [f64; 3] {
+        let norm = self.polarizer.iter().map(|x| x * x).sum::<f64>().sqrt();
+        self.polarizer.map(|x| x / norm)
+    }
+
+    ///# Efficiency
+    /// Slonczewski epsilon at the cosine m . p of the angle to the polarizer.
+    pub fn efficiency(&self, cosine: f64) -> f64 {
+        let square = self.asymmetry * self.asymmetry;
+        self.polarization * square / ((square + 1.0) + (square - 1.0) * cosine)
+    }
+
+    ///# Torque Fields
+    /// Field in T on every cell for the current magnetization, zero in vacuum.
+    pub fn fields(&self, system: &MicromagneticSystem) -> Vec<[f64; 3]> {
+        let p = self.unit_polarizer();
+        let materials = system.get_materials();
+        system
+            .get_magnetizations()
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let ms = materials[i].saturation_magnetization;
+                if system.is_vacuum(i) || ms == 0.0 {
+                    return [0.0; 3];
+                }
+                let strength = REDUCED_PLANCK_CONSTANT * self.current_density
+                    / (ELEMENTARY_CHARGE * ms * self.thickness);
+                let epsilon = self.efficiency(m[0] * p[0] + m[1] * p[1] + m[2] * p[2]);
+                let m_cross_p = [
+                    m[1] * p[2] - m[2] * p[1],
+                    m[2] * p[0] - m[0] * p[2],
+                    m[0] * p[1] - m[1] * p[0],
+                ];
+                std::array::from_fn(|k| {
+                    strength * (epsilon * m_cross_p[k] + self.field_like * p[k])
+                })
+            })
+            .collect()
+    }
+
+    ///# Critical Current Density
+    /// Current density in A/m^2 at which the damping-like torque destabilizes
+    /// a macrospin of the material antiparallel to a polarizer along its easy
+    /// axis without applied and demagnetizing fields, where the torque
+    /// alpha B_K with B_K = 2 K / Ms balances the damping.
+    pub fn critical_current_density(&self, material: &Material) -> f64 {
+        let ms = material.saturation_magnetization;
+        let anisotropy_field = 2.0 * material.anisotropy_constant / ms;
+        material.damping * anisotropy_field * ELEMENTARY_CHARGE * ms * self.thickness
+            / (REDUCED_PLANCK_CONSTANT * self.efficiency(-1.0))
+    }
+}
+
+///# Switching Diagram
+/// Current pulses of every current density and duration on the free layer,
+/// which starts antiparallel to the polarizer, tilted by `initial_tilt` in
+/// rad as by a thermal fluctuation. After a pulse the state relaxes without
+/// current, and it has switched when it ends up on the side of the
+/// polarizer. The pulses of one current density are the same run, read at
+/// every duration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchingDiagram {
+    // Polarizer, polarization and free layer, the current density is swept
+    pub torque: SpinTransferTorque,
+    pub current_densities: Vec<f64>,
+    // Pulse durations in s
+    pub durations: Vec<f64>,
+    // Time step, integrator and applied field of the pulses
+    pub dynamics: DynamicsRun,
+    pub initial_tilt: f64,
+}
+
+///# Switching Point
+/// Outcome of one pulse
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwitchingPoint {
+    // Current density in A/m^2
+    pub current_density: f64,
+    // Pulse duration in s
+    pub duration: f64,
+    pub switched: bool,
+    // Average magnetization along the polarizer after the relaxation
+    pub projection: f64,
+}
+
+///# Boundary Point
+/// Lowest current density of a pulse duration above which every swept
+/// current density switched, `None` when the largest did not switch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundaryPoint {
+    pub duration: f64,
+    pub critical_current_density: Option<f64>,
+}
+
+impl SwitchingDiagram {
+    ///# Run Switching Diagram
+    /// The points are in rows of constant current density with the
+    /// duration running fastest.
+    pub fn run(&self, system: &MicromagneticSystem) -> Result<Vec<SwitchingPoint>, Box<dyn Error>> {
+        self.torque.validate()?;
+        if self.current_densities.is_empty() || self.durations.is_empty() {
+            return Err("The diagram needs at least one current density and duration".into());
+        }
+        if self.durations.iter().any(|d| d.is_nan() || *d <= 0.0)
+            || self.durations.windows(2).any(|pair| pair[1] <= pair[0])
+        {
+            return Err("The pulse durations must be positive and increasing".into());
+        }
+        let p = self.torque.unit_polarizer();
+        let helper = if p[0].abs() < 0.9 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        };
+        let along = (0..3).map(|k| helper[k] * p[k]).sum::<f64>();
+        let perpendicular: [f64; 3] = std::array::from_fn(|k| helper[k] - along * p[k]);
+        let norm = perpendicular.iter().map(|x| x * x).sum::<f64>().sqrt();
+        let (sin, cos) = self.initial_tilt.sin_cos();
+        let start: Vec<f64> = (0..3)
+            .map(|k| -cos * p[k] + sin * perpendicular[k] / norm)
+            .collect();
+        let longest = self.durations[self.durations.len() - 1];
+
+        let mut points = Vec::with_capacity(self.current_densities.len() * self.durations.len());
+        for &current_density in &self.current_densities {
+            let mut state = system.clone();
+            for cell in 0..state.size() {
+                state.set_magnetization(cell, Array1::from_vec(start.clone()));
+            }
+            let run = DynamicsRun {
+                spin_transfer: Some(SpinTransferTorque {
+                    current_density,
+                    ..self.torque
+                }),
+                ..self.dynamics
+            };
+            let mut pulses = Vec::with_capacity(self.durations.len());
+            let step = run.time_step;
+            run.run(&mut state, longest, |time, state| {
+                // A duration off the time step grid is read at the nearest step
+                while let Some(&duration) = self.durations.get(pulses.len()) {
+                    if time < duration - 0.5 * step {
+                        break;
+                    }
+                    pulses.push((duration, state.clone()));
+                }
+            });
+            for (duration, mut pulsed) in pulses {
+                pulsed
+                    .set_local_fields(Vec::new())
+                    .expect("an empty list removes the local fields");
+                relax(&mut pulsed);
+                let m = pulsed.average_magnetization();
+                let projection = m[0] * p[0] + m[1] * p[1] + m[2] * p[2];
+                points.push(SwitchingPoint {
+                    current_density,
+                    duration,
+                    switched: projection > 0.0,
+                    projection,
+                });
+            }
+        }
+        Ok(points)
+    }
+}
+
+///# Relax
+/// Relax for up to the given number of iteration limits.
+fn relax(system: &mut MicromagneticSystem) {
+    for _ in 0..MAX_RELAXATION_ROUNDS {
+        if !matches!(
+            system.minimize_energy_until(|_, _| true),
+            MinimizationOutcome::NotConverged { .. }
+        ) {
+            break;
+        }
+    }
+}
+
+///# Switching Boundary
+/// Critical current density of every pulse duration, from the points of a
+/// switching diagram in any order.
+pub fn switching_boundary(points: &[SwitchingPoint]) -> Vec<BoundaryPoint> {
+    let mut durations: Vec<f64> = points.iter().map(|p| p.duration).collect();
+    durations.sort_by(f64::total_cmp);
+    durations.dedup();
+    durations
+        .into_iter()
+        .map(|duration| {
+            let mut pulses: Vec<&SwitchingPoint> =
+                points.iter().filter(|p| p.duration == duration).collect();
+            pulses.sort_by(|a, b| b.current_density.total_cmp(&a.current_density));
+            BoundaryPoint {
+                duration,
+                critical_current_density: pulses
+                    .iter()
+                    .take_while(|p| p.switched)
+                    .last()
+                    .map(|p| p.current_density),
+            }
+        })
+        .collect()
+}
+
+///# Write Switching Map
+/// Tab-separated current density, duration and outcome of every pulse.
+pub fn write_switching_map(path: &Path, points: &[SwitchingPoint]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "# j (A/m^2)\tt (s)\tswitched\tm_p ()")?;
+    for point in points {
+        writeln!(
+            writer,
+            "{:e}\t{:e}\t{}\t{:e}",
+            point.current_density, point.duration, point.switched, point.projection
+        )?;
+    }
+    writer.flush()
+}
+
+///# Write Switching Boundary
+/// Tab-separated duration and critical current density, NaN for durations
+/// that did not switch.
+pub fn write_switching_boundary(path: &Path, boundary: &[BoundaryPoint]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "# t (s)\tj_c (A/m^2)")?;
+    for point in boundary {
+        writeln!(
+            writer,
+            "{:e}\t{:e}",
+            point.duration,
+            point.critical_current_density.unwrap_or(f64::NAN)
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::TimeDependentField;
+    use ndarray::array;
+
+    #[test]
+    /// Test the Slonczewski field and the switching of a macrospin above
+    /// the critical current density
+    fn test_spin_transfer_switching() {
+        let torque = SpinTransferTorque {
+            current_density: 1.0e11,
+            polarization: 0.6,
+            polarizer: [2.0, 0.0, 0.0],
+            thickness: 2.0e-9,
+            asymmetry: 2.0,
+            field_like: 0.0,
+        };
+        // epsilon = P L^2 / (L^2 + 1 + (L^2 - 1) cos)
+        assert!((torque.efficiency(-1.0) - 1.2).abs() < 1e-12);
+        assert!((torque.efficiency(1.0) - 0.3).abs() < 1e-12);
+        let mut system = MicromagneticSystem::new(1);
+        system.set_magnetization(0, array![0.0, 1.0, 0.0]);
+        let field = torque.fields(&system)[0];
+        let ms = system.get_materials()[0].saturation_magnetization;
+        let expected = REDUCED_PLANCK_CONSTANT * 1.0e11 * torque.efficiency(0.0)
+            / (ELEMENTARY_CHARGE * ms * 2.0e-9);
+        // m x p = y x x = -z
+        assert!(field[0].abs() < 1e-15 && field[1].abs() < 1e-15);
+        assert!((field[2] + expected).abs() < 1e-12 * expected);
+
+        // Pulses below the critical current never switch, above it the
+        // longer pulse does
+        let critical = torque.critical_current_density(&system.get_materials()[0]);
+        let diagram = SwitchingDiagram {
+            torque,
+            current_densities: vec![0.5 * critical, 3.0 * critical],
+            durations: vec![5e-11, 4e-9],
+            dynamics: DynamicsRun {
+                time_step: 1e-13,
+                ..DynamicsRun::new(TimeDependentField::Constant([0.0; 3]))
+            },
+            initial_tilt: 0.05,
+        };
+        let points = diagram.run(&system).unwrap();
+        let switched: Vec<bool> = points.iter().map(|p| p.switched).collect();
+        assert_eq!(switched, [false, false, false, true]);
+        assert!(points[0].projection < -0.99 && points[3].projection > 0.99);
+        let boundary = switching_boundary(&points);
+        assert_eq!(boundary[0].critical_current_density, None);
+        assert_eq!(boundary[1].critical_current_density, Some(3.0 * critical));
+        assert!(SwitchingDiagram {
+            durations: vec![1e-9, 1e-10],
+            ..diagram
+        }
+        .run(&system)
+        .is_err());
+    }
+}
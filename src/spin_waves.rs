@@ -0,0 +1,361 @@
+use crate::dynamics::DynamicsRun;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::summation::compensated_sum;
+use ndarray::Array1;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use std::f64::consts::PI;
+
+// Peaks below this fraction of the strongest peak are treated as noise
+pub const DEFAULT_PEAK_THRESHOLD: f64 = 0.05;
+
+///# Magnetization History
+/// Per-cell magnetization recorded during dynamics at a fixed sampling
+/// interval of simulated time, the input of the spectral analysis.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MagnetizationHistory {
+    // Simulated time between two samples in s
+    pub sampling_interval: f64,
+    // samples[n][i] is the magnetization of cell i at time n * sampling_interval
+    pub samples: Vec<Vec<[f64; 3]>>,
+}
+
+///# Mode Map
+/// Spatial amplitude and phase of the magnetization oscillation at one
+/// frequency, per cell and component. The phase is in rad relative to the
+/// first sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeMap {
+    pub frequency: f64,
+    pub amplitude: Vec<[f64; 3]>,
+    pub phase: Vec<[f64; 3]>,
+}
+
+impl MagnetizationHistory {
+    ///# New Magnetization History
+    pub fn new(sampling_interval: f64) -> Self {
+        Self {
+            sampling_interval,
+            samples: Vec::new(),
+        }
+    }
+
+    ///# Record
+    /// Store the state when `t` has reached the time of the next sample and
+    /// report whether it was stored. The first sample is taken at t = 0.
+    pub fn record(&mut self, t: f64, system: &MicromagneticSystem) -> bool {
+        let next_sample = self.samples.len() as f64 * self.sampling_interval;
+        // Allow for the rounding of accumulated time steps
+        if t < next_sample - 1e-9 * self.sampling_interval {
+            return false;
+        }
+        self.samples.push(
+            system
+                .get_magnetizations()
+                .iter()
+                .map(|m| [m[0], m[1], m[2]])
+                .collect(),
+        );
+        true
+    }
+
+    ///# Number of Cells
+    pub fn cells(&self) -> usize {
+        self.samples.first().map_or(0, Vec::len)
+    }
+
+    ///# Power Spectrum
+    /// Frequencies in Hz and the power summed over all cells and components,
+    /// so modes without a net moment show up as well. The mean-free,
+    /// Hann-windowed signals are zero padded to twice the next power of two.
+    pub fn power_spectrum(&self) -> (Vec<f64>, Vec<f64>) {
+        let samples = self.samples.len();
+        let cells = self.cells();
+        if samples < 2 {
+            return (Vec::new(), Vec::new());
+        }
+        let length = 2 * samples.next_power_of_two();
+        let window: Vec<f64> = (0..samples)
+            .map(|n| 0.5 - 0.5 * (2.0 * PI * n as f64 / samples as f64).cos())
+            .collect();
+        let fft = FftPlanner::new().plan_fft_forward(length);
+
+        let mut power = vec![0.0; length / 2 + 1];
+        let mut buffer = vec![Complex::new(0.0, 0.0); length];
+        for i in 0..cells {
+            for k in 0..3 {
+                let mean = compensated_sum(self.samples.iter().map(|sample| sample[i][k]))
+                    / samples as f64;
+                for (n, value) in buffer.iter_mut().enumerate() {
+                    *value = if n < samples {
+                        Complex::new(window[n] * (self.samples[n][i][k] - mean), 0.0)
+                    } else {
+                        Complex::new(0.0, 0.0)
+                    };
+                }
+                fft.process(&mut buffer);
+                for (bin, value) in power.iter_mut().enumerate() {
+                    *value += buffer[bin].norm_sqr();
+                }
+            }
+        }
+        let frequencies = (0..power.len())
+            .map(|bin| bin as f64 / (length as f64 * self.sampling_interval))
+            .collect();
+        (frequencies, power)
+    }
+
+    ///# Eigenmodes
+    /// Detect the peaks of the power spectrum of a ringdown and reconstruct
+    /// the spatial profile of every mode at its peak frequency.
+    pub fn eigenmodes(&self, relative_threshold: f64) -> Vec<ModeMap> {
+        let (frequencies, power) = self.power_spectrum();
+        self.mode_maps(&find_peaks(&frequencies, &power, relative_threshold))
+    }
+
+    ///# Mode Maps
+    /// Fourier transform of every cell and component in time, evaluated at
+    /// the given frequencies in Hz. The static part is removed and a Hann
+    /// window suppresses the leakage of the finite record, the amplitude is
+    /// corrected for the window.
+    pub fn mode_maps(&self, frequencies: &[f64]) -> Vec<ModeMap> {
+        let samples = self.samples.len();
+        let cells = self.cells();
+        let window: Vec<f64> = (0..samples)
+            .map(|n| 0.5 - 0.5 * (2.0 * PI * n as f64 / samples.max(2) as f64).cos())
+            .collect();
+        let window_sum: f64 = window.iter().sum();
+
+        // Static part of every cell and component
+        let mut mean = vec![[0.0; 3]; cells];
+        for i in 0..cells {
+            for k in 0..3 {
+                mean[i][k] = compensated_sum(self.samples.iter().map(|sample| sample[i][k]))
+                    / samples as f64;
+            }
+        }
+
+        frequencies
+            .iter()
+            .map(|&frequency| {
+                let mut real = vec![[0.0; 3]; cells];
+                let mut imaginary = vec![[0.0; 3]; cells];
+                for (n, sample) in self.samples.iter().enumerate() {
+                    let angle = 2.0 * PI * frequency * n as f64 * self.sampling_interval;
+                    let (sin, cos) = angle.sin_cos();
+                    for i in 0..cells {
+                        for k in 0..3 {
+                            let value = window[n] * (sample[i][k] - mean[i][k]);
+                            real[i][k] += value * cos;
+                            imaginary[i][k] -= value * sin;
+                        }
+                    }
+                }
+                let mut amplitude = vec![[0.0; 3]; cells];
+                let mut phase = vec![[0.0; 3]; cells];
+                for i in 0..cells {
+                    for k in 0..3 {
+                        amplitude[i][k] = 2.0 * real[i][k].hypot(imaginary[i][k])
+                            / window_sum.max(f64::MIN_POSITIVE);
+                        phase[i][k] = imaginary[i][k].atan2(real[i][k]);
+                    }
+                }
+                ModeMap {
+                    frequency,
+                    amplitude,
+                    phase,
+                }
+            })
+            .collect()
+    }
+}
+
+///# Find Peaks
+/// Frequencies of the local maxima of a spectrum above the given fraction
+/// of its largest value, refined by a parabola through the three bins
+/// around each maximum. The zero frequency bin is never a peak.
+pub fn find_peaks(frequencies: &[f64], power: &[f64], relative_threshold: f64) -> Vec<f64> {
+    let maximum = power.iter().skip(1).cloned().fold(0.0, f64::max);
+    if maximum <= 0.0 {
+        return Vec::new();
+    }
+    let mut peaks = Vec::new();
+    for bin in 2..power.len().saturating_sub(1) {
+        let (left, center, right) = (power[bin - 1], power[bin], power[bin + 1]);
+        if center < relative_threshold * maximum || center <= left || center < right {
+            continue;
+        }
+        // Vertex of the parabola in units of bins
+        let curvature = left - 2.0 * center + right;
+        let offset = if curvature < 0.0 {
+            0.5 * (left - right) / curvature
+        } else {
+            0.0
+        };
+        let spacing = frequencies[bin + 1] - frequencies[bin];
+        peaks.push(frequencies[bin] + offset * spacing);
+    }
+    peaks
+}
+
+///# Ringdown Excitation
+/// Tilt the magnetization away from its current direction about an axis
+/// perpendicular to it. The angle decreases linearly from `tilt` (in rad) at the
+/// first cell to zero after the last, so that both symmetric and
+/// antisymmetric modes of the chain are excited.
+pub fn excite_ringdown(system: &mut MicromagneticSystem, tilt: f64) {
+    let size = system.size();
+    let magnetizations = system.get_magnetizations();
+    for (i, m) in magnetizations.iter().enumerate() {
+        if system.is_vacuum(i) {
+            continue;
+        }
+        // Tilt towards the direction perpendicular to m that is closest to z, or y
+        let helper = if m[2].abs() < 0.9 {
+            Array1::from_vec(vec![0.0, 0.0, 1.0])
+        } else {
+            Array1::from_vec(vec![0.0, 1.0, 0.0])
+        };
+        let mut perpendicular = &helper - m.dot(&helper) * m;
+        perpendicular /= perpendicular.dot(&perpendicular).sqrt();
+        let angle = tilt * (1.0 - i as f64 / size as f64);
+        system.set_magnetization(i, angle.cos() * m + angle.sin() * perpendicular);
+    }
+}
+
+///# Ringdown
+/// Let the excited system ring down and record its magnetization.
+pub fn ringdown(
+    system: &mut MicromagneticSystem,
+    run: &DynamicsRun,
+    duration: f64,
+    sampling_interval: f64,
+) -> MagnetizationHistory {
+    let mut history = MagnetizationHistory::new(sampling_interval);
+    run.run(system, duration, |time, system| {
+        history.record(time, system);
+    });
+    history
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the amplitude and phase of a synthetic standing wave
+    fn test_mode_maps() {
+        let frequency = 10e9;
+        let sampling_interval = 1e-12;
+        let cells = 5;
+        // Whole number of periods, with a different amplitude and phase per cell
+        let samples = (0..2000)
+            .map(|n| {
+                let t = n as f64 * sampling_interval;
+                (0..cells)
+                    .map(|i| {
+                        let phase = 0.3 * i as f64;
+                        let amplitude = 0.01 * (i + 1) as f64;
+                        [
+                            1.0,
+                            amplitude * (2.0 * PI * frequency * t + phase).cos(),
+                            0.002 * (2.0 * PI * 3.0 * frequency * t).sin(),
+                        ]
+                    })
+                    .collect()
+            })
+            .collect();
+        let history = MagnetizationHistory {
+            sampling_interval,
+            samples,
+        };
+
+        let maps = history.mode_maps(&[frequency, 3.0 * frequency]);
+        for i in 0..cells {
+            let expected = 0.01 * (i + 1) as f64;
+            assert!((maps[0].amplitude[i][1] - expected).abs() < 1e-3 * expected);
+            assert!((maps[0].phase[i][1] - 0.3 * i as f64).abs() < 1e-6);
+            assert!(maps[0].amplitude[i][0] < 1e-12);
+            assert!(maps[0].amplitude[i][2] < 1e-6);
+            assert!((maps[1].amplitude[i][2] - 0.002).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    /// Test the sampling of the recorder
+    fn test_record() {
+        let system = MicromagneticSystem::new(3);
+        let mut history = MagnetizationHistory::new(1e-12);
+        for step in 0..=250 {
+            history.record(step as f64 * 1e-14, &system);
+        }
+        assert_eq!(history.samples.len(), 3);
+        assert_eq!(history.cells(), 3);
+    }
+
+    #[test]
+    /// Test that the peaks of a two mode signal are found with their profiles
+    fn test_eigenmodes() {
+        let sampling_interval = 1e-12;
+        // Uniform mode at 5 GHz and an antisymmetric mode at 12.3 GHz
+        let samples = (0..1500)
+            .map(|n| {
+                let t = n as f64 * sampling_interval;
+                (0..4)
+                    .map(|i| {
+                        let sign = if i < 2 { 1.0 } else { -1.0 };
+                        [
+                            0.0,
+                            0.02 * (2.0 * PI * 5e9 * t).cos()
+                                + 0.01 * sign * (2.0 * PI * 12.3e9 * t).cos(),
+                            1.0,
+                        ]
+                    })
+                    .collect()
+            })
+            .collect();
+        let history = MagnetizationHistory {
+            sampling_interval,
+            samples,
+        };
+
+        let modes = history.eigenmodes(DEFAULT_PEAK_THRESHOLD);
+        assert_eq!(modes.len(), 2);
+        assert!((modes[0].frequency - 5e9).abs() < 0.05e9);
+        assert!((modes[1].frequency - 12.3e9).abs() < 0.05e9);
+        // The second mode has opposite phases on the two halves of the chain
+        let phase_difference = (modes[1].phase[0][1] - modes[1].phase[3][1]).abs();
+        assert!((phase_difference - PI).abs() < 0.1);
+        assert!((modes[1].amplitude[0][1] - 0.01).abs() < 1e-3);
+    }
+
+    #[test]
+    /// Test the ringdown frequency of a macrospin against the Kittel formula
+    fn test_macrospin_ringdown() {
+        let mut system = MicromagneticSystem::new(1);
+        let mut material = system.get_materials()[0];
+        material.damping = 0.01;
+        system.set_material(0, material);
+        system.set_magnetization(0, Array1::from_vec(vec![1.0, 0.0, 0.0]));
+        excite_ringdown(&mut system, 0.05);
+
+        let field = 0.1;
+        // A single cell has no exchange modes, so a coarse time step is stable
+        let run = DynamicsRun {
+            time_step: 1e-13,
+            ..DynamicsRun::new(crate::dynamics::TimeDependentField::Constant([
+                field, 0.0, 0.0,
+            ]))
+        };
+        let history = ringdown(&mut system, &run, 2e-9, 1e-12);
+        let modes = history.eigenmodes(DEFAULT_PEAK_THRESHOLD);
+
+        // f = gamma / (2 pi (1 + alpha^2)) (H + 2 K / (mu0 Ms))
+        let h = field / crate::PERMEABILITY_OF_FREE_SPACE
+            + 2.0 * material.anisotropy_constant
+                / (crate::PERMEABILITY_OF_FREE_SPACE * material.saturation_magnetization);
+        let expected = crate::GILBERT_GYROMAGNETIC_RATIO * h / (2.0 * PI * (1.0 + 0.01f64.powi(2)));
+        assert_eq!(modes.len(), 1);
+        assert!((modes[0].frequency - expected).abs() < 0.01 * expected);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::eigen::symmetric_eigen;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::normal_modes::tangent_hessian;
+use ndarray::Array2;
+use std::fmt;
+
+// Eigenvalues within this stiffness of zero count as soft, in T
+pub const DEFAULT_SOFT_THRESHOLD: f64 = 1e-4;
+
+///# Stability
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stability {
+    // Every eigenvalue of the Hessian is clearly positive
+    Minimum,
+    // No negative eigenvalue, but a soft mode leaves the state undetermined
+    // to second order, e.g. the free rotation of an isotropic chain
+    Marginal,
+    // The energy decreases along this many directions, the relaxation stopped falsely
+    Saddle { unstable_modes: usize },
+}
+
+impl fmt::Display for Stability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stability::Minimum => write!(f, "minimum"),
+            Stability::Marginal => write!(f, "marginal"),
+            Stability::Saddle { unstable_modes } => {
+                write!(f, "saddle point with {} unstable modes", unstable_modes)
+            }
+        }
+    }
+}
+
+///# Soft Mode
+/// Eigenvector of the Hessian with an eigenvalue below the soft threshold,
+/// as the rotation of the magnetization of every cell, normalized to a
+/// largest rotation of 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoftMode {
+    // Stiffness in T, negative for a direction of decreasing energy
+    pub eigenvalue: f64,
+    pub displacement: Vec<[f64; 3]>,
+}
+
+///# Stability Report
+#[derive(Debug, Clone, PartialEq)]
+pub struct StabilityReport {
+    pub stability: Stability,
+    // The lowest eigenvalues of the Hessian in T, ascending
+    pub lowest_eigenvalues: Vec<f64>,
+    pub soft_modes: Vec<SoftMode>,
+}
+
+///# Classify Stability
+/// Eigenvalues of the tangent Hessian of a relaxed state, divided by
+/// Ms V of the cells so that they are stiffness fields in T independent
+/// of the cell size: the curvature of the energy per moment. A minimum
+/// has only positive eigenvalues, a negative one shows that the
+/// relaxation stopped on a saddle, typically where the torque vanishes
+/// by symmetry. The Hessian is dense, which suits chains up to a few
+/// hundred cells.
+pub fn classify_stability(
+    system: &MicromagneticSystem,
+    count: usize,
+    soft_threshold: f64,
+) -> StabilityReport {
+    let hessian = tangent_hessian(system);
+    let n = hessian.matrix.nrows();
+    let materials = system.get_materials();
+    let volume = system.cell_volume();
+    let scale: Vec<f64> = (0..n)
+        .map(|r| {
+            let moment = materials[hessian.cells[r / 2]].saturation_magnetization * volume;
+            1.0 / moment.sqrt()
+        })
+        .collect();
+    let stiffness = Array2::from_shape_fn((n, n), |(r, s)| {
+        scale[r] * hessian.matrix[[r, s]] * scale[s]
+    });
+    let (values, vectors) = symmetric_eigen(&stiffness);
+
+    let unstable_modes = values.iter().filter(|&&v| v < -soft_threshold).count();
+    let stability = if unstable_modes > 0 {
+        Stability::Saddle { unstable_modes }
+    } else if values.first().is_some_and(|&v| v <= soft_threshold) {
+        Stability::Marginal
+    } else {
+        Stability::Minimum
+    };
+
+    let soft_modes = values
+        .iter()
+        .enumerate()
+        .take_while(|(_, &value)| value <= soft_threshold)
+        .map(|(j, &eigenvalue)| {
+            let mut displacement = vec![[0.0; 3]; system.size()];
+            for (k, &i) in hessian.cells.iter().enumerate() {
+                for c in 0..3 {
+                    displacement[i][c] =
+                        scale[2 * k] * vectors[[2 * k, j]] * hessian.frames[k][0][c]
+                            + scale[2 * k + 1] * vectors[[2 * k + 1, j]] * hessian.frames[k][1][c];
+                }
+            }
+            let largest = displacement
+                .iter()
+                .map(|d| d.iter().map(|c| c * c).sum::<f64>().sqrt())
+                .fold(0.0f64, f64::max);
+            if largest > 0.0 {
+                for d in &mut displacement {
+                    *d = d.map(|c| c / largest);
+                }
+            }
+            SoftMode {
+                eigenvalue,
+                displacement,
+            }
+        })
+        .collect();
+
+    StabilityReport {
+        stability,
+        lowest_eigenvalues: values.into_iter().take(count).collect(),
+        soft_modes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::{SATURATION_MAGNETIZATION, UNIAXIAL_ANISOTROPY_CONSTANT};
+    use ndarray::array;
+
+    #[test]
+    /// Test a minimum, a saddle and the free rotation of an isotropic chain
+    fn test_classify_stability() {
+        let size = 6;
+        let mut system = MicromagneticSystem::new(size);
+        for i in 0..size {
+            system.set_magnetization(i, array![1.0, 0.0, 0.0]);
+        }
+        system.set_applied_field([0.1, 0.0, 0.0]);
+        let report = classify_stability(&system, 3, DEFAULT_SOFT_THRESHOLD);
+        assert_eq!(report.stability, Stability::Minimum);
+        assert_eq!(report.lowest_eigenvalues.len(), 3);
+        assert!(report.soft_modes.is_empty());
+        // The uniform rotation is stiffened by the field and the anisotropy field, twice
+        let expected = 0.1 + 2.0 * UNIAXIAL_ANISOTROPY_CONSTANT / SATURATION_MAGNETIZATION;
+        assert!((report.lowest_eigenvalues[0] - expected).abs() < 1e-6 * expected);
+        assert!((report.lowest_eigenvalues[1] - expected).abs() < 1e-6 * expected);
+
+        // Against a field stronger than the anisotropy field the uniform
+        // rotation lowers the energy, the exchange modes stay stable
+        system.set_applied_field([-0.1, 0.0, 0.0]);
+        let report = classify_stability(&system, 3, DEFAULT_SOFT_THRESHOLD);
+        assert_eq!(report.stability, Stability::Saddle { unstable_modes: 2 });
+        assert!(report.lowest_eigenvalues[0] < 0.0);
+        assert_eq!(report.soft_modes.len(), 2);
+        assert!(report.stability.to_string().starts_with("saddle"));
+
+        let isotropic = Material {
+            anisotropy_constant: 0.0,
+            ..Material::default()
+        };
+        for i in 0..size {
+            system.set_material(i, isotropic);
+        }
+        system.set_applied_field([0.0; 3]);
+        let report = classify_stability(&system, 3, DEFAULT_SOFT_THRESHOLD);
+        assert_eq!(report.stability, Stability::Marginal);
+        assert_eq!(report.soft_modes.len(), 2);
+        // The free rotation turns all cells together
+        for displacement in &report.soft_modes[0].displacement {
+            let length = displacement.iter().map(|c| c * c).sum::<f64>().sqrt();
+            assert!((length - 1.0).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::domains::wall_position;
+use crate::magnetic_moments::MicromagneticSystem;
+use std::time::{Duration, Instant};
+
+///# Stop Condition
+/// Decides after every step of a minimization or a dynamics run whether
+/// the run should end. `step` counts the steps so far, `time` is the
+/// simulated time in s, zero during minimizations. Conditions may keep
+/// state between calls, e.g. the previous energy, and are called once
+/// before the first step. Closures with the same arguments returning a
+/// bool are conditions too.
+pub trait StopCondition {
+    fn should_stop(&mut self, step: usize, time: f64, system: &MicromagneticSystem) -> bool;
+
+    ///# And
+    /// Stop when both conditions hold.
+    fn and<C: StopCondition + 'static>(self, other: C) -> All
+    where
+        Self: Sized + 'static,
+    {
+        All(vec![Box::new(self), Box::new(other)])
+    }
+
+    ///# Or
+    /// Stop when either condition holds.
+    fn or<C: StopCondition + 'static>(self, other: C) -> Any
+    where
+        Self: Sized + 'static,
+    {
+        Any(vec![Box::new(self), Box::new(other)])
+    }
+}
+
+impl<F: FnMut(usize, f64, &MicromagneticSystem) -> bool> StopCondition for F {
+    fn should_stop(&mut self, step: usize, time: f64, system: &MicromagneticSystem) -> bool {
+        self(step, time, system)
+    }
+}
+
+///# All
+/// Stops when every condition holds. All conditions see every step, so
+/// stateful ones stay up to date while another one is still false.
+pub struct All(pub Vec<Box<dyn StopCondition>>);
+
+impl StopCondition for All {
+    fn should_stop(&mut self, step: usize, time: f64, system: &MicromagneticSystem) -> bool {
+        let mut stop = true;
+        for condition in &mut self.0 {
+            stop &= condition.should_stop(step, time, system);
+        }
+        stop
+    }
+}
+
+///# Any
+/// Stops when at least one condition holds, every condition sees every step.
+pub struct Any(pub Vec<Box<dyn StopCondition>>);
+
+impl StopCondition for Any {
+    fn should_stop(&mut self, step: usize, time: f64, system: &MicromagneticSystem) -> bool {
+        let mut stop = false;
+        for condition in &mut self.0 {
+            stop |= condition.should_stop(step, time, system);
+        }
+        stop
+    }
+}
+
+///# Torque Below
+/// Maximum torque |m x H_eff| in A/m below the tolerance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TorqueBelow(pub f64);
+
+impl StopCondition for TorqueBelow {
+    fn should_stop(&mut self, _step: usize, _time: f64, system: &MicromagneticSystem) -> bool {
+        system.compute_max_torque() < self.0
+    }
+}
+
+///# Energy Change Below
+/// Change of the total energy in J since the previous step below the
+/// tolerance. Never holds before the first step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnergyChangeBelow {
+    pub tolerance: f64,
+    previous: Option<f64>,
+}
+
+impl EnergyChangeBelow {
+    pub fn new(tolerance: f64) -> Self {
+        Self {
+            tolerance,
+            previous: None,
+        }
+    }
+}
+
+impl StopCondition for EnergyChangeBelow {
+    fn should_stop(&mut self, _step: usize, _time: f64, system: &MicromagneticSystem) -> bool {
+        let energy = system.compute_energies().total();
+        let previous = self.previous.replace(energy);
+        previous.is_some_and(|previous| (energy - previous).abs() < self.tolerance)
+    }
+}
+
+///# Wall Reaches
+/// The first domain wall along the axis has reached the laboratory position
+/// in m, coming from either side. The side is that of the first state with
+/// a wall, the condition holds once the wall is at or beyond the position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WallReaches {
+    pub position: f64,
+    pub easy_axis: [f64; 3],
+    side: Option<f64>,
+}
+
+impl WallReaches {
+    pub fn new(position: f64, easy_axis: [f64; 3]) -> Self {
+        Self {
+            position,
+            easy_axis,
+            side: None,
+        }
+    }
+}
+
+impl StopCondition for WallReaches {
+    fn should_stop(&mut self, _step: usize, _time: f64, system: &MicromagneticSystem) -> bool {
+        let Some(wall) = wall_position(
+            &system.get_magnetizations(),
+            &self.easy_axis,
+            system.get_cell_size(),
+        ) else {
+            return false;
+        };
+        let offset = wall + system.frame_offset() - self.position;
+        let side = *self.side.get_or_insert(offset.signum());
+        offset == 0.0 || offset.signum() != side
+    }
+}
+
+///# Maximum Steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxSteps(pub usize);
+
+impl StopCondition for MaxSteps {
+    fn should_stop(&mut self, step: usize, _time: f64, _system: &MicromagneticSystem) -> bool {
+        step >= self.0
+    }
+}
+
+///# Wall Time
+/// The wall-clock budget is used up. The clock starts at the first call,
+/// so a condition can be created before the system is set up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WallTime {
+    pub budget: Duration,
+    start: Option<Instant>,
+}
+
+impl WallTime {
+    ///# Wall Time Budget in Seconds
+    /// Negative budgets are used up at once, too long ones never.
+    pub fn from_secs(seconds: f64) -> Self {
+        Self {
+            budget: Duration::try_from_secs_f64(seconds.max(0.0)).unwrap_or(Duration::MAX),
+            start: None,
+        }
+    }
+
+    ///# Elapsed Wall Time
+    /// Time since the first call, zero before it.
+    pub fn elapsed(&self) -> Duration {
+        self.start.map_or(Duration::ZERO, |start| start.elapsed())
+    }
+}
+
+impl StopCondition for WallTime {
+    fn should_stop(&mut self, _step: usize, _time: f64, _system: &MicromagneticSystem) -> bool {
+        self.start.get_or_insert_with(Instant::now).elapsed() >= self.budget
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::{DynamicsRun, TimeDependentField};
+    use crate::magnetic_moments::MinimizationOutcome;
+    use crate::SPATIAL_DISCRETION_STEP;
+    use ndarray::{array, Array1};
+
+    #[test]
+    /// Test combined conditions on a minimization and a dynamics run
+    fn test_stop_conditions() {
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.3, 0.0]; 10]);
+        system.set_applied_field([0.0; 3]);
+        let mut condition = TorqueBelow(1e3).and(EnergyChangeBelow::new(1e-26));
+        let outcome = system.minimize_energy_until_condition(&mut condition);
+        let MinimizationOutcome::Stopped { iterations } = outcome else {
+            panic!("expected the condition to stop the run, got {:?}", outcome);
+        };
+        assert!(iterations > 0);
+        assert!(system.compute_max_torque() < 1e3);
+
+        // Either the step limit or a closure on the state ends the dynamics
+        let mut condition = MaxSteps(25).or(|_: usize, _: f64, system: &MicromagneticSystem| {
+            system.average_magnetization()[2] > 2.0
+        });
+        let run = DynamicsRun::new(TimeDependentField::Constant([0.0, 0.0, 0.5]));
+        let time = run.run_until(&mut system, 1e-11, &mut condition, |_, _| {});
+        assert!((time - 25.0 * run.time_step).abs() < 1e-20);
+
+        let profile = |center: f64| {
+            MicromagneticSystem::from_magnetizations(
+                (0..20)
+                    .map(|i| {
+                        let phi = 2.0 * ((i as f64 - center) / 2.0).tanh().atan();
+                        array![phi.sin(), phi.cos(), 0.0]
+                    })
+                    .collect::<Vec<Array1<f64>>>(),
+            )
+        };
+        let mut reaches = WallReaches::new(12.0 * SPATIAL_DISCRETION_STEP, [1.0, 0.0, 0.0]);
+        assert!(!reaches.should_stop(0, 0.0, &MicromagneticSystem::new(0)));
+        assert!(!reaches.should_stop(0, 0.0, &profile(8.5)));
+        assert!(!reaches.should_stop(1, 0.0, &profile(11.5)));
+        assert!(reaches.should_stop(2, 0.0, &profile(12.5)));
+
+        let mut unlimited = WallTime::from_secs(1e6);
+        assert_eq!(unlimited.elapsed(), Duration::ZERO);
+        assert!(!unlimited.should_stop(0, 0.0, &system));
+        let mut exhausted = WallTime::from_secs(0.0);
+        let outcome = system.minimize_energy_until_condition(&mut exhausted);
+        assert_eq!(outcome, MinimizationOutcome::Stopped { iterations: 0 });
+    }
+}
@@ -0,0 +1,106 @@
+use crate::dipolar::dipolar_field_at_point;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::parallel::map_cells;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+///# Line Points
+/// `count` equally spaced points from `start` to `end`, both included.
+pub fn line_points(start: [f64; 3], end: [f64; 3], count: usize) -> Vec<[f64; 3]> {
+    (0..count)
+        .map(|n| {
+            let fraction = if count > 1 {
+                n as f64 / (count - 1) as f64
+            } else {
+                0.0
+            };
+            [0, 1, 2].map(|k| start[k] + fraction * (end[k] - start[k]))
+        })
+        .collect()
+}
+
+///# Plane Points
+/// Grid of `counts.0` by `counts.1` points spanning the parallelogram from
+/// `origin` along the edges `first_edge` and `second_edge`, with the
+/// first index running fastest.
+pub fn plane_points(
+    origin: [f64; 3],
+    first_edge: [f64; 3],
+    second_edge: [f64; 3],
+    counts: (usize, usize),
+) -> Vec<[f64; 3]> {
+    let mut points = Vec::with_capacity(counts.0 * counts.1);
+    for second in line_points([0.0; 3], second_edge, counts.1) {
+        for first in line_points([0.0; 3], first_edge, counts.0) {
+            points.push([0, 1, 2].map(|k| origin[k] + first[k] + second[k]));
+        }
+    }
+    points
+}
+
+///# Compute Stray Field
+/// Field H in A/m of the magnetization at every observation point outside
+/// the sample, evaluated in parallel. The cells are treated as point
+/// dipoles, which is accurate a few cell sizes away from the sample.
+pub fn compute_stray_field(system: &MicromagneticSystem, points: &[[f64; 3]]) -> Vec<[f64; 3]> {
+    let magnetizations = system.get_magnetizations();
+    let saturation_magnetizations = system.get_saturation_magnetizations();
+    let grid = system.get_grid();
+    map_cells(points.len(), |n| {
+        dipolar_field_at_point(
+            &grid,
+            system.get_cell_size(),
+            points[n],
+            &magnetizations,
+            &saturation_magnetizations,
+        )
+    })
+}
+
+///# Write Stray Field
+/// Tab separated columns x, y, z in m and Hx, Hy, Hz in A/m.
+pub fn write_stray_field(path: &Path, points: &[[f64; 3]], fields: &[[f64; 3]]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "# x (m)\ty (m)\tz (m)\tHx (A/m)\tHy (A/m)\tHz (A/m)"
+    )?;
+    for (point, field) in points.iter().zip(fields) {
+        writeln!(
+            writer,
+            "{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}",
+            point[0], point[1], point[2], field[0], field[1], field[2]
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SPATIAL_DISCRETION_STEP;
+    use ndarray::array;
+
+    #[test]
+    /// Test the observation grids and the far field of a uniformly magnetized chain
+    fn test_stray_field() {
+        let line = line_points([0.0; 3], [1.0, 2.0, 0.0], 3);
+        assert_eq!(line, vec![[0.0; 3], [0.5, 1.0, 0.0], [1.0, 2.0, 0.0]]);
+        let plane = plane_points([0.0, 0.0, 1.0], [2.0, 0.0, 0.0], [0.0, 1.0, 0.0], (3, 2));
+        assert_eq!(plane.len(), 6);
+        assert_eq!(plane[1], [1.0, 0.0, 1.0]);
+        assert_eq!(plane[3], [0.0, 1.0, 1.0]);
+
+        // Far above a chain along z the field is that of one dipole of N cells
+        let cells = 4;
+        let system = MicromagneticSystem::from_magnetizations(vec![array![0.0, 0.0, 1.0]; cells]);
+        let center = 0.5 * (cells - 1) as f64 * SPATIAL_DISCRETION_STEP;
+        let height = 200.0 * SPATIAL_DISCRETION_STEP;
+        let fields = compute_stray_field(&system, &[[center, 0.0, height]]);
+        let moment = cells as f64 * system.get_saturation_magnetizations()[0] * crate::CELL_VOLUME;
+        let expected = 2.0 * moment / (4.0 * std::f64::consts::PI * height.powi(3));
+        assert!((fields[0][2] - expected).abs() < 1e-3 * expected);
+        assert!(fields[0][0].abs() < 1e-6 * expected);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::config::SimulationConfig;
+use crate::domains::analyze_domains;
+use crate::hooks::RunSummary;
+use crate::magnetic_moments::{Energies, MicromagneticSystem};
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Version of the crate and git commit of the sources it was built from
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_COMMIT: &str = env!("ENERGY_RELAXATION_GIT_COMMIT");
+
+///# Final Observables
+/// State of the system at the end of a run
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FinalObservables {
+    // Energy contributions in J
+    pub energies: Energies,
+    pub total_energy: f64,
+    pub average_magnetization: [f64; 3],
+    // Maximum torque |m x H_eff| in A/m
+    pub max_torque: f64,
+    // Number of domains along the easy axis of the material
+    pub domain_count: usize,
+}
+
+impl FinalObservables {
+    ///# Observables of a System
+    pub fn of(system: &MicromagneticSystem) -> Self {
+        let energies = system.compute_energies();
+        Self {
+            energies,
+            total_energy: energies.total(),
+            average_magnetization: system.average_magnetization(),
+            max_torque: system.compute_max_torque(),
+            domain_count: analyze_domains(&system.get_magnetizations(), &system.easy_axis())
+                .count(),
+        }
+    }
+}
+
+///# Run Record
+/// Machine-readable summary.json of one run: the hook summary with the
+/// version, timings, resolved configuration and final observables, so the
+/// runs of a sweep campaign can be aggregated by a script.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunRecord {
+    #[serde(flatten)]
+    pub summary: RunSummary,
+    pub version: String,
+    pub git_commit: String,
+    // Start of the run in s since the Unix epoch
+    pub started_at: f64,
+    // Wall-clock duration of the run in s
+    pub wall_time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_state: Option<FinalObservables>,
+    // The configuration with relative paths resolved
+    pub parameters: SimulationConfig,
+}
+
+impl RunRecord {
+    ///# New Run Record
+    /// Record of a run that started at `started` and ends now.
+    pub fn new(summary: RunSummary, config: &SimulationConfig, started: SystemTime) -> Self {
+        let since_epoch = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .map_or(0.0, |duration| duration.as_secs_f64())
+        };
+        Self {
+            summary,
+            version: VERSION.to_string(),
+            git_commit: GIT_COMMIT.to_string(),
+            started_at: since_epoch(started),
+            wall_time: started
+                .elapsed()
+                .map_or(0.0, |duration| duration.as_secs_f64()),
+            final_state: None,
+            parameters: config.clone(),
+        }
+    }
+
+    ///# With Final State
+    pub fn with_final_state(self, system: &MicromagneticSystem) -> Self {
+        Self {
+            final_state: Some(FinalObservables::of(system)),
+            ..self
+        }
+    }
+
+    ///# Write Record
+    /// Write the record as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::magnetic_moments::MinimizationOutcome;
+    use crate::material::Material;
+    use ndarray::array;
+
+    #[test]
+    /// Test the fields of the summary of a relaxation
+    fn test_run_record() {
+        let started = SystemTime::now();
+        let mut system = MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]; 4]);
+        system.set_applied_field([0.0; 3]);
+        let outcome = MinimizationOutcome::Converged { iterations: 7 };
+        let config = SimulationConfig::default();
+        let record = RunRecord::new(
+            RunSummary::relaxed("relax", &system, outcome),
+            &config,
+            started,
+        )
+        .with_final_state(&system);
+        assert!(record.wall_time >= 0.0 && record.started_at > 1.0e9);
+
+        let path = std::env::temp_dir().join("energy_relaxation_summary_test.json");
+        record.write(&path).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(json["run"], "relax");
+        assert_eq!(json["converged"], true);
+        assert_eq!(json["iterations"], 7);
+        assert_eq!(json["version"], VERSION);
+        assert!(!json["git_commit"].as_str().unwrap().is_empty());
+        assert_eq!(
+            json["parameters"]["number_of_cells"],
+            config.number_of_cells
+        );
+        let state = &json["final_state"];
+        assert_eq!(state["domain_count"], 1);
+        assert_eq!(state["average_magnetization"][0], 1.0);
+        let anisotropy = -crate::UNIAXIAL_ANISOTROPY_CONSTANT * crate::CELL_VOLUME * 4.0;
+        let stored = state["energies"]["anisotropy"].as_f64().unwrap();
+        assert!((stored - anisotropy).abs() < 1e-12 * anisotropy.abs());
+    }
+
+    #[test]
+    /// Test that the domains of a perpendicular film are counted along the
+    /// easy axis of its material
+    fn test_perpendicular_domain_count() {
+        let mut film = MicromagneticSystem::new(4);
+        for cell in 0..4 {
+            film.set_material(
+                cell,
+                Material {
+                    easy_axis: [0.0, 0.0, 1.0],
+                    ..Material::default()
+                },
+            );
+            let mz = if cell < 2 { 0.9 } else { -0.9 };
+            film.set_magnetization(cell, array![0.1, 0.0, mz]);
+        }
+        assert_eq!(film.easy_axis(), [0.0, 0.0, 1.0]);
+        assert_eq!(FinalObservables::of(&film).domain_count, 2);
+    }
+}
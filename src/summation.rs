@@ -0,0 +1,75 @@
+use std::iter::Sum;
+use std::ops::AddAssign;
+
+///# Compensated Sum
+/// Neumaier's variant of Kahan summation. The rounding error of every
+/// addition is carried along, so the result does not depend on the number
+/// of terms or on their mix of magnitudes beyond the last few ulps.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CompensatedSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl CompensatedSum {
+    ///# Add
+    pub fn add(&mut self, value: f64) {
+        let sum = self.sum + value;
+        // The low-order bits lost by the rounding in this addition
+        if self.sum.abs() >= value.abs() {
+            self.compensation += (self.sum - sum) + value;
+        } else {
+            self.compensation += (value - sum) + self.sum;
+        }
+        self.sum = sum;
+    }
+
+    ///# Merge
+    /// Add another partial sum, carrying its compensation along.
+    pub fn merge(&mut self, other: &Self) {
+        self.add(other.sum);
+        self.compensation += other.compensation;
+    }
+
+    ///# Value
+    pub fn value(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+impl AddAssign<f64> for CompensatedSum {
+    fn add_assign(&mut self, value: f64) {
+        self.add(value);
+    }
+}
+
+impl Sum<f64> for CompensatedSum {
+    fn sum<I: Iterator<Item = f64>>(iter: I) -> Self {
+        let mut sum = Self::default();
+        for value in iter {
+            sum.add(value);
+        }
+        sum
+    }
+}
+
+///# Compensated Sum of Values
+pub fn compensated_sum<I: IntoIterator<Item = f64>>(values: I) -> f64 {
+    values.into_iter().sum::<CompensatedSum>().value()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test that small terms are not lost next to large ones
+    fn test_compensated_sum() {
+        let values = [1.0, 1e100, 1.0, -1e100];
+        assert_eq!(values.iter().sum::<f64>(), 0.0);
+        assert_eq!(compensated_sum(values), 2.0);
+
+        let many = std::iter::once(1.0).chain(std::iter::repeat_n(1e-16, 1_000_000));
+        assert!((compensated_sum(many) - (1.0 + 1e-10)).abs() < 1e-15);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+///# Table Writer
+/// Writes a tab separated `table.txt` with the columns in the order and
+/// with the header names that mumax3 uses, so existing plotting scripts
+/// can read it unchanged.
+pub struct TableWriter<W: Write> {
+    writer: W,
+}
+
+impl TableWriter<BufWriter<File>> {
+    ///# Create Table File
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> TableWriter<W> {
+    ///# New Table Writer
+    /// Writes the header line to the given writer.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(
+            writer,
+            "# t (s)\tmx ()\tmy ()\tmz ()\tE_total (J)\tE_exch (J)\tE_anis (J)\tE_Zeeman (J)\tE_demag (J)\tE_DMI (J)"
+        )?;
+        Ok(Self { writer })
+    }
+
+    ///# Write Row
+    /// Appends the average magnetization and the energies of the system at time `t`.
+    pub fn write_row(&mut self, t: f64, system: &MicromagneticSystem) -> io::Result<()> {
+        let m = system.average_magnetization();
+        let energies = system.compute_energies();
+        writeln!(
+            self.writer,
+            "{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}\t{:e}",
+            t,
+            m[0],
+            m[1],
+            m[2],
+            energies.total(),
+            energies.exchange,
+            energies.anisotropy,
+            energies.zeeman,
+            energies.dipolar,
+            energies.dmi
+        )
+    }
+
+    ///# Flush
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    ///# Into Inner
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the header and the column count of the table
+    fn test_table_rows() {
+        let system = MicromagneticSystem::new(5);
+        let mut table = TableWriter::new(Vec::new()).unwrap();
+        table.write_row(0.0, &system).unwrap();
+        table.write_row(1e-13, &system).unwrap();
+        let text = String::from_utf8(table.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("# t (s)\tmx ()\tmy ()\tmz ()\tE_total (J)"));
+        for line in &lines[1..] {
+            let values: Vec<f64> = line.split('\t').map(|v| v.parse().unwrap()).collect();
+            assert_eq!(values.len(), 10);
+        }
+        assert!(lines[2].starts_with("1e-13\t"));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::time_series::TimeSeriesColumn;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+///# Well
+/// One of the two minima a superparamagnet hops between
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Well {
+    // The signal is above +threshold
+    Up,
+    // The signal is below -threshold
+    Down,
+}
+
+///# Dwell Statistics
+/// Complete stays in one well. The first and the last stay of a trace are
+/// cut off by its ends and left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DwellStatistics {
+    // Dwell times in s in the order they occurred
+    pub dwell_times: Vec<f64>,
+    // Mean and standard deviation in s, NaN without complete stays
+    pub mean: f64,
+    pub standard_deviation: f64,
+}
+
+impl DwellStatistics {
+    fn new(dwell_times: Vec<f64>) -> Self {
+        let n = dwell_times.len() as f64;
+        let mean = dwell_times.iter().sum::<f64>() / n;
+        let variance = dwell_times.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        Self {
+            standard_deviation: if dwell_times.len() > 1 {
+                variance.sqrt()
+            } else {
+                f64::NAN
+            },
+            mean,
+            dwell_times,
+        }
+    }
+
+    ///# Histogram
+    /// Number of dwell times in `bins` equal bins from zero to the longest
+    /// dwell time, as (left edge in s, count).
+    pub fn histogram(&self, bins: usize) -> Vec<(f64, usize)> {
+        let longest = self.dwell_times.iter().copied().fold(0.0, f64::max);
+        if bins == 0 || longest == 0.0 {
+            return Vec::new();
+        }
+        let width = longest / bins as f64;
+        let mut counts = vec![0; bins];
+        for &time in &self.dwell_times {
+            counts[((time / width) as usize).min(bins - 1)] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(bin, count)| (bin as f64 * width, count))
+            .collect()
+    }
+}
+
+///# Telegraph Analysis
+/// Random telegraph signal of a superparamagnet, e.g. <mx> of a thermal
+/// dynamics run along the easy axis. The signal enters a well when it
+/// crosses +threshold or -threshold, so fluctuations inside a well and
+/// failed attempts that turn back before the other threshold do not count
+/// as switches. For thermally activated switching the dwell times are
+/// exponentially distributed with the Neel-Arrhenius mean
+/// tau = tau_0 exp(K V / (k_B T)), and the standard deviation is close to
+/// the mean.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelegraphAnalysis {
+    // Times in s at which the signal reached the other well, interpolated
+    // between the samples to the threshold crossing
+    pub switching_times: Vec<(f64, Well)>,
+    pub up: DwellStatistics,
+    pub down: DwellStatistics,
+    // Mean time in s between two switches over both wells, NaN without complete stays
+    pub mean_switching_time: f64,
+}
+
+impl TelegraphAnalysis {
+    ///# Analyze
+    /// Detect the switches of the sampled signal with the hysteresis
+    /// threshold, which must be within 0..1 of the well values +-1.
+    pub fn analyze(times: &[f64], values: &[f64], threshold: f64) -> Result<Self, Box<dyn Error>> {
+        if times.len() != values.len() {
+            return Err("The trace needs one value per time".into());
+        }
+        if threshold.is_nan() || threshold <= 0.0 {
+            return Err("The threshold must be positive".into());
+        }
+        if times.windows(2).any(|pair| pair[1] <= pair[0]) {
+            return Err("The times must increase".into());
+        }
+
+        let mut well = None;
+        let mut switching_times = Vec::new();
+        for (k, (&time, &value)) in times.iter().zip(values).enumerate() {
+            let entered = if value > threshold {
+                Well::Up
+            } else if value < -threshold {
+                Well::Down
+            } else {
+                continue;
+            };
+            match well {
+                None => well = Some(entered),
+                Some(current) if current != entered => {
+                    well = Some(entered);
+                    // The previous sample is still short of the new threshold
+                    let target = if entered == Well::Up {
+                        threshold
+                    } else {
+                        -threshold
+                    };
+                    let (t0, v0) = (times[k - 1], values[k - 1]);
+                    let crossing = t0 + (time - t0) * (target - v0) / (value - v0);
+                    switching_times.push((crossing, entered));
+                }
+                Some(_) => {}
+            }
+        }
+
+        // Stays between consecutive switches, named after the well left
+        let mut up = Vec::new();
+        let mut down = Vec::new();
+        for pair in switching_times.windows(2) {
+            let dwell = pair[1].0 - pair[0].0;
+            match pair[0].1 {
+                Well::Up => up.push(dwell),
+                Well::Down => down.push(dwell),
+            }
+        }
+        let stays = up.len() + down.len();
+        let mean_switching_time = match switching_times.as_slice() {
+            [first, .., last] => (last.0 - first.0) / stays as f64,
+            _ => f64::NAN,
+        };
+        Ok(Self {
+            switching_times,
+            up: DwellStatistics::new(up),
+            down: DwellStatistics::new(down),
+            mean_switching_time,
+        })
+    }
+
+    ///# Switching Rate
+    /// Switches per second, 1 / mean switching time.
+    pub fn switching_rate(&self) -> f64 {
+        1.0 / self.mean_switching_time
+    }
+}
+
+///# Read Time Series Column
+/// Times and values of one column of a time series written by the
+/// dynamics, found by its header.
+pub fn read_time_series_column(
+    path: &Path,
+    column: TimeSeriesColumn,
+) -> Result<(Vec<f64>, Vec<f64>), Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text.lines();
+    let header = lines
+        .next()
+        .and_then(|line| line.strip_prefix("# "))
+        .ok_or_else(|| format!("{} has no time series header", path.display()))?;
+    let index = header
+        .split('\t')
+        .position(|name| name == column.header())
+        .ok_or_else(|| format!("{} has no {} column", path.display(), column.header()))?;
+    let mut times = Vec::new();
+    let mut values = Vec::new();
+    for (number, line) in lines.enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let parse = |field: usize| {
+            fields
+                .get(field)
+                .and_then(|value| value.parse::<f64>().ok())
+        };
+        match (parse(0), parse(index)) {
+            (Some(time), Some(value)) => {
+                times.push(time);
+                values.push(value);
+            }
+            _ => {
+                return Err(
+                    format!("line {} of {} is malformed", number + 2, path.display()).into(),
+                )
+            }
+        }
+    }
+    Ok((times, values))
+}
+
+///# Write Dwell Times
+/// Tab-separated well and dwell time of every complete stay, in the order
+/// of the switches.
+pub fn write_dwell_times(path: &Path, analysis: &TelegraphAnalysis) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "# switch (s)\twell\tdwell (s)")?;
+    for pair in analysis.switching_times.windows(2) {
+        let well = match pair[0].1 {
+            Well::Up => "up",
+            Well::Down => "down",
+        };
+        writeln!(
+            writer,
+            "{:e}\t{}\t{:e}",
+            pair[0].0,
+            well,
+            pair[1].0 - pair[0].0
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the switches and dwell times of a noisy telegraph signal
+    fn test_telegraph_analysis() {
+        // Up, down with a failed attempt to return at 5 ns, up with noise
+        // inside the well and down until the end
+        let values = [
+            0.9, 0.8, 0.95, -0.9, -0.7, 0.3, -0.85, -0.9, 0.9, 0.1, 0.8, -0.9, -0.95,
+        ];
+        let times: Vec<f64> = (0..values.len()).map(|k| k as f64 * 1e-9).collect();
+        let analysis = TelegraphAnalysis::analyze(&times, &values, 0.5).unwrap();
+        let switches: Vec<Well> = analysis.switching_times.iter().map(|s| s.1).collect();
+        assert_eq!(switches, [Well::Down, Well::Up, Well::Down]);
+        // Crossing of -0.5 between 0.95 at 2 ns and -0.9 at 3 ns
+        assert!((analysis.switching_times[0].0 - 2.7837837838e-9).abs() < 1e-18);
+        assert_eq!(analysis.down.dwell_times.len(), 1);
+        assert_eq!(analysis.up.dwell_times.len(), 1);
+        assert!((analysis.down.dwell_times[0] - 4.993993994e-9).abs() < 1e-18);
+        assert!(analysis.up.mean > 0.0 && analysis.up.standard_deviation.is_nan());
+        let total = analysis.switching_times[2].0 - analysis.switching_times[0].0;
+        assert!((analysis.mean_switching_time - total / 2.0).abs() < 1e-24);
+        assert!((analysis.switching_rate() * analysis.mean_switching_time - 1.0).abs() < 1e-12);
+
+        let histogram = analysis.down.histogram(4);
+        assert_eq!(histogram.len(), 4);
+        assert_eq!(histogram[3].1, 1);
+
+        // A signal that never leaves its well has no stays
+        let quiet = TelegraphAnalysis::analyze(&times, &[0.9; 13], 0.5).unwrap();
+        assert!(quiet.switching_times.is_empty() && quiet.mean_switching_time.is_nan());
+        assert!(TelegraphAnalysis::analyze(&times, &values, 0.0).is_err());
+
+        // Round trip through a time series file
+        let path = std::env::temp_dir().join("energy_relaxation_telegraph_test.txt");
+        let mut text = String::from("# t (s)\t<mx> ()\t<my> ()\n");
+        for (t, v) in times.iter().zip(values) {
+            text.push_str(&format!("{:e}\t{:e}\t0e0\n", t, v));
+        }
+        fs::write(&path, text).unwrap();
+        let (read_times, read_values) =
+            read_time_series_column(&path, TimeSeriesColumn::Mx).unwrap();
+        assert!(read_time_series_column(&path, TimeSeriesColumn::Mz).is_err());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read_times, times);
+        assert_eq!(read_values, values);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::material::Material;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::ops::Range;
+
+///# Magnetization Law
+/// Reduced magnetization m(T) = Ms(T) / Ms(0) below the Curie temperature.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum MagnetizationLaw {
+    // m = 1 - (T / Tc)^(3/2), the spin wave result at low temperature
+    #[default]
+    Bloch,
+    // m = (1 - T / Tc)^exponent, about 1/3 for a 3D ferromagnet near Tc
+    Critical {
+        exponent: f64,
+    },
+}
+
+///# Temperature Scaling
+/// Material constants at a temperature, derived from their values at zero
+/// temperature and the reduced magnetization m(T). Following Callen and
+/// Callen the uniaxial anisotropy scales with m^(l (l + 1) / 2) = m^3 for
+/// l = 2, and the exchange stiffness with m^2 in the mean field limit,
+/// as does the DMI, so Ms, K, A and D of a temperature sweep all follow
+/// the same m(T) instead of being set independently. The damping is
+/// unchanged.
+///
+/// ```toml
+/// [temperature_scaling]
+/// curie_temperature = 1388.0
+/// anisotropy_exponent = 3.0
+/// exchange_exponent = 1.8
+/// magnetization = { type = "critical", exponent = 0.34 }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemperatureScaling {
+    // Curie temperature in K
+    pub curie_temperature: f64,
+    #[serde(default)]
+    pub magnetization: MagnetizationLaw,
+    // K(T) / K(0) = m(T)^anisotropy_exponent
+    #[serde(default = "default_anisotropy_exponent")]
+    pub anisotropy_exponent: f64,
+    // A(T) / A(0) = m(T)^exchange_exponent
+    #[serde(default = "default_exchange_exponent")]
+    pub exchange_exponent: f64,
+}
+
+fn default_anisotropy_exponent() -> f64 {
+    3.0
+}
+
+fn default_exchange_exponent() -> f64 {
+    2.0
+}
+
+impl TemperatureScaling {
+    ///# Scaling with the Curie Temperature
+    /// The Bloch law with the Callen-Callen exponents.
+    pub fn new(curie_temperature: f64) -> Self {
+        Self {
+            curie_temperature,
+            magnetization: MagnetizationLaw::default(),
+            anisotropy_exponent: default_anisotropy_exponent(),
+            exchange_exponent: default_exchange_exponent(),
+        }
+    }
+
+    ///# Reduced Magnetization
+    /// m(T) at the temperature in K, the material is not magnetic at and
+    /// above the Curie temperature.
+    pub fn reduced_magnetization(&self, temperature: f64) -> Result<f64, Box<dyn Error>> {
+        if self.curie_temperature.is_nan() || self.curie_temperature <= 0.0 {
+            return Err("The Curie temperature must be positive".into());
+        }
+        if temperature.is_nan() || temperature < 0.0 {
+            return Err("The temperature must not be negative".into());
+        }
+        if temperature >= self.curie_temperature {
+            return Err(format!(
+                "The temperature {} K is not below the Curie temperature {} K",
+                temperature, self.curie_temperature
+            )
+            .into());
+        }
+        let reduced = temperature / self.curie_temperature;
+        Ok(match self.magnetization {
+            MagnetizationLaw::Bloch => 1.0 - reduced.powf(1.5),
+            MagnetizationLaw::Critical { exponent } => {
+                if exponent.is_nan() || exponent <= 0.0 {
+                    return Err("The critical exponent must be positive".into());
+                }
+                (1.0 - reduced).powf(exponent)
+            }
+        })
+    }
+
+    ///# Material at a Temperature
+    /// The material with its zero temperature constants scaled to the
+    /// temperature in K.
+    pub fn material_at(
+        &self,
+        material: &Material,
+        temperature: f64,
+    ) -> Result<Material, Box<dyn Error>> {
+        let m = self.reduced_magnetization(temperature)?;
+        let exchange = m.powf(self.exchange_exponent);
+        Ok(Material {
+            saturation_magnetization: material.saturation_magnetization * m,
+            anisotropy_constant: material.anisotropy_constant * m.powf(self.anisotropy_exponent),
+            exchange_constant: material.exchange_constant * exchange,
+            second_neighbor_exchange_constant: material.second_neighbor_exchange_constant
+                * exchange,
+            dmi_constant: material.dmi_constant * exchange,
+            ..*material
+        })
+    }
+
+    ///# Apply Temperature Scaling
+    /// Scale the materials of the cells in the range, taken as their zero
+    /// temperature values, to the temperature in K. Vacuum cells are skipped.
+    pub fn apply(
+        &self,
+        system: &mut MicromagneticSystem,
+        cells: Range<usize>,
+        temperature: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        if cells.end > system.size() {
+            return Err(format!(
+                "Temperature scaling over {}..{} is outside the {} cells",
+                cells.start,
+                cells.end,
+                system.size()
+            )
+            .into());
+        }
+        let materials = system.get_materials();
+        for cell in cells {
+            if materials[cell].is_vacuum() {
+                continue;
+            }
+            system.set_material(cell, self.material_at(&materials[cell], temperature)?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Test the Callen-Callen scaling of the material constants
+    fn test_temperature_scaling() {
+        let scaling = TemperatureScaling::new(1000.0);
+        assert_eq!(scaling.reduced_magnetization(0.0).unwrap(), 1.0);
+        let m = scaling.reduced_magnetization(250.0).unwrap();
+        assert!((m - (1.0 - 0.125)).abs() < 1e-12);
+
+        let material = Material::default();
+        let scaled = scaling.material_at(&material, 250.0).unwrap();
+        let ms_ratio = scaled.saturation_magnetization / material.saturation_magnetization;
+        assert!((ms_ratio - m).abs() < 1e-12);
+        // K(T) / K(0) = (Ms(T) / Ms(0))^3 ties the anisotropy to the magnetization
+        let k_ratio = scaled.anisotropy_constant / material.anisotropy_constant;
+        assert!((k_ratio - m.powi(3)).abs() < 1e-12);
+        let a_ratio = scaled.exchange_constant / material.exchange_constant;
+        assert!((a_ratio - m.powi(2)).abs() < 1e-12);
+        assert_eq!(scaled.damping, material.damping);
+        assert_eq!(scaled.easy_axis, material.easy_axis);
+
+        let critical = TemperatureScaling {
+            magnetization: MagnetizationLaw::Critical { exponent: 0.5 },
+            ..scaling
+        };
+        assert!((critical.reduced_magnetization(750.0).unwrap() - 0.5).abs() < 1e-12);
+        assert!(scaling.reduced_magnetization(1000.0).is_err());
+        assert!(scaling.reduced_magnetization(-1.0).is_err());
+
+        let mut system = MicromagneticSystem::new(4);
+        system.set_saturation_magnetization(1, 0.0);
+        scaling.apply(&mut system, 0..4, 250.0).unwrap();
+        let materials = system.get_materials();
+        assert!(materials[1].is_vacuum());
+        assert_eq!(materials[0], scaled);
+        assert!(scaling.apply(&mut system, 0..5, 250.0).is_err());
+    }
+}
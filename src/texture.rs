@@ -0,0 +1,197 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::normal_modes::tangent_frame;
+use crate::roughness::gaussian;
+use ndarray::Array1;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::f64::consts::PI;
+use std::ops::Range;
+
+///# Dispersion Distribution
+/// Distribution of the tilt of the easy axis from its nominal direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DispersionDistribution {
+    // Both tilt components perpendicular to the axis normal with the rms `angle`
+    #[default]
+    Gaussian,
+    // Uniform over the cone of half angle `angle` around the axis
+    Uniform,
+}
+
+///# Texture Dispersion
+/// Random tilt of the easy axis of every cell away from the axis of its
+/// material, modeling the imperfect texture of a polycrystalline film.
+/// The direction of the tilt is uniform around the axis, its angle in
+/// rad follows the distribution, so the cells are independent grains of
+/// one cell each. Misaligned grains lower the coercivity and round the
+/// hysteresis loop at the switching field.
+///
+/// ```toml
+/// [texture_dispersion]
+/// angle = 0.05
+/// distribution = "uniform"
+/// seed = 7
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TextureDispersion {
+    pub angle: f64,
+    #[serde(default)]
+    pub distribution: DispersionDistribution,
+    // Seed of the random tilts, the same seed gives the same film
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl TextureDispersion {
+    ///# Tilted Axes
+    /// Easy axes of `axes.len()` cells, each tilted from its nominal axis.
+    pub fn tilted_axes(&self, axes: &[[f64; 3]]) -> Result<Vec<[f64; 3]>, Box<dyn Error>> {
+        if self.angle.is_nan() || self.angle < 0.0 || self.angle > PI {
+            return Err("The texture dispersion angle must be between 0 and pi".into());
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let tilted = axes
+            .iter()
+            .map(|axis| {
+                let (polar, azimuth) = match self.distribution {
+                    DispersionDistribution::Gaussian => {
+                        let u = self.angle * gaussian(&mut rng);
+                        let v = self.angle * gaussian(&mut rng);
+                        (u.hypot(v), v.atan2(u))
+                    }
+                    DispersionDistribution::Uniform => {
+                        // Uniform in cos(polar) covers the cone with equal density
+                        let cos = 1.0 - rng.random::<f64>() * (1.0 - self.angle.cos());
+                        (cos.clamp(-1.0, 1.0).acos(), 2.0 * PI * rng.random::<f64>())
+                    }
+                };
+                let [first, second] = tangent_frame(&Array1::from_vec(axis.to_vec()));
+                let norm = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt();
+                [0, 1, 2].map(|k| {
+                    polar.cos() * axis[k] / norm
+                        + polar.sin() * (azimuth.cos() * first[k] + azimuth.sin() * second[k])
+                })
+            })
+            .collect();
+        Ok(tilted)
+    }
+
+    ///# Apply Texture Dispersion
+    /// Tilt the easy axes of the cells in the range, vacuum cells are skipped.
+    pub fn apply(
+        &self,
+        system: &mut MicromagneticSystem,
+        cells: Range<usize>,
+    ) -> Result<(), Box<dyn Error>> {
+        if cells.end > system.size() {
+            return Err(format!(
+                "Texture dispersion over {}..{} is outside the {} cells",
+                cells.start,
+                cells.end,
+                system.size()
+            )
+            .into());
+        }
+        let materials = system.get_materials();
+        let axes: Vec<[f64; 3]> = cells
+            .clone()
+            .map(|cell| materials[cell].easy_axis)
+            .collect();
+        let tilted = self.tilted_axes(&axes)?;
+        for (cell, axis) in cells.zip(tilted) {
+            if system.is_vacuum(cell) {
+                continue;
+            }
+            let mut material = materials[cell];
+            material.easy_axis = axis;
+            system.set_material(cell, material);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EASY_AXIS;
+
+    #[test]
+    /// Test the tilt statistics of both distributions
+    fn test_tilted_axes() {
+        let n = 20_000;
+        let axes = vec![EASY_AXIS; n];
+        let angle = 0.1;
+        let gaussian = TextureDispersion {
+            angle,
+            distribution: DispersionDistribution::Gaussian,
+            seed: 5,
+        };
+        let tilted = gaussian.tilted_axes(&axes).unwrap();
+        assert_eq!(tilted, gaussian.tilted_axes(&axes).unwrap());
+        let polar: Vec<f64> = tilted
+            .iter()
+            .map(|a| {
+                assert!((a[0] * a[0] + a[1] * a[1] + a[2] * a[2] - 1.0).abs() < 1e-12);
+                (a[0] * EASY_AXIS[0] + a[1] * EASY_AXIS[1] + a[2] * EASY_AXIS[2]).acos()
+            })
+            .collect();
+        // The polar angle is Rayleigh distributed with a mean square of 2 angle^2
+        let mean_square = polar.iter().map(|p| p * p).sum::<f64>() / n as f64;
+        assert!((mean_square / (2.0 * angle * angle) - 1.0).abs() < 0.05);
+        // No preferred direction of the tilt perpendicular to the axis along x
+        let mean: Vec<f64> = (1..3)
+            .map(|k| tilted.iter().map(|a| a[k]).sum::<f64>() / n as f64)
+            .collect();
+        assert!(mean.iter().all(|m| m.abs() < 5e-3));
+
+        let uniform = TextureDispersion {
+            distribution: DispersionDistribution::Uniform,
+            ..gaussian
+        };
+        let tilted = uniform.tilted_axes(&axes).unwrap();
+        let cos: Vec<f64> = tilted
+            .iter()
+            .map(|a| a[0] * EASY_AXIS[0] + a[1] * EASY_AXIS[1] + a[2] * EASY_AXIS[2])
+            .collect();
+        assert!(cos.iter().all(|&c| c >= angle.cos() - 1e-12));
+        let mean_cos = cos.iter().sum::<f64>() / n as f64;
+        assert!((mean_cos - 0.5 * (1.0 + angle.cos())).abs() < 1e-4);
+
+        let none = TextureDispersion {
+            angle: 0.0,
+            ..gaussian
+        };
+        assert_eq!(
+            none.tilted_axes(&[[0.0, 0.0, 2.0]]).unwrap(),
+            vec![[0.0, 0.0, 1.0]]
+        );
+        let invalid = TextureDispersion {
+            angle: -0.1,
+            ..gaussian
+        };
+        assert!(invalid.tilted_axes(&axes).is_err());
+    }
+
+    #[test]
+    /// Test that only the magnetic cells are tilted
+    fn test_apply_texture_dispersion() {
+        let mut system = MicromagneticSystem::new(10);
+        system.set_saturation_magnetization(3, 0.0);
+        let dispersion = TextureDispersion {
+            angle: 0.2,
+            distribution: DispersionDistribution::Gaussian,
+            seed: 1,
+        };
+        dispersion.apply(&mut system, 0..10).unwrap();
+        let materials = system.get_materials();
+        assert_eq!(materials[3].easy_axis, EASY_AXIS);
+        assert!((0..10)
+            .filter(|&cell| cell != 3)
+            .all(|cell| materials[cell].easy_axis != EASY_AXIS));
+        assert!(dispersion.apply(&mut system, 0..11).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::material::Material;
+use crate::roughness::gaussian;
+use crate::{GILBERT_GYROMAGNETIC_RATIO, PERMEABILITY_OF_FREE_SPACE};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Boltzmann constant in J/K
+const BOLTZMANN_CONSTANT: f64 = 1.380_649e-23;
+
+///# Thermal Field
+/// Brown's fluctuating field of finite-temperature Langevin dynamics. Every
+/// component of every cell is an independent Gaussian field, constant over
+/// one time step, with the standard deviation
+///
+/// sigma = sqrt(2 alpha k_B T mu0 / (gamma Ms V dt))
+///
+/// in T, which balances the Gilbert damping so that the dynamics samples the
+/// Boltzmann distribution of the energy. The noise of a step is drawn from
+/// the seed and the time of the step, so a run with the same seed repeats
+/// exactly. The Heun integrator converges to the Stratonovich solution the
+/// field is meant for, and the fixed time step sets its variance.
+///
+/// ```toml
+/// [thermal_field]
+/// temperature = 300.0
+/// seed = 7
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThermalField {
+    // Temperature of the heat bath in K
+    pub temperature: f64,
+    // Seed of the noise, the same seed gives the same trajectory
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl ThermalField {
+    ///# Validate
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !self.temperature.is_finite() || self.temperature < 0.0 {
+            return Err("The temperature of the thermal field must not be negative".into());
+        }
+        Ok(())
+    }
+
+    ///# Standard Deviation
+    /// Standard deviation in T of every field component of a cell of the
+    /// material and volume over a step of the given length.
+    pub fn standard_deviation(&self, material: &Material, cell_volume: f64, time_step: f64) -> f64 {
+        (2.0 * material.damping
+            * BOLTZMANN_CONSTANT
+            * self.temperature
+            * PERMEABILITY_OF_FREE_SPACE
+            / (GILBERT_GYROMAGNETIC_RATIO
+                * material.saturation_magnetization
+                * cell_volume
+                * time_step))
+            .sqrt()
+    }
+
+    ///# Thermal Fields
+    /// Field in T on every cell for the step of the given length that
+    /// starts at `time`, zero in vacuum.
+    pub fn fields(&self, system: &MicromagneticSystem, time: f64, time_step: f64) -> Vec<[f64; 3]> {
+        // Distinct seeds give independent streams, the golden ratio spreads
+        // the bits of neighboring times
+        let key = time.to_bits().wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let mut rng = StdRng::seed_from_u64(self.seed ^ key);
+        let materials = system.get_materials();
+        let cell_volume = system.cell_volume();
+        (0..system.size())
+            .map(|i| {
+                let noise = [(); 3].map(|_| gaussian(&mut rng));
+                if system.is_vacuum(i) || self.temperature == 0.0 {
+                    return [0.0; 3];
+                }
+                let sigma = self.standard_deviation(&materials[i], cell_volume, time_step);
+                noise.map(|x| sigma * x)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::{DynamicsRun, Integrator, TimeDependentField};
+
+    #[test]
+    /// Test the reproducible noise and the Langevin function of a
+    /// paramagnetic macrospin in thermal equilibrium
+    fn test_thermal_field() {
+        let thermal = ThermalField {
+            temperature: 300.0,
+            seed: 7,
+        };
+        let mut system = MicromagneticSystem::new(1);
+        let mut material = system.get_materials()[0];
+        material.anisotropy_constant = 0.0;
+        material.damping = 1.0;
+        system.set_material(0, material);
+        assert_eq!(
+            thermal.fields(&system, 1e-13, 1e-13),
+            thermal.fields(&system, 1e-13, 1e-13)
+        );
+        assert_ne!(
+            thermal.fields(&system, 1e-13, 1e-13),
+            thermal.fields(&system, 2e-13, 1e-13)
+        );
+        assert_ne!(
+            thermal.fields(&system, 1e-13, 1e-13),
+            ThermalField { seed: 8, ..thermal }.fields(&system, 1e-13, 1e-13)
+        );
+        assert!(ThermalField {
+            temperature: -1.0,
+            seed: 0
+        }
+        .validate()
+        .is_err());
+
+        // <mz> = coth(x) - 1 / x with x = Ms V B / (k_B T)
+        let field = 2.0;
+        let run = DynamicsRun {
+            time_step: 1e-13,
+            integrator: Integrator::Heun,
+            thermal_field: Some(thermal),
+            ..DynamicsRun::new(TimeDependentField::Constant([0.0, 0.0, field]))
+        };
+        let (mut sum, mut samples) = (0.0, 0);
+        run.run(&mut system, 1e-8, |time, system| {
+            if time > 1e-10 {
+                sum += system.get_magnetizations()[0][2];
+                samples += 1;
+            }
+        });
+        let x = material.saturation_magnetization * system.cell_volume() * field
+            / (BOLTZMANN_CONSTANT * thermal.temperature);
+        let langevin = 1.0 / x.tanh() - 1.0 / x;
+        assert!((sum / samples as f64 - langevin).abs() < 0.05);
+        let norm = system.get_magnetizations()[0].dot(&system.get_magnetizations()[0]);
+        assert!((norm - 1.0).abs() < 1e-12);
+    }
+}
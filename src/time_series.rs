@@ -0,0 +1,301 @@
+use crate::domains::wall_position;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::readout::Magnetoresistance;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+///# Time Series Column
+/// Observable recorded in a column of the time series. The wall position
+/// is NaN while the chain has no domain wall along the easy axis, the
+/// resistance without a magnetoresistance readout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeSeriesColumn {
+    Mx,
+    My,
+    Mz,
+    ExchangeEnergy,
+    AnisotropyEnergy,
+    ZeemanEnergy,
+    DipolarEnergy,
+    DmiEnergy,
+    TotalEnergy,
+    WallPosition,
+    MaxTorque,
+    Resistance,
+}
+
+// Columns written unless the configuration selects others
+pub const DEFAULT_COLUMNS: [TimeSeriesColumn; 4] = [
+    TimeSeriesColumn::Mx,
+    TimeSeriesColumn::My,
+    TimeSeriesColumn::Mz,
+    TimeSeriesColumn::TotalEnergy,
+];
+
+impl TimeSeriesColumn {
+    ///# Column Header
+    /// Name and unit of the column in the style of the mumax3 table.
+    pub fn header(self) -> &'static str {
+        match self {
+            TimeSeriesColumn::Mx => "<mx> ()",
+            TimeSeriesColumn::My => "<my> ()",
+            TimeSeriesColumn::Mz => "<mz> ()",
+            TimeSeriesColumn::ExchangeEnergy => "E_exch (J)",
+            TimeSeriesColumn::AnisotropyEnergy => "E_anis (J)",
+            TimeSeriesColumn::ZeemanEnergy => "E_Zeeman (J)",
+            TimeSeriesColumn::DipolarEnergy => "E_demag (J)",
+            TimeSeriesColumn::DmiEnergy => "E_DMI (J)",
+            TimeSeriesColumn::TotalEnergy => "E_total (J)",
+            TimeSeriesColumn::WallPosition => "x_wall (m)",
+            TimeSeriesColumn::MaxTorque => "max_torque (A/m)",
+            TimeSeriesColumn::Resistance => "R (Ohm)",
+        }
+    }
+}
+
+impl FromStr for TimeSeriesColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::from(s))
+            .map_err(|_| format!("unknown time series column {}", s))
+    }
+}
+
+///# Evaluate Columns
+/// Values of the observables in the state of the system, in the given order.
+pub fn evaluate_columns(
+    columns: &[TimeSeriesColumn],
+    system: &MicromagneticSystem,
+    readout: Option<&Magnetoresistance>,
+) -> Vec<f64> {
+    let m = system.average_magnetization();
+    // The energies are only evaluated once a column needs them
+    let mut energies = None;
+    let mut energy = || *energies.get_or_insert_with(|| system.compute_energies());
+    columns
+        .iter()
+        .map(|column| match column {
+            TimeSeriesColumn::Mx => m[0],
+            TimeSeriesColumn::My => m[1],
+            TimeSeriesColumn::Mz => m[2],
+            TimeSeriesColumn::ExchangeEnergy => energy().exchange,
+            TimeSeriesColumn::AnisotropyEnergy => energy().anisotropy,
+            TimeSeriesColumn::ZeemanEnergy => energy().zeeman,
+            TimeSeriesColumn::DipolarEnergy => energy().dipolar,
+            TimeSeriesColumn::DmiEnergy => energy().dmi,
+            TimeSeriesColumn::TotalEnergy => energy().total(),
+            TimeSeriesColumn::WallPosition => {
+                // Laboratory position, which differs in a moving frame
+                wall_position(
+                    &system.get_magnetizations(),
+                    &system.easy_axis(),
+                    system.get_cell_size(),
+                )
+                .map_or(f64::NAN, |x| x + system.frame_offset())
+            }
+            TimeSeriesColumn::MaxTorque => system.compute_max_torque(),
+            TimeSeriesColumn::Resistance => {
+                readout.map_or(f64::NAN, |readout| readout.resistance(system))
+            }
+        })
+        .collect()
+}
+
+///# Time Series Writer
+/// Writes the selected observables during dynamics as tab separated
+/// columns after the time, by default t, <mx>, <my>, <mz>, E_total. Rows
+/// are only written once per sampling interval of simulated time,
+/// independent of the integration time step.
+pub struct TimeSeriesWriter<W: Write> {
+    writer: W,
+    columns: Vec<TimeSeriesColumn>,
+    // Simulated time between two rows in s
+    sampling_interval: f64,
+    // Time of the next row to write
+    next_sample: f64,
+    readout: Option<Magnetoresistance>,
+}
+
+impl TimeSeriesWriter<BufWriter<File>> {
+    ///# Create Time Series File
+    pub fn create(
+        path: &Path,
+        sampling_interval: f64,
+        columns: &[TimeSeriesColumn],
+    ) -> io::Result<Self> {
+        Self::with_columns(
+            BufWriter::new(File::create(path)?),
+            sampling_interval,
+            columns,
+        )
+    }
+}
+
+impl<W: Write> TimeSeriesWriter<W> {
+    ///# New Time Series Writer
+    /// Writes the header line to the given writer. A sampling interval of
+    /// zero writes every recorded state.
+    pub fn new(writer: W, sampling_interval: f64) -> io::Result<Self> {
+        Self::with_columns(writer, sampling_interval, &DEFAULT_COLUMNS)
+    }
+
+    ///# Time Series Writer with Columns
+    /// Writes the given columns after the time, in the given order.
+    pub fn with_columns(
+        mut writer: W,
+        sampling_interval: f64,
+        columns: &[TimeSeriesColumn],
+    ) -> io::Result<Self> {
+        if sampling_interval.is_nan() || sampling_interval < 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The sampling interval must not be negative",
+            ));
+        }
+        if columns.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "At least one time series column is needed",
+            ));
+        }
+        write!(writer, "# t (s)")?;
+        for column in columns {
+            write!(writer, "\t{}", column.header())?;
+        }
+        writeln!(writer)?;
+        Ok(Self {
+            writer,
+            columns: columns.to_vec(),
+            sampling_interval,
+            next_sample: 0.0,
+            readout: None,
+        })
+    }
+
+    ///# With Readout
+    /// Evaluate the resistance column with the given magnetoresistance readout.
+    pub fn with_readout(self, readout: Option<Magnetoresistance>) -> Self {
+        Self { readout, ..self }
+    }
+
+    ///# Record
+    /// Write a row when the sampling time has been reached and report
+    /// whether it was written.
+    pub fn record(&mut self, t: f64, system: &MicromagneticSystem) -> io::Result<bool> {
+        // Allow for the rounding of accumulated time steps
+        if t < self.next_sample - 1e-9 * self.sampling_interval {
+            return Ok(false);
+        }
+        write!(self.writer, "{:e}", t)?;
+        for value in evaluate_columns(&self.columns, system, self.readout.as_ref()) {
+            write!(self.writer, "\t{:e}", value)?;
+        }
+        writeln!(self.writer)?;
+        if self.sampling_interval > 0.0 {
+            let samples = (t / self.sampling_interval + 1e-9).floor() + 1.0;
+            self.next_sample = samples * self.sampling_interval;
+        }
+        Ok(true)
+    }
+
+    ///# Flush
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    ///# Into Inner
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EASY_AXIS;
+
+    #[test]
+    /// Test that rows are written once per sampling interval
+    fn test_sampling_interval() {
+        let system = MicromagneticSystem::new(4);
+        let mut series = TimeSeriesWriter::new(Vec::new(), 1e-12).unwrap();
+        let mut written = 0;
+        for step in 0..=100 {
+            if series.record(step as f64 * 1e-14, &system).unwrap() {
+                written += 1;
+            }
+        }
+        assert_eq!(written, 2);
+
+        let text = String::from_utf8(series.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "# t (s)\t<mx> ()\t<my> ()\t<mz> ()\tE_total (J)");
+        assert_eq!(lines[1].split('\t').count(), 5);
+        assert!(lines[2].starts_with("1e-12\t"));
+        assert!(TimeSeriesWriter::new(Vec::new(), -1.0).is_err());
+    }
+
+    #[test]
+    /// Test that only the selected columns are written
+    fn test_column_selection() {
+        let system = MicromagneticSystem::new(4);
+        let columns = [
+            TimeSeriesColumn::ZeemanEnergy,
+            TimeSeriesColumn::MaxTorque,
+            TimeSeriesColumn::WallPosition,
+        ];
+        let mut series = TimeSeriesWriter::with_columns(Vec::new(), 0.0, &columns).unwrap();
+        series.record(0.0, &system).unwrap();
+
+        let text = String::from_utf8(series.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "# t (s)\tE_Zeeman (J)\tmax_torque (A/m)\tx_wall (m)"
+        );
+        let values: Vec<f64> = lines[1].split('\t').map(|v| v.parse().unwrap()).collect();
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[1], system.compute_energies().zeeman);
+        assert_eq!(values[2], system.compute_max_torque());
+        let wall = wall_position(
+            &system.get_magnetizations(),
+            &EASY_AXIS,
+            system.get_cell_size(),
+        );
+        assert_eq!(values[3].is_nan(), wall.is_none());
+        assert!(TimeSeriesWriter::with_columns(Vec::new(), 0.0, &[]).is_err());
+
+        // The resistance column needs a readout
+        let columns = [TimeSeriesColumn::Resistance];
+        assert!(evaluate_columns(&columns, &system, None)[0].is_nan());
+        let readout = Magnetoresistance {
+            model: crate::readout::MagnetoresistanceModel::Gmr,
+            start: 0,
+            end: 4,
+            reference: EASY_AXIS,
+            parallel_resistance: 100.0,
+            ratio: 0.1,
+        };
+        let mut series = TimeSeriesWriter::with_columns(Vec::new(), 0.0, &columns)
+            .unwrap()
+            .with_readout(Some(readout));
+        series.record(0.0, &system).unwrap();
+        let text = String::from_utf8(series.into_inner()).unwrap();
+        let value: f64 = text
+            .lines()
+            .nth(1)
+            .unwrap()
+            .split('\t')
+            .nth(1)
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(value, readout.resistance(&system));
+    }
+}
@@ -0,0 +1,391 @@
+use crate::dynamics::DynamicsRun;
+use crate::laser::LaserPulse;
+use crate::magnetic_moments::MicromagneticSystem;
+use crate::material::Material;
+use crate::temperature::TemperatureScaling;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Highest T / Tc used for the materials, cells whose electrons are hotter keep
+// a small moment and with it their direction
+const MAX_REDUCED_TEMPERATURE: f64 = 0.999;
+
+///# Two-Temperature Model
+/// Electrons with the heat capacity C_e = gamma T_e absorb the power of
+/// the heat source and pass it to the lattice,
+/// C_e dT_e/dt = -G (T_e - T_l) + P,
+/// C_l dT_l/dt = G (T_e - T_l) - C_l (T_l - T_0) / tau,
+/// where the last term cools the lattice to the ambient temperature T_0
+/// through the substrate. Every cell is heated on its own, the model
+/// neglects heat diffusion along the chain.
+///
+/// ```toml
+/// electron_heat_capacity = 1.065e3
+/// lattice_heat_capacity = 2.2e6
+/// coupling = 3.6e17
+/// substrate_cooling_time = 1.0e-10
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TwoTemperatureModel {
+    // Coefficient gamma of the electron heat capacity C_e = gamma T_e in J/(m^3 K^2)
+    pub electron_heat_capacity: f64,
+    // Lattice heat capacity C_l in J/(m^3 K)
+    pub lattice_heat_capacity: f64,
+    // Electron-phonon coupling G in W/(m^3 K)
+    pub coupling: f64,
+    // Time constant of the heat flow into the substrate in s, none keeps the heat
+    #[serde(default)]
+    pub substrate_cooling_time: Option<f64>,
+}
+
+///# Thermal State
+/// Electron and lattice temperature of every cell in K.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThermalState {
+    pub electron: Vec<f64>,
+    pub lattice: Vec<f64>,
+}
+
+impl ThermalState {
+    ///# Thermal Equilibrium
+    /// Electrons and lattice of all cells at the same temperature.
+    pub fn uniform(size: usize, temperature: f64) -> Self {
+        Self {
+            electron: vec![temperature; size],
+            lattice: vec![temperature; size],
+        }
+    }
+}
+
+///# Heat Source
+/// Absorbed power density in W/m^3 as a function of the x position and
+/// time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeatSource {
+    None,
+    // The same power density in every cell from start for duration in s
+    Uniform {
+        power_density: f64,
+        start: f64,
+        duration: f64,
+    },
+    // Gaussian pulse in space and time
+    Laser(LaserPulse),
+}
+
+impl HeatSource {
+    ///# Power Density
+    pub fn power_density(&self, position: f64, time: f64) -> f64 {
+        match self {
+            HeatSource::None => 0.0,
+            HeatSource::Uniform {
+                power_density,
+                start,
+                duration,
+            } => {
+                if time >= *start && time < start + duration {
+                    *power_density
+                } else {
+                    0.0
+                }
+            }
+            HeatSource::Laser(pulse) => pulse.power_density(position, time),
+        }
+    }
+}
+
+impl TwoTemperatureModel {
+    ///# Validate
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let positive = [
+            self.electron_heat_capacity,
+            self.lattice_heat_capacity,
+            self.coupling,
+        ]
+        .iter()
+        .all(|&value| value > 0.0);
+        if !positive {
+            return Err("The heat capacities and the coupling must be positive".into());
+        }
+        if self
+            .substrate_cooling_time
+            .is_some_and(|tau| tau.is_nan() || tau <= 0.0)
+        {
+            return Err("The substrate cooling time must be positive".into());
+        }
+        Ok(())
+    }
+
+    // Rates dT_e/dt and dT_l/dt of one cell
+    fn rates(&self, electron: f64, lattice: f64, power_density: f64, ambient: f64) -> [f64; 2] {
+        let exchanged = self.coupling * (electron - lattice);
+        let cooling = self
+            .substrate_cooling_time
+            .map_or(0.0, |tau| (lattice - ambient) / tau);
+        [
+            (power_density - exchanged) / (self.electron_heat_capacity * electron),
+            exchanged / self.lattice_heat_capacity - cooling,
+        ]
+    }
+
+    ///# Step
+    /// Advance the temperatures from `time` by `time_step` with a Heun
+    /// step, the lattice cooling towards `ambient` in K. The source heats
+    /// every cell by its x position in m.
+    pub fn step(
+        &self,
+        state: &mut ThermalState,
+        source: &HeatSource,
+        positions: &[f64],
+        ambient: f64,
+        time: f64,
+        time_step: f64,
+    ) {
+        for cell in 0..state.electron.len() {
+            let (electron, lattice) = (state.electron[cell], state.lattice[cell]);
+            let position = positions[cell];
+            let first = self.rates(
+                electron,
+                lattice,
+                source.power_density(position, time),
+                ambient,
+            );
+            let predicted = [
+                electron + time_step * first[0],
+                lattice + time_step * first[1],
+            ];
+            let second = self.rates(
+                predicted[0],
+                predicted[1],
+                source.power_density(position, time + time_step),
+                ambient,
+            );
+            state.electron[cell] = electron + 0.5 * time_step * (first[0] + second[0]);
+            state.lattice[cell] = lattice + 0.5 * time_step * (first[1] + second[1]);
+        }
+    }
+
+    ///# Heat per Volume
+    /// Energy per volume in J/m^3 stored in electrons and lattice of a
+    /// cell above zero temperature.
+    pub fn heat(&self, electron: f64, lattice: f64) -> f64 {
+        0.5 * self.electron_heat_capacity * electron * electron
+            + self.lattice_heat_capacity * lattice
+    }
+}
+
+///# Ultrafast Run
+/// Magnetization dynamics driven by the two-temperature model. Before
+/// every step the materials are set to their values at the electron
+/// temperature, so the magnitude of the moment follows the equilibrium
+/// m(T_e) of the temperature scaling while its direction follows the
+/// dynamics. The materials of the system at the start are taken as the
+/// constants at zero temperature, at the end the system keeps the
+/// materials at the final electron temperature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UltrafastRun {
+    pub dynamics: DynamicsRun,
+    pub model: TwoTemperatureModel,
+    pub scaling: TemperatureScaling,
+    pub source: HeatSource,
+    // Initial temperature of electrons and lattice and the substrate temperature in K
+    pub ambient_temperature: f64,
+}
+
+impl UltrafastRun {
+    ///# Run
+    /// Integrate for the given duration in s. The observer sees the time,
+    /// the state and the temperatures at the start and after every step.
+    /// Returns the final temperatures.
+    pub fn run<F: FnMut(f64, &MicromagneticSystem, &ThermalState)>(
+        &self,
+        system: &mut MicromagneticSystem,
+        duration: f64,
+        mut observer: F,
+    ) -> Result<ThermalState, Box<dyn Error>> {
+        self.model.validate()?;
+        if self.ambient_temperature.is_nan() || self.ambient_temperature <= 0.0 {
+            return Err("The ambient temperature must be positive".into());
+        }
+        if let HeatSource::Laser(pulse) = &self.source {
+            pulse.validate()?;
+        }
+        let time_step = self.dynamics.time_step;
+        let base = system.get_materials();
+        let mut state = ThermalState::uniform(system.size(), self.ambient_temperature);
+        let grid = system.get_grid();
+        let positions: Vec<f64> = (0..system.size())
+            .map(|cell| grid.position(cell, system.get_cell_size())[0])
+            .collect();
+        self.set_materials(system, &base, &state)?;
+        observer(0.0, system, &state);
+        let steps = (duration / time_step).round() as usize;
+        let mut time = 0.0;
+        for step in 1..=steps {
+            self.model.step(
+                &mut state,
+                &self.source,
+                &positions,
+                self.ambient_temperature,
+                time,
+                time_step,
+            );
+            self.set_materials(system, &base, &state)?;
+            self.dynamics.step(system, time);
+            if let Some(frame) = &self.dynamics.moving_frame {
+                frame.follow(system);
+            }
+            time = step as f64 * time_step;
+            observer(time, system, &state);
+        }
+        Ok(state)
+    }
+
+    // Materials at the electron temperature of each cell
+    fn set_materials(
+        &self,
+        system: &mut MicromagneticSystem,
+        base: &[Material],
+        state: &ThermalState,
+    ) -> Result<(), Box<dyn Error>> {
+        let hottest = MAX_REDUCED_TEMPERATURE * self.scaling.curie_temperature;
+        for (cell, material) in base.iter().enumerate() {
+            if material.is_vacuum() {
+                continue;
+            }
+            let temperature = state.electron[cell].min(hottest);
+            system.set_material(cell, self.scaling.material_at(material, temperature)?);
+        }
+        Ok(())
+    }
+}
+
+///# Reduced Moment
+/// Mean of Ms(T) m / Ms(0) over the magnetic cells, the quantity measured
+/// by the magneto-optical Kerr effect in demagnetization experiments.
+pub fn reduced_moment(system: &MicromagneticSystem, base: &[Material]) -> [f64; 3] {
+    let materials = system.get_materials();
+    let mut total = [0.0; 3];
+    let mut cells = 0;
+    for (cell, m) in system.get_magnetizations().iter().enumerate() {
+        if base[cell].is_vacuum() {
+            continue;
+        }
+        let ratio = materials[cell].saturation_magnetization / base[cell].saturation_magnetization;
+        for k in 0..3 {
+            total[k] += ratio * m[k];
+        }
+        cells += 1;
+    }
+    total.map(|c| c / cells.max(1) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamics::TimeDependentField;
+    use ndarray::array;
+
+    fn nickel() -> TwoTemperatureModel {
+        TwoTemperatureModel {
+            electron_heat_capacity: 1.065e3,
+            lattice_heat_capacity: 2.2e6,
+            coupling: 3.6e17,
+            substrate_cooling_time: None,
+        }
+    }
+
+    #[test]
+    /// Test that the model conserves the deposited energy and equilibrates
+    fn test_two_temperature_model() {
+        let model = nickel();
+        let source = HeatSource::Uniform {
+            power_density: 1e22,
+            start: 0.0,
+            duration: 1e-13,
+        };
+        let mut state = ThermalState::uniform(1, 300.0);
+        let initial = model.heat(300.0, 300.0);
+        let time_step = 1e-15;
+        let mut peak: f64 = 0.0;
+        let mut deposited = 0.0;
+        for step in 0..30000 {
+            let time = step as f64 * time_step;
+            model.step(&mut state, &source, &[0.0], 300.0, time, time_step);
+            peak = peak.max(state.electron[0]);
+            // The trapezoidal rule of the Heun step over the edges of the pulse
+            deposited += 0.5
+                * time_step
+                * (source.power_density(0.0, time) + source.power_density(0.0, time + time_step));
+        }
+        let heat = model.heat(state.electron[0], state.lattice[0]);
+        assert!((heat - initial - deposited).abs() < 1e-3 * deposited);
+        // The electrons heat far above the lattice, then both meet
+        assert!(peak > 1000.0);
+        assert!((state.electron[0] - state.lattice[0]).abs() < 1.0);
+
+        // The substrate takes the heat away again
+        let cooled = TwoTemperatureModel {
+            substrate_cooling_time: Some(1e-12),
+            ..model
+        };
+        for step in 0..20000 {
+            cooled.step(
+                &mut state,
+                &HeatSource::None,
+                &[0.0],
+                300.0,
+                step as f64 * 1e-15,
+                1e-15,
+            );
+        }
+        assert!((state.lattice[0] - 300.0).abs() < 1.0);
+        assert!(TwoTemperatureModel {
+            coupling: 0.0,
+            ..model
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    /// Test the demagnetization and recovery of a laser heated chain
+    fn test_ultrafast_demagnetization() {
+        let size = 4;
+        let mut system =
+            MicromagneticSystem::from_magnetizations(vec![array![1.0, 0.0, 0.0]; size]);
+        system.set_applied_field([0.0; 3]);
+        let base = system.get_materials();
+        let run = UltrafastRun {
+            dynamics: DynamicsRun::new(TimeDependentField::Constant([0.0; 3])),
+            model: TwoTemperatureModel {
+                substrate_cooling_time: Some(2e-12),
+                ..nickel()
+            },
+            scaling: TemperatureScaling::new(630.0),
+            source: HeatSource::Uniform {
+                power_density: 2e21,
+                start: 0.0,
+                duration: 5e-14,
+            },
+            ambient_temperature: 300.0,
+        };
+        let mut moments = Vec::new();
+        let state = run
+            .run(&mut system, 1e-11, |_, system, _| {
+                moments.push(reduced_moment(system, &base)[0]);
+            })
+            .unwrap();
+
+        let initial = run.scaling.reduced_magnetization(300.0).unwrap();
+        assert!((moments[0] - initial).abs() < 1e-12);
+        let minimum = moments.iter().cloned().fold(f64::INFINITY, f64::min);
+        let lowest = moments.iter().position(|&m| m == minimum).unwrap();
+        // The moment drops within a picosecond, then recovers with the cooling
+        assert!(minimum < 0.5 * initial);
+        assert!(lowest < 100);
+        assert!(moments[moments.len() - 1] > 0.9 * initial);
+        assert!(state.electron[0] - 300.0 < 10.0);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::magnetic_moments::{Energies, MicromagneticSystem};
+use crate::ovf::read_ovf;
+use ndarray::Array1;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+///# Comparison Report
+/// Pointwise and energy differences between a reference state,
+/// e.g. from OOMMF or a muMAG standard problem, and a result of this crate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonReport {
+    // Number of cells that are magnetic in both states
+    pub compared_cells: usize,
+    // Cells that are vacuum in exactly one of the two states
+    pub vacuum_mismatches: usize,
+    // Largest and root mean square length of m - m_reference
+    pub max_difference: f64,
+    pub rms_difference: f64,
+    // Largest and mean angle between m and m_reference in degrees
+    pub max_angle: f64,
+    pub mean_angle: f64,
+    // Energies of the reference state and of the result, evaluated with this crate
+    pub reference_energies: Energies,
+    pub energies: Energies,
+}
+
+impl ComparisonReport {
+    ///# Relative Energy Difference
+    /// (E - E_reference) / |E_reference| of the total energy
+    pub fn relative_energy_difference(&self) -> f64 {
+        let reference = self.reference_energies.total();
+        (self.energies.total() - reference) / reference.abs().max(f64::MIN_POSITIVE)
+    }
+}
+
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Compared cells:       {}", self.compared_cells)?;
+        writeln!(f, "Vacuum mismatches:    {}", self.vacuum_mismatches)?;
+        writeln!(f, "Max |dm|:             {:e}", self.max_difference)?;
+        writeln!(f, "RMS |dm|:             {:e}", self.rms_difference)?;
+        writeln!(f, "Max angle (deg):      {:.4}", self.max_angle)?;
+        writeln!(f, "Mean angle (deg):     {:.4}", self.mean_angle)?;
+        writeln!(
+            f,
+            "{:<12} {:>16} {:>16} {:>16}",
+            "Energy (J)", "reference", "result", "difference"
+        )?;
+        let rows = [
+            (
+                "exchange",
+                self.reference_energies.exchange,
+                self.energies.exchange,
+            ),
+            (
+                "anisotropy",
+                self.reference_energies.anisotropy,
+                self.energies.anisotropy,
+            ),
+            (
+                "zeeman",
+                self.reference_energies.zeeman,
+                self.energies.zeeman,
+            ),
+            (
+                "total",
+                self.reference_energies.total(),
+                self.energies.total(),
+            ),
+        ];
+        for (name, reference, result) in rows {
+            writeln!(
+                f,
+                "{:<12} {:>16.6e} {:>16.6e} {:>16.6e}",
+                name,
+                reference,
+                result,
+                result - reference
+            )?;
+        }
+        write!(
+            f,
+            "Relative total energy difference: {:e}",
+            self.relative_energy_difference()
+        )
+    }
+}
+
+///# Compare States
+/// Both systems must describe the same problem (same cells and materials),
+/// the reference energies are therefore evaluated with the materials of `result`.
+pub fn compare_states(
+    reference: &[Array1<f64>],
+    result: &MicromagneticSystem,
+) -> Result<ComparisonReport, Box<dyn Error>> {
+    if reference.len() != result.size() {
+        return Err(format!(
+            "Reference has {} cells, the result {}",
+            reference.len(),
+            result.size()
+        )
+        .into());
+    }
+
+    // The reference state with the materials of the result
+    let mut reference_system = result.clone();
+    for (i, m) in reference.iter().enumerate() {
+        reference_system.set_magnetization(i, m.clone());
+    }
+
+    let magnetizations = result.get_magnetizations();
+    let reference_magnetizations = reference_system.get_magnetizations();
+    let mut compared_cells = 0;
+    let mut vacuum_mismatches = 0;
+    let mut max_difference: f64 = 0.0;
+    let mut squared_differences = 0.0;
+    let mut max_angle: f64 = 0.0;
+    let mut angles = 0.0;
+    for i in 0..result.size() {
+        let reference_is_vacuum = reference[i].dot(&reference[i]) == 0.0;
+        if reference_is_vacuum != result.is_vacuum(i) {
+            vacuum_mismatches += 1;
+            continue;
+        }
+        if reference_is_vacuum {
+            continue;
+        }
+        let difference = &magnetizations[i] - &reference_magnetizations[i];
+        let length = difference.dot(&difference).sqrt();
+        let angle = magnetizations[i]
+            .dot(&reference_magnetizations[i])
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees();
+        compared_cells += 1;
+        max_difference = max_difference.max(length);
+        squared_differences += length * length;
+        max_angle = max_angle.max(angle);
+        angles += angle;
+    }
+    let count = compared_cells.max(1) as f64;
+
+    Ok(ComparisonReport {
+        compared_cells,
+        vacuum_mismatches,
+        max_difference,
+        rms_difference: (squared_differences / count).sqrt(),
+        max_angle,
+        mean_angle: angles / count,
+        reference_energies: reference_system.compute_energies(),
+        energies: result.compute_energies(),
+    })
+}
+
+///# Compare with OVF Reference
+/// Load an OOMMF/muMAG reference state and compare it with the result.
+pub fn compare_with_ovf(
+    path: &Path,
+    result: &MicromagneticSystem,
+) -> Result<ComparisonReport, Box<dyn Error>> {
+    let data = read_ovf(path)?;
+    let reference: Vec<Array1<f64>> = data
+        .vectors
+        .iter()
+        .map(|v| Array1::from_vec(v.to_vec()))
+        .collect();
+    compare_states(&reference, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    /// Test that a state compared with itself has no differences
+    fn test_identical_states() {
+        let system = MicromagneticSystem::new(8);
+        let report = compare_states(&system.get_magnetizations(), &system).unwrap();
+        assert_eq!(report.compared_cells, 8);
+        assert!(report.max_difference < 1e-12);
+        assert!(report.max_angle < 1e-4);
+        assert!(report.relative_energy_difference().abs() < 1e-9);
+    }
+
+    #[test]
+    /// Test the pointwise differences of a single rotated cell
+    fn test_rotated_cell() {
+        let mut system = MicromagneticSystem::new(4);
+        for i in 0..4 {
+            system.set_magnetization(i, array![1.0, 0.0, 0.0]);
+        }
+        let mut reference = system.get_magnetizations();
+        reference[1] = array![0.0, 1.0, 0.0];
+        let report = compare_states(&reference, &system).unwrap();
+        assert!((report.max_angle - 90.0).abs() < 1e-9);
+        assert!((report.mean_angle - 22.5).abs() < 1e-9);
+        assert!((report.max_difference - 2.0f64.sqrt()).abs() < 1e-12);
+        assert!(report.relative_energy_difference() < 0.0);
+        assert!(compare_states(&reference[..3], &system).is_err());
+        let text = report.to_string();
+        assert!(text.contains("Compared cells:       4\n"));
+        assert!(text.contains("Max angle (deg):      90.0000\n"));
+    }
+}
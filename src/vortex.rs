@@ -0,0 +1,321 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+///# Vortex Core
+/// Core of a vortex (winding +1) or antivortex (winding -1) in one xy layer
+/// of a grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VortexCore {
+    // Laboratory position (x, y) of the core in m, x includes the offset
+    // of a moving frame
+    pub position: [f64; 2],
+    // Layer z of the grid
+    pub layer: usize,
+    // Winding number of the in-plane magnetization around the core
+    pub winding: i32,
+    // Sign of mz in the core, +1 up and -1 down
+    pub polarity: f64,
+}
+
+///# Find Vortex Cores
+/// Every vortex and antivortex core in the xy layers of the grid of the
+/// system. A core lies in the plaquette of four cells around which the
+/// in-plane magnetization winds by a full turn, and its position is refined
+/// to a fraction of a cell by a parabola through the out-of-plane
+/// magnetization of the core cell and its neighbors along x and along y.
+/// Plaquettes with a vacuum cell are skipped, and the cores of the same
+/// winding that refine to within one cell are the same core.
+pub fn find_cores(system: &MicromagneticSystem) -> Vec<VortexCore> {
+    let grid = system.get_grid();
+    let magnetizations = system.get_magnetizations();
+    let cell_size = system.get_cell_size();
+    let mut cores: Vec<VortexCore> = Vec::new();
+    for z in 0..grid.nz {
+        for y in 0..grid.ny.saturating_sub(1) {
+            for x in 0..grid.nx.saturating_sub(1) {
+                // Counter-clockwise around the plaquette
+                let corners = [
+                    grid.index(x, y, z),
+                    grid.index(x + 1, y, z),
+                    grid.index(x + 1, y + 1, z),
+                    grid.index(x, y + 1, z),
+                ];
+                if corners.iter().any(|&cell| system.is_vacuum(cell)) {
+                    continue;
+                }
+                let angles = corners.map(|cell| {
+                    let m = &magnetizations[cell];
+                    m[1].atan2(m[0])
+                });
+                let turn: f64 = (0..4)
+                    .map(|k| wrap_angle(angles[(k + 1) % 4] - angles[k]))
+                    .sum();
+                let winding = (turn / (2.0 * PI)).round() as i32;
+                if winding == 0 {
+                    continue;
+                }
+                let mz: f64 = corners.iter().map(|&cell| magnetizations[cell][2]).sum();
+                let polarity = if mz < 0.0 { -1.0 } else { 1.0 };
+                let [cx, cy] = refine(system, &magnetizations, corners, polarity);
+                let core = VortexCore {
+                    position: [system.frame_offset() + cx * cell_size, cy * cell_size],
+                    layer: z,
+                    winding,
+                    polarity,
+                };
+                let duplicate = cores.iter().any(|other| {
+                    other.layer == z
+                        && other.winding == winding
+                        && distance(other.position, core.position) < cell_size
+                });
+                if !duplicate {
+                    cores.push(core);
+                }
+            }
+        }
+    }
+    cores
+}
+
+// Angle difference folded into -pi..pi
+fn wrap_angle(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+// Sub-cell core position in units of cells: the corner cell with the
+// largest polarity * mz, shifted to the vertex of the parabola through it
+// and its two neighbors along each axis, within half a cell
+fn refine(
+    system: &MicromagneticSystem,
+    magnetizations: &[ndarray::Array1<f64>],
+    corners: [usize; 4],
+    polarity: f64,
+) -> [f64; 2] {
+    let grid = system.get_grid();
+    let height = |cell: usize| polarity * magnetizations[cell][2];
+    let center = corners
+        .into_iter()
+        .max_by(|&a, &b| height(a).total_cmp(&height(b)))
+        .expect("a plaquette has four corners");
+    let [x, y, z] = grid.coordinates(center);
+    let vertex = |coordinate: usize, length: usize, at: &dyn Fn(usize) -> usize| {
+        if coordinate == 0 || coordinate + 1 >= length {
+            return coordinate as f64;
+        }
+        let (before, after) = (at(coordinate - 1), at(coordinate + 1));
+        if system.is_vacuum(before) || system.is_vacuum(after) {
+            return coordinate as f64;
+        }
+        let (low, middle, high) = (height(before), height(center), height(after));
+        let curvature = low - 2.0 * middle + high;
+        if curvature >= 0.0 {
+            return coordinate as f64;
+        }
+        coordinate as f64 + (0.5 * (low - high) / curvature).clamp(-0.5, 0.5)
+    };
+    [
+        vertex(x, grid.nx, &|i| grid.index(i, y, z)),
+        vertex(y, grid.ny, &|j| grid.index(x, j, z)),
+    ]
+}
+
+///# Core Trajectory
+/// Positions of one core over the snapshots it was found in
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreTrajectory {
+    pub layer: usize,
+    pub winding: i32,
+    pub polarity: f64,
+    // Time in s and position (x, y) in m
+    pub points: Vec<(f64, [f64; 2])>,
+}
+
+///# Core Tracker
+/// Finds the cores once per sampling interval and links every core to the
+/// trajectory of the same layer, winding and polarity whose last position
+/// is nearest, within `max_jump` in m. Cores without such a trajectory, as
+/// after a core reversal, start a new one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreTracker {
+    // Simulated time between two snapshots in s
+    sampling_interval: f64,
+    // Time of the next snapshot to track
+    next_sample: f64,
+    // Largest core displacement between two snapshots in m
+    pub max_jump: f64,
+    trajectories: Vec<CoreTrajectory>,
+}
+
+impl CoreTracker {
+    ///# New Core Tracker
+    /// A sampling interval of zero tracks every recorded state. The cores
+    /// may move by up to five cells of the given size between two snapshots.
+    pub fn new(sampling_interval: f64, cell_size: f64) -> Self {
+        Self {
+            sampling_interval,
+            next_sample: 0.0,
+            max_jump: 5.0 * cell_size,
+            trajectories: Vec::new(),
+        }
+    }
+
+    ///# Record
+    /// Track the cores when the sampling time has been reached and report
+    /// whether they were tracked.
+    pub fn record(&mut self, t: f64, system: &MicromagneticSystem) -> bool {
+        // Allow for the rounding of accumulated time steps
+        if t < self.next_sample - 1e-9 * self.sampling_interval {
+            return false;
+        }
+        if self.sampling_interval > 0.0 {
+            let samples = (t / self.sampling_interval + 1e-9).floor() + 1.0;
+            self.next_sample = samples * self.sampling_interval;
+        }
+        let mut extended = vec![false; self.trajectories.len()];
+        for core in find_cores(system) {
+            let nearest = self
+                .trajectories
+                .iter()
+                .enumerate()
+                .filter(|(k, trajectory)| {
+                    !extended[*k]
+                        && trajectory.layer == core.layer
+                        && trajectory.winding == core.winding
+                        && trajectory.polarity == core.polarity
+                })
+                .map(|(k, trajectory)| {
+                    let last = trajectory.points[trajectory.points.len() - 1].1;
+                    (k, distance(last, core.position))
+                })
+                .filter(|&(_, jump)| jump <= self.max_jump)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            match nearest {
+                Some((k, _)) => {
+                    self.trajectories[k].points.push((t, core.position));
+                    extended[k] = true;
+                }
+                None => {
+                    self.trajectories.push(CoreTrajectory {
+                        layer: core.layer,
+                        winding: core.winding,
+                        polarity: core.polarity,
+                        points: vec![(t, core.position)],
+                    });
+                    extended.push(true);
+                }
+            }
+        }
+        true
+    }
+
+    ///# Trajectories
+    pub fn trajectories(&self) -> &[CoreTrajectory] {
+        &self.trajectories
+    }
+}
+
+///# Write Core Trajectories
+/// Tab-separated trajectory number, time and position of every tracked
+/// core, one trajectory after the other.
+pub fn write_core_trajectories(path: &Path, trajectories: &[CoreTrajectory]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "# core\tlayer\twinding\tpolarity\tt (s)\tx (m)\ty (m)"
+    )?;
+    for (k, trajectory) in trajectories.iter().enumerate() {
+        for (t, [x, y]) in &trajectory.points {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{:e}\t{:e}\t{:e}",
+                k, trajectory.layer, trajectory.winding, trajectory.polarity, t, x, y
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::parameters::SimulationParameters;
+    use crate::SPATIAL_DISCRETION_STEP;
+    use ndarray::array;
+
+    // Vortex (winding +1) or antivortex (-1) centered at (x0, y0) in cells
+    // with a core of radius 2 cells and the given polarity
+    fn texture(system: &mut MicromagneticSystem, center: [f64; 2], winding: f64, polarity: f64) {
+        let grid = system.get_grid();
+        for cell in 0..system.size() {
+            let [x, y, _] = grid.coordinates(cell);
+            let (dx, dy) = (x as f64 - center[0], y as f64 - center[1]);
+            let angle = winding * dy.atan2(dx) + PI / 2.0;
+            let mz = polarity * (-(dx * dx + dy * dy) / 4.0).exp();
+            let inplane = (1.0 - mz * mz).sqrt();
+            system.set_magnetization(
+                cell,
+                array![inplane * angle.cos(), inplane * angle.sin(), mz],
+            );
+        }
+    }
+
+    #[test]
+    /// Test the sub-cell position of vortex and antivortex cores and their
+    /// trajectories
+    fn test_core_tracking() {
+        let grid = Grid::new(20, 20, 1);
+        let mut system = MicromagneticSystem::on_grid(grid, SimulationParameters::default());
+        let cell = SPATIAL_DISCRETION_STEP;
+        texture(&mut system, [7.3, 11.6], 1.0, -1.0);
+        let cores = find_cores(&system);
+        assert_eq!(cores.len(), 1);
+        assert_eq!((cores[0].winding, cores[0].polarity), (1, -1.0));
+        assert!((cores[0].position[0] - 7.3 * cell).abs() < 0.1 * cell);
+        assert!((cores[0].position[1] - 11.6 * cell).abs() < 0.1 * cell);
+
+        texture(&mut system, [10.5, 9.2], -1.0, 1.0);
+        let cores = find_cores(&system);
+        assert_eq!(cores.len(), 1);
+        assert_eq!(cores[0].winding, -1);
+        assert!((cores[0].position[0] - 10.5 * cell).abs() < 0.1 * cell);
+
+        // A gyrating core gives one trajectory per sample
+        let mut tracker = CoreTracker::new(1e-10, cell);
+        for k in 0..8 {
+            let phase = k as f64 * PI / 4.0;
+            texture(
+                &mut system,
+                [9.5 + 2.0 * phase.cos(), 9.5 + 2.0 * phase.sin()],
+                1.0,
+                1.0,
+            );
+            assert!(tracker.record(k as f64 * 1e-10, &system));
+            assert!(!tracker.record(k as f64 * 1e-10 + 1e-11, &system));
+        }
+        let trajectories = tracker.trajectories();
+        assert_eq!(trajectories.len(), 1);
+        assert_eq!(trajectories[0].points.len(), 8);
+        let [x, y] = trajectories[0].points[2].1;
+        assert!((x - 9.5 * cell).abs() < 0.1 * cell && (y - 11.5 * cell).abs() < 0.1 * cell);
+
+        // A uniform state has no core
+        for cell in 0..system.size() {
+            system.set_magnetization(cell, array![1.0, 0.0, 0.0]);
+        }
+        assert!(find_cores(&system).is_empty());
+
+        let path = std::env::temp_dir().join("energy_relaxation_core_test.txt");
+        write_core_trajectories(&path, trajectories).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text.lines().count(), 9);
+    }
+}
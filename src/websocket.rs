@@ -0,0 +1,228 @@
+use crate::magnetic_moments::MicromagneticSystem;
+use serde::Serialize;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+// Longest a client may stall a send or its handshake before it is dropped
+const CLIENT_TIMEOUT: Duration = Duration::from_millis(250);
+
+///# Live Message
+/// JSON messages streamed to the connected clients, tagged by `type`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveMessage {
+    // Summary of one relaxation step
+    Iteration {
+        step: usize,
+        energy: f64,
+        max_torque: f64,
+        average_magnetization: [f64; 3],
+    },
+    // Magnetization of every `stride`-th cell
+    Frame {
+        step: usize,
+        stride: usize,
+        magnetizations: Vec<[f64; 3]>,
+    },
+    // Sent once when the run has ended
+    Finished {
+        step: usize,
+        converged: bool,
+    },
+}
+
+type Clients = Arc<Mutex<Vec<WebSocket<TcpStream>>>>;
+
+///# Live Server
+/// WebSocket endpoint that accepts browser connections on a background
+/// thread and broadcasts the messages of a running simulation to all of
+/// them. Every handshake runs on its own thread and every socket has a
+/// write timeout, so a slow or stalled client never holds up another
+/// client or the simulation. Clients that disconnect or stall are dropped
+/// at the next broadcast.
+pub struct LiveServer {
+    address: SocketAddr,
+    clients: Clients,
+}
+
+impl LiveServer {
+    ///# Bind Server
+    /// Listen on the given address, port 0 picks a free port.
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let clients = Clients::default();
+        let accepted = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let accepted = Arc::clone(&accepted);
+                thread::spawn(move || {
+                    let _ = stream.set_nodelay(true);
+                    let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+                    let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+                    // A failed or stalled handshake only loses that connection
+                    if let Ok(socket) = tungstenite::accept(stream) {
+                        accepted.lock().unwrap().push(socket);
+                    }
+                });
+            }
+        });
+        Ok(Self { address, clients })
+    }
+
+    ///# Local Address
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    ///# Client Count
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    ///# Broadcast
+    /// Send the message to every client and return how many received it.
+    /// The clients are taken out of the shared list for the sends, so
+    /// connections accepted meanwhile never wait on a slow client.
+    pub fn broadcast(&self, message: &LiveMessage) -> usize {
+        let text = serde_json::to_string(message).expect("live messages serialize to JSON");
+        let mut sending = std::mem::take(&mut *self.clients.lock().unwrap());
+        sending.retain_mut(|socket| socket.send(Message::text(text.clone())).is_ok());
+        let received = sending.len();
+        self.clients.lock().unwrap().append(&mut sending);
+        received
+    }
+}
+
+///# Live Stream
+/// Observer of the minimizer that broadcasts an iteration summary every
+/// `summary_interval` steps and a frame of at most `max_frame_cells`
+/// cells every `frame_interval` steps, so large chains stay cheap to draw.
+pub struct LiveStream {
+    pub server: LiveServer,
+    pub summary_interval: usize,
+    pub frame_interval: usize,
+    pub max_frame_cells: usize,
+}
+
+impl LiveStream {
+    pub fn new(server: LiveServer) -> Self {
+        Self {
+            server,
+            summary_interval: 100,
+            frame_interval: 500,
+            max_frame_cells: 200,
+        }
+    }
+
+    ///# Observe Step
+    pub fn observe(&self, step: usize, system: &MicromagneticSystem) {
+        // Nothing is evaluated while nobody is watching
+        if self.server.client_count() == 0 {
+            return;
+        }
+        if step.is_multiple_of(self.summary_interval.max(1)) {
+            self.server.broadcast(&LiveMessage::Iteration {
+                step,
+                energy: system.compute_energies().total(),
+                max_torque: system.compute_max_torque(),
+                average_magnetization: system.average_magnetization(),
+            });
+        }
+        if step.is_multiple_of(self.frame_interval.max(1)) {
+            self.server.broadcast(&self.frame(step, system));
+        }
+    }
+
+    ///# Down-sampled Frame
+    pub fn frame(&self, step: usize, system: &MicromagneticSystem) -> LiveMessage {
+        let stride = system.size().div_ceil(self.max_frame_cells.max(1)).max(1);
+        let magnetizations = system
+            .get_magnetizations()
+            .iter()
+            .step_by(stride)
+            .map(|m| [m[0], m[1], m[2]])
+            .collect();
+        LiveMessage::Frame {
+            step,
+            stride,
+            magnetizations,
+        }
+    }
+
+    ///# Finish
+    /// Send the final frame and the end of the run.
+    pub fn finish(&self, step: usize, converged: bool, system: &MicromagneticSystem) {
+        self.server.broadcast(&self.frame(step, system));
+        self.server
+            .broadcast(&LiveMessage::Finished { step, converged });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    /// Test streaming summaries and down-sampled frames to a client
+    fn test_live_stream() {
+        let server = LiveServer::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr();
+        // A connection that never sends its handshake does not block others
+        let _silent = TcpStream::connect(address).unwrap();
+        let stream = TcpStream::connect(address).unwrap();
+        let (mut client, _) = tungstenite::client(format!("ws://{}", address), stream).unwrap();
+        let start = Instant::now();
+        while server.client_count() == 0 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut live = LiveStream::new(server);
+        live.max_frame_cells = 4;
+        let system = MicromagneticSystem::new(10);
+        live.observe(0, &system);
+        live.observe(1, &system);
+        live.finish(1, true, &system);
+
+        let mut read = || -> serde_json::Value {
+            let text = client.read().unwrap().into_text().unwrap();
+            serde_json::from_str(text.as_str()).unwrap()
+        };
+        let summary = read();
+        assert_eq!(summary["type"], "iteration");
+        assert_eq!(summary["step"], 0);
+        // JSON keeps the energy to the last digit or so
+        let energy = system.compute_energies().total();
+        assert!((summary["energy"].as_f64().unwrap() - energy).abs() <= 1e-15 * energy.abs());
+        let frame = read();
+        assert_eq!(frame["type"], "frame");
+        assert_eq!(frame["stride"], 3);
+        assert_eq!(frame["magnetizations"].as_array().unwrap().len(), 4);
+        // Step 1 is neither a summary nor a frame step
+        assert_eq!(read()["type"], "frame");
+        assert_eq!(
+            read(),
+            serde_json::json!({"type": "finished", "step": 1, "converged": true})
+        );
+
+        // A client that stops reading times out and is dropped once the
+        // socket buffers are full
+        let large = LiveMessage::Frame {
+            step: 2,
+            stride: 1,
+            magnetizations: vec![[0.123456789; 3]; 200_000],
+        };
+        let start = Instant::now();
+        while live.server.broadcast(&large) == 1 {
+            assert!(start.elapsed() < Duration::from_secs(30));
+        }
+        assert_eq!(live.server.client_count(), 0);
+        drop(client);
+    }
+}